@@ -0,0 +1,101 @@
+// How fast each encoding of events (`--encoding`; see src/vst.rs) can be written, in events per
+// second, both on its own and as part of saving a trace to a .vst file, and how fast a trace can be
+// streamed to `vistrace connect` (see src/remote.rs), which parses the lines again. Run with
+// `cargo bench --bench encoding`. MessagePack should keep up with at least 500k events/sec, so that
+// saving a firehose trace does not hold it up; streaming is bound by parsing, and by the three
+// threads (sending, receiving, and the consumer) having enough cores.
+
+use std::hint::black_box;
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use vistrace::remote::{Address, Listener, Receiver};
+use vistrace::strace::{LineParser, Message};
+use vistrace::vst::{Encoding, Writer};
+
+// a mix of the syscalls that make up most busy traces
+const LINES: &[&str] = &[
+    "1234 1720000000.000001 openat(AT_FDCWD, \"/usr/lib/x86_64-linux-gnu/libc.so.6\", O_RDONLY|O_CLOEXEC) = 3 <0.000012>\n",
+    "1234 1720000000.000002 read(3, \"\\177ELF\\2\\1\\1\\3\\0\\0\\0\\0\\0\\0\\0\\0\\3\\0>\\0\\1\\0\\0\\0\"..., 832) = 832 <0.000004>\n",
+    "1234 1720000000.000003 fstat(3, {st_mode=S_IFREG|0755, st_size=2220400, ...}) = 0 <0.000003>\n",
+    "1234 1720000000.000004 mmap(NULL, 2264656, PROT_READ, MAP_PRIVATE|MAP_DENYWRITE, 3, 0) = 0x7f0e1a200000 <0.000005>\n",
+    "1234 1720000000.000005 close(3) = 0 <0.000002>\n",
+    "1235 1720000000.000006 futex(0x7f0e1a41a990, FUTEX_WAKE_PRIVATE, 1) = 0 <0.000002>\n",
+    "1235 1720000000.000007 write(1, \"hello, world\\n\", 13) = 13 <0.000006>\n",
+    "1235 1720000000.000008 stat(\"/etc/missing\", 0x7ffd2a3c8f40) = -1 ENOENT (No such file or directory) <0.000004>\n",
+];
+
+const EVENTS: usize = 1_000_000;
+
+fn main() {
+    // otherwise anyhow captures a backtrace for every error that the parser backtracks over
+    std::env::set_var("RUST_LIB_BACKTRACE", "0");
+
+    let mut parser = LineParser::default();
+    let events: Vec<(&str, Message)> = LINES
+        .iter()
+        .map(|line| (*line, parser.parse_line(line).unwrap()))
+        .collect();
+
+    for encoding in [Encoding::Json, Encoding::Msgpack] {
+        let mut buf = Vec::new();
+        let mut bytes = 0;
+        let elapsed = time(|| {
+            for (_, msg) in events.iter().cycle().take(EVENTS) {
+                buf.clear();
+                encoding.encode(black_box(msg), &mut buf);
+                bytes += buf.len();
+            }
+        });
+        report(&format!("encode {:?}", encoding), elapsed);
+        println!("    {} bytes per event", bytes / EVENTS);
+
+        let path = std::env::temp_dir().join(format!("vistrace-bench-{}.vst", std::process::id()));
+        let mut writer = Writer::create(&path, encoding).unwrap();
+        let elapsed = time(|| {
+            for (line, msg) in events.iter().cycle().take(EVENTS) {
+                writer.write(line, Some(black_box(msg))).unwrap();
+            }
+            writer.finish().unwrap();
+        });
+        report(&format!("save {:?}", encoding), elapsed);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    // from the first line sent until the client has parsed the last
+    let path = std::env::temp_dir().join(format!("vistrace-bench-{}.sock", std::process::id()));
+    let address = Address::Unix(path);
+    let listener = Listener::bind(&address).unwrap();
+    let (tx, rx) = mpsc::channel();
+    let client = thread::spawn(move || {
+        Receiver::connect(&address)
+            .and_then(|receiver| receiver.receive(LineParser::default(), tx))
+            .unwrap()
+    });
+    let counter = thread::spawn(move || rx.iter().count());
+    let mut sender = listener.accept().unwrap();
+    let elapsed = time(|| {
+        for (line, _) in events.iter().cycle().take(EVENTS) {
+            sender.write(black_box(line)).unwrap();
+        }
+        drop(sender);
+        client.join().unwrap();
+    });
+    assert_eq!(counter.join().unwrap(), EVENTS);
+    report("stream", elapsed);
+}
+
+fn time(f: impl FnOnce()) -> Duration {
+    let start = Instant::now();
+    f();
+    start.elapsed()
+}
+
+fn report(name: &str, elapsed: Duration) {
+    println!(
+        "{:<16} {:>10.0} events/sec",
+        name,
+        EVENTS as f64 / elapsed.as_secs_f64()
+    );
+}
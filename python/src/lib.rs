@@ -0,0 +1,220 @@
+// Python bindings for vistrace's parser and analyzers, for analysis pipelines written in Python:
+//
+//   import vistrace
+//
+//   for event in vistrace.parse_file("trace.txt"):
+//       if event["type"] == "syscall" and event["errno"]:
+//           print(event["name"], event["errno"])
+//
+//   analyzer = vistrace.Analyzer()
+//   for line in open("trace.txt"):
+//       event = analyzer.feed(line)
+//   analyzer.fds()            # every file descriptor that was used, and what it referred to
+//   analyzer.syscall_stats()  # calls, errors, and time of each syscall
+//
+// Events are dicts in the schema of `vistrace export --format jsonl` (see src/jsonl.rs in the
+// vistrace crate).
+
+use std::fs::File;
+use std::io::BufReader;
+
+use pyo3::exceptions::PyIOError;
+use pyo3::prelude::*;
+use pyo3::types::{PyBool, PyDict, PyList};
+
+use vistrace::fdtable::{FdInfo, FdKind, FdTable};
+use vistrace::json::Json;
+use vistrace::strace::{self, LineParser, Message};
+use vistrace::{jsonl, summary};
+
+fn to_python(py: Python<'_>, json: &Json) -> PyResult<PyObject> {
+    Ok(match json {
+        Json::Null => py.None(),
+        Json::Bool(b) => PyBool::new(py, *b).to_owned().into_any().unbind(),
+        Json::Number(n) => n.into_pyobject(py)?.into_any().unbind(),
+        Json::String(s) => s.into_pyobject(py)?.into_any().unbind(),
+        Json::Array(items) => {
+            let list = PyList::empty(py);
+            for item in items {
+                list.append(to_python(py, item)?)?;
+            }
+            list.into_any().unbind()
+        }
+        Json::Object(fields) => {
+            let dict = PyDict::new(py);
+            for (key, value) in fields {
+                dict.set_item(key, to_python(py, value)?)?;
+            }
+            dict.into_any().unbind()
+        }
+    })
+}
+
+// `None` for messages that have no record, e.g. stack frames
+fn event(py: Python<'_>, msg: &Message) -> PyResult<Option<PyObject>> {
+    jsonl::to_json(msg)
+        .map(|json| to_python(py, &json))
+        .transpose()
+}
+
+/// parse(text) -> list of the events in strace's output
+#[pyfunction]
+fn parse(py: Python<'_>, text: &str) -> PyResult<Vec<PyObject>> {
+    let mut events = Vec::new();
+    for msg in strace::parse_reader(text.as_bytes()) {
+        let msg = msg.map_err(|e| PyIOError::new_err(e.to_string()))?;
+        events.extend(event(py, &msg)?);
+    }
+    Ok(events)
+}
+
+/// parse_file(path) -> iterator over the events in a file of strace's output
+#[pyfunction]
+fn parse_file(path: &str) -> PyResult<Events> {
+    let file = File::open(path)
+        .map_err(|e| PyIOError::new_err(format!("unable to open {}: {}", path, e)))?;
+    Ok(Events {
+        messages: Box::new(strace::parse_reader(BufReader::new(file))),
+    })
+}
+
+#[pyclass]
+struct Events {
+    messages: Box<dyn Iterator<Item = anyhow::Result<Message>> + Send + Sync>,
+}
+
+#[pymethods]
+impl Events {
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__(&mut self, py: Python<'_>) -> PyResult<Option<PyObject>> {
+        for msg in self.messages.by_ref() {
+            let msg = msg.map_err(|e| PyIOError::new_err(e.to_string()))?;
+            if let Some(event) = event(py, &msg)? {
+                return Ok(Some(event));
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// Parses strace's output a line at a time, keeping track of file descriptors and of statistics
+/// along the way.
+#[pyclass(unsendable)]
+struct Analyzer {
+    parser: LineParser,
+    fds: FdTable,
+    summary: summary::Summary,
+}
+
+#[pymethods]
+impl Analyzer {
+    #[new]
+    fn new() -> Analyzer {
+        Analyzer {
+            parser: LineParser::default(),
+            fds: FdTable::default(),
+            summary: summary::Summary::default(),
+        }
+    }
+
+    /// feed(line) -> the event that the line completes, or None
+    fn feed(&mut self, py: Python<'_>, line: &str) -> PyResult<Option<PyObject>> {
+        let Some(msg) = self.parser.parse_line(line) else {
+            return Ok(None);
+        };
+        let event = event(py, &msg)?;
+        if let Message::Syscall(syscall) = &msg {
+            self.fds.update(syscall);
+        }
+        self.summary.update(msg);
+        Ok(event)
+    }
+
+    /// fd(pid, fd) -> what a file descriptor that is open refers to, or None if it is not known to
+    /// be open (pid is None if strace did not print pids)
+    #[pyo3(signature = (pid, fd))]
+    fn fd(&self, py: Python<'_>, pid: Option<u32>, fd: i64) -> PyResult<Option<PyObject>> {
+        let info = self.fds.resolve(pid, fd, u64::MAX);
+        if info.kind == FdKind::Unknown {
+            return Ok(None);
+        }
+        fd_to_python(py, pid, fd, &info).map(Some)
+    }
+
+    /// fds() -> every use of every file descriptor, oldest first for each
+    fn fds(&self, py: Python<'_>) -> PyResult<Vec<PyObject>> {
+        self.fds
+            .all()
+            .into_iter()
+            .map(|(pid, fd, info)| fd_to_python(py, pid, fd, info))
+            .collect()
+    }
+
+    /// syscall_stats() -> dict of the calls, errors, and total time (in microseconds) of each
+    /// syscall, those that took the most time first
+    fn syscall_stats(&self, py: Python<'_>) -> PyResult<PyObject> {
+        let stats = PyDict::new(py);
+        for (name, s) in self.summary.syscall_rows() {
+            let json = Json::object([
+                ("calls", Json::Number(s.calls as i64)),
+                ("errors", Json::Number(s.errors as i64)),
+                ("total_us", Json::Number(s.total_micros as i64)),
+            ]);
+            stats.set_item(name, to_python(py, &json)?)?;
+        }
+        Ok(stats.into_any().unbind())
+    }
+
+    /// summary() -> the report of `vistrace --summary`, as text
+    fn summary(&self) -> PyResult<String> {
+        let mut report = Vec::new();
+        self.summary
+            .write(&mut report, false)
+            .map_err(|e| PyIOError::new_err(e.to_string()))?;
+        Ok(String::from_utf8_lossy(&report).into_owned())
+    }
+}
+
+fn fd_to_python(py: Python<'_>, pid: Option<u32>, fd: i64, info: &FdInfo) -> PyResult<PyObject> {
+    let (kind, path) = match &info.kind {
+        FdKind::File(path) => ("file", Json::string(path)),
+        FdKind::Socket { .. } => ("socket", Json::Null),
+        FdKind::Pipe { .. } => ("pipe", Json::Null),
+        FdKind::Other(_) => ("other", Json::Null),
+        FdKind::Unknown => ("unknown", Json::Null),
+    };
+    let micros = |t: u64| {
+        if t == 0 {
+            Json::Null
+        } else {
+            Json::Number(t as i64)
+        }
+    };
+    let json = Json::object([
+        ("pid", pid.into()),
+        ("fd", Json::Number(fd)),
+        ("kind", Json::string(kind)),
+        ("path", path),
+        // as vistrace shows it, e.g. "socket(AF_INET, SOCK_STREAM)"
+        ("description", Json::String(info.kind.to_string())),
+        ("opened_us", micros(info.opened_micros)),
+        ("closed_us", info.closed_micros.map_or(Json::Null, micros)),
+        ("cloexec", Json::Bool(info.cloexec)),
+        ("bytes_read", Json::Number(info.bytes.read as i64)),
+        ("bytes_written", Json::Number(info.bytes.written as i64)),
+    ]);
+    to_python(py, &json)
+}
+
+#[pymodule]
+#[pyo3(name = "vistrace")]
+fn vistrace_module(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(parse, m)?)?;
+    m.add_function(wrap_pyfunction!(parse_file, m)?)?;
+    m.add_class::<Analyzer>()?;
+    m.add_class::<Events>()?;
+    Ok(())
+}
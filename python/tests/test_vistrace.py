@@ -0,0 +1,38 @@
+# Run with `maturin develop && python -m unittest discover tests`.
+
+import unittest
+
+import vistrace
+
+TRACE = """\
+10 1720000000.000100 openat(AT_FDCWD, "/etc/hostname", O_RDONLY|O_CLOEXEC) = 3 <0.000010>
+10 1720000000.000200 read(3, "box\\n", 4096) = 4 <0.000005>
+10 1720000000.000300 openat(AT_FDCWD, "/nope", O_RDONLY) = -1 ENOENT (No such file or directory) <0.000004>
+10 1720000000.000400 close(3) = 0 <0.000002>
+10 1720000000.000500 +++ exited with 0 +++
+"""
+
+
+class TestVistrace(unittest.TestCase):
+    def test_parse(self):
+        events = vistrace.parse(TRACE)
+        self.assertEqual([e["type"] for e in events], ["syscall"] * 4 + ["exit"])
+        self.assertEqual(events[2]["errno"], "ENOENT")
+        self.assertEqual(events[0]["args"][1]["value"], "/etc/hostname")
+
+    def test_analyzer(self):
+        analyzer = vistrace.Analyzer()
+        for line in TRACE.splitlines():
+            analyzer.feed(line)
+        [fd] = analyzer.fds()
+        self.assertEqual((fd["pid"], fd["fd"], fd["kind"]), (10, 3, "file"))
+        self.assertEqual((fd["path"], fd["bytes_read"]), ("/etc/hostname", 4))
+        # closed by the end of the trace
+        self.assertIsNone(analyzer.fd(10, 3))
+        stats = analyzer.syscall_stats()
+        self.assertEqual(stats["openat"], {"calls": 2, "errors": 1, "total_us": 14})
+        self.assertIn("openat", analyzer.summary())
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,291 @@
+// `vistrace check` runs a command without the interface and fails if the command did anything that
+// an allowlist does not allow, for CI: e.g., to catch a dependency that starts making network
+// connections, or a sandboxed program that starts needing syscalls that its sandbox would block.
+//
+// The allowlist has one entry per line:
+//
+//   read                     a syscall
+//   %memory                  a category of syscalls, as for --exclude
+//   path /usr/lib/*          a file that a syscall used, where `*` matches anything (even '/')
+//   host *.example.com:443   a host that was connected to, by address or by the name that was
+//                            looked up for it, with or without the port
+//
+// Blank lines and lines that start with '#' are ignored. Paths are only checked if the allowlist
+// has a `path` entry, and hosts only if it has a `host` entry, so that a list of syscalls alone
+// allows any file and host. Only syscalls that succeeded count as using a path.
+//
+// The report has one JSON object per line for each thing that was not allowed, e.g.:
+//
+//   {"kind":"syscall","value":"ptrace","count":2,"pid":4120,"time_us":1720000000123456}
+//
+// where "kind" is "syscall", "path", or "host", and "pid" and "time_us" are of the first time.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs;
+use std::io::{self, Write};
+use std::net::IpAddr;
+use std::path::Path;
+
+use anyhow::{anyhow, Result};
+
+use crate::dns;
+use crate::fdtable::FdTable;
+use crate::json::Json;
+use crate::net::{Connections, Endpoint, Role};
+use crate::procfs;
+use crate::strace::{self, Message, Syscall};
+use crate::syscalls;
+
+#[derive(Debug, Default)]
+pub struct Allowlist {
+    // names and `%category`s
+    syscalls: Vec<String>,
+    paths: Vec<String>,
+    hosts: Vec<String>,
+}
+
+impl Allowlist {
+    pub fn load(path: &Path) -> Result<Allowlist> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| anyhow!("unable to read {}: {}", path.display(), e))?;
+        Allowlist::parse(&text).map_err(|e| anyhow!("in {}: {}", path.display(), e))
+    }
+
+    pub fn parse(text: &str) -> Result<Allowlist> {
+        let mut allowlist = Allowlist::default();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let error = |e| anyhow!("line {}: {}", i + 1, e);
+            match line.split_once(char::is_whitespace) {
+                Some(("path", pattern)) => allowlist.paths.push(pattern.trim().to_string()),
+                Some(("host", pattern)) => allowlist.hosts.push(pattern.trim().to_string()),
+                Some(_) => return Err(error(anyhow!("expected one entry, not {:?}", line))),
+                None => {
+                    let syscall = strace::parse_exclude(&[line.to_string()]).map_err(error)?;
+                    allowlist.syscalls.extend(syscall);
+                }
+            }
+        }
+        Ok(allowlist)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Kind {
+    Syscall,
+    Path,
+    Host,
+}
+
+impl Kind {
+    fn name(self) -> &'static str {
+        match self {
+            Kind::Syscall => "syscall",
+            Kind::Path => "path",
+            Kind::Host => "host",
+        }
+    }
+}
+
+struct Violation {
+    count: usize,
+    // of the first time
+    pid: Option<u32>,
+    time_micros: u64,
+}
+
+/// Checks a trace against an allowlist as it streams.
+pub struct Check<'a> {
+    allowlist: &'a Allowlist,
+    fds: FdTable,
+    net: Connections,
+    // the names that were looked up for each address
+    names: HashMap<IpAddr, BTreeSet<String>>,
+    violations: BTreeMap<(Kind, String), Violation>,
+}
+
+impl Check<'_> {
+    pub fn new(allowlist: &Allowlist) -> Check<'_> {
+        Check {
+            allowlist,
+            fds: FdTable::default(),
+            net: Connections::default(),
+            names: HashMap::new(),
+            violations: BTreeMap::new(),
+        }
+    }
+
+    /// Records that process `pid` (or any process, if `None`) was in `dir` when tracing began, so
+    /// that relative paths can be checked.
+    pub fn set_cwd(&mut self, pid: Option<u32>, dir: String) {
+        self.fds.set_cwd(pid, dir);
+    }
+
+    pub fn update(&mut self, msg: &Message) {
+        match msg {
+            Message::Syscall(syscall) => self.update_syscall(syscall),
+            Message::Exit(exit) => self.net.record_exit(exit, &self.fds),
+            _ => {}
+        }
+    }
+
+    fn update_syscall(&mut self, syscall: &Syscall) {
+        if !strace::excludes(&self.allowlist.syscalls, &syscall.name) {
+            self.add(Kind::Syscall, syscall.name.clone(), syscall);
+        }
+        if !self.allowlist.paths.is_empty() && syscall.errno.is_none() {
+            // an empty path (with AT_EMPTY_PATH) is of a file descriptor
+            for path in syscalls::path_args(syscall)
+                .iter()
+                .filter(|p| !p.is_empty())
+            {
+                let path = self.fds.absolute(syscall.pid, path);
+                if !self
+                    .allowlist
+                    .paths
+                    .iter()
+                    .any(|p| procfs::glob_match(p, &path))
+                {
+                    self.add(Kind::Path, path, syscall);
+                }
+            }
+        }
+
+        let connected = self.net.connections.len();
+        self.fds.update(syscall);
+        self.net.update(syscall, &self.fds);
+        if self.allowlist.hosts.is_empty() {
+            return;
+        }
+        for resolution in dns::decode(syscall, &self.fds, &self.net) {
+            for address in resolution.addresses {
+                self.names
+                    .entry(address)
+                    .or_default()
+                    .insert(resolution.name.clone());
+            }
+        }
+        // a connection is only known to be outbound once it is connected, which may be after the
+        // socket was opened
+        let hosts: Vec<(IpAddr, u16)> = syscalls::fd_args(syscall)
+            .into_iter()
+            .filter_map(|fd| self.net.find(syscall.pid, fd, &self.fds))
+            .chain(&self.net.connections[connected..])
+            .filter(|c| !matches!(c.role, Role::Listener | Role::Server))
+            .filter_map(|c| match c.remote {
+                Some(Endpoint::Inet(address, port)) => Some((address, port)),
+                _ => None,
+            })
+            .collect();
+        for (address, port) in hosts {
+            if !self.is_allowed_host(address, port) {
+                self.add(
+                    Kind::Host,
+                    Endpoint::Inet(address, port).to_string(),
+                    syscall,
+                );
+            }
+        }
+    }
+
+    fn is_allowed_host(&self, address: IpAddr, port: u16) -> bool {
+        let mut hosts = vec![address.to_string()];
+        hosts.extend(self.names.get(&address).into_iter().flatten().cloned());
+        let with_ports: Vec<String> = hosts
+            .iter()
+            .map(|host| match address {
+                IpAddr::V6(_) if *host == address.to_string() => format!("[{}]:{}", host, port),
+                _ => format!("{}:{}", host, port),
+            })
+            .collect();
+        self.allowlist.hosts.iter().any(|pattern| {
+            hosts
+                .iter()
+                .chain(&with_ports)
+                .any(|host| procfs::glob_match(pattern, host))
+        })
+    }
+
+    fn add(&mut self, kind: Kind, value: String, syscall: &Syscall) {
+        self.violations
+            .entry((kind, value))
+            .or_insert(Violation {
+                count: 0,
+                pid: syscall.pid,
+                time_micros: syscall.entry_time_micros,
+            })
+            .count += 1;
+    }
+
+    /// The number of different things that were not allowed.
+    pub fn violations(&self) -> usize {
+        self.violations.len()
+    }
+
+    pub fn write(&self, w: &mut impl Write) -> io::Result<()> {
+        for ((kind, value), violation) in &self.violations {
+            let time = match violation.time_micros {
+                0 => Json::Null,
+                t => Json::Number(t as i64),
+            };
+            let json = Json::object([
+                ("kind", Json::string(kind.name())),
+                ("value", Json::string(value)),
+                ("count", Json::Number(violation.count as i64)),
+                ("pid", violation.pid.into()),
+                ("time_us", time),
+            ]);
+            writeln!(w, "{}", json)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::strace::LineParser;
+
+    use super::{Allowlist, Check};
+
+    #[test]
+    fn test_check() {
+        let allowlist = Allowlist::parse(
+            "# a comment\n\
+             openat\nclose\n%network\n\
+             path /etc/*\n\
+             host 10.0.0.1:443\n",
+        )
+        .unwrap();
+        assert!(Allowlist::parse("path /etc/* /usr/*\nopenat close").is_err());
+        assert!(Allowlist::parse("%nope").is_err());
+
+        let mut check = Check::new(&allowlist);
+        check.set_cwd(None, "/home/me".to_string());
+        let mut parser = LineParser::default();
+        for line in [
+            "10 openat(AT_FDCWD, \"/etc/hosts\", O_RDONLY) = 3",
+            "10 openat(AT_FDCWD, \"secrets\", O_RDONLY) = 4",
+            "10 openat(AT_FDCWD, \"/nope\", O_RDONLY) = -1 ENOENT (No such file or directory)",
+            "10 ptrace(PTRACE_TRACEME) = -1 EPERM (Operation not permitted)",
+            "10 ptrace(PTRACE_TRACEME) = -1 EPERM (Operation not permitted)",
+            "10 socket(AF_INET, SOCK_STREAM, IPPROTO_TCP) = 5",
+            "10 connect(5, {sa_family=AF_INET, sin_port=htons(443), sin_addr=inet_addr(\"10.0.0.1\")}, 16) = 0",
+            "10 socket(AF_INET, SOCK_STREAM, IPPROTO_TCP) = 6",
+            "10 connect(6, {sa_family=AF_INET, sin_port=htons(80), sin_addr=inet_addr(\"10.0.0.1\")}, 16) = 0",
+        ] {
+            check.update(&parser.parse_line(line).unwrap());
+        }
+        let mut report = Vec::new();
+        check.write(&mut report).unwrap();
+        assert_eq!(check.violations(), 3);
+        assert_eq!(
+            String::from_utf8(report).unwrap(),
+            "{\"kind\":\"syscall\",\"value\":\"ptrace\",\"count\":2,\"pid\":10,\"time_us\":null}\n\
+             {\"kind\":\"path\",\"value\":\"/home/me/secrets\",\"count\":1,\"pid\":10,\"time_us\":null}\n\
+             {\"kind\":\"host\",\"value\":\"10.0.0.1:80\",\"count\":1,\"pid\":10,\"time_us\":null}\n"
+        );
+    }
+}
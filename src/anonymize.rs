@@ -0,0 +1,260 @@
+// Anonymization (`vistrace export --anonymize`), which replaces usernames and hostnames with
+// pseudonyms, e.g. "/home/alice/notes.txt" with "/home/user-3f9a0c/notes.txt", but otherwise leaves
+// the trace as it was, so that it can still be analyzed (unlike with --redact, which masks
+// everything).
+//
+// Names are learned from the trace as it goes: usernames from home directories (/home/NAME,
+// /Users/NAME, /var/home/NAME) and the USER and LOGNAME environment variables, and hostnames from
+// the HOSTNAME environment variable and uname's nodename. Once learned, a name is replaced wherever
+// it appears as a whole word in a string, from that message on. "root" and "localhost" are kept.
+//
+// A pseudonym is a hash of the name with a key, which is random unless one is given with
+// `--anonymize=KEY`, so that traces that were anonymized with the same key can be diffed.
+
+use std::collections::hash_map::{DefaultHasher, RandomState};
+use std::collections::BTreeMap;
+use std::hash::{BuildHasher, Hash, Hasher};
+
+use crate::strace::{Message, SyscallArg, SyscallArgValue};
+
+// where home directories are
+const HOMES: &[&str] = &["/home/", "/Users/", "/var/home/"];
+
+pub struct Anonymizer {
+    key: String,
+    // each name that has been learned, with its pseudonym
+    names: BTreeMap<String, String>,
+}
+
+impl Anonymizer {
+    pub fn new(key: Option<&str>) -> Anonymizer {
+        let key = match key {
+            Some(key) => key.to_string(),
+            None => format!("{:016x}", RandomState::new().hash_one(0)),
+        };
+        Anonymizer {
+            key,
+            names: BTreeMap::new(),
+        }
+    }
+
+    pub fn anonymize(&mut self, msg: &mut Message) {
+        for_each_string(msg, &mut |field, text| self.learn(field, text));
+        if self.names.is_empty() {
+            return;
+        }
+        // longest first, so that "alice" is not replaced within "alice-laptop"
+        let mut names: Vec<(&String, &String)> = self.names.iter().collect();
+        names.sort_by_key(|(name, _)| std::cmp::Reverse(name.len()));
+        for_each_string(msg, &mut |_, text| {
+            for (name, pseudonym) in &names {
+                if text.contains(name.as_str()) {
+                    *text = replace_word(text, name, pseudonym);
+                }
+            }
+        });
+    }
+
+    // finds the usernames and hostnames in a string of the trace
+    fn learn(&mut self, field: Option<&str>, text: &str) {
+        if field == Some("nodename") {
+            self.add("host", text);
+        }
+        for (var, kind) in [
+            ("USER=", "user"),
+            ("LOGNAME=", "user"),
+            ("HOSTNAME=", "host"),
+        ] {
+            if let Some(value) = text.strip_prefix(var) {
+                self.add(kind, value);
+            }
+        }
+        for home in HOMES {
+            for (i, _) in text.match_indices(home) {
+                let rest = &text[i + home.len()..];
+                let end = rest.find(|c| !is_word_char(c)).unwrap_or(rest.len());
+                self.add("user", &rest[..end]);
+            }
+        }
+    }
+
+    fn add(&mut self, kind: &str, name: &str) {
+        if name.is_empty()
+            || name == "root"
+            || name == "localhost"
+            || !name.chars().all(is_word_char)
+        {
+            return;
+        }
+        if !self.names.contains_key(name) {
+            let mut hasher = DefaultHasher::new();
+            (&self.key, name).hash(&mut hasher);
+            let pseudonym = format!("{}-{:06x}", kind, hasher.finish() & 0xff_ffff);
+            self.names.insert(name.to_string(), pseudonym);
+        }
+    }
+}
+
+// calls `f` with each string in `msg`, and the name of the struct field that it is, if any
+fn for_each_string(msg: &mut Message, f: &mut impl FnMut(Option<&str>, &mut String)) {
+    match msg {
+        Message::Syscall(syscall) => {
+            for arg in &mut syscall.args {
+                for_each_value(&mut arg.value, None, f);
+            }
+            if let Some(returned) = &mut syscall.returned {
+                for_each_value(returned, None, f);
+            }
+            // the line that could not be parsed
+            if let Some(details) = &mut syscall.error_details {
+                f(None, &mut details.fulltext);
+            }
+        }
+        Message::Signal(signal) => {
+            for (name, arg) in &mut signal.info {
+                for_each_value(&mut arg.value, Some(name), f);
+            }
+        }
+        Message::Frame(frame) => f(None, &mut frame.module),
+        Message::LibCall(call) => {
+            f(None, &mut call.args);
+            f(None, &mut call.return_value);
+        }
+        Message::Exit(_) | Message::Skipped(_) | Message::Notice(_) => {}
+    }
+}
+
+fn for_each_value(
+    value: &mut SyscallArgValue,
+    field: Option<&str>,
+    f: &mut impl FnMut(Option<&str>, &mut String),
+) {
+    let each = |args: &mut Vec<SyscallArg>, f: &mut _| {
+        for arg in args {
+            for_each_value(&mut arg.value, None, f);
+        }
+    };
+    match value {
+        // names are made of characters that strace does not escape
+        SyscallArgValue::Quoted { text, .. } => f(field, text),
+        SyscallArgValue::Array(args) | SyscallArgValue::FunctionCall(_, args) => each(args, f),
+        SyscallArgValue::Struct(fields) => {
+            for (name, arg) in fields {
+                for_each_value(&mut arg.value, Some(name), f);
+            }
+        }
+        SyscallArgValue::Symbol(_)
+        | SyscallArgValue::FlagSet(_)
+        | SyscallArgValue::Number(_)
+        | SyscallArgValue::Product(_, _) => {}
+    }
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.'
+}
+
+// replaces `word` in `text` wherever it is not part of a longer word
+fn replace_word(text: &str, word: &str, with: &str) -> String {
+    let mut replaced = String::with_capacity(text.len());
+    let mut last = 0;
+    for (i, _) in text.match_indices(word) {
+        let before = text[..i].chars().next_back();
+        let after = text[i + word.len()..].chars().next();
+        let whole = !before.is_some_and(|c| c.is_ascii_alphanumeric() || c == '_')
+            && !after.is_some_and(|c| c.is_ascii_alphanumeric() || c == '_');
+        if whole && i >= last {
+            replaced.push_str(&text[last..i]);
+            replaced.push_str(with);
+            last = i + word.len();
+        }
+    }
+    replaced.push_str(&text[last..]);
+    replaced
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::strace::LineParser;
+
+    use super::Anonymizer;
+
+    #[test]
+    fn test_anonymize() {
+        let mut parser = LineParser::default();
+        let mut anonymize = |anonymizer: &mut Anonymizer, line: &str| {
+            let mut msg = parser.parse_line(line).unwrap();
+            anonymizer.anonymize(&mut msg);
+            msg.to_string()
+        };
+
+        let mut anonymizer = Anonymizer::new(Some("key"));
+        let execve = anonymize(
+            &mut anonymizer,
+            "10 execve(\"/usr/bin/ssh\", [\"ssh\", \"alice@alice-laptop\"], [\"USER=alice\", \"HOSTNAME=alice-laptop\"]) = 0",
+        );
+        let user = &anonymizer.names["alice"].clone();
+        let host = &anonymizer.names["alice-laptop"].clone();
+        assert!(user.starts_with("user-") && host.starts_with("host-"));
+        assert_eq!(
+            execve,
+            format!(
+                "10 execve(\"/usr/bin/ssh\", [\"ssh\", \"{user}@{host}\"], [\"USER={user}\", \"HOSTNAME={host}\"]) = 0"
+            )
+        );
+        // a name is replaced only as a whole word, and paths are otherwise kept
+        assert_eq!(
+            anonymize(
+                &mut anonymizer,
+                "10 openat(AT_FDCWD, \"/home/alice/malice/bob\", O_RDONLY) = 3"
+            ),
+            format!("10 openat(AT_FDCWD, \"/home/{user}/malice/bob\", O_RDONLY) = 3")
+        );
+        assert_eq!(
+            anonymize(
+                &mut anonymizer,
+                "10 openat(AT_FDCWD, \"/root/.bashrc\", O_RDONLY) = 3"
+            ),
+            "10 openat(AT_FDCWD, \"/root/.bashrc\", O_RDONLY) = 3"
+        );
+        let bob = anonymize(
+            &mut anonymizer,
+            "10 stat(\"/Users/bob/x\", {st_mode=S_IFREG|0644, st_size=0, ...}) = 0",
+        );
+        assert!(!bob.contains("bob"));
+
+        // the same key gives the same pseudonyms
+        let mut again = Anonymizer::new(Some("key"));
+        anonymize(&mut again, "10 chdir(\"/home/alice\") = 0");
+        assert_eq!(&again.names["alice"], user);
+        let mut other = Anonymizer::new(Some("other"));
+        anonymize(&mut other, "10 chdir(\"/home/alice\") = 0");
+        assert_ne!(&other.names["alice"], user);
+
+        let uname = anonymize(
+            &mut again,
+            "10 uname({sysname=\"Linux\", nodename=\"build7\", ...}) = 0",
+        );
+        assert!(!uname.contains("build7") && uname.contains("nodename=\"host-"));
+    }
+
+    #[test]
+    fn test_anonymize_malformed() {
+        let mut parser = LineParser::default();
+        let mut anonymizer = Anonymizer::new(Some("key"));
+        let anonymized: Vec<String> = [
+            "10 chdir(\"/home/alice\") = 0",
+            "10 openat(AT_FDCWD, \"/home/alice/no",
+        ]
+        .iter()
+        .map(|line| {
+            let mut msg = parser.parse_line(line).unwrap();
+            anonymizer.anonymize(&mut msg);
+            msg.to_string()
+        })
+        .collect();
+        // the line is kept as it was, but without the name
+        let user = &anonymizer.names["alice"];
+        assert_eq!(anonymized[1], format!("openat(AT_FDCWD, \"/home/{user}/no"));
+    }
+}
@@ -0,0 +1,214 @@
+// Notices a traced program checking whether it is being traced. Some programs (malware, copy
+// protection, anti-cheat, and many CTF challenges) do this in order to behave differently under a
+// debugger, in which case the trace shows what the program does when it knows it is watched, not
+// what it does otherwise.
+//
+// The checks that show up in a trace are:
+//
+//   - ptrace(PTRACE_TRACEME), which fails with EPERM when the process is already traced (as it is
+//     under strace)
+//   - reading its own /proc/PID/status, whose TracerPid line is nonzero under strace. Plenty of
+//     programs read the file for other reasons, e.g. for their memory use, so this is only a hint
+//     unless the data that was read shows TracerPid (which needs a larger --strace-arg=-s)
+//
+// A process that exits with an error (or kills itself) soon after a check probably noticed strace.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::fdtable::{FdKind, FdTable};
+use crate::strace::{format_timestamp, Syscall, SyscallArgValue};
+use crate::syscalls;
+
+// how many syscalls after a check a process must exit within to count as bailing out
+const EXIT_WINDOW: usize = 20;
+
+#[derive(Default)]
+pub struct AntiDebug {
+    pub detections: Vec<Detection>,
+    // the processes that checked for a tracer, with how many syscalls they have made since
+    checked: HashMap<Option<u32>, usize>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Detection {
+    pub pid: Option<u32>,
+    pub time_micros: u64,
+    pub kind: Kind,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Kind {
+    // whether it failed, i.e. the process found out that it is traced
+    TraceMe { failed: bool },
+    // whether the data that was read shows TracerPid
+    Status { path: String, tracer_pid: bool },
+    // how it exited, e.g. "exit_group(1)" or "kill(SIGKILL)"
+    Bailed(String),
+}
+
+impl AntiDebug {
+    /// Returns the index into `detections` of what `syscall` gives away, if anything. Must be
+    /// called after `fds` has been updated with `syscall`.
+    pub fn update(&mut self, syscall: &Syscall, fds: &FdTable) -> Option<usize> {
+        if syscall.error_details.is_some() {
+            return None;
+        }
+
+        let kind = check(syscall, fds).or_else(|| {
+            let since = self.checked.get_mut(&syscall.pid)?;
+            *since += 1;
+            if *since > EXIT_WINDOW {
+                self.checked.remove(&syscall.pid);
+                return None;
+            }
+            bails(syscall)
+        })?;
+        match kind {
+            Kind::Bailed(_) => self.checked.remove(&syscall.pid),
+            _ => self.checked.insert(syscall.pid, 0),
+        };
+        self.detections.push(Detection {
+            pid: syscall.pid,
+            time_micros: syscall.entry_time_micros,
+            kind,
+        });
+        Some(self.detections.len() - 1)
+    }
+}
+
+fn check(syscall: &Syscall, fds: &FdTable) -> Option<Kind> {
+    let symbol = |i: usize| match syscall.arg(i).map(|a| &a.value) {
+        Some(SyscallArgValue::Symbol(s)) => Some(s.as_str()),
+        _ => None,
+    };
+    match syscall.name.as_str() {
+        "ptrace" if symbol(0) == Some("PTRACE_TRACEME") => Some(Kind::TraceMe {
+            failed: syscall.errno.is_some(),
+        }),
+        "read" | "pread64" if syscall.errno.is_none() => {
+            let fd = *syscalls::fd_args(syscall).first()?;
+            let path = match fds.resolve(syscall.pid, fd, syscall.entry_time_micros).kind {
+                FdKind::File(path) if is_own_status(&path, syscall.pid) => path,
+                _ => return None,
+            };
+            let tracer_pid = matches!(
+                syscall.arg(1).map(|a| &a.value),
+                Some(SyscallArgValue::Quoted { text, .. }) if text.contains("TracerPid:")
+            );
+            Some(Kind::Status { path, tracer_pid })
+        }
+        _ => None,
+    }
+}
+
+// "/proc/self/status", "/proc/thread-self/status", or "/proc/PID/status" (or
+// "/proc/PID/task/TID/status") for the process's own pid
+fn is_own_status(path: &str, pid: Option<u32>) -> bool {
+    let rest = match path
+        .strip_prefix("/proc/")
+        .and_then(|p| p.strip_suffix("/status"))
+    {
+        Some(rest) => rest,
+        None => return false,
+    };
+    let first = rest.split('/').next().unwrap_or_default();
+    first == "self" || first == "thread-self" || pid.is_some_and(|pid| first == pid.to_string())
+}
+
+// whether `syscall` ends the process in a way that suggests something went wrong
+fn bails(syscall: &Syscall) -> Option<Kind> {
+    let arg = |i: usize| {
+        syscall
+            .arg(i)
+            .map(|a| a.value.to_string())
+            .unwrap_or_default()
+    };
+    match syscall.name.as_str() {
+        "exit" | "exit_group" if arg(0) != "0" => {
+            Some(Kind::Bailed(format!("{}({})", syscall.name, arg(0))))
+        }
+        "kill" | "tgkill" | "tkill" if syscall.pid.is_some_and(|pid| arg(0) == pid.to_string()) => {
+            let signal = syscall.args.last().map(|a| a.value.to_string())?;
+            Some(Kind::Bailed(format!("{}({})", syscall.name, signal)))
+        }
+        _ => None,
+    }
+}
+
+impl fmt::Display for Detection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            Kind::TraceMe { failed: true } => write!(
+                f,
+                "called ptrace(PTRACE_TRACEME), which failed because it is being traced"
+            )?,
+            Kind::TraceMe { failed: false } => write!(
+                f,
+                "called ptrace(PTRACE_TRACEME), a common check for a debugger"
+            )?,
+            Kind::Status {
+                path,
+                tracer_pid: true,
+            } => write!(f, "read TracerPid from {}", path)?,
+            Kind::Status {
+                path,
+                tracer_pid: false,
+            } => write!(
+                f,
+                "read {}, which says whether it is being traced (TracerPid)",
+                path
+            )?,
+            Kind::Bailed(how) => write!(f, "quit with {} soon after checking for a tracer", how)?,
+        }
+        write!(f, " at {}", format_timestamp(self.time_micros))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fdtable::FdTable;
+    use crate::strace::LineParser;
+
+    use super::{AntiDebug, Kind};
+
+    #[test]
+    fn test_anti_debug() {
+        let mut detector = AntiDebug::default();
+        let mut fds = FdTable::default();
+        let mut parser = LineParser::default();
+        let mut kinds = Vec::new();
+        for line in [
+            "10 openat(AT_FDCWD, \"/proc/self/status\", O_RDONLY) = 3",
+            "10 read(3, \"Name:\\tx\\nTracerPid:\\t9\\n\", 4096) = 22",
+            "10 openat(AT_FDCWD, \"/proc/11/status\", O_RDONLY) = 4",
+            "10 read(4, \"Name:\\ty\\n\", 4096) = 8",
+            "11 ptrace(PTRACE_TRACEME) = -1 EPERM (Operation not permitted)",
+            "11 write(2, \"no\", 2) = 2",
+            "11 exit_group(1) = ?",
+            "12 exit_group(1) = ?",
+        ] {
+            let syscall = parser.parse_line(line).unwrap().into_syscall().unwrap();
+            fds.update(&syscall);
+            if let Some(i) = detector.update(&syscall, &fds) {
+                kinds.push(detector.detections[i].kind.clone());
+            }
+        }
+
+        assert_eq!(
+            kinds,
+            vec![
+                Kind::Status {
+                    path: "/proc/self/status".to_string(),
+                    tracer_pid: true
+                },
+                Kind::TraceMe { failed: true },
+                Kind::Bailed("exit_group(1)".to_string()),
+            ]
+        );
+        assert_eq!(
+            detector.detections[1].to_string(),
+            "called ptrace(PTRACE_TRACEME), which failed because it is being traced at 0.000000"
+        );
+    }
+}
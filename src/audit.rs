@@ -0,0 +1,189 @@
+// Flags the events that a security review of an unfamiliar program would want to look at: access
+// to credentials and other sensitive files, connections to other hosts, shells being started, and
+// syscalls that change privileges. Attempts count too, even if they failed.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::net::{self, Endpoint};
+use crate::strace::Syscall;
+use crate::syscalls;
+
+// files that hold credentials or let a process read another's memory
+const SENSITIVE_FILES: &[&str] = &["/etc/shadow", "/etc/gshadow", "/etc/sudoers"];
+// directories in a home directory (or anywhere else) that hold credentials
+const SENSITIVE_DIRS: &[&str] = &[".ssh", ".aws", ".gnupg", ".kube", ".docker"];
+const SHELLS: &[&str] = &[
+    "sh", "bash", "dash", "zsh", "ksh", "mksh", "fish", "csh", "tcsh",
+];
+const PRIVILEGED: &[&str] = &[
+    "setuid",
+    "setgid",
+    "setreuid",
+    "setregid",
+    "setresuid",
+    "setresgid",
+    "setfsuid",
+    "setfsgid",
+    "setgroups",
+    "capset",
+    "ptrace",
+    "process_vm_readv",
+    "process_vm_writev",
+    "mount",
+    "umount2",
+    "chroot",
+    "pivot_root",
+    "setns",
+    "unshare",
+    "init_module",
+    "finit_module",
+    "delete_module",
+    "kexec_load",
+    "kexec_file_load",
+    "bpf",
+    "iopl",
+    "ioperm",
+    "reboot",
+];
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Finding {
+    SensitiveFile(String),
+    // a connection to (or datagram for) an address other than this host
+    Outbound(Endpoint),
+    Shell(String),
+    Privileged(String),
+}
+
+/// Returns what is worth a security reviewer's attention in `syscall`, if anything.
+pub fn check(syscall: &Syscall) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    if syscall.error_details.is_some() {
+        return findings;
+    }
+
+    for path in syscalls::path_args(syscall) {
+        if is_sensitive(&path) {
+            findings.push(Finding::SensitiveFile(path));
+        }
+    }
+
+    match syscall.name.as_str() {
+        "execve" | "execveat" => {
+            if let Some(path) = syscalls::path_args(syscall).into_iter().next() {
+                let program = path.rsplit('/').next().unwrap_or(&path);
+                if SHELLS.contains(&program) {
+                    findings.push(Finding::Shell(path));
+                }
+            }
+        }
+        "connect" | "sendto" => {
+            let name = if syscall.name == "connect" {
+                "addr"
+            } else {
+                "dest_addr"
+            };
+            let endpoint = syscalls::arg_index(&syscall.name, name)
+                .and_then(|i| syscall.arg(i))
+                .and_then(|a| net::decode_sockaddr(&a.value));
+            if let Some(endpoint @ Endpoint::Inet(addr, _)) = endpoint {
+                if !addr.is_loopback() && !addr.is_unspecified() {
+                    findings.push(Finding::Outbound(endpoint));
+                }
+            }
+        }
+        name if PRIVILEGED.contains(&name) => {
+            findings.push(Finding::Privileged(name.to_string()));
+        }
+        _ => {}
+    }
+    findings
+}
+
+fn is_sensitive(path: &str) -> bool {
+    if SENSITIVE_FILES.contains(&path) {
+        return true;
+    }
+    let components: Vec<&str> = path.split('/').collect();
+    if components.iter().any(|c| SENSITIVE_DIRS.contains(c)) {
+        return true;
+    }
+    // e.g., /proc/1234/mem or /proc/self/mem
+    matches!(components[..], ["", "proc", _, "mem"])
+}
+
+/// The findings in a whole trace, each counted once per process.
+#[derive(Default)]
+pub struct Audit {
+    pub findings: Vec<(Option<u32>, Finding, usize)>,
+    // index into `findings`
+    seen: HashMap<(Option<u32>, String), usize>,
+}
+
+impl Audit {
+    pub fn update(&mut self, syscall: &Syscall) {
+        for finding in check(syscall) {
+            let key = (syscall.pid, finding.to_string());
+            match self.seen.get(&key) {
+                Some(index) => self.findings[*index].2 += 1,
+                None => {
+                    self.seen.insert(key, self.findings.len());
+                    self.findings.push((syscall.pid, finding, 1));
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Finding::SensitiveFile(path) => write!(f, "sensitive file {}", path),
+            Finding::Outbound(endpoint) => write!(f, "outbound connection to {}", endpoint),
+            Finding::Shell(path) => write!(f, "ran a shell, {}", path),
+            Finding::Privileged(name) => write!(f, "privileged syscall {}", name),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::strace::parse_syscall;
+
+    use super::check;
+
+    #[test]
+    fn test_check() {
+        let findings = |line: &str| -> Vec<String> {
+            check(&parse_syscall(line, false))
+                .iter()
+                .map(|f| f.to_string())
+                .collect()
+        };
+        assert_eq!(
+            findings("openat(AT_FDCWD, \"/etc/shadow\", O_RDONLY) = -1 EACCES (Permission denied)"),
+            ["sensitive file /etc/shadow"]
+        );
+        assert_eq!(
+            findings("openat(AT_FDCWD, \"/home/me/.ssh/id_ed25519\", O_RDONLY) = 3"),
+            ["sensitive file /home/me/.ssh/id_ed25519"]
+        );
+        assert_eq!(
+            findings("openat(AT_FDCWD, \"/proc/self/mem\", O_RDWR) = 3"),
+            ["sensitive file /proc/self/mem"]
+        );
+        assert!(findings("openat(AT_FDCWD, \"/proc/self/maps\", O_RDONLY) = 3").is_empty());
+        assert_eq!(
+            findings("execve(\"/bin/sh\", [\"sh\", \"-c\", \"id\"], 0x7ffc /* 1 vars */) = 0"),
+            ["ran a shell, /bin/sh"]
+        );
+        assert_eq!(
+            findings("connect(3, {sa_family=AF_INET, sin_port=htons(443), sin_addr=inet_addr(\"1.2.3.4\")}, 16) = 0"),
+            ["outbound connection to 1.2.3.4:443"]
+        );
+        assert!(findings("connect(3, {sa_family=AF_INET, sin_port=htons(53), sin_addr=inet_addr(\"127.0.0.53\")}, 16) = 0").is_empty());
+        assert_eq!(findings("setuid(0) = 0"), ["privileged syscall setuid"]);
+        assert!(findings("getuid() = 1000").is_empty());
+    }
+}
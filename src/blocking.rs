@@ -0,0 +1,159 @@
+// Splits each process's time into the time it spent inside syscalls (mostly blocked, waiting on
+// I/O, locks, or timers) and the time between them (running its own code, or waiting for a CPU), to
+// answer whether a program is CPU-bound or waiting on something.
+//
+// Needs strace's timestamps and durations (-tt -T), which vistrace asks for. The time between
+// syscalls includes strace's own overhead, so it overstates running time for syscall-heavy
+// programs.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+use crate::strace::Syscall;
+use crate::units;
+
+const BAR_WIDTH: usize = 20;
+// how many syscalls `mostly_in` names
+const MOSTLY_IN: usize = 3;
+
+#[derive(Default)]
+pub struct Blocking {
+    // keyed by pid (threads are counted separately, since each one can block on its own)
+    pub processes: BTreeMap<u32, Time>,
+}
+
+#[derive(Default)]
+pub struct Time {
+    // from the start of the first syscall to the end of the last one
+    first_micros: u64,
+    last_micros: u64,
+    pub blocked_micros: u64,
+    by_syscall: HashMap<String, u64>,
+}
+
+impl Blocking {
+    pub fn update(&mut self, syscall: &Syscall) {
+        let pid = match syscall.pid {
+            // without timestamps, there is nothing to go on
+            Some(pid) if syscall.entry_time_micros != 0 => pid,
+            _ => return,
+        };
+        let time = self.processes.entry(pid).or_insert_with(|| Time {
+            first_micros: syscall.entry_time_micros,
+            ..Default::default()
+        });
+        let end = syscall.entry_time_micros + syscall.syscall_time_micros.unwrap_or(0);
+        time.last_micros = time.last_micros.max(end);
+        time.blocked_micros += syscall.syscall_time_micros.unwrap_or(0);
+        *time.by_syscall.entry(syscall.name.clone()).or_default() +=
+            syscall.syscall_time_micros.unwrap_or(0);
+    }
+}
+
+impl Time {
+    pub fn running_micros(&self) -> u64 {
+        (self.last_micros - self.first_micros).saturating_sub(self.blocked_micros)
+    }
+
+    pub fn total_micros(&self) -> u64 {
+        self.blocked_micros + self.running_micros()
+    }
+
+    /// The share of the time that was spent in syscalls, from 0 to 1.
+    pub fn blocked_share(&self) -> f64 {
+        let total = self.total_micros();
+        if total == 0 {
+            0.0
+        } else {
+            self.blocked_micros as f64 / total as f64
+        }
+    }
+
+    /// Names the syscalls that the most time was spent in, e.g., "read 0.750000s, poll 0.250000s",
+    /// or returns `None` if no time was spent in any.
+    pub fn mostly_in(&self) -> Option<String> {
+        let syscalls: Vec<String> = self
+            .blocked_in(MOSTLY_IN)
+            .iter()
+            .map(|(name, micros)| format!("{} {}", name, units::duration(*micros)))
+            .collect();
+        (!syscalls.is_empty()).then(|| syscalls.join(", "))
+    }
+
+    // the syscalls that the most time was spent in, most first, with the microseconds for each
+    fn blocked_in(&self, count: usize) -> Vec<(&str, u64)> {
+        let mut syscalls: Vec<(&str, u64)> = self
+            .by_syscall
+            .iter()
+            .filter(|(_, micros)| **micros > 0)
+            .map(|(name, micros)| (name.as_str(), *micros))
+            .collect();
+        syscalls.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        syscalls.truncate(count);
+        syscalls
+    }
+
+    // blocked time as the solid part of the bar, running time as the shaded part
+    fn bar(&self) -> String {
+        let blocked = (self.blocked_share() * BAR_WIDTH as f64).round() as usize;
+        format!("{}{}", "█".repeat(blocked), "░".repeat(BAR_WIDTH - blocked))
+    }
+}
+
+impl fmt::Display for Time {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {:>5.1}% blocked ({} in syscalls, {} between them)",
+            self.bar(),
+            self.blocked_share() * 100.0,
+            units::duration(self.blocked_micros),
+            units::duration(self.running_micros())
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::strace::{LineParser, Message};
+
+    use super::Blocking;
+
+    #[test]
+    fn test_blocking() {
+        let mut blocking = Blocking::default();
+        let mut parser = LineParser::default();
+        for line in [
+            "10 1.000000 read(3, \"x\", 1) = 1 <0.500000>",
+            "10 1.600000 poll([{fd=3, events=POLLIN}], 1, -1) = 1 ([{fd=3, revents=POLLIN}]) <0.250000>",
+            "10 1.950000 read(3, \"x\", 1) = 1 <0.250000>",
+            "11 1.000000 getpid() = 11 <0.000001>",
+            "11 2.000000 getpid() = 11 <0.000001>",
+            "12 getpid() = 12",
+        ] {
+            let syscall = parser
+                .parse_line(line)
+                .and_then(Message::into_syscall)
+                .unwrap();
+            blocking.update(&syscall);
+        }
+
+        let time = &blocking.processes[&10];
+        assert_eq!(time.blocked_micros, 1_000_000);
+        assert_eq!(time.running_micros(), 200_000);
+        assert_eq!(time.blocked_in(1), [("read", 750_000)]);
+        assert_eq!(
+            time.mostly_in().as_deref(),
+            Some("read 750 ms, poll 250 ms")
+        );
+        assert_eq!(
+            time.to_string(),
+            "█████████████████░░░  83.3% blocked (1.0 s in syscalls, 200 ms between them)"
+        );
+        assert_eq!(
+            blocking.processes[&11].to_string(),
+            "░░░░░░░░░░░░░░░░░░░░   0.0% blocked (2 µs in syscalls, 1.0 s between them)"
+        );
+        assert!(!blocking.processes.contains_key(&12));
+    }
+}
@@ -0,0 +1,466 @@
+// A tracer that uses eBPF (`--backend bpf`), through bpftrace, for when ptrace slows the traced
+// processes down too much: the script attaches to the kernel's raw syscall tracepoints, so the
+// processes never stop to be traced. It can also trace every process in a cgroup (`vistrace attach
+// --cgroup`), such as a container or a systemd service, or every process on the machine (`vistrace
+// attach --system`). It needs root, and bpftrace 0.18 or later.
+//
+// It records less than strace does: the raw arguments and return value of every syscall, the paths
+// of the common syscalls that take one, signals, and exits, but not the data of reads and writes,
+// nor the structs that syscalls take or fill in. The script (see `script`) prints one event per
+// line, with times in nanoseconds of the kernel's monotonic clock:
+//
+//   B NSECS                                      the script started
+//   P TID NAME PATH                              thread TID called syscall NAME with PATH
+//   E TID NR RET START DURATION A0 A1 A2 A3 A4 A5  thread TID returned RET from syscall NR (RET is
+//                                                '?' if the thread exited first)
+//   S TID NSECS SIG CODE                         signal SIG was delivered to thread TID
+//   X TID NSECS STATUS                           thread TID exited with wait status STATUS
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{anyhow, Result};
+
+use crate::strace::{
+    self, Exit, ExitKind, Message, Parse, Signal, Syscall, SyscallArg, SyscallArgValue,
+};
+use crate::syscalls;
+
+/// Processes to trace other than a command or pids.
+#[derive(Clone, Debug)]
+pub enum Scope {
+    // the cgroup's directory, e.g. /sys/fs/cgroup/system.slice/nginx.service
+    Cgroup(PathBuf),
+    System,
+}
+
+pub struct BpfOptions {
+    pub bpftrace_path: PathBuf,
+    // program to run under bpftrace, if any
+    pub command: Vec<String>,
+    // already-running processes to trace
+    pub pids: Vec<u32>,
+    pub scope: Option<Scope>,
+    // trace the children of the command or pids as well
+    pub follow: bool,
+}
+
+// the syscalls whose path is recorded, with the field of their tracepoint that holds it
+const PATHS: &[(&str, &str)] = &[
+    ("open", "filename"),
+    ("openat", "filename"),
+    ("creat", "pathname"),
+    ("execve", "filename"),
+    ("execveat", "filename"),
+    ("stat", "filename"),
+    ("lstat", "filename"),
+    ("newfstatat", "filename"),
+    ("statx", "filename"),
+    ("access", "filename"),
+    ("faccessat", "filename"),
+    ("chdir", "filename"),
+    ("mkdir", "pathname"),
+    ("mkdirat", "pathname"),
+    ("unlink", "pathname"),
+    ("unlinkat", "pathname"),
+    ("rmdir", "pathname"),
+    ("readlink", "path"),
+    ("truncate", "path"),
+];
+
+/// Returns the bpftrace script that traces the processes that `options` picks out.
+pub fn script(options: &BpfOptions, own_pid: u32) -> String {
+    // the command and pids are tracked in @traced, by pid, along with their children
+    let tracked = options.scope.is_none();
+    let filter = match &options.scope {
+        None => "@traced[pid]".to_string(),
+        Some(Scope::Cgroup(path)) => format!("cgroup == cgroupid(\"{}\")", path.display()),
+        // not vistrace, nor bpftrace, lest they trace themselves tracing
+        Some(Scope::System) => format!("pid != {} && comm != \"bpftrace\"", own_pid),
+    };
+
+    let mut s = String::from("BEGIN {\n  printf(\"B %llu\\n\", nsecs);\n");
+    if tracked {
+        if !options.command.is_empty() {
+            s.push_str("  @traced[cpid] = 1;\n");
+        }
+        for pid in &options.pids {
+            s.push_str(&format!("  @traced[{}] = 1;\n", pid));
+        }
+        let live = options.pids.len() + usize::from(!options.command.is_empty());
+        s.push_str(&format!("  @live = {};\n", live));
+    }
+    s.push_str("}\n\n");
+
+    if tracked && options.follow {
+        s.push_str(
+            "tracepoint:sched:sched_process_fork /@traced[pid]/ {\n  \
+             @traced[args.child_pid] = 1;\n  \
+             @live++;\n\
+             }\n\n",
+        );
+    }
+
+    for (name, field) in PATHS {
+        s.push_str(&format!(
+            "tracepoint:syscalls:sys_enter_{} /{}/ {{\n  \
+             printf(\"P %d {} %s\\n\", tid, str(args.{}));\n\
+             }}\n\n",
+            name, filter, name, field
+        ));
+    }
+
+    let raw_args: Vec<String> = (0..6).map(|i| format!("args.args[{}]", i)).collect();
+    let saved_args: Vec<String> = (1..7).map(|i| format!("$a.{}", i)).collect();
+    s.push_str(&format!(
+        "tracepoint:raw_syscalls:sys_enter /{}/ {{\n  \
+         @start[tid] = nsecs;\n  \
+         @args[tid] = (args.id, {});\n\
+         }}\n\n",
+        filter,
+        raw_args.join(", ")
+    ));
+    s.push_str(&format!(
+        "tracepoint:raw_syscalls:sys_exit /@start[tid]/ {{\n  \
+         $a = @args[tid];\n  \
+         printf(\"E %d %d %d %llu %llu {}\\n\", tid, $a.0, args.ret, @start[tid], \
+         nsecs - @start[tid], {});\n  \
+         delete(@start[tid]);\n  \
+         delete(@args[tid]);\n\
+         }}\n\n",
+        ["%lu"; 6].join(" "),
+        saved_args.join(", ")
+    ));
+    // exit and exit_group never return
+    s.push_str(&format!(
+        "tracepoint:sched:sched_process_exit /@start[tid]/ {{\n  \
+         $a = @args[tid];\n  \
+         printf(\"E %d %d ? %llu 0 {}\\n\", tid, $a.0, @start[tid], {});\n  \
+         delete(@start[tid]);\n  \
+         delete(@args[tid]);\n\
+         }}\n\n",
+        ["%lu"; 6].join(" "),
+        saved_args.join(", ")
+    ));
+
+    s.push_str(&format!(
+        "tracepoint:signal:signal_deliver /{}/ {{\n  \
+         printf(\"S %d %llu %d %d\\n\", tid, nsecs, args.sig, args.code);\n\
+         }}\n\n",
+        filter
+    ));
+    s.push_str(&format!(
+        "tracepoint:sched:sched_process_exit /{}/ {{\n  \
+         printf(\"X %d %llu %d\\n\", tid, nsecs, curtask->exit_code);\n\
+         }}\n",
+        filter
+    ));
+    // once every traced process has exited, so has the trace, as with strace
+    if tracked {
+        s.push_str(
+            "\ntracepoint:sched:sched_process_exit /@traced[tid]/ {\n  \
+             delete(@traced[tid]);\n  \
+             @live--;\n  \
+             if (@live == 0) {\n    \
+             exit();\n  \
+             }\n\
+             }\n",
+        );
+    }
+    s
+}
+
+pub fn spawn(options: &BpfOptions) -> Result<Child> {
+    let mut cmd = Command::new(&options.bpftrace_path);
+    // like strace, bpftrace runs the command itself, but it splits the command on spaces
+    if let Some(arg) = options
+        .command
+        .iter()
+        .find(|arg| arg.contains(char::is_whitespace))
+    {
+        return Err(anyhow!(
+            "--backend bpf cannot run a command with spaces in an argument: {:?}",
+            arg
+        ));
+    }
+    cmd.args(["-o", "/dev/stderr", "-e"])
+        .arg(script(options, std::process::id()));
+    if !options.command.is_empty() {
+        cmd.arg("-c").arg(options.command.join(" "));
+    }
+    // by default, bpftrace cuts strings off at 64 bytes, which is too short for many paths
+    cmd.env("BPFTRACE_MAX_STRLEN", "200")
+        .stderr(Stdio::piped())
+        .stdout(Stdio::piped());
+    tracing::info!(command = ?cmd, "spawning bpftrace");
+    let child = cmd
+        .spawn()
+        .map_err(|e| anyhow!("unable to spawn bpftrace: {}", e))?;
+    tracing::info!(pid = child.id(), "bpftrace started");
+    Ok(child)
+}
+
+/// Turns lines of the script's output into messages.
+#[derive(Default)]
+pub struct Parser {
+    // syscall names and `%category`s to drop
+    exclude: Vec<String>,
+    // the monotonic time that the script started at, and the time since the epoch that that was, in
+    // microseconds
+    start: Option<(u64, u64)>,
+    // the syscall that each thread is in, and the path that it was called with
+    paths: HashMap<u32, (String, String)>,
+}
+
+impl Parser {
+    pub fn with_exclude(exclude: Vec<String>) -> Parser {
+        Parser {
+            exclude,
+            ..Parser::default()
+        }
+    }
+}
+
+impl Parse for Parser {
+    fn parse_line(&mut self, line: &str) -> Option<Message> {
+        let line = line.trim_end_matches('\n');
+        let mut fields = line.split(' ');
+        let kind = fields.next()?;
+        let parsed = match kind {
+            "B" => {
+                let nsecs: u64 = fields.next()?.parse().ok()?;
+                let now = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map_or(0, |d| d.as_micros() as u64);
+                self.start = Some((nsecs, now));
+                return None;
+            }
+            "P" => {
+                let mut fields = line.splitn(4, ' ').skip(1);
+                let tid: u32 = fields.next()?.parse().ok()?;
+                let name = fields.next()?.to_string();
+                let path = fields.next()?.to_string();
+                self.paths.insert(tid, (name, path));
+                return None;
+            }
+            "E" => self.parse_syscall(fields.collect()),
+            "S" => self.parse_signal(fields.collect()),
+            "X" => self.parse_exit(fields.collect()),
+            _ => None,
+        };
+        match parsed {
+            Some(msg) => msg,
+            // bpftrace's own messages, e.g., errors
+            None if !line.is_empty() && !line.starts_with("Attaching ") => {
+                Some(Message::Notice(line.to_string()))
+            }
+            None => None,
+        }
+    }
+}
+
+impl Parser {
+    // returns `None` if the line is malformed, and `Some(None)` if the syscall is excluded
+    fn parse_syscall(&mut self, fields: Vec<&str>) -> Option<Option<Message>> {
+        let [tid, nr, ret, start, duration, raw @ ..] = fields.as_slice() else {
+            return None;
+        };
+        let tid: u32 = tid.parse().ok()?;
+        let name = syscalls::syscall_name(nr.parse().ok()?);
+        let path = self
+            .paths
+            .remove(&tid)
+            .filter(|(path_of, _)| *path_of == name)
+            .map(|(_, path)| path);
+        if strace::excludes(&self.exclude, &name) {
+            return Some(None);
+        }
+        let raw: Vec<u64> = raw.iter().map(|v| v.parse().ok()).collect::<Option<_>>()?;
+        // exit and exit_group do not return, which strace shows as `= ?`
+        let no_return = *ret == "?";
+        let ret: i64 = if no_return { 0 } else { ret.parse().ok()? };
+
+        let names: Vec<String> = match syscalls::arg_names(&name) {
+            Some(names) => names.iter().map(|n| n.to_string()).collect(),
+            None => (0..raw.len()).map(|i| format!("arg{}", i)).collect(),
+        };
+        let mut path = path;
+        let args = names
+            .iter()
+            .zip(&raw)
+            .map(|(arg_name, value)| SyscallArg {
+                name: String::new(),
+                value: match path.take_if(|_| syscalls::is_path_arg(arg_name)) {
+                    Some(path) => SyscallArgValue::Quoted {
+                        text: strace::escape(path.as_bytes()),
+                        truncated: false,
+                    },
+                    None => syscalls::decode_raw(&name, arg_name, *value),
+                },
+            })
+            .collect();
+        let (return_value, errno) = if (-4095..0).contains(&ret) {
+            (-1, Some(syscalls::errno_name(-ret as i32)))
+        } else {
+            (ret, None)
+        };
+        let duration = duration.parse::<u64>().ok()? / 1000;
+        Some(Some(Message::Syscall(Syscall {
+            pid: Some(tid),
+            name,
+            args,
+            return_value,
+            no_return,
+            errno,
+            returned: None,
+            explanation: None,
+            entry_time_micros: self.time_micros(start.parse().ok()?),
+            // a syscall that never returned has no duration
+            syscall_time_micros: (!no_return).then_some(duration),
+            injected: false,
+            error_details: None,
+        })))
+    }
+
+    fn parse_signal(&self, fields: Vec<&str>) -> Option<Option<Message>> {
+        let [tid, nsecs, sig, code] = fields.as_slice() else {
+            return None;
+        };
+        let name = syscalls::signal_name(sig.parse().ok()?);
+        let mut info = HashMap::new();
+        info.insert(
+            "si_signo".to_string(),
+            SyscallArg {
+                name: "si_signo".to_string(),
+                value: SyscallArgValue::Symbol(name.clone()),
+            },
+        );
+        info.insert(
+            "si_code".to_string(),
+            SyscallArg {
+                name: "si_code".to_string(),
+                value: SyscallArgValue::Number(code.parse().ok()?),
+            },
+        );
+        Some(Some(Message::Signal(Signal {
+            pid: Some(tid.parse().ok()?),
+            time_micros: self.time_micros(nsecs.parse().ok()?),
+            name,
+            info,
+        })))
+    }
+
+    fn parse_exit(&self, fields: Vec<&str>) -> Option<Option<Message>> {
+        let [tid, nsecs, status] = fields.as_slice() else {
+            return None;
+        };
+        let status: i32 = status.parse().ok()?;
+        let kind = if status & 0x7f == 0 {
+            ExitKind::Exited(((status >> 8) & 0xff) as i64)
+        } else {
+            ExitKind::Killed {
+                signal: syscalls::signal_name(status & 0x7f),
+                core_dumped: status & 0x80 != 0,
+            }
+        };
+        Some(Some(Message::Exit(Exit {
+            pid: Some(tid.parse().ok()?),
+            time_micros: self.time_micros(nsecs.parse().ok()?),
+            status: kind,
+        })))
+    }
+
+    // converts monotonic nanoseconds to microseconds since the epoch
+    fn time_micros(&self, nsecs: u64) -> u64 {
+        match self.start {
+            Some((start, epoch_micros)) => {
+                epoch_micros.saturating_add_signed((nsecs as i64 - start as i64) / 1000)
+            }
+            None => 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::strace::{Message, Parse};
+
+    use super::{script, BpfOptions, Parser, Scope};
+
+    #[test]
+    fn test_script() {
+        let mut options = BpfOptions {
+            bpftrace_path: PathBuf::from("bpftrace"),
+            command: Vec::new(),
+            pids: vec![10, 20],
+            scope: None,
+            follow: true,
+        };
+        let s = script(&options, 1);
+        assert!(s.contains("  @traced[10] = 1;\n  @traced[20] = 1;\n  @live = 2;\n"));
+        assert!(s.contains("tracepoint:sched:sched_process_fork /@traced[pid]/"));
+        assert!(s.contains(
+            "tracepoint:syscalls:sys_enter_openat /@traced[pid]/ {\n  \
+             printf(\"P %d openat %s\\n\", tid, str(args.filename));\n}"
+        ));
+        assert!(s.contains("@args[tid] = (args.id, args.args[0], args.args[1], args.args[2], args.args[3], args.args[4], args.args[5]);"));
+        assert!(s.contains("if (@live == 0)"));
+
+        options.scope = Some(Scope::Cgroup(PathBuf::from("/sys/fs/cgroup/a.service")));
+        let s = script(&options, 1);
+        assert!(s.contains("/cgroup == cgroupid(\"/sys/fs/cgroup/a.service\")/"));
+        assert!(!s.contains("@traced"));
+    }
+
+    #[test]
+    fn test_parse() {
+        let mut p = Parser::with_exclude(vec!["%memory".to_string()]);
+        assert!(p.parse_line("Attaching 27 probes...\n").is_none());
+        assert!(p.parse_line("B 1000000000\n").is_none());
+        assert!(p.parse_line("P 7 openat /etc/my hosts\n").is_none());
+
+        let lines = [
+            "E 7 257 3 1000002000 4000 4294967196 140000 524288 0 0 0",
+            "E 7 257 -2 1000010000 1000 4294967196 140000 0 0 0 0",
+            "E 7 9 140000 1000020000 1000 0 4096 3 34 4294967295 0",
+            "S 7 1000030000 17 1",
+            "E 7 231 ? 1000040000 0 3 0 0 0 0 0",
+            "X 7 1000050000 768",
+            "X 8 1000060000 139",
+        ];
+        let printed: Vec<String> = lines
+            .iter()
+            .filter_map(|line| p.parse_line(line))
+            .map(|mut msg| {
+                // without the times, which depend on when the script started
+                match &mut msg {
+                    Message::Syscall(syscall) => syscall.entry_time_micros = 0,
+                    Message::Signal(signal) => signal.time_micros = 0,
+                    Message::Exit(exit) => exit.time_micros = 0,
+                    _ => {}
+                }
+                msg.to_string()
+            })
+            .collect();
+        assert_eq!(
+            printed,
+            [
+                "7 openat(AT_FDCWD, \"/etc/my hosts\", O_RDONLY|O_CLOEXEC, 0) = 3 <0.000004>",
+                "7 openat(AT_FDCWD, 140000, O_RDONLY, 0) = -1 ENOENT <0.000001>",
+                "7 --- SIGCHLD {si_code=1, si_signo=SIGCHLD} ---",
+                "7 exit_group(3) = ?",
+                "7 +++ exited with 3 +++",
+                "8 +++ killed by SIGSEGV (core dumped) +++",
+            ]
+        );
+
+        assert!(matches!(
+            p.parse_line("ERROR: permission denied\n"),
+            Some(Message::Notice(notice)) if notice == "ERROR: permission denied"
+        ));
+    }
+}
@@ -0,0 +1,259 @@
+// Lists what the traced program changed on disk, in the order it did it: the files and directories
+// it created, wrote to, renamed, deleted, linked, truncated, or changed the mode or owner of. For
+// an installer or a script from the internet, this is what it did to the machine.
+//
+// Opening a file for writing counts as a change, even if nothing was written, and O_CREAT counts as
+// creating the file, even if it already existed, since the trace cannot tell.
+
+use std::fmt;
+use std::io::{self, Write};
+
+use crate::fdtable::{self, FdKind, FdTable};
+use crate::strace::{format_timestamp, Syscall, SyscallArgValue};
+use crate::syscalls;
+
+#[derive(Default)]
+pub struct Changes {
+    // in the order they were made
+    pub changes: Vec<Change>,
+}
+
+pub struct Change {
+    pub pid: Option<u32>,
+    pub time_micros: u64,
+    pub path: String,
+    pub kind: Kind,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Kind {
+    Create { truncate: bool },
+    // opened for writing (or appending) without O_CREAT
+    Write { truncate: bool },
+    Truncate { length: i64 },
+    MakeDir,
+    Delete,
+    RemoveDir,
+    RenameTo(String),
+    // `path` is the new link, to the target
+    Symlink(String),
+    Link(String),
+    // e.g., "0755"
+    Chmod(String),
+    // e.g., "1000:-1", where -1 means unchanged
+    Chown(String),
+}
+
+impl Changes {
+    /// Must be called after `fds` has been updated with `syscall`.
+    pub fn update(&mut self, syscall: &Syscall, fds: &FdTable) {
+        if syscall.errno.is_some() || syscall.error_details.is_some() {
+            return;
+        }
+        let arg = |name: &str| {
+            syscalls::arg_index(&syscall.name, name)
+                .and_then(|i| syscall.arg(i))
+                .map(|a| &a.value)
+        };
+        let flag =
+            |name: &str, flag: &str| arg(name).is_some_and(|v| syscalls::value_has_flag(v, flag));
+        let number = |name: &str| match arg(name) {
+            Some(SyscallArgValue::Number(n)) => Some(*n),
+            _ => None,
+        };
+        let path = |name: &str, dirfd: &str| path_arg(syscall, fds, arg(name)?, number(dirfd));
+        // the file that argument `name` is an fd for
+        let fd_path = |name: &str| match fds
+            .resolve(syscall.pid, number(name)?, syscall.entry_time_micros)
+            .kind
+        {
+            FdKind::File(path) => Some(path),
+            _ => None,
+        };
+
+        let (path, kind) = match syscall.name.as_str() {
+            "creat" => (fd_path_of(syscall, fds), Kind::Create { truncate: true }),
+            "open" | "openat" => {
+                let truncate = flag("flags", "O_TRUNC");
+                let kind = if flag("flags", "O_CREAT") {
+                    Kind::Create { truncate }
+                } else if flag("flags", "O_WRONLY") || flag("flags", "O_RDWR") {
+                    Kind::Write { truncate }
+                } else {
+                    return;
+                };
+                (fd_path_of(syscall, fds), kind)
+            }
+            "truncate" => (
+                path("path", ""),
+                Kind::Truncate {
+                    length: number("length").unwrap_or(0),
+                },
+            ),
+            "ftruncate" => (
+                fd_path("fd"),
+                Kind::Truncate {
+                    length: number("length").unwrap_or(0),
+                },
+            ),
+            "mkdir" | "mkdirat" => (path("pathname", "dirfd"), Kind::MakeDir),
+            "rmdir" => (path("pathname", ""), Kind::RemoveDir),
+            "unlink" | "unlinkat" if flag("flags", "AT_REMOVEDIR") => {
+                (path("pathname", "dirfd"), Kind::RemoveDir)
+            }
+            "unlink" | "unlinkat" => (path("pathname", "dirfd"), Kind::Delete),
+            "rename" | "renameat" | "renameat2" => match path("newpath", "newdirfd") {
+                Some(to) => (path("oldpath", "olddirfd"), Kind::RenameTo(to)),
+                None => return,
+            },
+            "symlink" | "symlinkat" => match arg("target") {
+                Some(SyscallArgValue::Quoted { text, .. }) => {
+                    (path("linkpath", "newdirfd"), Kind::Symlink(text.clone()))
+                }
+                _ => return,
+            },
+            "link" | "linkat" => match path("oldpath", "olddirfd") {
+                Some(target) => (path("newpath", "newdirfd"), Kind::Link(target)),
+                None => return,
+            },
+            "chmod" | "fchmod" | "fchmodat" => {
+                let mode = format!("{:04o}", number("mode").unwrap_or(0));
+                let path = match syscall.name.as_str() {
+                    "fchmod" => fd_path("fd"),
+                    _ => path("pathname", "dirfd"),
+                };
+                (path, Kind::Chmod(mode))
+            }
+            "chown" | "lchown" | "fchown" | "fchownat" => {
+                let id = |name: &str| arg(name).map(|v| v.to_string()).unwrap_or_default();
+                let owner = format!("{}:{}", id("owner"), id("group"));
+                let path = match syscall.name.as_str() {
+                    "fchown" => fd_path("fd"),
+                    _ => path("pathname", "dirfd"),
+                };
+                (path, Kind::Chown(owner))
+            }
+            _ => return,
+        };
+        if let Some(path) = path {
+            self.changes.push(Change {
+                pid: syscall.pid,
+                time_micros: syscall.entry_time_micros,
+                path,
+                kind,
+            });
+        }
+    }
+
+    /// Writes one line per change, in order, e.g. "1.000000 10: created /tmp/x".
+    pub fn write(&self, w: &mut impl Write) -> io::Result<()> {
+        for change in &self.changes {
+            writeln!(w, "{}", change)?;
+        }
+        Ok(())
+    }
+}
+
+// the file that an open or creat opened, which `fds` has already resolved to an absolute path
+fn fd_path_of(syscall: &Syscall, fds: &FdTable) -> Option<String> {
+    match fds
+        .resolve(syscall.pid, syscall.return_value, syscall.entry_time_micros)
+        .kind
+    {
+        FdKind::File(path) => Some(path),
+        _ => None,
+    }
+}
+
+// the path in `value`, joined to the directory that `dirfd` refers to or else to the process's
+// working directory if it is relative
+fn path_arg(
+    syscall: &Syscall,
+    fds: &FdTable,
+    value: &SyscallArgValue,
+    dirfd: Option<i64>,
+) -> Option<String> {
+    let path = match value {
+        SyscallArgValue::Quoted { text, .. } => text,
+        _ => return None,
+    };
+    let dir = dirfd.map(|fd| fds.resolve(syscall.pid, fd, syscall.entry_time_micros).kind);
+    Some(match dir {
+        Some(FdKind::File(dir)) if !path.starts_with('/') => fdtable::join(&dir, path),
+        _ => fds.absolute(syscall.pid, path),
+    })
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ", format_timestamp(self.time_micros))?;
+        if let Some(pid) = self.pid {
+            write!(f, "{}: ", pid)?;
+        }
+        let path = &self.path;
+        match &self.kind {
+            Kind::Create { truncate: false } => write!(f, "created {}", path),
+            Kind::Create { truncate: true } => write!(f, "created (or truncated) {}", path),
+            Kind::Write { truncate: false } => write!(f, "opened {} for writing", path),
+            Kind::Write { truncate: true } => write!(f, "truncated {} to write it", path),
+            Kind::Truncate { length } => write!(f, "truncated {} to {} bytes", path, length),
+            Kind::MakeDir => write!(f, "made directory {}", path),
+            Kind::Delete => write!(f, "deleted {}", path),
+            Kind::RemoveDir => write!(f, "removed directory {}", path),
+            Kind::RenameTo(to) => write!(f, "renamed {} to {}", path, to),
+            Kind::Symlink(target) => write!(f, "made symlink {} -> {}", path, target),
+            Kind::Link(target) => write!(f, "made hard link {} to {}", path, target),
+            Kind::Chmod(mode) => write!(f, "changed mode of {} to {}", path, mode),
+            Kind::Chown(owner) => write!(f, "changed owner of {} to {}", path, owner),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fdtable::FdTable;
+    use crate::strace::LineParser;
+
+    use super::Changes;
+
+    #[test]
+    fn test_changes() {
+        let mut changes = Changes::default();
+        let mut fds = FdTable::default();
+        fds.set_cwd(Some(10), "/home/me".to_string());
+        let mut parser = LineParser::default();
+        for line in [
+            "10 1.000000 openat(AT_FDCWD, \"/etc/hosts\", O_RDONLY) = 3",
+            "10 1.000001 openat(AT_FDCWD, \"out.tmp\", O_WRONLY|O_CREAT|O_TRUNC, 0644) = 4",
+            "10 1.000002 fchmod(4, 0755) = 0",
+            "10 1.000003 rename(\"out.tmp\", \"/usr/local/bin/tool\") = 0",
+            "10 1.000004 openat(AT_FDCWD, \"/home/me/.bashrc\", O_WRONLY|O_APPEND) = 5",
+            "10 1.000005 mkdir(\"/opt/tool\", 0755) = 0",
+            "10 1.000006 openat(AT_FDCWD, \"/opt/tool\", O_RDONLY|O_DIRECTORY) = 6",
+            "10 1.000007 unlinkat(6, \"old\", 0) = 0",
+            "10 1.000008 unlinkat(6, \"cache\", AT_REMOVEDIR) = 0",
+            "10 1.000009 symlinkat(\"/opt/tool/bin\", AT_FDCWD, \"/usr/bin/tool\") = 0",
+            "10 1.000010 unlink(\"/etc/missing\") = -1 ENOENT (No such file or directory)",
+            "10 1.000011 fchownat(AT_FDCWD, \"/opt/tool\", 0, 0, 0) = 0",
+        ] {
+            let syscall = parser.parse_line(line).unwrap().into_syscall().unwrap();
+            fds.update(&syscall);
+            changes.update(&syscall, &fds);
+        }
+
+        let mut out = Vec::new();
+        changes.write(&mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "1.000001 10: created (or truncated) /home/me/out.tmp\n\
+             1.000002 10: changed mode of /home/me/out.tmp to 0755\n\
+             1.000003 10: renamed /home/me/out.tmp to /usr/local/bin/tool\n\
+             1.000004 10: opened /home/me/.bashrc for writing\n\
+             1.000005 10: made directory /opt/tool\n\
+             1.000007 10: deleted /opt/tool/old\n\
+             1.000008 10: removed directory /opt/tool/cache\n\
+             1.000009 10: made symlink /usr/bin/tool -> /opt/tool/bin\n\
+             1.000011 10: changed owner of /opt/tool to 0:0\n"
+        );
+    }
+}
@@ -0,0 +1,204 @@
+// Writes a trace in Chrome's Trace Event format, which Perfetto (ui.perfetto.dev) and
+// chrome://tracing can open, so that syscalls can be explored on a timeline alongside other
+// profiling data.
+//
+// Each syscall is a complete ("X") event on the track of the thread that made it, grouped under its
+// process; signals and exits are instant ("i") events. Process and thread names are metadata ("M")
+// events at the end. Times are in microseconds since the Unix epoch, so the trace must have been
+// recorded with timestamps (as vistrace does by default).
+
+use std::io::{self, Write};
+
+use crate::json::Json;
+use crate::processes::ProcessTable;
+use crate::strace::Message;
+use crate::syscalls;
+
+#[derive(Default)]
+pub struct ChromeTrace {
+    processes: ProcessTable,
+    written: usize,
+}
+
+impl ChromeTrace {
+    pub fn write_message(&mut self, w: &mut impl Write, msg: &Message) -> io::Result<()> {
+        let event = match msg {
+            Message::Syscall(syscall) => {
+                self.processes.update(syscall);
+                // e.g., a line that could not be parsed, which would be drawn at the Unix epoch
+                if syscall.entry_time_micros == 0 {
+                    return Ok(());
+                }
+                let categories: Vec<&str> = syscalls::CATEGORIES
+                    .iter()
+                    .copied()
+                    .filter(|c| syscalls::in_category(&syscall.name, c))
+                    .collect();
+                let args: Vec<String> = syscall.args.iter().map(|a| a.to_string()).collect();
+                let mut fields = vec![
+                    ("name", Json::string(&syscall.name)),
+                    ("cat", Json::String(categories.join(","))),
+                    ("ph", Json::string("X")),
+                    ("ts", Json::Number(syscall.entry_time_micros as i64)),
+                    (
+                        "dur",
+                        Json::Number(syscall.syscall_time_micros.unwrap_or(0) as i64),
+                    ),
+                ];
+                fields.extend(self.ids(syscall.pid));
+                fields.push((
+                    "args",
+                    Json::object([
+                        ("args", Json::String(args.join(", "))),
+                        ("return", syscall.result().into()),
+                        ("errno", syscall.errno.as_deref().into()),
+                    ]),
+                ));
+                Json::object(fields)
+            }
+            Message::Signal(signal) => self.instant(&signal.name, signal.pid, signal.time_micros),
+            Message::Exit(exit) => {
+                self.instant(&exit.status.to_string(), exit.pid, exit.time_micros)
+            }
+            Message::LibCall(call) => {
+                let mut fields = vec![
+                    ("name", Json::string(&call.name)),
+                    ("cat", Json::string("libcall")),
+                    ("ph", Json::string("X")),
+                    ("ts", Json::Number(call.time_micros as i64)),
+                    ("dur", Json::Number(call.duration_micros as i64)),
+                ];
+                fields.extend(self.ids(call.pid));
+                fields.push((
+                    "args",
+                    Json::object([
+                        ("args", Json::string(&call.args)),
+                        ("return", Json::string(&call.return_value)),
+                    ]),
+                ));
+                Json::object(fields)
+            }
+            Message::Frame(_) | Message::Skipped(_) | Message::Notice(_) => return Ok(()),
+        };
+        self.write_event(w, event)
+    }
+
+    /// Names the tracks after the processes and threads, and ends the trace.
+    pub fn finish(&mut self, w: &mut impl Write) -> io::Result<()> {
+        let names: Vec<(&str, u32, u32, String)> = self
+            .processes
+            .processes
+            .values()
+            .map(|p| {
+                let kind = if p.is_thread() {
+                    "thread_name"
+                } else {
+                    "process_name"
+                };
+                (kind, p.tgid, p.pid, self.processes.label(p.pid))
+            })
+            .collect();
+        for (kind, pid, tid, label) in names {
+            let event = Json::object([
+                ("name", Json::string(kind)),
+                ("ph", Json::string("M")),
+                ("pid", pid.into()),
+                ("tid", tid.into()),
+                ("args", Json::object([("name", Json::String(label))])),
+            ]);
+            self.write_event(w, event)?;
+        }
+        if self.written == 0 {
+            write!(w, "{{\"traceEvents\":[")?;
+        }
+        writeln!(w, "\n]}}")
+    }
+
+    fn instant(&self, name: &str, pid: Option<u32>, time_micros: u64) -> Json {
+        let mut fields = vec![
+            ("name", Json::string(name)),
+            ("ph", Json::string("i")),
+            // scoped to the thread, so that it is drawn on the thread's track
+            ("s", Json::string("t")),
+            ("ts", Json::Number(time_micros as i64)),
+        ];
+        fields.extend(self.ids(pid));
+        Json::object(fields)
+    }
+
+    // the process and thread IDs of an event (0 if strace did not say, i.e., only one process was
+    // traced)
+    fn ids(&self, pid: Option<u32>) -> [(&'static str, Json); 2] {
+        let tid = pid.unwrap_or(0);
+        let tgid = self.processes.processes.get(&tid).map_or(tid, |p| p.tgid);
+        [("pid", tgid.into()), ("tid", tid.into())]
+    }
+
+    fn write_event(&mut self, w: &mut impl Write, event: Json) -> io::Result<()> {
+        // one event per line, so that the file can be read (and diffed) line by line
+        if self.written == 0 {
+            write!(w, "{{\"traceEvents\":[\n{}", event)?;
+        } else {
+            write!(w, ",\n{}", event)?;
+        }
+        self.written += 1;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::strace::LineParser;
+
+    use super::ChromeTrace;
+
+    #[test]
+    fn test_chrome_trace() {
+        let mut trace = ChromeTrace::default();
+        let mut parser = LineParser::default();
+        let mut out = Vec::new();
+        for line in [
+            "10 1.000000 clone3({flags=CLONE_VM|CLONE_THREAD, exit_signal=0, stack=0x7f00, stack_size=0x1000}, 88) = 11 <0.000020>",
+            "11 1.000100 openat(AT_FDCWD, \"/etc/hosts\", O_RDONLY) = -1 ENOENT (No such file or directory) <0.000010>",
+            "10 1.000200 --- SIGCHLD {si_signo=SIGCHLD, si_code=CLD_EXITED, si_pid=12} ---",
+            "10 1.000300 +++ exited with 0 +++",
+        ] {
+            trace
+                .write_message(&mut out, &parser.parse_line(line).unwrap())
+                .unwrap();
+        }
+        trace.finish(&mut out).unwrap();
+
+        let lines: Vec<&str> = std::str::from_utf8(&out).unwrap().lines().collect();
+        assert_eq!(
+            lines,
+            [
+                r#"{"traceEvents":["#,
+                r#"{"name":"clone3","cat":"process","ph":"X","ts":1000000,"dur":20,"pid":10,"tid":10,"args":{"args":"{exit_signal=0, flags=CLONE_VM|CLONE_THREAD, stack=32512, stack_size=4096}, 88","return":11,"errno":null}},"#,
+                r#"{"name":"openat","cat":"file,desc","ph":"X","ts":1000100,"dur":10,"pid":10,"tid":11,"args":{"args":"AT_FDCWD, \"/etc/hosts\", O_RDONLY","return":-1,"errno":"ENOENT"}},"#,
+                r#"{"name":"SIGCHLD","ph":"i","s":"t","ts":1000200,"pid":10,"tid":10},"#,
+                r#"{"name":"exited with 0","ph":"i","s":"t","ts":1000300,"pid":10,"tid":10},"#,
+                r#"{"name":"process_name","ph":"M","pid":10,"tid":10,"args":{"name":"10"}},"#,
+                r#"{"name":"thread_name","ph":"M","pid":10,"tid":11,"args":{"name":"11 (thread of 10)"}}"#,
+                "]}",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_malformed() {
+        let mut trace = ChromeTrace::default();
+        let mut parser = LineParser::default();
+        let mut out = Vec::new();
+        trace
+            .write_message(
+                &mut out,
+                &parser.parse_line("10 1.000000 getpid((").unwrap(),
+            )
+            .unwrap();
+        trace.finish(&mut out).unwrap();
+        // no event at the Unix epoch
+        let json = String::from_utf8(out).unwrap();
+        assert!(!json.contains("\"getpid\""), "{}", json);
+    }
+}
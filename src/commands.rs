@@ -0,0 +1,256 @@
+// Records every program that the traced processes ran, with its arguments and (if strace was run
+// with -v, which prints the environment instead of its address) its environment, arranged as a
+// tree of which command ran which. For a build system or a shell script, this is the list of what
+// it actually did.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::processes::ProcessTable;
+use crate::strace::{self, Exit, ExitKind, Syscall, SyscallArgValue};
+
+#[derive(Default)]
+pub struct Commands {
+    // in the order they ran
+    pub commands: Vec<Command>,
+    // the most recent command that each process ran, as an index into `commands`
+    latest: HashMap<u32, usize>,
+}
+
+pub struct Command {
+    pub pid: Option<u32>,
+    pub time_micros: u64,
+    pub path: String,
+    // as strace printed them, with escape sequences left as-is
+    pub argv: Vec<String>,
+    // `None` unless strace was run with -v
+    pub env: Option<Vec<String>>,
+    // the command that ran this one (as an index into `commands`): the last one that the same
+    // process ran, or else the last one that its nearest ancestor ran
+    pub parent: Option<usize>,
+    // how the process ended, if it did so without running anything else
+    pub exit: Option<ExitKind>,
+}
+
+impl Commands {
+    /// Must be called after `processes` has been updated with `syscall`.
+    pub fn update(&mut self, syscall: &Syscall, processes: &ProcessTable) {
+        let forks = matches!(syscall.name.as_str(), "clone" | "clone3" | "fork" | "vfork");
+        if forks && syscall.return_value > 0 {
+            self.adopt(syscall.return_value as u32, syscall.pid, processes);
+            return;
+        }
+        let execs = matches!(syscall.name.as_str(), "execve" | "execveat");
+        if !execs || syscall.errno.is_some() || syscall.error_details.is_some() {
+            return;
+        }
+        // execveat has a directory fd in front of the path
+        let offset = usize::from(syscall.name == "execveat");
+        let path = match syscall.arg(offset).map(|a| &a.value) {
+            Some(SyscallArgValue::Quoted { text, .. }) => text.clone(),
+            _ => return,
+        };
+        let strings = |index: usize| match syscall.arg(index).map(|a| &a.value) {
+            Some(SyscallArgValue::Array(values)) => Some(
+                values
+                    .iter()
+                    .filter_map(|v| match &v.value {
+                        SyscallArgValue::Quoted { text, .. } => Some(text.clone()),
+                        _ => None,
+                    })
+                    .collect::<Vec<String>>(),
+            ),
+            _ => None,
+        };
+
+        let parent = self.running(syscall.pid, processes);
+        if let Some(pid) = syscall.pid {
+            self.latest.insert(pid, self.commands.len());
+        }
+        self.commands.push(Command {
+            pid: syscall.pid,
+            time_micros: syscall.entry_time_micros,
+            path,
+            argv: strings(offset + 1).unwrap_or_default(),
+            env: strings(offset + 2),
+            parent,
+            exit: None,
+        });
+    }
+
+    /// The command (as an index into `commands`) that process `pid` is running: the last one that
+    /// it ran, or else the last one that its nearest ancestor ran.
+    pub fn running(&self, pid: Option<u32>, processes: &ProcessTable) -> Option<usize> {
+        let mut ancestor = pid;
+        while let Some(pid) = ancestor {
+            if let Some(index) = self.latest.get(&pid) {
+                return Some(*index);
+            }
+            ancestor = processes.processes.get(&pid).and_then(|p| p.parent);
+        }
+        None
+    }
+
+    // with -f, a child can run a command before the fork returns in its parent, when the command
+    // has no parent yet; it gets the one that the parent is running
+    fn adopt(&mut self, child: u32, parent: Option<u32>, processes: &ProcessTable) {
+        let mut index = match self.latest.get(&child) {
+            Some(index) => *index,
+            None => return,
+        };
+        // the first command in the chain that the child ran
+        while let Some(previous) = self.commands[index].parent {
+            if self.commands[previous].pid != Some(child) {
+                return;
+            }
+            index = previous;
+        }
+        self.commands[index].parent = self.running(parent, processes);
+    }
+
+    pub fn record_exit(&mut self, exit: &Exit) {
+        if let Some(index) = exit.pid.and_then(|pid| self.latest.get(&pid)) {
+            self.commands[*index].exit = Some(exit.status.clone());
+        }
+    }
+
+    /// The commands (as indices into `commands`) in depth-first order, each with its depth in the
+    /// tree.
+    pub fn tree(&self) -> Vec<(usize, usize)> {
+        let mut children: HashMap<Option<usize>, Vec<usize>> = HashMap::new();
+        for (index, command) in self.commands.iter().enumerate() {
+            children.entry(command.parent).or_default().push(index);
+        }
+        let mut tree = Vec::new();
+        let mut stack: Vec<(usize, usize)> = children
+            .get(&None)
+            .map(|roots| roots.iter().rev().map(|index| (0, *index)).collect())
+            .unwrap_or_default();
+        while let Some((depth, index)) = stack.pop() {
+            tree.push((depth, index));
+            if let Some(indices) = children.get(&Some(index)) {
+                stack.extend(indices.iter().rev().map(|child| (depth + 1, *child)));
+            }
+        }
+        tree
+    }
+}
+
+impl fmt::Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(pid) = self.pid {
+            write!(f, "{}: ", pid)?;
+        }
+        // the program is named by its path rather than argv[0], which could be anything
+        write!(f, "{}", quote(&self.path))?;
+        for arg in self.argv.iter().skip(1) {
+            write!(f, " {}", quote(arg))?;
+        }
+        if let Some(exit) = &self.exit {
+            write!(f, "  ({})", exit)?;
+        }
+        Ok(())
+    }
+}
+
+// quotes `word` (as strace printed it) for a shell if it needs to be
+fn quote(word: &str) -> String {
+    shell_quote(&String::from_utf8_lossy(&strace::unescape(word)))
+}
+
+/// Quotes `word` for a shell if it needs to be.
+pub fn shell_quote(word: &str) -> String {
+    let plain = !word.is_empty()
+        && word
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./=:,+@%".contains(c));
+    if plain {
+        word.to_string()
+    } else {
+        format!("'{}'", word.replace('\'', r"'\''"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::processes::ProcessTable;
+    use crate::strace::{LineParser, Message};
+
+    use super::Commands;
+
+    #[test]
+    fn test_commands() {
+        let mut commands = Commands::default();
+        let mut processes = ProcessTable::default();
+        let mut parser = LineParser::default();
+        for line in [
+            "10 execve(\"/usr/bin/make\", [\"make\", \"all\"], 0x7ffc1000 /* 20 vars */) = 0",
+            "10 clone(child_stack=NULL, flags=SIGCHLD) = 11",
+            "11 execve(\"/bin/sh\", [\"sh\", \"-c\", \"cc -c main.c \\\"$1\\\"\"], [\"PATH=/usr/bin\", \"CC=cc\"]) = 0",
+            "11 vfork() = 12",
+            "12 execve(\"/usr/local/bin/cc\", [\"cc\", \"-c\", \"main.c\"], 0x7ffc1000 /* 20 vars */) = -1 ENOENT (No such file or directory)",
+            "12 execve(\"/usr/bin/cc\", [\"cc\", \"-c\", \"main.c\"], 0x7ffc1000 /* 20 vars */) = 0",
+            "12 +++ exited with 1 +++",
+            "10 clone(child_stack=NULL, flags=SIGCHLD) = 13",
+            "13 execve(\"/bin/rm\", [\"rm\", \"-f\", \"main.o\"], 0x7ffc1000 /* 20 vars */) = 0",
+        ] {
+            match parser.parse_line(line).unwrap() {
+                Message::Syscall(syscall) => {
+                    processes.update(&syscall);
+                    commands.update(&syscall, &processes);
+                }
+                Message::Exit(exit) => commands.record_exit(&exit),
+                _ => {}
+            }
+        }
+
+        let tree: Vec<String> = commands
+            .tree()
+            .iter()
+            .map(|(depth, index)| format!("{}{}", "  ".repeat(*depth), commands.commands[*index]))
+            .collect();
+        assert_eq!(
+            tree,
+            [
+                "10: /usr/bin/make all",
+                "  11: /bin/sh -c 'cc -c main.c \"$1\"'",
+                "    12: /usr/bin/cc -c main.c  (exited with 1)",
+                "  13: /bin/rm -f main.o",
+            ]
+        );
+        assert_eq!(
+            commands.commands[1].env.as_deref(),
+            Some(&["PATH=/usr/bin".to_string(), "CC=cc".to_string()][..])
+        );
+        assert_eq!(commands.commands[0].env, None);
+    }
+
+    #[test]
+    fn test_exec_before_fork_returns() {
+        let mut commands = Commands::default();
+        let mut processes = ProcessTable::default();
+        let mut parser = LineParser::default();
+        for line in [
+            "10 execve(\"/usr/bin/make\", [\"make\"], 0x7ffc1000 /* 20 vars */) = 0",
+            "10 clone(child_stack=NULL, flags=SIGCHLD <unfinished ...>",
+            "11 execve(\"/bin/sh\", [\"sh\"], 0x7ffc1000 /* 20 vars */) = 0",
+            "11 execve(\"/bin/true\", [\"true\"], 0x7ffc1000 /* 20 vars */) = 0",
+            "10 <... clone resumed>) = 11",
+        ] {
+            if let Some(syscall) = parser.parse_line(line).and_then(Message::into_syscall) {
+                processes.update(&syscall);
+                commands.update(&syscall, &processes);
+            }
+        }
+
+        let tree: Vec<String> = commands
+            .tree()
+            .iter()
+            .map(|(depth, index)| format!("{}{}", "  ".repeat(*depth), commands.commands[*index]))
+            .collect();
+        assert_eq!(
+            tree,
+            ["10: /usr/bin/make", "  11: /bin/sh", "    11: /bin/true",]
+        );
+    }
+}
@@ -0,0 +1,236 @@
+// Compares the syscalls of two runs (`vistrace stats --compare before.vst after.vst`), e.g. of the
+// builds before and after a performance regression: how many times each syscall was made and how
+// long it took, as percentiles, with the changes that are large enough to matter highlighted.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{self, Write};
+
+use crate::palette::{self, Color};
+use crate::strace::{format_timestamp, Message};
+
+// changes smaller than this are not highlighted
+const THRESHOLD_PERCENT: f64 = 10.0;
+// nor are changes in latency smaller than this, which are within the noise of measuring them
+const THRESHOLD_MICROS: u64 = 5;
+
+#[derive(Default)]
+pub struct Latencies {
+    // how long each call to each syscall took, in microseconds
+    syscalls: BTreeMap<String, Vec<u64>>,
+}
+
+// the statistics for one syscall in one run
+#[derive(Default)]
+struct Row {
+    calls: u64,
+    p50: u64,
+    p99: u64,
+    total_micros: u64,
+}
+
+impl Latencies {
+    pub fn update(&mut self, msg: &Message) {
+        if let Message::Syscall(syscall) = msg {
+            self.syscalls
+                .entry(syscall.name.clone())
+                .or_default()
+                .push(syscall.syscall_time_micros.unwrap_or(0));
+        }
+    }
+
+    fn row(&self, name: &str) -> Row {
+        let mut micros = match self.syscalls.get(name) {
+            Some(micros) => micros.clone(),
+            None => return Row::default(),
+        };
+        micros.sort_unstable();
+        Row {
+            calls: micros.len() as u64,
+            p50: percentile(&micros, 50.0),
+            p99: percentile(&micros, 99.0),
+            total_micros: micros.iter().sum(),
+        }
+    }
+}
+
+/// Writes a table of each syscall's calls, median and 99th-percentile latency, and total time in
+/// `before` and `after`, and the change between them. Syscalls whose total time changed the most
+/// come first. Changes are colored (red for worse, green for better) if `color`.
+pub fn write(
+    w: &mut impl Write,
+    before_title: &str,
+    after_title: &str,
+    before: &Latencies,
+    after: &Latencies,
+    color: bool,
+) -> io::Result<()> {
+    let names: BTreeSet<&String> = before
+        .syscalls
+        .keys()
+        .chain(after.syscalls.keys())
+        .collect();
+    let mut rows: Vec<(&String, Row, Row)> = names
+        .into_iter()
+        .map(|name| (name, before.row(name), after.row(name)))
+        .collect();
+    rows.sort_by_key(|(_, before, after)| {
+        std::cmp::Reverse(before.total_micros.abs_diff(after.total_micros))
+    });
+
+    writeln!(w, "before: {}", before_title)?;
+    writeln!(w, "after:  {}", after_title)?;
+    writeln!(w)?;
+    let groups = format!(
+        "{:<16} {:^26} {:^26} {:^26} {:^32}",
+        "", "calls", "p50 usecs", "p99 usecs", "seconds"
+    );
+    writeln!(w, "{}", groups.trim_end())?;
+    writeln!(
+        w,
+        "{:<16} {:>8} {:>8} {:>8} {:>8} {:>8} {:>8} {:>8} {:>8} {:>8} {:>11} {:>11} {:>8}",
+        "syscall",
+        "before",
+        "after",
+        "change",
+        "before",
+        "after",
+        "change",
+        "before",
+        "after",
+        "change",
+        "before",
+        "after",
+        "change"
+    )?;
+    writeln!(w, "{}", "-".repeat(16 + 3 * 27 + 33))?;
+    for (name, before, after) in &rows {
+        writeln!(
+            w,
+            "{:<16} {:>8} {:>8} {} {:>8} {:>8} {} {:>8} {:>8} {} {:>11} {:>11} {}",
+            name,
+            before.calls,
+            after.calls,
+            change(before.calls, after.calls, 1, color),
+            before.p50,
+            after.p50,
+            change(before.p50, after.p50, THRESHOLD_MICROS, color),
+            before.p99,
+            after.p99,
+            change(before.p99, after.p99, THRESHOLD_MICROS, color),
+            format_timestamp(before.total_micros),
+            format_timestamp(after.total_micros),
+            change(
+                before.total_micros,
+                after.total_micros,
+                THRESHOLD_MICROS,
+                color
+            ),
+        )?;
+    }
+
+    let total = |rows: &[(&String, Row, Row)], f: fn(&(&String, Row, Row)) -> (u64, u64)| {
+        rows.iter()
+            .map(f)
+            .fold((0, 0), |(a, b), (x, y)| (a + x, b + y))
+    };
+    let calls = total(&rows, |(_, before, after)| (before.calls, after.calls));
+    let micros = total(&rows, |(_, before, after)| {
+        (before.total_micros, after.total_micros)
+    });
+    writeln!(w, "{}", "-".repeat(16 + 3 * 27 + 33))?;
+    writeln!(
+        w,
+        "{:<16} {:>8} {:>8} {} {:>53} {:>11} {:>11} {}",
+        "total",
+        calls.0,
+        calls.1,
+        change(calls.0, calls.1, 1, color),
+        "",
+        format_timestamp(micros.0),
+        format_timestamp(micros.1),
+        change(micros.0, micros.1, THRESHOLD_MICROS, color)
+    )
+}
+
+// the nearest-rank percentile of `sorted`
+fn percentile(sorted: &[u64], p: f64) -> u64 {
+    let rank = (p / 100.0 * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+// the change from `before` to `after` as a percentage, highlighted if it is at least
+// `THRESHOLD_PERCENT` and `min_diff`
+fn change(before: u64, after: u64, min_diff: u64, color: bool) -> String {
+    let text = if before == after {
+        String::new()
+    } else if before == 0 {
+        "new".to_string()
+    } else if after == 0 {
+        "gone".to_string()
+    } else {
+        format!(
+            "{:+.1}%",
+            (after as f64 - before as f64) / before as f64 * 100.0
+        )
+    };
+
+    let significant = before.abs_diff(after) >= min_diff
+        && (before == 0
+            || before.abs_diff(after) as f64 / before as f64 * 100.0 >= THRESHOLD_PERCENT);
+    let highlight = if !significant {
+        None
+    } else if after > before {
+        Some(Color::Red)
+    } else {
+        Some(Color::Green)
+    };
+    palette::paint(&format!("{:>8}", text), highlight, color)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::strace::LineParser;
+
+    use super::{percentile, write, Latencies};
+
+    #[test]
+    fn test_compare() {
+        assert_eq!(percentile(&[1, 2, 3, 4], 50.0), 2);
+        assert_eq!(percentile(&[1, 2, 3, 4], 99.0), 4);
+        assert_eq!(percentile(&[7], 50.0), 7);
+
+        let latencies = |lines: &[&str]| {
+            let mut latencies = Latencies::default();
+            let mut parser = LineParser::default();
+            for line in lines {
+                latencies.update(&parser.parse_line(line).unwrap());
+            }
+            latencies
+        };
+        let before = latencies(&[
+            "1.000000 read(3, \"a\", 1) = 1 <0.000010>",
+            "1.000100 read(3, \"a\", 1) = 1 <0.000010>",
+            "1.000200 close(3) = 0 <0.000002>",
+        ]);
+        let after = latencies(&[
+            "2.000000 read(3, \"a\", 1) = 1 <0.000010>",
+            "2.000100 read(3, \"a\", 1) = 1 <0.000030>",
+            "2.000200 fsync(3) = 0 <0.000500>",
+            "2.000300 close(3) = 0 <0.000002>",
+        ]);
+
+        let mut out = Vec::new();
+        write(&mut out, "a.vst", "b.vst", &before, &after, false).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines[0], "before: a.vst");
+        // biggest change in total time first
+        assert!(lines[6].starts_with("fsync"));
+        assert!(lines[6].contains("new"));
+        assert!(lines[7].starts_with("read"));
+        assert!(lines[7].contains("+200.0%"));
+        assert!(lines[8].starts_with("close"));
+        assert!(lines[10].starts_with("total"));
+        assert!(lines[10].contains("+33.3%"));
+    }
+}
@@ -0,0 +1,144 @@
+// Lists the configuration files that a program looked for, and which of them it actually read,
+// going by where config files usually live (/etc, ~/.config, dotfiles) and what they are usually
+// called (*.conf, *.toml, *.yaml, and so on).
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::strace::Syscall;
+use crate::syscalls;
+
+const EXTENSIONS: &[&str] = &["conf", "cfg", "cnf", "ini", "toml", "yaml", "yml", "json"];
+// files under /etc that are not configuration
+const NOT_CONFIG: &[&str] = &["/etc/ld.so.cache"];
+
+#[derive(Default)]
+pub struct ConfigFiles {
+    // in the order they were first looked for
+    pub files: Vec<(String, Status)>,
+    // index into `files`
+    indices: HashMap<String, usize>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Status {
+    Read,
+    // found with stat or access, but not opened
+    Exists,
+    // the error from the last attempt, e.g., "ENOENT"
+    Failed(String),
+}
+
+impl ConfigFiles {
+    pub fn update(&mut self, syscall: &Syscall) {
+        if syscall.error_details.is_some() {
+            return;
+        }
+        let has_flag = |flag: &str| {
+            syscalls::arg_index(&syscall.name, "flags")
+                .and_then(|i| syscall.arg(i))
+                .is_some_and(|a| syscalls::value_has_flag(&a.value, flag))
+        };
+        let status = match (syscall.name.as_str(), &syscall.errno) {
+            (_, Some(errno)) => Status::Failed(errno.clone()),
+            ("open" | "openat" | "openat2", None) => Status::Read,
+            (_, None) => Status::Exists,
+        };
+        match syscall.name.as_str() {
+            "open" | "openat" | "openat2" => {
+                if has_flag("O_WRONLY") || has_flag("O_DIRECTORY") {
+                    return;
+                }
+            }
+            "stat" | "lstat" | "newfstatat" | "statx" | "access" | "faccessat" | "faccessat2" => {}
+            _ => return,
+        }
+        let path = match syscalls::path_args(syscall).into_iter().next() {
+            Some(path) if is_config(&path) => path,
+            _ => return,
+        };
+
+        match self.indices.get(&path) {
+            Some(index) => {
+                let current = &mut self.files[*index].1;
+                // reading a file is what matters, whatever else happened to it
+                let worse = matches!(
+                    (&*current, &status),
+                    (Status::Read, _) | (Status::Exists, Status::Failed(_))
+                );
+                if !worse {
+                    *current = status;
+                }
+            }
+            None => {
+                self.indices.insert(path.clone(), self.files.len());
+                self.files.push((path, status));
+            }
+        }
+    }
+}
+
+fn is_config(path: &str) -> bool {
+    if NOT_CONFIG.contains(&path) {
+        return false;
+    }
+    if path.starts_with("/etc/") || path.contains("/.config/") {
+        return true;
+    }
+    let name = path.rsplit('/').next().unwrap_or(path);
+    // e.g., ~/.bashrc or ~/.gitconfig
+    if name.starts_with('.') && (name.ends_with("rc") || name.ends_with("config")) {
+        return true;
+    }
+    match name.rsplit_once('.') {
+        Some((stem, extension)) => !stem.is_empty() && EXTENSIONS.contains(&extension),
+        None => false,
+    }
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // pad, so that the status can be right-aligned in a column
+        match self {
+            Status::Read => f.pad("read"),
+            Status::Exists => f.pad("exists"),
+            Status::Failed(errno) => f.pad(errno),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::strace::parse_syscall;
+
+    use super::{ConfigFiles, Status};
+
+    #[test]
+    fn test_config_files() {
+        let mut config = ConfigFiles::default();
+        for line in [
+            "openat(AT_FDCWD, \"/home/me/.config/app/config.toml\", O_RDONLY) = -1 ENOENT (No such file or directory)",
+            "access(\"/home/me/.apprc\", R_OK) = 0",
+            "newfstatat(AT_FDCWD, \"/etc/app.conf\", 0x7ffc, 0) = 0",
+            "openat(AT_FDCWD, \"/etc/app.conf\", O_RDONLY|O_CLOEXEC) = 3",
+            "newfstatat(AT_FDCWD, \"/etc/app.conf\", 0x7ffc, 0) = -1 EACCES (Permission denied)",
+            "openat(AT_FDCWD, \"/etc/ld.so.cache\", O_RDONLY|O_CLOEXEC) = 4",
+            "openat(AT_FDCWD, \"data/records.csv\", O_RDONLY) = 5",
+            "openat(AT_FDCWD, \"out.yaml\", O_WRONLY|O_CREAT|O_TRUNC, 0644) = 6",
+        ] {
+            config.update(&parse_syscall(line, false));
+        }
+
+        assert_eq!(
+            config.files,
+            [
+                (
+                    "/home/me/.config/app/config.toml".to_string(),
+                    Status::Failed("ENOENT".to_string())
+                ),
+                ("/home/me/.apprc".to_string(), Status::Exists),
+                ("/etc/app.conf".to_string(), Status::Read),
+            ]
+        );
+    }
+}
@@ -0,0 +1,106 @@
+// What the traced processes wrote to their standard output and error (fds 1 and 2), for the output
+// pane of the interface (see src/layout.rs), so that a program's output can be read alongside the
+// syscalls that produced it.
+//
+// The output is as strace printed it, so a long write is cut off where strace cut it off (see its
+// -s option), which is marked with "...". Only the last MAX_LINES lines are kept.
+
+use std::collections::VecDeque;
+
+use crate::net;
+use crate::strace::{self, Syscall, SyscallArgValue};
+
+const MAX_LINES: usize = 1000;
+
+#[derive(Default)]
+pub struct Console {
+    // complete lines, oldest first
+    pub lines: VecDeque<Line>,
+    // the last line written to stdout and to stderr, if it has no newline yet
+    partial: [String; 2],
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Line {
+    pub stderr: bool,
+    pub text: String,
+}
+
+impl Console {
+    pub fn update(&mut self, syscall: &Syscall) {
+        if !matches!(syscall.name.as_str(), "write" | "writev") || syscall.return_value < 0 {
+            return;
+        }
+        let stderr = match syscall.arg(0).map(|a| &a.value) {
+            Some(SyscallArgValue::Number(1)) => false,
+            Some(SyscallArgValue::Number(2)) => true,
+            _ => return,
+        };
+        for (text, truncated) in net::payloads(syscall) {
+            let mut text = String::from_utf8_lossy(&strace::unescape(text)).into_owned();
+            if truncated {
+                text.push_str("...");
+            }
+            self.write(stderr, &text);
+        }
+    }
+
+    fn write(&mut self, stderr: bool, text: &str) {
+        let mut pieces = text.split('\n');
+        // `split` always yields at least one piece, the rest of the current line
+        let partial = &mut self.partial[stderr as usize];
+        partial.push_str(pieces.next().unwrap_or_default());
+        for piece in pieces {
+            let text = std::mem::replace(&mut self.partial[stderr as usize], piece.to_string());
+            self.lines.push_back(Line { stderr, text });
+            if self.lines.len() > MAX_LINES {
+                self.lines.pop_front();
+            }
+        }
+    }
+
+    /// The lines of output, including any that are not finished yet.
+    pub fn lines(&self) -> impl Iterator<Item = (bool, &str)> {
+        let partial = self
+            .partial
+            .iter()
+            .enumerate()
+            .filter(|(_, text)| !text.is_empty())
+            .map(|(stderr, text)| (stderr == 1, text.as_str()));
+        self.lines
+            .iter()
+            .map(|line| (line.stderr, line.text.as_str()))
+            .chain(partial)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::strace::parse_syscall;
+
+    use super::Console;
+
+    #[test]
+    fn test_console() {
+        let mut console = Console::default();
+        for line in [
+            r#"write(1, "hello, ", 7) = 7"#,
+            r#"write(1, "world\nbye", 9) = 9"#,
+            r#"write(2, "error: no such file\n", 19) = 19"#,
+            r#"writev(1, [{iov_base="\n", iov_len=1}, {iov_base="0123456789"..., iov_len=100}], 2) = 101"#,
+            r#"write(3, "not output\n", 11) = 11"#,
+            r#"write(1, "lost\n", 5) = -1 EPIPE (Broken pipe)"#,
+        ] {
+            console.update(&parse_syscall(line, false));
+        }
+        assert_eq!(
+            console.lines().collect::<Vec<_>>(),
+            [
+                (false, "hello, world"),
+                (true, "error: no such file"),
+                (false, "bye"),
+                (false, "0123456789..."),
+            ]
+        );
+    }
+}
@@ -0,0 +1,350 @@
+// The control socket (`--control-socket PATH`), a unix socket through which other programs (e.g.,
+// an editor plugin or a dashboard) can follow and steer a trace while it runs. Any number of
+// clients can connect. Each sends commands, one per line:
+//
+//   subscribe            send the events from now on, as JSON Lines records (see src/jsonl.rs)
+//   unsubscribe          stop sending them
+//   filter [EXPR]        send only the events that pass a filter, as in the interface (see
+//                        src/filter.rs); with no expression, send every event again
+//   pause                stop the traced processes (with SIGSTOP) until `resume`
+//   resume               continue them
+//   export FORMAT PATH   write the trace so far to PATH, as `vistrace export --format FORMAT` would
+//   status               how many syscalls there have been, and which processes are being traced
+//
+// and gets a response to each as a line of JSON with an "ok" field, e.g. `{"ok":true}` or
+// `{"ok":false,"error":"..."}`. Events are interleaved with the responses, and can be told apart by
+// their "type" field. Once the trace is over, subscribers are sent `{"ok":true,"done":true}`.
+//
+// The socket is removed when vistrace exits. To export the trace, vistrace keeps a copy of it while
+// the socket is open. A subscriber that stops reading for more than a few seconds is disconnected
+// rather than left to hold up the trace.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+
+use crate::filter::Filter;
+use crate::json::Json;
+use crate::jsonl;
+use crate::strace::{self, Message};
+
+// how long a subscriber may hold up the trace before it is disconnected
+const WRITE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Writes the trace so far (as lines of strace output) to a file in an export format, e.g. "jsonl".
+pub type Exporter = Box<dyn Fn(&str, &Path, Vec<String>) -> Result<()> + Send + Sync>;
+
+pub struct Server {
+    path: PathBuf,
+    state: Arc<Mutex<State>>,
+}
+
+struct State {
+    lines: Vec<String>,
+    // how many syscalls there have been, since filters work by the index of each
+    syscalls: usize,
+    // the processes that have made syscalls and not yet exited
+    pids: BTreeSet<u32>,
+    paused: bool,
+    // false if the traced processes are on another machine
+    stoppable: bool,
+    done: bool,
+    clients: BTreeMap<usize, Client>,
+    exporter: Arc<Exporter>,
+}
+
+struct Client {
+    stream: UnixStream,
+    subscribed: bool,
+    filter: Filter,
+}
+
+impl Server {
+    /// Listens at `path`, serving each client that connects on its own thread.
+    pub fn bind(path: &Path, stoppable: bool, exporter: Exporter) -> Result<Arc<Server>> {
+        let listener = UnixListener::bind(path)
+            .map_err(|e| anyhow!("unable to listen on {}: {}", path.display(), e))?;
+        let state = Arc::new(Mutex::new(State {
+            lines: Vec::new(),
+            syscalls: 0,
+            pids: BTreeSet::new(),
+            paused: false,
+            stoppable,
+            done: false,
+            clients: BTreeMap::new(),
+            exporter: Arc::new(exporter),
+        }));
+        let accepting = state.clone();
+        thread::spawn(move || {
+            for (id, stream) in listener.incoming().enumerate() {
+                let Ok(stream) = stream else { continue };
+                let state = accepting.clone();
+                thread::spawn(move || serve(id, stream, state));
+            }
+        });
+        Ok(Arc::new(Server {
+            path: path.to_path_buf(),
+            state,
+        }))
+    }
+
+    /// Records a line of strace output, and sends the message that it completed, if any, to the
+    /// subscribers whose filter it passes.
+    pub fn write(&self, line: &str, msg: Option<&Message>) {
+        let mut state = self.state.lock().unwrap();
+        state.lines.push(line.to_string());
+        let Some(msg) = msg else { return };
+        let index = state.syscalls;
+        match msg {
+            Message::Syscall(syscall) => {
+                state.syscalls += 1;
+                state.pids.extend(syscall.pid);
+            }
+            Message::Exit(exit) => {
+                if let Some(pid) = exit.pid {
+                    state.pids.remove(&pid);
+                }
+            }
+            _ => {}
+        }
+        let Some(json) = jsonl::to_json(msg) else {
+            return;
+        };
+        let record = format!("{}\n", json);
+        state.clients.retain(|_, client| {
+            let passes = match msg {
+                Message::Syscall(syscall) => client.filter.matches(index, syscall),
+                // filters are of syscalls
+                _ => client.filter.is_empty(),
+            };
+            !(client.subscribed && passes) || client.stream.write_all(record.as_bytes()).is_ok()
+        });
+    }
+
+    /// Tells the subscribers that the trace is over.
+    pub fn finish(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.done = true;
+        state.paused = false;
+        let done = format!("{}\n", response([("done", Json::Bool(true))]));
+        for client in state.clients.values_mut().filter(|c| c.subscribed) {
+            let _ = client.stream.write_all(done.as_bytes());
+        }
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+        // so that they are not left stopped after vistrace exits
+        let state = self.state.lock().unwrap();
+        if state.paused {
+            state.pids.iter().for_each(|pid| strace::resume(*pid));
+        }
+    }
+}
+
+fn serve(id: usize, stream: UnixStream, state: Arc<Mutex<State>>) {
+    let Ok(reader) = stream.try_clone() else {
+        return;
+    };
+    let _ = stream.set_write_timeout(Some(WRITE_TIMEOUT));
+    state.lock().unwrap().clients.insert(
+        id,
+        Client {
+            stream,
+            subscribed: false,
+            filter: Filter::default(),
+        },
+    );
+    for line in BufReader::new(reader).lines() {
+        let Ok(line) = line else { break };
+        if line.trim().is_empty() {
+            continue;
+        }
+        let reply = match handle(id, line.trim(), &state) {
+            Ok(reply) => reply,
+            Err(e) => Json::object([
+                ("ok", Json::Bool(false)),
+                ("error", Json::String(e.to_string())),
+            ]),
+        };
+        let mut state = state.lock().unwrap();
+        let Some(client) = state.clients.get_mut(&id) else {
+            return;
+        };
+        if writeln!(client.stream, "{}", reply).is_err() {
+            break;
+        }
+    }
+    state.lock().unwrap().clients.remove(&id);
+}
+
+fn handle(id: usize, command: &str, state: &Mutex<State>) -> Result<Json> {
+    let (command, rest) = command.split_once(' ').unwrap_or((command, ""));
+    let rest = rest.trim();
+    // the export is written without holding up the trace
+    if command == "export" {
+        let (format, path) = rest
+            .split_once(' ')
+            .ok_or_else(|| anyhow!("expected 'export FORMAT PATH'"))?;
+        let (lines, exporter) = {
+            let state = state.lock().unwrap();
+            (state.lines.clone(), state.exporter.clone())
+        };
+        let events = lines.len();
+        exporter(format, Path::new(path.trim()), lines)?;
+        return Ok(response([("lines", Json::Number(events as i64))]));
+    }
+
+    let mut state = state.lock().unwrap();
+    let state = &mut *state;
+    let client = state
+        .clients
+        .get_mut(&id)
+        .ok_or_else(|| anyhow!("client is disconnected"))?;
+    Ok(match command {
+        "subscribe" => {
+            client.subscribed = true;
+            response([])
+        }
+        "unsubscribe" => {
+            client.subscribed = false;
+            response([])
+        }
+        "filter" => {
+            client.filter = Filter::parse(rest)?;
+            response([])
+        }
+        "pause" | "resume" => {
+            if !state.stoppable {
+                return Err(anyhow!("the traced processes are on another machine"));
+            }
+            if state.done {
+                return Err(anyhow!("the trace is over"));
+            }
+            if state.pids.is_empty() {
+                return Err(anyhow!(
+                    "no processes to {} (strace does not print PIDs with --no-follow)",
+                    command
+                ));
+            }
+            state.paused = command == "pause";
+            for pid in &state.pids {
+                if state.paused {
+                    strace::stop(*pid);
+                } else {
+                    strace::resume(*pid);
+                }
+            }
+            response([("pids", pids(&state.pids))])
+        }
+        "status" => response([
+            ("syscalls", Json::Number(state.syscalls as i64)),
+            ("pids", pids(&state.pids)),
+            ("paused", Json::Bool(state.paused)),
+            ("done", Json::Bool(state.done)),
+        ]),
+        _ => {
+            return Err(anyhow!(
+                "unknown command {:?} (expected subscribe, unsubscribe, filter, pause, resume, export, or status)",
+                command
+            ))
+        }
+    })
+}
+
+fn response<const N: usize>(fields: [(&str, Json); N]) -> Json {
+    Json::object([("ok", Json::Bool(true))].into_iter().chain(fields))
+}
+
+fn pids(pids: &BTreeSet<u32>) -> Json {
+    Json::Array(pids.iter().map(|pid| Json::Number(*pid as i64)).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufRead, BufReader, Write};
+    use std::os::unix::net::UnixStream;
+    use std::sync::{Arc, Mutex};
+
+    use crate::strace::LineParser;
+
+    use super::Server;
+
+    #[test]
+    fn test_control() {
+        let path =
+            std::env::temp_dir().join(format!("vistrace-control-{}.sock", std::process::id()));
+        let exported = Arc::new(Mutex::new(Vec::new()));
+        let sink = exported.clone();
+        let server = Server::bind(
+            &path,
+            false,
+            Box::new(move |format, _, lines| {
+                sink.lock().unwrap().push((format.to_string(), lines.len()));
+                Ok(())
+            }),
+        )
+        .unwrap();
+
+        let stream = UnixStream::connect(&path).unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut client = stream;
+        let mut send = |command: &str| {
+            writeln!(client, "{}", command).unwrap();
+            let mut reply = String::new();
+            reader.read_line(&mut reply).unwrap();
+            reply.trim_end().to_string()
+        };
+        assert_eq!(send("subscribe"), r#"{"ok":true}"#);
+        assert_eq!(send("filter failed"), r#"{"ok":true}"#);
+        assert!(send("filter bogus=1").starts_with(r#"{"ok":false,"error":"#));
+        assert!(send("pause").contains("another machine"));
+        assert!(send("frobnicate").contains("unknown command"));
+
+        let mut parser = LineParser::default();
+        for line in [
+            "10 openat(AT_FDCWD, \"/a\", O_RDONLY) = 3\n",
+            "10 openat(AT_FDCWD, \"/b\", O_RDONLY) = -1 ENOENT (No such file or directory)\n",
+            "10 +++ exited with 0 +++\n",
+        ] {
+            server.write(line, parser.parse_line(line).as_ref());
+        }
+        server.finish();
+
+        // only the failed syscall passes the filter
+        let mut event = String::new();
+        reader.read_line(&mut event).unwrap();
+        assert!(event.contains(r#""name":"openat""#) && event.contains(r#""errno":"ENOENT""#));
+        let mut done = String::new();
+        reader.read_line(&mut done).unwrap();
+        assert_eq!(done.trim_end(), r#"{"ok":true,"done":true}"#);
+
+        let stream = UnixStream::connect(&path).unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut client = stream;
+        let mut send = |command: &str| {
+            writeln!(client, "{}", command).unwrap();
+            let mut reply = String::new();
+            reader.read_line(&mut reply).unwrap();
+            reply.trim_end().to_string()
+        };
+        assert_eq!(
+            send("status"),
+            r#"{"ok":true,"syscalls":2,"pids":[],"paused":false,"done":true}"#
+        );
+        assert_eq!(send("export jsonl /dev/null"), r#"{"ok":true,"lines":3}"#);
+        assert_eq!(*exported.lock().unwrap(), [("jsonl".to_string(), 3)]);
+
+        drop(server);
+        assert!(!path.exists());
+    }
+}
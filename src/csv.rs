@@ -0,0 +1,143 @@
+// Writes events as CSV (RFC 4180), one row per syscall, signal, exit, library call, or count of
+// syscalls left out by --sample, for spreadsheets. The columns are a selection of the JSON Lines
+// fields (see src/jsonl.rs), with the arguments joined into one column as strace prints them.
+
+use std::io::{self, Write};
+
+use crate::strace::{ExitKind, Message};
+
+const HEADER: &[&str] = &[
+    "type",
+    "pid",
+    "time_us",
+    "name",
+    "args",
+    "return",
+    "errno",
+    "duration_us",
+];
+
+/// Writes the header row, which must come before any events.
+pub fn write_header(w: &mut impl Write) -> io::Result<()> {
+    write_row(w, HEADER.iter().map(|h| h.to_string()))
+}
+
+pub fn write_message(w: &mut impl Write, msg: &Message) -> io::Result<()> {
+    let pid = |pid: Option<u32>| pid.map(|p| p.to_string()).unwrap_or_default();
+    let micros = |t: u64| if t == 0 { String::new() } else { t.to_string() };
+    let row = match msg {
+        Message::Syscall(syscall) => {
+            let args: Vec<String> = syscall.args.iter().map(|a| a.to_string()).collect();
+            [
+                "syscall".to_string(),
+                pid(syscall.pid),
+                micros(syscall.entry_time_micros),
+                syscall.name.clone(),
+                args.join(", "),
+                syscall.result().map(|r| r.to_string()).unwrap_or_default(),
+                syscall.errno.clone().unwrap_or_default(),
+                syscall
+                    .syscall_time_micros
+                    .map(|t| t.to_string())
+                    .unwrap_or_default(),
+            ]
+        }
+        Message::Signal(signal) => [
+            "signal".to_string(),
+            pid(signal.pid),
+            micros(signal.time_micros),
+            signal.name.clone(),
+            String::new(),
+            String::new(),
+            String::new(),
+            String::new(),
+        ],
+        // the return column holds the exit code
+        Message::Exit(exit) => [
+            "exit".to_string(),
+            pid(exit.pid),
+            micros(exit.time_micros),
+            exit.status.to_string(),
+            String::new(),
+            match exit.status {
+                ExitKind::Exited(code) => code.to_string(),
+                ExitKind::Killed { .. } => String::new(),
+            },
+            String::new(),
+            String::new(),
+        ],
+        Message::LibCall(call) => [
+            "libcall".to_string(),
+            pid(call.pid),
+            micros(call.time_micros),
+            call.name.clone(),
+            call.args.clone(),
+            call.return_value.clone(),
+            String::new(),
+            micros(call.duration_micros),
+        ],
+        // the return column holds the number of calls, and the errno column the number that failed
+        Message::Skipped(skipped) => [
+            "skipped".to_string(),
+            String::new(),
+            String::new(),
+            skipped.name.clone(),
+            String::new(),
+            skipped.calls.to_string(),
+            skipped.errors.to_string(),
+            micros(skipped.total_micros),
+        ],
+        Message::Frame(_) | Message::Notice(_) => return Ok(()),
+    };
+    write_row(w, row)
+}
+
+/// Writes one row, quoting fields as needed.
+pub fn write_row(w: &mut impl Write, fields: impl IntoIterator<Item = String>) -> io::Result<()> {
+    let fields: Vec<String> = fields.into_iter().map(|f| quote(&f)).collect();
+    // CRLF, as the RFC says, which spreadsheets expect
+    write!(w, "{}\r\n", fields.join(","))
+}
+
+fn quote(field: &str) -> String {
+    if field.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::strace::LineParser;
+
+    use super::{write_header, write_message};
+
+    #[test]
+    fn test_csv() {
+        let mut parser = LineParser::default();
+        let mut out = Vec::new();
+        write_header(&mut out).unwrap();
+        for line in [
+            "10 1.000000 openat(AT_FDCWD, \"/etc/hosts\", O_RDONLY) = -1 ENOENT (No such file or directory) <0.000010>",
+            "10 1.000100 getpid() = 10 <0.000001>",
+            "10 1.000200 --- SIGTERM {si_signo=SIGTERM, si_code=SI_USER, si_pid=1, si_uid=0} ---",
+            "10 1.000300 +++ exited with 1 +++",
+        ] {
+            write_message(&mut out, &parser.parse_line(line).unwrap()).unwrap();
+        }
+
+        let text = String::from_utf8(out).unwrap();
+        let rows: Vec<&str> = text.split_terminator("\r\n").collect();
+        assert_eq!(
+            rows,
+            [
+                "type,pid,time_us,name,args,return,errno,duration_us",
+                r#"syscall,10,1000000,openat,"AT_FDCWD, ""/etc/hosts"", O_RDONLY",-1,ENOENT,10"#,
+                "syscall,10,1000100,getpid,,10,,1",
+                "signal,10,1000200,SIGTERM,,,,",
+                "exit,10,1000300,exited with 1,,1,,",
+            ]
+        );
+    }
+}
@@ -0,0 +1,572 @@
+// Aligns two traces so that the differences between them can be displayed side by side, or
+// reported as text (`vistrace diff --report`), e.g. to find where a failing run went differently
+// from a good one.
+//
+// Some values differ between runs even when the program did exactly the same thing: pids, and
+// addresses (e.g., from mmap, or of buffers). These are replaced by placeholders before syscalls
+// are compared, and pids are numbered in the order they first appear in each trace, so that the
+// first process of one trace matches the first process of the other. Only the arguments that are
+// known to be addresses (see `syscalls::is_pointer_arg`) are taken to be addresses, so that, e.g.,
+// a large file offset still counts.
+//
+// Traces that have little in common would take Myers' diff a long time, and a lot of memory, to
+// align, so past `MAX_EDITS` differences the rest is reported as removed from one trace and added
+// in the other.
+
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+use crate::strace::{Syscall, SyscallArg, SyscallArgValue};
+use crate::syscalls;
+
+// the most differences that are aligned, for which Myers' diff keeps O(MAX_EDITS^2) values
+const MAX_EDITS: isize = 1000;
+
+#[derive(Debug, PartialEq)]
+pub enum DiffEntry {
+    // indices are into the left and right traces, respectively
+    Same(usize, usize),
+    Changed(usize, usize),
+    Removed(usize),
+    Added(usize),
+}
+
+// the values in a trace that are expected to differ between runs
+struct Volatile {
+    // the order in which each pid first appeared in the trace
+    pids: HashMap<i64, usize>,
+}
+
+/// Aligns `left` and `right` by syscall name. Aligned syscalls whose arguments or return values
+/// differ, other than in pids and addresses, are reported as changed.
+pub fn diff(left: &[Syscall], right: &[Syscall]) -> Vec<DiffEntry> {
+    let (left_volatile, right_volatile) = (Volatile::new(left), Volatile::new(right));
+    let left_names: Vec<&str> = left.iter().map(|s| s.name.as_str()).collect();
+    let right_names: Vec<&str> = right.iter().map(|s| s.name.as_str()).collect();
+
+    align(&left_names, &right_names)
+        .into_iter()
+        .map(|entry| match entry {
+            DiffEntry::Same(i, j)
+                if left_volatile.render(&left[i]) != right_volatile.render(&right[j]) =>
+            {
+                DiffEntry::Changed(i, j)
+            }
+            entry => entry,
+        })
+        .collect()
+}
+
+/// Renders a syscall without timestamps, which are expected to differ between runs.
+pub fn render(syscall: &Syscall) -> String {
+    let mut r = format!("{}(", syscall.name);
+    for (i, arg) in syscall.args.iter().enumerate() {
+        if i > 0 {
+            r.push_str(", ");
+        }
+        r.push_str(&arg.to_string());
+    }
+    r.push_str(&format!(") = {}", syscall.return_value));
+    if let Some(errno) = &syscall.errno {
+        r.push(' ');
+        r.push_str(errno);
+    }
+    r
+}
+
+/// Writes the differences between `left` and `right` as text, grouped into syscalls that are only
+/// in one trace, syscalls that returned different values, syscalls with different paths, and
+/// syscalls with other differences in their arguments.
+pub fn write_report(
+    w: &mut impl Write,
+    left_title: &str,
+    right_title: &str,
+    left: &[Syscall],
+    right: &[Syscall],
+    entries: &[DiffEntry],
+) -> io::Result<()> {
+    let (left_volatile, right_volatile) = (Volatile::new(left), Volatile::new(right));
+    let mut only_left = Vec::new();
+    let mut only_right = Vec::new();
+    let mut returns = Vec::new();
+    let mut paths = Vec::new();
+    let mut args = Vec::new();
+    for entry in entries {
+        match *entry {
+            DiffEntry::Same(_, _) => {}
+            DiffEntry::Removed(i) => only_left.push(format!("  {}", render(&left[i]))),
+            DiffEntry::Added(j) => only_right.push(format!("  {}", render(&right[j]))),
+            DiffEntry::Changed(i, j) => {
+                let (l, r) = (&left[i], &right[j]);
+                let (l_return, r_return) = (
+                    left_volatile.return_value(l),
+                    right_volatile.return_value(r),
+                );
+                let (l_paths, r_paths) = (syscalls::path_args(l), syscalls::path_args(r));
+                if l_return != r_return {
+                    returns.push(format!(
+                        "  {}: {} in {}, {} in {}",
+                        l.name, l_return, left_title, r_return, right_title
+                    ));
+                }
+                if l_paths != r_paths {
+                    paths.push(format!(
+                        "  {}: {} in {}, {} in {}",
+                        l.name,
+                        quote_all(&l_paths),
+                        left_title,
+                        quote_all(&r_paths),
+                        right_title
+                    ));
+                }
+                if l_return == r_return && l_paths == r_paths {
+                    args.push(format!(
+                        "  {}: {}\n  {}: {}",
+                        left_title,
+                        render(l),
+                        right_title,
+                        render(r)
+                    ));
+                }
+            }
+        }
+    }
+
+    if only_left.len() + only_right.len() + returns.len() + paths.len() + args.len() == 0 {
+        return writeln!(w, "no differences (other than in pids and addresses)");
+    }
+    let sections = [
+        (format!("only in {}", left_title), only_left),
+        (format!("only in {}", right_title), only_right),
+        ("different return values".to_string(), returns),
+        ("different paths".to_string(), paths),
+        ("different arguments".to_string(), args),
+    ];
+    let mut first = true;
+    for (title, lines) in sections {
+        if lines.is_empty() {
+            continue;
+        }
+        if !first {
+            writeln!(w)?;
+        }
+        first = false;
+        writeln!(w, "{} ({}):", title, lines.len())?;
+        for line in lines {
+            writeln!(w, "{}", line)?;
+        }
+    }
+    Ok(())
+}
+
+impl Volatile {
+    fn new(syscalls: &[Syscall]) -> Volatile {
+        let mut pids = HashMap::new();
+        for syscall in syscalls {
+            let forks = matches!(syscall.name.as_str(), "clone" | "clone3" | "fork" | "vfork");
+            let child = (forks && syscall.return_value > 0).then_some(syscall.return_value);
+            for pid in syscall.pid.map(i64::from).into_iter().chain(child) {
+                let next = pids.len();
+                pids.entry(pid).or_insert(next);
+            }
+        }
+        Volatile { pids }
+    }
+
+    // like `render`, but with placeholders for pids and addresses
+    fn render(&self, syscall: &Syscall) -> String {
+        let names = syscalls::arg_names(&syscall.name).unwrap_or_default();
+        let args: Vec<String> = syscall
+            .args
+            .iter()
+            .enumerate()
+            .map(|(i, arg)| {
+                let name = names.get(i).copied().unwrap_or(&arg.name);
+                self.value(&arg.value, syscalls::is_pointer_arg(name))
+            })
+            .collect();
+        format!(
+            "{}({}) = {}",
+            syscall.name,
+            args.join(", "),
+            self.return_value(syscall)
+        )
+    }
+
+    fn return_value(&self, syscall: &Syscall) -> String {
+        let mut r = match syscall.result() {
+            Some(x) => self.number(x, syscalls::returns_pointer(&syscall.name)),
+            None => "?".to_string(),
+        };
+        if let Some(errno) = &syscall.errno {
+            r.push(' ');
+            r.push_str(errno);
+        }
+        r
+    }
+
+    // the elements of an array, or the arguments of a function call, which are pointers if the
+    // value that holds them is
+    fn args(&self, args: &[SyscallArg], pointer: bool) -> String {
+        args.iter()
+            .map(|arg| self.value(&arg.value, pointer))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    fn value(&self, value: &SyscallArgValue, pointer: bool) -> String {
+        match value {
+            SyscallArgValue::Number(x) => self.number(*x, pointer),
+            SyscallArgValue::Array(xs) => format!("[{}]", self.args(xs, pointer)),
+            SyscallArgValue::Struct(fields) => {
+                let mut keys: Vec<&String> = fields.keys().collect();
+                keys.sort();
+                let fields: Vec<String> = keys
+                    .into_iter()
+                    .map(|key| {
+                        let pointer = syscalls::is_pointer_arg(key);
+                        format!("{}={}", key, self.value(&fields[key].value, pointer))
+                    })
+                    .collect();
+                format!("{{{}}}", fields.join(", "))
+            }
+            SyscallArgValue::FunctionCall(name, args) => {
+                format!("{}({})", name, self.args(args, pointer))
+            }
+            value => value.to_string(),
+        }
+    }
+
+    fn number(&self, x: i64, pointer: bool) -> String {
+        match self.pids.get(&x) {
+            // NULL is the same in every run
+            _ if pointer && x != 0 => "<address>".to_string(),
+            Some(i) => format!("<pid {}>", i + 1),
+            None => x.to_string(),
+        }
+    }
+}
+
+fn quote_all(paths: &[String]) -> String {
+    if paths.is_empty() {
+        return "no path".to_string();
+    }
+    paths
+        .iter()
+        .map(|p| format!("\"{}\"", p))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn align<T: PartialEq>(left: &[T], right: &[T]) -> Vec<DiffEntry> {
+    // Myers' diff is quadratic in the number of differences, so trim the common prefix and suffix
+    // (often most of the trace) first
+    let prefix = left
+        .iter()
+        .zip(right.iter())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let suffix = left[prefix..]
+        .iter()
+        .rev()
+        .zip(right[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let mut r: Vec<DiffEntry> = (0..prefix).map(|i| DiffEntry::Same(i, i)).collect();
+    let middle = myers(
+        &left[prefix..left.len() - suffix],
+        &right[prefix..right.len() - suffix],
+    );
+    r.extend(middle.into_iter().map(|entry| match entry {
+        DiffEntry::Same(i, j) => DiffEntry::Same(i + prefix, j + prefix),
+        DiffEntry::Changed(i, j) => DiffEntry::Changed(i + prefix, j + prefix),
+        DiffEntry::Removed(i) => DiffEntry::Removed(i + prefix),
+        DiffEntry::Added(j) => DiffEntry::Added(j + prefix),
+    }));
+    let (left_start, right_start) = (left.len() - suffix, right.len() - suffix);
+    r.extend((0..suffix).map(|i| DiffEntry::Same(left_start + i, right_start + i)));
+    r
+}
+
+// see "An O(ND) Difference Algorithm and Its Variations" (Myers, 1986)
+fn myers<T: PartialEq>(left: &[T], right: &[T]) -> Vec<DiffEntry> {
+    let n = left.len() as isize;
+    let m = right.len() as isize;
+    let max = n + m;
+    let offset = max + 1;
+
+    // v[k + offset] is the furthest x reached on diagonal k
+    let mut v = vec![0isize; (2 * max + 3) as usize];
+    // trace[d] holds v[-d..=d] after round d
+    let mut trace: Vec<Vec<isize>> = Vec::new();
+
+    'outer: for d in 0..=max {
+        if d > MAX_EDITS {
+            return replaced(n as usize, m as usize);
+        }
+        for k in (-d..=d).step_by(2) {
+            let mut x = if k == -d
+                || (k != d && v[(k - 1 + offset) as usize] < v[(k + 1 + offset) as usize])
+            {
+                v[(k + 1 + offset) as usize]
+            } else {
+                v[(k - 1 + offset) as usize] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && left[x as usize] == right[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[(k + offset) as usize] = x;
+
+            if x >= n && y >= m {
+                trace.push(v[(offset - d) as usize..=(offset + d) as usize].to_vec());
+                break 'outer;
+            }
+        }
+        trace.push(v[(offset - d) as usize..=(offset + d) as usize].to_vec());
+    }
+
+    let mut r = Vec::new();
+    let (mut x, mut y) = (n, m);
+    for d in (1..trace.len() as isize).rev() {
+        let prev = &trace[(d - 1) as usize];
+        let get = |k: isize| prev[(k + d - 1) as usize];
+        let k = x - y;
+        let down = k == -d || (k != d && get(k - 1) < get(k + 1));
+        let prev_k = if down { k + 1 } else { k - 1 };
+        let prev_x = get(prev_k);
+        let prev_y = prev_x - prev_k;
+        let (mid_x, mid_y) = if down {
+            (prev_x, prev_y + 1)
+        } else {
+            (prev_x + 1, prev_y)
+        };
+
+        while x > mid_x && y > mid_y {
+            r.push(DiffEntry::Same((x - 1) as usize, (y - 1) as usize));
+            x -= 1;
+            y -= 1;
+        }
+        if down {
+            r.push(DiffEntry::Added(prev_y as usize));
+        } else {
+            r.push(DiffEntry::Removed(prev_x as usize));
+        }
+        x = prev_x;
+        y = prev_y;
+    }
+    while x > 0 && y > 0 {
+        r.push(DiffEntry::Same((x - 1) as usize, (y - 1) as usize));
+        x -= 1;
+        y -= 1;
+    }
+
+    r.reverse();
+    r
+}
+
+// every syscall on the left removed, and every syscall on the right added
+fn replaced(n: usize, m: usize) -> Vec<DiffEntry> {
+    (0..n)
+        .map(DiffEntry::Removed)
+        .chain((0..m).map(DiffEntry::Added))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::strace::{parse_syscall, LineParser};
+
+    use super::{align, diff, write_report, DiffEntry, MAX_EDITS};
+
+    #[test]
+    fn test_align() {
+        let entries = align(&['a', 'b', 'c', 'd'], &['a', 'c', 'd', 'e']);
+        assert_eq!(
+            entries,
+            vec![
+                DiffEntry::Same(0, 0),
+                DiffEntry::Removed(1),
+                DiffEntry::Same(2, 1),
+                DiffEntry::Same(3, 2),
+                DiffEntry::Added(3),
+            ]
+        );
+
+        let entries = align(
+            &['a', 'b', 'c', 'a', 'b', 'b', 'a'],
+            &['c', 'b', 'a', 'b', 'a', 'c'],
+        );
+        let same = entries
+            .iter()
+            .filter(|e| matches!(e, DiffEntry::Same(_, _)))
+            .count();
+        // the longest common subsequence has length 4
+        assert_eq!(same, 4);
+        assert_eq!(entries.len(), 4 + 3 + 2);
+
+        assert_eq!(align::<char>(&[], &[]), vec![]);
+        assert_eq!(align(&['a'], &[]), vec![DiffEntry::Removed(0)]);
+        assert_eq!(align(&[], &['a']), vec![DiffEntry::Added(0)]);
+    }
+
+    #[test]
+    fn test_diff() {
+        let left = vec![
+            parse_syscall(
+                "1.000001 openat(AT_FDCWD, \"/a\", O_RDONLY) = 3 <0.000010>",
+                true,
+            ),
+            parse_syscall("1.000002 read(3, \"abc\", 4096) = 3 <0.000010>", true),
+            parse_syscall("1.000003 close(3) = 0 <0.000010>", true),
+        ];
+        let right = vec![
+            parse_syscall(
+                "2.000001 openat(AT_FDCWD, \"/a\", O_RDONLY) = -1 ENOENT <0.000010>",
+                true,
+            ),
+            parse_syscall("2.000002 write(2, \"error\", 5) = 5 <0.000010>", true),
+        ];
+
+        assert_eq!(
+            diff(&left, &right),
+            vec![
+                DiffEntry::Changed(0, 0),
+                DiffEntry::Removed(1),
+                DiffEntry::Removed(2),
+                DiffEntry::Added(1),
+            ]
+        );
+
+        // timestamps alone do not count as a change
+        assert_eq!(diff(&left[..1], &left[..1]), vec![DiffEntry::Same(0, 0)]);
+
+        let mut out = Vec::new();
+        write_report(&mut out, "good", "bad", &left, &right, &diff(&left, &right)).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "only in good (2):
+  read(3, \"abc\", 4096) = 3
+  close(3) = 0
+
+only in bad (1):
+  write(2, \"error\", 5) = 5
+
+different return values (1):
+  openat: 3 in good, -1 ENOENT in bad
+"
+        );
+    }
+
+    #[test]
+    fn test_volatile() {
+        let parse = |lines: &[&str]| -> Vec<_> {
+            let mut parser = LineParser::default();
+            lines
+                .iter()
+                .filter_map(|line| parser.parse_line(line)?.into_syscall())
+                .collect()
+        };
+        let left = parse(&[
+            "100 mmap(NULL, 8192, PROT_READ, MAP_PRIVATE, 3, 0) = 0x7f1a2b000000",
+            "100 clone(child_stack=NULL, flags=SIGCHLD) = 101",
+            "100 wait4(101, NULL, 0, NULL) = 101",
+            "100 openat(AT_FDCWD, \"/etc/a\", O_RDONLY) = 3",
+        ]);
+        let right = parse(&[
+            "200 mmap(NULL, 8192, PROT_READ, MAP_PRIVATE, 3, 0) = 0x7f99ff000000",
+            "200 clone(child_stack=NULL, flags=SIGCHLD) = 205",
+            "200 wait4(205, NULL, 0, NULL) = 205",
+            "200 openat(AT_FDCWD, \"/etc/b\", O_RDONLY) = 3",
+        ]);
+        assert_eq!(
+            diff(&left, &right),
+            vec![
+                DiffEntry::Same(0, 0),
+                DiffEntry::Same(1, 1),
+                DiffEntry::Same(2, 2),
+                DiffEntry::Changed(3, 3),
+            ]
+        );
+
+        let mut out = Vec::new();
+        write_report(&mut out, "a", "b", &left, &right, &diff(&left, &right)).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "different paths (1):\n  openat: \"/etc/a\" in a, \"/etc/b\" in b\n"
+        );
+    }
+
+    #[test]
+    fn test_align_too_different() {
+        // past MAX_EDITS differences, the middle of the traces is replaced wholesale
+        let n = MAX_EDITS as usize + 1;
+        let left: Vec<char> = "x".repeat(n).chars().chain(['a']).collect();
+        let right: Vec<char> = "y".repeat(n).chars().chain(['a']).collect();
+        let entries = align(&left, &right);
+        assert_eq!(entries.len(), 2 * n + 1);
+        assert!(entries[..n]
+            .iter()
+            .all(|e| matches!(e, DiffEntry::Removed(_))));
+        assert!(entries[n..2 * n]
+            .iter()
+            .all(|e| matches!(e, DiffEntry::Added(_))));
+        assert_eq!(entries[2 * n], DiffEntry::Same(n, n));
+    }
+
+    #[test]
+    fn test_large_numbers_are_not_addresses() {
+        let left = vec![parse_syscall(
+            "lseek(3, 33554432, SEEK_SET) = 33554432",
+            false,
+        )];
+        let right = vec![parse_syscall(
+            "lseek(3, 50331648, SEEK_SET) = 50331648",
+            false,
+        )];
+        assert_eq!(diff(&left, &right), vec![DiffEntry::Changed(0, 0)]);
+    }
+
+    #[test]
+    fn test_pointer_args_are_addresses() {
+        let left = vec![
+            parse_syscall("read(3, 0x7ffd1000, 832) = 832", false),
+            parse_syscall("munmap(0x7f1a2b000000, 8192) = 0", false),
+            parse_syscall("set_tid_address(0x7f1a2b0a0) = 100", false),
+        ];
+        let right = vec![
+            parse_syscall("read(3, 0x7ffe2000, 832) = 832", false),
+            parse_syscall("munmap(0x7f99ff000000, 8192) = 0", false),
+            parse_syscall("set_tid_address(0x7f99ff0a0) = 100", false),
+        ];
+        assert_eq!(
+            diff(&left, &right),
+            vec![
+                DiffEntry::Same(0, 0),
+                DiffEntry::Same(1, 1),
+                DiffEntry::Same(2, 2)
+            ]
+        );
+
+        // but NULL is not just another address
+        let right = vec![parse_syscall("brk(0x55d4a000) = 0x55d4a000", false)];
+        let left = vec![parse_syscall("brk(NULL) = 0x55e10000", false)];
+        assert_eq!(diff(&left, &right), vec![DiffEntry::Changed(0, 0)]);
+    }
+
+    #[test]
+    fn test_diff_empty() {
+        let right = vec![parse_syscall("close(3) = 0", false)];
+        assert_eq!(diff(&[], &[]), vec![]);
+        assert_eq!(diff(&[], &right), vec![DiffEntry::Added(0)]);
+
+        let mut out = Vec::new();
+        write_report(&mut out, "a", "b", &[], &[], &[]).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "no differences (other than in pids and addresses)\n"
+        );
+    }
+}
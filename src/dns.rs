@@ -0,0 +1,263 @@
+// Decodes DNS responses that the tracee receives over UDP, so that vistrace can show which names
+// it resolved to which addresses.
+//
+// strace only prints the first 32 bytes of a buffer by default, which is rarely enough for the
+// answers; pass `--strace-arg=-s1024` to see them.
+
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use crate::fdtable::FdTable;
+use crate::net::{self, Connections, Endpoint};
+use crate::strace::{self, Syscall, SyscallArgValue};
+use crate::syscalls::{self, Io};
+
+const DNS_PORT: u16 = 53;
+const HEADER_LEN: usize = 12;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Resolution {
+    pub pid: Option<u32>,
+    pub time_micros: u64,
+    // the name that was looked up, e.g. "example.com"
+    pub name: String,
+    // e.g., "A" or "AAAA"
+    pub query_type: String,
+    pub addresses: Vec<IpAddr>,
+    // e.g., "NXDOMAIN", if the lookup failed
+    pub error: Option<String>,
+    // the answers were cut off by strace
+    pub truncated: bool,
+}
+
+/// Decodes the DNS responses, if any, that `syscall` received. `fds` and `net` must have already
+/// been updated with `syscall`.
+pub fn decode(syscall: &Syscall, fds: &FdTable, net: &Connections) -> Vec<Resolution> {
+    // recvmmsg returns a count of messages rather than bytes, so it is not in `io_direction`
+    let reads =
+        syscalls::io_direction(&syscall.name) == Some(Io::Read) || syscall.name == "recvmmsg";
+    if syscall.errno.is_some() || !reads {
+        return Vec::new();
+    }
+    let fd = match syscall.arg(0).map(|a| &a.value) {
+        Some(SyscallArgValue::Number(fd)) => *fd,
+        _ => return Vec::new(),
+    };
+    let is_dns = net.find(syscall.pid, fd, fds).is_some_and(|c| {
+        c.protocol != "tcp" && matches!(c.remote, Some(Endpoint::Inet(_, DNS_PORT)))
+    });
+    if !is_dns {
+        return Vec::new();
+    }
+
+    net::payloads(syscall)
+        .into_iter()
+        .filter_map(|(text, truncated)| {
+            let mut resolution = decode_response(&strace::unescape(text), truncated)?;
+            resolution.pid = syscall.pid;
+            resolution.time_micros = syscall.entry_time_micros;
+            Some(resolution)
+        })
+        .collect()
+}
+
+// Returns `None` unless `message` is a DNS response with a well-formed question.
+fn decode_response(message: &[u8], truncated: bool) -> Option<Resolution> {
+    if message.len() < HEADER_LEN {
+        return None;
+    }
+    let u16_at = |i: usize| u16::from_be_bytes([message[i], message[i + 1]]);
+    let flags = u16_at(2);
+    let questions = u16_at(4);
+    let answers = u16_at(6);
+    // only responses to a single question are of interest
+    if flags & 0x8000 == 0 || questions != 1 {
+        return None;
+    }
+
+    let mut reader = Reader {
+        message,
+        index: HEADER_LEN,
+    };
+    let name = reader.name()?;
+    let query_type = reader.u16()?;
+    reader.u16()?;
+
+    let mut resolution = Resolution {
+        pid: None,
+        time_micros: 0,
+        name,
+        query_type: type_name(query_type),
+        addresses: Vec::new(),
+        error: match flags & 0xf {
+            0 => None,
+            1 => Some("FORMERR".to_string()),
+            2 => Some("SERVFAIL".to_string()),
+            3 => Some("NXDOMAIN".to_string()),
+            4 => Some("NOTIMP".to_string()),
+            5 => Some("REFUSED".to_string()),
+            rcode => Some(format!("RCODE{}", rcode)),
+        },
+        truncated: false,
+    };
+    for _ in 0..answers {
+        match reader.answer() {
+            Some(Some(address)) => resolution.addresses.push(address),
+            Some(None) => {}
+            None => {
+                // a malformed answer is only expected if strace cut the message off
+                resolution.truncated = truncated;
+                if !truncated {
+                    return None;
+                }
+                break;
+            }
+        }
+    }
+    Some(resolution)
+}
+
+struct Reader<'a> {
+    message: &'a [u8],
+    index: usize,
+}
+
+impl Reader<'_> {
+    fn u16(&mut self) -> Option<u16> {
+        let bytes = self.bytes(2)?;
+        Some(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn bytes(&mut self, n: usize) -> Option<&[u8]> {
+        let bytes = self.message.get(self.index..self.index + n)?;
+        self.index += n;
+        Some(bytes)
+    }
+
+    // e.g., "example.com", possibly compressed by pointing back to an earlier name
+    fn name(&mut self) -> Option<String> {
+        let mut labels: Vec<String> = Vec::new();
+        let mut index = self.index;
+        let mut jumped = false;
+        // guards against pointer loops
+        for _ in 0..128 {
+            let len = *self.message.get(index)? as usize;
+            if len & 0xc0 == 0xc0 {
+                let pointer = u16::from_be_bytes([len as u8, *self.message.get(index + 1)?]);
+                if !jumped {
+                    self.index = index + 2;
+                    jumped = true;
+                }
+                index = (pointer & 0x3fff) as usize;
+            } else if len == 0 {
+                if !jumped {
+                    self.index = index + 1;
+                }
+                return Some(labels.join("."));
+            } else {
+                let label = self.message.get(index + 1..index + 1 + len)?;
+                labels.push(String::from_utf8_lossy(label).into_owned());
+                index += 1 + len;
+            }
+        }
+        None
+    }
+
+    // returns the address, if the answer is an A or AAAA record
+    fn answer(&mut self) -> Option<Option<IpAddr>> {
+        self.name()?;
+        let record_type = self.u16()?;
+        // class and TTL
+        self.bytes(6)?;
+        let len = self.u16()? as usize;
+        let data = self.bytes(len)?;
+        Some(match (record_type, data.len()) {
+            (1, 4) => Some(IpAddr::V4(Ipv4Addr::new(
+                data[0], data[1], data[2], data[3],
+            ))),
+            (28, 16) => Some(IpAddr::V6(Ipv6Addr::from(<[u8; 16]>::try_from(data).ok()?))),
+            _ => None,
+        })
+    }
+}
+
+fn type_name(query_type: u16) -> String {
+    match query_type {
+        1 => "A".to_string(),
+        5 => "CNAME".to_string(),
+        12 => "PTR".to_string(),
+        15 => "MX".to_string(),
+        16 => "TXT".to_string(),
+        28 => "AAAA".to_string(),
+        33 => "SRV".to_string(),
+        65 => "HTTPS".to_string(),
+        n => format!("TYPE{}", n),
+    }
+}
+
+impl fmt::Display for Resolution {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(error) = &self.error {
+            return write!(f, "failed to resolve {} ({})", self.name, error);
+        }
+        write!(f, "resolved {} → ", self.name)?;
+        if self.addresses.is_empty() {
+            if self.truncated {
+                write!(f, "? (answer cut off; try --strace-arg=-s1024)")
+            } else {
+                write!(f, "no {} records", self.query_type)
+            }
+        } else {
+            let addresses: Vec<String> = self.addresses.iter().map(|a| a.to_string()).collect();
+            write!(f, "{}", addresses.join(", "))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fdtable::FdTable;
+    use crate::net::Connections;
+    use crate::strace::parse_syscall;
+
+    use super::decode;
+
+    #[test]
+    fn test_decode() {
+        let mut fds = FdTable::default();
+        let mut net = Connections::default();
+        let mut resolved = Vec::new();
+        for line in [
+            "socket(AF_INET, SOCK_DGRAM|SOCK_CLOEXEC|SOCK_NONBLOCK, IPPROTO_IP) = 3",
+            r#"connect(3, {sa_family=AF_INET, sin_port=htons(53), sin_addr=inet_addr("127.0.0.53")}, 16) = 0"#,
+            // an A response for example.com with a compressed answer name
+            r#"recvfrom(3, "\22\64\201\200\0\1\0\1\0\0\0\0\7example\3com\0\0\1\0\1\300\f\0\1\0\1\0\0\0<\0\4]\270\330\"", 2048, 0, {sa_family=AF_INET, sin_port=htons(53), sin_addr=inet_addr("127.0.0.53")}, [28 => 16]) = 45"#,
+            // the same response, cut off by strace
+            r#"recvfrom(3, "\22\64\201\200\0\1\0\1\0\0\0\0\7example\3com\0\0\1\0\1\300"..., 2048, 0, NULL, NULL) = 45"#,
+            // NXDOMAIN, received with recvmmsg
+            r#"recvmmsg(3, [{msg_hdr={msg_name=NULL, msg_namelen=0, msg_iov=[{iov_base="\x12\x35\x81\x83\x00\x01\x00\x00\x00\x00\x00\x00\x04nope\x00\x00\x1c\x00\x01", iov_len=2048}], msg_iovlen=1, msg_controllen=0, msg_flags=0}, msg_len=22}], 1, 0, NULL) = 1"#,
+            // not a response
+            r#"read(3, "hello, world", 4096) = 12"#,
+        ] {
+            let syscall = parse_syscall(line, false);
+            fds.update(&syscall);
+            net.update(&syscall, &fds);
+            resolved.extend(decode(&syscall, &fds, &net).iter().map(|r| r.to_string()));
+        }
+        assert_eq!(
+            resolved,
+            [
+                "resolved example.com → 93.184.216.34",
+                "resolved example.com → ? (answer cut off; try --strace-arg=-s1024)",
+                "failed to resolve nope (NXDOMAIN)",
+            ]
+        );
+
+        // responses on other sockets are ignored
+        let syscall = parse_syscall(
+            r#"recvfrom(4, "\22\64\201\200\0\1\0\0\0\0\0\0\7example\3com\0\0\1\0\1", 2048, 0, NULL, NULL) = 29"#,
+            false,
+        );
+        assert!(decode(&syscall, &fds, &net).is_empty());
+    }
+}
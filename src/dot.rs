@@ -0,0 +1,141 @@
+// Draws the process tree as a Graphviz graph (e.g., `dot -Tsvg tree.dot > tree.svg`), with each
+// process's command and how it exited, its threads, and the pipes and sockets that processes used
+// to talk to each other.
+
+use std::io::{self, Write};
+
+use crate::peers::Channels;
+use crate::processes::ProcessTable;
+use crate::strace::ExitKind;
+
+pub fn write_graph(
+    w: &mut impl Write,
+    processes: &ProcessTable,
+    channels: &Channels,
+) -> io::Result<()> {
+    writeln!(w, "digraph processes {{")?;
+    writeln!(w, "  node [shape=box, fontname=\"monospace\"];")?;
+    for process in processes.processes.values() {
+        let mut label = if process.is_thread() {
+            format!("thread {}", process.pid)
+        } else {
+            process.pid.to_string()
+        };
+        if let Some(command) = process.command.as_ref().filter(|_| !process.is_thread()) {
+            label.push_str(&format!("\n{}", command));
+        }
+        if let Some(exit) = &process.exit {
+            label.push_str(&format!("\n{}", exit));
+        }
+        let mut attributes = vec![format!("label={}", quote(&label))];
+        if process.is_thread() {
+            attributes.push("style=dashed".to_string());
+        }
+        if !matches!(process.exit, None | Some(ExitKind::Exited(0))) {
+            attributes.push("color=red".to_string());
+        }
+        writeln!(w, "  {} [{}];", process.pid, attributes.join(", "))?;
+    }
+
+    for process in processes.processes.values() {
+        // a thread hangs off its process, and a process off the process that started it
+        let (parent, style) = if process.is_thread() {
+            (Some(process.tgid), " [style=dashed, arrowhead=none]")
+        } else {
+            (process.parent, "")
+        };
+        if let Some(parent) = parent {
+            writeln!(w, "  {} -> {}{};", parent, process.pid, style)?;
+        }
+    }
+
+    // data that went from a writer at one end of a channel to a reader at the other
+    for channel in &channels.channels {
+        for end in 0..2 {
+            for (writer, sent) in &channel.ends[end] {
+                for (reader, received) in &channel.ends[1 - end] {
+                    if sent.written == 0 || received.read == 0 || reader == writer {
+                        continue;
+                    }
+                    let label = format!("{}\n{} bytes", channel.kind, received.read);
+                    writeln!(
+                        w,
+                        "  {} -> {} [label={}, style=dotted, color=blue, constraint=false];",
+                        writer,
+                        reader,
+                        quote(&label)
+                    )?;
+                }
+            }
+        }
+    }
+    writeln!(w, "}}")
+}
+
+// a double-quoted DOT string
+fn quote(text: &str) -> String {
+    let escaped = text
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n");
+    format!("\"{}\"", escaped)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fdtable::FdTable;
+    use crate::net::Connections;
+    use crate::peers::Channels;
+    use crate::processes::ProcessTable;
+    use crate::strace::{LineParser, Message};
+
+    use super::{quote, write_graph};
+
+    #[test]
+    fn test_graph() {
+        let mut processes = ProcessTable::default();
+        let mut fds = FdTable::default();
+        let net = Connections::default();
+        let mut channels = Channels::default();
+        let mut parser = LineParser::default();
+        for line in [
+            "10 execve(\"/bin/sh\", [\"sh\", \"-c\", \"echo hi | cat\"], 0x7ffc1000 /* 3 vars */) = 0",
+            "10 pipe2([3, 4], 0) = 0",
+            "10 clone(child_stack=NULL, flags=SIGCHLD) = 11",
+            "10 clone(child_stack=NULL, flags=SIGCHLD) = 12",
+            "10 clone3({flags=CLONE_VM|CLONE_THREAD, exit_signal=0, stack=0x7f00, stack_size=0x1000}, 88) = 13",
+            "11 write(4, \"hi\\n\", 3) = 3",
+            "12 read(3, \"hi\\n\", 4096) = 3",
+            "12 +++ killed by SIGPIPE +++",
+        ] {
+            match parser.parse_line(line).unwrap() {
+                Message::Syscall(syscall) => {
+                    processes.update(&syscall);
+                    fds.update(&syscall);
+                    channels.update(&syscall, &fds, &net);
+                }
+                Message::Exit(exit) => processes.record_exit(exit),
+                _ => {}
+            }
+        }
+
+        let mut out = Vec::new();
+        write_graph(&mut out, &processes, &channels).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            r#"digraph processes {
+  node [shape=box, fontname="monospace"];
+  10 [label="10\nsh -c echo hi | cat"];
+  11 [label="11\nsh -c echo hi | cat"];
+  12 [label="12\nsh -c echo hi | cat\nkilled by SIGPIPE", color=red];
+  13 [label="thread 13", style=dashed];
+  10 -> 11;
+  10 -> 12;
+  10 -> 13 [style=dashed, arrowhead=none];
+  11 -> 12 [label="pipe\n3 bytes", style=dotted, color=blue, constraint=false];
+}
+"#
+        );
+        assert_eq!(quote("say \"hi\"\n\\"), r#""say \"hi\"\n\\""#);
+    }
+}
@@ -0,0 +1,248 @@
+// A tracer for macOS (`--backend dtruss`), where there is no strace nor ptrace to speak of: dtruss
+// is a DTrace script that ships with the system. It needs root, and System Integrity Protection
+// stops it from tracing Apple's own binaries (those in /bin, /usr/bin, and so on), so a copy of one
+// has to be traced instead.
+//
+// With -f -d -e, dtruss prints a line like this when a syscall returns, with the time since it
+// started and the time that the syscall took in microseconds, and the errno after the return value:
+//
+//   PID/THRD  RELATIVE  ELAPSD SYSCALL(args)           = return
+//   4120/0x9a0e:      1085      12 open("/etc/hosts\0", 0x0, 0x0)          = 3 0
+//   4120/0x9a0e:      1101       4 stat64("/nope\0", 0x7FF7B5E0, 0x0)         = -1 Err#2
+//
+// It does not say when processes exit nor which signals they get. Its output can also be viewed
+// later, with `vistrace view`.
+
+#[cfg(feature = "native")]
+use std::path::PathBuf;
+#[cfg(feature = "native")]
+use std::process::{Child, Command, Stdio};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[cfg(feature = "native")]
+use anyhow::{anyhow, Result};
+
+use crate::strace::{self, Message, Parse, Syscall, SyscallArg, SyscallArgValue};
+use crate::syscalls;
+
+#[cfg(feature = "native")]
+pub struct DtrussOptions {
+    pub dtruss_path: PathBuf,
+    // program to run under dtruss, if any
+    pub command: Vec<String>,
+    // already-running processes to trace (dtruss can only attach to one)
+    pub pids: Vec<u32>,
+    // trace the children of the command or process as well
+    pub follow: bool,
+}
+
+#[cfg(feature = "native")]
+pub fn spawn(options: &DtrussOptions) -> Result<Child> {
+    let mut cmd = Command::new(&options.dtruss_path);
+    cmd.args(["-d", "-e"]);
+    if options.follow {
+        cmd.arg("-f");
+    }
+    match options.pids.as_slice() {
+        [] => {}
+        [pid] => {
+            cmd.arg("-p").arg(pid.to_string());
+        }
+        _ => return Err(anyhow!("--backend dtruss can only attach to one process")),
+    }
+    // like strace, dtruss runs the command itself, but it splits the command on spaces
+    if let Some(arg) = options
+        .command
+        .iter()
+        .find(|arg| arg.contains(char::is_whitespace))
+    {
+        return Err(anyhow!(
+            "--backend dtruss cannot run a command with spaces in an argument: {:?}",
+            arg
+        ));
+    }
+    cmd.args(&options.command)
+        .stderr(Stdio::piped())
+        .stdout(Stdio::piped());
+    tracing::info!(command = ?cmd, "spawning dtruss");
+    let child = cmd
+        .spawn()
+        .map_err(|e| anyhow!("unable to spawn dtruss: {}", e))?;
+    tracing::info!(pid = child.id(), "dtruss started");
+    Ok(child)
+}
+
+/// Returns true if `line`, the first of a trace, looks like the output of dtruss.
+pub fn is_dtruss(line: &str) -> bool {
+    let line = line.trim_start();
+    line.starts_with("PID/THRD") || line.starts_with("SYSCALL(") || pid_of(line).is_some()
+}
+
+// e.g., 4120 for "4120/0x9a0e:  1085  12 open(...)"
+fn pid_of(line: &str) -> Option<u32> {
+    let (pid, thread) = line.split_once(' ')?.0.strip_suffix(':')?.split_once('/')?;
+    thread.strip_prefix("0x")?;
+    pid.parse().ok()
+}
+
+/// Turns lines of dtruss's output into messages.
+#[derive(Default)]
+pub struct Parser {
+    // syscall names and `%category`s to drop
+    exclude: Vec<String>,
+    // the time since the epoch that dtruss started at, in microseconds
+    start_micros: Option<u64>,
+}
+
+impl Parser {
+    pub fn with_exclude(exclude: Vec<String>) -> Parser {
+        Parser {
+            exclude,
+            ..Parser::default()
+        }
+    }
+
+    // returns `None` if the line is malformed, and `Some(None)` if the syscall is excluded
+    fn parse_syscall(&mut self, line: &str) -> Option<Option<Message>> {
+        let (pid, line) = match pid_of(line) {
+            Some(pid) => (Some(pid), line.split_once(' ')?.1.trim_start()),
+            None => (None, line),
+        };
+        let (relative, line) = line.split_once(' ')?;
+        let (elapsed, call) = line.trim_start().split_once(' ')?;
+        let relative: u64 = relative.parse().ok()?;
+        let elapsed: u64 = elapsed.parse().ok()?;
+        let (call, result) = call.rsplit_once(" = ")?;
+        let (name, args) = call.trim_end().split_once('(')?;
+        let args = args.strip_suffix(')')?;
+
+        let name = syscall_name(name);
+        if strace::excludes(&self.exclude, &name) {
+            return Some(None);
+        }
+        let (return_value, errno) = result.trim().split_once(' ')?;
+        let return_value: i64 = return_value.parse().ok()?;
+        let errno = match errno.strip_prefix("Err#") {
+            Some(errno) => Some(syscalls::bsd_errno_name(errno.parse().ok()?)),
+            None => None,
+        };
+        let args = strace::split_args(args)
+            .into_iter()
+            .map(|arg| {
+                Some(SyscallArg {
+                    name: String::new(),
+                    value: parse_arg(arg)?,
+                })
+            })
+            .collect::<Option<_>>()?;
+
+        // the relative time is of when the syscall returned
+        let start_micros = *self.start_micros.get_or_insert_with(|| {
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_micros() as u64);
+            now.saturating_sub(relative)
+        });
+        Some(Some(Message::Syscall(Syscall {
+            pid,
+            name,
+            args,
+            return_value,
+            no_return: false,
+            errno,
+            returned: None,
+            explanation: None,
+            entry_time_micros: start_micros + relative.saturating_sub(elapsed),
+            syscall_time_micros: Some(elapsed),
+            injected: false,
+            error_details: None,
+        })))
+    }
+}
+
+impl Parse for Parser {
+    fn parse_line(&mut self, line: &str) -> Option<Message> {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with("PID/THRD") || line.starts_with("SYSCALL(") {
+            return None;
+        }
+        match self.parse_syscall(line) {
+            Some(msg) => msg,
+            // e.g., "dtrace: 12 dynamic variable drops"
+            None => Some(Message::Notice(line.to_string())),
+        }
+    }
+}
+
+// the names that strace would use, e.g. "read" rather than "read_nocancel"
+fn syscall_name(name: &str) -> String {
+    let name = name.strip_suffix("_nocancel").unwrap_or(name);
+    match name {
+        "stat64" | "lstat64" | "fstat64" | "fstatat64" | "statfs64" | "fstatfs64" => {
+            name.trim_end_matches("64").to_string()
+        }
+        _ => name.to_string(),
+    }
+}
+
+// e.g., "0x1B6", "-1", or "\"/etc/hosts\\0\""
+fn parse_arg(arg: &str) -> Option<SyscallArgValue> {
+    if let Some(text) = arg.strip_prefix('"').and_then(|s| s.strip_suffix('"')) {
+        // strings are printed with their terminating null byte
+        let text = text.strip_suffix("\\0").unwrap_or(text);
+        return Some(SyscallArgValue::Quoted {
+            text: text.to_string(),
+            truncated: false,
+        });
+    }
+    let number = match arg.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16).ok()? as i64,
+        None => arg.parse().ok()?,
+    };
+    Some(SyscallArgValue::Number(number))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::strace::{Message, Parse};
+
+    use super::Parser;
+
+    #[test]
+    fn test_parse() {
+        let mut p = Parser::with_exclude(vec!["close".to_string()]);
+        let lines = [
+            "\tPID/THRD  RELATIVE  ELAPSD SYSCALL(args) \t\t = return",
+            "4120/0x9a0e:      1085      12 open_nocancel(\"/etc/hosts\\0\", 0x0, 0x1B6)\t\t = 3 0",
+            "4120/0x9a0e:      1101       4 stat64(\"/no, \\\"pe\\\"\\0\", 0x7FF7, 0x0)\t\t = -1 Err#2",
+            "4120/0x9a0e:      1200       9 connect(0x4, 0x7FF7, 0x10)\t\t = -1 Err#61",
+            "4120/0x9a0e:      1300       2 close(0x3)\t\t = 0 0",
+            "4121/0x9a10:      1400      30 write(0x1, \"hi\\n\\0\", 0x3)\t\t = 3 0",
+        ];
+        let printed: Vec<String> = lines
+            .iter()
+            .filter_map(|line| p.parse_line(line))
+            .map(|mut msg| {
+                // without the times, which depend on when dtruss started
+                if let Message::Syscall(syscall) = &mut msg {
+                    syscall.entry_time_micros = 0;
+                }
+                msg.to_string()
+            })
+            .collect();
+        assert_eq!(
+            printed,
+            [
+                "4120 open(\"/etc/hosts\", 0, 438) = 3 <0.000012>",
+                "4120 stat(\"/no, \\\"pe\\\"\", 32759, 0) = -1 ENOENT <0.000004>",
+                "4120 connect(4, 32759, 16) = -1 ECONNREFUSED <0.000009>",
+                "4121 write(1, \"hi\\n\", 3) = 3 <0.000030>",
+            ]
+        );
+
+        assert!(matches!(
+            p.parse_line("dtrace: 12 dynamic variable drops\n"),
+            Some(Message::Notice(notice)) if notice == "dtrace: 12 dynamic variable drops"
+        ));
+    }
+}
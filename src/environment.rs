@@ -0,0 +1,126 @@
+// Compares the environments that programs were run with, with each other or with vistrace's own
+// environment, since "it works in my shell but not under the wrapper" is usually down to an
+// environment variable.
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt;
+
+use crate::strace;
+
+pub type Environment = BTreeMap<String, String>;
+
+#[derive(Debug, PartialEq)]
+pub enum Change {
+    Added {
+        name: String,
+        value: String,
+    },
+    Removed {
+        name: String,
+        value: String,
+    },
+    Changed {
+        name: String,
+        old: String,
+        new: String,
+    },
+}
+
+/// Parses an environment from execve's envp, as strace printed it.
+pub fn from_trace(envp: &[String]) -> Environment {
+    envp.iter()
+        .map(|entry| {
+            let entry = String::from_utf8_lossy(&strace::unescape(entry)).into_owned();
+            match entry.split_once('=') {
+                Some((name, value)) => (name.to_string(), value.to_string()),
+                None => (entry, String::new()),
+            }
+        })
+        .collect()
+}
+
+/// vistrace's own environment, which is the environment of the shell it was run from.
+pub fn current() -> Environment {
+    env::vars_os()
+        .map(|(name, value)| {
+            (
+                name.to_string_lossy().into_owned(),
+                value.to_string_lossy().into_owned(),
+            )
+        })
+        .collect()
+}
+
+/// What changed from `left` to `right`, by variable name.
+pub fn diff(left: &Environment, right: &Environment) -> Vec<Change> {
+    let mut names: Vec<&String> = left.keys().chain(right.keys()).collect();
+    names.sort();
+    names.dedup();
+    names
+        .into_iter()
+        .filter_map(|name| {
+            let old = left.get(name).cloned();
+            let new = right.get(name).cloned();
+            let name = name.clone();
+            match (old, new) {
+                (Some(old), Some(new)) if old != new => Some(Change::Changed { name, old, new }),
+                (Some(value), None) => Some(Change::Removed { name, value }),
+                (None, Some(value)) => Some(Change::Added { name, value }),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Change::Added { name, value } => write!(f, "+ {}={}", name, value),
+            Change::Removed { name, value } => write!(f, "- {}={}", name, value),
+            Change::Changed { name, old, new } => {
+                write!(f, "- {}={}\n+ {}={}", name, old, name, new)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{diff, from_trace, Change};
+
+    #[test]
+    fn test_diff() {
+        let env = |entries: &[&str]| {
+            from_trace(&entries.iter().map(|e| e.to_string()).collect::<Vec<_>>())
+        };
+        let left = env(&["PATH=/usr/bin", "HOME=/root", "LANG=C", "IFS=\\t"]);
+        let right = env(&[
+            "PATH=/opt/bin:/usr/bin",
+            "HOME=/root",
+            "IFS=\\t",
+            "CC=clang",
+        ]);
+
+        let changes = diff(&left, &right);
+        assert_eq!(
+            changes,
+            [
+                Change::Added {
+                    name: "CC".to_string(),
+                    value: "clang".to_string()
+                },
+                Change::Removed {
+                    name: "LANG".to_string(),
+                    value: "C".to_string()
+                },
+                Change::Changed {
+                    name: "PATH".to_string(),
+                    old: "/usr/bin".to_string(),
+                    new: "/opt/bin:/usr/bin".to_string()
+                },
+            ]
+        );
+        assert_eq!(left["IFS"], "\t");
+    }
+}
@@ -0,0 +1,301 @@
+// Follows event loops: which fds a process has registered with epoll (or passed to poll), how often
+// each one was ready, and how much time each thread spent waiting for them.
+//
+// epoll_wait does not say which fd was ready, only the data that was registered along with it, so
+// the data from each epoll_ctl is remembered and mapped back to its fd.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+use crate::fdtable::{FdKind, FdTable};
+use crate::strace::{Syscall, SyscallArgValue};
+use crate::units;
+
+#[derive(Default)]
+pub struct EventLoops {
+    // keyed by fd-table owner
+    pub loops: BTreeMap<Option<u32>, EventLoop>,
+    // the fd that each registration stands for, keyed by owner, epoll fd, and the registered data
+    data: HashMap<(Option<u32>, i64, String), i64>,
+}
+
+#[derive(Default)]
+pub struct EventLoop {
+    // keyed by fd
+    pub interests: BTreeMap<i64, Interest>,
+    // keyed by thread
+    pub waits: BTreeMap<Option<u32>, Waits>,
+}
+
+pub struct Interest {
+    pub fd: i64,
+    // e.g., "EPOLLIN|EPOLLET" or "POLLIN"
+    pub events: String,
+    // `None` if the fd was passed to poll rather than registered with epoll
+    pub epfd: Option<i64>,
+    // what the fd referred to, if it was known
+    pub kind: Option<String>,
+    // false once the fd has been removed from the epoll set or closed
+    pub registered: bool,
+    pub ready: usize,
+}
+
+#[derive(Default)]
+pub struct Waits {
+    pub waits: usize,
+    pub timeouts: usize,
+    pub wait_micros: u64,
+    // the total number of fds that were ready
+    pub ready: usize,
+}
+
+impl EventLoops {
+    /// Must be called after `fds` has been updated with `syscall`.
+    pub fn update(&mut self, syscall: &Syscall, fds: &FdTable) {
+        if syscall.error_details.is_some() {
+            return;
+        }
+        let owner = fds.owner(syscall.pid);
+        match syscall.name.as_str() {
+            "epoll_ctl" => self.update_ctl(syscall, owner, fds),
+            "epoll_wait" | "epoll_pwait" | "epoll_pwait2" => {
+                let epfd = match number(syscall.arg(0).map(|a| &a.value)) {
+                    Some(epfd) => epfd,
+                    None => return,
+                };
+                let mut ready = Vec::new();
+                if let Some(SyscallArgValue::Array(events)) = syscall.arg(1).map(|a| &a.value) {
+                    for event in events {
+                        let data = match &event.value {
+                            SyscallArgValue::Struct(fields) => fields.get("data"),
+                            _ => None,
+                        };
+                        let key = (
+                            owner,
+                            epfd,
+                            data.map(|d| d.value.to_string()).unwrap_or_default(),
+                        );
+                        ready.extend(self.data.get(&key).copied());
+                    }
+                }
+                self.record_wait(syscall, owner, ready);
+            }
+            "poll" | "ppoll" => {
+                if let Some(SyscallArgValue::Array(polled)) = syscall.arg(0).map(|a| &a.value) {
+                    let event_loop = self.loops.entry(owner).or_default();
+                    for entry in polled {
+                        let (fd, events) = match poll_entry(&entry.value, "events") {
+                            Some(entry) => entry,
+                            None => continue,
+                        };
+                        let interest = event_loop
+                            .interests
+                            .entry(fd)
+                            .or_insert_with(|| Interest::new(syscall, fd, fds));
+                        interest.events = events;
+                        interest.epfd = None;
+                        interest.registered = true;
+                    }
+                }
+                let mut ready = Vec::new();
+                if let Some(SyscallArgValue::Array(returned)) = &syscall.returned {
+                    for entry in returned {
+                        ready.extend(poll_entry(&entry.value, "revents").map(|(fd, _)| fd));
+                    }
+                }
+                self.record_wait(syscall, owner, ready);
+            }
+            "close" if syscall.errno.is_none() => {
+                let fd = number(syscall.arg(0).map(|a| &a.value));
+                if let Some(interest) = fd.and_then(|fd| {
+                    self.loops
+                        .get_mut(&owner)
+                        .and_then(|l| l.interests.get_mut(&fd))
+                }) {
+                    interest.registered = false;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn update_ctl(&mut self, syscall: &Syscall, owner: Option<u32>, fds: &FdTable) {
+        if syscall.errno.is_some() {
+            return;
+        }
+        let (epfd, op, fd) = match (
+            number(syscall.arg(0).map(|a| &a.value)),
+            syscall.arg(1).map(|a| &a.value),
+            number(syscall.arg(2).map(|a| &a.value)),
+        ) {
+            (Some(epfd), Some(SyscallArgValue::Symbol(op)), Some(fd)) => (epfd, op.as_str(), fd),
+            _ => return,
+        };
+        let event_loop = self.loops.entry(owner).or_default();
+        if op == "EPOLL_CTL_DEL" {
+            if let Some(interest) = event_loop.interests.get_mut(&fd) {
+                interest.registered = false;
+            }
+            self.data
+                .retain(|(o, e, _), f| !(*o == owner && *e == epfd && *f == fd));
+            return;
+        }
+
+        let fields = match syscall.arg(3).map(|a| &a.value) {
+            Some(SyscallArgValue::Struct(fields)) => fields,
+            _ => return,
+        };
+        let events = fields
+            .get("events")
+            .map(|e| e.value.to_string())
+            .unwrap_or_default();
+        let data = fields
+            .get("data")
+            .map(|d| d.value.to_string())
+            .unwrap_or_default();
+        let interest = event_loop
+            .interests
+            .entry(fd)
+            .or_insert_with(|| Interest::new(syscall, fd, fds));
+        interest.events = events;
+        interest.epfd = Some(epfd);
+        interest.registered = true;
+        self.data.insert((owner, epfd, data), fd);
+    }
+
+    fn record_wait(&mut self, syscall: &Syscall, owner: Option<u32>, ready: Vec<i64>) {
+        let event_loop = self.loops.entry(owner).or_default();
+        let waits = event_loop.waits.entry(syscall.pid).or_default();
+        waits.waits += 1;
+        waits.wait_micros += syscall.syscall_time_micros.unwrap_or(0);
+        if syscall.errno.is_none() && syscall.return_value == 0 {
+            waits.timeouts += 1;
+        }
+        waits.ready += ready.len();
+        for fd in ready {
+            if let Some(interest) = event_loop.interests.get_mut(&fd) {
+                interest.ready += 1;
+            }
+        }
+    }
+}
+
+impl Interest {
+    fn new(syscall: &Syscall, fd: i64, fds: &FdTable) -> Self {
+        let kind = fds.resolve(syscall.pid, fd, syscall.entry_time_micros).kind;
+        Interest {
+            fd,
+            events: String::new(),
+            epfd: None,
+            kind: (kind != FdKind::Unknown).then(|| kind.to_string()),
+            registered: true,
+            ready: 0,
+        }
+    }
+}
+
+// the fd and the events or revents of an entry in poll's array, e.g. {fd=3, events=POLLIN}
+fn poll_entry(value: &SyscallArgValue, events: &str) -> Option<(i64, String)> {
+    let fields = match value {
+        SyscallArgValue::Struct(fields) => fields,
+        _ => return None,
+    };
+    let fd = number(fields.get("fd").map(|a| &a.value))?;
+    let events = fields
+        .get(events)
+        .map(|e| e.value.to_string())
+        .unwrap_or_default();
+    Some((fd, events))
+}
+
+fn number(value: Option<&SyscallArgValue>) -> Option<i64> {
+    match value {
+        Some(SyscallArgValue::Number(n)) => Some(*n),
+        _ => None,
+    }
+}
+
+impl fmt::Display for Interest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "fd {}", self.fd)?;
+        if let Some(kind) = &self.kind {
+            write!(f, " ({})", kind)?;
+        }
+        match self.epfd {
+            Some(epfd) => write!(f, ": {} on epoll fd {}", self.events, epfd)?,
+            None => write!(f, ": {} with poll", self.events)?,
+        }
+        write!(f, ", ready {} times", self.ready)?;
+        if !self.registered {
+            write!(f, " (no longer watched)")?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Waits {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} waits ({} timed out) for {}, {} fds ready",
+            self.waits,
+            self.timeouts,
+            units::duration(self.wait_micros),
+            self.ready
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fdtable::FdTable;
+    use crate::strace::{LineParser, Message};
+
+    use super::EventLoops;
+
+    #[test]
+    fn test_event_loops() {
+        let mut loops = EventLoops::default();
+        let mut fds = FdTable::default();
+        let mut parser = LineParser::default();
+        for line in [
+            "10 1700000000.000001 epoll_create1(EPOLL_CLOEXEC) = 5 <0.000010>",
+            "10 1700000000.000002 socket(AF_INET, SOCK_STREAM, IPPROTO_TCP) = 3 <0.000010>",
+            "10 1700000000.000003 epoll_ctl(5, EPOLL_CTL_ADD, 3, {events=EPOLLIN, data={u32=3, u64=3}}) = 0 <0.000010>",
+            "10 1700000000.000004 epoll_ctl(5, EPOLL_CTL_ADD, 0, {events=EPOLLIN|EPOLLET, data={u32=0, u64=0}}) = 0 <0.000010>",
+            "10 1700000000.000005 epoll_wait(5, [{events=EPOLLIN, data={u32=3, u64=3}}], 16, -1) = 1 <0.250000>",
+            "10 1700000000.300000 epoll_wait(5, [], 16, 100) = 0 <0.100000>",
+            "10 1700000000.500000 epoll_ctl(5, EPOLL_CTL_DEL, 0, NULL) = 0 <0.000010>",
+            "11 1700000000.600000 poll([{fd=7, events=POLLIN}, {fd=8, events=POLLOUT}], 2, -1) = 1 ([{fd=8, revents=POLLOUT}]) <0.000100>",
+        ] {
+            let syscall = parser
+                .parse_line(line)
+                .and_then(Message::into_syscall)
+                .unwrap();
+            fds.update(&syscall);
+            loops.update(&syscall, &fds);
+        }
+
+        let event_loop = &loops.loops[&Some(10)];
+        let interests: Vec<String> = event_loop
+            .interests
+            .values()
+            .map(|i| i.to_string())
+            .collect();
+        assert_eq!(
+            interests,
+            [
+                "fd 0: EPOLLIN|EPOLLET on epoll fd 5, ready 0 times (no longer watched)",
+                "fd 3 (socket (AF_INET, SOCK_STREAM)): EPOLLIN on epoll fd 5, ready 1 times",
+            ]
+        );
+        assert_eq!(
+            event_loop.waits[&Some(10)].to_string(),
+            "2 waits (1 timed out) for 350 ms, 1 fds ready"
+        );
+
+        let event_loop = &loops.loops[&Some(11)];
+        assert_eq!(event_loop.interests[&8].ready, 1);
+        assert_eq!(event_loop.interests[&7].ready, 0);
+    }
+}
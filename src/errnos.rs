@@ -0,0 +1,282 @@
+// What an errno means, for the detail pane of the interface: its number, the text that strerror
+// gives for it, and a hint at the usual cause when a given syscall fails with it, e.g. for openat
+// failing with EACCES:
+//
+//   error: EACCES (13), Permission denied
+//   hint: check the file's permissions, and that each directory above it is searchable (has its
+//         x bit) by the process's user
+//
+// The numbers and texts are Linux's (and glibc's), which vistrace knows without libc, so that they
+// are the same wherever the trace is viewed. Traces from macOS and FreeBSD name their errnos the
+// same way, but number some of them differently.
+
+use crate::syscalls;
+
+/// e.g., `(13, "EACCES", "Permission denied")`
+const ERRNOS: &[(i32, &str, &str)] = &[
+    (1, "EPERM", "Operation not permitted"),
+    (2, "ENOENT", "No such file or directory"),
+    (3, "ESRCH", "No such process"),
+    (4, "EINTR", "Interrupted system call"),
+    (5, "EIO", "Input/output error"),
+    (6, "ENXIO", "No such device or address"),
+    (7, "E2BIG", "Argument list too long"),
+    (8, "ENOEXEC", "Exec format error"),
+    (9, "EBADF", "Bad file descriptor"),
+    (10, "ECHILD", "No child processes"),
+    (11, "EAGAIN", "Resource temporarily unavailable"),
+    (12, "ENOMEM", "Cannot allocate memory"),
+    (13, "EACCES", "Permission denied"),
+    (14, "EFAULT", "Bad address"),
+    (15, "ENOTBLK", "Block device required"),
+    (16, "EBUSY", "Device or resource busy"),
+    (17, "EEXIST", "File exists"),
+    (18, "EXDEV", "Invalid cross-device link"),
+    (19, "ENODEV", "No such device"),
+    (20, "ENOTDIR", "Not a directory"),
+    (21, "EISDIR", "Is a directory"),
+    (22, "EINVAL", "Invalid argument"),
+    (23, "ENFILE", "Too many open files in system"),
+    (24, "EMFILE", "Too many open files"),
+    (25, "ENOTTY", "Inappropriate ioctl for device"),
+    (26, "ETXTBSY", "Text file busy"),
+    (27, "EFBIG", "File too large"),
+    (28, "ENOSPC", "No space left on device"),
+    (29, "ESPIPE", "Illegal seek"),
+    (30, "EROFS", "Read-only file system"),
+    (31, "EMLINK", "Too many links"),
+    (32, "EPIPE", "Broken pipe"),
+    (33, "EDOM", "Numerical argument out of domain"),
+    (34, "ERANGE", "Numerical result out of range"),
+    (35, "EDEADLK", "Resource deadlock avoided"),
+    (36, "ENAMETOOLONG", "File name too long"),
+    (37, "ENOLCK", "No locks available"),
+    (38, "ENOSYS", "Function not implemented"),
+    (39, "ENOTEMPTY", "Directory not empty"),
+    (40, "ELOOP", "Too many levels of symbolic links"),
+    (42, "ENOMSG", "No message of desired type"),
+    (61, "ENODATA", "No data available"),
+    (62, "ETIME", "Timer expired"),
+    (71, "EPROTO", "Protocol error"),
+    (75, "EOVERFLOW", "Value too large for defined data type"),
+    (
+        84,
+        "EILSEQ",
+        "Invalid or incomplete multibyte or wide character",
+    ),
+    (88, "ENOTSOCK", "Socket operation on non-socket"),
+    (89, "EDESTADDRREQ", "Destination address required"),
+    (90, "EMSGSIZE", "Message too long"),
+    (91, "EPROTOTYPE", "Protocol wrong type for socket"),
+    (92, "ENOPROTOOPT", "Protocol not available"),
+    (93, "EPROTONOSUPPORT", "Protocol not supported"),
+    (95, "EOPNOTSUPP", "Operation not supported"),
+    (
+        97,
+        "EAFNOSUPPORT",
+        "Address family not supported by protocol",
+    ),
+    (98, "EADDRINUSE", "Address already in use"),
+    (99, "EADDRNOTAVAIL", "Cannot assign requested address"),
+    (100, "ENETDOWN", "Network is down"),
+    (101, "ENETUNREACH", "Network is unreachable"),
+    (103, "ECONNABORTED", "Software caused connection abort"),
+    (104, "ECONNRESET", "Connection reset by peer"),
+    (105, "ENOBUFS", "No buffer space available"),
+    (106, "EISCONN", "Transport endpoint is already connected"),
+    (107, "ENOTCONN", "Transport endpoint is not connected"),
+    (110, "ETIMEDOUT", "Connection timed out"),
+    (111, "ECONNREFUSED", "Connection refused"),
+    (113, "EHOSTUNREACH", "No route to host"),
+    (114, "EALREADY", "Operation already in progress"),
+    (115, "EINPROGRESS", "Operation now in progress"),
+    (116, "ESTALE", "Stale file handle"),
+    (122, "EDQUOT", "Disk quota exceeded"),
+    (125, "ECANCELED", "Operation canceled"),
+    // errnos that only the kernel sees, but a tracer does too
+    (
+        512,
+        "ERESTARTSYS",
+        "Interrupted by a signal, to be restarted",
+    ),
+    (
+        513,
+        "ERESTARTNOINTR",
+        "Interrupted by a signal, to be restarted",
+    ),
+    (
+        514,
+        "ERESTARTNOHAND",
+        "Interrupted by a signal, to be restarted if no handler ran",
+    ),
+    (
+        516,
+        "ERESTART_RESTARTBLOCK",
+        "Interrupted by a signal, to be restarted",
+    ),
+];
+
+/// The number and strerror text of the errno named `name`, e.g. `(2, "No such file or
+/// directory")` for "ENOENT".
+pub fn lookup(name: &str) -> Option<(i32, &'static str)> {
+    // the aliases of errnos that glibc names differently
+    let name = match name {
+        "EWOULDBLOCK" => "EAGAIN",
+        "EDEADLOCK" => "EDEADLK",
+        "ENOTSUP" => "EOPNOTSUPP",
+        name => name,
+    };
+    ERRNOS
+        .iter()
+        .find(|(_, n, _)| *n == name)
+        .map(|(number, _, text)| (*number, *text))
+}
+
+/// What usually makes `syscall` fail with the errno named `errno`, if there is something more to
+/// say than the errno's text.
+pub fn hint(syscall: &str, errno: &str) -> Option<&'static str> {
+    let takes_path = syscalls::arg_names(syscall)
+        .is_some_and(|names| names.iter().any(|name| syscalls::is_path_arg(name)));
+    let is_io = syscalls::io_direction(syscall).is_some();
+    // the errnos of restarted syscalls mean that a signal interrupted them, as EINTR does
+    let errno = if errno.starts_with("ERESTART") {
+        "EINTR"
+    } else {
+        errno
+    };
+    let hint = match (errno, syscall) {
+        ("EACCES", "execve" | "execveat") => {
+            "the program must be executable (have its x bit) by the process's user, and not be on \
+             a file system mounted noexec"
+        }
+        ("EACCES", _) if takes_path => {
+            "check the file's permissions, and that each directory above it is searchable (has its \
+             x bit) by the process's user"
+        }
+        ("EACCES" | "EPERM", "connect" | "sendto" | "bind") => {
+            "a firewall rule forbids the connection, or binding to a port below 1024 needs root or \
+             CAP_NET_BIND_SERVICE"
+        }
+        ("EPERM", "kill") => {
+            "the process belongs to another user, and sending it a signal needs root"
+        }
+        ("EPERM", _) if takes_path => {
+            "the process lacks a privilege the operation needs (e.g., root, or owning the file), \
+             or the file is immutable or append-only (see lsattr)"
+        }
+        ("EPERM", _) => {
+            "the process lacks a privilege the operation needs (e.g., root or a capability), or a \
+             seccomp filter or security module (SELinux, AppArmor) denied it"
+        }
+        ("ENOENT", "execve" | "execveat") => {
+            "the program does not exist, or neither does its interpreter (the #! line of a script, \
+             or the dynamic loader of a binary built for another system)"
+        }
+        ("ENOENT", _) if takes_path => {
+            "the file, or a directory above it, does not exist; a relative path starts from the \
+             working directory. Programs often look in several places, so this may be expected"
+        }
+        ("EEXIST", _) => {
+            "something is already at that path (e.g., O_CREAT|O_EXCL or mkdir was used to make \
+             sure that nothing was)"
+        }
+        ("ENOTDIR", _) => "a part of the path that should be a directory is a file",
+        ("EISDIR", _) => "the path is a directory, which can only be opened for reading",
+        ("ELOOP", _) => {
+            "the path has too many symlinks or a loop of them, or O_NOFOLLOW was used on a symlink"
+        }
+        ("ENOEXEC", _) => {
+            "the file is not something the kernel can run, e.g. a script with no #! line, or a \
+             binary for another architecture"
+        }
+        ("E2BIG", _) => "the arguments and environment together are too large",
+        ("ETXTBSY", _) => "the file is a program that is running, so it cannot be written to",
+        ("EXDEV", _) => {
+            "the paths are on different file systems, which rename cannot move between, so \
+             programs copy and delete instead"
+        }
+        ("ENOTEMPTY", _) => "the directory still has files in it",
+        ("EROFS", _) => "the file system is mounted read-only",
+        ("ENOSPC", _) => "the file system is out of space, or of inodes (see df -i)",
+        ("EDQUOT", _) => "the user has used up their disk quota",
+        ("EBADF", _) => {
+            "the fd is not open (it may have been closed already, perhaps by another thread), or \
+             is not open for this kind of access, e.g. writing to an fd opened read-only"
+        }
+        ("EMFILE", _) => {
+            "the process has as many fds open as its limit allows (see ulimit -n); look for fds \
+             that are opened and never closed"
+        }
+        ("ENFILE", _) => "the whole system has as many files open as it allows (see fs.file-max)",
+        ("EAGAIN", _) if is_io || matches!(syscall, "accept" | "accept4") => {
+            "the fd is non-blocking and nothing is ready yet, which is normal for programs that \
+             wait with poll, select, or epoll"
+        }
+        ("EAGAIN", "clone" | "clone3" | "fork" | "vfork") => {
+            "the user has as many processes as they may (see ulimit -u), or the cgroup's pids \
+             limit was reached"
+        }
+        ("EINTR", _) => "a signal arrived while the syscall waited; programs usually try again",
+        ("EPIPE", _) => {
+            "the other end of the pipe or socket was closed; the process is also sent SIGPIPE, \
+             unless it ignores it"
+        }
+        ("ECONNREFUSED", _) => "nothing is listening at that address and port",
+        ("EINPROGRESS", "connect") => {
+            "the socket is non-blocking, so the connection is made in the background; look for the \
+             poll or epoll_wait that waits for it"
+        }
+        ("EADDRINUSE", _) => {
+            "another socket is already bound to that address and port; see what else is running, \
+             or SO_REUSEADDR"
+        }
+        ("EADDRNOTAVAIL", _) => "the address is not one of this machine's",
+        ("ETIMEDOUT", _) => "the other end did not answer in time",
+        ("ENETUNREACH" | "EHOSTUNREACH", _) => {
+            "there is no route to that address from this machine"
+        }
+        ("ECONNRESET", _) => "the other end closed the connection abruptly",
+        ("ENOTCONN", _) => "the socket is not connected (yet, or any more)",
+        ("ENOMEM", _) => {
+            "the process is out of memory or address space (see ulimit -v), or the cgroup's memory \
+             limit was reached"
+        }
+        ("ENOSYS", _) => {
+            "the kernel does not have this syscall, or a seccomp filter (as in many containers) \
+             rejects it as if it did not; programs often fall back to an older syscall"
+        }
+        ("ENOTTY", "ioctl") => {
+            "the fd is not a terminal (or a device that knows this request), e.g. since output is \
+             redirected to a file; programs check this on purpose, so it is usually harmless"
+        }
+        ("ESPIPE", _) => "the fd is a pipe, socket, or terminal, which cannot seek",
+        ("ECHILD", _) => "the process has no children to wait for (or no such child)",
+        ("ESRCH", _) => "there is no such process; it may have exited already",
+        ("EFAULT", _) => "a pointer that was passed is invalid, which is a bug in the program",
+        ("EINVAL", _) => "an argument is invalid for this syscall; compare them with its man page",
+        _ => return None,
+    };
+    Some(hint)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{hint, lookup};
+
+    #[test]
+    fn test_errnos() {
+        assert_eq!(lookup("ENOENT"), Some((2, "No such file or directory")));
+        assert_eq!(
+            lookup("EWOULDBLOCK"),
+            Some((11, "Resource temporarily unavailable"))
+        );
+        assert_eq!(lookup("EBOGUS"), None);
+
+        assert!(hint("openat", "EACCES").unwrap().contains("searchable"));
+        assert!(hint("execve", "EACCES").unwrap().contains("noexec"));
+        assert!(hint("read", "EAGAIN").unwrap().contains("non-blocking"));
+        assert_eq!(hint("fork", "EAGAIN"), hint("clone", "EAGAIN"));
+        assert_eq!(hint("getrandom", "EAGAIN"), None);
+        assert_eq!(hint("openat", "EBOGUS"), None);
+    }
+}
@@ -0,0 +1,407 @@
+// Decodes the fds that event loops use to wait for things other than I/O: timerfds, eventfds, and
+// signalfds. Reads and writes on them move 8-byte counters (or 128-byte signalfd_siginfo structs),
+// which strace prints as opaque escaped bytes, so each such syscall is described instead, e.g.
+// "armed 5s timer", "timer fired 3 times", "signaled eventfd (+1)", or "received SIGCHLD".
+//
+// Each fd is followed from the syscall that created it, to know what a read from it means and to
+// count what happened to it over the trace.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::fdtable::{FdKind, FdTable};
+use crate::strace::{self, Syscall, SyscallArgValue};
+use crate::syscalls;
+
+// the size of a struct signalfd_siginfo
+const SIGINFO_BYTES: i64 = 128;
+
+#[derive(Default)]
+pub struct EventFds {
+    pub fds: Vec<EventFd>,
+    // the latest fd in `fds` for each fd-table owner and fd number
+    current: HashMap<(Option<u32>, i64), usize>,
+}
+
+pub struct EventFd {
+    pub pid: Option<u32>,
+    pub fd: i64,
+    pub kind: Kind,
+    // times armed, signaled, or given new signals
+    pub sets: usize,
+    // timer expirations, eventfd counts read, or signals received
+    pub events: u64,
+}
+
+pub enum Kind {
+    // e.g., "CLOCK_MONOTONIC", with the interval the timer was last armed with
+    Timer { clock: String, interval_micros: u64 },
+    Event { semaphore: bool },
+    // e.g., "[CHLD TERM]"
+    Signal { mask: String },
+}
+
+impl EventFds {
+    /// Returns a description of what `syscall` did to a timerfd, eventfd, or signalfd, if it was
+    /// one of them. Must be called after `fds` has been updated with `syscall`.
+    pub fn update(&mut self, syscall: &Syscall, fds: &FdTable) -> Option<String> {
+        if syscall.error_details.is_some() {
+            return None;
+        }
+        let owner = fds.owner(syscall.pid);
+        let arg = |i: usize| syscall.arg(i).map(|a| &a.value);
+        match syscall.name.as_str() {
+            "timerfd_create" if syscall.errno.is_none() => {
+                let clock = arg(0).map(|v| v.to_string()).unwrap_or_default();
+                let description = format!("created timer on {}", clock);
+                self.create(
+                    syscall,
+                    owner,
+                    syscall.return_value,
+                    Kind::Timer {
+                        clock,
+                        interval_micros: 0,
+                    },
+                );
+                Some(description)
+            }
+            "eventfd" | "eventfd2" if syscall.errno.is_none() => {
+                let initial = number(arg(0)).unwrap_or(0);
+                let semaphore =
+                    arg(1).is_some_and(|v| syscalls::value_has_flag(v, "EFD_SEMAPHORE"));
+                self.create(
+                    syscall,
+                    owner,
+                    syscall.return_value,
+                    Kind::Event { semaphore },
+                );
+                Some(format!(
+                    "created {}eventfd with count {}",
+                    if semaphore { "semaphore " } else { "" },
+                    initial
+                ))
+            }
+            "signalfd" | "signalfd4" if syscall.errno.is_none() => {
+                let mask = arg(1).map(|v| v.to_string()).unwrap_or_default();
+                let description = format!("signalfd now takes {}", mask);
+                match number(arg(0)) {
+                    Some(-1) | None => {
+                        let description = format!("created signalfd for {}", mask);
+                        self.create(syscall, owner, syscall.return_value, Kind::Signal { mask });
+                        Some(description)
+                    }
+                    Some(fd) => {
+                        if let Some(event_fd) = self.get_mut(syscall, owner, fd, fds) {
+                            if let Kind::Signal { mask: old } = &mut event_fd.kind {
+                                *old = mask;
+                            }
+                            event_fd.sets += 1;
+                        }
+                        Some(description)
+                    }
+                }
+            }
+            "timerfd_settime" => {
+                let fd = number(arg(0))?;
+                let (interval, value) = itimerspec(arg(2)?)?;
+                let absolute =
+                    arg(1).is_some_and(|v| syscalls::value_has_flag(v, "TFD_TIMER_ABSTIME"));
+                if let Some(event_fd) = self.get_mut(syscall, owner, fd, fds) {
+                    if let Kind::Timer {
+                        interval_micros, ..
+                    } = &mut event_fd.kind
+                    {
+                        *interval_micros = interval;
+                    }
+                    event_fd.sets += 1;
+                }
+                let mut description = if value == 0 {
+                    "disarmed timer".to_string()
+                } else if absolute {
+                    format!("armed timer for {}", strace::format_timestamp(value))
+                } else {
+                    format!("armed {} timer", format_micros(value))
+                };
+                if value != 0 && interval != 0 {
+                    description.push_str(&format!(", repeating every {}", format_micros(interval)));
+                }
+                Some(description)
+            }
+            "timerfd_gettime" if syscall.errno.is_none() => {
+                let (_, value) = itimerspec(arg(1)?)?;
+                Some(if value == 0 {
+                    "timer is disarmed".to_string()
+                } else {
+                    format!("timer fires in {}", format_micros(value))
+                })
+            }
+            name => {
+                let io = syscalls::io_direction(name)?;
+                let fd = *syscalls::fd_args(syscall).first()?;
+                let event_fd = self.get_mut(syscall, owner, fd, fds)?;
+                let data = match arg(1) {
+                    Some(SyscallArgValue::Quoted { text, .. }) => strace::unescape(text),
+                    _ => Vec::new(),
+                };
+                Some(event_fd.describe(syscall, io, &data))
+            }
+        }
+    }
+
+    fn create(&mut self, syscall: &Syscall, owner: Option<u32>, fd: i64, kind: Kind) {
+        self.fds.push(EventFd {
+            pid: syscall.pid,
+            fd,
+            kind,
+            sets: 0,
+            events: 0,
+        });
+        self.current.insert((owner, fd), self.fds.len() - 1);
+    }
+
+    // the fd that `fd` refers to in `syscall`'s process, if it is still open
+    fn get_mut(
+        &mut self,
+        syscall: &Syscall,
+        owner: Option<u32>,
+        fd: i64,
+        fds: &FdTable,
+    ) -> Option<&mut EventFd> {
+        // the fd may since have been closed and reused for something else
+        let created_by = match fds.resolve(syscall.pid, fd, syscall.entry_time_micros).kind {
+            FdKind::Other(name) => name,
+            _ => return None,
+        };
+        let event_fd = &mut self.fds[*self.current.get(&(owner, fd))?];
+        let matches = match event_fd.kind {
+            Kind::Timer { .. } => created_by == "timerfd_create",
+            Kind::Event { .. } => created_by.starts_with("eventfd"),
+            Kind::Signal { .. } => created_by.starts_with("signalfd"),
+        };
+        matches.then_some(event_fd)
+    }
+}
+
+impl EventFd {
+    fn describe(&mut self, syscall: &Syscall, io: syscalls::Io, data: &[u8]) -> String {
+        let would_block = syscall.errno.as_deref() == Some("EAGAIN");
+        let counter = data
+            .get(..8)
+            .map(|b| u64::from_le_bytes(b.try_into().unwrap()));
+        match (&self.kind, io) {
+            (Kind::Timer { .. }, syscalls::Io::Read) => match counter {
+                _ if would_block => "timer has not fired".to_string(),
+                Some(n) if syscall.errno.is_none() => {
+                    self.events += n;
+                    match n {
+                        1 => "timer fired".to_string(),
+                        n => format!("timer fired {} times", n),
+                    }
+                }
+                _ => "read timer".to_string(),
+            },
+            (Kind::Event { .. }, syscalls::Io::Write) => match counter {
+                Some(n) if syscall.errno.is_none() => {
+                    self.sets += 1;
+                    format!("signaled eventfd (+{})", n)
+                }
+                _ if would_block => "eventfd is full".to_string(),
+                _ => "signaled eventfd".to_string(),
+            },
+            (Kind::Event { semaphore }, syscalls::Io::Read) => match counter {
+                _ if would_block => "eventfd was not signaled".to_string(),
+                Some(n) if syscall.errno.is_none() => {
+                    self.events += n;
+                    if *semaphore {
+                        "took 1 from eventfd".to_string()
+                    } else {
+                        format!("eventfd was signaled (count {})", n)
+                    }
+                }
+                _ => "read eventfd".to_string(),
+            },
+            (Kind::Signal { .. }, syscalls::Io::Read) => {
+                if would_block {
+                    return "no signal is pending".to_string();
+                }
+                let count = (syscall.return_value / SIGINFO_BYTES).max(0) as u64;
+                self.events += count;
+                let field = |offset: usize| {
+                    data.get(offset..offset + 4)
+                        .map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+                };
+                let mut description = match field(0) {
+                    Some(signo) if count > 0 => format!("received {}", signal_name(signo)),
+                    _ => return "read signalfd".to_string(),
+                };
+                // ssi_pid, which is 0 unless the signal was sent by a process
+                if let Some(pid) = field(12).filter(|pid| *pid != 0) {
+                    description.push_str(&format!(" from pid {}", pid));
+                }
+                if count > 1 {
+                    description.push_str(&format!(" (and {} more)", count - 1));
+                }
+                description
+            }
+            (_, io) => format!("{} {}", io, self.kind.name()),
+        }
+    }
+}
+
+impl Kind {
+    fn name(&self) -> &'static str {
+        match self {
+            Kind::Timer { .. } => "timerfd",
+            Kind::Event { .. } => "eventfd",
+            Kind::Signal { .. } => "signalfd",
+        }
+    }
+}
+
+// e.g., "timerfd 5 on CLOCK_MONOTONIC every 1s: armed 2 times, fired 30 times"
+impl fmt::Display for EventFd {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.kind.name(), self.fd)?;
+        match &self.kind {
+            Kind::Timer {
+                clock,
+                interval_micros,
+            } => {
+                write!(f, " on {}", clock)?;
+                if *interval_micros != 0 {
+                    write!(f, " every {}", format_micros(*interval_micros))?;
+                }
+                write!(
+                    f,
+                    ": armed {} times, fired {} times",
+                    self.sets, self.events
+                )
+            }
+            Kind::Event { .. } => write!(
+                f,
+                ": signaled {} times, counted {} in reads",
+                self.sets, self.events
+            ),
+            Kind::Signal { mask } => write!(f, " for {}: received {} signals", mask, self.events),
+        }
+    }
+}
+
+fn number(value: Option<&SyscallArgValue>) -> Option<i64> {
+    match value {
+        Some(SyscallArgValue::Number(n)) => Some(*n),
+        _ => None,
+    }
+}
+
+// the interval and value of a struct itimerspec, in microseconds
+fn itimerspec(value: &SyscallArgValue) -> Option<(u64, u64)> {
+    let fields = match value {
+        SyscallArgValue::Struct(fields) => fields,
+        _ => return None,
+    };
+    let timespec = |name: &str| match fields.get(name).map(|a| &a.value) {
+        Some(SyscallArgValue::Struct(ts)) => {
+            let field = |name: &str| number(ts.get(name).map(|a| &a.value)).unwrap_or(0).max(0);
+            Some(field("tv_sec") as u64 * 1_000_000 + field("tv_nsec") as u64 / 1000)
+        }
+        _ => None,
+    };
+    Some((timespec("it_interval")?, timespec("it_value")?))
+}
+
+// e.g., "5s", "1.5s", "250ms", or "40µs"
+fn format_micros(micros: u64) -> String {
+    if micros >= 1_000_000 {
+        format!("{}s", micros as f64 / 1_000_000.0)
+    } else if micros >= 1000 {
+        format!("{}ms", micros as f64 / 1000.0)
+    } else {
+        format!("{}µs", micros)
+    }
+}
+
+// the signals that Linux numbers the same on every architecture, which are most of them
+fn signal_name(signo: u32) -> String {
+    let name = match signo {
+        1 => "SIGHUP",
+        2 => "SIGINT",
+        3 => "SIGQUIT",
+        4 => "SIGILL",
+        5 => "SIGTRAP",
+        6 => "SIGABRT",
+        8 => "SIGFPE",
+        9 => "SIGKILL",
+        11 => "SIGSEGV",
+        13 => "SIGPIPE",
+        14 => "SIGALRM",
+        15 => "SIGTERM",
+        // the rest are numbered as on x86-64 and arm64, where vistrace runs
+        10 => "SIGUSR1",
+        12 => "SIGUSR2",
+        17 => "SIGCHLD",
+        18 => "SIGCONT",
+        19 => "SIGSTOP",
+        20 => "SIGTSTP",
+        28 => "SIGWINCH",
+        29 => "SIGIO",
+        signo => return format!("signal {}", signo),
+    };
+    name.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fdtable::FdTable;
+    use crate::strace::LineParser;
+
+    use super::EventFds;
+
+    #[test]
+    fn test_event_fds() {
+        let mut event_fds = EventFds::default();
+        let mut fds = FdTable::default();
+        let mut parser = LineParser::default();
+        let mut descriptions = Vec::new();
+        for line in [
+            "10 timerfd_create(CLOCK_MONOTONIC, TFD_NONBLOCK|TFD_CLOEXEC) = 5",
+            "10 timerfd_settime(5, 0, {it_interval={tv_sec=1, tv_nsec=0}, it_value={tv_sec=5, tv_nsec=0}}, NULL) = 0",
+            "10 read(5, 0x7ffd, 8) = -1 EAGAIN (Resource temporarily unavailable)",
+            "10 read(5, \"\\3\\0\\0\\0\\0\\0\\0\\0\", 8) = 8",
+            "10 timerfd_settime(5, 0, {it_interval={tv_sec=0, tv_nsec=0}, it_value={tv_sec=0, tv_nsec=0}}, NULL) = 0",
+            "10 eventfd2(0, EFD_CLOEXEC|EFD_NONBLOCK) = 6",
+            "10 write(6, \"\\1\\0\\0\\0\\0\\0\\0\\0\", 8) = 8",
+            "10 read(6, \"\\2\\0\\0\\0\\0\\0\\0\\0\", 8) = 8",
+            "10 signalfd4(-1, [CHLD], 8, SFD_NONBLOCK|SFD_CLOEXEC) = 7",
+            "10 read(7, \"\\21\\0\\0\\0\\0\\0\\0\\0\\1\\0\\0\\0\\14\\0\\0\\0\", 128) = 128",
+            // no longer an eventfd
+            "10 close(6) = 0",
+            "10 openat(AT_FDCWD, \"/tmp/x\", O_RDONLY) = 6",
+            "10 read(6, \"\\1\\0\\0\\0\\0\\0\\0\\0\", 8) = 8",
+        ] {
+            let syscall = parser.parse_line(line).unwrap().into_syscall().unwrap();
+            fds.update(&syscall);
+            descriptions.extend(event_fds.update(&syscall, &fds));
+        }
+
+        assert_eq!(
+            descriptions,
+            [
+                "created timer on CLOCK_MONOTONIC",
+                "armed 5s timer, repeating every 1s",
+                "timer has not fired",
+                "timer fired 3 times",
+                "disarmed timer",
+                "created eventfd with count 0",
+                "signaled eventfd (+1)",
+                "eventfd was signaled (count 2)",
+                "created signalfd for [CHLD]",
+                "received SIGCHLD from pid 12",
+            ]
+        );
+        assert_eq!(
+            event_fds.fds[0].to_string(),
+            "timerfd 5 on CLOCK_MONOTONIC: armed 2 times, fired 3 times"
+        );
+    }
+}
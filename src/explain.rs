@@ -0,0 +1,437 @@
+// Explains common syscalls in plain English, for anyone who does not know them by heart, e.g.
+// `openat(AT_FDCWD, "/etc/hosts", O_RDONLY|O_CLOEXEC) = 3` is "opened /etc/hosts read-only as
+// fd 3", and the same call failing with ENOENT is "failed to open /etc/hosts read-only (ENOENT)".
+// The interface shows the explanation in the detail pane, and next to each event with --explain or
+// after 'i'.
+//
+// Only what strace printed is explained: paths are as the process passed them (so may be relative),
+// and fds are described by whatever the caller knows about them (see src/fdtable.rs).
+
+use crate::net;
+use crate::strace::{Syscall, SyscallArgValue};
+use crate::syscalls::{self, Io};
+use crate::units;
+
+/// Explains `syscall`, or returns `None` if it is not one that vistrace knows how to explain.
+/// `describe_fd` says what an fd that was passed to the syscall refers to, if known, e.g.
+/// "/etc/hosts".
+pub fn explain(syscall: &Syscall, describe_fd: impl Fn(i64) -> Option<String>) -> Option<String> {
+    if syscall.error_details.is_some() {
+        return None;
+    }
+    let args = Args {
+        syscall,
+        describe_fd: &describe_fd,
+    };
+    let ret = syscall.return_value;
+    // what the syscall did if it succeeded, and what it tried to do, for if it failed
+    let (done, attempt) = match syscall.name.as_str() {
+        "open" | "openat" | "creat" => {
+            let path = args.path("pathname")?;
+            let (how, notes) = open_mode(syscall);
+            let mut done = format!("opened {} {} as fd {}", path, how, ret);
+            if !notes.is_empty() {
+                done.push_str(&format!(", {}", notes.join(" and ")));
+            }
+            (done, format!("open {} {}", path, how))
+        }
+        "close" => {
+            let fd = args.fd("fd")?;
+            (format!("closed {}", fd), format!("close {}", fd))
+        }
+        name if syscalls::io_direction(name).is_some() => {
+            let fd = args.fd(if name.starts_with("send") || name.starts_with("recv") {
+                "sockfd"
+            } else {
+                "fd"
+            })?;
+            match syscalls::io_direction(name)? {
+                Io::Read if ret == 0 => (
+                    format!("read nothing from {}, as it is at its end", fd),
+                    String::new(),
+                ),
+                Io::Read => (
+                    format!("read {} from {}", units::bytes(ret as u64), fd),
+                    format!("read from {}", fd),
+                ),
+                Io::Write => (
+                    format!("wrote {} to {}", units::bytes(ret as u64), fd),
+                    format!("write to {}", fd),
+                ),
+            }
+        }
+        "stat" | "lstat" | "newfstatat" | "statx" => {
+            let path = args.path("pathname")?;
+            (
+                format!("looked up the size, owner, and times of {}", path),
+                format!("look up {}", path),
+            )
+        }
+        "fstat" => {
+            let fd = args.fd("fd")?;
+            (
+                format!("looked up the size, owner, and times of {}", fd),
+                format!("look up {}", fd),
+            )
+        }
+        "access" | "faccessat" | "faccessat2" => {
+            let path = args.path("pathname")?;
+            (
+                format!("checked that it may use {}", path),
+                format!("use {}", path),
+            )
+        }
+        "lseek" => {
+            let fd = args.fd("fd")?;
+            (
+                format!("moved to byte {} of {}", ret, fd),
+                format!("move within {}", fd),
+            )
+        }
+        "getdents64" => {
+            let fd = args.fd("fd")?;
+            match ret {
+                0 => (
+                    format!("listed the last of directory {}", fd),
+                    String::new(),
+                ),
+                _ => (
+                    format!("listed {} of directory {}", units::bytes(ret as u64), fd),
+                    format!("list directory {}", fd),
+                ),
+            }
+        }
+        "mmap" => {
+            let size = units::bytes(args.number("length")? as u64);
+            match args.number("fd") {
+                Some(fd) if fd >= 0 => {
+                    let fd = args.describe(fd);
+                    (
+                        format!("mapped {} of {} into memory", size, fd),
+                        format!("map {} of {} into memory", size, fd),
+                    )
+                }
+                _ => (
+                    format!("allocated {} of memory", size),
+                    format!("allocate {} of memory", size),
+                ),
+            }
+        }
+        "munmap" => {
+            let size = units::bytes(args.number("length")? as u64);
+            (
+                format!("released {} of memory", size),
+                format!("release {} of memory", size),
+            )
+        }
+        "brk" if args.is_null("addr") => {
+            ("looked up where its heap ends".to_string(), String::new())
+        }
+        "brk" => (
+            "grew or shrank its heap".to_string(),
+            "resize its heap".to_string(),
+        ),
+        "dup" | "dup2" | "dup3" => {
+            let fd = args.fd("oldfd")?;
+            (
+                format!("copied {} to fd {}", fd, ret),
+                format!("copy {}", fd),
+            )
+        }
+        "pipe" | "pipe2" => {
+            let done = match syscalls::created_fds(syscall)[..] {
+                [read, write] => format!(
+                    "created a pipe, reading from fd {} and writing to fd {}",
+                    read, write
+                ),
+                _ => "created a pipe".to_string(),
+            };
+            (done, "create a pipe".to_string())
+        }
+        "socket" => {
+            let kind = format!("{} socket", args.symbol("domain")?);
+            (
+                format!("created a {} as fd {}", kind, ret),
+                format!("create a {}", kind),
+            )
+        }
+        "connect" | "bind" => {
+            let fd = args.fd("sockfd")?;
+            let addr = net::decode_sockaddr(args.value("addr")?)?;
+            let (done, attempt) = match syscall.name.as_str() {
+                "connect" => ("connected", "connect"),
+                _ => ("bound", "bind"),
+            };
+            (
+                format!("{} {} to {}", done, fd, addr),
+                format!("{} {} to {}", attempt, fd, addr),
+            )
+        }
+        "listen" => {
+            let fd = args.fd("sockfd")?;
+            (
+                format!("started listening for connections on {}", fd),
+                format!("listen on {}", fd),
+            )
+        }
+        "accept" | "accept4" => {
+            let fd = args.fd("sockfd")?;
+            (
+                format!("accepted a connection on {} as fd {}", fd, ret),
+                format!("accept a connection on {}", fd),
+            )
+        }
+        "execve" | "execveat" => {
+            let path = args.path("pathname")?;
+            (
+                format!("replaced itself with the program {}", path),
+                format!("run {}", path),
+            )
+        }
+        "exit_group" => (
+            format!("exited with status {}", args.number("status")?),
+            String::new(),
+        ),
+        "chdir" => {
+            let path = args.path("path")?;
+            (
+                format!("changed its working directory to {}", path),
+                format!("change its working directory to {}", path),
+            )
+        }
+        "unlink" | "unlinkat" => {
+            let path = args.path("pathname")?;
+            (format!("deleted {}", path), format!("delete {}", path))
+        }
+        "rmdir" => {
+            let path = args.path("pathname")?;
+            (
+                format!("deleted the directory {}", path),
+                format!("delete the directory {}", path),
+            )
+        }
+        "mkdir" | "mkdirat" => {
+            let path = args.path("pathname")?;
+            (
+                format!("created the directory {}", path),
+                format!("create the directory {}", path),
+            )
+        }
+        "rename" | "renameat" | "renameat2" => {
+            let (old, new) = (args.path("oldpath")?, args.path("newpath")?);
+            (
+                format!("renamed {} to {}", old, new),
+                format!("rename {} to {}", old, new),
+            )
+        }
+        "readlink" | "readlinkat" => {
+            let path = args.path("pathname")?;
+            (
+                format!("looked up where the symlink {} leads", path),
+                format!("look up where {} leads", path),
+            )
+        }
+        "clone" | "clone3" | "fork" | "vfork" => {
+            let what = if syscalls::has_clone_flag(syscall, "CLONE_THREAD") {
+                "thread"
+            } else {
+                "process"
+            };
+            (
+                format!("started {} {}", what, ret),
+                format!("start a {}", what),
+            )
+        }
+        "wait4" => (
+            format!("waited for process {} to exit or stop", ret),
+            "wait for a process".to_string(),
+        ),
+        "kill" => {
+            let (pid, signal) = (args.number("pid")?, args.value("sig")?);
+            (
+                format!("sent {} to process {}", signal, pid),
+                format!("send {} to process {}", signal, pid),
+            )
+        }
+        _ => return None,
+    };
+    match &syscall.errno {
+        Some(errno) if !attempt.is_empty() => Some(format!("failed to {} ({})", attempt, errno)),
+        Some(_) => None,
+        None => Some(done),
+    }
+}
+
+// e.g., ("read-only", ["creating it if it did not exist"])
+fn open_mode(syscall: &Syscall) -> (&'static str, Vec<&'static str>) {
+    if syscall.name == "creat" {
+        return (
+            "write-only",
+            vec!["creating it if it did not exist", "emptying it"],
+        );
+    }
+    let flags = syscalls::arg_index(&syscall.name, "flags")
+        .and_then(|i| syscall.arg(i))
+        .map(|a| &a.value);
+    let has = |flag: &str| flags.is_some_and(|flags| syscalls::value_has_flag(flags, flag));
+    let how = if has("O_RDWR") {
+        "read-write"
+    } else if has("O_WRONLY") {
+        "write-only"
+    } else {
+        "read-only"
+    };
+    let mut notes = Vec::new();
+    if has("O_CREAT") {
+        notes.push(if has("O_EXCL") {
+            "creating it"
+        } else {
+            "creating it if it did not exist"
+        });
+    }
+    if has("O_TRUNC") {
+        notes.push("emptying it");
+    }
+    if has("O_APPEND") {
+        notes.push("appending to it");
+    }
+    (how, notes)
+}
+
+// the arguments of a syscall, by their names in `syscalls::arg_names`
+struct Args<'a> {
+    syscall: &'a Syscall,
+    describe_fd: &'a dyn Fn(i64) -> Option<String>,
+}
+
+impl<'a> Args<'a> {
+    fn value(&self, name: &str) -> Option<&'a SyscallArgValue> {
+        let i = syscalls::arg_index(&self.syscall.name, name)?;
+        self.syscall.arg(i).map(|a| &a.value)
+    }
+
+    fn number(&self, name: &str) -> Option<i64> {
+        match self.value(name)? {
+            SyscallArgValue::Number(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    // strace prints a null pointer as NULL
+    fn is_null(&self, name: &str) -> bool {
+        match self.value(name) {
+            Some(SyscallArgValue::Number(n)) => *n == 0,
+            Some(SyscallArgValue::Symbol(s)) => s == "NULL",
+            _ => false,
+        }
+    }
+
+    fn path(&self, name: &str) -> Option<&'a str> {
+        match self.value(name)? {
+            SyscallArgValue::Quoted { text, .. } => Some(text),
+            _ => None,
+        }
+    }
+
+    fn symbol(&self, name: &str) -> Option<String> {
+        match self.value(name)? {
+            SyscallArgValue::Symbol(s) => Some(s.clone()),
+            SyscallArgValue::Number(n) => Some(n.to_string()),
+            _ => None,
+        }
+    }
+
+    // e.g., "fd 3 (/etc/hosts)"
+    fn fd(&self, name: &str) -> Option<String> {
+        Some(self.describe(self.number(name)?))
+    }
+
+    fn describe(&self, fd: i64) -> String {
+        match (self.describe_fd)(fd) {
+            Some(description) => format!("fd {} ({})", fd, description),
+            None => format!("fd {}", fd),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::strace::parse_syscall;
+
+    use super::explain;
+
+    #[test]
+    fn test_explain() {
+        let explained = |line: &str| explain(&parse_syscall(line, false), |_| None);
+        let hosts = |fd: i64| (fd == 3).then(|| "/etc/hosts".to_string());
+
+        assert_eq!(
+            explained(r#"openat(AT_FDCWD, "/etc/hosts", O_RDONLY|O_CLOEXEC) = 3"#).as_deref(),
+            Some("opened /etc/hosts read-only as fd 3")
+        );
+        assert_eq!(
+            explained(r#"openat(AT_FDCWD, "o", O_WRONLY|O_CREAT|O_TRUNC, 0644) = 4"#).as_deref(),
+            Some("opened o write-only as fd 4, creating it if it did not exist and emptying it")
+        );
+        assert_eq!(
+            explained(
+                r#"openat(AT_FDCWD, "/nope", O_RDONLY) = -1 ENOENT (No such file or directory)"#
+            )
+            .as_deref(),
+            Some("failed to open /nope read-only (ENOENT)")
+        );
+        assert_eq!(
+            explain(
+                &parse_syscall(r#"read(3, "127.0.0.1 localhost\n", 4096) = 20"#, false),
+                hosts
+            )
+            .as_deref(),
+            Some("read 20 B from fd 3 (/etc/hosts)")
+        );
+        assert_eq!(
+            explain(&parse_syscall("read(3, \"\", 4096) = 0", false), hosts).as_deref(),
+            Some("read nothing from fd 3 (/etc/hosts), as it is at its end")
+        );
+        assert_eq!(
+            explained("close(3) = -1 EBADF (Bad file descriptor)").as_deref(),
+            Some("failed to close fd 3 (EBADF)")
+        );
+        assert_eq!(
+            explained(
+                r#"connect(3, {sa_family=AF_INET, sin_port=htons(80), sin_addr=inet_addr("10.0.0.1")}, 16) = 0"#
+            )
+            .as_deref(),
+            Some("connected fd 3 to 10.0.0.1:80")
+        );
+        assert_eq!(
+            explained("pipe2([3, 4], O_CLOEXEC) = 0").as_deref(),
+            Some("created a pipe, reading from fd 3 and writing to fd 4")
+        );
+        assert_eq!(
+            explained(
+                "mmap(NULL, 8192, PROT_READ, MAP_PRIVATE|MAP_ANONYMOUS, -1, 0) = 0x7f0000000000"
+            )
+            .as_deref(),
+            Some("allocated 8.0 KiB of memory")
+        );
+        assert_eq!(
+            explained("exit_group(0) = ?").as_deref(),
+            Some("exited with status 0")
+        );
+        assert_eq!(explained("getpid() = 42"), None);
+    }
+
+    #[test]
+    fn test_explain_brk() {
+        let explained = |line: &str| explain(&parse_syscall(line, false), |_| None);
+        // as strace prints it
+        assert_eq!(
+            explained("brk(NULL) = 0x55d5c5a4e000").as_deref(),
+            Some("looked up where its heap ends")
+        );
+        assert_eq!(
+            explained("brk(0x55d5c5a6f000) = 0x55d5c5a6f000").as_deref(),
+            Some("grew or shrank its heap")
+        );
+    }
+}
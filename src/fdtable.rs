@@ -0,0 +1,567 @@
+// Tracks what each file descriptor referred to over the course of a trace, so that e.g. a
+// `read(3, ...)` can be annotated with the file that fd 3 was at the time.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+use crate::strace::{FlagSetValue, Syscall, SyscallArgValue};
+use crate::syscalls::{self, Io};
+
+#[derive(Default)]
+pub struct FdTable {
+    // threads (and processes cloned with CLONE_FILES) share an fd table, so each process maps to
+    // the process that owns its table
+    owners: HashMap<Option<u32>, Option<u32>>,
+    // every use of each fd number in each table, oldest first
+    fds: HashMap<(Option<u32>, i64), Vec<FdInfo>>,
+    next_description: u64,
+    // the working directory of each table's owner (threads share their working directory as well),
+    // where known, so that relative paths can be made absolute; under `None`, the directory that
+    // processes not otherwise known started in
+    cwds: HashMap<Option<u32>, String>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct FdInfo {
+    pub kind: FdKind,
+    // in microseconds since the Unix epoch (0 if unknown)
+    pub opened_micros: u64,
+    // `None` if the fd is still open
+    pub closed_micros: Option<u64>,
+    // whether the fd will be closed when the process execs
+    pub cloexec: bool,
+    pub bytes: ByteCounts,
+    // identifies the open file description, which is shared by fds that were dup'd or inherited
+    // from one another (0 if unknown)
+    pub description: u64,
+}
+
+/// Bytes read and written through a file descriptor (or every descriptor for a file).
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ByteCounts {
+    pub read: u64,
+    pub written: u64,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum FdKind {
+    // the path as strace printed it
+    File(String),
+    Socket { domain: String, socket_type: String },
+    Pipe { read_end: bool },
+    // e.g., an eventfd or an epoll instance, named by the syscall that created it
+    Other(String),
+    // opened before tracing began (e.g., stdin), or by a syscall that vistrace does not know about
+    Unknown,
+}
+
+impl FdTable {
+    pub fn update(&mut self, syscall: &Syscall) {
+        if syscall.errno.is_some() || syscall.error_details.is_some() {
+            return;
+        }
+
+        let owner = self.owner(syscall.pid);
+        let time = syscall.entry_time_micros;
+        let arg_fd = |name: &str| match syscalls::arg_index(&syscall.name, name)
+            .and_then(|i| syscall.arg(i))
+            .map(|a| &a.value)
+        {
+            Some(SyscallArgValue::Number(fd)) => Some(*fd),
+            _ => None,
+        };
+
+        if syscall.return_value > 0 {
+            let bytes = syscall.return_value as u64;
+            if let Some(io) = syscalls::io_direction(&syscall.name) {
+                if let Some(fd) = syscalls::fd_args(syscall).first() {
+                    self.count(owner, *fd, io, bytes, time);
+                }
+            } else if syscall.name == "sendfile" {
+                if let (Some(out_fd), Some(in_fd)) = (arg_fd("out_fd"), arg_fd("in_fd")) {
+                    self.count(owner, in_fd, Io::Read, bytes, time);
+                    self.count(owner, out_fd, Io::Write, bytes, time);
+                }
+            }
+        }
+
+        match syscall.name.as_str() {
+            "close" => {
+                if let Some(fd) = arg_fd("fd") {
+                    self.close(owner, fd, time);
+                }
+            }
+            "open" | "openat" | "openat2" | "creat" => {
+                let path = match syscalls::path_args(syscall).into_iter().next() {
+                    Some(path) => path,
+                    None => return,
+                };
+                // a relative path is relative to the directory fd, if there is one, or else to the
+                // working directory
+                let path = match arg_fd("dirfd").map(|fd| self.resolve(syscall.pid, fd, time)) {
+                    Some(FdInfo {
+                        kind: FdKind::File(dir),
+                        ..
+                    }) if !path.starts_with('/') => join(&dir, &path),
+                    _ => self.absolute(syscall.pid, &path),
+                };
+                let cloexec = has_flag(syscall, "flags", "O_CLOEXEC");
+                self.open(
+                    owner,
+                    syscall.return_value,
+                    FdKind::File(path),
+                    time,
+                    cloexec,
+                );
+            }
+            "socket" => {
+                let kind = socket_kind(syscall);
+                let cloexec = has_flag(syscall, "type", "SOCK_CLOEXEC");
+                self.open(owner, syscall.return_value, kind, time, cloexec);
+            }
+            "accept" | "accept4" => {
+                let kind = match arg_fd("sockfd") {
+                    Some(fd) => self.resolve(syscall.pid, fd, time).kind,
+                    None => FdKind::Unknown,
+                };
+                let cloexec = has_flag(syscall, "flags", "SOCK_CLOEXEC");
+                self.open(owner, syscall.return_value, kind, time, cloexec);
+            }
+            "pipe" | "pipe2" | "socketpair" => {
+                let fds = syscalls::created_fds(syscall);
+                let cloexec = has_flag(syscall, "flags", "O_CLOEXEC")
+                    || has_flag(syscall, "type", "SOCK_CLOEXEC");
+                for (i, fd) in fds.into_iter().enumerate() {
+                    let kind = if syscall.name == "socketpair" {
+                        socket_kind(syscall)
+                    } else {
+                        FdKind::Pipe { read_end: i == 0 }
+                    };
+                    self.open(owner, fd, kind, time, cloexec);
+                }
+            }
+            "dup" | "dup2" | "dup3" | "fcntl" => {
+                let created = syscalls::created_fds(syscall);
+                let (oldfd, newfd) = match (arg_fd("oldfd").or(arg_fd("fd")), created.first()) {
+                    (Some(oldfd), Some(newfd)) => (oldfd, *newfd),
+                    _ => {
+                        if syscall.name == "fcntl" {
+                            self.update_fcntl(owner, syscall);
+                        }
+                        return;
+                    }
+                };
+                // dup2 and dup3 silently close the new fd if it was open
+                let old = self.resolve(syscall.pid, oldfd, time);
+                self.close(owner, newfd, time);
+                let cloexec = has_flag(syscall, "flags", "O_CLOEXEC")
+                    || has_flag(syscall, "cmd", "F_DUPFD_CLOEXEC");
+                self.open(owner, newfd, old.kind, time, cloexec);
+                if let Some(new) = self.fds.get_mut(&(owner, newfd)).and_then(|h| h.last_mut()) {
+                    new.description = old.description;
+                }
+            }
+            "clone" | "clone3" | "fork" | "vfork" if syscall.return_value > 0 => {
+                let child = Some(syscall.return_value as u32);
+                if syscalls::has_clone_flag(syscall, "CLONE_FILES") {
+                    self.owners.insert(child, owner);
+                } else {
+                    self.copy_open_fds(owner, child);
+                    if let Some(cwd) = self.cwds.get(&owner).cloned() {
+                        self.cwds.insert(child, cwd);
+                    }
+                }
+            }
+            "chdir" => {
+                if let Some(path) = syscalls::path_args(syscall).into_iter().next() {
+                    let cwd = self.absolute(syscall.pid, &path);
+                    self.cwds.insert(owner, cwd);
+                }
+            }
+            "fchdir" => {
+                let dir = arg_fd("fd").map(|fd| self.resolve(syscall.pid, fd, time).kind);
+                if let Some(FdKind::File(dir)) = dir {
+                    self.cwds.insert(owner, dir);
+                }
+            }
+            "execve" | "execveat" => {
+                for history in self
+                    .fds
+                    .iter_mut()
+                    .filter(|((o, _), _)| *o == owner)
+                    .map(|(_, history)| history)
+                {
+                    if let Some(last) = history.last_mut() {
+                        if last.cloexec && last.closed_micros.is_none() {
+                            last.closed_micros = Some(time);
+                        }
+                    }
+                }
+            }
+            name if syscalls::returns_fd(name) => {
+                let kind = FdKind::Other(name.to_string());
+                self.open(owner, syscall.return_value, kind, time, false);
+            }
+            _ => {}
+        }
+    }
+
+    /// Returns what `fd` referred to in process `pid` at `at_micros` (microseconds since the Unix
+    /// epoch). If the trace has no timestamps, every time is 0, so this returns the most recent
+    /// use of the fd.
+    pub fn resolve(&self, pid: Option<u32>, fd: i64, at_micros: u64) -> FdInfo {
+        let found = self.fds.get(&(self.owner(pid), fd)).and_then(|history| {
+            // the most recent use of the fd wins, in case of ties between a close and an open
+            history.iter().rev().find(|info| {
+                info.opened_micros <= at_micros
+                    && info.closed_micros.is_none_or(|closed| closed >= at_micros)
+            })
+        });
+        match found {
+            Some(info) => info.clone(),
+            None => FdInfo::unknown(0),
+        }
+    }
+
+    /// Returns the bytes read and written through each file, summed over every fd that referred
+    /// to it.
+    pub fn file_bytes(&self) -> BTreeMap<&str, ByteCounts> {
+        let mut totals: BTreeMap<&str, ByteCounts> = BTreeMap::new();
+        for info in self.fds.values().flatten() {
+            if let FdKind::File(path) = &info.kind {
+                let total = totals.entry(path).or_default();
+                total.read += info.bytes.read;
+                total.written += info.bytes.written;
+            }
+        }
+        totals
+    }
+
+    /// Returns every use of every fd, as (fd table owner, fd, use), by owner and fd and then oldest
+    /// first.
+    pub fn all(&self) -> Vec<(Option<u32>, i64, &FdInfo)> {
+        let mut keys: Vec<&(Option<u32>, i64)> = self.fds.keys().collect();
+        keys.sort();
+        keys.into_iter()
+            .flat_map(|key| self.fds[key].iter().map(move |info| (key.0, key.1, info)))
+            .collect()
+    }
+
+    /// Records that process `pid` (or, if `None`, any process whose working directory is not
+    /// otherwise known) was in `dir` when tracing began.
+    pub fn set_cwd(&mut self, pid: Option<u32>, dir: String) {
+        self.cwds.insert(pid, dir);
+    }
+
+    /// Returns `path` relative to process `pid`'s working directory, if it is relative and the
+    /// working directory is known, and otherwise `path` as is.
+    pub fn absolute(&self, pid: Option<u32>, path: &str) -> String {
+        let cwd = self
+            .cwds
+            .get(&self.owner(pid))
+            .or_else(|| self.cwds.get(&None));
+        match cwd {
+            Some(cwd) if !path.starts_with('/') => join(cwd, path),
+            _ => path.to_string(),
+        }
+    }
+
+    /// Returns the process whose fd table `pid` uses, which is `pid` itself unless it is a thread
+    /// (or was otherwise cloned with `CLONE_FILES`).
+    pub fn owner(&self, pid: Option<u32>) -> Option<u32> {
+        self.owners.get(&pid).copied().unwrap_or(pid)
+    }
+
+    fn open(&mut self, owner: Option<u32>, fd: i64, kind: FdKind, time: u64, cloexec: bool) {
+        self.next_description += 1;
+        self.fds.entry((owner, fd)).or_default().push(FdInfo {
+            kind,
+            opened_micros: time,
+            closed_micros: None,
+            cloexec,
+            bytes: ByteCounts::default(),
+            description: self.next_description,
+        });
+    }
+
+    fn count(&mut self, owner: Option<u32>, fd: i64, io: Io, bytes: u64, time: u64) {
+        let history = self.fds.entry((owner, fd)).or_default();
+        // the fd was opened before tracing began (e.g., stdout) or by an untracked syscall
+        match history.last() {
+            None => history.push(FdInfo::unknown(0)),
+            Some(info) if info.closed_micros.is_some() => history.push(FdInfo::unknown(time)),
+            Some(_) => {}
+        }
+
+        let info = history.last_mut().unwrap();
+        match io {
+            Io::Read => info.bytes.read += bytes,
+            Io::Write => info.bytes.written += bytes,
+        }
+    }
+
+    fn close(&mut self, owner: Option<u32>, fd: i64, time: u64) {
+        if let Some(last) = self.fds.get_mut(&(owner, fd)).and_then(|h| h.last_mut()) {
+            if last.closed_micros.is_none() {
+                last.closed_micros = Some(time);
+            }
+        }
+    }
+
+    fn update_fcntl(&mut self, owner: Option<u32>, syscall: &Syscall) {
+        let fd = match syscall.arg(0).map(|a| &a.value) {
+            Some(SyscallArgValue::Number(fd)) => *fd,
+            _ => return,
+        };
+        if !matches!(syscall.arg(1).map(|a| &a.value), Some(SyscallArgValue::Symbol(cmd)) if cmd == "F_SETFD")
+        {
+            return;
+        }
+        let cloexec = has_flag(syscall, "arg", "FD_CLOEXEC");
+        if let Some(last) = self.fds.get_mut(&(owner, fd)).and_then(|h| h.last_mut()) {
+            last.cloexec = cloexec;
+        }
+    }
+
+    // a forked child starts with a copy of its parent's open fds
+    fn copy_open_fds(&mut self, parent: Option<u32>, child: Option<u32>) {
+        let open: Vec<(i64, FdInfo)> = self
+            .fds
+            .iter()
+            .filter(|((owner, _), _)| *owner == parent)
+            .filter_map(|((_, fd), history)| {
+                history
+                    .last()
+                    .filter(|info| info.closed_micros.is_none())
+                    .map(|info| {
+                        // the child's share of the traffic is counted separately
+                        let bytes = ByteCounts::default();
+                        (
+                            *fd,
+                            FdInfo {
+                                bytes,
+                                ..info.clone()
+                            },
+                        )
+                    })
+            })
+            .collect();
+        for (fd, info) in open {
+            // the child may have shown up in the trace before the fork returned
+            let history = self.fds.entry((child, fd)).or_default();
+            if history.is_empty() {
+                history.push(info);
+            }
+        }
+    }
+}
+
+/// Joins a directory and a relative path, resolving "." and ".." (without regard to symlinks).
+pub fn join(dir: &str, path: &str) -> String {
+    let absolute = dir.starts_with('/');
+    let mut parts: Vec<&str> = dir.split('/').filter(|p| !p.is_empty()).collect();
+    for part in path.split('/') {
+        match part {
+            "" | "." => {}
+            ".." if parts.last().is_some_and(|p| *p != "..") => {
+                parts.pop();
+            }
+            // the parent of the root is the root
+            ".." if absolute => {}
+            part => parts.push(part),
+        }
+    }
+    if absolute {
+        format!("/{}", parts.join("/"))
+    } else {
+        parts.join("/")
+    }
+}
+
+impl FdInfo {
+    fn unknown(opened_micros: u64) -> FdInfo {
+        FdInfo {
+            kind: FdKind::Unknown,
+            opened_micros,
+            closed_micros: None,
+            cloexec: false,
+            bytes: ByteCounts::default(),
+            description: 0,
+        }
+    }
+}
+
+impl fmt::Display for FdKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FdKind::File(path) => write!(f, "{}", path),
+            FdKind::Socket {
+                domain,
+                socket_type,
+            } => write!(f, "socket ({}, {})", domain, socket_type),
+            FdKind::Pipe { read_end: true } => write!(f, "pipe (read end)"),
+            FdKind::Pipe { read_end: false } => write!(f, "pipe (write end)"),
+            FdKind::Other(name) => write!(f, "{}", name),
+            FdKind::Unknown => write!(f, "unknown"),
+        }
+    }
+}
+
+fn socket_kind(syscall: &Syscall) -> FdKind {
+    let symbol = |i: usize| match syscall.arg(i).map(|a| &a.value) {
+        Some(SyscallArgValue::Symbol(s)) => s.clone(),
+        Some(SyscallArgValue::FlagSet(flags)) => match flags.first() {
+            Some(FlagSetValue::Symbol(s)) => s.clone(),
+            _ => "?".to_string(),
+        },
+        _ => "?".to_string(),
+    };
+    FdKind::Socket {
+        domain: symbol(0),
+        socket_type: symbol(1),
+    }
+}
+
+// true if the argument called `arg` includes `flag`, e.g. `O_CLOEXEC` in `O_RDONLY|O_CLOEXEC`
+fn has_flag(syscall: &Syscall, arg: &str, flag: &str) -> bool {
+    syscalls::arg_index(&syscall.name, arg)
+        .and_then(|i| syscall.arg(i))
+        .is_some_and(|a| syscalls::value_has_flag(&a.value, flag))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::strace::{LineParser, Message};
+
+    use super::{ByteCounts, FdKind, FdTable};
+
+    #[test]
+    fn test_fd_table() {
+        let mut table = FdTable::default();
+        let mut parser = LineParser::default();
+        for line in [
+            "10 1.000001 openat(AT_FDCWD, \"/etc\", O_RDONLY|O_DIRECTORY) = 3 <0.000001>",
+            "10 1.000002 openat(3, \"hosts\", O_RDONLY|O_CLOEXEC) = 4 <0.000001>",
+            "10 1.000003 close(4) = 0 <0.000001>",
+            "10 1.000004 socket(AF_INET, SOCK_STREAM|SOCK_CLOEXEC, IPPROTO_TCP) = 4 <0.000001>",
+            "10 1.000005 pipe2([5, 6], 0) = 0 <0.000001>",
+            "10 1.000006 dup2(5, 0) = 0 <0.000001>",
+            "10 1.000007 clone(child_stack=NULL, flags=CLONE_VM|CLONE_FILES|SIGCHLD) = 11 <0.000001>",
+            "11 1.000008 close(3) = 0 <0.000001>",
+            "10 1.000009 clone(child_stack=NULL, flags=SIGCHLD) = 12 <0.000001>",
+            "12 1.000010 execve(\"/bin/true\", [\"true\"], 0x7ffc /* 1 vars */) = 0 <0.000001>",
+            "10 1.000011 clone3({flags=CLONE_VM|CLONE_FILES, exit_signal=0, stack=0x7f00, stack_size=0x1000}, 88) = 13 <0.000001>",
+            "13 1.000012 close(5) = 0 <0.000001>",
+        ] {
+            table.update(
+                &parser
+                    .parse_line(line)
+                    .and_then(Message::into_syscall)
+                    .unwrap(),
+            );
+        }
+
+        let kind = |pid: u32, fd: i64, time: u64| table.resolve(Some(pid), fd, time).kind;
+        let file = |path: &str| FdKind::File(path.to_string());
+        assert_eq!(kind(10, 4, 1000002), file("/etc/hosts"));
+        assert_eq!(
+            kind(10, 4, 1000004),
+            FdKind::Socket {
+                domain: "AF_INET".to_string(),
+                socket_type: "SOCK_STREAM".to_string()
+            }
+        );
+        assert_eq!(kind(10, 0, 1000001), FdKind::Unknown);
+        assert_eq!(kind(10, 0, 1000006), FdKind::Pipe { read_end: true });
+        assert_eq!(kind(10, 6, 1000006), FdKind::Pipe { read_end: false });
+
+        // 11 shares 10's fds, so closing fd 3 closes it for both
+        assert_eq!(kind(10, 3, 1000007), file("/etc"));
+        assert_eq!(kind(10, 3, 1000009), FdKind::Unknown);
+
+        // 12 has its own copy of 10's fds, minus those closed on exec
+        assert_eq!(kind(12, 6, 1000009), FdKind::Pipe { read_end: false });
+        assert_eq!(kind(12, 4, 1000009), kind(10, 4, 1000009));
+        assert_eq!(kind(12, 4, 1000011), FdKind::Unknown);
+        assert_eq!(kind(10, 4, 1000011), kind(10, 4, 1000004));
+
+        // as does 13, which was started with clone3
+        assert_eq!(kind(10, 5, 1000013), FdKind::Unknown);
+
+        // dup'd and inherited fds share an open file description
+        let description = |pid: u32, fd: i64| table.resolve(Some(pid), fd, 1000009).description;
+        assert_eq!(description(10, 0), description(10, 5));
+        assert_eq!(description(12, 6), description(10, 6));
+        assert_ne!(description(10, 5), description(10, 6));
+    }
+
+    #[test]
+    fn test_byte_counts() {
+        let mut table = FdTable::default();
+        let mut parser = LineParser::default();
+        for line in [
+            "openat(AT_FDCWD, \"/etc/hosts\", O_RDONLY) = 3",
+            "read(3, \"127.0.0.1 localhost\\n\", 4096) = 20",
+            "read(3, \"\", 4096) = 0",
+            "close(3) = 0",
+            "openat(AT_FDCWD, \"/etc/hosts\", O_RDONLY) = 3",
+            "pread64(3, \"127\", 3, 0) = 3",
+            "socket(AF_INET, SOCK_STREAM, IPPROTO_TCP) = 4",
+            "sendto(4, \"hello\", 5, 0, NULL, 0) = 5",
+            "sendfile(4, 3, NULL, 100) = 17",
+            "write(1, \"ok\\n\", 3) = 3",
+            "write(1, \"ok\\n\", 3) = -1 EPIPE (Broken pipe)",
+        ] {
+            table.update(
+                &parser
+                    .parse_line(line)
+                    .and_then(Message::into_syscall)
+                    .unwrap(),
+            );
+        }
+
+        let bytes = |read, written| ByteCounts { read, written };
+        assert_eq!(
+            table.file_bytes().into_iter().collect::<Vec<_>>(),
+            vec![("/etc/hosts", bytes(40, 0))]
+        );
+        assert_eq!(table.resolve(None, 4, 0).bytes, bytes(0, 22));
+        assert_eq!(table.resolve(None, 1, 0).bytes, bytes(0, 3));
+    }
+
+    #[test]
+    fn test_working_directory() {
+        let mut table = FdTable::default();
+        table.set_cwd(None, "/home/me".to_string());
+        let mut parser = LineParser::default();
+        for line in [
+            "10 openat(AT_FDCWD, \"./data.json\", O_RDONLY) = 3",
+            "10 chdir(\"src/../build\") = 0",
+            "10 clone(child_stack=NULL, flags=SIGCHLD) = 11",
+            "10 openat(AT_FDCWD, \"data.json\", O_RDONLY) = 4",
+            "11 openat(AT_FDCWD, \"/tmp\", O_RDONLY|O_DIRECTORY) = 5",
+            "11 fchdir(5) = 0",
+            "11 openat(AT_FDCWD, \"../etc/data.json\", O_RDONLY) = 6",
+        ] {
+            table.update(
+                &parser
+                    .parse_line(line)
+                    .and_then(Message::into_syscall)
+                    .unwrap(),
+            );
+        }
+
+        assert_eq!(
+            table.file_bytes().into_keys().collect::<Vec<_>>(),
+            [
+                "/etc/data.json",
+                "/home/me/build/data.json",
+                "/home/me/data.json",
+                "/tmp"
+            ]
+        );
+        assert_eq!(table.absolute(Some(11), "x"), "/tmp/x");
+        // 12 was not seen, so it is assumed to be where tracing began
+        assert_eq!(table.absolute(Some(12), "x"), "/home/me/x");
+    }
+}
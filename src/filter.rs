@@ -0,0 +1,454 @@
+// Filters decide which syscalls are shown in the event view.
+//
+// A filter expression is a whitespace-separated list of terms, all of which must match:
+//
+//   openat,close       syscall is one of the listed names
+//   errno=ENOENT       syscall failed with the given errno
+//   failed             syscall failed with any errno
+//   fd=3               syscall takes file descriptor 3 as an argument
+//   pid=1234           syscall was made by process 1234
+//   match=REGEX        any string argument matches the regular expression, e.g.
+//                      match=\.sqlite(-wal)?$ (use \s for whitespace)
+//   path=REGEX         any path argument matches the regular expression
+//   port=5432          syscall has a socket address with port 5432 (e.g., connect or sendto)
+//   follow=1234:3@120  syscall involves fd 3 (or a duplicate of it) in process 1234 during its
+//                      lifetime, starting from event 120, which must be the event that created the
+//                      descriptor (the PID may be omitted if the trace has no PIDs)
+//   !<term>            negates a term
+//
+// The empty filter matches everything.
+
+use std::borrow::Cow;
+use std::cell::OnceCell;
+use std::collections::HashSet;
+use std::fmt;
+
+use anyhow::{anyhow, Result};
+use regex::bytes::Regex;
+
+use crate::net::{self, Endpoint};
+use crate::strace::{self, Syscall};
+use crate::syscalls;
+
+#[derive(Default)]
+pub struct Filter {
+    pub text: String,
+    terms: Vec<Term>,
+    follow: Option<FdFollow>,
+}
+
+enum Term {
+    Names(Vec<String>),
+    Errno(String),
+    Failed,
+    Fd(i64),
+    Pid(u32),
+    // matched against the arguments' bytes with strace's escapes resolved
+    Match(Regex),
+    Path(Regex),
+    Port(u16),
+    Not(Box<Term>),
+}
+
+/// Tracks the lifetime of a single file descriptor, including any duplicates made of it with
+/// `dup`, `dup2`, `dup3`, or `fcntl(F_DUPFD)`.
+struct FdFollow {
+    pid: Option<u32>,
+    fd: i64,
+    start: usize,
+    aliases: HashSet<i64>,
+    closed: bool,
+}
+
+impl Filter {
+    pub fn parse(text: &str) -> Result<Filter> {
+        let mut filter = Filter {
+            text: text.trim().to_string(),
+            ..Default::default()
+        };
+
+        for word in text.split_whitespace() {
+            if let Some(follow) = word.strip_prefix("follow=") {
+                if filter.follow.is_some() {
+                    return Err(anyhow!("only one follow= term is allowed"));
+                }
+                filter.follow = Some(FdFollow::parse(follow)?);
+            } else {
+                filter.terms.push(Term::parse(word)?);
+            }
+        }
+
+        Ok(filter)
+    }
+
+    /// Returns a filter that follows `fd` as used by the syscall at `index`, from the event that
+    /// created it until it is closed.
+    pub fn follow_fd(syscalls: &[Syscall], index: usize, fd: i64) -> Filter {
+        let pid = syscalls[index].pid;
+        let same_process = |i: &usize| syscalls[*i].pid == pid;
+        // if no event created it, then the descriptor was inherited (e.g., standard output)
+        let start = (0..=index)
+            .rev()
+            .filter(same_process)
+            .find(|i| syscalls::created_fds(&syscalls[*i]).contains(&fd))
+            .or_else(|| (0..=index).find(same_process))
+            .unwrap_or(0);
+        let follow = FdFollow::new(pid, fd, start);
+        Filter {
+            text: follow.to_string(),
+            terms: Vec::new(),
+            follow: Some(follow),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.terms.is_empty() && self.follow.is_none()
+    }
+
+    /// The syscall names that the filter picks out, if it picks them out by name alone, as strace
+    /// does (e.g., `-e inject=`).
+    pub fn names_only(&self) -> Option<&[String]> {
+        match (&self.terms[..], &self.follow) {
+            ([Term::Names(names)], None) => Some(names),
+            _ => None,
+        }
+    }
+
+    /// Clears any state accumulated by `matches`. Must be called before re-running the filter
+    /// over events that it has already seen.
+    pub fn reset(&mut self) {
+        if let Some(follow) = &mut self.follow {
+            *follow = FdFollow::new(follow.pid, follow.fd, follow.start);
+        }
+    }
+
+    /// Must be called on every event in order, since some terms depend on earlier events.
+    pub fn matches(&mut self, index: usize, syscall: &Syscall) -> bool {
+        if let Some(follow) = &mut self.follow {
+            if !follow.update(index, syscall) {
+                return false;
+            }
+        }
+
+        // shared by the terms, so that the payloads are unescaped at most once
+        let payloads = OnceCell::new();
+        self.terms.iter().all(|t| t.matches(syscall, &payloads))
+    }
+}
+
+impl Term {
+    fn parse(word: &str) -> Result<Term> {
+        if let Some(rest) = word.strip_prefix('!') {
+            return Ok(Term::Not(Box::new(Term::parse(rest)?)));
+        }
+
+        if word == "failed" {
+            return Ok(Term::Failed);
+        }
+
+        match word.split_once('=') {
+            Some(("errno", errno)) => Ok(Term::Errno(errno.to_string())),
+            Some(("fd", fd)) => Ok(Term::Fd(
+                fd.parse().map_err(|_| anyhow!("invalid fd: {:?}", fd))?,
+            )),
+            Some(("pid", pid)) => Ok(Term::Pid(
+                pid.parse().map_err(|_| anyhow!("invalid pid: {:?}", pid))?,
+            )),
+            Some(("match", pattern)) => Ok(Term::Match(parse_regex(pattern)?)),
+            Some(("path", pattern)) => Ok(Term::Path(parse_regex(pattern)?)),
+            Some(("port", port)) => Ok(Term::Port(
+                port.parse()
+                    .map_err(|_| anyhow!("invalid port: {:?}", port))?,
+            )),
+            Some((key, _)) => Err(anyhow!("unknown filter key: {:?}", key)),
+            None => Ok(Term::Names(
+                word.split(',').map(|s| s.to_string()).collect(),
+            )),
+        }
+    }
+
+    // `payloads` holds the syscall's payloads, with strace's escapes resolved, once a term needs it
+    fn matches<'a>(&self, syscall: &'a Syscall, payloads: &OnceCell<Vec<Cow<'a, [u8]>>>) -> bool {
+        match self {
+            Term::Names(names) => names.contains(&syscall.name),
+            Term::Errno(errno) => syscall.errno.as_ref() == Some(errno),
+            Term::Failed => syscall.errno.is_some(),
+            Term::Fd(fd) => syscalls::fd_args(syscall).contains(fd),
+            Term::Pid(pid) => syscall.pid == Some(*pid),
+            Term::Match(regex) => payloads
+                .get_or_init(|| unescaped_payloads(syscall))
+                .iter()
+                .any(|payload| regex.is_match(payload)),
+            Term::Path(regex) => syscalls::path_args(syscall)
+                .iter()
+                .any(|path| regex.is_match(&strace::unescape(path))),
+            Term::Port(port) => syscall.args.iter().any(|arg| {
+                matches!(net::decode_sockaddr(&arg.value), Some(Endpoint::Inet(_, p)) if p == *port)
+            }),
+            Term::Not(term) => !term.matches(syscall, payloads),
+        }
+    }
+}
+
+fn unescaped_payloads(syscall: &Syscall) -> Vec<Cow<'_, [u8]>> {
+    net::payloads(syscall)
+        .into_iter()
+        .map(|(text, _)| {
+            if text.contains('\\') {
+                Cow::Owned(strace::unescape(text))
+            } else {
+                Cow::Borrowed(text.as_bytes())
+            }
+        })
+        .collect()
+}
+
+fn parse_regex(pattern: &str) -> Result<Regex> {
+    Regex::new(pattern).map_err(|e| anyhow!("invalid regular expression {:?}: {}", pattern, e))
+}
+
+impl FdFollow {
+    fn new(pid: Option<u32>, fd: i64, start: usize) -> Self {
+        Self {
+            pid,
+            fd,
+            start,
+            aliases: HashSet::from([fd]),
+            closed: false,
+        }
+    }
+
+    fn parse(text: &str) -> Result<Self> {
+        let (fd, start) = text
+            .split_once('@')
+            .ok_or(anyhow!("expected follow=<pid>:<fd>@<event>"))?;
+        let (pid, fd) = match fd.split_once(':') {
+            Some((pid, fd)) => (
+                Some(pid.parse().map_err(|_| anyhow!("invalid pid: {:?}", pid))?),
+                fd,
+            ),
+            None => (None, fd),
+        };
+        let fd = fd.parse().map_err(|_| anyhow!("invalid fd: {:?}", fd))?;
+        let start = start
+            .parse()
+            .map_err(|_| anyhow!("invalid event number: {:?}", start))?;
+        Ok(Self::new(pid, fd, start))
+    }
+
+    fn update(&mut self, index: usize, syscall: &Syscall) -> bool {
+        // file descriptors are per-process
+        if index < self.start || self.closed || syscall.pid != self.pid {
+            return false;
+        }
+
+        if index == self.start {
+            // for inherited descriptors, the starting event need not involve the descriptor
+            return syscalls::created_fds(syscall).contains(&self.fd)
+                || syscalls::fd_args(syscall).contains(&self.fd);
+        }
+
+        let fds = syscalls::fd_args(syscall);
+        let involved = fds.iter().any(|fd| self.aliases.contains(fd));
+        let failed = syscall.errno.is_some();
+
+        if !involved {
+            // if the descriptor number is reused then we must have missed the close (e.g.,
+            // close_range or close-on-exec)
+            if syscalls::created_fds(syscall)
+                .iter()
+                .any(|fd| self.aliases.contains(fd))
+            {
+                self.closed = true;
+            }
+            return false;
+        }
+
+        if failed {
+            return true;
+        }
+
+        match syscall.name.as_str() {
+            "close" => {
+                for fd in &fds {
+                    self.aliases.remove(fd);
+                }
+            }
+            "dup2" | "dup3" if fds.len() == 2 => {
+                let (oldfd, newfd) = (fds[0], fds[1]);
+                if self.aliases.contains(&oldfd) {
+                    self.aliases.insert(newfd);
+                } else {
+                    // newfd is silently closed before being reused
+                    self.aliases.remove(&newfd);
+                }
+            }
+            _ => {
+                self.aliases.extend(syscalls::created_fds(syscall));
+            }
+        }
+
+        if self.aliases.is_empty() {
+            self.closed = true;
+        }
+        true
+    }
+}
+
+impl fmt::Display for FdFollow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.pid {
+            Some(pid) => write!(f, "follow={}:{}@{}", pid, self.fd, self.start),
+            None => write!(f, "follow={}@{}", self.fd, self.start),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::strace::{parse_syscall, LineParser, Message, Syscall};
+
+    use super::Filter;
+
+    fn parse_all(lines: &[&str]) -> Vec<Syscall> {
+        lines.iter().map(|l| parse_syscall(l, false)).collect()
+    }
+
+    fn matching(filter: &mut Filter, syscalls: &[Syscall]) -> Vec<usize> {
+        filter.reset();
+        (0..syscalls.len())
+            .filter(|i| filter.matches(*i, &syscalls[*i]))
+            .collect()
+    }
+
+    #[test]
+    fn test_filter_terms() {
+        let syscalls = parse_all(&[
+            "openat(AT_FDCWD, \"/a\", O_RDONLY) = 3",
+            "openat(AT_FDCWD, \"/b\", O_RDONLY) = -1 ENOENT (No such file or directory)",
+            "read(3, \"abc\", 4096) = 3",
+            "close(3) = 0",
+        ]);
+
+        let mut f = Filter::parse("").unwrap();
+        assert!(f.is_empty());
+        assert_eq!(matching(&mut f, &syscalls), vec![0, 1, 2, 3]);
+
+        f = Filter::parse("openat").unwrap();
+        assert_eq!(matching(&mut f, &syscalls), vec![0, 1]);
+
+        f = Filter::parse("openat !failed").unwrap();
+        assert_eq!(matching(&mut f, &syscalls), vec![0]);
+
+        f = Filter::parse("errno=ENOENT").unwrap();
+        assert_eq!(matching(&mut f, &syscalls), vec![1]);
+
+        f = Filter::parse("read,close fd=3").unwrap();
+        assert_eq!(matching(&mut f, &syscalls), vec![2, 3]);
+
+        f = Filter::parse("match=^ab").unwrap();
+        assert_eq!(matching(&mut f, &syscalls), vec![2]);
+
+        f = Filter::parse("path=^/[ab]$ !failed").unwrap();
+        assert_eq!(matching(&mut f, &syscalls), vec![0]);
+
+        let connect = parse_all(&[
+            "connect(3, {sa_family=AF_INET, sin_port=htons(5432), sin_addr=inet_addr(\"127.0.0.1\")}, 16) = 0",
+            "connect(4, {sa_family=AF_INET, sin_port=htons(80), sin_addr=inet_addr(\"127.0.0.1\")}, 16) = 0",
+        ]);
+        f = Filter::parse("connect port=5432").unwrap();
+        assert_eq!(matching(&mut f, &connect), vec![0]);
+
+        assert!(Filter::parse("match=(").is_err());
+        assert!(Filter::parse("fd=abc").is_err());
+        assert!(Filter::parse("bogus=1").is_err());
+    }
+
+    #[test]
+    fn test_filter_match() {
+        let syscalls = parse_all(&[
+            "read(3, \"\\177ELF\\2\\1\", 4096) = 4",
+            "write(1, \"hello\\nworld\", 11) = 11",
+            "sendmsg(4, {msg_iov=[{iov_base=\"GET /\", iov_len=5}], msg_iovlen=1}, 0) = 5",
+            "close(3) = 0",
+        ]);
+
+        // payloads are matched with strace's escapes resolved
+        let mut f = Filter::parse("match=^\\x7fELF").unwrap();
+        assert_eq!(matching(&mut f, &syscalls), vec![0]);
+        f = Filter::parse("match=hello\\sworld").unwrap();
+        assert_eq!(matching(&mut f, &syscalls), vec![1]);
+        f = Filter::parse("match=^GET").unwrap();
+        assert_eq!(matching(&mut f, &syscalls), vec![2]);
+
+        // several match terms share the unescaped payloads
+        f = Filter::parse("match=hello match=world !match=ELF").unwrap();
+        assert_eq!(matching(&mut f, &syscalls), vec![1]);
+        f = Filter::parse("!match=.").unwrap();
+        assert_eq!(matching(&mut f, &syscalls), vec![3]);
+    }
+
+    #[test]
+    fn test_follow_fd() {
+        let syscalls = parse_all(&[
+            "openat(AT_FDCWD, \"/a\", O_RDONLY) = 3",
+            "openat(AT_FDCWD, \"/b\", O_RDONLY) = 4",
+            "dup(3) = 5",
+            "fcntl(5, F_DUPFD_CLOEXEC, 10) = 10",
+            "close(3) = 0",
+            "read(4, \"\", 4096) = 0",
+            "read(10, \"abc\", 4096) = 3",
+            "dup2(4, 5) = 5",
+            "read(5, \"\", 4096) = 0",
+            "close(10) = 0",
+            "openat(AT_FDCWD, \"/c\", O_RDONLY) = 3",
+            "read(3, \"\", 4096) = 0",
+        ]);
+
+        let f = Filter::follow_fd(&syscalls, 6, 10);
+        assert_eq!(f.text, "follow=10@3");
+        let mut f = Filter::follow_fd(&syscalls, 2, 3);
+        assert_eq!(f.text, "follow=3@0");
+        assert_eq!(matching(&mut f, &syscalls), vec![0, 2, 3, 4, 6, 7, 9]);
+
+        // round-trips through the textual form
+        let mut f = Filter::parse("follow=3@0").unwrap();
+        assert_eq!(matching(&mut f, &syscalls), vec![0, 2, 3, 4, 6, 7, 9]);
+
+        let mut f = Filter::parse("follow=3@0 read").unwrap();
+        assert_eq!(matching(&mut f, &syscalls), vec![6]);
+    }
+
+    #[test]
+    fn test_follow_fd_per_process() {
+        let mut parser = LineParser::default();
+        let syscalls: Vec<Syscall> = [
+            "10 read(0, \"\", 1) = 0",
+            "10 openat(AT_FDCWD, \"/a\", O_RDONLY) = 3",
+            "11 openat(AT_FDCWD, \"/b\", O_RDONLY) = 3",
+            "11 close(3) = 0",
+            "10 read(3, \"abc\", 4096) = 3",
+            "11 read(0, \"\", 1) = 0",
+        ]
+        .iter()
+        .map(|l| {
+            parser
+                .parse_line(l)
+                .and_then(Message::into_syscall)
+                .unwrap()
+        })
+        .collect();
+
+        let mut f = Filter::follow_fd(&syscalls, 4, 3);
+        assert_eq!(f.text, "follow=10:3@1");
+        assert_eq!(matching(&mut f, &syscalls), vec![1, 4]);
+
+        // inherited descriptors are followed from the process's first event
+        let mut f = Filter::follow_fd(&syscalls, 5, 0);
+        assert_eq!(f.text, "follow=11:0@2");
+        assert_eq!(matching(&mut f, &syscalls), vec![5]);
+
+        let mut f = Filter::parse("pid=11").unwrap();
+        assert_eq!(matching(&mut f, &syscalls), vec![2, 3, 5]);
+    }
+}
@@ -0,0 +1,261 @@
+// Aggregates the stacks that `strace -k` prints after each syscall (see `vistrace run --stacks`),
+// weighted by the time spent in the syscall, to show which code paths are responsible for the
+// expensive syscalls.
+//
+// Stacks can be written in the folded format of Brendan Gregg's flamegraph.pl, which speedscope
+// and most other flamegraph tools read:
+//
+//   main;copy;__write;write 1500
+//
+// i.e., the frames from the outermost in, ending in the syscall itself, and the total microseconds
+// spent in syscalls with that stack. They can also be drawn as an SVG flamegraph directly.
+
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+use std::path::Path;
+
+use crate::strace::{Frame, Message};
+
+const SVG_WIDTH: f64 = 1200.0;
+const ROW_HEIGHT: f64 = 16.0;
+// roughly the width of a character at the font size below, to decide how much of a name fits
+const CHAR_WIDTH: f64 = 7.0;
+// boxes narrower than this (in pixels) are left out
+const MIN_WIDTH: f64 = 0.1;
+
+#[derive(Default)]
+pub struct Stacks {
+    // the total microseconds of each stack, from the outermost frame to the syscall
+    stacks: BTreeMap<Vec<String>, u64>,
+    // the last syscall, as its pid, its name, and its microseconds, and its frames so far
+    // (innermost first)
+    last: Option<(Option<u32>, String, u64)>,
+    frames: Vec<String>,
+}
+
+// a box in the flamegraph, and the boxes above it
+#[derive(Default)]
+struct Node {
+    micros: u64,
+    children: BTreeMap<String, Node>,
+}
+
+impl Stacks {
+    pub fn update(&mut self, msg: &Message) {
+        match msg {
+            Message::Syscall(syscall) => {
+                self.finish();
+                self.last = Some((
+                    syscall.pid,
+                    syscall.name.clone(),
+                    syscall.syscall_time_micros.unwrap_or(0),
+                ));
+            }
+            Message::Frame(frame) => {
+                if matches!(&self.last, Some((pid, _, _)) if *pid == frame.pid) {
+                    self.frames.push(frame_name(frame));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Adds the last syscall's stack. Must be called at the end of the trace.
+    pub fn finish(&mut self) {
+        let frames = std::mem::take(&mut self.frames);
+        if let Some((_, name, micros)) = self.last.take() {
+            // syscalls without a stack (e.g., if the trace was recorded without --stacks) are left
+            // out, rather than shown as a flat bar for each syscall
+            if !frames.is_empty() {
+                let mut stack: Vec<String> = frames.into_iter().rev().collect();
+                stack.push(name);
+                *self.stacks.entry(stack).or_default() += micros;
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.stacks.is_empty()
+    }
+
+    pub fn write_folded(&self, w: &mut impl Write) -> io::Result<()> {
+        for (stack, micros) in &self.stacks {
+            writeln!(w, "{} {}", stack.join(";"), micros)?;
+        }
+        Ok(())
+    }
+
+    pub fn write_svg(&self, w: &mut impl Write) -> io::Result<()> {
+        let mut root = Node::default();
+        for (stack, micros) in &self.stacks {
+            root.micros += micros;
+            let mut node = &mut root;
+            for frame in stack {
+                node = node.children.entry(frame.clone()).or_default();
+                node.micros += micros;
+            }
+        }
+
+        let height = (root.depth() + 1) as f64 * ROW_HEIGHT;
+        writeln!(
+            w,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{0}" height="{1}" viewBox="0 0 {0} {1}">"#,
+            SVG_WIDTH, height
+        )?;
+        writeln!(
+            w,
+            "<style>text {{ font-family: monospace; font-size: 12px; pointer-events: none; }}</style>"
+        )?;
+        let total = root.micros.max(1) as f64;
+        write_node(w, "all syscalls", &root, 0.0, height - ROW_HEIGHT, total)?;
+        writeln!(w, "</svg>")
+    }
+}
+
+impl Node {
+    fn depth(&self) -> usize {
+        self.children
+            .values()
+            .map(|child| child.depth() + 1)
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+// draws `node` at (`x`, `y`), and its children in the row above it, left to right
+fn write_node(
+    w: &mut impl Write,
+    name: &str,
+    node: &Node,
+    x: f64,
+    y: f64,
+    total: f64,
+) -> io::Result<()> {
+    let width = node.micros as f64 / total * SVG_WIDTH;
+    if width < MIN_WIDTH {
+        return Ok(());
+    }
+    let title = format!(
+        "{} ({} µs, {:.2}%)",
+        name,
+        node.micros,
+        node.micros as f64 / total * 100.0
+    );
+    writeln!(w, "<g>")?;
+    writeln!(w, "<title>{}</title>", escape(&title))?;
+    writeln!(
+        w,
+        r#"<rect x="{:.1}" y="{:.1}" width="{:.1}" height="{:.1}" fill="{}" rx="2"/>"#,
+        x,
+        y,
+        width,
+        ROW_HEIGHT - 1.0,
+        color(name)
+    )?;
+    let fits = ((width - 6.0) / CHAR_WIDTH) as usize;
+    if fits >= 3 {
+        let text: String = if name.chars().count() > fits {
+            let mut text: String = name.chars().take(fits - 2).collect();
+            text.push_str("..");
+            text
+        } else {
+            name.to_string()
+        };
+        writeln!(
+            w,
+            r#"<text x="{:.1}" y="{:.1}">{}</text>"#,
+            x + 3.0,
+            y + ROW_HEIGHT - 4.0,
+            escape(&text)
+        )?;
+    }
+    writeln!(w, "</g>")?;
+
+    let mut child_x = x;
+    for (child_name, child) in &node.children {
+        write_node(w, child_name, child, child_x, y - ROW_HEIGHT, total)?;
+        child_x += child.micros as f64 / total * SVG_WIDTH;
+    }
+    Ok(())
+}
+
+// the function, or if strace could not find it, the file name of the module in brackets (as perf
+// does), so that unknown frames in the same module are merged
+fn frame_name(frame: &Frame) -> String {
+    match &frame.function {
+        Some(function) => function.clone(),
+        None => {
+            let module = Path::new(&frame.module)
+                .file_name()
+                .map_or(frame.module.clone(), |f| f.to_string_lossy().into_owned());
+            format!("[{}]", module)
+        }
+    }
+}
+
+// a warm color that is the same for every box with the same name, as in flamegraph.pl
+fn color(name: &str) -> String {
+    let hash = name
+        .bytes()
+        .fold(5381u32, |h, b| h.wrapping_mul(33).wrapping_add(b as u32));
+    format!(
+        "rgb({},{},{})",
+        205 + hash % 50,
+        hash / 50 % 180,
+        hash / 9000 % 55
+    )
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::strace::LineParser;
+
+    use super::Stacks;
+
+    #[test]
+    fn test_stacks() {
+        let mut stacks = Stacks::default();
+        let mut parser = LineParser::default();
+        for line in [
+            "10 1.000000 write(1, \"hi\\n\", 3) = 3 <0.000100>",
+            " > /usr/lib/libc.so.6(__write+0x14) [0x114887]",
+            " > /usr/bin/cat(copy+0x20) [0x4f8e]",
+            " > /usr/bin/cat() [0x4f00]",
+            "10 1.000200 write(1, \"hi\\n\", 3) = 3 <0.000050>",
+            " > /usr/lib/libc.so.6(__write+0x14) [0x114887]",
+            " > /usr/bin/cat(copy+0x20) [0x4f8e]",
+            " > /usr/bin/cat() [0x4f00]",
+            "10 1.000300 read(0, \"\", 4096) = 0 <0.000010>",
+            " > /usr/lib/libc.so.6(read+0x10) [0x114800]",
+            " > /usr/bin/cat() [0x4f00]",
+            "10 1.000400 close(0) = 0 <0.000001>",
+        ] {
+            if let Some(msg) = parser.parse_line(line) {
+                stacks.update(&msg);
+            }
+        }
+        stacks.finish();
+
+        let mut out = Vec::new();
+        stacks.write_folded(&mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "[cat];copy;__write;write 150\n[cat];read;read 10\n"
+        );
+
+        let mut out = Vec::new();
+        stacks.write_svg(&mut out).unwrap();
+        let svg = String::from_utf8(out).unwrap();
+        assert!(svg.contains("<title>all syscalls (160 µs, 100.00%)</title>"));
+        assert!(svg.contains("<title>[cat] (160 µs, 100.00%)</title>"));
+        assert!(svg.contains("<title>__write (150 µs, 93.75%)</title>"));
+        assert!(svg.ends_with("</svg>\n"));
+    }
+}
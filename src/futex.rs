@@ -0,0 +1,124 @@
+// Adds up the futex calls on each address, which is usually a lock or a condition variable, to show
+// from the outside which locks threads spend their time waiting on and which threads contend for
+// them.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::strace::{Syscall, SyscallArgValue};
+use crate::syscalls;
+
+#[derive(Default)]
+pub struct Futexes {
+    // keyed by address
+    pub futexes: BTreeMap<i64, FutexStats>,
+}
+
+#[derive(Default)]
+pub struct FutexStats {
+    pub waits: usize,
+    // the total time spent in waits (including ones that timed out or were interrupted)
+    pub wait_micros: u64,
+    pub wakes: usize,
+    // the number of waiters that the wakes woke up
+    pub woken: u64,
+    // the threads that waited on the futex
+    pub waiters: BTreeSet<u32>,
+    // the threads that woke it
+    pub wakers: BTreeSet<u32>,
+}
+
+impl Futexes {
+    pub fn update(&mut self, syscall: &Syscall) {
+        if syscall.name != "futex" || syscall.error_details.is_some() {
+            return;
+        }
+        let address = match syscall.arg(0).map(|a| &a.value) {
+            Some(SyscallArgValue::Number(address)) => *address,
+            _ => return,
+        };
+        let op = match syscall.arg(1) {
+            Some(arg) => &arg.value,
+            None => return,
+        };
+        let is_op = |names: &[&str]| {
+            names.iter().any(|name| {
+                syscalls::value_has_flag(op, name)
+                    || syscalls::value_has_flag(op, &format!("{}_PRIVATE", name))
+            })
+        };
+
+        if is_op(&[
+            "FUTEX_WAIT",
+            "FUTEX_WAIT_BITSET",
+            "FUTEX_LOCK_PI",
+            "FUTEX_LOCK_PI2",
+        ]) {
+            let stats = self.futexes.entry(address).or_default();
+            stats.waits += 1;
+            stats.wait_micros += syscall.syscall_time_micros.unwrap_or(0);
+            stats.waiters.extend(syscall.pid);
+        } else if is_op(&["FUTEX_WAKE", "FUTEX_WAKE_BITSET", "FUTEX_UNLOCK_PI"]) {
+            let stats = self.futexes.entry(address).or_default();
+            stats.wakes += 1;
+            if syscall.errno.is_none() {
+                stats.woken += syscall.return_value.max(0) as u64;
+            }
+            stats.wakers.extend(syscall.pid);
+        }
+    }
+
+    /// The futexes that more than one thread used, most waited on first.
+    pub fn contended(&self) -> Vec<(i64, &FutexStats)> {
+        let mut futexes: Vec<(i64, &FutexStats)> = self
+            .futexes
+            .iter()
+            .filter(|(_, stats)| stats.waiters.union(&stats.wakers).count() > 1)
+            .map(|(address, stats)| (*address, stats))
+            .collect();
+        futexes.sort_by_key(|(address, stats)| {
+            (
+                std::cmp::Reverse((stats.wait_micros, stats.waits)),
+                *address,
+            )
+        });
+        futexes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::strace::{LineParser, Message};
+
+    use super::Futexes;
+
+    #[test]
+    fn test_futexes() {
+        let mut futexes = Futexes::default();
+        let mut parser = LineParser::default();
+        for line in [
+            "11 1700000000.000001 futex(0x7f0000001000, FUTEX_WAIT_PRIVATE, 2, NULL) = 0 <0.500000>",
+            "12 1700000000.000002 futex(0x7f0000001000, FUTEX_WAIT_BITSET_PRIVATE|FUTEX_CLOCK_REALTIME, 2, NULL, FUTEX_BITSET_MATCH_ANY) = -1 EAGAIN (Resource temporarily unavailable) <0.000002>",
+            "10 1700000000.000003 futex(0x7f0000001000, FUTEX_WAKE_PRIVATE, 1) = 1 <0.000010>",
+            // only one thread uses this one
+            "10 1700000000.000004 futex(0x7f0000002000, FUTEX_WAIT, 0, NULL) = 0 <1.000000>",
+            "10 1700000000.000005 futex(0x7f0000002000, FUTEX_WAKE, 1) = 0 <0.000010>",
+        ] {
+            let syscall = parser
+                .parse_line(line)
+                .and_then(Message::into_syscall)
+                .unwrap();
+            futexes.update(&syscall);
+        }
+
+        let contended = futexes.contended();
+        assert_eq!(contended.len(), 1);
+        let (address, stats) = contended[0];
+        assert_eq!(address, 0x7f0000001000);
+        assert_eq!(
+            (stats.waits, stats.wait_micros, stats.wakes, stats.woken),
+            (2, 500_002, 1, 1)
+        );
+        assert_eq!(stats.waiters.iter().copied().collect::<Vec<_>>(), [11, 12]);
+        assert_eq!(stats.wakers.iter().copied().collect::<Vec<_>>(), [10]);
+    }
+}
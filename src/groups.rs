@@ -0,0 +1,199 @@
+// Groups events by a key (the syscall's name, the path it used, its errno, its process, or its fd)
+// and adds up each group's calls, errors, bytes, and time, like a pivot table. The interface shows
+// the groups (see src/table.rs) and lets you drill down into one by grouping its events by another
+// key.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::fdtable::{FdKind, FdTable};
+use crate::strace::Syscall;
+use crate::syscalls::{self, Io};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Key {
+    Name,
+    Path,
+    Errno,
+    Pid,
+    Fd,
+}
+
+pub const KEYS: [Key; 5] = [Key::Name, Key::Path, Key::Errno, Key::Pid, Key::Fd];
+
+// the group of the events that have no value for the key, e.g. no path
+pub const NONE: &str = "(none)";
+
+pub struct Group {
+    // e.g., "openat", "/etc/hosts", or "ENOENT"
+    pub value: String,
+    // indices into the syscalls, in order
+    pub indices: Vec<usize>,
+    pub errors: usize,
+    pub read: u64,
+    pub written: u64,
+    pub micros: u64,
+}
+
+/// Groups the syscalls at `indices` by `key`, the groups with the most calls first. Must be called
+/// with `fds` updated with all of the syscalls.
+pub fn group(syscalls: &[Syscall], indices: &[usize], key: Key, fds: &FdTable) -> Vec<Group> {
+    let mut groups: Vec<Group> = Vec::new();
+    let mut positions: HashMap<String, usize> = HashMap::new();
+    for index in indices {
+        let syscall = &syscalls[*index];
+        let value = key.value(syscall, fds).unwrap_or_else(|| NONE.to_string());
+        let position = *positions.entry(value.clone()).or_insert_with(|| {
+            groups.push(Group {
+                value,
+                indices: Vec::new(),
+                errors: 0,
+                read: 0,
+                written: 0,
+                micros: 0,
+            });
+            groups.len() - 1
+        });
+        let group = &mut groups[position];
+        group.indices.push(*index);
+        group.micros += syscall.syscall_time_micros.unwrap_or(0);
+        if syscall.errno.is_some() {
+            group.errors += 1;
+            continue;
+        }
+        let bytes = syscall.return_value.max(0) as u64;
+        match syscalls::io_direction(&syscall.name) {
+            Some(Io::Read) => group.read += bytes,
+            Some(Io::Write) => group.written += bytes,
+            None => {}
+        }
+    }
+    groups.sort_by(|a, b| {
+        b.indices
+            .len()
+            .cmp(&a.indices.len())
+            .then_with(|| a.value.cmp(&b.value))
+    });
+    groups
+}
+
+impl Key {
+    fn value(&self, syscall: &Syscall, fds: &FdTable) -> Option<String> {
+        let time = syscall.entry_time_micros;
+        let fd = syscalls::fd_args(syscall).first().copied();
+        match self {
+            Key::Name => Some(syscall.name.clone()),
+            Key::Path => {
+                if let Some(path) = syscalls::path_args(syscall).first() {
+                    return Some(fds.absolute(syscall.pid, path));
+                }
+                match fds.resolve(syscall.pid, fd?, time).kind {
+                    FdKind::File(path) => Some(path),
+                    _ => None,
+                }
+            }
+            // a line that could not be parsed is not known to have failed or succeeded
+            Key::Errno if syscall.error_details.is_some() => None,
+            Key::Errno => Some(
+                syscall
+                    .errno
+                    .clone()
+                    .unwrap_or_else(|| "(succeeded)".to_string()),
+            ),
+            Key::Pid => syscall.pid.map(|pid| pid.to_string()),
+            // the fd with what it referred to, since fd numbers are reused
+            Key::Fd => {
+                let fd = fd?;
+                Some(match fds.resolve(syscall.pid, fd, time).kind {
+                    FdKind::Unknown => fd.to_string(),
+                    kind => format!("{} ({})", fd, kind),
+                })
+            }
+        }
+    }
+
+    /// The key to drill down by after this one.
+    pub fn next(&self) -> Key {
+        let i = KEYS.iter().position(|k| k == self).unwrap_or(0);
+        KEYS[(i + 1) % KEYS.len()]
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Key::Name => write!(f, "name"),
+            Key::Path => write!(f, "path"),
+            Key::Errno => write!(f, "errno"),
+            Key::Pid => write!(f, "pid"),
+            Key::Fd => write!(f, "fd"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fdtable::FdTable;
+    use crate::strace::{parse_syscall, Syscall};
+
+    use super::{group, Key, NONE};
+
+    #[test]
+    fn test_group() {
+        let mut fds = FdTable::default();
+        let syscalls: Vec<Syscall> = [
+            "1.000000 openat(AT_FDCWD, \"/etc/hosts\", O_RDONLY) = 3 <0.000010>",
+            "1.000001 read(3, \"127.0.0.1 localhost\\n\", 4096) = 20 <0.000005>",
+            "1.000002 read(3, \"\", 4096) = 0 <0.000001>",
+            "1.000003 close(3) = 0 <0.000001>",
+            "1.000004 openat(AT_FDCWD, \"/etc/missing\", O_RDONLY) = -1 ENOENT (No such file or directory) <0.000004>",
+        ]
+        .iter()
+        .map(|line| {
+            let syscall = parse_syscall(line, true);
+            fds.update(&syscall);
+            syscall
+        })
+        .collect();
+        let all: Vec<usize> = (0..syscalls.len()).collect();
+
+        let by_name = group(&syscalls, &all, Key::Name, &fds);
+        let rows: Vec<(&str, usize, usize, u64, u64)> = by_name
+            .iter()
+            .map(|g| {
+                (
+                    g.value.as_str(),
+                    g.indices.len(),
+                    g.errors,
+                    g.read,
+                    g.micros,
+                )
+            })
+            .collect();
+        assert_eq!(
+            rows,
+            [
+                ("openat", 2, 1, 0, 14),
+                ("read", 2, 0, 20, 6),
+                ("close", 1, 0, 0, 1)
+            ]
+        );
+
+        let by_path = group(&syscalls, &by_name[1].indices, Key::Path, &fds);
+        assert_eq!(by_path.len(), 1);
+        assert_eq!(by_path[0].value, "/etc/hosts");
+
+        let by_errno = group(&syscalls, &all, Key::Errno, &fds);
+        assert_eq!(by_errno[1].value, "ENOENT");
+        assert_eq!(by_errno[1].indices, [4]);
+    }
+
+    #[test]
+    fn test_group_malformed() {
+        let fds = FdTable::default();
+        let syscalls = [parse_syscall("1.000000 openat(AT_FDCWD, \"/etc/hos", true)];
+        let by_errno = group(&syscalls, &[0], Key::Errno, &fds);
+        assert_eq!(by_errno.len(), 1);
+        assert_eq!(by_errno[0].value, NONE);
+    }
+}
@@ -0,0 +1,123 @@
+// Draws when each kind of syscall was made over the course of the trace, as a grid of time buckets
+// (columns) by syscall (rows), shaded by how many calls fell in each bucket. Phases of a program
+// stand out: a burst of openat and mmap at startup, then reads and writes in its steady state, then
+// closes and unlinks as it shuts down.
+//
+// Each row is shaded relative to its own busiest bucket, so that rare syscalls show up as clearly
+// as common ones.
+
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+
+use crate::strace::{format_timestamp, Syscall};
+use crate::units;
+
+// from no calls to the row's busiest bucket
+const SHADES: &[char] = &[' ', '░', '▒', '▓', '█'];
+// the busiest syscalls that get a row
+const MAX_ROWS: usize = 20;
+// the fewest calls that are worth a heatmap
+pub const MIN_CALLS: usize = 100;
+
+#[derive(Default)]
+pub struct Heatmap {
+    // the start time of each call to each syscall
+    times: BTreeMap<String, Vec<u64>>,
+    calls: usize,
+}
+
+impl Heatmap {
+    pub fn update(&mut self, syscall: &Syscall) {
+        // without timestamps, there is nothing to place the call by
+        if syscall.entry_time_micros == 0 {
+            return;
+        }
+        self.times
+            .entry(syscall.name.clone())
+            .or_default()
+            .push(syscall.entry_time_micros);
+        self.calls += 1;
+    }
+
+    pub fn calls(&self) -> usize {
+        self.calls
+    }
+
+    /// Writes the heatmap, `width` columns wide (not counting the syscall names), with a row for
+    /// each of the busiest syscalls.
+    pub fn write(&self, w: &mut impl Write, width: usize) -> io::Result<()> {
+        let all = self.times.values().flatten();
+        let (start, end) = match (all.clone().min(), all.max()) {
+            (Some(start), Some(end)) => (*start, *end),
+            _ => return Ok(()),
+        };
+        let span = end - start + 1;
+        let width = width.max(1);
+
+        let mut rows: Vec<(&String, &Vec<u64>)> = self.times.iter().collect();
+        rows.sort_by_key(|(name, times)| (std::cmp::Reverse(times.len()), *name));
+        rows.truncate(MAX_ROWS);
+        let name_width = rows.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+
+        writeln!(
+            w,
+            "{:name_width$}  {} to {} ({} per column)",
+            "syscall",
+            format_timestamp(start),
+            format_timestamp(end),
+            units::duration(span / width as u64)
+        )?;
+        for (name, times) in rows {
+            let mut buckets = vec![0usize; width];
+            for time in times {
+                let column = ((time - start) as u128 * width as u128 / span as u128) as usize;
+                buckets[column] += 1;
+            }
+            let busiest = buckets.iter().copied().max().unwrap_or(1);
+            let cells: String = buckets
+                .iter()
+                .map(|count| {
+                    // any call at all gets at least the lightest shade
+                    let level = (count * (SHADES.len() - 1)).div_ceil(busiest);
+                    SHADES[level]
+                })
+                .collect();
+            writeln!(w, "{:name_width$} |{}|", name, cells)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::strace::parse_syscall;
+
+    use super::Heatmap;
+
+    #[test]
+    fn test_heatmap() {
+        let mut heatmap = Heatmap::default();
+        let lines = [
+            "1.000000 openat(AT_FDCWD, \"/a\", O_RDONLY) = 3",
+            "1.100000 openat(AT_FDCWD, \"/b\", O_RDONLY) = 4",
+            "1.200000 openat(AT_FDCWD, \"/c\", O_RDONLY) = 5",
+            "2.000000 read(3, \"x\", 1) = 1",
+            "2.500000 read(3, \"x\", 1) = 1",
+            "3.900000 close(3) = 0",
+        ];
+        for line in lines {
+            heatmap.update(&parse_syscall(line, true));
+        }
+        assert_eq!(heatmap.calls(), 6);
+
+        let mut out = Vec::new();
+        heatmap.write(&mut out, 4).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "syscall  1.000000 to 3.900000 (725 ms per column)\n\
+             openat |█   |\n\
+             read   | ██ |\n\
+             close  |   █|\n"
+        );
+    }
+}
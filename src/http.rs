@@ -0,0 +1,198 @@
+// Spots HTTP/1.x request and status lines in the data sent and received on sockets, and pairs
+// them up into exchanges such as "GET /health → 503 Service Unavailable".
+//
+// Only the start of each buffer is checked, so a request line is found only if it begins a
+// read or write, which is almost always the case. HTTPS traffic is encrypted and is not seen.
+
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+
+use crate::fdtable::FdTable;
+use crate::net::{self, Connections};
+use crate::strace::{self, Syscall, SyscallArgValue};
+use crate::syscalls;
+
+const METHODS: &[&str] = &[
+    "GET", "HEAD", "POST", "PUT", "DELETE", "CONNECT", "OPTIONS", "TRACE", "PATCH",
+];
+
+#[derive(Default)]
+pub struct Exchanges {
+    pub exchanges: Vec<Exchange>,
+    // exchanges still waiting for a response, by index into `Connections::connections`, oldest
+    // first (HTTP/1.1 clients may pipeline requests)
+    pending: HashMap<usize, VecDeque<usize>>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Exchange {
+    // index into `Connections::connections`
+    pub connection: usize,
+    // the process that sent or received the request
+    pub pid: Option<u32>,
+    // e.g., "GET /health", or `None` if the request was not seen
+    pub request: Option<String>,
+    // e.g., "503 Service Unavailable", or `None` if there was no response (yet)
+    pub status: Option<String>,
+}
+
+impl Exchanges {
+    /// Returns the index into `exchanges` of the exchange that `syscall` started or completed,
+    /// if any. `fds` and `net` must have already been updated with `syscall`.
+    pub fn update(&mut self, syscall: &Syscall, fds: &FdTable, net: &Connections) -> Option<usize> {
+        let io = syscalls::io_direction(&syscall.name).is_some()
+            || matches!(syscall.name.as_str(), "sendmmsg" | "recvmmsg");
+        if syscall.errno.is_some() || !io {
+            return None;
+        }
+        let fd = match syscall.arg(0).map(|a| &a.value) {
+            Some(SyscallArgValue::Number(fd)) => *fd,
+            _ => return None,
+        };
+        let connection = net.index(syscall.pid, fd, fds)?;
+        if net.connections[connection].protocol == "udp" {
+            return None;
+        }
+
+        // a client writes requests and reads responses, and a server does the opposite, so the
+        // direction does not matter
+        let (text, _) = net::payloads(syscall).into_iter().next()?;
+        let line = first_line(&strace::unescape(text));
+        if let Some(request) = request_line(&line) {
+            self.exchanges.push(Exchange {
+                connection,
+                pid: syscall.pid,
+                request: Some(request),
+                status: None,
+            });
+            let index = self.exchanges.len() - 1;
+            self.pending.entry(connection).or_default().push_back(index);
+            Some(index)
+        } else if let Some(status) = status_line(&line) {
+            // "100 Continue" precedes the real response
+            let informational = status.starts_with('1');
+            let pending = self.pending.entry(connection).or_default();
+            let index = if informational {
+                pending.front().copied()
+            } else {
+                pending.pop_front()
+            };
+            let index = match index {
+                Some(index) => index,
+                // the request was sent before tracing began
+                None => {
+                    self.exchanges.push(Exchange {
+                        connection,
+                        pid: syscall.pid,
+                        request: None,
+                        status: None,
+                    });
+                    self.exchanges.len() - 1
+                }
+            };
+            self.exchanges[index].status = Some(status);
+            Some(index)
+        } else {
+            None
+        }
+    }
+}
+
+fn first_line(data: &[u8]) -> String {
+    let end = data
+        .iter()
+        .position(|b| *b == b'\r' || *b == b'\n')
+        .unwrap_or(data.len());
+    String::from_utf8_lossy(&data[..end]).into_owned()
+}
+
+// e.g., "GET /health HTTP/1.1" → "GET /health"
+fn request_line(line: &str) -> Option<String> {
+    let mut words = line.split(' ');
+    let method = words.next()?;
+    let target = words.next()?;
+    let version = words.next()?;
+    if !METHODS.contains(&method) || target.is_empty() || !version.starts_with("HTTP/1.") {
+        return None;
+    }
+    Some(format!("{} {}", method, target))
+}
+
+// e.g., "HTTP/1.1 503 Service Unavailable" → "503 Service Unavailable"
+fn status_line(line: &str) -> Option<String> {
+    let (version, status) = line.split_once(' ')?;
+    let code = status.split(' ').next()?;
+    if !version.starts_with("HTTP/1.") || code.len() != 3 || code.parse::<u16>().is_err() {
+        return None;
+    }
+    Some(status.trim_end().to_string())
+}
+
+impl fmt::Display for Exchange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} → {}",
+            self.request.as_deref().unwrap_or("?"),
+            self.status.as_deref().unwrap_or("(no response)")
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fdtable::FdTable;
+    use crate::net::Connections;
+    use crate::strace::parse_syscall;
+
+    use super::Exchanges;
+
+    #[test]
+    fn test_exchanges() {
+        let mut fds = FdTable::default();
+        let mut net = Connections::default();
+        let mut exchanges = Exchanges::default();
+        let mut updates = Vec::new();
+        for line in [
+            "socket(AF_INET, SOCK_STREAM, IPPROTO_TCP) = 3",
+            r#"connect(3, {sa_family=AF_INET, sin_port=htons(8080), sin_addr=inet_addr("127.0.0.1")}, 16) = 0"#,
+            r#"write(3, "GET /health HTTP/1.1\r\nHost: localhost\r\n\r\n", 39) = 39"#,
+            r#"write(3, "POST /items HTTP/1.1\r\nExpect: 100-continue\r\n\r\n", 46) = 46"#,
+            // not HTTP
+            r#"write(3, "{\"name\": \"x\"}", 13) = 13"#,
+            r#"read(3, "HTTP/1.1 503 Service Unavailable\r\nContent-Length: 0\r\n\r\n", 4096) = 55"#,
+            r#"read(3, "HTTP/1.1 100 Continue\r\n\r\n", 4096) = 25"#,
+            r#"read(3, "HTTP/1.1 201 Created\r\n"..., 4096) = 120"#,
+            r#"writev(3, [{iov_base="DELETE /items/1 HTTP/1.1\r\n", iov_len=26}, {iov_base="\r\n", iov_len=2}], 2) = 28"#,
+        ] {
+            let syscall = parse_syscall(line, false);
+            fds.update(&syscall);
+            net.update(&syscall, &fds);
+            updates.push(exchanges.update(&syscall, &fds, &net));
+        }
+        assert_eq!(
+            updates,
+            [
+                None,
+                None,
+                Some(0),
+                Some(1),
+                None,
+                Some(0),
+                Some(1),
+                Some(1),
+                Some(2)
+            ]
+        );
+
+        let exchanges: Vec<String> = exchanges.exchanges.iter().map(|e| e.to_string()).collect();
+        assert_eq!(
+            exchanges,
+            [
+                "GET /health → 503 Service Unavailable",
+                "POST /items → 201 Created",
+                "DELETE /items/1 → (no response)",
+            ]
+        );
+    }
+}
@@ -0,0 +1,133 @@
+// Injects faults into the traced program with strace's syscall tampering (`-e inject=`), to see how
+// it copes with errors, e.g. `--inject openat:error=EIO:every=3` makes every third openat fail with
+// EIO. A fault is written as strace writes it, except that `every=N` is short for `when=N+N`:
+//
+//   SYSCALLS:ACTION[:ACTION...][:when=EXPR]
+//
+// SYSCALLS is a comma-separated list of syscall names, and each ACTION is one of error=ERRNO,
+// retval=VALUE, signal=SIGNAL, delay_enter=DELAY, or delay_exit=DELAY (see strace(1) for these and
+// for `when`). strace picks the calls to tamper with by name and count, not by argument, so a fault
+// cannot be limited to one file.
+//
+// strace marks the syscalls that it tampered with, which the interface highlights. The faults can
+// be changed in the interface ('J'), which restarts the trace: the command is run again from the
+// start, or strace reattaches to the processes.
+
+use std::fmt;
+
+use anyhow::{anyhow, Result};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Injection {
+    pub syscalls: Vec<String>,
+    // e.g., ("error", "EIO") or ("when", "3+3"), in the order given
+    pub params: Vec<(String, String)>,
+}
+
+const ACTIONS: &[&str] = &["error", "retval", "signal", "delay_enter", "delay_exit"];
+
+impl Injection {
+    pub fn parse(spec: &str) -> Result<Injection> {
+        let mut parts = spec.split(':');
+        let syscalls: Vec<String> = parts
+            .next()
+            .unwrap_or_default()
+            .split(',')
+            .filter(|name| !name.is_empty())
+            .map(String::from)
+            .collect();
+        if syscalls.is_empty() {
+            return Err(anyhow!(
+                "expected syscalls to inject a fault into: {:?}",
+                spec
+            ));
+        }
+
+        let mut params = Vec::new();
+        for part in parts {
+            let (key, value) = part
+                .split_once('=')
+                .ok_or(anyhow!("expected KEY=VALUE in fault: {:?}", part))?;
+            if value.is_empty() {
+                return Err(anyhow!("missing value for {} in fault: {:?}", key, spec));
+            }
+            let param = match key {
+                "every" => {
+                    let n: u32 = value.parse().ok().filter(|n| *n > 0).ok_or(anyhow!(
+                        "invalid every={} in fault: {:?}",
+                        value,
+                        spec
+                    ))?;
+                    ("when".to_string(), format!("{}+{}", n, n))
+                }
+                "when" => (key.to_string(), value.to_string()),
+                key if ACTIONS.contains(&key) => (key.to_string(), value.to_string()),
+                _ => return Err(anyhow!("unknown key {:?} in fault: {:?}", key, spec)),
+            };
+            params.push(param);
+        }
+        if !params
+            .iter()
+            .any(|(key, _)| ACTIONS.contains(&key.as_str()))
+        {
+            return Err(anyhow!(
+                "fault {:?} does nothing (expected one of: {})",
+                spec,
+                ACTIONS.join(", ")
+            ));
+        }
+        Ok(Injection { syscalls, params })
+    }
+}
+
+/// Parses whitespace-separated faults, as typed into the interface.
+pub fn parse_all(text: &str) -> Result<Vec<Injection>> {
+    text.split_whitespace().map(Injection::parse).collect()
+}
+
+/// The arguments that make strace inject the faults.
+pub fn strace_args(injections: &[Injection]) -> Vec<String> {
+    injections
+        .iter()
+        .flat_map(|injection| ["-e".to_string(), format!("inject={}", injection)])
+        .collect()
+}
+
+impl fmt::Display for Injection {
+    // in strace's syntax
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.syscalls.join(","))?;
+        for (key, value) in &self.params {
+            write!(f, ":{}={}", key, value)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_all, strace_args, Injection};
+
+    #[test]
+    fn test_injection() {
+        let injections =
+            parse_all("openat:error=EIO:every=3  read,write:delay_enter=10ms").unwrap();
+        assert_eq!(injections[0].to_string(), "openat:error=EIO:when=3+3");
+        assert_eq!(
+            strace_args(&injections),
+            [
+                "-e",
+                "inject=openat:error=EIO:when=3+3",
+                "-e",
+                "inject=read,write:delay_enter=10ms"
+            ]
+        );
+
+        assert!(Injection::parse("openat").is_err());
+        assert!(Injection::parse("openat:when=2").is_err());
+        assert!(Injection::parse(":error=EIO").is_err());
+        assert!(Injection::parse("openat:error=EIO:every=0").is_err());
+        assert!(Injection::parse("openat:bogus=1").is_err());
+        assert!(parse_all("").unwrap().is_empty());
+    }
+}
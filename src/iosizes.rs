@@ -0,0 +1,196 @@
+// Collects how many bytes each read and write moved, per file (or pipe, socket, ...), to find
+// unbuffered I/O: a program making millions of tiny writes where a few large ones would do, which
+// is one of the most common performance problems that a trace shows.
+//
+// Sizes are counted in power-of-two buckets (0, 1, 2-3, 4-7, ...), and a file is flagged if it was
+// read or written many times, a few bytes at a time on average.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::fdtable::{FdKind, FdTable};
+use crate::strace::Syscall;
+use crate::syscalls::{self, Io};
+
+// the fewest calls that are flagged
+const MIN_CALLS: u64 = 1000;
+// the largest average size, in bytes, that is flagged
+const SMALL_BYTES: u64 = 64;
+// buckets for 0 bytes, and for each bit length of a 64-bit size
+const BUCKETS: usize = 65;
+
+#[derive(Default)]
+pub struct IoSizes {
+    // keyed by what the fd referred to (e.g., a path) and the direction
+    pub targets: BTreeMap<(String, Io), Sizes>,
+}
+
+pub struct Sizes {
+    pub calls: u64,
+    pub bytes: u64,
+    // how many calls moved 0 bytes, 1 byte, 2-3 bytes, 4-7 bytes, ...
+    buckets: [u64; BUCKETS],
+}
+
+impl IoSizes {
+    /// Must be called after `fds` has been updated with `syscall`.
+    pub fn update(&mut self, syscall: &Syscall, fds: &FdTable) {
+        // a call that never returned, or whose line could not be parsed, moved an unknown amount
+        let (io, bytes) = match (syscalls::io_direction(&syscall.name), syscall.result()) {
+            (Some(io), Some(bytes)) if syscall.errno.is_none() && bytes >= 0 => (io, bytes as u64),
+            _ => return,
+        };
+        let fd = match syscalls::fd_args(syscall).first() {
+            Some(fd) => *fd,
+            None => return,
+        };
+        let target = match fds.resolve(syscall.pid, fd, syscall.entry_time_micros).kind {
+            FdKind::Unknown => format!("fd {}", fd),
+            kind => kind.to_string(),
+        };
+        let sizes = self.targets.entry((target, io)).or_insert_with(|| Sizes {
+            calls: 0,
+            bytes: 0,
+            buckets: [0; BUCKETS],
+        });
+        sizes.calls += 1;
+        sizes.bytes += bytes;
+        sizes.buckets[(u64::BITS - bytes.leading_zeros()) as usize] += 1;
+    }
+
+    /// The targets with the most calls first.
+    pub fn busiest(&self) -> Vec<(&String, Io, &Sizes)> {
+        let mut rows: Vec<(&String, Io, &Sizes)> = self
+            .targets
+            .iter()
+            .map(|((target, io), sizes)| (target, *io, sizes))
+            .collect();
+        rows.sort_by_key(|(_, _, sizes)| std::cmp::Reverse(sizes.calls));
+        rows
+    }
+}
+
+impl Sizes {
+    pub fn average(&self) -> u64 {
+        self.bytes / self.calls.max(1)
+    }
+
+    /// A suggestion, if the calls look like unbuffered I/O.
+    pub fn hint(&self, io: Io) -> Option<String> {
+        if self.calls < MIN_CALLS || self.average() > SMALL_BYTES {
+            return None;
+        }
+        Some(match io {
+            Io::Write => format!(
+                "{} writes of {} bytes on average: buffer them (e.g., with BufWriter, or setvbuf \
+                 in C) to make fewer, larger writes",
+                self.calls,
+                self.average()
+            ),
+            Io::Read => format!(
+                "{} reads of {} bytes on average: read larger chunks at once (e.g., with \
+                 BufReader, or fread in C)",
+                self.calls,
+                self.average()
+            ),
+        })
+    }
+}
+
+// e.g., "1: 5000, 2-3: 12, 4K-8K: 1"
+impl fmt::Display for Sizes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut first = true;
+        for (i, count) in self.buckets.iter().enumerate() {
+            if *count == 0 {
+                continue;
+            }
+            if !first {
+                write!(f, ", ")?;
+            }
+            first = false;
+            match i {
+                0 => write!(f, "0")?,
+                1 => write!(f, "1")?,
+                i => write!(f, "{}-{}", size(1 << (i - 1)), size((1 << i) - 1))?,
+            }
+            write!(f, ": {}", count)?;
+        }
+        Ok(())
+    }
+}
+
+// the bounds of a bucket, which are a power of two or one less, e.g. 1048575 is "1M"
+fn size(bytes: u64) -> String {
+    match bytes + 1 {
+        0..=1024 => bytes.to_string(),
+        1025..=1048575 => format!("{}K", (bytes + 1) / 1024),
+        _ => format!("{}M", (bytes + 1) / 1048576),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fdtable::FdTable;
+    use crate::strace::parse_syscall;
+    use crate::syscalls::Io;
+
+    use super::{size, IoSizes};
+
+    #[test]
+    fn test_io_sizes() {
+        let mut sizes = IoSizes::default();
+        let mut fds = FdTable::default();
+        let mut update = |line: &str| {
+            let syscall = parse_syscall(line, false);
+            fds.update(&syscall);
+            sizes.update(&syscall, &fds);
+        };
+        update("openat(AT_FDCWD, \"/tmp/out\", O_WRONLY|O_CREAT, 0644) = 3");
+        for _ in 0..1500 {
+            update("write(3, \"x\", 1) = 1");
+        }
+        update("write(3, \"xyz\", 3) = 3");
+        update("write(3, \"...\", 5000) = 5000");
+        update("write(3, \"...\", 5000) = -1 ENOSPC (No space left on device)");
+        update("read(0, \"abc\", 4096) = 3");
+        update("read(0, \"\", 4096) = 0");
+
+        let busiest = sizes.busiest();
+        assert_eq!(busiest.len(), 2);
+        let (target, io, out) = busiest[0];
+        assert_eq!((target.as_str(), io), ("/tmp/out", Io::Write));
+        assert_eq!(out.calls, 1502);
+        assert_eq!(out.to_string(), "1: 1500, 2-3: 1, 4K-8K: 1");
+        assert!(out.hint(io).unwrap().starts_with("1502 writes of 4 bytes"));
+
+        let (target, io, stdin) = busiest[1];
+        assert_eq!((target.as_str(), io), ("fd 0", Io::Read));
+        assert_eq!(stdin.to_string(), "0: 1, 2-3: 1");
+        assert_eq!(stdin.hint(io), None);
+    }
+
+    #[test]
+    fn test_io_sizes_unknown() {
+        let mut sizes = IoSizes::default();
+        let fds = FdTable::default();
+        for line in [
+            // never returned
+            "write(1, \"abc\", 3) = ?",
+            // could not be parsed
+            "write(1, \"abc",
+        ] {
+            sizes.update(&parse_syscall(line, false), &fds);
+        }
+        assert!(sizes.busiest().is_empty());
+    }
+
+    #[test]
+    fn test_size() {
+        assert_eq!(size(1023), "1023");
+        assert_eq!(size(1024), "1K");
+        assert_eq!(size(2047), "2K");
+        assert_eq!(size(1048575), "1M");
+        assert_eq!(size(4194303), "4M");
+    }
+}
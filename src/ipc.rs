@@ -0,0 +1,279 @@
+// Lists the System V and POSIX IPC objects (shared memory, semaphores, and message queues) that the
+// traced processes created or used, and which processes shared each one.
+//
+// glibc implements shm_open and sem_open by opening files under /dev/shm, so that is how POSIX
+// shared memory and semaphores show up in a trace.
+
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+
+use crate::fdtable::{FdKind, FdTable};
+use crate::strace::{Syscall, SyscallArgValue};
+use crate::syscalls;
+
+const SHM_DIR: &str = "/dev/shm/";
+
+#[derive(Default)]
+pub struct IpcObjects {
+    // in the order they were first used
+    pub objects: Vec<IpcObject>,
+    // index into `objects`, keyed by kind and the SysV id or the POSIX name
+    indices: HashMap<(Kind, String), usize>,
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Kind {
+    SysvShm,
+    SysvSem,
+    SysvMsg,
+    PosixShm,
+    PosixSem,
+    PosixMq,
+}
+
+pub struct IpcObject {
+    pub kind: Kind,
+    // the key (e.g., "0x1234" or "IPC_PRIVATE") of a SysV object, or the name of a POSIX one
+    pub name: String,
+    // the id of a SysV object
+    pub id: Option<i64>,
+    // bytes for shared memory, or the number of semaphores in a SysV set
+    pub size: Option<u64>,
+    pub creator: Option<u32>,
+    // the processes (by fd-table owner) that created, opened, or used the object
+    pub users: BTreeSet<u32>,
+    pub removed: bool,
+}
+
+impl IpcObjects {
+    /// Must be called after `fds` has been updated with `syscall`.
+    pub fn update(&mut self, syscall: &Syscall, fds: &FdTable) {
+        if syscall.errno.is_some() || syscall.error_details.is_some() {
+            return;
+        }
+        let owner = fds.owner(syscall.pid);
+        let id = match syscall.arg(0).map(|a| &a.value) {
+            Some(SyscallArgValue::Number(id)) => Some(*id),
+            _ => None,
+        };
+        let has_flag = |index: usize, flag: &str| {
+            syscall
+                .arg(index)
+                .is_some_and(|a| syscalls::value_has_flag(&a.value, flag))
+        };
+
+        match syscall.name.as_str() {
+            "shmget" | "semget" | "msgget" => {
+                let (kind, flags) = match syscall.name.as_str() {
+                    "shmget" => (Kind::SysvShm, 2),
+                    "semget" => (Kind::SysvSem, 2),
+                    _ => (Kind::SysvMsg, 1),
+                };
+                let key = match syscall.arg(0).map(|a| &a.value) {
+                    Some(SyscallArgValue::Number(key)) => format!("{:#x}", key),
+                    Some(value) => value.to_string(),
+                    None => return,
+                };
+                let size = match (kind, syscall.arg(1).map(|a| &a.value)) {
+                    (Kind::SysvMsg, _) => None,
+                    (_, Some(SyscallArgValue::Number(size))) => Some(*size as u64),
+                    _ => None,
+                };
+                let object = self.get(kind, syscall.return_value.to_string(), &key);
+                if object.name.is_empty() {
+                    // the object was used before the trace saw it looked up by its key
+                    object.name = key;
+                }
+                object.id = Some(syscall.return_value);
+                // looking up an existing object passes a size of 0
+                object.size = object.size.or(size.filter(|size| *size > 0));
+                object.used_by(owner, has_flag(flags, "IPC_CREAT"));
+            }
+            "shmat" | "semop" | "semtimedop" | "msgsnd" | "msgrcv" | "shmctl" | "semctl"
+            | "msgctl" => {
+                let kind = match &syscall.name[..3] {
+                    "shm" => Kind::SysvShm,
+                    "sem" => Kind::SysvSem,
+                    _ => Kind::SysvMsg,
+                };
+                let id = match id {
+                    Some(id) => id,
+                    None => return,
+                };
+                let object = self.get(kind, id.to_string(), "");
+                object.id = Some(id);
+                object.used_by(owner, false);
+                let cmd = if kind == Kind::SysvSem { 2 } else { 1 };
+                if syscall.name.ends_with("ctl") && has_flag(cmd, "IPC_RMID") {
+                    object.removed = true;
+                }
+            }
+            "open" | "openat" | "openat2" => {
+                let path = syscalls::path_args(syscall).into_iter().next();
+                let (kind, name) = match path.as_deref().and_then(posix_name) {
+                    Some(found) => found,
+                    None => return,
+                };
+                let flags = syscalls::arg_index(&syscall.name, "flags").unwrap_or(2);
+                self.get(kind, name.clone(), &name)
+                    .used_by(owner, has_flag(flags, "O_CREAT"));
+            }
+            "mq_open" => {
+                let name = match syscall.arg(0).map(|a| &a.value) {
+                    Some(SyscallArgValue::Quoted { text, .. }) => text.clone(),
+                    _ => return,
+                };
+                self.get(Kind::PosixMq, name.clone(), &name)
+                    .used_by(owner, has_flag(1, "O_CREAT"));
+            }
+            "ftruncate" => {
+                let path = match id
+                    .map(|fd| fds.resolve(syscall.pid, fd, syscall.entry_time_micros).kind)
+                {
+                    Some(FdKind::File(path)) => path,
+                    _ => return,
+                };
+                let size = match syscall.arg(1).map(|a| &a.value) {
+                    Some(SyscallArgValue::Number(size)) => *size as u64,
+                    _ => return,
+                };
+                if let Some((kind, name)) = posix_name(&path) {
+                    self.get(kind, name.clone(), &name).size = Some(size);
+                }
+            }
+            "unlink" | "mq_unlink" => {
+                let name = match syscall.arg(0).map(|a| &a.value) {
+                    Some(SyscallArgValue::Quoted { text, .. }) => text.clone(),
+                    _ => return,
+                };
+                let found = if syscall.name == "mq_unlink" {
+                    Some((Kind::PosixMq, name))
+                } else {
+                    posix_name(&name)
+                };
+                if let Some(index) = found.and_then(|key| self.indices.get(&key)) {
+                    self.objects[*index].removed = true;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn get(&mut self, kind: Kind, key: String, name: &str) -> &mut IpcObject {
+        let index = *self.indices.entry((kind, key)).or_insert_with(|| {
+            self.objects.push(IpcObject {
+                kind,
+                name: name.to_string(),
+                id: None,
+                size: None,
+                creator: None,
+                users: BTreeSet::new(),
+                removed: false,
+            });
+            self.objects.len() - 1
+        });
+        &mut self.objects[index]
+    }
+}
+
+impl IpcObject {
+    fn used_by(&mut self, owner: Option<u32>, creating: bool) {
+        if creating && self.creator.is_none() {
+            self.creator = owner;
+        }
+        self.users.extend(owner);
+    }
+}
+
+// e.g., "/dev/shm/sem.lock" is the POSIX semaphore "/lock"
+fn posix_name(path: &str) -> Option<(Kind, String)> {
+    let name = path.strip_prefix(SHM_DIR)?;
+    match name.strip_prefix("sem.") {
+        Some(name) => Some((Kind::PosixSem, format!("/{}", name))),
+        None => Some((Kind::PosixShm, format!("/{}", name))),
+    }
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Kind::SysvShm => "SysV shared memory",
+            Kind::SysvSem => "SysV semaphores",
+            Kind::SysvMsg => "SysV message queue",
+            Kind::PosixShm => "POSIX shared memory",
+            Kind::PosixSem => "POSIX semaphore",
+            Kind::PosixMq => "POSIX message queue",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl fmt::Display for IpcObject {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.kind)?;
+        if !self.name.is_empty() {
+            write!(f, " {}", self.name)?;
+        }
+        if let Some(id) = self.id {
+            write!(f, " (id {})", id)?;
+        }
+        match (self.kind, self.size) {
+            (Kind::SysvSem, Some(size)) => write!(f, ", {} semaphores", size)?,
+            (_, Some(size)) => write!(f, ", {} bytes", size)?,
+            _ => {}
+        }
+        if self.removed {
+            write!(f, ", removed")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fdtable::FdTable;
+    use crate::strace::{LineParser, Message};
+
+    use super::IpcObjects;
+
+    #[test]
+    fn test_ipc_objects() {
+        let mut ipc = IpcObjects::default();
+        let mut fds = FdTable::default();
+        let mut parser = LineParser::default();
+        for line in [
+            "10 shmget(0x1234, 65536, IPC_CREAT|0600) = 7",
+            "10 shmat(7, NULL, 0) = 0x7f0000000000",
+            "10 clone(child_stack=NULL, flags=SIGCHLD) = 11",
+            "11 shmget(0x1234, 0, 0) = 7",
+            "11 shmat(7, NULL, SHM_RDONLY) = 0x7f0000100000",
+            "10 shmctl(7, IPC_RMID, NULL) = 0",
+            "11 openat(AT_FDCWD, \"/dev/shm/sem.ready\", O_RDWR|O_CREAT|O_EXCL, 0600) = 3",
+            "11 openat(AT_FDCWD, \"/dev/shm/frames\", O_RDWR|O_CREAT|O_NOFOLLOW|O_CLOEXEC, 0600) = 4",
+            "11 ftruncate(4, 1048576) = 0",
+            "11 unlink(\"/dev/shm/frames\") = 0",
+        ] {
+            let syscall = parser
+                .parse_line(line)
+                .and_then(Message::into_syscall)
+                .unwrap();
+            fds.update(&syscall);
+            ipc.update(&syscall, &fds);
+        }
+
+        let objects: Vec<String> = ipc.objects.iter().map(|o| o.to_string()).collect();
+        assert_eq!(
+            objects,
+            [
+                "SysV shared memory 0x1234 (id 7), 65536 bytes, removed",
+                "POSIX semaphore /ready",
+                "POSIX shared memory /frames, 1048576 bytes, removed",
+            ]
+        );
+        let users = |i: usize| ipc.objects[i].users.iter().copied().collect::<Vec<_>>();
+        assert_eq!(users(0), [10, 11]);
+        assert_eq!(users(1), [11]);
+        assert_eq!(ipc.objects[0].creator, Some(10));
+        assert_eq!(ipc.objects[1].creator, Some(11));
+    }
+}
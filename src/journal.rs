@@ -0,0 +1,296 @@
+// Sends alerts and the summary of a trace to the system log (`--journal`), so that tracing
+// sessions, e.g. ones that watch a service for suspicious activity, feed into existing log
+// pipelines. Each event that sets off an alert from the config file (see src/rules.rs), other than
+// one that only highlights, becomes an entry with priority "warning", and once the trace is over,
+// its summary becomes an entry with priority "info". This works whether or not the trace is shown
+// in the interface.
+//
+// With journald, entries have structured fields: MESSAGE, PRIORITY, SYSLOG_IDENTIFIER=vistrace, and
+//
+//   VISTRACE_TRACE       what was traced, e.g. "curl example.com" or "pid 1234"
+//   VISTRACE_EVENT       the number of the event that set off the alert, as in the interface
+//   VISTRACE_RULE        the alert, as written in the config file
+//   VISTRACE_SYSCALL     the syscall's name, and VISTRACE_PID and VISTRACE_ERRNO if known
+//   VISTRACE_LINE        the syscall as strace printed it
+//   VISTRACE_SYSCALLS    for the summary, how many syscalls there were, and VISTRACE_FAILED,
+//                        VISTRACE_PROCESSES, and VISTRACE_ALERTS how many of them failed, how many
+//                        processes made them, and how many alerts they set off
+//   VISTRACE_SUMMARY     the report of `vistrace --summary`
+//
+// e.g., `journalctl SYSLOG_IDENTIFIER=vistrace VISTRACE_RULE='alert log path=^/etc/shadow$'`.
+// syslog has no structured fields, so they are appended to the message as KEY="value" (except
+// for the line, which is in the message already, and the report).
+
+use std::collections::BTreeSet;
+use std::os::unix::net::UnixDatagram;
+use std::path::Path;
+use std::process;
+
+use anyhow::{anyhow, Result};
+use clap::ValueEnum;
+
+use crate::rules::{Action, Rules};
+use crate::strace::{LineParser, Message};
+use crate::summary::Summary;
+
+const JOURNALD_SOCKET: &str = "/run/systemd/journal/socket";
+const SYSLOG_SOCKET: &str = "/dev/log";
+
+// syslog's severities, and the "user" facility
+const WARNING: u8 = 4;
+const INFO: u8 = 6;
+const FACILITY_USER: u8 = 1;
+
+/// Where `--journal` sends entries.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum Target {
+    /// journald if it is running, otherwise syslog
+    Auto,
+    Journald,
+    Syslog,
+}
+
+pub struct Journal {
+    socket: UnixDatagram,
+    syslog: bool,
+    trace: String,
+    rules: Rules,
+    // the summary is of messages of its own, parsed again from the lines of the trace
+    parser: LineParser,
+    summary: Summary,
+    syscalls: usize,
+    failed: usize,
+    alerts: usize,
+    pids: BTreeSet<u32>,
+}
+
+impl Journal {
+    /// Connects to the system log. `trace` says what is being traced.
+    pub fn connect(target: Target, trace: String, rules: Rules) -> Result<Journal> {
+        let journald = match target {
+            Target::Auto => Path::new(JOURNALD_SOCKET).exists(),
+            Target::Journald => true,
+            Target::Syslog => false,
+        };
+        let path = if journald {
+            JOURNALD_SOCKET
+        } else {
+            SYSLOG_SOCKET
+        };
+        Journal::connect_to(Path::new(path), !journald, trace, rules)
+    }
+
+    fn connect_to(path: &Path, syslog: bool, trace: String, rules: Rules) -> Result<Journal> {
+        let socket = UnixDatagram::unbound()
+            .and_then(|socket| socket.connect(path).map(|()| socket))
+            .map_err(|e| anyhow!("unable to connect to {}: {}", path.display(), e))?;
+        Ok(Journal {
+            socket,
+            syslog,
+            trace,
+            rules,
+            parser: LineParser::default(),
+            summary: Summary::default(),
+            syscalls: 0,
+            failed: 0,
+            alerts: 0,
+            pids: BTreeSet::new(),
+        })
+    }
+
+    /// Sends an entry for each alert that `msg` sets off. `line` is the line of strace output that
+    /// completed it.
+    pub fn write(&mut self, line: &str, msg: Option<&Message>) {
+        if let Some(Message::Syscall(syscall)) = msg {
+            let index = self.syscalls;
+            self.syscalls += 1;
+            if syscall.errno.is_some() {
+                self.failed += 1;
+            }
+            self.pids.extend(syscall.pid);
+            for alert in self.rules.check(index, syscall) {
+                let alert = &self.rules.alerts[alert];
+                if alert
+                    .actions
+                    .iter()
+                    .all(|action| *action == Action::Highlight)
+                {
+                    continue;
+                }
+                self.alerts += 1;
+                let rule = alert.to_string();
+                let text = syscall.to_string();
+                let mut fields = vec![
+                    (
+                        "MESSAGE",
+                        format!("#{} set off `{}`: {}", index, rule, text),
+                    ),
+                    ("VISTRACE_TRACE", self.trace.clone()),
+                    ("VISTRACE_EVENT", index.to_string()),
+                    ("VISTRACE_RULE", rule),
+                    ("VISTRACE_SYSCALL", syscall.name.clone()),
+                ];
+                fields.extend(syscall.pid.map(|pid| ("VISTRACE_PID", pid.to_string())));
+                fields.extend(syscall.errno.clone().map(|errno| ("VISTRACE_ERRNO", errno)));
+                fields.push(("VISTRACE_LINE", text));
+                self.send(WARNING, &fields);
+            }
+        }
+        if let Some(msg) = self.parser.parse_line(line) {
+            self.summary.update(msg);
+        }
+    }
+
+    /// Sends the summary of the trace, once it is over.
+    pub fn finish(&mut self) {
+        let mut report = Vec::new();
+        let _ = self.summary.write(&mut report, false);
+        let mut fields = vec![
+            (
+                "MESSAGE",
+                format!(
+                    "trace of {} finished: {} syscalls ({} failed) in {} processes, {} alerts",
+                    self.trace,
+                    self.syscalls,
+                    self.failed,
+                    self.pids.len(),
+                    self.alerts
+                ),
+            ),
+            ("VISTRACE_TRACE", self.trace.clone()),
+            ("VISTRACE_SYSCALLS", self.syscalls.to_string()),
+            ("VISTRACE_FAILED", self.failed.to_string()),
+            ("VISTRACE_PROCESSES", self.pids.len().to_string()),
+            ("VISTRACE_ALERTS", self.alerts.to_string()),
+        ];
+        fields.push((
+            "VISTRACE_SUMMARY",
+            String::from_utf8_lossy(&report).into_owned(),
+        ));
+        self.send(INFO, &fields);
+    }
+
+    // the system log is best-effort: the trace goes on without it
+    fn send(&self, severity: u8, fields: &[(&str, String)]) {
+        let datagram = if self.syslog {
+            syslog_datagram(severity, fields)
+        } else {
+            journald_datagram(severity, fields)
+        };
+        if let Err(e) = self.socket.send(&datagram) {
+            tracing::warn!(error = %e, "unable to write to the system log");
+        }
+    }
+}
+
+// journald's native protocol: a field per line, with values that have newlines in them preceded by
+// their length as a little-endian u64 instead of '='
+fn journald_datagram(severity: u8, fields: &[(&str, String)]) -> Vec<u8> {
+    let mut datagram = Vec::new();
+    let mut field = |name: &str, value: &str| {
+        datagram.extend_from_slice(name.as_bytes());
+        if value.contains('\n') {
+            datagram.push(b'\n');
+            datagram.extend_from_slice(&(value.len() as u64).to_le_bytes());
+        } else {
+            datagram.push(b'=');
+        }
+        datagram.extend_from_slice(value.as_bytes());
+        datagram.push(b'\n');
+    };
+    field("PRIORITY", &severity.to_string());
+    field("SYSLOG_IDENTIFIER", "vistrace");
+    for (name, value) in fields {
+        field(name, value);
+    }
+    datagram
+}
+
+// e.g., `<12>vistrace[1234]: #3 set off ... trace="ls" event="3"`
+fn syslog_datagram(severity: u8, fields: &[(&str, String)]) -> Vec<u8> {
+    let mut datagram = format!(
+        "<{}>vistrace[{}]:",
+        FACILITY_USER * 8 + severity,
+        process::id()
+    );
+    for (name, value) in fields {
+        match name.strip_prefix("VISTRACE_") {
+            // already in the message, or too long for it
+            Some("LINE" | "SUMMARY") => {}
+            Some(name) => {
+                datagram.push_str(&format!(" {}={:?}", name.to_lowercase(), value));
+            }
+            None => {
+                datagram.push(' ');
+                datagram.push_str(value);
+            }
+        }
+    }
+    datagram.into_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::net::UnixDatagram;
+
+    use crate::rules::Rules;
+    use crate::strace::LineParser;
+
+    use super::Journal;
+
+    #[test]
+    fn test_journal() {
+        let dir = std::env::temp_dir();
+        let path = dir.join(format!("vistrace-journal-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let log = UnixDatagram::bind(&path).unwrap();
+        let rules = Rules::parse("alert log path=^/etc/shadow$\nhighlight errno=ENOENT").unwrap();
+        let mut journal = Journal::connect_to(&path, false, "cat".to_string(), rules).unwrap();
+
+        let mut parser = LineParser::default();
+        for line in [
+            "10 openat(AT_FDCWD, \"/etc/passwd\", O_RDONLY) = -1 ENOENT (No such file or directory)\n",
+            "10 openat(AT_FDCWD, \"/etc/shadow\", O_RDONLY) = -1 EACCES (Permission denied)\n",
+            "10 +++ exited with 1 +++\n",
+        ] {
+            journal.write(line, parser.parse_line(line).as_ref());
+        }
+        journal.finish();
+
+        let mut buf = vec![0; 65536];
+        let n = log.recv(&mut buf).unwrap();
+        let alert = String::from_utf8_lossy(&buf[..n]).into_owned();
+        assert!(alert.starts_with("PRIORITY=4\nSYSLOG_IDENTIFIER=vistrace\nMESSAGE=#1 set off `alert log path=^/etc/shadow$`: 10 openat("));
+        assert!(alert.contains("\nVISTRACE_EVENT=1\n"));
+        assert!(alert.contains("\nVISTRACE_PID=10\nVISTRACE_ERRNO=EACCES\n"));
+
+        // the summary comes next, since a highlight is not sent
+        let n = log.recv(&mut buf).unwrap();
+        let summary = &buf[..n];
+        let text = String::from_utf8_lossy(summary);
+        assert!(text.starts_with("PRIORITY=6\n"));
+        assert!(text.contains(
+            "MESSAGE=trace of cat finished: 2 syscalls (2 failed) in 1 processes, 1 alerts\n"
+        ));
+        // the report has newlines in it, so its length comes first
+        let at = text.find("VISTRACE_SUMMARY\n").unwrap() + "VISTRACE_SUMMARY\n".len();
+        let len = u64::from_le_bytes(summary[at..at + 8].try_into().unwrap()) as usize;
+        assert_eq!(summary.len(), at + 8 + len + 1);
+        assert!(String::from_utf8_lossy(&summary[at + 8..at + 8 + len]).contains("openat"));
+
+        // syslog gets the fields in the message
+        let mut journal =
+            Journal::connect_to(&path, true, "cat".to_string(), Rules::default()).unwrap();
+        journal.finish();
+        let n = log.recv(&mut buf).unwrap();
+        let entry = String::from_utf8_lossy(&buf[..n]).into_owned();
+        assert!(entry.starts_with(&format!(
+            "<14>vistrace[{}]: trace of cat finished",
+            std::process::id()
+        )));
+        assert!(entry
+            .ends_with(" trace=\"cat\" syscalls=\"0\" failed=\"0\" processes=\"0\" alerts=\"0\""));
+
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,538 @@
+// Writes parsed events as JSON Lines (one JSON object per line), for consumption by other tools.
+//
+// Every record has a "schema_version" (currently 1) and a "type", which is one of "syscall",
+// "signal", "exit", "libcall", or "skipped". The schema version will be incremented whenever a
+// field is removed or its meaning changes; new fields may be added without changing the version.
+// `vistrace schema` prints a JSON Schema of the records (src/jsonl.schema.json), which consumers
+// can validate them against.
+//
+// Fields common to all records:
+//
+//   pid            process that the event belongs to, or null if strace did not say (i.e., only a
+//                  single process was traced)
+//   time_us        wall-clock time of the event, in microseconds since the Unix epoch, or null if
+//                  unknown
+//
+// "syscall" records:
+//
+//   name           e.g., "openat"
+//   args           list of arguments (see below)
+//   return         return value, as an integer, or null if the syscall never returned (e.g.,
+//                  exit_group, which strace shows as `= ?`) or its line could not be parsed
+//   errno          e.g., "ENOENT" if the syscall failed, otherwise null
+//   injected       true if strace tampered with the syscall, e.g. to make it fail (see
+//                  src/inject.rs)
+//   duration_us    time spent in the syscall, in microseconds, or null if unknown
+//   content        null, unless the syscall read or wrote data, in which case an object with
+//                  "type" ("text", "json", or "binary"), "format" (e.g., "gzip", for binary data
+//                  in a known format, or else null), "truncated" (true if strace cut the data
+//                  off), and "preview" (a snippet of the text, the JSON pretty-printed, or a
+//                  hexdump of the first bytes; see src/preview.rs)
+//   parse_error    null, unless vistrace could not parse the line, in which case this is an error
+//                  message, "raw" holds the original line, and the other fields may be incomplete
+//
+// "signal" records:
+//
+//   signal         e.g., "SIGCHLD"
+//   info           the fields of the signal's siginfo_t as an object of arguments, or null if
+//                  strace did not print it
+//
+// "exit" records:
+//
+//   exit_code      exit status, if the process exited normally, otherwise null
+//   signal         signal that killed the process, if it was killed, otherwise null
+//   core_dumped    true if the process was killed and dumped core
+//
+// "libcall" records, of calls to library functions (with --ltrace; see src/ltrace.rs):
+//
+//   caller         the program or library that made the call, if ltrace said, otherwise null
+//   name           e.g., "malloc"
+//   args           the arguments as ltrace printed them, as a string
+//   return         the return value as ltrace printed it, e.g. "0x55d0c8a2a2a0" or "<void>"
+//   duration_us    time spent in the call, in microseconds, or null if unknown
+//
+// "skipped" records, of the calls of a syscall that --sample left out since the last one that was
+// kept (see src/sample.rs), with a null pid and time_us:
+//
+//   name           e.g., "read"
+//   calls          how many calls were left out
+//   errors         how many of them failed
+//   duration_us    time spent in them in all, in microseconds, or null if unknown
+//
+// "connection" records are written at the end of the trace by `vistrace export --format
+// connections`, one per socket, instead of the records above:
+//
+//   fd             the socket's file descriptor in `pid`, the process that created it
+//   time_us        when the socket was opened
+//   protocol       e.g., "tcp", "udp", or "unix", or "" if unknown
+//   role           "socket", "client", "listener", or "server" (accepted from a listener)
+//   local          local address, e.g. "127.0.0.1:8080" or a Unix socket path, or null if unknown
+//   remote         remote address, in the same form, or null if unknown
+//   closed_us      when the socket was closed, or null if it was still open
+//   duration_us    how long the socket was open for, or null if unknown
+//   bytes_read     bytes received on the socket
+//   bytes_written  bytes sent on the socket
+//   close_reason   "closed", "closed after <errno>", or "process exited", or null if still open
+//   tls            null, unless a TLS handshake was seen, in which case an object with
+//                  "server_name" (from the ClientHello) and "version" (e.g., "1.3", from the
+//                  ServerHello), either of which may be null
+//
+// "command" records are written at the end of the trace by `vistrace export --format
+// commands-json`, one per program that was run, instead of the records above:
+//
+//   time_us        when the program was run
+//   index          the command's position in the order that commands ran, starting from 0
+//   parent         the index of the command that ran this one, or null if it was the first
+//   path           the program's path, as passed to execve
+//   argv           the arguments, including argv[0], as a list of strings
+//   env            the environment as a list of "NAME=value" strings, or null if strace did not
+//                  print it (run strace with -v for that)
+//   exit_code      exit status, if the process exited normally without running anything else,
+//                  otherwise null
+//   signal         signal that killed the process, if it was killed, otherwise null
+//
+// An argument is an object with a "kind" and a "value", plus a "name" if strace printed one (e.g.,
+// the fields of a struct):
+//
+//   kind           value
+//   ----           -----
+//   "string"       the string as strace printed it, with escape sequences such as \n left as-is;
+//                  "truncated" is true if strace abbreviated it
+//   "symbol"       a constant or other identifier, e.g., "AT_FDCWD"
+//   "flags"        list of flags, each either a symbol (string) or numeric bits (integer)
+//   "number"       an integer
+//   "product"      a pair of integers [a, b], for values that strace prints as a * b
+//   "array"        list of arguments
+//   "struct"       object mapping field names to arguments
+//   "call"         a macro-like call such as makedev(0x1, 0x3), with "function" holding the name
+//                  and "value" the list of arguments
+
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+use crate::commands::Command;
+use crate::json::Json;
+use crate::net::{Connection, Endpoint};
+use crate::preview;
+use crate::strace::{self, ExitKind, FlagSetValue, Message, Syscall, SyscallArg, SyscallArgValue};
+
+pub const SCHEMA_VERSION: i64 = 1;
+
+/// The JSON Schema of the records, for `vistrace schema`; it must be updated along with them.
+pub const SCHEMA: &str = include_str!("jsonl.schema.json");
+
+/// Writes `msg` as a single line. Messages that are not part of the schema (e.g., notices from
+/// strace itself) are skipped.
+pub fn write_message(w: &mut impl Write, msg: &Message) -> io::Result<()> {
+    match to_json(msg) {
+        Some(json) => writeln!(w, "{}", json),
+        None => Ok(()),
+    }
+}
+
+pub fn to_json(msg: &Message) -> Option<Json> {
+    let mut fields = vec![("schema_version", Json::Number(SCHEMA_VERSION))];
+    match msg {
+        Message::Syscall(syscall) => {
+            fields.extend([
+                ("type", Json::string("syscall")),
+                ("pid", syscall.pid.into()),
+                ("time_us", micros(syscall.entry_time_micros)),
+                ("name", Json::string(&syscall.name)),
+                ("args", args(&syscall.args)),
+                ("return", syscall.result().into()),
+                ("errno", syscall.errno.as_deref().into()),
+                ("injected", Json::Bool(syscall.injected)),
+                (
+                    "duration_us",
+                    syscall.syscall_time_micros.map(|t| t as i64).into(),
+                ),
+                ("content", content(syscall)),
+            ]);
+            match &syscall.error_details {
+                Some(details) => fields.extend([
+                    ("parse_error", Json::string(&details.message)),
+                    ("raw", Json::string(details.fulltext.trim_end())),
+                ]),
+                None => fields.push(("parse_error", Json::Null)),
+            }
+        }
+        Message::Signal(signal) => {
+            let info = if signal.info.is_empty() {
+                Json::Null
+            } else {
+                fields_of(&signal.info)
+            };
+            fields.extend([
+                ("type", Json::string("signal")),
+                ("pid", signal.pid.into()),
+                ("time_us", micros(signal.time_micros)),
+                ("signal", Json::string(&signal.name)),
+                ("info", info),
+            ]);
+        }
+        Message::Exit(exit) => {
+            let (exit_code, signal, core_dumped) = match &exit.status {
+                ExitKind::Exited(code) => (Json::Number(*code), Json::Null, false),
+                ExitKind::Killed {
+                    signal,
+                    core_dumped,
+                } => (Json::Null, Json::string(signal), *core_dumped),
+            };
+            fields.extend([
+                ("type", Json::string("exit")),
+                ("pid", exit.pid.into()),
+                ("time_us", micros(exit.time_micros)),
+                ("exit_code", exit_code),
+                ("signal", signal),
+                ("core_dumped", Json::Bool(core_dumped)),
+            ]);
+        }
+        Message::LibCall(call) => {
+            fields.extend([
+                ("type", Json::string("libcall")),
+                ("pid", call.pid.into()),
+                ("time_us", micros(call.time_micros)),
+                ("caller", call.caller.as_deref().into()),
+                ("name", Json::string(&call.name)),
+                ("args", Json::string(&call.args)),
+                ("return", Json::string(&call.return_value)),
+                ("duration_us", micros(call.duration_micros)),
+            ]);
+        }
+        Message::Skipped(skipped) => {
+            fields.extend([
+                ("type", Json::string("skipped")),
+                ("pid", Json::Null),
+                ("time_us", Json::Null),
+                ("name", Json::string(&skipped.name)),
+                ("calls", Json::Number(skipped.calls as i64)),
+                ("errors", Json::Number(skipped.errors as i64)),
+                ("duration_us", micros(skipped.total_micros)),
+            ]);
+        }
+        Message::Frame(_) | Message::Notice(_) => return None,
+    }
+    Some(Json::object(fields))
+}
+
+/// The "content" field of a syscall's record.
+pub fn content(syscall: &Syscall) -> Json {
+    preview::of_syscall(syscall).map_or(Json::Null, |preview| {
+        Json::object(vec![
+            ("type", Json::string(&preview.content_type.to_string())),
+            ("format", preview.format.into()),
+            ("truncated", Json::Bool(preview.truncated)),
+            ("preview", Json::string(&preview.text)),
+        ])
+    })
+}
+
+pub fn connection_to_json(connection: &Connection) -> Json {
+    let endpoint = |e: &Option<Endpoint>| match e {
+        Some(e) => Json::string(&e.to_string()),
+        None => Json::Null,
+    };
+    Json::object(vec![
+        ("schema_version", Json::Number(SCHEMA_VERSION)),
+        ("type", Json::string("connection")),
+        ("pid", connection.pid.into()),
+        ("fd", Json::Number(connection.fd)),
+        ("time_us", micros(connection.opened_micros)),
+        ("protocol", Json::string(&connection.protocol)),
+        ("role", Json::string(&connection.role.to_string())),
+        ("local", endpoint(&connection.local)),
+        ("remote", endpoint(&connection.remote)),
+        (
+            "closed_us",
+            connection.closed_micros.map_or(Json::Null, micros),
+        ),
+        (
+            "duration_us",
+            connection
+                .duration_micros()
+                .map_or(Json::Null, |d| Json::Number(d as i64)),
+        ),
+        ("bytes_read", Json::Number(connection.bytes.read as i64)),
+        (
+            "bytes_written",
+            Json::Number(connection.bytes.written as i64),
+        ),
+        (
+            "close_reason",
+            connection
+                .close_reason
+                .as_ref()
+                .map_or(Json::Null, |r| Json::string(&r.to_string())),
+        ),
+        (
+            "tls",
+            connection.tls.as_ref().map_or(Json::Null, |tls| {
+                Json::object(vec![
+                    ("server_name", tls.server_name.as_deref().into()),
+                    ("version", tls.version.as_deref().into()),
+                ])
+            }),
+        ),
+    ])
+}
+
+// strace output never has a timestamp of zero, so zero means it was missing
+pub fn command_to_json(index: usize, command: &Command) -> Json {
+    let strings = |values: &[String]| {
+        Json::Array(
+            values
+                .iter()
+                .map(|v| Json::String(String::from_utf8_lossy(&strace::unescape(v)).into_owned()))
+                .collect(),
+        )
+    };
+    let (exit_code, signal) = match &command.exit {
+        Some(ExitKind::Exited(code)) => (Json::Number(*code), Json::Null),
+        Some(ExitKind::Killed { signal, .. }) => (Json::Null, Json::string(signal)),
+        None => (Json::Null, Json::Null),
+    };
+    Json::object(vec![
+        ("schema_version", Json::Number(SCHEMA_VERSION)),
+        ("type", Json::string("command")),
+        ("pid", command.pid.into()),
+        ("time_us", micros(command.time_micros)),
+        ("index", Json::Number(index as i64)),
+        (
+            "parent",
+            command
+                .parent
+                .map_or(Json::Null, |p| Json::Number(p as i64)),
+        ),
+        ("path", Json::string(&command.path)),
+        ("argv", strings(&command.argv)),
+        ("env", command.env.as_deref().map_or(Json::Null, strings)),
+        ("exit_code", exit_code),
+        ("signal", signal),
+    ])
+}
+
+fn micros(t: u64) -> Json {
+    if t == 0 {
+        Json::Null
+    } else {
+        Json::Number(t as i64)
+    }
+}
+
+fn args(args: &[SyscallArg]) -> Json {
+    Json::Array(args.iter().map(arg).collect())
+}
+
+fn arg(arg: &SyscallArg) -> Json {
+    let mut fields = Vec::new();
+    if !arg.name.is_empty() {
+        fields.push(("name", Json::string(&arg.name)));
+    }
+    fields.extend(value(&arg.value));
+    Json::object(fields)
+}
+
+fn value(value: &SyscallArgValue) -> Vec<(&'static str, Json)> {
+    match value {
+        SyscallArgValue::Quoted { text, truncated } => vec![
+            ("kind", Json::string("string")),
+            ("value", Json::string(text)),
+            ("truncated", Json::Bool(*truncated)),
+        ],
+        SyscallArgValue::Symbol(symbol) => {
+            vec![
+                ("kind", Json::string("symbol")),
+                ("value", Json::string(symbol)),
+            ]
+        }
+        SyscallArgValue::FlagSet(flags) => {
+            let flags = flags
+                .iter()
+                .map(|flag| match flag {
+                    FlagSetValue::Symbol(symbol) => Json::string(symbol),
+                    FlagSetValue::Bits(bits) => Json::Number(*bits),
+                })
+                .collect();
+            vec![
+                ("kind", Json::string("flags")),
+                ("value", Json::Array(flags)),
+            ]
+        }
+        SyscallArgValue::Number(n) => {
+            vec![
+                ("kind", Json::string("number")),
+                ("value", Json::Number(*n)),
+            ]
+        }
+        SyscallArgValue::Product(a, b) => vec![
+            ("kind", Json::string("product")),
+            (
+                "value",
+                Json::Array(vec![Json::Number(*a), Json::Number(*b)]),
+            ),
+        ],
+        SyscallArgValue::Array(values) => {
+            vec![("kind", Json::string("array")), ("value", args(values))]
+        }
+        SyscallArgValue::Struct(map) => {
+            vec![("kind", Json::string("struct")), ("value", fields_of(map))]
+        }
+        SyscallArgValue::FunctionCall(name, values) => vec![
+            ("kind", Json::string("call")),
+            ("function", Json::string(name)),
+            ("value", args(values)),
+        ],
+    }
+}
+
+// keys are sorted so that the output is deterministic
+fn fields_of(map: &HashMap<String, SyscallArg>) -> Json {
+    let mut keys: Vec<&String> = map.keys().collect();
+    keys.sort();
+    Json::Object(
+        keys.into_iter()
+            .map(|key| (key.clone(), Json::object(value(&map[key].value))))
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::strace::{LineParser, Message};
+
+    use crate::json::Json;
+
+    use super::{to_json, SCHEMA, SCHEMA_VERSION};
+
+    fn render(line: &str) -> String {
+        let msg = LineParser::default().parse_line(line).unwrap();
+        to_json(&msg).unwrap().to_string()
+    }
+
+    #[test]
+    fn test_syscall() {
+        assert_eq!(
+            render("10 1720000000.000001 openat(AT_FDCWD, \"/a\\n\", O_RDONLY|O_CLOEXEC) = -1 ENOENT (No such file or directory) <0.000010>"),
+            concat!(
+                r#"{"schema_version":1,"type":"syscall","pid":10,"time_us":1720000000000001,"#,
+                r#""name":"openat","args":[{"kind":"symbol","value":"AT_FDCWD"},"#,
+                r#"{"kind":"string","value":"/a\\n","truncated":false},"#,
+                r#"{"kind":"flags","value":["O_RDONLY","O_CLOEXEC"]}],"#,
+                r#""return":-1,"errno":"ENOENT","injected":false,"duration_us":10,"content":null,"#,
+                r#""parse_error":null}"#,
+            )
+        );
+
+        assert_eq!(
+            render("fstat(3, {st_mode=S_IFREG|0644, st_size=12}) = 0"),
+            concat!(
+                r#"{"schema_version":1,"type":"syscall","pid":null,"time_us":null,"#,
+                r#""name":"fstat","args":[{"kind":"number","value":3},{"kind":"struct","value":{"#,
+                r#""st_mode":{"kind":"flags","value":["S_IFREG",420]},"#,
+                r#""st_size":{"kind":"number","value":12}}}],"#,
+                r#""return":0,"errno":null,"injected":false,"duration_us":null,"content":null,"#,
+                r#""parse_error":null}"#,
+            )
+        );
+
+        assert_eq!(
+            render("write(1, \"[1,2]\", 5) = 5"),
+            concat!(
+                r#"{"schema_version":1,"type":"syscall","pid":null,"time_us":null,"#,
+                r#""name":"write","args":[{"kind":"number","value":1},"#,
+                r#"{"kind":"string","value":"[1,2]","truncated":false},"#,
+                r#"{"kind":"number","value":5}],"return":5,"errno":null,"injected":false,"#,
+                r#""duration_us":null,"#,
+                r#""content":{"type":"json","format":null,"truncated":false,"#,
+                r#""preview":"[\n  1,\n  2\n]"},"parse_error":null}"#,
+            )
+        );
+    }
+
+    #[test]
+    fn test_syscall_no_return() {
+        assert_eq!(
+            render("10 exit_group(1) = ?"),
+            concat!(
+                r#"{"schema_version":1,"type":"syscall","pid":10,"time_us":null,"#,
+                r#""name":"exit_group","args":[{"kind":"number","value":1}],"#,
+                r#""return":null,"errno":null,"injected":false,"duration_us":null,"content":null,"#,
+                r#""parse_error":null}"#,
+            )
+        );
+    }
+
+    #[test]
+    fn test_signal_and_exit() {
+        assert_eq!(
+            render("10 --- SIGCHLD {si_signo=SIGCHLD, si_pid=11} ---"),
+            concat!(
+                r#"{"schema_version":1,"type":"signal","pid":10,"time_us":null,"signal":"SIGCHLD","#,
+                r#""info":{"si_pid":{"kind":"number","value":11},"#,
+                r#""si_signo":{"kind":"symbol","value":"SIGCHLD"}}}"#,
+            )
+        );
+        assert_eq!(
+            render("10 +++ killed by SIGKILL +++"),
+            concat!(
+                r#"{"schema_version":1,"type":"exit","pid":10,"time_us":null,"#,
+                r#""exit_code":null,"signal":"SIGKILL","core_dumped":false}"#,
+            )
+        );
+
+        let notice = Message::Notice("Process 10 attached".to_string());
+        assert!(to_json(&notice).is_none());
+    }
+
+    #[test]
+    fn test_schema() {
+        assert!(SCHEMA.contains(&format!(
+            "\"title\": \"vistrace JSON Lines export, schema version {}\"",
+            SCHEMA_VERSION
+        )));
+        assert!(SCHEMA.contains(&format!(
+            "\"schema_version\": {{ \"const\": {} }}",
+            SCHEMA_VERSION
+        )));
+
+        // every field of every kind of record is in the schema
+        fn check(json: &Json) {
+            match json {
+                Json::Object(fields) => {
+                    for (key, value) in fields {
+                        assert!(
+                            SCHEMA.contains(&format!("\"{}\"", key)),
+                            "{} is not in the schema",
+                            key
+                        );
+                        if key == "type" || key == "kind" {
+                            if let Json::String(kind) = value {
+                                assert!(SCHEMA.contains(&format!("\"{}\"", kind)));
+                            }
+                        }
+                        if key != "info" && !(key == "value" && matches!(value, Json::Object(_))) {
+                            check(value);
+                        }
+                    }
+                }
+                Json::Array(items) => items.iter().for_each(check),
+                _ => {}
+            }
+        }
+        for line in [
+            "10 1720000000.000001 openat(AT_FDCWD, \"/a\", O_RDONLY) = -1 ENOENT (No such file or directory) <0.000010>",
+            "10 mmap(NULL, 4096, PROT_READ, MAP_PRIVATE, 3, 0) = 0x7f0000000000",
+            "10 mknodat(AT_FDCWD, \"/dev/null\", S_IFCHR|0666, makedev(0x1, 0x3)) = 0",
+            "10 poll([{fd=3, events=POLLIN}], 1, 0) = 0 (Timeout)",
+            "10 write(1, \"{}\", 2) = 2",
+            "10 --- SIGCHLD {si_signo=SIGCHLD, si_pid=11} ---",
+            "10 +++ exited with 0 +++",
+            "10 ->malloc(16) = 0x55d0c8a2a2a0 <0.000003>",
+            "+++ skipped 3 read (1 failed) <0.000010> +++",
+        ] {
+            let msg = LineParser::default().parse_line(line).unwrap();
+            check(&to_json(&msg).unwrap());
+        }
+    }
+}
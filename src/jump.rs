@@ -0,0 +1,149 @@
+// Times to jump to in the interface, which 'g' asks for, e.g. to find what a program was doing when
+// another program logged something. A time is one of
+//
+//   +5s, -250ms, +1:30     relative to the selected event (as for --duration, see src/limits.rs)
+//   14:02:10, 14:02:10.5   a time of day, in this machine's time zone
+//   1720000000.123456      a Unix timestamp, as the events show them
+//
+// A time of day is taken on whichever day puts it nearest the selected event, so that traces that
+// run past midnight still work. The interface then selects the first event at or after the time.
+
+use anyhow::{anyhow, Result};
+
+use crate::limits;
+
+#[derive(Debug, PartialEq)]
+pub enum Time {
+    // in microseconds, negative to go back
+    Relative(i64),
+    // in microseconds since midnight
+    OfDay(u64),
+    // in microseconds since the Unix epoch
+    Absolute(u64),
+}
+
+const MICROS_PER_DAY: i64 = 86_400_000_000;
+
+impl Time {
+    pub fn parse(text: &str) -> Result<Time> {
+        let text = text.trim();
+        if let Some((sign, duration)) = text
+            .strip_prefix('+')
+            .map(|rest| (1, rest))
+            .or(text.strip_prefix('-').map(|rest| (-1, rest)))
+        {
+            let micros = limits::parse_duration(duration)?.as_micros() as i64;
+            return Ok(Time::Relative(sign * micros));
+        }
+        let number = |part: &str| {
+            part.parse::<f64>()
+                .ok()
+                .filter(|n| *n >= 0.0)
+                .ok_or(anyhow!("invalid time: {:?}", text))
+        };
+        if text.contains(':') {
+            let parts: Vec<&str> = text.split(':').collect();
+            if parts.len() > 3 {
+                return Err(anyhow!("invalid time: {:?} (expected HH:MM:SS)", text));
+            }
+            let mut seconds = 0.0;
+            for (i, part) in parts.iter().enumerate() {
+                seconds += number(part)? * [3600.0, 60.0, 1.0][i];
+            }
+            if seconds >= 86_400.0 {
+                return Err(anyhow!("invalid time of day: {:?}", text));
+            }
+            return Ok(Time::OfDay((seconds * 1e6).round() as u64));
+        }
+        Ok(Time::Absolute((number(text)? * 1e6).round() as u64))
+    }
+
+    /// The time, in microseconds since the Unix epoch, relative to the selected event at `from`
+    /// (also since the epoch), where this machine's time zone is `utc_offset` seconds ahead of UTC.
+    pub fn resolve(&self, from: u64, utc_offset: i64) -> u64 {
+        match *self {
+            Time::Relative(micros) => from.saturating_add_signed(micros),
+            Time::OfDay(micros) => {
+                let local = from as i64 + utc_offset * 1_000_000;
+                let midnight = local - local.rem_euclid(MICROS_PER_DAY);
+                let candidate = midnight + micros as i64;
+                let nearest = [
+                    candidate - MICROS_PER_DAY,
+                    candidate,
+                    candidate + MICROS_PER_DAY,
+                ]
+                .into_iter()
+                .min_by_key(|t| t.abs_diff(local))
+                .unwrap_or(candidate);
+                (nearest - utc_offset * 1_000_000).max(0) as u64
+            }
+            Time::Absolute(micros) => micros,
+        }
+    }
+}
+
+/// The position of the first of `times` (of the events shown, in microseconds since the Unix
+/// epoch, or 0 if unknown) that is at or after `target`, if there is one.
+pub fn first_at_or_after(times: &[u64], target: u64) -> Option<usize> {
+    times.iter().position(|t| *t != 0 && *t >= target)
+}
+
+/// How many seconds this machine's time zone is ahead of UTC at `micros` since the Unix epoch.
+pub fn local_utc_offset(micros: u64) -> i64 {
+    let seconds = (micros / 1_000_000) as libc::time_t;
+    // SAFETY: `tm` is plain data, which localtime_r fills in
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    if unsafe { libc::localtime_r(&seconds, &mut tm) }.is_null() {
+        return 0;
+    }
+    tm.tm_gmtoff
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{first_at_or_after, Time};
+
+    #[test]
+    fn test_jump() {
+        assert_eq!(Time::parse("+5s").unwrap(), Time::Relative(5_000_000));
+        assert_eq!(Time::parse("-250ms").unwrap(), Time::Relative(-250_000));
+        assert_eq!(
+            Time::parse("14:02:10.5").unwrap(),
+            Time::OfDay(50_530_500_000)
+        );
+        assert_eq!(Time::parse("14:02").unwrap(), Time::OfDay(50_520_000_000));
+        assert_eq!(
+            Time::parse("1720000000.000002").unwrap(),
+            Time::Absolute(1_720_000_000_000_002)
+        );
+        assert!(Time::parse("25:00").is_err());
+        assert!(Time::parse("1:2:3:4").is_err());
+        assert!(Time::parse("soon").is_err());
+        assert!(Time::parse("+5 parsecs").is_err());
+
+        // 2024-07-03 09:46:40 UTC
+        let from = 1_720_000_000_000_000;
+        assert_eq!(
+            Time::parse("-1s").unwrap().resolve(from, 0),
+            from - 1_000_000
+        );
+        let ten = Time::parse("10:00").unwrap();
+        assert_eq!(ten.resolve(from, 0), from + 800_000_000);
+        // 10:00 in UTC+2 is 08:00 UTC
+        assert_eq!(ten.resolve(from, 7200), from - 6_400_000_000);
+        // shortly after midnight, 23:59 is the day before
+        let late = Time::parse("23:59").unwrap();
+        assert_eq!(
+            late.resolve(1_720_051_260_000_000, 0),
+            1_720_051_140_000_000
+        );
+    }
+
+    #[test]
+    fn test_first_at_or_after() {
+        // an unknown time is not at or after anything
+        assert_eq!(first_at_or_after(&[0, 5, 10], 0), Some(1));
+        assert_eq!(first_at_or_after(&[5, 0, 10], 6), Some(2));
+        assert_eq!(first_at_or_after(&[5, 10], 11), None);
+    }
+}
@@ -0,0 +1,195 @@
+// Layouts of the interface's panes, which the config file can define (see src/rules.rs), and which
+// --layout and 'V' in the interface pick between. A layout is a line of the config file:
+//
+//   layout NAME COLUMN | COLUMN ...
+//
+// Each column lists its panes from top to bottom, and may start with its width in characters;
+// columns without a width share what is left of the screen. A pane is one of
+//
+//   events    the trace
+//   detail    the selected event
+//   watches   the watch expressions (added with 'w')
+//   output    what the traced processes wrote to stdout and stderr
+//   stats     calls, errors, and time of each syscall so far
+//
+// optionally followed by ':' and its height in rows; panes without a height share what is left of
+// the column. Every layout has exactly one events pane, and the status line goes below it. These
+// layouts are built in, and one in the config file with the same name replaces them:
+//
+//   layout default events detail:10 | 32 watches
+//   layout output events detail:10 output:12 | 32 watches
+//   layout stats events detail:10 | 44 stats watches:10
+
+use std::fmt;
+
+use anyhow::{anyhow, Result};
+
+const BUILT_IN: &[&str] = &[
+    "default events detail:10 | 32 watches",
+    "output events detail:10 output:12 | 32 watches",
+    "stats events detail:10 | 44 stats watches:10",
+];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Pane {
+    Events,
+    Detail,
+    Watches,
+    Output,
+    Stats,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Layout {
+    pub name: String,
+    pub columns: Vec<Column>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Column {
+    pub width: Option<usize>,
+    // each with its height, if fixed
+    pub panes: Vec<(Pane, Option<usize>)>,
+}
+
+/// The built-in layouts followed by those of the config file, where a layout in the config file
+/// replaces a built-in one of the same name.
+pub fn all(custom: &[Layout]) -> Vec<Layout> {
+    let mut layouts: Vec<Layout> = BUILT_IN
+        .iter()
+        .map(|text| Layout::parse(text).expect("invalid built-in layout"))
+        .collect();
+    for layout in custom {
+        match layouts.iter_mut().find(|l| l.name == layout.name) {
+            Some(built_in) => *built_in = layout.clone(),
+            None => layouts.push(layout.clone()),
+        }
+    }
+    layouts
+}
+
+impl Layout {
+    /// Parses the rest of a `layout` line of the config file: `NAME COLUMN | COLUMN ...`.
+    pub fn parse(text: &str) -> Result<Layout> {
+        let (name, columns) = text
+            .trim()
+            .split_once(char::is_whitespace)
+            .ok_or(anyhow!("expected `layout NAME PANES`"))?;
+        let columns = columns
+            .split('|')
+            .map(Column::parse)
+            .collect::<Result<Vec<_>>>()?;
+
+        let panes: Vec<Pane> = columns
+            .iter()
+            .flat_map(|c| c.panes.iter().map(|(pane, _)| *pane))
+            .collect();
+        if !panes.contains(&Pane::Events) {
+            return Err(anyhow!("layout {} has no events pane", name));
+        }
+        for (i, pane) in panes.iter().enumerate() {
+            if panes[..i].contains(pane) {
+                return Err(anyhow!("layout {} has more than one {} pane", name, pane));
+            }
+        }
+        Ok(Layout {
+            name: name.to_string(),
+            columns,
+        })
+    }
+}
+
+impl Column {
+    fn parse(text: &str) -> Result<Column> {
+        let mut words = text.split_whitespace().peekable();
+        let width = match words.next_if(|word| word.starts_with(|c: char| c.is_ascii_digit())) {
+            Some(width) => Some(parse_size(width)?),
+            None => None,
+        };
+        let panes = words
+            .map(|word| {
+                let (pane, height) = match word.split_once(':') {
+                    Some((pane, height)) => (pane, Some(parse_size(height)?)),
+                    None => (word, None),
+                };
+                Ok((Pane::parse(pane)?, height))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        if panes.is_empty() {
+            return Err(anyhow!("expected panes in each column"));
+        }
+        Ok(Column { width, panes })
+    }
+}
+
+fn parse_size(text: &str) -> Result<usize> {
+    match text.parse::<usize>() {
+        Ok(size) if size > 0 => Ok(size),
+        _ => Err(anyhow!("invalid size: {:?}", text)),
+    }
+}
+
+impl Pane {
+    fn parse(word: &str) -> Result<Pane> {
+        match word {
+            "events" => Ok(Pane::Events),
+            "detail" => Ok(Pane::Detail),
+            "watches" => Ok(Pane::Watches),
+            "output" => Ok(Pane::Output),
+            "stats" => Ok(Pane::Stats),
+            _ => Err(anyhow!("unknown pane: {:?}", word)),
+        }
+    }
+}
+
+impl fmt::Display for Pane {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Pane::Events => "events",
+            Pane::Detail => "detail",
+            Pane::Watches => "watches",
+            Pane::Output => "output",
+            Pane::Stats => "stats",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{all, Column, Layout, Pane};
+
+    #[test]
+    fn test_layout() {
+        let layout = Layout::parse("wide  40 stats | events output:8 ").unwrap();
+        assert_eq!(
+            layout,
+            Layout {
+                name: "wide".to_string(),
+                columns: vec![
+                    Column {
+                        width: Some(40),
+                        panes: vec![(Pane::Stats, None)],
+                    },
+                    Column {
+                        width: None,
+                        panes: vec![(Pane::Events, None), (Pane::Output, Some(8))],
+                    },
+                ],
+            }
+        );
+
+        assert!(Layout::parse("bare").is_err());
+        assert!(Layout::parse("x detail | watches").is_err());
+        assert!(Layout::parse("x events | events").is_err());
+        assert!(Layout::parse("x events detail:0").is_err());
+        assert!(Layout::parse("x events | 30").is_err());
+        assert!(Layout::parse("x events graph").is_err());
+
+        let custom = Layout::parse("stats events stats:20").unwrap();
+        let layouts = all(&[layout.clone(), custom.clone()]);
+        let names: Vec<&str> = layouts.iter().map(|l| l.name.as_str()).collect();
+        assert_eq!(names, ["default", "output", "stats", "wide"]);
+        assert_eq!(layouts[2], custom);
+    }
+}
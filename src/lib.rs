@@ -0,0 +1,190 @@
+// vistrace as a library, for programs that want to read strace's output themselves, e.g.:
+//
+//   let file = BufReader::new(File::open("trace.txt")?);
+//   for msg in vistrace::strace::parse_reader(file) {
+//       if let Message::Syscall(syscall) = msg? { ... }
+//   }
+//
+// Only `strace` is meant to be used this way; the other modules are public for the vistrace binary
+// and may change at any time.
+//
+// Without the "native" feature, which is on by default, the modules that trace processes or show
+// the interface are left out, and the rest (the parsers and the analyses) build for wasm32, so
+// that a viewer in the browser can parse uploaded traces the same way.
+
+#[doc(hidden)]
+pub mod allowlist;
+#[doc(hidden)]
+pub mod anonymize;
+#[doc(hidden)]
+pub mod antidebug;
+#[doc(hidden)]
+pub mod audit;
+#[doc(hidden)]
+pub mod blocking;
+#[cfg(feature = "native")]
+#[doc(hidden)]
+pub mod bpf;
+#[doc(hidden)]
+pub mod changes;
+#[doc(hidden)]
+pub mod chrome;
+#[doc(hidden)]
+pub mod commands;
+#[doc(hidden)]
+pub mod compare;
+#[doc(hidden)]
+pub mod config;
+#[doc(hidden)]
+pub mod console;
+#[cfg(feature = "native")]
+#[doc(hidden)]
+pub mod control;
+#[doc(hidden)]
+pub mod csv;
+#[doc(hidden)]
+pub mod diff;
+#[doc(hidden)]
+pub mod dns;
+#[doc(hidden)]
+pub mod dot;
+#[doc(hidden)]
+pub mod dtruss;
+#[doc(hidden)]
+pub mod environment;
+#[doc(hidden)]
+pub mod epoll;
+#[doc(hidden)]
+pub mod errnos;
+#[doc(hidden)]
+pub mod eventfds;
+#[doc(hidden)]
+pub mod explain;
+#[doc(hidden)]
+pub mod fdtable;
+#[doc(hidden)]
+pub mod filter;
+#[doc(hidden)]
+pub mod flamegraph;
+#[doc(hidden)]
+pub mod futex;
+#[doc(hidden)]
+pub mod groups;
+#[doc(hidden)]
+pub mod heatmap;
+#[doc(hidden)]
+pub mod http;
+#[doc(hidden)]
+pub mod inject;
+#[doc(hidden)]
+pub mod iosizes;
+#[doc(hidden)]
+pub mod ipc;
+#[cfg(feature = "native")]
+#[doc(hidden)]
+pub mod journal;
+#[doc(hidden)]
+pub mod json;
+#[doc(hidden)]
+pub mod jsonl;
+#[cfg(feature = "native")]
+#[doc(hidden)]
+pub mod jump;
+#[doc(hidden)]
+pub mod layout;
+#[doc(hidden)]
+pub mod libraries;
+#[cfg(feature = "native")]
+#[doc(hidden)]
+pub mod limits;
+#[cfg(feature = "native")]
+#[doc(hidden)]
+pub mod logging;
+#[doc(hidden)]
+pub mod loops;
+#[doc(hidden)]
+pub mod ltrace;
+#[doc(hidden)]
+pub mod memory;
+#[doc(hidden)]
+pub mod mermaid;
+#[doc(hidden)]
+pub mod model;
+#[doc(hidden)]
+pub mod msgpack;
+#[doc(hidden)]
+pub mod namespaces;
+#[doc(hidden)]
+pub mod net;
+#[doc(hidden)]
+pub mod outliers;
+#[doc(hidden)]
+pub mod palette;
+#[doc(hidden)]
+pub mod pcap;
+#[doc(hidden)]
+pub mod peers;
+#[doc(hidden)]
+pub mod policy;
+#[doc(hidden)]
+pub mod postmortem;
+#[doc(hidden)]
+pub mod preview;
+#[doc(hidden)]
+pub mod privileges;
+#[doc(hidden)]
+pub mod processes;
+#[doc(hidden)]
+pub mod procfs;
+#[doc(hidden)]
+pub mod provenance;
+#[cfg(feature = "native")]
+#[doc(hidden)]
+pub mod ptrace;
+#[doc(hidden)]
+pub mod redact;
+#[cfg(feature = "native")]
+#[doc(hidden)]
+pub mod remote;
+#[doc(hidden)]
+pub mod rules;
+#[doc(hidden)]
+pub mod sample;
+#[doc(hidden)]
+pub mod search;
+#[doc(hidden)]
+pub mod seccomp;
+#[doc(hidden)]
+pub mod session;
+#[doc(hidden)]
+pub mod snapshot;
+#[doc(hidden)]
+pub mod sqlite;
+pub mod strace;
+#[doc(hidden)]
+pub mod summary;
+#[doc(hidden)]
+pub mod symlinks;
+#[doc(hidden)]
+pub mod syscalls;
+#[doc(hidden)]
+pub mod table;
+#[doc(hidden)]
+pub mod targets;
+#[doc(hidden)]
+pub mod tls;
+#[doc(hidden)]
+pub mod trim;
+#[doc(hidden)]
+pub mod truss;
+#[cfg(feature = "native")]
+#[doc(hidden)]
+pub mod ui;
+#[doc(hidden)]
+pub mod units;
+#[doc(hidden)]
+pub mod usage;
+#[doc(hidden)]
+pub mod vst;
+#[doc(hidden)]
+pub mod watch;
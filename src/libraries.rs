@@ -0,0 +1,170 @@
+// Sums up how each program loaded its shared libraries: which ones were opened, in what order,
+// how many places the loader looked in without finding one, and how long it all took (from the
+// execve to the last syscall on a library, including any loaded later with dlopen).
+
+use std::collections::{BTreeMap, HashSet};
+
+use crate::strace::{Syscall, SyscallArgValue};
+use crate::syscalls;
+
+#[derive(Default)]
+pub struct Libraries {
+    // keyed by pid, for the last program that each process ran
+    pub programs: BTreeMap<Option<u32>, Loading>,
+}
+
+#[derive(Default)]
+pub struct Loading {
+    // the paths of the libraries that were opened, in order
+    pub loaded: Vec<String>,
+    // opens of a library that failed, usually the loader trying each directory in its search path
+    pub misses: usize,
+    start_micros: Option<u64>,
+    end_micros: u64,
+    // the fds of libraries that are still open
+    fds: HashSet<i64>,
+}
+
+impl Libraries {
+    pub fn update(&mut self, syscall: &Syscall) {
+        if syscall.error_details.is_some() {
+            return;
+        }
+        let end = syscall.entry_time_micros + syscall.syscall_time_micros.unwrap_or(0);
+        if matches!(syscall.name.as_str(), "execve" | "execveat") && syscall.errno.is_none() {
+            self.programs.insert(
+                syscall.pid,
+                Loading {
+                    start_micros: Some(end),
+                    ..Default::default()
+                },
+            );
+            return;
+        }
+
+        let fd = match syscall.arg(0).map(|a| &a.value) {
+            Some(SyscallArgValue::Number(fd)) => *fd,
+            _ => -1,
+        };
+        match syscall.name.as_str() {
+            "open" | "openat" | "openat2" => {
+                let path = match syscalls::path_args(syscall).into_iter().next() {
+                    Some(path) if is_library(&path) => path,
+                    _ => return,
+                };
+                let loading = self.programs.entry(syscall.pid).or_default();
+                loading
+                    .start_micros
+                    .get_or_insert(syscall.entry_time_micros);
+                loading.end_micros = end;
+                if syscall.errno.is_some() {
+                    loading.misses += 1;
+                } else {
+                    loading.loaded.push(path);
+                    loading.fds.insert(syscall.return_value);
+                }
+            }
+            // the loader reads the ELF header, maps the segments, and closes the file
+            "read" | "pread64" | "fstat" | "newfstatat" | "mmap" | "close" => {
+                // mmap's fd is its fifth argument
+                let fd = if syscall.name == "mmap" {
+                    match syscall.arg(4).map(|a| &a.value) {
+                        Some(SyscallArgValue::Number(fd)) => *fd,
+                        _ => return,
+                    }
+                } else {
+                    fd
+                };
+                let loading = match self.programs.get_mut(&syscall.pid) {
+                    Some(loading) if loading.fds.contains(&fd) => loading,
+                    _ => return,
+                };
+                loading.end_micros = end;
+                if syscall.name == "close" {
+                    loading.fds.remove(&fd);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Loading {
+    pub fn duration_micros(&self) -> u64 {
+        self.start_micros
+            .map(|start| self.end_micros.saturating_sub(start))
+            .unwrap_or_default()
+    }
+
+    /// The directories that libraries were loaded from, with how many came from each, in the
+    /// order that they were first used.
+    pub fn directories(&self) -> Vec<(&str, usize)> {
+        let mut dirs: Vec<(&str, usize)> = Vec::new();
+        for path in &self.loaded {
+            let dir = path.rsplit_once('/').map(|(dir, _)| dir).unwrap_or(".");
+            match dirs.iter_mut().find(|(d, _)| *d == dir) {
+                Some((_, count)) => *count += 1,
+                None => dirs.push((dir, 1)),
+            }
+        }
+        dirs
+    }
+}
+
+// e.g., "libc.so.6" or "libfoo.so", but not "ld.so.cache"
+fn is_library(path: &str) -> bool {
+    let name = path.rsplit('/').next().unwrap_or(path);
+    match name.rsplit_once(".so") {
+        Some((_, version)) => version.strip_prefix('.').map_or(version.is_empty(), |v| {
+            v.chars().all(|c| c.is_ascii_digit() || c == '.')
+        }),
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::strace::{LineParser, Message};
+
+    use super::{is_library, Libraries};
+
+    #[test]
+    fn test_libraries() {
+        let mut libraries = Libraries::default();
+        let mut parser = LineParser::default();
+        for line in [
+            "10 1700000000.000000 execve(\"/usr/bin/app\", [\"app\"], 0x7ffc /* 1 vars */) = 0 <0.000100>",
+            "10 1700000000.000200 openat(AT_FDCWD, \"/etc/ld.so.cache\", O_RDONLY|O_CLOEXEC) = 3 <0.000010>",
+            "10 1700000000.000300 close(3) = 0 <0.000005>",
+            "10 1700000000.000400 openat(AT_FDCWD, \"/opt/app/lib/libfoo.so.1\", O_RDONLY|O_CLOEXEC) = -1 ENOENT (No such file or directory) <0.000010>",
+            "10 1700000000.000500 openat(AT_FDCWD, \"/usr/lib/libfoo.so.1\", O_RDONLY|O_CLOEXEC) = 3 <0.000010>",
+            "10 1700000000.000600 read(3, \"\\177ELF\", 832) = 832 <0.000010>",
+            "10 1700000000.000700 mmap(NULL, 8192, PROT_READ, MAP_PRIVATE|MAP_DENYWRITE, 3, 0) = 0x7f00 <0.000010>",
+            "10 1700000000.000800 close(3) = 0 <0.000010>",
+            "10 1700000000.000900 openat(AT_FDCWD, \"/usr/lib/libc.so.6\", O_RDONLY|O_CLOEXEC) = 3 <0.000010>",
+            "10 1700000000.001000 close(3) = 0 <0.000050>",
+            "10 1700000000.002000 openat(AT_FDCWD, \"data.txt\", O_RDONLY) = 3 <0.000010>",
+            "10 1700000000.003000 close(3) = 0 <0.000010>",
+        ] {
+            let syscall = parser
+                .parse_line(line)
+                .and_then(Message::into_syscall)
+                .unwrap();
+            libraries.update(&syscall);
+        }
+
+        let loading = &libraries.programs[&Some(10)];
+        assert_eq!(
+            loading.loaded,
+            ["/usr/lib/libfoo.so.1", "/usr/lib/libc.so.6"]
+        );
+        assert_eq!(loading.misses, 1);
+        assert_eq!(loading.duration_micros(), 950);
+        assert_eq!(loading.directories(), [("/usr/lib", 2)]);
+
+        assert!(is_library("libfoo.so"));
+        assert!(is_library("/lib64/ld-linux-x86-64.so.2"));
+        assert!(!is_library("/etc/ld.so.preload"));
+        assert!(!is_library("/home/me/also.something"));
+    }
+}
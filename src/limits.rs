@@ -0,0 +1,179 @@
+// Stops a trace once it has gone on long enough, for bounded captures of long-running processes.
+
+use std::collections::BTreeSet;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use clap::ValueEnum;
+
+use crate::procfs;
+use crate::strace::{self, Syscall};
+
+pub struct Limits {
+    pub max_events: Option<usize>,
+    pub duration: Option<Duration>,
+    pub action: LimitAction,
+}
+
+/// What to do to the traced processes when a limit is reached.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum LimitAction {
+    /// stop tracing but leave the processes running
+    Detach,
+    /// kill the processes
+    Kill,
+}
+
+pub struct Limiter {
+    limits: Limits,
+    strace_pid: u32,
+    attached_pids: Vec<u32>,
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    events: usize,
+    // every process seen in the trace, so that they can all be killed
+    pids: BTreeSet<u32>,
+    // why tracing was stopped, if it was
+    stopped: Option<String>,
+    finished: bool,
+}
+
+impl Limiter {
+    pub fn new(limits: Limits, strace_pid: u32, attached_pids: Vec<u32>) -> Arc<Limiter> {
+        let limiter = Arc::new(Limiter {
+            limits,
+            strace_pid,
+            attached_pids,
+            state: Mutex::new(State::default()),
+        });
+
+        if let Some(duration) = limiter.limits.duration {
+            let limiter = limiter.clone();
+            thread::spawn(move || {
+                thread::sleep(duration);
+                limiter.stop(format!("reached --duration {:?}", duration));
+            });
+        }
+        limiter
+    }
+
+    /// Counts `syscall` towards the limits. Returns false if it arrived after tracing was stopped
+    /// and should be dropped.
+    pub fn record(&self, syscall: &Syscall) -> bool {
+        let events = {
+            let mut state = self.state.lock().unwrap();
+            if state.stopped.is_some() {
+                return false;
+            }
+            state.events += 1;
+            if let Some(pid) = syscall.pid {
+                state.pids.insert(pid);
+            }
+            state.events
+        };
+
+        if self.limits.max_events == Some(events) {
+            self.stop(format!("reached --max-events {}", events));
+        }
+        true
+    }
+
+    /// Called once strace has exited. Returns why tracing was stopped early, if it was.
+    pub fn finish(&self) -> Option<String> {
+        let mut state = self.state.lock().unwrap();
+        state.finished = true;
+        state.stopped.clone()
+    }
+
+    pub fn stopped(&self) -> bool {
+        self.state.lock().unwrap().stopped.is_some()
+    }
+
+    fn stop(&self, reason: String) {
+        let mut state = self.state.lock().unwrap();
+        // once strace has exited, its PIDs may have been reused by unrelated processes
+        if state.finished || state.stopped.is_some() {
+            return;
+        }
+        tracing::info!(reason, action = ?self.limits.action, "stopping trace");
+        state.stopped = Some(reason);
+
+        match self.limits.action {
+            LimitAction::Detach => strace::detach(self.strace_pid),
+            LimitAction::Kill => {
+                // strace's own children cover the traced command when strace did not print PIDs
+                let mut pids = state.pids.clone();
+                pids.extend(&self.attached_pids);
+                pids.extend(procfs::children(self.strace_pid));
+                for pid in pids {
+                    strace::kill(pid);
+                }
+            }
+        }
+    }
+}
+
+/// Parses a duration such as "30s", "5m", "1h", "500ms", or "1:30" (as on a clock, with hours
+/// first if there are three parts). A bare number is in seconds.
+pub fn parse_duration(text: &str) -> Result<Duration> {
+    // e.g., "1:30" or "00:01:30"
+    if text.contains(':') {
+        let mut seconds = 0.0;
+        for part in text.split(':') {
+            let part: f64 = part
+                .parse()
+                .map_err(|_| anyhow!("invalid duration: {:?}", text))?;
+            seconds = seconds * 60.0 + part;
+        }
+        return Ok(Duration::from_secs_f64(seconds));
+    }
+    let split = text
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(text.len());
+    let (number, unit) = text.split_at(split);
+    let number: f64 = number
+        .parse()
+        .map_err(|_| anyhow!("invalid duration: {:?}", text))?;
+    let seconds = match unit {
+        "ms" => number / 1000.0,
+        "" | "s" => number,
+        "m" => number * 60.0,
+        "h" => number * 3600.0,
+        _ => {
+            return Err(anyhow!(
+                "invalid duration unit {:?} (expected ms, s, m, or h)",
+                unit
+            ))
+        }
+    };
+    Ok(Duration::from_secs_f64(seconds))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::parse_duration;
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("30s").unwrap(), Duration::from_secs(30));
+        assert_eq!(parse_duration("30").unwrap(), Duration::from_secs(30));
+        assert_eq!(parse_duration("1.5m").unwrap(), Duration::from_secs(90));
+        assert_eq!(parse_duration("2h").unwrap(), Duration::from_secs(7200));
+        assert_eq!(parse_duration("250ms").unwrap(), Duration::from_millis(250));
+        assert_eq!(parse_duration("00:01:30").unwrap(), Duration::from_secs(90));
+        assert_eq!(
+            parse_duration("2:00.5").unwrap(),
+            Duration::from_millis(120_500)
+        );
+        assert!(parse_duration("1::2").is_err());
+        assert!(parse_duration("10d").is_err());
+        assert!(parse_duration("s").is_err());
+    }
+}
@@ -0,0 +1,44 @@
+// Logs vistrace's own internal events to a file (--log-file), so that bugs in vistrace can be
+// diagnosed without the log messages getting mixed in with the trace or the interface.
+
+use std::fs::File;
+use std::path::Path;
+use std::sync::Mutex;
+
+use anyhow::{anyhow, Result};
+use clap::ValueEnum;
+use tracing::Level;
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum LogLevel {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl From<LogLevel> for Level {
+    fn from(level: LogLevel) -> Level {
+        match level {
+            LogLevel::Error => Level::ERROR,
+            LogLevel::Warn => Level::WARN,
+            LogLevel::Info => Level::INFO,
+            LogLevel::Debug => Level::DEBUG,
+            LogLevel::Trace => Level::TRACE,
+        }
+    }
+}
+
+/// Sends log messages at `level` and above to the file at `path`. Without this, nothing is logged.
+pub fn init(path: &Path, level: LogLevel) -> Result<()> {
+    let file =
+        File::create(path).map_err(|e| anyhow!("unable to create {}: {}", path.display(), e))?;
+    tracing_subscriber::fmt()
+        .with_writer(Mutex::new(file))
+        .with_ansi(false)
+        .with_max_level(Level::from(level))
+        .with_thread_names(true)
+        .init();
+    Ok(())
+}
@@ -0,0 +1,221 @@
+// Finds busy loops: a process making the same syscall over and over without getting anywhere, such
+// as poll with a zero timeout or a read on a non-blocking socket that keeps failing with EAGAIN.
+// These burn CPU while looking idle from the outside.
+//
+// A call gets nowhere if it fails, if it waits for something and returns 0 (a timeout, or the end
+// of a file), or if it is sched_yield. Calls with the same name, fd, and outcome belong to the same
+// loop as long as they come close enough together, so loops of several syscalls (say, epoll_wait
+// and then read) are found as well.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::fdtable::{FdKind, FdTable};
+use crate::strace::{format_timestamp, Syscall};
+use crate::syscalls;
+
+// the fewest calls that count as a loop
+const MIN_CALLS: usize = 100;
+// the slowest rate that counts as a loop, in calls per second
+const MIN_RATE: f64 = 500.0;
+// the syscalls that wait for something, which return 0 if nothing happened
+const WAITS: &[&str] = &[
+    "poll",
+    "ppoll",
+    "select",
+    "pselect6",
+    "epoll_wait",
+    "epoll_pwait",
+    "epoll_pwait2",
+    "read",
+    "recvfrom",
+    "recvmsg",
+    "wait4",
+    "waitid",
+];
+// the longest gap between two calls in the same loop
+const MAX_GAP_MICROS: u64 = 100_000;
+
+#[derive(Default)]
+pub struct Loops {
+    // loops that are over
+    loops: Vec<Loop>,
+    runs: HashMap<Key, Loop>,
+}
+
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+struct Key {
+    pid: Option<u32>,
+    name: String,
+    fd: Option<i64>,
+    // e.g., "0" or "-1 EAGAIN"
+    outcome: String,
+}
+
+#[derive(Clone, Debug)]
+pub struct Loop {
+    pub pid: Option<u32>,
+    // e.g., "read(3) = -1 EAGAIN"
+    pub call: String,
+    // what the fd referred to, if it was known
+    pub fd_kind: Option<String>,
+    pub calls: usize,
+    pub start_micros: u64,
+    pub end_micros: u64,
+}
+
+impl Loops {
+    /// Must be called after `fds` has been updated with `syscall`.
+    pub fn update(&mut self, syscall: &Syscall, fds: &FdTable) {
+        let stuck = syscall.errno.is_some()
+            || (syscall.return_value == 0 && WAITS.contains(&syscall.name.as_str()))
+            || syscall.name == "sched_yield";
+        if !stuck || syscall.error_details.is_some() {
+            return;
+        }
+        let fd = syscalls::fd_args(syscall).into_iter().next();
+        let outcome = match &syscall.errno {
+            Some(errno) => format!("{} {}", syscall.return_value, errno),
+            None => syscall.return_value.to_string(),
+        };
+        let key = Key {
+            pid: syscall.pid,
+            name: syscall.name.clone(),
+            fd,
+            outcome,
+        };
+        let time = syscall.entry_time_micros;
+
+        if let Some(run) = self.runs.get_mut(&key) {
+            if time.saturating_sub(run.end_micros) <= MAX_GAP_MICROS {
+                run.calls += 1;
+                run.end_micros = time;
+                return;
+            }
+            let run = self.runs.remove(&key).unwrap();
+            if run.is_busy() {
+                self.loops.push(run);
+            }
+        }
+
+        let fd_kind = fd
+            .map(|fd| fds.resolve(syscall.pid, fd, time).kind)
+            .filter(|kind| *kind != FdKind::Unknown)
+            .map(|kind| kind.to_string());
+        let call = match fd {
+            Some(fd) => format!("{}({}) = {}", key.name, fd, key.outcome),
+            None => format!("{}() = {}", key.name, key.outcome),
+        };
+        self.runs.insert(
+            key.clone(),
+            Loop {
+                pid: key.pid,
+                call,
+                fd_kind,
+                calls: 1,
+                start_micros: time,
+                end_micros: time,
+            },
+        );
+    }
+
+    /// Returns the busy loops so far, including any that are still going, in the order they began.
+    pub fn found(&self) -> Vec<&Loop> {
+        let mut loops: Vec<&Loop> = self
+            .loops
+            .iter()
+            .chain(self.runs.values().filter(|r| r.is_busy()))
+            .collect();
+        loops.sort_by_key(|l| (l.start_micros, l.pid, l.call.clone()));
+        loops
+    }
+}
+
+impl Loop {
+    pub fn duration_micros(&self) -> u64 {
+        self.end_micros - self.start_micros
+    }
+
+    // calls per second, if the trace has timestamps
+    pub fn rate(&self) -> Option<f64> {
+        match self.duration_micros() {
+            0 => None,
+            micros => Some(self.calls as f64 * 1_000_000.0 / micros as f64),
+        }
+    }
+
+    fn is_busy(&self) -> bool {
+        self.calls >= MIN_CALLS && self.rate().is_none_or(|rate| rate >= MIN_RATE)
+    }
+}
+
+impl fmt::Display for Loop {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} × {}", self.calls, self.call)?;
+        if let Some(kind) = &self.fd_kind {
+            write!(f, " on {}", kind)?;
+        }
+        if let Some(rate) = self.rate() {
+            write!(
+                f,
+                " in {} seconds ({:.0}/s)",
+                format_timestamp(self.duration_micros()),
+                rate
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fdtable::FdTable;
+    use crate::strace::{LineParser, Message};
+
+    use super::Loops;
+
+    #[test]
+    fn test_loops() {
+        let mut loops = Loops::default();
+        let mut fds = FdTable::default();
+        let mut parser = LineParser::default();
+        let mut lines = vec![
+            "10 1700000000.000000 socket(AF_INET, SOCK_STREAM|SOCK_NONBLOCK, IPPROTO_TCP) = 3 <0.000010>"
+                .to_string(),
+        ];
+        // 200 spins in 0.1 seconds, alternating between poll and read
+        for i in 0..200 {
+            let time = 1_700_000_000_000_100u64 + i * 500;
+            let time = format!("{}.{:06}", time / 1_000_000, time % 1_000_000);
+            lines.push(format!(
+                "10 {} poll([{{fd=3, events=POLLIN}}], 1, 0) = 0 (Timeout) <0.000001>",
+                time
+            ));
+            lines.push(format!("10 {} read(3, 0x7ffc1000, 4096) = -1 EAGAIN (Resource temporarily unavailable) <0.000001>", time));
+        }
+        // a slow loop: once a second
+        for i in 0..200 {
+            lines.push(format!(
+                "11 {}.000000 nanosleep({{tv_sec=1, tv_nsec=0}}, NULL) = 0 <1.000000>",
+                1_700_000_001 + i
+            ));
+        }
+        for line in &lines {
+            let syscall = parser
+                .parse_line(line)
+                .and_then(Message::into_syscall)
+                .unwrap();
+            fds.update(&syscall);
+            loops.update(&syscall, &fds);
+        }
+
+        let found: Vec<String> = loops.found().iter().map(|l| l.to_string()).collect();
+        assert_eq!(
+            found,
+            [
+                "200 × poll() = 0 in 0.099500 seconds (2010/s)",
+                "200 × read(3) = -1 EAGAIN on socket (AF_INET, SOCK_STREAM) in 0.099500 seconds (2010/s)",
+            ]
+        );
+    }
+}
@@ -0,0 +1,275 @@
+// Library calls as well as syscalls (`--ltrace`, or `--backend ltrace`), by running ltrace rather
+// than strace. With -f -ttt -T -S, ltrace prints the calls that the traced program makes into
+// shared libraries, and its syscalls with a "SYS_" in front, e.g.:
+//
+//   [pid 4120] 1720000000.123456 puts("hello" <unfinished ...>
+//   [pid 4120] 1720000000.123470 SYS_write(1, "hello\n", 6) = 6 <0.000009>
+//   [pid 4120] 1720000000.123490 <... puts resumed> ) = 6 <0.000034>
+//   [pid 4120] 1720000000.123500 +++ exited (status 0) +++
+//
+// A library call is left unfinished while the calls that it makes run, so the calls of a process
+// nest. The syscalls become syscalls as strace would print them, and the library calls become
+// `Message::LibCall`s, which are shown among them.
+
+use std::collections::HashMap;
+#[cfg(feature = "native")]
+use std::path::PathBuf;
+#[cfg(feature = "native")]
+use std::process::{Child, Command, Stdio};
+
+#[cfg(feature = "native")]
+use anyhow::{anyhow, Result};
+
+use crate::strace::{self, Exit, ExitKind, LibCall, Message, Parse, Signal};
+
+#[cfg(feature = "native")]
+pub struct LtraceOptions {
+    pub ltrace_path: PathBuf,
+    // program to run under ltrace, if any
+    pub command: Vec<String>,
+    // already-running processes to trace
+    pub pids: Vec<u32>,
+    // trace the children of the command or processes as well
+    pub follow: bool,
+}
+
+#[cfg(feature = "native")]
+pub fn spawn(options: &LtraceOptions) -> Result<Child> {
+    let mut cmd = Command::new(&options.ltrace_path);
+    cmd.args(["-ttt", "-T", "-S"]);
+    if options.follow {
+        cmd.arg("-f");
+    }
+    for pid in &options.pids {
+        cmd.arg("-p").arg(pid.to_string());
+    }
+    cmd.args(&options.command)
+        .stderr(Stdio::piped())
+        .stdout(Stdio::piped());
+    tracing::info!(command = ?cmd, "spawning ltrace");
+    let child = cmd
+        .spawn()
+        .map_err(|e| anyhow!("unable to spawn ltrace: {}", e))?;
+    tracing::info!(pid = child.id(), "ltrace started");
+    Ok(child)
+}
+
+/// Returns true if `line`, without its pid and time, is a library call as `Message::LibCall`
+/// prints it, e.g. "->malloc(16) = 0x55d0c8a2a2a0".
+pub fn is_libcall_line(line: &str) -> bool {
+    line.split_once('(')
+        .is_some_and(|(name, _)| name.contains("->") && !name.contains(char::is_whitespace))
+}
+
+/// Parses a library call without its pid and time, e.g. "ls->malloc(16) = 0x55d0c8a2a2a0
+/// <0.000012>".
+pub fn parse_call(line: &str) -> Option<LibCall> {
+    let line = line.trim_end();
+    // how long the call took, with -T
+    let (line, duration_micros) = match line.rsplit_once(" <") {
+        Some((before, time)) => match time.strip_suffix('>').map(str::parse::<f64>) {
+            Some(Ok(secs)) => (before, (secs * 1e6).round() as u64),
+            // e.g., "free(0x55d0c8a2a2a0) = <void>"
+            _ => (line, 0),
+        },
+        None => (line, 0),
+    };
+    let (call, return_value) = line.rsplit_once(" = ")?;
+    let (name, args) = call.split_once('(')?;
+    let args = args.trim_end().strip_suffix(')')?;
+    let (caller, name) = match name.split_once("->") {
+        Some((caller, name)) => ((!caller.is_empty()).then(|| caller.to_string()), name),
+        None => (None, name),
+    };
+    if name.is_empty() || name.contains(char::is_whitespace) {
+        return None;
+    }
+    Some(LibCall {
+        pid: None,
+        caller,
+        name: name.to_string(),
+        args: args.trim().to_string(),
+        return_value: return_value.trim().to_string(),
+        time_micros: 0,
+        duration_micros,
+    })
+}
+
+/// Turns lines of ltrace's output into messages.
+#[derive(Default)]
+pub struct Parser {
+    // syscall names and `%category`s to drop
+    exclude: Vec<String>,
+    // the calls that have yet to return, innermost last, by pid, with when they started
+    unfinished: HashMap<Option<u32>, Vec<(u64, String)>>,
+}
+
+impl Parser {
+    pub fn with_exclude(exclude: Vec<String>) -> Parser {
+        Parser {
+            exclude,
+            ..Parser::default()
+        }
+    }
+
+    // joins "<... puts resumed> ) = 6" to the start of the call, returning it with when it started
+    fn resume(&mut self, pid: Option<u32>, resumed: &str) -> Option<(u64, String)> {
+        let (name, rest) = resumed.split_once(" resumed>")?;
+        let unfinished = self.unfinished.get_mut(&pid)?;
+        let i = unfinished.iter().rposition(|(_, start)| {
+            start
+                .split_once('(')
+                .is_some_and(|(called, _)| called.ends_with(name))
+        })?;
+        let (time_micros, start) = unfinished.remove(i);
+        Some((time_micros, format!("{}{}", start.trim_end(), rest)))
+    }
+
+    // returns `None` if the line is malformed
+    fn parse_body(
+        &mut self,
+        pid: Option<u32>,
+        time_micros: u64,
+        body: &str,
+    ) -> Option<Option<Message>> {
+        if let Some(signal) = body.strip_prefix("--- ") {
+            // e.g., "--- SIGCHLD (Child exited) ---"
+            let name = signal
+                .split(' ')
+                .next()
+                .filter(|name| name.starts_with("SIG"))?;
+            return Some(Some(Message::Signal(Signal {
+                pid,
+                time_micros,
+                name: name.to_string(),
+                info: HashMap::new(),
+            })));
+        }
+        if let Some(status) = body.strip_prefix("+++ ") {
+            // e.g., "+++ exited (status 0) +++" or "+++ killed by SIGSEGV +++"
+            let status = status.strip_suffix(" +++")?;
+            let status = match status.strip_prefix("exited (status ") {
+                Some(code) => ExitKind::Exited(code.strip_suffix(')')?.parse().ok()?),
+                None => {
+                    let signal = status.strip_prefix("killed by ")?;
+                    let (signal, core_dumped) = match signal.strip_suffix(" (core dumped)") {
+                        Some(signal) => (signal, true),
+                        None => (signal, false),
+                    };
+                    ExitKind::Killed {
+                        signal: signal.to_string(),
+                        core_dumped,
+                    }
+                }
+            };
+            return Some(Some(Message::Exit(Exit {
+                pid,
+                time_micros,
+                status,
+            })));
+        }
+
+        if let Some(start) = body.strip_suffix("<unfinished ...>") {
+            self.unfinished
+                .entry(pid)
+                .or_default()
+                .push((time_micros, start.to_string()));
+            return Some(None);
+        }
+        let (time_micros, call) = match body.strip_prefix("<... ") {
+            Some(resumed) => self.resume(pid, resumed)?,
+            None => match body.strip_suffix("<no return ...>") {
+                // e.g., "SYS_exit_group(0 <no return ...>", as strace prints "= ?"
+                Some(start) => (time_micros, format!("{}) = ?", start.trim_end())),
+                None => (time_micros, body.to_string()),
+            },
+        };
+
+        if let Some(syscall) = call.strip_prefix("SYS_") {
+            // strace's parser only reads the time that a syscall took if it starts with a time
+            let mut syscall = if syscall.ends_with('>') {
+                let timed = format!("{} {}", strace::format_timestamp(time_micros), syscall);
+                strace::parse_syscall(&timed, true)
+            } else {
+                strace::parse_syscall(syscall, false)
+            };
+            if strace::excludes(&self.exclude, &syscall.name) {
+                return Some(None);
+            }
+            syscall.pid = pid;
+            syscall.entry_time_micros = time_micros;
+            return Some(Some(Message::Syscall(syscall)));
+        }
+        let call = parse_call(&call)?;
+        Some(Some(Message::LibCall(LibCall {
+            pid,
+            time_micros,
+            ..call
+        })))
+    }
+}
+
+impl Parse for Parser {
+    fn parse_line(&mut self, line: &str) -> Option<Message> {
+        let line = line.trim();
+        let (pid, rest) = strace::split_pid_prefix(line);
+        let (time_micros, body) = strace::split_timestamp(rest);
+        if body.is_empty() {
+            return None;
+        }
+        match self.parse_body(pid, time_micros, body) {
+            Some(msg) => msg,
+            // e.g., "Can't attach to process 4120: Operation not permitted"
+            None => Some(Message::Notice(line.to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::strace::{LineParser, Message, Parse};
+
+    use super::Parser;
+
+    #[test]
+    fn test_parse() {
+        let mut p = Parser::with_exclude(vec!["brk".to_string()]);
+        let lines = [
+            "[pid 4120] 1720000000.123400 __libc_start_main(0x401136, 1, 0x7ffd, 0x401160 <unfinished ...>",
+            "[pid 4120] 1720000000.123410 SYS_brk(0) = 0x55d0c8a2a000 <0.000002>",
+            "[pid 4120] 1720000000.123456 puts(\"hello\" <unfinished ...>",
+            "[pid 4120] 1720000000.123470 SYS_write(1, \"hello\\n\", 6) = 6 <0.000009>",
+            "[pid 4120] 1720000000.123490 <... puts resumed> ) = 6 <0.000034>",
+            "[pid 4120] 1720000000.123495 ls->free(0x55d0c8a2a2a0) = <void> <0.000001>",
+            "[pid 4120] 1720000000.123498 --- SIGCHLD (Child exited) ---",
+            "[pid 4120] 1720000000.123499 SYS_exit_group(0 <no return ...>",
+            "[pid 4120] 1720000000.123500 +++ exited (status 0) +++",
+        ];
+        let messages: Vec<Message> = lines.iter().filter_map(|l| p.parse_line(l)).collect();
+        let printed: Vec<String> = messages.iter().map(|msg| msg.to_string()).collect();
+        assert_eq!(
+            printed,
+            [
+                "4120 1720000000.123470 write(1, \"hello\\n\", 6) = 6 <0.000009>",
+                "4120 1720000000.123456 ->puts(\"hello\") = 6 <0.000034>",
+                "4120 1720000000.123495 ls->free(0x55d0c8a2a2a0) = <void> <0.000001>",
+                "4120 1720000000.123498 --- SIGCHLD ---",
+                "4120 1720000000.123499 exit_group(0) = ?",
+                "4120 1720000000.123500 +++ exited with 0 +++",
+            ]
+        );
+
+        // saved traces are read back by strace's parser
+        let mut strace = LineParser::default();
+        let reparsed: Vec<String> = printed
+            .iter()
+            .filter_map(|l| strace.parse_line(l))
+            .map(|msg| msg.to_string())
+            .collect();
+        assert_eq!(reparsed, printed);
+        assert!(matches!(
+            &messages[2],
+            Message::LibCall(call) if call.caller.as_deref() == Some("ls") && call.name == "free"
+        ));
+    }
+}
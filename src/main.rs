@@ -1,20 +1,805 @@
-use std::sync::mpsc;
-use std::{env, process, thread};
+use std::fs::File;
+use std::io::{self, IsTerminal, LineWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitStatus;
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use std::{env, process};
 
-use anyhow::Result;
-use clap::Parser;
+use anyhow::{anyhow, Result};
+use clap::{Args, Parser, Subcommand, ValueEnum};
 
-mod strace;
-mod ui;
+use vistrace::{
+    allowlist, anonymize, audit, bpf, changes, chrome, commands, compare, control, csv, diff, dot,
+    dtruss, environment, eventfds, fdtable, filter, flamegraph, inject, journal, jsonl, layout,
+    limits, logging, ltrace, mermaid, namespaces, net, palette, pcap, peers, policy, processes,
+    procfs, provenance, ptrace, redact, remote, rules, sample, seccomp, session, sqlite, strace,
+    summary, targets, trim, truss, ui, units, usage, vst,
+};
 
 #[derive(Parser, Debug)]
-#[clap(trailing_var_arg = true)]
-struct Args {
-    /// passed on to strace
-    #[arg(required = true, num_args = 1..)]
+#[clap(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    // `vistrace <cmd>` is short for `vistrace run <cmd>`
+    #[command(flatten)]
+    run: RunArgs,
+
+    /// log vistrace's own internal events to a file, for diagnosing problems with vistrace
+    #[arg(long, value_name = "PATH", global = true)]
+    log_file: Option<PathBuf>,
+
+    /// how much to write to --log-file
+    #[arg(long, value_enum, value_name = "LEVEL", default_value_t = logging::LogLevel::Info, global = true)]
+    log_level: logging::LogLevel,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// run a command and trace it
+    Run(RunArgs),
+    /// trace processes that are already running
+    Attach(AttachArgs),
+    /// view a trace saved with --save
+    View(ViewArgs),
+    /// print a summary of the syscalls, files, and processes in a trace saved with --save
+    Stats(StatsArgs),
+    /// show the differences between two traces saved with --save (or written by `strace -o`)
+    Diff(DiffArgs),
+    /// convert a trace saved with --save to text or JSON
+    Export(ExportArgs),
+    /// run a command on another machine over ssh and trace it there, showing the trace here
+    Ssh(SshArgs),
+    /// show a trace that `vistrace run --listen` (or `attach --listen`) is streaming
+    Connect(ConnectArgs),
+    /// compare the environment that a process in a trace saved with --save ran its program with
+    /// to another process's, or to the current environment
+    Env(EnvArgs),
+    /// run a command without the interface and fail if it made syscalls, or used files or hosts,
+    /// that an allowlist does not allow, printing a report of them (e.g., for CI)
+    Check(CheckArgs),
+    /// print the JSON Schema of the records of `vistrace export --format jsonl`
+    Schema,
+}
+
+#[derive(Args, Debug)]
+struct RunArgs {
+    #[command(flatten)]
+    trace: TraceArgs,
+
+    #[command(flatten)]
+    output: OutputArgs,
+
+    /// name of the command in the interface's tabs; to trace several commands at once, give each
+    /// one after `-- --name NAME`
+    #[arg(long)]
+    name: Option<String>,
+
+    /// the command to trace
+    #[arg(required = true, num_args = 1.., trailing_var_arg = true)]
+    args: Vec<String>,
+}
+
+#[derive(Args, Debug)]
+struct SshArgs {
+    /// where to run the command, as for ssh (e.g., 'user@host'); strace must be installed there
+    host: String,
+
+    #[command(flatten)]
+    trace: TraceArgs,
+
+    #[command(flatten)]
+    output: OutputArgs,
+
+    /// the command to trace, as run by the remote shell
+    #[arg(required = true, num_args = 1.., trailing_var_arg = true)]
+    args: Vec<String>,
+}
+
+#[derive(Args, Debug)]
+struct AttachArgs {
+    /// process to attach to (may be repeated)
+    #[arg(
+        short,
+        long = "pid",
+        value_name = "PID",
+        required_unless_present_any = ["pid_glob", "cgroup", "system"]
+    )]
+    pids: Vec<u32>,
+
+    /// attach to every running process whose name matches the pattern (e.g., 'nginx*')
+    #[arg(long, value_name = "PATTERN")]
+    pid_glob: Option<String>,
+
+    /// trace every process in the cgroup, e.g. '/sys/fs/cgroup/system.slice/nginx.service' (with
+    /// --backend bpf)
+    #[arg(long, value_name = "PATH", conflicts_with = "system")]
+    cgroup: Option<PathBuf>,
+
+    /// trace every process on the machine (with --backend bpf)
+    #[arg(long)]
+    system: bool,
+
+    #[command(flatten)]
+    trace: TraceArgs,
+
+    #[command(flatten)]
+    output: OutputArgs,
+}
+
+#[derive(Args, Debug)]
+struct ViewArgs {
+    /// trace saved with --save
+    path: PathBuf,
+
+    /// show events at the pace they were recorded at (use '<' and '>' in the interface to change
+    /// the speed)
+    #[arg(long)]
+    replay: bool,
+
+    /// start this far into the trace (e.g., '90s', '1h'), skipping the events before it
+    #[arg(long, value_name = "DURATION", value_parser = limits::parse_duration)]
+    from: Option<Duration>,
+
+    /// when the interface quits, save its filter, watches, marks, tab, and selection next to the
+    /// trace (as PATH.session), to be restored the next time it is viewed (a session that already
+    /// exists is always restored and saved)
+    #[arg(long)]
+    session: bool,
+
+    #[command(flatten)]
+    exclude: ExcludeArgs,
+
+    #[command(flatten)]
+    output: OutputArgs,
+}
+
+#[derive(Args, Debug)]
+struct ConnectArgs {
+    /// where vistrace is listening ('unix:PATH' or 'HOST:PORT')
+    #[arg(value_parser = remote::parse_address)]
+    address: remote::Address,
+
+    #[command(flatten)]
+    exclude: ExcludeArgs,
+
+    #[command(flatten)]
+    output: OutputArgs,
+}
+
+#[derive(Args, Debug)]
+struct DiffArgs {
+    /// trace of the run to compare against, e.g. one that worked
+    left: PathBuf,
+
+    /// trace of the other run
+    right: PathBuf,
+
+    /// print the differences as text, rather than showing the traces side by side
+    #[arg(long)]
+    report: bool,
+}
+
+#[derive(Args, Debug)]
+struct StatsArgs {
+    /// trace saved with --save
+    path: PathBuf,
+
+    #[command(flatten)]
+    exclude: ExcludeArgs,
+
+    /// add a section for security-relevant activity (see --audit for `run`)
+    #[arg(long)]
+    audit: bool,
+
+    /// compare the syscalls in the trace to those in this earlier trace (e.g., of a build without
+    /// a regression), with the counts and latency percentiles of each side by side
+    #[arg(long, value_name = "PATH", conflicts_with = "audit")]
+    compare: Option<PathBuf>,
+
+    /// whether to color the summary
+    #[arg(long, value_enum, value_name = "WHEN", default_value_t = palette::ColorChoice::Auto)]
+    color: palette::ColorChoice,
+
+    /// write the summary to a file instead of standard output
+    #[arg(long, value_name = "PATH")]
+    output_file: Option<PathBuf>,
+}
+
+#[derive(Args, Debug)]
+struct ExportArgs {
+    /// trace saved with --save
+    path: PathBuf,
+
+    /// instead, write the trace to OUT as a .vst file, cut down to --from and --to (with the
+    /// events before them that they depend on; see src/trim.rs)
+    #[arg(conflicts_with_all = ["format", "output_file", "connection", "seccomp", "apparmor", "landlock", "filter"])]
+    out: Option<PathBuf>,
+
+    /// with OUT, start this far into the trace (e.g., '90s' or '00:01:30')
+    #[arg(long, value_name = "DURATION", value_parser = limits::parse_duration, requires = "out")]
+    from: Option<Duration>,
+
+    /// with OUT, end this far into the trace
+    #[arg(long, value_name = "DURATION", value_parser = limits::parse_duration, requires = "out")]
+    to: Option<Duration>,
+
+    /// format to export to
+    #[arg(long, value_enum, default_value_t = ExportFormat::Jsonl)]
+    format: ExportFormat,
+
+    #[command(flatten)]
+    exclude: ExcludeArgs,
+
+    /// whether to color text output (JSON is never colored)
+    #[arg(long, value_enum, value_name = "WHEN", default_value_t = palette::ColorChoice::Auto)]
+    color: palette::ColorChoice,
+
+    /// write to a file instead of standard output
+    #[arg(long, value_name = "PATH")]
+    output_file: Option<PathBuf>,
+
+    /// with --format pcap, only export the sockets whose description contains TEXT (e.g.,
+    /// '93.184.216.34:443' or 'udp')
+    #[arg(long, value_name = "TEXT")]
+    connection: Option<String>,
+
+    /// instead, write a seccomp profile (for Docker and other OCI runtimes) that allows only the
+    /// syscalls in the trace
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["format", "output_file"])]
+    seccomp: Option<PathBuf>,
+
+    /// instead, write a skeleton AppArmor profile that allows the file and network access in the
+    /// trace
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["format", "output_file", "seccomp"])]
+    apparmor: Option<PathBuf>,
+
+    /// instead, write the Landlock rules that would allow the file and network access in the trace
+    /// (see src/policy.rs for the format)
+    #[arg(
+        long,
+        value_name = "PATH",
+        conflicts_with_all = ["format", "output_file", "seccomp", "apparmor"]
+    )]
+    landlock: Option<PathBuf>,
+
+    /// mask strings, keep only the last component of paths, and replace IP addresses with made-up
+    /// ones (e.g., to attach the trace to a public bug report; see src/redact.rs)
+    #[arg(long)]
+    redact: bool,
+
+    /// replace usernames and hostnames with pseudonyms, which are the same for the same KEY so
+    /// that anonymized traces can be diffed (see src/anonymize.rs)
+    #[arg(long, value_name = "KEY", num_args = 0..=1, require_equals = true)]
+    anonymize: Option<Option<String>>,
+
+    /// only export the syscalls that pass a filter, as typed into the interface (e.g.,
+    /// 'path=^/etc/ !failed'; see src/filter.rs); other events are kept
+    #[arg(long, value_name = "EXPR", value_parser = parse_filter)]
+    filter: Option<String>,
+}
+
+fn parse_filter(text: &str) -> Result<String> {
+    filter::Filter::parse(text)?;
+    Ok(text.to_string())
+}
+
+#[derive(Args, Debug)]
+struct EnvArgs {
+    /// trace saved with --save, with strace's -v option (e.g., `--strace-arg=-v`) so that
+    /// environments were recorded
+    path: PathBuf,
+
+    /// process whose environment to compare (the one its last program was run with)
+    pid: u32,
+
+    /// process to compare it to, instead of the current environment
+    other: Option<u32>,
+}
+
+#[derive(Args, Debug)]
+struct CheckArgs {
+    /// the allowlist: one syscall, `%category`, `path PATTERN`, or `host PATTERN` per line (see
+    /// src/allowlist.rs)
+    #[arg(long, value_name = "PATH")]
+    allow: PathBuf,
+
+    #[command(flatten)]
+    trace: TraceArgs,
+
+    /// write the report to a file instead of standard output
+    #[arg(long, value_name = "PATH")]
+    output_file: Option<PathBuf>,
+
+    /// the command to trace
+    #[arg(required = true, num_args = 1.., trailing_var_arg = true)]
     args: Vec<String>,
 }
 
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+enum ExportFormat {
+    /// one line per event, in the same format as strace
+    Text,
+    /// one line per event, as strace prints it (without colors, annotations, or vistrace's own
+    /// lines, e.g. skipped calls), for other tools that read strace's output, or to view or
+    /// export again
+    Strace,
+    /// one JSON object per event (see src/jsonl.rs, or `vistrace schema` for a JSON Schema)
+    Jsonl,
+    /// one JSON object per network connection, at the end of the trace
+    Connections,
+    /// the tree of commands that were run, with their arguments
+    Commands,
+    /// what the program changed on disk, in order: the files and directories it created, wrote
+    /// to, renamed, deleted, or changed the mode or owner of
+    Changes,
+    /// each file that the trace wrote, with the processes and commands that created and wrote it
+    Provenance,
+    /// one JSON object per command that was run, with its arguments and environment (see
+    /// src/jsonl.rs for the schema)
+    CommandsJson,
+    /// Chrome's Trace Event format, to open in Perfetto or chrome://tracing, with a track for each
+    /// process and thread
+    ChromeTrace,
+    /// one CSV row per event, with a selection of the JSON Lines fields (see src/csv.rs)
+    Csv,
+    /// CSV of the calls, errors, and time for each syscall, as in the summary
+    SummaryCsv,
+    /// a Graphviz graph of the processes and threads, with their commands and exits and the pipes
+    /// and sockets between them
+    Dot,
+    /// a Mermaid sequence diagram of the data that processes sent each other over pipes and
+    /// sockets, in order
+    Mermaid,
+    /// the stacks of the syscalls, recorded with `--stacks`, in the folded format of flamegraph.pl
+    /// (one stack per line, weighted by the microseconds spent in the syscall), for speedscope or
+    /// other flamegraph tools
+    Folded,
+    /// an SVG flamegraph of the stacks of the syscalls, recorded with `--stacks`, weighted by the
+    /// time spent in them
+    Flamegraph,
+    /// the data sent and received over TCP and UDP sockets, as a pcap file for Wireshark, with
+    /// made-up IP, TCP, and UDP headers (see src/pcap.rs)
+    Pcap,
+    /// a SQLite database of events, arguments, processes, fds, and connections (see src/sqlite.rs
+    /// for the schema), written with the sqlite3 program to --output-file, or else the SQL to
+    /// create it
+    Sqlite,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+enum Backend {
+    /// run strace and parse its output
+    Strace,
+    /// trace with ptrace directly, without strace, recording the data of reads and writes in full
+    /// (`run` on x86-64 Linux only; see src/ptrace.rs)
+    Ptrace,
+    /// trace with eBPF through bpftrace, which barely slows the processes down, and can trace a
+    /// cgroup or the whole machine, but records less (needs root; see src/bpf.rs)
+    Bpf,
+    /// run dtruss and parse its output (macOS only, and needs root; see src/dtruss.rs)
+    Dtruss,
+    /// run truss and parse its output (FreeBSD only; see src/truss.rs)
+    Truss,
+    /// run ltrace, which reports calls into shared libraries as well as syscalls (see
+    /// src/ltrace.rs)
+    Ltrace,
+}
+
+impl Backend {
+    // the backend that works out of the box on this OS
+    fn native() -> Backend {
+        if cfg!(target_os = "macos") {
+            Backend::Dtruss
+        } else if cfg!(target_os = "freebsd") {
+            Backend::Truss
+        } else {
+            Backend::Strace
+        }
+    }
+
+    // what a non-zero exit status of the tracer's thread means failed
+    fn failed(self) -> &'static str {
+        match self {
+            // strace and ltrace exit with the traced command's status, unless they fail themselves
+            Backend::Strace => "the traced command (or strace)",
+            Backend::Ltrace => "the traced command (or ltrace)",
+            Backend::Ptrace => "the traced command",
+            Backend::Bpf => "bpftrace",
+            Backend::Dtruss => "dtruss",
+            Backend::Truss => "truss",
+        }
+    }
+}
+
+// options for running strace, shared by `run` and `attach`
+#[derive(Args, Debug)]
+struct TraceArgs {
+    /// trace child processes and threads too (the default)
+    #[arg(long, overrides_with = "no_follow")]
+    follow: bool,
+
+    /// only trace the initial process
+    #[arg(long, overrides_with = "follow")]
+    no_follow: bool,
+
+    /// strace binary to use
+    #[arg(long, env = "VISTRACE_STRACE", default_value = "strace")]
+    strace_path: PathBuf,
+
+    /// how to trace the syscalls
+    #[arg(long, value_enum, env = "VISTRACE_BACKEND", default_value_t = Backend::native())]
+    backend: Backend,
+
+    /// show library calls (e.g., to malloc or printf) among the syscalls, by tracing with ltrace
+    /// (the same as --backend ltrace)
+    #[arg(long)]
+    ltrace: bool,
+
+    /// bpftrace binary to use, for --backend bpf
+    #[arg(long, env = "VISTRACE_BPFTRACE", default_value = "bpftrace")]
+    bpftrace_path: PathBuf,
+
+    /// dtruss binary to use, for --backend dtruss
+    #[arg(long, env = "VISTRACE_DTRUSS", default_value = "dtruss")]
+    dtruss_path: PathBuf,
+
+    /// truss binary to use, for --backend truss
+    #[arg(long, env = "VISTRACE_TRUSS", default_value = "truss")]
+    truss_path: PathBuf,
+
+    /// ltrace binary to use, for --ltrace
+    #[arg(long, env = "VISTRACE_LTRACE", default_value = "ltrace")]
+    ltrace_path: PathBuf,
+
+    #[command(flatten)]
+    exclude: ExcludeArgs,
+
+    /// stop tracing after this many syscalls
+    #[arg(long, value_name = "N")]
+    max_events: Option<usize>,
+
+    /// keep only a fraction of the calls of each syscall, counting the rest, for very busy
+    /// programs (e.g., '1/100' or 'read,write=1/1000'; may be repeated; see src/sample.rs)
+    #[arg(long, value_name = "RATE", value_parser = sample::Rate::parse)]
+    sample: Vec<sample::Rate>,
+
+    /// stop tracing after this long (e.g., '30s', '5m')
+    #[arg(long, value_parser = limits::parse_duration)]
+    duration: Option<Duration>,
+
+    /// what to do to the traced processes when --max-events or --duration is reached (default:
+    /// detach for `attach`, kill for `run`)
+    #[arg(long, value_enum, value_name = "ACTION")]
+    on_limit: Option<limits::LimitAction>,
+
+    /// option to pass on to strace, for options that vistrace does not have (e.g., '-s 4096'; may
+    /// be repeated)
+    #[arg(long = "strace-arg", value_name = "ARG", allow_hyphen_values = true)]
+    strace_args: Vec<String>,
+
+    /// record the stack of each syscall (strace -k), for `vistrace export --format flamegraph`
+    #[arg(long)]
+    stacks: bool,
+
+    /// make syscalls fail, or return or wait, as strace's -e inject= does (e.g.,
+    /// 'openat:error=EIO:every=3'; may be repeated; see src/inject.rs; change with 'J')
+    #[arg(long, value_name = "FAULT", value_parser = inject::Injection::parse)]
+    inject: Vec<inject::Injection>,
+
+    /// save the trace to a file as it streams, to be viewed again later with `vistrace view`
+    #[arg(long, value_name = "PATH")]
+    save: Option<PathBuf>,
+
+    /// when the interface quits, save its filter, watches, marks, tab, and selection next to the
+    /// trace saved with --save, for `vistrace view` to restore
+    #[arg(long, requires = "save")]
+    session: bool,
+
+    /// how often to sample the CPU, memory, threads, and fds of the traced processes for the
+    /// resource view ('U' in the interface); 0 not to
+    #[arg(long, value_name = "DURATION", value_parser = limits::parse_duration, default_value = "1s")]
+    sample_interval: Duration,
+
+    /// instead of showing the trace, wait for `vistrace connect` to connect to ADDRESS
+    /// ('unix:PATH' or 'HOST:PORT') and stream the trace to it
+    #[arg(long, value_name = "ADDRESS", value_parser = remote::parse_address)]
+    listen: Option<remote::Address>,
+
+    /// how --save writes each parsed event: 'json', or 'msgpack', which is smaller and keeps up
+    /// with firehose traces
+    #[arg(long, value_name = "ENCODING", default_value = "json")]
+    encoding: vst::Encoding,
+
+    /// accept commands from other programs (e.g., an editor) on a unix socket at PATH, to follow
+    /// the trace as JSON, filter it, pause and resume the traced processes, and export it (see
+    /// src/control.rs)
+    #[arg(long, value_name = "PATH")]
+    control_socket: Option<PathBuf>,
+
+    /// send the events that set off alerts from the config file, and the summary of the trace, to
+    /// journald or syslog, with structured fields (see src/journal.rs)
+    #[arg(
+        long,
+        value_enum,
+        value_name = "TARGET",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "auto"
+    )]
+    journal: Option<journal::Target>,
+
+    /// mask strings, keep only the last component of paths, and replace IP addresses with made-up
+    /// ones, everywhere the trace goes (e.g., to attach a saved trace to a public bug report; see
+    /// src/redact.rs)
+    #[arg(long)]
+    redact: bool,
+}
+
+impl TraceArgs {
+    fn backend(&self) -> Backend {
+        if self.ltrace {
+            Backend::Ltrace
+        } else {
+            self.backend
+        }
+    }
+}
+
+#[derive(Args, Debug, Default)]
+struct ExcludeArgs {
+    /// syscalls not to show, as a comma-separated list of names and categories (e.g.,
+    /// 'futex,%memory'; categories: file, desc, network, process, signal, ipc, memory)
+    #[arg(long, value_name = "SYSCALLS", value_delimiter = ',')]
+    exclude: Vec<String>,
+}
+
+// options for showing a trace, shared by `run`, `attach`, and `view`
+#[derive(Args, Debug)]
+struct OutputArgs {
+    /// how to show the trace
+    #[arg(long, value_enum, default_value_t = Output::Tui)]
+    output: Output,
+
+    /// run the trace to completion and print a summary of syscalls, files, and processes
+    #[arg(long, conflicts_with = "output")]
+    summary: bool,
+
+    /// print events as they happen instead of starting the interactive interface
+    #[arg(long, conflicts_with_all = ["output", "summary"])]
+    no_tui: bool,
+
+    /// pause the interface at the first failed syscall that passes the filter (toggle with 'e')
+    #[arg(long)]
+    pause_on_error: bool,
+
+    /// flag access to credentials and other sensitive files, outbound connections, shells, and
+    /// privileged syscalls, in the interface, --no-tui, and --summary
+    #[arg(long)]
+    audit: bool,
+
+    /// config file of alerts, highlights, and named filters for the interface (default:
+    /// ~/.config/vistrace/config; see src/rules.rs for the format; reload with 'r')
+    #[arg(long, value_name = "PATH", env = "VISTRACE_CONFIG")]
+    config: Option<PathBuf>,
+
+    /// explain each event in plain English next to it in the interface, e.g. "opened /etc/hosts
+    /// read-only as fd 3" (toggle with 'i'; the detail pane always explains the selected event)
+    #[arg(long)]
+    explain: bool,
+
+    /// show sizes and durations exactly (e.g., 4096 and 0.003412s) rather than in human units (4.0
+    /// KiB and 3.4 ms), in the interface (toggle with 'x') and in --summary
+    #[arg(long)]
+    exact: bool,
+
+    /// how to arrange the interface's panes: default, output, stats, or a layout from the config
+    /// file (see src/layout.rs; switch with 'V')
+    #[arg(long, value_name = "NAME", default_value = "default")]
+    layout: String,
+
+    /// start the interface with a named filter from the config file (e.g., `--preset network-only`
+    /// for a line `filter network-only %network`; see src/rules.rs), which '/' can save filters as
+    #[arg(long, value_name = "NAME")]
+    preset: Option<String>,
+
+    /// when tracing live, look up paths on this machine to show where symlinks lead (symlinks
+    /// that the program itself read with readlink are always shown)
+    #[arg(long)]
+    resolve_links: bool,
+
+    /// whether to color --no-tui and --summary output (JSON is never colored)
+    #[arg(long, value_enum, value_name = "WHEN", default_value_t = palette::ColorChoice::Auto)]
+    color: palette::ColorChoice,
+
+    /// write non-interactive output to a file instead of standard output
+    #[arg(long, value_name = "PATH")]
+    output_file: Option<PathBuf>,
+
+    // set by `export --connection`
+    #[arg(skip)]
+    connection: Option<String>,
+
+    // set by `check`
+    #[arg(skip)]
+    allowlist: Option<allowlist::Allowlist>,
+
+    // set by `export --redact`
+    #[arg(skip)]
+    redactor: Option<redact::Redactor>,
+
+    // set by `export --anonymize`, with its key
+    #[arg(skip)]
+    anonymize: Option<Option<String>>,
+
+    // set by `export --filter`
+    #[arg(skip)]
+    filter: Option<String>,
+
+    // set by `view`, where the session of the trace is kept
+    #[arg(skip)]
+    session: Option<PathBuf>,
+
+    // set by `view`, the session at the top of a snapshot, if it has no session file
+    #[arg(skip)]
+    snapshot: Option<session::Session>,
+}
+
+impl OutputArgs {
+    /// Non-interactive output with no other options, for `stats` and `export`.
+    fn plain(
+        output: Output,
+        color: palette::ColorChoice,
+        output_file: Option<PathBuf>,
+    ) -> OutputArgs {
+        OutputArgs {
+            output,
+            summary: false,
+            no_tui: false,
+            pause_on_error: false,
+            audit: false,
+            config: None,
+            exact: false,
+            explain: false,
+            layout: "default".to_string(),
+            preset: None,
+            resolve_links: false,
+            color,
+            output_file,
+            connection: None,
+            allowlist: None,
+            redactor: None,
+            anonymize: None,
+            filter: None,
+            session: None,
+            snapshot: None,
+        }
+    }
+
+    /// The rules for the interface, if the trace will be shown in it.
+    fn rules(&self) -> Result<rules::Rules> {
+        if self.output() != Output::Tui {
+            return Ok(rules::Rules::default());
+        }
+        let rules = rules::Rules::load(self.config.as_deref())?;
+        // checked here, before the trace starts
+        let layouts = layout::all(&rules.layouts);
+        if !layouts.iter().any(|l| l.name == self.layout) {
+            let names: Vec<&str> = layouts.iter().map(|l| l.name.as_str()).collect();
+            return Err(anyhow!(
+                "unknown layout {:?} (expected one of {})",
+                self.layout,
+                names.join(", ")
+            ));
+        }
+        if let Some(preset) = &self.preset {
+            if rules.named_filter(preset).is_none() {
+                let names: Vec<&str> = rules.filters.iter().map(|(n, _)| n.as_str()).collect();
+                let names = if names.is_empty() {
+                    "none".to_string()
+                } else {
+                    names.join(", ")
+                };
+                return Err(anyhow!(
+                    "unknown preset {:?} (the config file's named filters: {})",
+                    preset,
+                    names
+                ));
+            }
+        }
+        Ok(rules)
+    }
+
+    fn output(&self) -> Output {
+        if self.summary {
+            Output::Summary
+        } else if self.no_tui {
+            Output::Text
+        } else {
+            self.output
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+enum Output {
+    /// interactive terminal interface
+    Tui,
+    /// one line per event, in the same format as strace (same as --no-tui)
+    Text,
+    /// one line per event, as strace prints it (without colors, annotations, or vistrace's own
+    /// lines, e.g. skipped calls), for other tools that read strace's output
+    Strace,
+    /// one JSON object per event (see src/jsonl.rs, or `vistrace schema` for a JSON Schema)
+    Jsonl,
+    /// a report of the whole trace (same as --summary)
+    Summary,
+    /// one JSON object per network connection, at the end of the trace
+    Connections,
+    /// the tree of commands that were run, with their arguments
+    Commands,
+    /// one JSON object per command that was run, with its arguments and environment
+    CommandsJson,
+    /// the files and directories that the program changed, in order
+    Changes,
+    /// the processes and commands that created and wrote each file
+    Provenance,
+    /// Chrome's Trace Event format
+    ChromeTrace,
+    /// one CSV row per event
+    Csv,
+    /// CSV of per-syscall statistics
+    SummaryCsv,
+    /// a Graphviz graph of the process tree
+    Dot,
+    /// a Mermaid sequence diagram of traffic between processes
+    Mermaid,
+    /// folded syscall stacks
+    Folded,
+    /// an SVG flamegraph of syscall stacks
+    Flamegraph,
+    /// socket payloads as a pcap file
+    Pcap,
+    /// SQL to create a SQLite database of the trace
+    Sqlite,
+    /// a seccomp profile that allows only the syscalls in the trace (see src/seccomp.rs)
+    Seccomp,
+    /// a skeleton AppArmor profile for the file and network access in the trace
+    Apparmor,
+    /// the Landlock rules for the file and network access in the trace (see src/policy.rs)
+    Landlock,
+    // the report of `vistrace check`
+    #[value(skip)]
+    Check,
+}
+
+impl From<ExportFormat> for Output {
+    fn from(format: ExportFormat) -> Output {
+        match format {
+            ExportFormat::Text => Output::Text,
+            ExportFormat::Strace => Output::Strace,
+            ExportFormat::Jsonl => Output::Jsonl,
+            ExportFormat::Connections => Output::Connections,
+            ExportFormat::Commands => Output::Commands,
+            ExportFormat::CommandsJson => Output::CommandsJson,
+            ExportFormat::Changes => Output::Changes,
+            ExportFormat::Provenance => Output::Provenance,
+            ExportFormat::ChromeTrace => Output::ChromeTrace,
+            ExportFormat::Csv => Output::Csv,
+            ExportFormat::SummaryCsv => Output::SummaryCsv,
+            ExportFormat::Dot => Output::Dot,
+            ExportFormat::Mermaid => Output::Mermaid,
+            ExportFormat::Folded => Output::Folded,
+            ExportFormat::Flamegraph => Output::Flamegraph,
+            ExportFormat::Pcap => Output::Pcap,
+            ExportFormat::Sqlite => Output::Sqlite,
+        }
+    }
+}
+
 fn main() {
     let result = main_can_err();
     if let Err(e) = result {
@@ -24,29 +809,1126 @@ fn main() {
 }
 
 fn main_can_err() -> Result<()> {
-    ensure_linux();
-    let args = Args::parse();
+    let cli = Cli::parse();
+    if let Some(path) = &cli.log_file {
+        logging::init(path, cli.log_level)?;
+    }
+
+    match cli.command {
+        Some(Command::Run(args)) => run(args),
+        Some(Command::Attach(args)) => attach(args),
+        Some(Command::View(args)) => view(args),
+        Some(Command::Stats(args)) if args.compare.is_some() => compare_stats(args),
+        Some(Command::Stats(args)) => {
+            let mut output = OutputArgs::plain(Output::Summary, args.color, args.output_file);
+            output.audit = args.audit;
+            replay(&args.path, args.exclude, None, None, output)
+        }
+        Some(Command::Diff(args)) => diff(args),
+        Some(Command::Env(args)) => compare_env(args),
+        Some(Command::Check(args)) => check(args),
+        Some(Command::Ssh(args)) => ssh(args),
+        Some(Command::Connect(args)) => connect(args),
+        Some(Command::Schema) => {
+            print!("{}", jsonl::SCHEMA);
+            Ok(())
+        }
+        Some(Command::Export(args)) if args.out.is_some() => export_trimmed(args),
+        Some(Command::Export(args)) => {
+            let format = Output::from(args.format);
+            let output = match (args.seccomp, args.apparmor, args.landlock) {
+                (Some(path), _, _) => OutputArgs::plain(Output::Seccomp, args.color, Some(path)),
+                (_, Some(path), _) => OutputArgs::plain(Output::Apparmor, args.color, Some(path)),
+                (_, _, Some(path)) => OutputArgs::plain(Output::Landlock, args.color, Some(path)),
+                _ => OutputArgs::plain(format, args.color, args.output_file),
+            };
+            let output = OutputArgs {
+                connection: args.connection,
+                redactor: args.redact.then(redact::Redactor::default),
+                anonymize: args.anonymize,
+                filter: args.filter,
+                ..output
+            };
+            replay(&args.path, args.exclude, None, None, output)
+        }
+        None => run(cli.run),
+    }
+}
 
+fn run(args: RunArgs) -> Result<()> {
+    ensure_supported(args.trace.backend());
+    let targets = targets::split(args.name, args.args)?;
+    trace(targets, Vec::new(), None, None, args.trace, args.output)
+}
+
+fn check(args: CheckArgs) -> Result<()> {
+    ensure_supported(args.trace.backend());
+    let mut output =
+        OutputArgs::plain(Output::Check, palette::ColorChoice::Never, args.output_file);
+    output.allowlist = Some(allowlist::Allowlist::load(&args.allow)?);
+    let targets = targets::split(None, args.args)?;
+    trace(targets, Vec::new(), None, None, args.trace, output)
+}
+
+fn attach(args: AttachArgs) -> Result<()> {
+    ensure_supported(args.trace.backend());
+
+    let mut pids = args.pids;
+    if let Some(pattern) = &args.pid_glob {
+        let found = procfs::find_pids(pattern)?;
+        if found.is_empty() {
+            return Err(anyhow!("no running process matches {:?}", pattern));
+        }
+        pids.extend(found);
+    }
+    let scope = match (args.cgroup, args.system) {
+        (Some(cgroup), _) => Some(bpf::Scope::Cgroup(cgroup)),
+        (None, true) => Some(bpf::Scope::System),
+        (None, false) => None,
+    };
+    if scope.is_some() && args.trace.backend() != Backend::Bpf {
+        return Err(anyhow!("--cgroup and --system need --backend bpf"));
+    }
+    trace(Vec::new(), pids, None, scope, args.trace, args.output)
+}
+
+fn ssh(args: SshArgs) -> Result<()> {
+    trace(
+        targets::split(None, args.args)?,
+        Vec::new(),
+        Some(args.host),
+        None,
+        args.trace,
+        args.output,
+    )
+}
+
+/// Runs strace on the commands of `targets` or attaches it to `pids`, here or on `host`, and shows
+/// the trace, over again each time that the faults to inject are changed in the interface.
+fn trace(
+    targets: Vec<targets::Target>,
+    pids: Vec<u32>,
+    host: Option<String>,
+    scope: Option<bpf::Scope>,
+    args: TraceArgs,
+    output: OutputArgs,
+) -> Result<()> {
+    let mut injections = args.inject.clone();
+    let (host, scope) = (host.as_deref(), scope.as_ref());
+    while let Some(changed) = trace_once(&targets, &pids, host, scope, &args, &output, injections)?
+    {
+        injections = changed;
+    }
+    Ok(())
+}
+
+/// Traces and shows the trace once. Returns the new faults to inject if they were changed in the
+/// interface, in which case the command was killed (or the processes were detached from) so that
+/// the trace can be restarted. A trace saved with --save is of the last run.
+fn trace_once(
+    targets: &[targets::Target],
+    pids: &[u32],
+    host: Option<&str>,
+    scope: Option<&bpf::Scope>,
+    args: &TraceArgs,
+    output: &OutputArgs,
+    injections: Vec<inject::Injection>,
+) -> Result<Option<Vec<inject::Injection>>> {
+    // open the output file and read the config file before starting the trace, so that a bad path
+    // fails fast
+    let mut out = open_output(output.output_file.as_deref())?;
+    let rules = output.rules()?;
+    let exclude = strace::parse_exclude(&args.exclude.exclude)?;
     let (tx, rx) = mpsc::channel::<strace::Message>();
 
-    let strace_thread = thread::spawn(move || strace::strace(&args.args, tx));
+    // the processes are on another machine, so their pids and paths mean nothing here
+    let on_host = host.is_some();
+    if on_host && args.on_limit == Some(limits::LimitAction::Kill) {
+        return Err(anyhow!(
+            "--on-limit kill is not supported with `vistrace ssh`"
+        ));
+    }
+    let strace_only = [
+        (on_host, "`vistrace ssh`"),
+        (!args.strace_args.is_empty(), "--strace-arg"),
+        (args.stacks, "--stacks"),
+        (!injections.is_empty(), "--inject"),
+    ];
+    let unsupported: Vec<(bool, &str)> = match args.backend() {
+        Backend::Strace => Vec::new(),
+        Backend::Ptrace => strace_only
+            .into_iter()
+            .chain([
+                (!pids.is_empty(), "`vistrace attach`"),
+                (
+                    args.on_limit == Some(limits::LimitAction::Detach),
+                    "--on-limit detach",
+                ),
+            ])
+            .collect(),
+        Backend::Bpf | Backend::Dtruss | Backend::Truss | Backend::Ltrace => strace_only.to_vec(),
+    };
+    if let Some((_, what)) = unsupported.iter().find(|(used, _)| *used) {
+        return Err(anyhow!(
+            "{} is not supported with --backend {}",
+            what,
+            args.backend().to_possible_value().unwrap().get_name()
+        ));
+    }
+    // the trace is restarted, or the tracer interrupted, as a whole
+    let several = targets.len() > 1;
+    if several && !injections.is_empty() {
+        return Err(anyhow!("--inject is not supported with several commands"));
+    }
+    // without following, strace does not say which process made each syscall
+    if several && args.no_follow {
+        return Err(anyhow!(
+            "--no-follow is not supported with several commands"
+        ));
+    }
+    if several && args.on_limit == Some(limits::LimitAction::Detach) {
+        return Err(anyhow!(
+            "--on-limit detach is not supported with several commands"
+        ));
+    }
+    // drop excluded syscalls at capture time if possible, since that is cheaper
+    let (capture_exclude, parse_exclude) = if args.backend() != Backend::Strace
+        || strace::supports_trace_categories(strace::check_version(&args.strace_path, host)?)
+    {
+        (exclude, Vec::new())
+    } else {
+        (Vec::new(), exclude)
+    };
+    let extra_args = strace::parse_strace_args(&args.strace_args, &capture_exclude)?;
+
+    let save = match &args.save {
+        Some(path) => Some(vst::Writer::create(path, args.encoding)?),
+        None => None,
+    };
+    // the client must be connected before tracing starts, so that it sees everything
+    let remote = match &args.listen {
+        Some(address) => {
+            let listener = remote::Listener::bind(address)?;
+            eprintln!("vistrace: waiting for `vistrace connect {}`", address);
+            Some(listener.accept()?)
+        }
+        None => None,
+    };
+    let control = match &args.control_socket {
+        Some(path) => Some(control::Server::bind(
+            path,
+            !on_host,
+            Box::new(export_lines),
+        )?),
+        None => None,
+    };
+    let journal = match args.journal {
+        Some(target) => {
+            let trace = trace_label(targets, pids, host, scope);
+            // alerts are sent even if the interface, which reads the rules itself, is not shown
+            let rules = rules::Rules::load(output.config.as_deref())?;
+            Some(journal::Journal::connect(target, trace, rules)?)
+        }
+        None => None,
+    };
+    let headless = remote.is_some();
+    // breakpoints that strace can stop at itself, which only the interface can resume from
+    let stop_injections = if !on_host && !headless && args.backend() == Backend::Strace {
+        rules.stop_injections()
+    } else {
+        Vec::new()
+    };
+
+    // one tracer for each command, or one for the processes to attach to
+    let commands: Vec<&[String]> = match targets {
+        [] => vec![&[]],
+        _ => targets.iter().map(|t| t.command.as_slice()).collect(),
+    };
+    let options = strace::StraceOptions {
+        strace_path: args.strace_path.clone(),
+        command: Vec::new(),
+        pids: pids.to_vec(),
+        follow: !args.no_follow,
+        exclude: capture_exclude,
+        extra_args,
+        stacks: args.stacks,
+        injections: injections.iter().chain(&stop_injections).cloned().collect(),
+        host: host.map(String::from),
+    };
+    let spawn_tracer = |command: &[String]| {
+        let command = command.to_vec();
+        Ok::<_, anyhow::Error>(match args.backend() {
+            Backend::Strace => Some(strace::spawn(&strace::StraceOptions {
+                command,
+                ..options.clone()
+            })?),
+            Backend::Ptrace => None,
+            Backend::Bpf => Some(bpf::spawn(&bpf::BpfOptions {
+                bpftrace_path: args.bpftrace_path.clone(),
+                command,
+                pids: options.pids.clone(),
+                scope: scope.cloned(),
+                follow: options.follow,
+            })?),
+            Backend::Dtruss => Some(dtruss::spawn(&dtruss::DtrussOptions {
+                dtruss_path: args.dtruss_path.clone(),
+                command,
+                pids: options.pids.clone(),
+                follow: options.follow,
+            })?),
+            Backend::Truss => Some(truss::spawn(&truss::TrussOptions {
+                truss_path: args.truss_path.clone(),
+                command,
+                pids: options.pids.clone(),
+                follow: options.follow,
+            })?),
+            Backend::Ltrace => Some(ltrace::spawn(&ltrace::LtraceOptions {
+                ltrace_path: args.ltrace_path.clone(),
+                command,
+                pids: options.pids.clone(),
+                follow: options.follow,
+            })?),
+        })
+    };
+    let children = commands
+        .iter()
+        .map(|command| spawn_tracer(command))
+        .collect::<Result<Vec<_>>>()?;
+    let attached = !options.pids.is_empty() || scope.is_some();
+    let mut limiter = None;
+    if args.max_events.is_some() || args.duration.is_some() {
+        // closing ssh stops strace on the other machine
+        let default_action = if attached || on_host {
+            limits::LimitAction::Detach
+        } else {
+            limits::LimitAction::Kill
+        };
+        let limits = limits::Limits {
+            max_events: args.max_events,
+            duration: args.duration,
+            action: args.on_limit.unwrap_or(default_action),
+        };
+        // without strace, the command is vistrace's own child
+        let tracer_pid = children[0]
+            .as_ref()
+            .map_or(process::id(), |child| child.id());
+        limiter = Some(limits::Limiter::new(
+            limits,
+            tracer_pid,
+            options.pids.clone(),
+        ));
+    }
+    let redactor = args.redact.then(redact::Redactor::default);
+    let sampler = (!args.sample.is_empty()).then(|| sample::Sampler::new(args.sample.clone()));
+    let outputs = strace::Outputs {
+        save,
+        remote,
+        control: control.clone(),
+        journal,
+    };
+    let sink = strace::Sink::new(tx, outputs, limiter.clone(), redactor, sampler);
+    let roots = targets::Roots::new(commands.len());
+    // `sampled` is the process to sample the resources of while it lasts, with the processes to
+    // sample (strace's children if none)
+    let mut strace_pid = None;
+    let mut sampled = None;
+    let mut tracer_threads: Vec<JoinHandle<Result<ExitStatus>>> = Vec::new();
+    for (i, (command, child)) in commands.iter().zip(children).enumerate() {
+        let sink = sink.clone();
+        match child {
+            Some(child) => {
+                strace_pid.get_or_insert(child.id());
+                sampled.get_or_insert((child.id(), options.pids.clone()));
+                let exclude = parse_exclude.clone();
+                let parser: Box<dyn strace::Parse + Send> = match args.backend() {
+                    Backend::Bpf => Box::new(bpf::Parser::with_exclude(options.exclude.clone())),
+                    Backend::Dtruss => {
+                        Box::new(dtruss::Parser::with_exclude(options.exclude.clone()))
+                    }
+                    Backend::Truss => {
+                        Box::new(truss::Parser::with_exclude(options.exclude.clone()))
+                    }
+                    Backend::Ltrace => {
+                        Box::new(ltrace::Parser::with_exclude(options.exclude.clone()))
+                    }
+                    _ => Box::new(strace::LineParser::with_exclude(exclude)),
+                };
+                let parser = targets::Tagged {
+                    parser,
+                    roots: roots.clone(),
+                    target: i,
+                };
+                tracer_threads.push(thread::spawn(move || strace::stream(child, parser, sink)));
+            }
+            None => {
+                let ptrace_options = ptrace::Options {
+                    command: command.to_vec(),
+                    follow: options.follow,
+                    exclude: options.exclude.clone(),
+                };
+                let (pid, thread) = ptrace::spawn(ptrace_options, sink)?;
+                roots.set(i, pid);
+                sampled.get_or_insert((pid, vec![pid]));
+                tracer_threads.push(thread);
+            }
+        }
+    }
+    // the trace is over once every tracer's clone of the sink is finished
+    drop(sink);
 
-    ui::main(rx);
+    // so that relative paths can be made absolute
+    let cwds = if on_host {
+        Vec::new()
+    } else if attached {
+        options
+            .pids
+            .iter()
+            .filter_map(|pid| procfs::cwd(*pid).map(|dir| (Some(*pid), dir)))
+            .collect()
+    } else {
+        env::current_dir()
+            .map(|dir| vec![(None, dir.to_string_lossy().into_owned())])
+            .unwrap_or_default()
+    };
+    // so that paths inside containers can be found
+    let containers = options
+        .pids
+        .iter()
+        .filter_map(|pid| namespaces::Container::inspect(*pid))
+        .collect();
+    // only the interface shows the samples, which are of a single command
+    let sample = !on_host && !headless && !several && output.output() == Output::Tui;
+    let samples = match sampled {
+        Some((pid, pids)) if sample && !args.sample_interval.is_zero() => {
+            Some(usage::spawn(pid, pids, args.sample_interval))
+        }
+        _ => None,
+    };
+    let ui_options = ui::Options {
+        strace_pid,
+        attached_pids: options.pids,
+        replay_speed: None,
+        pause_on_error: output.pause_on_error,
+        audit: output.audit,
+        resolve_links: output.resolve_links && !on_host,
+        cwds,
+        containers,
+        samples,
+        rules,
+        stoppable: !on_host,
+        stop_injections,
+        // the processes on another machine cannot be killed to restart the trace, and only strace
+        // can inject faults
+        injections: (!on_host && !several && args.backend() == Backend::Strace)
+            .then_some(injections),
+        targets: targets::Targets::new(targets.iter().map(|t| t.name.clone()).collect(), roots),
+        session: None,
+        layout: output.layout.clone(),
+        preset: output.preset.clone(),
+        explain: output.explain,
+        save_session: args
+            .save
+            .as_deref()
+            .filter(|_| args.session)
+            .map(session::path_for),
+    };
+
+    let restart = if headless {
+        // the client shows the trace
+        rx.iter().for_each(drop);
+        None
+    } else {
+        show(rx, ui_options, output, &mut out)?
+    };
 
     // unwrap() because join() returns error only if thread panicked
     // the '?' propagates any actual errors the thread returned
-    strace_thread.join().unwrap()?;
+    let mut failed = None;
+    for thread in tracer_threads {
+        let status = thread.join().unwrap()?;
+        if !status.success() {
+            failed.get_or_insert(status);
+        }
+    }
+    if restart.is_some() {
+        return Ok(restart);
+    }
+    // when attached (or stopped by a limit), strace is interrupted to make it detach, so its exit
+    // code is meaningless
+    let stopped = limiter.is_some_and(|l| l.stopped());
+    match failed {
+        Some(status) if !attached && !stopped => {
+            Err(anyhow!("{} failed ({})", args.backend().failed(), status))
+        }
+        _ => Ok(None),
+    }
+}
+
+// What is being traced, for the system log, e.g. "curl example.com" or "pid 1234".
+fn trace_label(
+    targets: &[targets::Target],
+    pids: &[u32],
+    host: Option<&str>,
+    scope: Option<&bpf::Scope>,
+) -> String {
+    let label = match scope {
+        Some(bpf::Scope::Cgroup(dir)) => format!("cgroup {}", dir.display()),
+        Some(bpf::Scope::System) => "every process".to_string(),
+        None if !pids.is_empty() => {
+            let pids: Vec<String> = pids.iter().map(u32::to_string).collect();
+            format!("pid {}", pids.join(","))
+        }
+        None => targets
+            .iter()
+            .map(|t| t.command.join(" "))
+            .collect::<Vec<_>>()
+            .join("; "),
+    };
+    match host {
+        Some(host) => format!("{} on {}", label, host),
+        None => label,
+    }
+}
 
+fn view(mut args: ViewArgs) -> Result<()> {
+    let speed = args.replay.then(|| Arc::new(Mutex::new(1.0)));
+    let session = session::path_for(&args.path);
+    if args.session || session.exists() {
+        args.output.session = Some(session);
+    }
+    if !args.output.session.as_deref().is_some_and(Path::exists) {
+        args.output.snapshot = session::Session::embedded(&args.path)?;
+    }
+    replay(&args.path, args.exclude, speed, args.from, args.output)
+}
+
+/// Shows a saved trace, at its original pace if `speed` is given, starting `from` into it.
+fn replay(
+    path: &Path,
+    exclude: ExcludeArgs,
+    speed: Option<vst::Speed>,
+    from: Option<Duration>,
+    output: OutputArgs,
+) -> Result<()> {
+    let path = path.to_path_buf();
+    let replay_speed = speed.clone();
+    show_recorded(exclude, speed, output, move |exclude, tx| {
+        vst::replay(&path, trace_parser(&path, exclude), tx, replay_speed, from)
+    })
+}
+
+// The parser for the saved trace at `path`, since a text trace may be from another tracer.
+fn trace_parser(path: &Path, exclude: Vec<String>) -> Box<dyn strace::Parse + Send> {
+    match vst::first_line(path) {
+        Some(line) if dtruss::is_dtruss(&line) => Box::new(dtruss::Parser::with_exclude(exclude)),
+        Some(line) if truss::is_truss(&line) => Box::new(truss::Parser::with_exclude(exclude)),
+        _ => Box::new(strace::LineParser::with_exclude(exclude)),
+    }
+}
+
+fn export_trimmed(args: ExportArgs) -> Result<()> {
+    let out = args.out.expect("no trace to write");
+    let exclude = strace::parse_exclude(&args.exclude.exclude)?;
+    let redactor = args.redact.then(redact::Redactor::default);
+    let mut anonymizer = args
+        .anonymize
+        .map(|key| anonymize::Anonymizer::new(key.as_deref()));
+    let parser = trace_parser(&args.path, exclude);
+    let written = trim::trim(&args.path, parser, &out, args.from, args.to, |msg| {
+        // as for other exports, names are learned before strings are masked
+        if let Some(anonymizer) = &mut anonymizer {
+            anonymizer.anonymize(msg);
+        }
+        if let Some(redactor) = &redactor {
+            redactor.redact(msg);
+        }
+    })?;
+    eprintln!("Wrote {} events to {}", written, out.display());
+    Ok(())
+}
+
+// Writes lines of strace output to `path` in one of `vistrace export`'s formats, for the `export`
+// command of the control socket.
+fn export_lines(format: &str, path: &Path, lines: Vec<String>) -> Result<()> {
+    let format = ExportFormat::from_str(format, true)
+        .map_err(|_| anyhow!("unknown export format {:?}", format))?;
+    let output = OutputArgs::plain(
+        Output::from(format),
+        palette::ColorChoice::Never,
+        Some(path.to_path_buf()),
+    );
+    show_recorded(ExcludeArgs::default(), None, output, move |exclude, tx| {
+        let mut parser = strace::LineParser::with_exclude(exclude);
+        for line in lines {
+            if let Some(msg) = parser.parse_line(&line) {
+                tx.send(msg).map_err(|e| anyhow!("transmit error: {}", e))?;
+            }
+        }
+        Ok(())
+    })
+}
+
+fn connect(args: ConnectArgs) -> Result<()> {
+    // connect before starting the interface, so that a bad address fails fast
+    let receiver = remote::Receiver::connect(&args.address)?;
+    show_recorded(args.exclude, None, args.output, move |exclude, tx| {
+        receiver.receive(strace::LineParser::with_exclude(exclude), tx)
+    })
+}
+
+// Shows a trace that was recorded elsewhere (or earlier), which `source` sends to the channel,
+// without the syscalls that it is given to exclude.
+fn show_recorded(
+    exclude: ExcludeArgs,
+    speed: Option<vst::Speed>,
+    mut output: OutputArgs,
+    source: impl FnOnce(Vec<String>, mpsc::Sender<strace::Message>) -> Result<()> + Send + 'static,
+) -> Result<()> {
+    // a database is filled in by the sqlite3 program, from the SQL that vistrace writes
+    let mut sqlite = match (output.output(), &output.output_file) {
+        (Output::Sqlite, Some(db)) => Some(spawn_sqlite(db)?),
+        _ => None,
+    };
+    let mut out: Box<dyn Write> = match sqlite.as_mut().and_then(|child| child.stdin.take()) {
+        Some(stdin) => Box::new(io::BufWriter::new(stdin)),
+        None => open_output(output.output_file.as_deref())?,
+    };
+    let exclude = strace::parse_exclude(&exclude.exclude)?;
+    let rules = output.rules()?;
+    let (tx, rx) = mpsc::channel::<strace::Message>();
+
+    let ui_options = ui::Options {
+        strace_pid: None,
+        attached_pids: Vec::new(),
+        replay_speed: speed.clone(),
+        pause_on_error: output.pause_on_error,
+        audit: output.audit,
+        // the paths may not exist here, or lead somewhere else
+        resolve_links: false,
+        // the trace does not say where it was recorded
+        cwds: Vec::new(),
+        containers: Vec::new(),
+        samples: None,
+        rules,
+        stoppable: false,
+        stop_injections: Vec::new(),
+        // there is no strace to restart
+        injections: None,
+        targets: Default::default(),
+        session: output
+            .session
+            .as_deref()
+            .map(session::Session::load)
+            .transpose()?
+            .flatten()
+            .or(output.snapshot.take()),
+        save_session: output.session.clone(),
+        layout: output.layout.clone(),
+        preset: output.preset.clone(),
+        explain: output.explain,
+    };
+    let source_thread = thread::spawn(move || source(exclude, tx));
+
+    show(rx, ui_options, &output, &mut out)?;
+    source_thread.join().unwrap()?;
+
+    // closing its input tells sqlite3 that the SQL is done
+    drop(out);
+    if let Some(mut child) = sqlite {
+        let status = child.wait()?;
+        if !status.success() {
+            return Err(anyhow!("sqlite3 returned a non-zero exit code"));
+        }
+    }
+    Ok(())
+}
+
+fn spawn_sqlite(db: &Path) -> Result<process::Child> {
+    process::Command::new("sqlite3")
+        // stop at the first error rather than carrying on with a half-made database
+        .arg("-bail")
+        .arg(db)
+        .stdin(process::Stdio::piped())
+        .spawn()
+        .map_err(|e| anyhow!("unable to run sqlite3 (is it installed?): {}", e))
+}
+
+fn open_output(path: Option<&Path>) -> Result<Box<dyn Write>> {
+    Ok(match path {
+        Some(path) => {
+            Box::new(LineWriter::new(File::create(path).map_err(|e| {
+                anyhow!("unable to create {}: {}", path.display(), e)
+            })?))
+        }
+        None => Box::new(io::stdout()),
+    })
+}
+
+/// Shows the messages from `rx` in the interface or writes them to `out`, until the trace ends.
+/// Returns the new faults to inject if they were changed in the interface.
+fn show(
+    rx: mpsc::Receiver<strace::Message>,
+    ui_options: ui::Options,
+    output: &OutputArgs,
+    out: &mut impl Write,
+) -> Result<Option<Vec<inject::Injection>>> {
+    let is_terminal = output.output_file.is_none() && io::stdout().is_terminal();
+    let no_color = env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+    let color = palette::use_color(output.color, is_terminal, no_color);
+    units::set_exact(output.exact);
+
+    match output.output() {
+        Output::Tui => Ok(ui::main(rx, ui_options)),
+        Output::Check => {
+            let allowlist = output.allowlist.as_ref().expect("no allowlist to check");
+            let mut check = allowlist::Check::new(allowlist);
+            for (pid, dir) in &ui_options.cwds {
+                check.set_cwd(*pid, dir.clone());
+            }
+            for msg in rx.iter() {
+                check.update(&msg);
+            }
+            check
+                .write(out)
+                .and_then(|()| out.flush())
+                .map_err(|e| anyhow!("unable to write output: {}", e))?;
+            match check.violations() {
+                0 => Ok(None),
+                n => Err(anyhow!(
+                    "the command did {} things that the allowlist does not allow",
+                    n
+                )),
+            }
+        }
+        format => {
+            write_output(format, rx, out, color, &ui_options, output)
+                .map_err(|e| anyhow!("unable to write output: {}", e))?;
+            Ok(None)
+        }
+    }
+}
+
+/// Writes the trace to `out` in one of the non-interactive formats.
+fn write_output(
+    output: Output,
+    rx: mpsc::Receiver<strace::Message>,
+    out: &mut impl Write,
+    color: bool,
+    options: &ui::Options,
+    export: &OutputArgs,
+) -> io::Result<()> {
+    let audit = options.audit;
+    let mut summary = if audit {
+        summary::Summary::with_audit()
+    } else {
+        summary::Summary::default()
+    };
+    summary.resolve_links(options.resolve_links);
+    for container in &options.containers {
+        summary.add_container(container.clone());
+    }
+    let mut fds = fdtable::FdTable::default();
+    for (pid, dir) in &options.cwds {
+        summary.set_cwd(*pid, dir.clone());
+        fds.set_cwd(*pid, dir.clone());
+    }
+    let mut connections = net::Connections::default();
+    let mut event_fds = eventfds::EventFds::default();
+    let mut changes = changes::Changes::default();
+    let mut provenance = provenance::Provenance::default();
+    let mut processes = processes::ProcessTable::default();
+    let mut channels = peers::Channels::default();
+    let mut commands = commands::Commands::default();
+    let mut chrome_trace = chrome::ChromeTrace::default();
+    let mut sql = sqlite::SqlExport::default();
+    let mut sequence = mermaid::Sequence::default();
+    let mut stacks = flamegraph::Stacks::default();
+    let mut capture = pcap::Capture::new(export.connection.clone());
+    let mut anonymizer = export
+        .anonymize
+        .as_ref()
+        .map(|key| anonymize::Anonymizer::new(key.as_deref()));
+    let mut profile = seccomp::Profile::default();
+    let mut policy = policy::Policy::default();
+    if output == Output::Csv {
+        csv::write_header(out)?;
+    }
+    let mut filter = match &export.filter {
+        Some(text) => Some(filter::Filter::parse(text).map_err(io::Error::other)?),
+        None => None,
+    };
+    // the number of syscalls so far, since filters work by the index of each, and whether the last
+    // one was filtered out, in which case so is its stack
+    let (mut syscalls, mut dropped) = (0, false);
+    for mut msg in rx.iter() {
+        if let Some(filter) = &mut filter {
+            match &msg {
+                strace::Message::Syscall(syscall) => {
+                    dropped = !filter.matches(syscalls, syscall);
+                    syscalls += 1;
+                }
+                strace::Message::Frame(_) => {}
+                _ => dropped = false,
+            }
+        }
+        // names are learned from every event, even those that are filtered out
+        if let Some(anonymizer) = &mut anonymizer {
+            anonymizer.anonymize(&mut msg);
+        }
+        if dropped {
+            continue;
+        }
+        if let Some(redactor) = &export.redactor {
+            redactor.redact(&mut msg);
+        }
+        if let strace::Message::Notice(notice) = &msg {
+            eprintln!("strace: {}", notice);
+            continue;
+        }
+
+        match output {
+            Output::Text => {
+                let text = msg.to_string();
+                write!(
+                    out,
+                    "{}",
+                    palette::paint(&text, palette::message_color(&msg), color)
+                )?;
+                if let strace::Message::Syscall(syscall) = &msg {
+                    fds.update(syscall);
+                    if let Some(description) = event_fds.update(syscall, &fds) {
+                        write!(out, "  [{}]", description)?;
+                    }
+                }
+                let findings = match &msg {
+                    strace::Message::Syscall(syscall) if audit => audit::check(syscall),
+                    _ => Vec::new(),
+                };
+                for finding in findings {
+                    let text = format!("  [audit: {}]", finding);
+                    write!(
+                        out,
+                        "{}",
+                        palette::paint(&text, Some(palette::Color::Red), color)
+                    )?;
+                }
+                writeln!(out)?;
+            }
+            // which round-trips through strace's parser
+            // strace prints neither of these, and other tools would fail to parse them
+            Output::Strace => match &msg {
+                strace::Message::Skipped(_) | strace::Message::LibCall(_) => {}
+                _ => writeln!(out, "{}", msg)?,
+            },
+            Output::Jsonl => jsonl::write_message(out, &msg)?,
+            Output::Csv => csv::write_message(out, &msg)?,
+            Output::ChromeTrace => chrome_trace.write_message(out, &msg)?,
+            Output::Sqlite => sql.write_message(out, &msg)?,
+            Output::Pcap => capture.write_message(out, &msg)?,
+            Output::Mermaid => sequence.update(&msg),
+            Output::Folded | Output::Flamegraph => stacks.update(&msg),
+            Output::Summary | Output::SummaryCsv => summary.update(msg),
+            Output::Connections => match &msg {
+                strace::Message::Syscall(syscall) => {
+                    fds.update(syscall);
+                    connections.update(syscall, &fds);
+                }
+                strace::Message::Exit(exit) => connections.record_exit(exit, &fds),
+                _ => {}
+            },
+            Output::Dot => match msg {
+                strace::Message::Syscall(syscall) => {
+                    processes.update(&syscall);
+                    fds.update(&syscall);
+                    connections.update(&syscall, &fds);
+                    channels.update(&syscall, &fds, &connections);
+                }
+                strace::Message::Exit(exit) => processes.record_exit(exit),
+                _ => {}
+            },
+            Output::Commands | Output::CommandsJson => match &msg {
+                strace::Message::Syscall(syscall) => {
+                    processes.update(syscall);
+                    commands.update(syscall, &processes);
+                }
+                strace::Message::Exit(exit) => commands.record_exit(exit),
+                _ => {}
+            },
+            Output::Changes => {
+                if let strace::Message::Syscall(syscall) = &msg {
+                    fds.update(syscall);
+                    changes.update(syscall, &fds);
+                }
+            }
+            Output::Provenance => match &msg {
+                strace::Message::Syscall(syscall) => {
+                    processes.update(syscall);
+                    commands.update(syscall, &processes);
+                    fds.update(syscall);
+                    provenance.update(syscall, &fds, &processes, &commands);
+                }
+                strace::Message::Exit(exit) => commands.record_exit(exit),
+                _ => {}
+            },
+            Output::Seccomp => {
+                if let strace::Message::Syscall(syscall) = &msg {
+                    profile.update(syscall);
+                }
+            }
+            Output::Apparmor | Output::Landlock => policy.update(&msg),
+            Output::Tui | Output::Check => unreachable!(),
+        }
+    }
+
+    match output {
+        Output::Summary => summary.write(out, color)?,
+        Output::SummaryCsv => summary.write_syscalls_csv(out)?,
+        Output::Connections => {
+            for connection in &connections.connections {
+                writeln!(out, "{}", jsonl::connection_to_json(connection))?;
+            }
+        }
+        Output::Commands => {
+            for (depth, index) in commands.tree() {
+                let command = &commands.commands[index];
+                writeln!(out, "{}{}", "  ".repeat(depth), command)?;
+            }
+        }
+        Output::Changes => changes.write(out)?,
+        Output::Provenance => provenance.write(out, &commands)?,
+        Output::CommandsJson => {
+            for (index, command) in commands.commands.iter().enumerate() {
+                writeln!(out, "{}", jsonl::command_to_json(index, command))?;
+            }
+        }
+        Output::ChromeTrace => chrome_trace.finish(out)?,
+        Output::Sqlite => sql.finish(out)?,
+        Output::Pcap => capture.finish(out)?,
+        Output::Dot => dot::write_graph(out, &processes, &channels)?,
+        Output::Mermaid => sequence.write(out)?,
+        Output::Folded | Output::Flamegraph => {
+            stacks.finish();
+            if stacks.is_empty() {
+                eprintln!("warning: the trace has no stacks (record it with --stacks)");
+            }
+            if output == Output::Folded {
+                stacks.write_folded(out)?;
+            } else {
+                stacks.write_svg(out)?;
+            }
+        }
+        Output::Seccomp => writeln!(out, "{}", profile.to_json())?,
+        Output::Apparmor => policy.write_apparmor(out)?,
+        Output::Landlock => policy.write_landlock(out)?,
+        _ => {}
+    }
+    out.flush()
+}
+
+fn diff(args: DiffArgs) -> Result<()> {
+    let left_syscalls = read_syscalls(&args.left)?;
+    let right_syscalls = read_syscalls(&args.right)?;
+    let entries = diff::diff(&left_syscalls, &right_syscalls);
+    let (left_title, right_title) = (
+        args.left.display().to_string(),
+        args.right.display().to_string(),
+    );
+    if args.report {
+        diff::write_report(
+            &mut io::stdout().lock(),
+            &left_title,
+            &right_title,
+            &left_syscalls,
+            &right_syscalls,
+            &entries,
+        )?;
+    } else {
+        ui::diff(
+            &left_title,
+            &right_title,
+            &left_syscalls,
+            &right_syscalls,
+            &entries,
+        );
+    }
     Ok(())
 }
 
-fn ensure_linux() {
+fn compare_stats(args: StatsArgs) -> Result<()> {
+    let before_path = args.compare.unwrap_or_default();
+    let exclude = strace::parse_exclude(&args.exclude.exclude)?;
+    let read = |path: &Path| {
+        let (tx, rx) = mpsc::channel::<strace::Message>();
+        let parser = strace::LineParser::with_exclude(exclude.clone());
+        vst::replay(path, parser, tx, None, None)?;
+        let mut latencies = compare::Latencies::default();
+        for msg in rx {
+            latencies.update(&msg);
+        }
+        Ok::<_, anyhow::Error>(latencies)
+    };
+    let before = read(&before_path)?;
+    let after = read(&args.path)?;
+
+    let is_terminal = args.output_file.is_none() && io::stdout().is_terminal();
+    let no_color = env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+    let color = palette::use_color(args.color, is_terminal, no_color);
+    let mut out = open_output(args.output_file.as_deref())?;
+    compare::write(
+        &mut out,
+        &before_path.display().to_string(),
+        &args.path.display().to_string(),
+        &before,
+        &after,
+        color,
+    )
+    .map_err(|e| anyhow!("unable to write output: {}", e))
+}
+
+// Reads the syscalls of a trace saved with --save, or written by `strace -o`.
+fn read_syscalls(path: &Path) -> Result<Vec<strace::Syscall>> {
+    if !vst::is_vst(path) {
+        return strace::parse_file(path);
+    }
+    let (tx, rx) = mpsc::channel::<strace::Message>();
+    vst::replay(path, strace::LineParser::default(), tx, None, None)?;
+    Ok(rx
+        .into_iter()
+        .filter_map(strace::Message::into_syscall)
+        .collect())
+}
+
+fn compare_env(args: EnvArgs) -> Result<()> {
+    let (tx, rx) = mpsc::channel::<strace::Message>();
+    vst::replay(&args.path, strace::LineParser::default(), tx, None, None)?;
+    let mut processes = processes::ProcessTable::default();
+    let mut commands = commands::Commands::default();
+    for msg in rx.iter() {
+        if let strace::Message::Syscall(syscall) = msg {
+            processes.update(&syscall);
+            commands.update(&syscall, &processes);
+        }
+    }
+
+    let environment_of = |pid: u32| {
+        let command = commands
+            .commands
+            .iter()
+            .rev()
+            .find(|c| c.pid == Some(pid))
+            .ok_or_else(|| anyhow!("process {} did not run a program in the trace", pid))?;
+        let envp = command.env.as_ref().ok_or_else(|| {
+            anyhow!(
+                "the trace does not have the environment of process {} (trace with \
+                 --strace-arg=-v to record it)",
+                pid
+            )
+        })?;
+        Ok::<_, anyhow::Error>((command.to_string(), environment::from_trace(envp)))
+    };
+    let (left_label, left) = environment_of(args.pid)?;
+    let (right_label, right) = match args.other {
+        Some(other) => environment_of(other)?,
+        None => (
+            "the current environment".to_string(),
+            environment::current(),
+        ),
+    };
+
+    println!("--- {}", left_label);
+    println!("+++ {}", right_label);
+    for change in environment::diff(&left, &right) {
+        println!("{}", change);
+    }
+    Ok(())
+}
+
+fn ensure_supported(backend: Backend) {
+    let (supported, name) = match backend {
+        Backend::Strace | Backend::Ptrace | Backend::Bpf | Backend::Ltrace => ("linux", "Linux"),
+        Backend::Dtruss => ("macos", "macOS"),
+        Backend::Truss => ("freebsd", "FreeBSD"),
+    };
     let os = env::consts::OS;
-    if os != "linux" {
+    if os != supported {
         eprintln!(
-            "This command only works on Linux (detected OS: {}). Sorry.",
+            "--backend {} only works on {} (detected OS: {}). Sorry.",
+            backend.to_possible_value().unwrap().get_name(),
+            name,
             os
         );
         process::exit(1);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use clap::{CommandFactory, Parser};
+
+    use super::{Cli, Command};
+
+    #[test]
+    fn test_cli() {
+        Cli::command().debug_assert();
+
+        // a bare command is the same as `run`
+        let cli = Cli::try_parse_from(["vistrace", "--no-tui", "ls", "-l"]).unwrap();
+        assert!(cli.command.is_none());
+        assert_eq!(cli.run.args, ["ls", "-l"]);
+        assert!(cli.run.output.no_tui);
+
+        let cli = Cli::try_parse_from(["vistrace", "run", "ls", "--no-tui"]).unwrap();
+        match cli.command {
+            Some(Command::Run(args)) => assert_eq!(args.args, ["ls", "--no-tui"]),
+            command => panic!("unexpected command: {:?}", command),
+        }
+
+        // the commands after the first are split off later
+        let cli = Cli::try_parse_from([
+            "vistrace", "run", "--name", "api", "./api", "--", "--name", "worker", "./worker",
+        ])
+        .unwrap();
+        match cli.command {
+            Some(Command::Run(args)) => {
+                assert_eq!(args.name.as_deref(), Some("api"));
+                assert_eq!(args.args, ["./api", "--", "--name", "worker", "./worker"]);
+            }
+            command => panic!("unexpected command: {:?}", command),
+        }
+
+        let cli = Cli::try_parse_from([
+            "vistrace",
+            "check",
+            "--allow",
+            "allow.txt",
+            "--",
+            "./binary",
+            "-v",
+        ])
+        .unwrap();
+        match cli.command {
+            Some(Command::Check(args)) => assert_eq!(args.args, ["./binary", "-v"]),
+            command => panic!("unexpected command: {:?}", command),
+        }
+
+        assert!(Cli::try_parse_from(["vistrace"]).is_err());
+        assert!(Cli::try_parse_from(["vistrace", "check", "./binary"]).is_err());
+        assert!(Cli::try_parse_from(["vistrace", "run", "--output", "check", "ls"]).is_err());
+        assert!(Cli::try_parse_from(["vistrace", "attach"]).is_err());
+        assert!(Cli::try_parse_from(["vistrace", "attach", "--pid-glob", "x"]).is_ok());
+        assert!(Cli::try_parse_from(["vistrace", "view", "a.vst", "--replay"]).is_ok());
+        assert!(Cli::try_parse_from([
+            "vistrace",
+            "export",
+            "a.vst",
+            "--format",
+            "strace",
+            "--filter",
+            "path=^/etc/ !failed"
+        ])
+        .is_ok());
+        assert!(
+            Cli::try_parse_from(["vistrace", "export", "a.vst", "--filter", "bogus=1"]).is_err()
+        );
+    }
+}
@@ -0,0 +1,344 @@
+// Keeps a model of each traced process's address space from its mmap, munmap, mremap, mprotect,
+// and brk calls: what is mapped where, with which protections, and backed by which file, like a
+// /proc/<pid>/maps that can be seen at any point in the trace.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::fdtable::{FdKind, FdTable};
+use crate::strace::{Syscall, SyscallArgValue};
+use crate::syscalls;
+
+const PAGE_SIZE: u64 = 4096;
+
+#[derive(Default)]
+pub struct Memory {
+    // keyed by fd-table owner (threads share their address space as well as their fds)
+    pub spaces: BTreeMap<Option<u32>, AddressSpace>,
+    // the total bytes mapped by all processes after each change, as (time in microseconds, bytes)
+    pub history: Vec<(u64, u64)>,
+}
+
+#[derive(Default)]
+pub struct AddressSpace {
+    // keyed by start address
+    pub mappings: BTreeMap<u64, Mapping>,
+    pub mapped: u64,
+    pub peak: u64,
+    // where the heap that brk grows begins
+    heap_start: Option<u64>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Mapping {
+    pub start: u64,
+    pub end: u64,
+    // e.g., "r-xp" as in /proc/<pid>/maps
+    pub perms: String,
+    // the file that backs the mapping, or "[heap]", or `None` if it is anonymous
+    pub backing: Option<String>,
+}
+
+impl Memory {
+    /// Must be called after `fds` has been updated with `syscall`.
+    pub fn update(&mut self, syscall: &Syscall, fds: &FdTable) {
+        if syscall.errno.is_some() || syscall.error_details.is_some() {
+            return;
+        }
+        let owner = fds.owner(syscall.pid);
+        let number = |i: usize| match syscall.arg(i).map(|a| &a.value) {
+            Some(SyscallArgValue::Number(n)) => Some(*n as u64),
+            _ => None,
+        };
+        let returned = syscall.return_value as u64;
+        let space = self.spaces.entry(owner).or_default();
+        match syscall.name.as_str() {
+            "mmap" | "mmap2" => {
+                let length = match number(1) {
+                    Some(length) => length,
+                    None => return,
+                };
+                let backing = number(4)
+                    .filter(|_| !flag(syscall, 3, "MAP_ANONYMOUS"))
+                    .map(|fd| {
+                        match fds
+                            .resolve(syscall.pid, fd as i64, syscall.entry_time_micros)
+                            .kind
+                        {
+                            FdKind::File(path) => path,
+                            FdKind::Unknown => format!("fd {}", fd),
+                            kind => kind.to_string(),
+                        }
+                    });
+                let shared = flag(syscall, 3, "MAP_SHARED");
+                space.map(Mapping {
+                    start: returned,
+                    end: returned + round_up(length),
+                    perms: perms(syscall, 2, shared),
+                    backing,
+                });
+            }
+            "munmap" => {
+                if let (Some(start), Some(length)) = (number(0), number(1)) {
+                    space.unmap(start, start + round_up(length));
+                }
+            }
+            "mprotect" | "pkey_mprotect" => {
+                let (start, length) = match (number(0), number(1)) {
+                    (Some(start), Some(length)) => (start, length),
+                    _ => return,
+                };
+                for mut mapping in space.unmap(start, start + round_up(length)) {
+                    let shared = mapping.perms.ends_with('s');
+                    mapping.perms = perms(syscall, 2, shared);
+                    space.map(mapping);
+                }
+            }
+            "mremap" => {
+                let (start, old_length, new_length) = match (number(0), number(1), number(2)) {
+                    (Some(start), Some(old_length), Some(new_length)) => {
+                        (start, old_length, new_length)
+                    }
+                    _ => return,
+                };
+                let old = space.unmap(start, start + round_up(old_length));
+                if let Some(first) = old.into_iter().next() {
+                    space.map(Mapping {
+                        start: returned,
+                        end: returned + round_up(new_length),
+                        ..first
+                    });
+                }
+            }
+            "brk" => {
+                let heap_start = *space.heap_start.get_or_insert(returned);
+                if let Some(end) = space.mappings.get(&heap_start).map(|heap| heap.end) {
+                    space.unmap(heap_start, end);
+                }
+                if returned > heap_start {
+                    space.map(Mapping {
+                        start: heap_start,
+                        end: returned,
+                        perms: "rw-p".to_string(),
+                        backing: Some("[heap]".to_string()),
+                    });
+                }
+            }
+            // the new program starts with a fresh address space
+            "execve" | "execveat" => *space = AddressSpace::default(),
+            _ => return,
+        }
+
+        let total = self.spaces.values().map(|s| s.mapped).sum();
+        if self.history.last().map(|(_, bytes)| *bytes) != Some(total) {
+            self.history.push((syscall.entry_time_micros, total));
+        }
+    }
+}
+
+impl AddressSpace {
+    /// The mappings backed by the same file (or all anonymous ones), as (backing, mappings, bytes).
+    pub fn by_backing(&self) -> Vec<(String, usize, u64)> {
+        let mut groups: BTreeMap<String, (usize, u64)> = BTreeMap::new();
+        for mapping in self.mappings.values() {
+            let backing = mapping
+                .backing
+                .clone()
+                .unwrap_or_else(|| "[anonymous]".to_string());
+            let group = groups.entry(backing).or_default();
+            group.0 += 1;
+            group.1 += mapping.size();
+        }
+        let mut groups: Vec<(String, usize, u64)> = groups
+            .into_iter()
+            .map(|(backing, (count, bytes))| (backing, count, bytes))
+            .collect();
+        groups.sort_by_key(|(backing, _, bytes)| (std::cmp::Reverse(*bytes), backing.clone()));
+        groups
+    }
+
+    fn map(&mut self, mapping: Mapping) {
+        // a new mapping replaces whatever was there (e.g., with MAP_FIXED)
+        self.unmap(mapping.start, mapping.end);
+        self.mapped += mapping.size();
+        self.peak = self.peak.max(self.mapped);
+        self.mappings.insert(mapping.start, mapping);
+    }
+
+    // removes [start, end), splitting mappings that straddle either end, and returns the parts that
+    // were removed
+    fn unmap(&mut self, start: u64, end: u64) -> Vec<Mapping> {
+        let overlapping: Vec<u64> = self
+            .mappings
+            .range(..end)
+            .rev()
+            .take_while(|(_, m)| m.end > start)
+            .map(|(s, _)| *s)
+            .collect();
+        let mut removed = Vec::new();
+        for key in overlapping.into_iter().rev() {
+            let mapping = self.mappings.remove(&key).unwrap();
+            self.mapped -= mapping.size();
+            if mapping.start < start {
+                self.keep(Mapping {
+                    end: start,
+                    ..mapping.clone()
+                });
+            }
+            if mapping.end > end {
+                self.keep(Mapping {
+                    start: end,
+                    ..mapping.clone()
+                });
+            }
+            removed.push(Mapping {
+                start: mapping.start.max(start),
+                end: mapping.end.min(end),
+                ..mapping
+            });
+        }
+        removed
+    }
+
+    fn keep(&mut self, mapping: Mapping) {
+        self.mapped += mapping.size();
+        self.mappings.insert(mapping.start, mapping);
+    }
+}
+
+impl Mapping {
+    pub fn size(&self) -> u64 {
+        self.end - self.start
+    }
+}
+
+fn flag(syscall: &Syscall, index: usize, name: &str) -> bool {
+    syscall
+        .arg(index)
+        .is_some_and(|a| syscalls::value_has_flag(&a.value, name))
+}
+
+// e.g., "rw-p" for PROT_READ|PROT_WRITE and MAP_PRIVATE
+fn perms(syscall: &Syscall, index: usize, shared: bool) -> String {
+    let mut perms = String::new();
+    perms.push(if flag(syscall, index, "PROT_READ") {
+        'r'
+    } else {
+        '-'
+    });
+    perms.push(if flag(syscall, index, "PROT_WRITE") {
+        'w'
+    } else {
+        '-'
+    });
+    perms.push(if flag(syscall, index, "PROT_EXEC") {
+        'x'
+    } else {
+        '-'
+    });
+    perms.push(if shared { 's' } else { 'p' });
+    perms
+}
+
+fn round_up(length: u64) -> u64 {
+    length.div_ceil(PAGE_SIZE) * PAGE_SIZE
+}
+
+/// Draws `history` as a one-line graph `width` characters wide, each showing the most that was
+/// mapped during its slice of the trace.
+pub fn graph(history: &[(u64, u64)], width: usize) -> String {
+    const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+    let (first, last) = match (history.first(), history.last()) {
+        (Some(first), Some(last)) if width > 0 => (first.0, last.0),
+        _ => return String::new(),
+    };
+    let max = history
+        .iter()
+        .map(|(_, bytes)| *bytes)
+        .max()
+        .unwrap_or(0)
+        .max(1);
+    let span = (last - first).max(1);
+    let mut columns: Vec<Option<u64>> = vec![None; width];
+    for (time, bytes) in history {
+        let i = ((time - first) as u128 * (width as u128 - 1) / span as u128) as usize;
+        columns[i] = Some(columns[i].unwrap_or(0).max(*bytes));
+    }
+    // a column without changes carries the level from the last change before it
+    let mut level = 0;
+    columns
+        .into_iter()
+        .map(|column| {
+            if let Some(bytes) = column {
+                level = bytes;
+            }
+            BARS[((level * (BARS.len() as u64 - 1)) / max) as usize]
+        })
+        .collect()
+}
+
+impl fmt::Display for Mapping {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:x}-{:x} {} {:>8} KiB",
+            self.start,
+            self.end,
+            self.perms,
+            self.size() / 1024
+        )?;
+        if let Some(backing) = &self.backing {
+            write!(f, " {}", backing)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fdtable::FdTable;
+    use crate::strace::{LineParser, Message};
+
+    use super::Memory;
+
+    #[test]
+    fn test_memory() {
+        let mut memory = Memory::default();
+        let mut fds = FdTable::default();
+        let mut parser = LineParser::default();
+        for line in [
+            "10 brk(NULL) = 0x55d000",
+            "10 brk(0x57e000) = 0x57e000",
+            "10 openat(AT_FDCWD, \"/lib/libc.so.6\", O_RDONLY|O_CLOEXEC) = 3",
+            "10 mmap(NULL, 8192, PROT_READ|PROT_EXEC, MAP_PRIVATE|MAP_DENYWRITE, 3, 0) = 0x7f0000000000",
+            "10 mmap(NULL, 16384, PROT_READ|PROT_WRITE, MAP_PRIVATE|MAP_ANONYMOUS, -1, 0) = 0x7f0000010000",
+            // the middle page of the anonymous mapping becomes a guard page
+            "10 mprotect(0x7f0000011000, 4096, PROT_NONE) = 0",
+            "10 munmap(0x7f0000013000, 100) = 0",
+            "10 mremap(0x7f0000000000, 8192, 12288, MREMAP_MAYMOVE) = 0x7f0000020000",
+        ] {
+            let syscall = parser
+                .parse_line(line)
+                .and_then(Message::into_syscall)
+                .unwrap();
+            fds.update(&syscall);
+            memory.update(&syscall, &fds);
+        }
+
+        let space = &memory.spaces[&Some(10)];
+        let mappings: Vec<String> = space.mappings.values().map(|m| m.to_string()).collect();
+        assert_eq!(
+            mappings,
+            [
+                "55d000-57e000 rw-p      132 KiB [heap]",
+                "7f0000010000-7f0000011000 rw-p        4 KiB",
+                "7f0000011000-7f0000012000 ---p        4 KiB",
+                "7f0000012000-7f0000013000 rw-p        4 KiB",
+                "7f0000020000-7f0000023000 r-xp       12 KiB /lib/libc.so.6",
+            ]
+        );
+        assert_eq!(space.mapped, (132 + 4 + 4 + 4 + 12) * 1024);
+        assert_eq!(space.peak, (132 + 8 + 16) * 1024);
+        assert_eq!(memory.history.len(), 6);
+    }
+}
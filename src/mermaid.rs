@@ -0,0 +1,215 @@
+// Draws the traffic between traced processes over pipes, socketpairs, and unix sockets as a Mermaid
+// sequence diagram (https://mermaid.js.org/syntax/sequenceDiagram.html), for documentation and
+// incident writeups.
+//
+// Each arrow is data that a process read, drawn from the process that wrote it, in the order that
+// it was read. Since a channel is a stream, which write a read got its bytes from is worked out by
+// counting bytes, so a read that took bytes from two writers becomes two arrows.
+
+use std::collections::{HashMap, VecDeque};
+use std::io::{self, Write};
+
+use crate::fdtable::FdTable;
+use crate::net::Connections;
+use crate::peers::Channels;
+use crate::processes::ProcessTable;
+use crate::strace::{Exit, Message, Syscall, SyscallArgValue};
+use crate::syscalls::Io;
+
+// how much of the data to show on an arrow
+const PREVIEW_CHARS: usize = 40;
+
+#[derive(Default)]
+pub struct Sequence {
+    processes: ProcessTable,
+    fds: FdTable,
+    net: Connections,
+    channels: Channels,
+    // the bytes written to each end of each channel (keyed by channel and end) that have not been
+    // read from the other end yet, as the writer and how many bytes, oldest first
+    unread: HashMap<(usize, usize), VecDeque<(u32, u64)>>,
+    arrows: Vec<Arrow>,
+}
+
+struct Arrow {
+    from: u32,
+    to: u32,
+    channel: usize,
+    bytes: u64,
+    data: Option<String>,
+}
+
+impl Sequence {
+    pub fn update(&mut self, msg: &Message) {
+        match msg {
+            Message::Syscall(syscall) => self.update_syscall(syscall),
+            Message::Exit(exit) => {
+                self.net.record_exit(exit, &self.fds);
+                self.processes.record_exit(Exit {
+                    pid: exit.pid,
+                    time_micros: exit.time_micros,
+                    status: exit.status.clone(),
+                });
+            }
+            _ => {}
+        }
+    }
+
+    pub fn write(&self, w: &mut impl Write) -> io::Result<()> {
+        writeln!(w, "sequenceDiagram")?;
+        let mut participants = Vec::new();
+        for arrow in &self.arrows {
+            for pid in [arrow.from, arrow.to] {
+                if !participants.contains(&pid) {
+                    participants.push(pid);
+                }
+            }
+        }
+        for pid in participants {
+            writeln!(
+                w,
+                "    participant p{} as {}",
+                pid,
+                escape(&self.processes.label(pid))
+            )?;
+        }
+        for arrow in &self.arrows {
+            let kind = &self.channels.channels[arrow.channel].kind;
+            let text = match &arrow.data {
+                Some(data) => format!("{}: {} ({} bytes)", kind, data, arrow.bytes),
+                None => format!("{}: {} bytes", kind, arrow.bytes),
+            };
+            writeln!(w, "    p{}->>p{}: {}", arrow.from, arrow.to, escape(&text))?;
+        }
+        Ok(())
+    }
+
+    fn update_syscall(&mut self, syscall: &Syscall) {
+        self.processes.update(syscall);
+        self.fds.update(syscall);
+        self.net.update(syscall, &self.fds);
+        let transfer = match self.channels.update(syscall, &self.fds, &self.net) {
+            Some(transfer) => transfer,
+            None => return,
+        };
+        if transfer.io == Io::Write {
+            self.unread
+                .entry((transfer.channel, transfer.end))
+                .or_default()
+                .push_back((transfer.pid, transfer.bytes));
+            return;
+        }
+
+        // take the bytes from the oldest writes to the other end
+        let queue = self
+            .unread
+            .entry((transfer.channel, 1 - transfer.end))
+            .or_default();
+        let mut remaining = transfer.bytes;
+        let mut from: Vec<(u32, u64)> = Vec::new();
+        while remaining > 0 {
+            let (writer, available) = match queue.front_mut() {
+                Some(front) => front,
+                // written before the trace started, or through an fd that vistrace lost track of
+                None => break,
+            };
+            let taken = remaining.min(*available);
+            match from.last_mut() {
+                Some((last, bytes)) if *last == *writer => *bytes += taken,
+                _ => from.push((*writer, taken)),
+            }
+            *available -= taken;
+            remaining -= taken;
+            if *available == 0 {
+                queue.pop_front();
+            }
+        }
+
+        // the data is only shown if it all came from one writer, since it can't be split exactly
+        let data = if from.len() == 1 {
+            preview(syscall)
+        } else {
+            None
+        };
+        for (writer, bytes) in from {
+            if writer == transfer.pid {
+                continue;
+            }
+            self.arrows.push(Arrow {
+                from: writer,
+                to: transfer.pid,
+                channel: transfer.channel,
+                bytes,
+                data: data.clone(),
+            });
+        }
+    }
+}
+
+// the start of the data that a read returned, as strace printed it
+fn preview(syscall: &Syscall) -> Option<String> {
+    match syscall.arg(1).map(|a| &a.value) {
+        Some(SyscallArgValue::Quoted { text, truncated }) => {
+            let mut preview: String = text.chars().take(PREVIEW_CHARS).collect();
+            if *truncated || preview.len() < text.len() {
+                preview.push_str("...");
+            }
+            Some(format!("\"{}\"", preview))
+        }
+        _ => None,
+    }
+}
+
+// Mermaid ends a message at a semicolon, and uses `#` for its own escapes
+fn escape(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            '#' => "#35;".to_string(),
+            ';' => "#59;".to_string(),
+            c => c.to_string(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::strace::LineParser;
+
+    use super::Sequence;
+
+    #[test]
+    fn test_sequence() {
+        let mut sequence = Sequence::default();
+        let mut parser = LineParser::default();
+        for line in [
+            "10 execve(\"/bin/sh\", [\"sh\", \"-c\", \"a | b\"], 0x7ffc1000 /* 3 vars */) = 0",
+            "10 pipe2([3, 4], 0) = 0",
+            "10 clone(child_stack=NULL, flags=SIGCHLD) = 11",
+            "10 clone(child_stack=NULL, flags=SIGCHLD) = 12",
+            "11 write(4, \"one;\", 4) = 4",
+            "10 write(4, \"#two\", 4) = 4",
+            "12 read(3, \"one;\", 4) = 4",
+            "12 read(3, \"#two\", 4096) = 4",
+            "11 write(4, \"x\", 1) = 1",
+            "10 write(4, \"y\", 1) = 1",
+            "12 read(3, \"xy\", 4096) = 2",
+        ] {
+            sequence.update(&parser.parse_line(line).unwrap());
+        }
+
+        let mut out = Vec::new();
+        sequence.write(&mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            r##"sequenceDiagram
+    participant p11 as 11 (sh -c a | b)
+    participant p12 as 12 (sh -c a | b)
+    participant p10 as 10 (sh -c a | b)
+    p11->>p12: pipe: "one#59;" (4 bytes)
+    p10->>p12: pipe: "#35;two" (4 bytes)
+    p11->>p12: pipe: 1 bytes
+    p10->>p12: pipe: 1 bytes
+"##
+        );
+    }
+}
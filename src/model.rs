@@ -0,0 +1,438 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::antidebug::AntiDebug;
+use crate::audit::{self, Finding};
+use crate::blocking::Blocking;
+use crate::commands::Commands;
+use crate::config::ConfigFiles;
+use crate::console::Console;
+use crate::dns::{self, Resolution};
+use crate::epoll::EventLoops;
+use crate::eventfds::EventFds;
+use crate::fdtable::FdTable;
+use crate::filter::Filter;
+use crate::futex::Futexes;
+use crate::http::Exchanges;
+use crate::inject::Injection;
+use crate::iosizes::IoSizes;
+use crate::ipc::IpcObjects;
+use crate::libraries::Libraries;
+use crate::loops::Loops;
+use crate::memory::Memory;
+use crate::namespaces::Namespaces;
+use crate::net::Connections;
+use crate::outliers::Outliers;
+use crate::peers::Channels;
+use crate::postmortem::PostMortem;
+use crate::privileges::Privileges;
+use crate::processes::ProcessTable;
+use crate::rules::{Action, Rules};
+use crate::search::Searches;
+use crate::strace::{Exit, LibCall, Message, Signal, Syscall};
+use crate::summary::SyscallStats;
+use crate::symlinks::Symlinks;
+use crate::syscalls;
+use crate::targets::Targets;
+use crate::usage::Samples;
+use crate::watch::Watch;
+
+/// A row of the events list, by index into `Model::syscalls` or `Model::libcalls`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Event {
+    Syscall(usize),
+    LibCall(usize),
+}
+
+/// Everything vistrace knows about the trace so far. Lives in the UI's user data.
+#[derive(Default)]
+pub struct Model {
+    pub syscalls: Vec<Syscall>,
+    // the library calls (with --ltrace), each with the number of syscalls before it, so that the
+    // two can be listed in order
+    pub libcalls: Vec<(usize, LibCall)>,
+    pub watches: Vec<Watch>,
+    pub filter: Filter,
+    pub processes: ProcessTable,
+    pub commands: Commands,
+    pub fds: FdTable,
+    pub net: Connections,
+    pub channels: Channels,
+    // DNS responses that were received, keyed by index into `syscalls`
+    pub resolutions: Vec<(usize, Resolution)>,
+    pub http: Exchanges,
+    // the HTTP exchange (as an index into `http.exchanges`) that each syscall started or
+    // completed, keyed by index into `syscalls`
+    pub http_syscalls: BTreeMap<usize, usize>,
+    // the connection (as an index into `net.connections`) that each syscall used, keyed by index
+    // into `syscalls`
+    pub connection_syscalls: BTreeMap<usize, usize>,
+    pub privileges: Privileges,
+    pub libraries: Libraries,
+    pub config: ConfigFiles,
+    pub postmortem: PostMortem,
+    pub loops: Loops,
+    pub outliers: Outliers,
+    // the outlier (as an index into `outliers.found`) that each syscall was, keyed by index into
+    // `syscalls`
+    pub outlier_syscalls: BTreeMap<usize, usize>,
+    pub antidebug: AntiDebug,
+    // the latest check for a tracer (as an index into `antidebug.detections`), until the UI has
+    // shown it
+    pub new_detection: Option<usize>,
+    pub futexes: Futexes,
+    pub event_loops: EventLoops,
+    pub event_fds: EventFds,
+    // what each syscall did to a timerfd, eventfd, or signalfd, keyed by index into `syscalls`
+    pub event_fd_syscalls: BTreeMap<usize, String>,
+    pub memory: Memory,
+    pub io_sizes: IoSizes,
+    pub ipc: IpcObjects,
+    pub namespaces: Namespaces,
+    pub blocking: Blocking,
+    pub symlinks: Symlinks,
+    pub console: Console,
+    // the calls, errors, and time of each syscall, by name
+    pub stats: BTreeMap<String, SyscallStats>,
+    // what the traced processes were using over time, if they are being sampled
+    pub samples: Option<Samples>,
+    pub searches: Searches,
+    // the search (as an index into `searches.searches`) that each syscall was part of, keyed by
+    // index into `syscalls`
+    pub search_syscalls: BTreeMap<usize, usize>,
+    // with --audit, what each flagged syscall was flagged for, keyed by index into `syscalls`
+    pub audit: bool,
+    pub findings: BTreeMap<usize, Vec<Finding>>,
+    pub rules: Rules,
+    // the events that a rule highlighted, by index into `syscalls`
+    pub highlighted: BTreeSet<usize>,
+    // the alerts that events set off (as indices into `syscalls` and `rules.alerts`), until the UI
+    // has shown them
+    pub new_alerts: Vec<(usize, usize)>,
+    // marked events, keyed by index into `syscalls`, with an optional note
+    pub marks: BTreeMap<usize, String>,
+    // while frozen, new syscalls and library calls are held in `buffered` instead of being added to
+    // the model
+    pub frozen: bool,
+    pub buffered: Vec<Message>,
+    // if set, the model freezes itself at the next failed syscall that passes the filter
+    pub pause_on_error: bool,
+    // the failed syscall that the model last froze itself at, until the UI has shown it
+    pub paused_on: Option<usize>,
+    // whether the traced processes are on this machine, so that breakpoints can stop them
+    pub stoppable: bool,
+    // the processes that breakpoints stopped, until the UI resumes them
+    pub stopped: BTreeSet<u32>,
+    // the faults that strace was started with to stop processes at breakpoints (see
+    // `Rules::stop_injections`)
+    pub stop_injections: Vec<Injection>,
+    // strace, and the processes that it attached to, to find the process that made a syscall
+    // without a pid
+    pub strace_pid: Option<u32>,
+    pub attached_pids: Vec<u32>,
+    // the faults being injected, if they can be changed
+    pub injections: Option<Vec<Injection>>,
+    // the faults to restart the trace with, once the UI has quit
+    pub restart: Option<Vec<Injection>>,
+    // the commands that were traced together, and the one whose tab is showing (`None` for all)
+    pub targets: Targets,
+    pub tab: Option<usize>,
+    // the syscalls that --sample left out of the trace
+    pub skipped: u64,
+    // the name of the layout of the interface's panes (see src/layout.rs)
+    pub layout: String,
+    // whether to explain each event next to it (see src/explain.rs)
+    pub explain: bool,
+}
+
+impl Model {
+    /// Returns the index of the new syscall if it passes the current filter.
+    pub fn push(&mut self, syscall: Syscall) -> Option<usize> {
+        if self.frozen {
+            self.buffered.push(Message::Syscall(syscall));
+            return None;
+        }
+
+        for watch in &mut self.watches {
+            watch.update(&syscall);
+        }
+        self.processes.update(&syscall);
+        self.commands.update(&syscall, &self.processes);
+        // before the update, since closing a socket forgets it
+        let mut connection = syscalls::fd_args(&syscall)
+            .first()
+            .and_then(|fd| self.net.index(syscall.pid, *fd, &self.fds));
+        self.fds.update(&syscall);
+        self.net.update(&syscall, &self.fds);
+        // e.g., the socket that `socket` or `accept` made
+        connection = connection.or_else(|| {
+            syscalls::created_fds(&syscall)
+                .first()
+                .and_then(|fd| self.net.index(syscall.pid, *fd, &self.fds))
+        });
+        self.channels.update(&syscall, &self.fds, &self.net);
+        self.privileges.update(&syscall);
+        self.libraries.update(&syscall);
+        self.config.update(&syscall);
+        self.postmortem.update(&syscall, &self.fds);
+        self.loops.update(&syscall, &self.fds);
+        self.futexes.update(&syscall);
+        self.event_loops.update(&syscall, &self.fds);
+        self.memory.update(&syscall, &self.fds);
+        self.io_sizes.update(&syscall, &self.fds);
+        self.ipc.update(&syscall, &self.fds);
+        self.namespaces.update(&syscall, &self.fds);
+        self.blocking.update(&syscall);
+        self.symlinks.update(&syscall, &self.fds, &self.namespaces);
+        self.console.update(&syscall);
+        self.stats
+            .entry(syscall.name.clone())
+            .or_default()
+            .add(&syscall);
+        let index = self.syscalls.len();
+        for resolution in dns::decode(&syscall, &self.fds, &self.net) {
+            self.resolutions.push((index, resolution));
+        }
+        if let Some(exchange) = self.http.update(&syscall, &self.fds, &self.net) {
+            self.http_syscalls.insert(index, exchange);
+        }
+        if let Some(connection) = connection {
+            self.connection_syscalls.insert(index, connection);
+        }
+        if let Some(search) = self.searches.update(&syscall) {
+            self.search_syscalls.insert(index, search);
+        }
+        if let Some(description) = self.event_fds.update(&syscall, &self.fds) {
+            self.event_fd_syscalls.insert(index, description);
+        }
+        if let Some(outlier) = self.outliers.update(&syscall, &self.fds) {
+            self.outlier_syscalls.insert(index, outlier);
+        }
+        if let Some(detection) = self.antidebug.update(&syscall, &self.fds) {
+            self.new_detection = Some(detection);
+        }
+        if self.audit {
+            let findings = audit::check(&syscall);
+            if !findings.is_empty() {
+                self.findings.insert(index, findings);
+            }
+        }
+        for alert in self.rules.check(index, &syscall) {
+            let actions = &self.rules.alerts[alert].actions;
+            // a breakpoint highlights the event that it stopped at
+            if actions.contains(&Action::Highlight) || actions.contains(&Action::Stop) {
+                self.highlighted.insert(index);
+            }
+            if actions.iter().any(|action| *action != Action::Highlight) {
+                self.new_alerts.push((index, alert));
+            }
+            if actions.contains(&Action::Pause) || actions.contains(&Action::Stop) {
+                self.frozen = true;
+            }
+        }
+        let visible = self.filter.matches(index, &syscall)
+            && in_tab(&self.targets, &self.processes, self.tab, syscall.pid);
+        if visible && self.pause_on_error && syscall.errno.is_some() {
+            // later syscalls (including the rest of a thaw) are buffered
+            self.frozen = true;
+            self.pause_on_error = false;
+            self.paused_on = Some(index);
+        }
+        self.syscalls.push(syscall);
+        if visible {
+            Some(index)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the index of the new library call if it is shown, which it is only when there is no
+    /// filter, since filters are of syscalls.
+    pub fn push_libcall(&mut self, call: LibCall) -> Option<usize> {
+        if self.frozen {
+            self.buffered.push(Message::LibCall(call));
+            return None;
+        }
+        let visible =
+            self.filter.is_empty() && in_tab(&self.targets, &self.processes, self.tab, call.pid);
+        let index = self.libcalls.len();
+        self.libcalls.push((self.syscalls.len(), call));
+        visible.then_some(index)
+    }
+
+    /// Returns whether the traced program failed, in which case `postmortem.report` says why.
+    pub fn record_exit(&mut self, exit: Exit) -> bool {
+        self.net.record_exit(&exit, &self.fds);
+        let failed = self.postmortem.record_exit(&exit);
+        self.commands.record_exit(&exit);
+        self.processes.record_exit(exit);
+        failed
+    }
+
+    pub fn record_signal(&mut self, signal: &Signal) {
+        self.postmortem.record_signal(signal);
+    }
+
+    /// Unfreezes the model and returns the buffered events that pass the current filter.
+    pub fn thaw(&mut self) -> Vec<Event> {
+        self.frozen = false;
+        let buffered = std::mem::take(&mut self.buffered);
+        buffered
+            .into_iter()
+            .filter_map(|msg| match msg {
+                Message::Syscall(syscall) => self.push(syscall).map(Event::Syscall),
+                Message::LibCall(call) => self.push_libcall(call).map(Event::LibCall),
+                _ => None,
+            })
+            .collect()
+    }
+
+    pub fn add_watch(&mut self, mut watch: Watch) {
+        // catch up on what happened before the watch was added
+        for syscall in &self.syscalls {
+            watch.update(syscall);
+        }
+        self.watches.push(watch);
+    }
+
+    /// Replaces the current filter and returns the events that pass it (and are in the current
+    /// tab), in order.
+    pub fn set_filter(&mut self, mut filter: Filter) -> Vec<Event> {
+        filter.reset();
+        let in_tab = |pid| in_tab(&self.targets, &self.processes, self.tab, pid);
+        let show_libcalls = filter.is_empty();
+        let mut libcalls = self.libcalls.iter().enumerate().peekable();
+        let mut visible = Vec::new();
+        for (i, syscall) in self.syscalls.iter().enumerate() {
+            while let Some((j, (_, call))) = libcalls.next_if(|(_, (before, _))| *before <= i) {
+                if show_libcalls && in_tab(call.pid) {
+                    visible.push(Event::LibCall(j));
+                }
+            }
+            if filter.matches(i, syscall) && in_tab(syscall.pid) {
+                visible.push(Event::Syscall(i));
+            }
+        }
+        for (j, (_, call)) in libcalls {
+            if show_libcalls && in_tab(call.pid) {
+                visible.push(Event::LibCall(j));
+            }
+        }
+        self.filter = filter;
+        visible
+    }
+
+    /// Shows only the events of target `tab`, or of every target if `None`, and returns the events
+    /// that pass the filter and are in the tab.
+    pub fn set_tab(&mut self, tab: Option<usize>) -> Vec<Event> {
+        self.tab = tab;
+        let filter = std::mem::take(&mut self.filter);
+        self.set_filter(filter)
+    }
+
+    /// Replaces the rules, and re-applies their highlights to the events so far.
+    pub fn set_rules(&mut self, mut rules: Rules) {
+        rules.reset();
+        self.highlighted.clear();
+        for (i, syscall) in self.syscalls.iter().enumerate() {
+            let highlight = rules
+                .check(i, syscall)
+                .iter()
+                .any(|alert| rules.alerts[*alert].actions.contains(&Action::Highlight));
+            if highlight {
+                self.highlighted.insert(i);
+            }
+        }
+        self.rules = rules;
+    }
+
+    pub fn toggle_mark(&mut self, index: usize) {
+        if self.marks.remove(&index).is_none() {
+            self.marks.insert(index, String::new());
+        }
+    }
+}
+
+// whether process `pid` belongs to the target whose tab is showing, if any
+fn in_tab(
+    targets: &Targets,
+    processes: &ProcessTable,
+    tab: Option<usize>,
+    pid: Option<u32>,
+) -> bool {
+    tab.is_none_or(|tab| pid.and_then(|pid| targets.target_of(pid, processes)) == Some(tab))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::filter::Filter;
+    use crate::strace::{parse_syscall, LineParser, Message};
+
+    use super::{Event, Model};
+
+    #[test]
+    fn test_pause_on_error() {
+        let mut m = Model {
+            pause_on_error: true,
+            ..Default::default()
+        };
+        let lines = [
+            "close(3) = 0",
+            "openat(AT_FDCWD, \"/a\", O_RDONLY) = -1 ENOENT (No such file or directory)",
+            "close(4) = -1 EBADF (Bad file descriptor)",
+        ];
+        let pushed: Vec<_> = lines
+            .iter()
+            .map(|l| m.push(parse_syscall(l, false)))
+            .collect();
+        assert_eq!(pushed, vec![Some(0), Some(1), None]);
+        assert!(m.frozen);
+        assert_eq!(m.paused_on, Some(1));
+        assert_eq!(m.buffered.len(), 1);
+
+        // only the first failure pauses
+        assert_eq!(m.thaw(), vec![Event::Syscall(2)]);
+        assert!(!m.frozen);
+    }
+
+    #[test]
+    fn test_libcalls() {
+        let mut m = Model::default();
+        let mut parser = LineParser::default();
+        for line in [
+            "10 ->malloc(16) = 0x55d0c8a2a2a0",
+            "10 close(3) = 0",
+            "10 ->puts(\"hi\") = 3",
+            "10 write(1, \"hi\\n\", 3) = 3",
+        ] {
+            match parser.parse_line(line) {
+                Some(Message::Syscall(syscall)) => {
+                    m.push(syscall);
+                }
+                Some(Message::LibCall(call)) => {
+                    m.push_libcall(call);
+                }
+                _ => panic!("unexpected line: {}", line),
+            }
+        }
+        assert_eq!(
+            m.set_filter(Filter::default()),
+            vec![
+                Event::LibCall(0),
+                Event::Syscall(0),
+                Event::LibCall(1),
+                Event::Syscall(1)
+            ]
+        );
+        // library calls are only shown without a filter
+        assert_eq!(
+            m.set_filter(Filter::parse("write").unwrap()),
+            vec![Event::Syscall(1)]
+        );
+        let call = parser.parse_line("10 ->free(0x55d0c8a2a2a0) = <void>");
+        let call = match call {
+            Some(Message::LibCall(call)) => call,
+            _ => panic!("not a library call"),
+        };
+        assert_eq!(m.push_libcall(call), None);
+    }
+}
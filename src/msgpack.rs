@@ -0,0 +1,326 @@
+// A minimal MessagePack writer, for the compact binary form of events (`--encoding msgpack`; see
+// src/vst.rs). It writes the same values as the JSON writer, so events have the
+// schema described in jsonl.rs either way; MessagePack is smaller and much faster to decode.
+//
+// Integers are written in the smallest form that holds them, as the format recommends. Syscalls,
+// which are most of a busy trace, are written without building their JSON first, since that takes
+// most of the time; the test checks that this gives the same bytes as going through the JSON.
+
+use std::collections::HashMap;
+
+use crate::json::Json;
+use crate::jsonl::{self, SCHEMA_VERSION};
+use crate::strace::{FlagSetValue, Message, SyscallArg, SyscallArgValue};
+
+/// Writes the record of `msg` in the schema of jsonl.rs, or nothing if it has none (e.g., a stack
+/// frame).
+pub fn write_message(buf: &mut Vec<u8>, msg: &Message) {
+    let Message::Syscall(syscall) = msg else {
+        if let Some(json) = jsonl::to_json(msg) {
+            write(buf, &json);
+        }
+        return;
+    };
+    let details = syscall.error_details.as_ref();
+    write_len(buf, if details.is_some() { 13 } else { 12 }, 0x80, 0xde);
+    write_str(buf, "schema_version");
+    write_int(buf, SCHEMA_VERSION);
+    write_str(buf, "type");
+    write_str(buf, "syscall");
+    write_str(buf, "pid");
+    write_option(buf, syscall.pid.map(i64::from));
+    write_str(buf, "time_us");
+    write_micros(buf, syscall.entry_time_micros);
+    write_str(buf, "name");
+    write_str(buf, &syscall.name);
+    write_str(buf, "args");
+    write_args(buf, &syscall.args);
+    write_str(buf, "return");
+    write_option(buf, syscall.result());
+    write_str(buf, "errno");
+    match &syscall.errno {
+        Some(errno) => write_str(buf, errno),
+        None => buf.push(0xc0),
+    }
+    write_str(buf, "injected");
+    write(buf, &Json::Bool(syscall.injected));
+    write_str(buf, "duration_us");
+    write_option(buf, syscall.syscall_time_micros.map(|t| t as i64));
+    write_str(buf, "content");
+    write(buf, &jsonl::content(syscall));
+    write_str(buf, "parse_error");
+    match details {
+        Some(details) => {
+            write_str(buf, &details.message);
+            write_str(buf, "raw");
+            write_str(buf, details.fulltext.trim_end());
+        }
+        None => buf.push(0xc0),
+    }
+}
+
+pub fn write(buf: &mut Vec<u8>, value: &Json) {
+    match value {
+        Json::Null => buf.push(0xc0),
+        Json::Bool(false) => buf.push(0xc2),
+        Json::Bool(true) => buf.push(0xc3),
+        Json::Number(n) => write_int(buf, *n),
+        Json::String(s) => write_str(buf, s),
+        Json::Array(items) => {
+            write_len(buf, items.len(), 0x90, 0xdc);
+            for item in items {
+                write(buf, item);
+            }
+        }
+        Json::Object(fields) => {
+            write_len(buf, fields.len(), 0x80, 0xde);
+            for (key, value) in fields {
+                write_str(buf, key);
+                write(buf, value);
+            }
+        }
+    }
+}
+
+fn write_args(buf: &mut Vec<u8>, args: &[SyscallArg]) {
+    write_len(buf, args.len(), 0x90, 0xdc);
+    for arg in args {
+        let named = !arg.name.is_empty();
+        write_len(buf, named as usize + value_len(&arg.value), 0x80, 0xde);
+        if named {
+            write_str(buf, "name");
+            write_str(buf, &arg.name);
+        }
+        write_value(buf, &arg.value);
+    }
+}
+
+// how many fields `write_value` writes
+fn value_len(value: &SyscallArgValue) -> usize {
+    match value {
+        SyscallArgValue::Quoted { .. } | SyscallArgValue::FunctionCall(..) => 3,
+        _ => 2,
+    }
+}
+
+fn write_value(buf: &mut Vec<u8>, value: &SyscallArgValue) {
+    let mut kind = |kind: &str| {
+        write_str(buf, "kind");
+        write_str(buf, kind);
+    };
+    match value {
+        SyscallArgValue::Quoted { text, truncated } => {
+            kind("string");
+            write_str(buf, "value");
+            write_str(buf, text);
+            write_str(buf, "truncated");
+            write(buf, &Json::Bool(*truncated));
+        }
+        SyscallArgValue::Symbol(symbol) => {
+            kind("symbol");
+            write_str(buf, "value");
+            write_str(buf, symbol);
+        }
+        SyscallArgValue::FlagSet(flags) => {
+            kind("flags");
+            write_str(buf, "value");
+            write_len(buf, flags.len(), 0x90, 0xdc);
+            for flag in flags {
+                match flag {
+                    FlagSetValue::Symbol(symbol) => write_str(buf, symbol),
+                    FlagSetValue::Bits(bits) => write_int(buf, *bits),
+                }
+            }
+        }
+        SyscallArgValue::Number(n) => {
+            kind("number");
+            write_str(buf, "value");
+            write_int(buf, *n);
+        }
+        SyscallArgValue::Product(a, b) => {
+            kind("product");
+            write_str(buf, "value");
+            buf.push(0x92);
+            write_int(buf, *a);
+            write_int(buf, *b);
+        }
+        SyscallArgValue::Array(values) => {
+            kind("array");
+            write_str(buf, "value");
+            write_args(buf, values);
+        }
+        SyscallArgValue::Struct(map) => {
+            kind("struct");
+            write_str(buf, "value");
+            write_fields(buf, map);
+        }
+        SyscallArgValue::FunctionCall(name, values) => {
+            kind("call");
+            write_str(buf, "function");
+            write_str(buf, name);
+            write_str(buf, "value");
+            write_args(buf, values);
+        }
+    }
+}
+
+// keys are sorted, as in the JSON
+fn write_fields(buf: &mut Vec<u8>, map: &HashMap<String, SyscallArg>) {
+    let mut keys: Vec<&String> = map.keys().collect();
+    keys.sort();
+    write_len(buf, keys.len(), 0x80, 0xde);
+    for key in keys {
+        write_str(buf, key);
+        let value = &map[key].value;
+        write_len(buf, value_len(value), 0x80, 0xde);
+        write_value(buf, value);
+    }
+}
+
+// strace output never has a timestamp of zero, so zero means it was missing
+fn write_micros(buf: &mut Vec<u8>, t: u64) {
+    write_option(buf, (t != 0).then_some(t as i64));
+}
+
+fn write_option(buf: &mut Vec<u8>, n: Option<i64>) {
+    match n {
+        Some(n) => write_int(buf, n),
+        None => buf.push(0xc0),
+    }
+}
+
+fn write_int(buf: &mut Vec<u8>, n: i64) {
+    match n {
+        0..=0x7f => buf.push(n as u8),
+        -32..=-1 => buf.push(n as i8 as u8),
+        0x80..=0xff => buf.extend_from_slice(&[0xcc, n as u8]),
+        0x100..=0xffff => {
+            buf.push(0xcd);
+            buf.extend_from_slice(&(n as u16).to_be_bytes());
+        }
+        0x1_0000..=0xffff_ffff => {
+            buf.push(0xce);
+            buf.extend_from_slice(&(n as u32).to_be_bytes());
+        }
+        0x1_0000_0000.. => {
+            buf.push(0xcf);
+            buf.extend_from_slice(&(n as u64).to_be_bytes());
+        }
+        -0x80..=-33 => buf.extend_from_slice(&[0xd0, n as i8 as u8]),
+        -0x8000..=-0x81 => {
+            buf.push(0xd1);
+            buf.extend_from_slice(&(n as i16).to_be_bytes());
+        }
+        -0x8000_0000..=-0x8001 => {
+            buf.push(0xd2);
+            buf.extend_from_slice(&(n as i32).to_be_bytes());
+        }
+        _ => {
+            buf.push(0xd3);
+            buf.extend_from_slice(&n.to_be_bytes());
+        }
+    }
+}
+
+fn write_str(buf: &mut Vec<u8>, s: &str) {
+    let len = s.len();
+    if len < 32 {
+        buf.push(0xa0 | len as u8);
+    } else if len <= 0xff {
+        buf.extend_from_slice(&[0xd9, len as u8]);
+    } else if len <= 0xffff {
+        buf.push(0xda);
+        buf.extend_from_slice(&(len as u16).to_be_bytes());
+    } else {
+        buf.push(0xdb);
+        buf.extend_from_slice(&(len as u32).to_be_bytes());
+    }
+    buf.extend_from_slice(s.as_bytes());
+}
+
+// the length of an array or map: `fix` holds up to 15 in its low bits, and `wide` is the marker for
+// a 16-bit length (and, plus one, for a 32-bit length)
+fn write_len(buf: &mut Vec<u8>, len: usize, fix: u8, wide: u8) {
+    if len < 16 {
+        buf.push(fix | len as u8);
+    } else if len <= 0xffff {
+        buf.push(wide);
+        buf.extend_from_slice(&(len as u16).to_be_bytes());
+    } else {
+        buf.push(wide + 1);
+        buf.extend_from_slice(&(len as u32).to_be_bytes());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::json::Json;
+    use crate::jsonl;
+    use crate::strace::LineParser;
+
+    use super::{write, write_message};
+
+    fn encode(value: Json) -> Vec<u8> {
+        let mut buf = Vec::new();
+        write(&mut buf, &value);
+        buf
+    }
+
+    #[test]
+    fn test_msgpack() {
+        assert_eq!(encode(Json::Null), [0xc0]);
+        assert_eq!(encode(Json::Bool(true)), [0xc3]);
+        assert_eq!(encode(Json::Number(5)), [0x05]);
+        assert_eq!(encode(Json::Number(-1)), [0xff]);
+        assert_eq!(encode(Json::Number(-33)), [0xd0, 0xdf]);
+        assert_eq!(encode(Json::Number(200)), [0xcc, 200]);
+        assert_eq!(encode(Json::Number(65536)), [0xce, 0, 1, 0, 0]);
+        assert_eq!(
+            encode(Json::Number(1_720_000_000_000_001)),
+            [0xcf, 0x00, 0x06, 0x1c, 0x54, 0xb5, 0x03, 0x80, 0x01]
+        );
+        assert_eq!(
+            encode(Json::Number(-40_000)),
+            [0xd2, 0xff, 0xff, 0x63, 0xc0]
+        );
+        assert_eq!(encode(Json::string("ab")), [0xa2, b'a', b'b']);
+        let long = "x".repeat(40);
+        assert_eq!(&encode(Json::string(&long))[..2], [0xd9, 40]);
+        assert_eq!(
+            encode(Json::object([
+                ("ok", Json::Bool(false)),
+                ("xs", Json::Array(vec![Json::Number(1), Json::Null])),
+            ])),
+            [0x82, 0xa2, b'o', b'k', 0xc2, 0xa2, b'x', b's', 0x92, 0x01, 0xc0]
+        );
+        let many = Json::Array((0..20).map(Json::Number).collect());
+        assert_eq!(&encode(many)[..3], [0xdc, 0, 20]);
+    }
+
+    #[test]
+    fn test_write_message() {
+        let mut parser = LineParser::default();
+        for line in [
+            "10 1720000000.000001 openat(AT_FDCWD, \"/etc/passwd\", O_RDONLY|O_CLOEXEC) = 3 <0.000012>\n",
+            "10 1720000000.000002 read(3, \"root:x:0:0:root:/root:/bin/bash\\n\"..., 4096) = 4096 <0.000004>\n",
+            "10 fstat(3, {st_mode=S_IFREG|0644, st_size=2220, ...}) = 0\n",
+            "10 mknodat(AT_FDCWD, \"/dev/x\", S_IFCHR|0600, makedev(0x1, 0x3)) = -1 EPERM (Operation not permitted)\n",
+            "10 poll([{fd=3, events=POLLIN}], 1, 1000 * 2) = 1 ([{fd=3, revents=POLLIN}])\n",
+            "10 ioctl(1, TCGETS, {c_iflag=ICRNL|IXON, ...}) = 0 (INJECTED)\n",
+            "nonsense(\n",
+            "10 --- SIGCHLD {si_signo=SIGCHLD, si_code=CLD_EXITED, si_pid=11} ---\n",
+            "10 +++ exited with 0 +++\n",
+        ] {
+            let Some(msg) = parser.parse_line(line) else {
+                continue;
+            };
+            let mut direct = Vec::new();
+            write_message(&mut direct, &msg);
+            let mut via_json = Vec::new();
+            if let Some(json) = jsonl::to_json(&msg) {
+                write(&mut via_json, &json);
+            }
+            assert_eq!(direct, via_json, "{}", line);
+        }
+    }
+}
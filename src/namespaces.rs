@@ -0,0 +1,267 @@
+// Follows the namespaces that the traced processes create and join (with unshare, setns, and the
+// CLONE_NEW* flags of clone), and, when attaching to a process in a container, which container it
+// is and where the paths that it sees are on this machine.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+use crate::fdtable::{FdKind, FdTable};
+use crate::procfs;
+use crate::strace::{format_timestamp, Syscall, SyscallArgValue};
+use crate::syscalls;
+
+// the clone flag for each kind of namespace, with its name under /proc/<pid>/ns
+const KINDS: &[(&str, &str)] = &[
+    ("CLONE_NEWNS", "mnt"),
+    ("CLONE_NEWUTS", "uts"),
+    ("CLONE_NEWIPC", "ipc"),
+    ("CLONE_NEWUSER", "user"),
+    ("CLONE_NEWPID", "pid"),
+    ("CLONE_NEWNET", "net"),
+    ("CLONE_NEWCGROUP", "cgroup"),
+    ("CLONE_NEWTIME", "time"),
+];
+
+#[derive(Default)]
+pub struct Namespaces {
+    // keyed by pid
+    pub changes: BTreeMap<u32, Vec<Change>>,
+    // the containers that were attached to, keyed by pid
+    pub containers: BTreeMap<u32, Container>,
+    // for each process in a container, where its root directory is on this machine
+    roots: HashMap<u32, String>,
+}
+
+pub struct Change {
+    pub time_micros: u64,
+    // e.g., "unshare", or "setns to /proc/1234/ns/net"
+    pub how: String,
+    // e.g., ["mnt", "net"]
+    pub kinds: Vec<&'static str>,
+    pub errno: Option<String>,
+}
+
+#[derive(Clone)]
+pub struct Container {
+    pub pid: u32,
+    pub cgroup: String,
+    // the full ID, for Docker, Podman, containerd, and CRI-O
+    pub id: Option<String>,
+}
+
+impl Namespaces {
+    /// Records that `container` was attached to, so that paths in it can be translated.
+    pub fn add_container(&mut self, container: Container) {
+        self.roots
+            .insert(container.pid, format!("/proc/{}/root", container.pid));
+        self.containers.insert(container.pid, container);
+    }
+
+    /// Must be called after `fds` has been updated with `syscall`.
+    pub fn update(&mut self, syscall: &Syscall, fds: &FdTable) {
+        let pid = match syscall.pid {
+            Some(pid) if syscall.error_details.is_none() => pid,
+            _ => return,
+        };
+        let flags = |index: usize| -> Vec<&'static str> {
+            KINDS
+                .iter()
+                .filter(|(flag, _)| {
+                    syscall
+                        .arg(index)
+                        .is_some_and(|a| syscalls::value_has_flag(&a.value, flag))
+                })
+                .map(|(_, kind)| *kind)
+                .collect()
+        };
+
+        let (how, kinds) = match syscall.name.as_str() {
+            "clone" | "clone3" => {
+                if syscall.return_value <= 0 {
+                    return;
+                }
+                let child = syscall.return_value as u32;
+                // the child of a process in a container is in the same container
+                if let Some(root) = self.roots.get(&pid).cloned() {
+                    self.roots.insert(child, root);
+                }
+                let kinds: Vec<&'static str> = KINDS
+                    .iter()
+                    .filter(|(flag, _)| syscalls::has_clone_flag(syscall, flag))
+                    .map(|(_, kind)| *kind)
+                    .collect();
+                (format!("{} of {}", syscall.name, child), kinds)
+            }
+            "fork" | "vfork" if syscall.return_value > 0 => {
+                if let Some(root) = self.roots.get(&pid).cloned() {
+                    self.roots.insert(syscall.return_value as u32, root);
+                }
+                return;
+            }
+            "unshare" => ("unshare".to_string(), flags(0)),
+            "setns" => {
+                let target = match syscall.arg(0).map(|a| &a.value) {
+                    Some(SyscallArgValue::Number(fd)) => {
+                        match fds
+                            .resolve(syscall.pid, *fd, syscall.entry_time_micros)
+                            .kind
+                        {
+                            FdKind::File(path) => path,
+                            _ => format!("fd {}", fd),
+                        }
+                    }
+                    _ => return,
+                };
+                // an nstype of 0 joins whatever kind of namespace the fd refers to
+                let mut kinds = flags(1);
+                if kinds.is_empty() {
+                    let name = target.rsplit('/').next().unwrap_or_default();
+                    kinds.extend(KINDS.iter().map(|(_, k)| *k).filter(|k| *k == name));
+                }
+                (format!("setns to {}", target), kinds)
+            }
+            _ => return,
+        };
+        if kinds.is_empty() {
+            return;
+        }
+        // after joining another mount namespace, the process sees a different filesystem
+        if syscall.name == "setns" && syscall.errno.is_none() && kinds.contains(&"mnt") {
+            self.roots.remove(&pid);
+        }
+        self.changes.entry(pid).or_default().push(Change {
+            time_micros: syscall.entry_time_micros,
+            how,
+            kinds,
+            errno: syscall.errno.clone(),
+        });
+    }
+
+    /// Returns where `path`, as process `pid` sees it, is on this machine, if `pid` is in a
+    /// container.
+    pub fn host_path(&self, pid: Option<u32>, path: &str) -> Option<String> {
+        let root = self.roots.get(&pid?)?;
+        path.starts_with('/')
+            .then(|| format!("{}{}", root, path.trim_end_matches('/')))
+    }
+
+    /// Returns whether process `pid` sees a different filesystem than vistrace does.
+    pub fn in_container(&self, pid: Option<u32>) -> bool {
+        pid.is_some_and(|pid| self.roots.contains_key(&pid))
+    }
+}
+
+impl Container {
+    /// Returns the container that `pid` is running in, or `None` if it is not in one, i.e., if its
+    /// cgroup does not name a container and it shares vistrace's mount namespace.
+    pub fn inspect(pid: u32) -> Option<Container> {
+        let cgroup = procfs::cgroup(pid).unwrap_or_default();
+        let id = container_id(&cgroup);
+        let own = procfs::namespace(None, "mnt");
+        let separate = own.is_some() && procfs::namespace(Some(pid), "mnt") != own;
+        (id.is_some() || separate).then_some(Container { pid, cgroup, id })
+    }
+}
+
+// finds the container ID in a cgroup path, e.g., "/system.slice/docker-<ID>.scope" or
+// "/kubepods/besteffort/pod<UID>/<ID>"
+fn container_id(cgroup: &str) -> Option<String> {
+    cgroup.split('/').rev().find_map(|part| {
+        let part = part.strip_suffix(".scope").unwrap_or(part);
+        let id = part.rsplit(['-', ':']).next()?;
+        (id.len() == 64 && id.chars().all(|c| c.is_ascii_hexdigit())).then(|| id.to_string())
+    })
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.time_micros != 0 {
+            write!(f, "{} ", format_timestamp(self.time_micros))?;
+        }
+        write!(f, "{}: {}", self.how, self.kinds.join(", "))?;
+        if let Some(errno) = &self.errno {
+            write!(f, " (failed with {})", errno)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Container {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // shortened as `docker ps` does
+        match &self.id {
+            Some(id) => write!(f, "container {}", &id[..12])?,
+            None => write!(f, "separate mount namespace")?,
+        }
+        if !self.cgroup.is_empty() {
+            write!(f, " in cgroup {}", self.cgroup)?;
+        }
+        write!(f, ", with its files under /proc/{}/root", self.pid)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fdtable::FdTable;
+    use crate::strace::{LineParser, Message};
+
+    use super::{container_id, Container, Namespaces};
+
+    #[test]
+    fn test_namespaces() {
+        let mut namespaces = Namespaces::default();
+        namespaces.add_container(Container {
+            pid: 10,
+            cgroup: "/system.slice/docker-3f2a.scope".to_string(),
+            id: None,
+        });
+        let mut fds = FdTable::default();
+        let mut parser = LineParser::default();
+        for line in [
+            "10 clone(child_stack=NULL, flags=CLONE_NEWPID|CLONE_NEWNET|SIGCHLD) = 11",
+            "11 unshare(CLONE_NEWNS|CLONE_NEWUTS) = 0",
+            "12 openat(AT_FDCWD, \"/proc/1/ns/net\", O_RDONLY) = 3",
+            "12 setns(3, 0) = -1 EPERM (Operation not permitted)",
+            "12 unshare(CLONE_FILES) = 0",
+        ] {
+            let syscall = parser
+                .parse_line(line)
+                .and_then(Message::into_syscall)
+                .unwrap();
+            fds.update(&syscall);
+            namespaces.update(&syscall, &fds);
+        }
+
+        let changes = |pid: u32| {
+            namespaces.changes[&pid]
+                .iter()
+                .map(|c| c.to_string())
+                .collect::<Vec<String>>()
+        };
+        assert_eq!(changes(10), ["clone of 11: pid, net"]);
+        assert_eq!(changes(11), ["unshare: mnt, uts"]);
+        assert_eq!(
+            changes(12),
+            ["setns to /proc/1/ns/net: net (failed with EPERM)"]
+        );
+        assert_eq!(
+            namespaces.host_path(Some(11), "/etc/hosts").as_deref(),
+            Some("/proc/10/root/etc/hosts")
+        );
+        assert_eq!(namespaces.host_path(Some(12), "/etc/hosts"), None);
+
+        let id = "3f2a9c1b2d4e".repeat(5) + "abcd";
+        assert_eq!(
+            container_id(&format!("/system.slice/docker-{}.scope", id)),
+            Some(id.clone())
+        );
+        assert_eq!(
+            container_id(&format!("/kubepods/besteffort/pod1234/{}", id)),
+            Some(id.clone())
+        );
+        assert_eq!(
+            container_id("/user.slice/user-1000.slice/session-2.scope"),
+            None
+        );
+    }
+}
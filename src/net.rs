@@ -0,0 +1,531 @@
+// Follows sockets through socket/connect/bind/listen/accept/getsockname and friends to describe
+// each network connection: what it connected to, for how long, how much it moved, and how it
+// ended.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::net::IpAddr;
+
+use crate::fdtable::{ByteCounts, FdTable};
+use crate::strace::{self, Exit, FlagSetValue, Syscall, SyscallArg, SyscallArgValue};
+use crate::syscalls::{self, Io};
+use crate::tls::{self, Hello, Tls};
+
+#[derive(Default)]
+pub struct Connections {
+    pub connections: Vec<Connection>,
+    // open sockets by fd table owner and fd, as indices into `connections`; a forked child shares
+    // its parent's sockets, so more than one key may refer to the same connection
+    open: HashMap<(Option<u32>, i64), usize>,
+}
+
+pub struct Connection {
+    // the process that created the socket
+    pub pid: Option<u32>,
+    pub fd: i64,
+    // e.g., "tcp", "udp", or "unix"
+    pub protocol: String,
+    pub role: Role,
+    pub local: Option<Endpoint>,
+    pub remote: Option<Endpoint>,
+    // in microseconds since the Unix epoch (0 if unknown)
+    pub opened_micros: u64,
+    // `None` if the socket is still open
+    pub closed_micros: Option<u64>,
+    pub close_reason: Option<CloseReason>,
+    pub bytes: ByteCounts,
+    // set if a TLS handshake was seen on the socket
+    pub tls: Option<Tls>,
+    // the last error seen on the socket, e.g., ECONNREFUSED from connect
+    error: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Role {
+    // not (yet) connected or listening
+    Unknown,
+    Client,
+    Listener,
+    // accepted from a listener
+    Server,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Endpoint {
+    Inet(IpAddr, u16),
+    // a path, or a name starting with '@' for abstract sockets, or empty for unnamed sockets
+    Unix(String),
+    // an address family that vistrace does not decode, e.g. "AF_NETLINK"
+    Other(String),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum CloseReason {
+    Closed,
+    // closed after an error, e.g. ECONNREFUSED or ECONNRESET
+    Error(String),
+    // still open when the process exited
+    Exited,
+}
+
+impl Connections {
+    /// Must be called after `fds` has been updated with `syscall`.
+    pub fn update(&mut self, syscall: &Syscall, fds: &FdTable) {
+        let owner = fds.owner(syscall.pid);
+        let time = syscall.entry_time_micros;
+        let succeeded = syscall.errno.is_none() && syscall.error_details.is_none();
+        let sockfd = match syscall.arg(0).map(|a| &a.value) {
+            Some(SyscallArgValue::Number(fd)) => *fd,
+            _ => -1,
+        };
+        let address = |name: &str| {
+            syscalls::arg_index(&syscall.name, name)
+                .and_then(|i| syscall.arg(i))
+                .and_then(|a| decode_sockaddr(&a.value))
+        };
+
+        match syscall.name.as_str() {
+            "socket" | "socketpair" if succeeded => {
+                let protocol = protocol(syscall);
+                for fd in syscalls::created_fds(syscall) {
+                    self.open(syscall.pid, owner, fd, protocol.clone(), time);
+                }
+            }
+            "accept" | "accept4" if succeeded => {
+                let listener = self.get(owner, sockfd);
+                let protocol = listener.map(|c| c.protocol.clone()).unwrap_or_default();
+                let local = listener.and_then(|c| c.local.clone());
+                let index = self.open(syscall.pid, owner, syscall.return_value, protocol, time);
+                let connection = &mut self.connections[index];
+                connection.role = Role::Server;
+                connection.local = local;
+                connection.remote = address("addr");
+            }
+            // a line that could not be parsed has no socket to connect
+            "connect" if sockfd >= 0 => {
+                let remote = address("addr");
+                let connection = self.get_or_insert(syscall.pid, owner, sockfd, time);
+                connection.role = Role::Client;
+                if remote.is_some() {
+                    connection.remote = remote;
+                }
+                connection.record_error(syscall);
+            }
+            "bind" if succeeded => {
+                self.get_or_insert(syscall.pid, owner, sockfd, time).local = address("addr");
+            }
+            "listen" if succeeded => {
+                self.get_or_insert(syscall.pid, owner, sockfd, time).role = Role::Listener;
+            }
+            "getsockname" if succeeded => {
+                self.get_or_insert(syscall.pid, owner, sockfd, time).local = address("addr");
+            }
+            "getpeername" if succeeded => {
+                self.get_or_insert(syscall.pid, owner, sockfd, time).remote = address("addr");
+            }
+            "close" if succeeded => self.close(owner, sockfd, time, None),
+            "clone" | "clone3" | "fork" | "vfork" if syscall.return_value > 0 => {
+                // a forked child shares its parent's sockets (a thread shares the fd table, so
+                // there is nothing to do)
+                if !syscalls::has_clone_flag(syscall, "CLONE_FILES") {
+                    let child = Some(syscall.return_value as u32);
+                    let inherited: Vec<_> = self
+                        .open
+                        .iter()
+                        .filter(|((o, _), _)| *o == owner)
+                        .map(|((_, fd), index)| (*fd, *index))
+                        .collect();
+                    for (fd, index) in inherited {
+                        self.open.entry((child, fd)).or_insert(index);
+                    }
+                }
+            }
+            name => {
+                let io = syscalls::io_direction(name);
+                if io.is_none() || !self.open.contains_key(&(owner, sockfd)) {
+                    return;
+                }
+                let connection = self.get_or_insert(syscall.pid, owner, sockfd, time);
+                // e.g., the destination of a UDP datagram
+                if connection.remote.is_none() {
+                    connection.remote = address("dest_addr").or(address("src_addr"));
+                }
+                if syscall.return_value > 0 {
+                    let bytes = syscall.return_value as u64;
+                    match io {
+                        Some(Io::Read) => connection.bytes.read += bytes,
+                        Some(Io::Write) => connection.bytes.written += bytes,
+                        None => {}
+                    }
+                }
+                connection.record_hello(syscall);
+                connection.record_error(syscall);
+            }
+        }
+    }
+
+    /// Marks the sockets of a process that has exited as closed.
+    pub fn record_exit(&mut self, exit: &Exit, fds: &FdTable) {
+        // a thread's exit does not close its process's sockets
+        if exit.pid.is_none() || fds.owner(exit.pid) != exit.pid {
+            return;
+        }
+        let fds: Vec<i64> = self
+            .open
+            .keys()
+            .filter(|(owner, _)| *owner == exit.pid)
+            .map(|(_, fd)| *fd)
+            .collect();
+        for fd in fds {
+            self.close(exit.pid, fd, exit.time_micros, Some(CloseReason::Exited));
+        }
+    }
+
+    /// Returns the open socket that `fd` refers to in process `pid`, if any.
+    pub fn find(&self, pid: Option<u32>, fd: i64, fds: &FdTable) -> Option<&Connection> {
+        self.index(pid, fd, fds).map(|i| &self.connections[i])
+    }
+
+    /// Like `find`, but returns the socket's index into `connections`.
+    pub fn index(&self, pid: Option<u32>, fd: i64, fds: &FdTable) -> Option<usize> {
+        self.open.get(&(fds.owner(pid), fd)).copied()
+    }
+
+    fn get(&self, owner: Option<u32>, fd: i64) -> Option<&Connection> {
+        self.open.get(&(owner, fd)).map(|i| &self.connections[*i])
+    }
+
+    // sockets created before tracing began (or by an untracked syscall) are added when first used
+    fn get_or_insert(
+        &mut self,
+        pid: Option<u32>,
+        owner: Option<u32>,
+        fd: i64,
+        time: u64,
+    ) -> &mut Connection {
+        let index = match self.open.get(&(owner, fd)) {
+            Some(index) => *index,
+            None => self.open(pid, owner, fd, String::new(), time),
+        };
+        &mut self.connections[index]
+    }
+
+    fn open(
+        &mut self,
+        pid: Option<u32>,
+        owner: Option<u32>,
+        fd: i64,
+        protocol: String,
+        time: u64,
+    ) -> usize {
+        // the fd was closed without vistrace noticing, e.g. by exec or dup2
+        self.close(owner, fd, time, None);
+
+        self.connections.push(Connection {
+            pid,
+            fd,
+            protocol,
+            role: Role::Unknown,
+            local: None,
+            remote: None,
+            opened_micros: time,
+            closed_micros: None,
+            close_reason: None,
+            bytes: ByteCounts::default(),
+            tls: None,
+            error: None,
+        });
+        let index = self.connections.len() - 1;
+        self.open.insert((owner, fd), index);
+        index
+    }
+
+    fn close(&mut self, owner: Option<u32>, fd: i64, time: u64, reason: Option<CloseReason>) {
+        let index = match self.open.remove(&(owner, fd)) {
+            Some(index) => index,
+            None => return,
+        };
+        // still open in another process
+        if self.open.values().any(|i| *i == index) {
+            return;
+        }
+
+        let connection = &mut self.connections[index];
+        connection.closed_micros = Some(time);
+        connection.close_reason = Some(reason.unwrap_or_else(|| match &connection.error {
+            Some(errno) => CloseReason::Error(errno.clone()),
+            None => CloseReason::Closed,
+        }));
+    }
+}
+
+impl Connection {
+    /// Describes the connection, e.g. "tcp client 10.0.0.2:40000 -> 93.184.216.34:80".
+    pub fn describe(&self) -> String {
+        let mut text = match self.protocol.as_str() {
+            "" => self.role.to_string(),
+            protocol => format!("{} {}", protocol, self.role),
+        };
+        let endpoint = |e: &Option<Endpoint>| e.as_ref().map_or("?".to_string(), |e| e.to_string());
+        match (&self.local, &self.remote) {
+            (None, None) => {}
+            (local, None) => text.push_str(&format!(" {}", endpoint(local))),
+            (local, remote) => {
+                text.push_str(&format!(" {} -> {}", endpoint(local), endpoint(remote)))
+            }
+        }
+        // e.g., "[TLS 1.3, example.com]"
+        if let Some(tls) = &self.tls {
+            text.push_str(&format!(" [{}]", tls));
+        }
+        text
+    }
+
+    fn record_hello(&mut self, syscall: &Syscall) {
+        let text = match payloads(syscall).first() {
+            Some((text, _)) if tls::maybe_handshake(text) => *text,
+            _ => return,
+        };
+        match tls::decode_hello(&strace::unescape(text)) {
+            Some(Hello::Client { server_name }) => {
+                self.tls.get_or_insert_with(Tls::default).server_name = server_name
+            }
+            Some(Hello::Server { version }) => {
+                self.tls.get_or_insert_with(Tls::default).version = Some(version)
+            }
+            None => {}
+        }
+    }
+
+    /// How long the socket was open for, in microseconds, if it has been closed.
+    pub fn duration_micros(&self) -> Option<u64> {
+        match (self.opened_micros, self.closed_micros) {
+            (0, _) | (_, None) => None,
+            (opened, Some(closed)) => Some(closed.saturating_sub(opened)),
+        }
+    }
+
+    fn record_error(&mut self, syscall: &Syscall) {
+        // errors that only mean "not yet" for non-blocking sockets
+        match syscall.errno.as_deref() {
+            None | Some("EINPROGRESS" | "EAGAIN" | "EWOULDBLOCK" | "EINTR" | "EALREADY") => {}
+            Some(errno) => self.error = Some(errno.to_string()),
+        }
+    }
+}
+
+/// Returns the data buffers that `syscall` sent or received, e.g. one for recvfrom or one per
+/// message for recvmmsg, each with whether strace cut it off.
+pub fn payloads(syscall: &Syscall) -> Vec<(&str, bool)> {
+    let mut payloads = Vec::new();
+    for arg in &syscall.args {
+        find_payloads(&arg.value, &mut payloads);
+    }
+    payloads
+}
+
+fn find_payloads<'a>(value: &'a SyscallArgValue, payloads: &mut Vec<(&'a str, bool)>) {
+    match value {
+        SyscallArgValue::Quoted { text, truncated } => payloads.push((text, *truncated)),
+        SyscallArgValue::Array(args) => {
+            for arg in args {
+                find_payloads(&arg.value, payloads);
+            }
+        }
+        SyscallArgValue::Struct(fields) => {
+            for arg in fields.values() {
+                find_payloads(&arg.value, payloads);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Decodes a `struct sockaddr` as strace prints it, e.g.
+/// `{sa_family=AF_INET, sin_port=htons(80), sin_addr=inet_addr("93.184.216.34")}`.
+pub fn decode_sockaddr(value: &SyscallArgValue) -> Option<Endpoint> {
+    let fields = match value {
+        SyscallArgValue::Struct(fields) => fields,
+        _ => return None,
+    };
+    let family = match fields.get("sa_family").map(|a| &a.value) {
+        Some(SyscallArgValue::Symbol(family)) => family.as_str(),
+        _ => return None,
+    };
+    let field = |name: &str| fields.get(name).map(|a| &a.value);
+
+    match family {
+        "AF_INET" | "AF_INET6" => {
+            let (port, addr) = if family == "AF_INET" {
+                (field("sin_port"), field("sin_addr"))
+            } else {
+                (field("sin6_port"), field("sin6_addr"))
+            };
+            // e.g., htons(80)
+            let port = match port? {
+                SyscallArgValue::FunctionCall(_, args) => number(args.first()?)?,
+                SyscallArgValue::Number(port) => *port,
+                _ => return None,
+            };
+            // e.g., inet_addr("127.0.0.1") or inet_pton(AF_INET6, "::1", &sin6_addr)
+            let addr = match addr? {
+                SyscallArgValue::FunctionCall(_, args) => {
+                    args.iter().find_map(|a| match &a.value {
+                        SyscallArgValue::Quoted { text, .. } => text.parse().ok(),
+                        _ => None,
+                    })?
+                }
+                _ => return None,
+            };
+            Some(Endpoint::Inet(addr, u16::try_from(port).ok()?))
+        }
+        "AF_UNIX" => match field("sun_path") {
+            Some(SyscallArgValue::Quoted { text, .. }) => Some(Endpoint::Unix(text.clone())),
+            _ => Some(Endpoint::Unix(String::new())),
+        },
+        family => Some(Endpoint::Other(family.to_string())),
+    }
+}
+
+// e.g., "tcp" for socket(AF_INET, SOCK_STREAM|SOCK_CLOEXEC, IPPROTO_TCP)
+fn protocol(syscall: &Syscall) -> String {
+    let symbol = |i: usize| match syscall.arg(i).map(|a| &a.value) {
+        Some(SyscallArgValue::Symbol(s)) => Some(s.as_str()),
+        Some(SyscallArgValue::FlagSet(flags)) => match flags.first() {
+            Some(FlagSetValue::Symbol(s)) => Some(s.as_str()),
+            _ => None,
+        },
+        _ => None,
+    };
+    match (symbol(0), symbol(1)) {
+        (Some("AF_INET" | "AF_INET6"), Some("SOCK_STREAM")) => "tcp".to_string(),
+        (Some("AF_INET" | "AF_INET6"), Some("SOCK_DGRAM")) => "udp".to_string(),
+        (Some("AF_UNIX" | "AF_LOCAL"), _) => "unix".to_string(),
+        // e.g., "netlink" for AF_NETLINK
+        (Some(domain), _) => domain.trim_start_matches("AF_").to_lowercase(),
+        (None, _) => String::new(),
+    }
+}
+
+fn number(arg: &SyscallArg) -> Option<i64> {
+    match arg.value {
+        SyscallArgValue::Number(n) => Some(n),
+        _ => None,
+    }
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let role = match self {
+            Role::Unknown => "socket",
+            Role::Client => "client",
+            Role::Listener => "listener",
+            Role::Server => "server",
+        };
+        write!(f, "{}", role)
+    }
+}
+
+impl fmt::Display for Endpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Endpoint::Inet(IpAddr::V4(addr), port) => write!(f, "{}:{}", addr, port),
+            Endpoint::Inet(IpAddr::V6(addr), port) => write!(f, "[{}]:{}", addr, port),
+            Endpoint::Unix(path) if path.is_empty() => write!(f, "(unnamed)"),
+            Endpoint::Unix(path) => write!(f, "{}", path),
+            Endpoint::Other(family) => write!(f, "({})", family),
+        }
+    }
+}
+
+impl fmt::Display for CloseReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CloseReason::Closed => write!(f, "closed"),
+            CloseReason::Error(errno) => write!(f, "closed after {}", errno),
+            CloseReason::Exited => write!(f, "process exited"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fdtable::{ByteCounts, FdTable};
+    use crate::strace::{LineParser, Message};
+
+    use super::{CloseReason, Connections};
+
+    #[test]
+    fn test_connections() {
+        let mut fds = FdTable::default();
+        let mut connections = Connections::default();
+        let mut parser = LineParser::default();
+        for line in [
+            "10 1.000000 socket(AF_INET, SOCK_STREAM|SOCK_NONBLOCK, IPPROTO_TCP) = 3 <0.000001>",
+            "10 1.000001 connect(3, {sa_family=AF_INET, sin_port=htons(80), sin_addr=inet_addr(\"93.184.216.34\")}, 16) = -1 EINPROGRESS (Operation now in progress) <0.000001>",
+            "10 1.000002 getsockname(3, {sa_family=AF_INET, sin_port=htons(40000), sin_addr=inet_addr(\"10.0.0.2\")}, [128 => 16]) = 0 <0.000001>",
+            "10 1.000003 sendto(3, \"GET / HTTP/1.1\\r\\n\\r\\n\", 18, MSG_NOSIGNAL, NULL, 0) = 18 <0.000001>",
+            "10 1.000004 recvfrom(3, \"HTTP/1.1 200 OK\\r\\n\", 4096, 0, NULL, NULL) = 17 <0.000001>",
+            "10 1.000005 recvfrom(3, 0x7ffc, 4096, 0, NULL, NULL) = -1 ECONNRESET (Connection reset by peer) <0.000001>",
+            "10 1.000006 close(3) = 0 <0.000001>",
+            "10 1.000007 socket(AF_INET6, SOCK_STREAM, IPPROTO_TCP) = 3 <0.000001>",
+            "10 1.000008 bind(3, {sa_family=AF_INET6, sin6_port=htons(8080), sin6_flowinfo=htonl(0), inet_pton(AF_INET6, \"::1\", &sin6_addr), sin6_scope_id=0}, 28) = 0 <0.000001>",
+            "10 1.000009 listen(3, 128) = 0 <0.000001>",
+            "10 1.000010 accept4(3, {sa_family=AF_INET6, sin6_port=htons(50000), sin6_flowinfo=htonl(0), inet_pton(AF_INET6, \"::1\", &sin6_addr), sin6_scope_id=0}, [128 => 28], SOCK_CLOEXEC) = 4 <0.000001>",
+            "10 1.000011 clone(child_stack=NULL, flags=SIGCHLD) = 11 <0.000001>",
+            "10 1.000012 close(4) = 0 <0.000001>",
+            "11 1.000013 write(4, \"hi\", 2) = 2 <0.000001>",
+            "11 1.000014 +++ exited with 0 +++",
+        ] {
+            match parser.parse_line(line).unwrap() {
+                Message::Syscall(syscall) => {
+                    fds.update(&syscall);
+                    connections.update(&syscall, &fds);
+                }
+                Message::Exit(exit) => connections.record_exit(&exit, &fds),
+                _ => unreachable!(),
+            }
+        }
+
+        let [client, listener, server] = &connections.connections[..] else {
+            panic!("expected 3 connections");
+        };
+        assert_eq!(
+            client.describe(),
+            "tcp client 10.0.0.2:40000 -> 93.184.216.34:80"
+        );
+        assert_eq!(
+            client.bytes,
+            ByteCounts {
+                read: 17,
+                written: 18
+            }
+        );
+        assert_eq!(client.duration_micros(), Some(6));
+        assert_eq!(
+            client.close_reason,
+            Some(CloseReason::Error("ECONNRESET".to_string()))
+        );
+
+        assert_eq!(listener.describe(), "tcp listener [::1]:8080");
+        assert_eq!(listener.closed_micros, None);
+
+        // the child inherited the accepted socket, so it stayed open until the child exited
+        assert_eq!(server.describe(), "tcp server [::1]:8080 -> [::1]:50000");
+        assert_eq!(server.bytes.written, 2);
+        assert_eq!(server.close_reason, Some(CloseReason::Exited));
+    }
+
+    #[test]
+    fn test_malformed_connect() {
+        let mut fds = FdTable::default();
+        let mut connections = Connections::default();
+        let syscall = LineParser::default()
+            .parse_line("10 connect(3, {sa_family=AF_INET, sin_port=htons(80")
+            .and_then(Message::into_syscall)
+            .unwrap();
+        fds.update(&syscall);
+        connections.update(&syscall, &fds);
+        assert!(connections.connections.is_empty());
+    }
+}
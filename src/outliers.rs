@@ -0,0 +1,228 @@
+// Flags syscalls that took far longer than calls to the same syscall usually do, e.g. a 2-second
+// openat among openats that take microseconds, which usually means a slow disk, a network
+// filesystem, or contention on a lock in the kernel.
+//
+// Each syscall has a baseline of how long its calls take, as the mean and standard deviation of the
+// logarithm of their durations (since durations are heavily skewed), which is updated with every
+// call. Once the baseline has enough calls behind it, a call is an outlier if it is several
+// deviations above the mean, many times longer than usual, and long enough to notice.
+//
+// Syscalls that wait for something (poll, futex, a read from a pipe or socket, ...) can take any
+// amount of time without anything being wrong, so they are left out.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::fdtable::{FdKind, FdTable};
+use crate::strace::{format_timestamp, Syscall};
+use crate::syscalls;
+
+// calls needed before a baseline is trusted
+const MIN_CALLS: u64 = 20;
+// how many standard deviations above the mean an outlier is
+const MIN_DEVIATIONS: f64 = 4.0;
+// how many times longer than usual an outlier is, for syscalls whose durations hardly vary
+const MIN_FACTOR: f64 = 50.0;
+// how long an outlier takes, at least
+const MIN_MICROS: u64 = 10_000;
+// syscalls that wait for something, whatever their arguments
+const WAITS: &[&str] = &[
+    "poll",
+    "ppoll",
+    "select",
+    "pselect6",
+    "epoll_wait",
+    "epoll_pwait",
+    "epoll_pwait2",
+    "futex",
+    "wait4",
+    "waitid",
+    "nanosleep",
+    "clock_nanosleep",
+    "pause",
+    "accept",
+    "accept4",
+    "rt_sigsuspend",
+    "rt_sigtimedwait",
+    "io_getevents",
+    "io_uring_enter",
+];
+
+#[derive(Default)]
+pub struct Outliers {
+    baselines: HashMap<String, Baseline>,
+    pub found: Vec<Outlier>,
+}
+
+// running statistics of the log of the durations (in microseconds) of a syscall's calls
+#[derive(Default)]
+struct Baseline {
+    calls: u64,
+    mean: f64,
+    // the sum of squared differences from the mean (see Welford's algorithm)
+    m2: f64,
+}
+
+#[derive(Clone, Debug)]
+pub struct Outlier {
+    pub pid: Option<u32>,
+    // e.g., "openat "/mnt/nfs/data"" or "fsync(3)"
+    pub call: String,
+    pub micros: u64,
+    // the geometric mean of the syscall's earlier calls
+    pub typical_micros: u64,
+    pub time_micros: u64,
+}
+
+impl Outliers {
+    /// Returns the index into `found` of the outlier that `syscall` is, if it is one. Must be
+    /// called after `fds` has been updated with `syscall`.
+    pub fn update(&mut self, syscall: &Syscall, fds: &FdTable) -> Option<usize> {
+        // without a duration (e.g., if strace was not run with -T), there is nothing to go on
+        let micros = match syscall.syscall_time_micros {
+            Some(micros) if syscall.error_details.is_none() && !waits(syscall, fds) => micros,
+            _ => return None,
+        };
+        let baseline = self.baselines.entry(syscall.name.clone()).or_default();
+        let log = (micros as f64 + 1.0).ln();
+        let outlier = baseline.calls >= MIN_CALLS
+            && micros >= MIN_MICROS
+            && log > baseline.mean + MIN_DEVIATIONS * baseline.deviation()
+            && log > baseline.mean + MIN_FACTOR.ln();
+        let typical_micros = (baseline.mean.exp() - 1.0).round() as u64;
+        // an outlier is left out of the baseline, so that a run of them stays flagged
+        if !outlier {
+            baseline.add(log);
+            return None;
+        }
+
+        self.found.push(Outlier {
+            pid: syscall.pid,
+            call: describe(syscall),
+            micros,
+            typical_micros,
+            time_micros: syscall.entry_time_micros,
+        });
+        Some(self.found.len() - 1)
+    }
+}
+
+impl Baseline {
+    fn add(&mut self, x: f64) {
+        self.calls += 1;
+        let delta = x - self.mean;
+        self.mean += delta / self.calls as f64;
+        self.m2 += delta * (x - self.mean);
+    }
+
+    fn deviation(&self) -> f64 {
+        if self.calls < 2 {
+            return 0.0;
+        }
+        (self.m2 / (self.calls - 1) as f64).sqrt()
+    }
+}
+
+// whether `syscall` waits for something, so that how long it takes says little
+fn waits(syscall: &Syscall, fds: &FdTable) -> bool {
+    if WAITS.contains(&syscall.name.as_str()) {
+        return true;
+    }
+    // reading from or writing to anything but a regular file (a pipe, a socket, a terminal, ...)
+    // can block until the other side is ready
+    syscalls::fd_args(syscall).into_iter().any(|fd| {
+        !matches!(
+            fds.resolve(syscall.pid, fd, syscall.entry_time_micros).kind,
+            FdKind::File(path) if !path.starts_with("/dev/")
+        )
+    })
+}
+
+fn describe(syscall: &Syscall) -> String {
+    if let Some(path) = syscalls::path_args(syscall).first() {
+        return format!("{} \"{}\"", syscall.name, path);
+    }
+    match syscalls::fd_args(syscall).first() {
+        Some(fd) => format!("{}({})", syscall.name, fd),
+        None => syscall.name.clone(),
+    }
+}
+
+impl fmt::Display for Outlier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} took {}s (typically {} µs) at {}",
+            self.call,
+            format_timestamp(self.micros),
+            self.typical_micros,
+            format_timestamp(self.time_micros)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fdtable::FdTable;
+    use crate::strace::parse_syscall;
+
+    use super::Outliers;
+
+    #[test]
+    fn test_outliers() {
+        let mut outliers = Outliers::default();
+        let mut fds = FdTable::default();
+        let mut update = |line: &str| {
+            let syscall = parse_syscall(line, true);
+            fds.update(&syscall);
+            outliers.update(&syscall, &fds)
+        };
+
+        for i in 0..30 {
+            let line = format!(
+                "1.{:06} openat(AT_FDCWD, \"/etc/a\", O_RDONLY) = 3 <0.0000{:02}>",
+                i,
+                10 + i % 5
+            );
+            assert_eq!(update(&line), None);
+        }
+        assert_eq!(
+            update("2.000000 openat(AT_FDCWD, \"/mnt/nfs/a\", O_RDONLY) = 4 <2.000000>"),
+            Some(0)
+        );
+        // slower than usual, but not enough to notice
+        assert_eq!(
+            update("3.000000 openat(AT_FDCWD, \"/etc/a\", O_RDONLY) = 5 <0.000200>"),
+            None
+        );
+        // waiting on a pipe is not an outlier, however long it takes
+        update("4.000000 pipe2([6, 7], 0) = 0 <0.000010>");
+        for _ in 0..30 {
+            update("4.000001 read(6, \"x\", 1) = 1 <0.000010>");
+        }
+        assert_eq!(update("5.000000 read(6, \"x\", 1) = 1 <3.000000>"), None);
+
+        assert_eq!(
+            outliers.found[0].to_string(),
+            "openat \"/mnt/nfs/a\" took 2.000000s (typically 12 µs) at 2.000000"
+        );
+    }
+
+    #[test]
+    fn test_outliers_without_durations() {
+        let mut outliers = Outliers::default();
+        let mut fds = FdTable::default();
+        // calls without a duration are not a baseline of instant calls
+        for _ in 0..30 {
+            let syscall = parse_syscall("openat(AT_FDCWD, \"/etc/a\", O_RDONLY) = 3", false);
+            fds.update(&syscall);
+            assert_eq!(outliers.update(&syscall, &fds), None);
+        }
+        let syscall = parse_syscall(
+            "2.000000 openat(AT_FDCWD, \"/mnt/nfs/a\", O_RDONLY) = 4 <2.000000>",
+            true,
+        );
+        fds.update(&syscall);
+        assert_eq!(outliers.update(&syscall, &fds), None);
+    }
+}
@@ -0,0 +1,127 @@
+// Colors for events, shared by the TUI and the plain-text output so that the two look alike.
+//
+// Severity takes precedence over category: failed syscalls are red and lines that could not be
+// parsed are magenta, whatever kind of syscall they are.
+
+use clap::ValueEnum;
+
+use crate::strace::{Message, Syscall};
+use crate::syscalls;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Color {
+    Red,
+    Green,
+    Yellow,
+    Magenta,
+    Cyan,
+    Gray,
+}
+
+pub fn message_color(msg: &Message) -> Option<Color> {
+    match msg {
+        Message::Syscall(syscall) => syscall_color(syscall),
+        // the same colors as the signal and process categories
+        Message::Signal(_) => Some(Color::Yellow),
+        Message::Exit(_) => Some(Color::Green),
+        Message::Frame(_) => Some(Color::Gray),
+        Message::Skipped(_) => Some(Color::Gray),
+        Message::LibCall(_) | Message::Notice(_) => None,
+    }
+}
+
+pub fn syscall_color(syscall: &Syscall) -> Option<Color> {
+    if syscall.error_details.is_some() {
+        Some(Color::Magenta)
+    } else if syscall.errno.is_some() {
+        Some(Color::Red)
+    } else {
+        category_color(&syscall.name)
+    }
+}
+
+pub fn category_color(name: &str) -> Option<Color> {
+    if syscalls::in_category(name, "process") {
+        Some(Color::Green)
+    } else if syscalls::in_category(name, "network") {
+        Some(Color::Cyan)
+    } else if syscalls::in_category(name, "signal") {
+        Some(Color::Yellow)
+    } else if syscalls::in_category(name, "memory") {
+        // memory management is usually noise, so it is dimmed
+        Some(Color::Gray)
+    } else {
+        None
+    }
+}
+
+impl Color {
+    fn ansi(self) -> &'static str {
+        match self {
+            Color::Red => "\x1b[31m",
+            Color::Green => "\x1b[32m",
+            Color::Yellow => "\x1b[33m",
+            Color::Magenta => "\x1b[35m",
+            Color::Cyan => "\x1b[36m",
+            Color::Gray => "\x1b[90m",
+        }
+    }
+}
+
+/// Wraps `text` in ANSI escape codes for `color`, if `enabled`.
+pub fn paint(text: &str, color: Option<Color>, enabled: bool) -> String {
+    match color {
+        Some(color) if enabled => format!("{}{}\x1b[0m", color.ansi(), text),
+        _ => text.to_string(),
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum ColorChoice {
+    /// color if writing to a terminal and NO_COLOR is not set
+    Auto,
+    Always,
+    Never,
+}
+
+/// Decides whether to color output. `no_color` is whether the NO_COLOR environment variable is
+/// set to a non-empty value (see https://no-color.org), which only affects `auto`.
+pub fn use_color(choice: ColorChoice, is_terminal: bool, no_color: bool) -> bool {
+    match choice {
+        ColorChoice::Auto => is_terminal && !no_color,
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::strace::parse_syscall;
+
+    use super::{paint, syscall_color, use_color, Color, ColorChoice};
+
+    #[test]
+    fn test_syscall_color() {
+        let color = |line: &str| syscall_color(&parse_syscall(line, false));
+        assert_eq!(color("close(3) = 0"), None);
+        assert_eq!(color("connect(3, NULL, 0) = 0"), Some(Color::Cyan));
+        assert_eq!(
+            color("connect(3, NULL, 0) = -1 ECONNREFUSED (Connection refused)"),
+            Some(Color::Red)
+        );
+        assert_eq!(color("brk(NULL) = 0x55d4"), Some(Color::Gray));
+    }
+
+    #[test]
+    fn test_use_color() {
+        assert!(use_color(ColorChoice::Auto, true, false));
+        assert!(!use_color(ColorChoice::Auto, true, true));
+        assert!(!use_color(ColorChoice::Auto, false, false));
+        assert!(use_color(ColorChoice::Always, false, true));
+        assert!(!use_color(ColorChoice::Never, true, false));
+
+        assert_eq!(paint("x", Some(Color::Red), true), "\x1b[31mx\x1b[0m");
+        assert_eq!(paint("x", Some(Color::Red), false), "x");
+        assert_eq!(paint("x", None, true), "x");
+    }
+}
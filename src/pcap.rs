@@ -0,0 +1,329 @@
+// Writes the data that the traced processes sent and received over TCP and UDP sockets as a pcap
+// file, so that it can be opened in Wireshark (or tcpdump -r) and picked apart by its dissectors.
+//
+// strace only sees the payloads, so vistrace makes up the IP and TCP or UDP headers around them
+// from what it knows of each socket: the addresses and ports (a made-up port on 127.0.0.1, or ::1,
+// if it never learned the local end), and sequence numbers that count the bytes in each direction.
+// There are no handshakes, acknowledgments, or retransmissions; each read or write is one packet
+// (or several, for more data than fits in one), timestamped with when the syscall started.
+//
+// strace only prints the first 32 bytes of a buffer by default, in which case the packets are cut
+// off as if by a small snap length; pass `--strace-arg=-s65536` to capture whole payloads.
+
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use crate::fdtable::FdTable;
+use crate::net::{self, Connection, Connections, Endpoint};
+use crate::strace::{self, Message, Syscall, SyscallArgValue};
+use crate::syscalls::{self, Io};
+
+// LINKTYPE_RAW: each packet starts with an IPv4 or IPv6 header
+const LINKTYPE_RAW: u32 = 101;
+const SNAPLEN: u32 = 262144;
+const TCP: u8 = 6;
+const UDP: u8 = 17;
+// the most payload that fits in a packet along with the largest headers (IPv6 and TCP)
+const MAX_SEGMENT: usize = 65535 - 40 - 20;
+// where the made-up local ports start
+const EPHEMERAL_PORT: u16 = 32768;
+
+pub struct Capture {
+    fds: FdTable,
+    net: Connections,
+    // only sockets whose description contains this are captured
+    filter: Option<String>,
+    started: bool,
+    // the sequence number of the next byte in each direction of each TCP socket, keyed by index
+    // into `net.connections` and whether the bytes are outgoing
+    sequences: HashMap<(usize, bool), u32>,
+}
+
+// one end of a packet
+type Address = (IpAddr, u16);
+
+impl Capture {
+    pub fn new(filter: Option<String>) -> Self {
+        Self {
+            fds: FdTable::default(),
+            net: Connections::default(),
+            filter,
+            started: false,
+            sequences: HashMap::new(),
+        }
+    }
+
+    pub fn write_message(&mut self, w: &mut impl Write, msg: &Message) -> io::Result<()> {
+        self.start(w)?;
+        match msg {
+            Message::Syscall(syscall) => {
+                self.fds.update(syscall);
+                self.net.update(syscall, &self.fds);
+                self.write_syscall(w, syscall)
+            }
+            Message::Exit(exit) => {
+                self.net.record_exit(exit, &self.fds);
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Writes the file header, if there were no packets.
+    pub fn finish(&mut self, w: &mut impl Write) -> io::Result<()> {
+        self.start(w)
+    }
+
+    fn start(&mut self, w: &mut impl Write) -> io::Result<()> {
+        if !self.started {
+            w.write_all(&0xa1b2c3d4u32.to_le_bytes())?;
+            w.write_all(&2u16.to_le_bytes())?;
+            w.write_all(&4u16.to_le_bytes())?;
+            // the time zone and the accuracy of the timestamps, which are always 0
+            w.write_all(&[0; 8])?;
+            w.write_all(&SNAPLEN.to_le_bytes())?;
+            w.write_all(&LINKTYPE_RAW.to_le_bytes())?;
+            self.started = true;
+        }
+        Ok(())
+    }
+
+    fn write_syscall(&mut self, w: &mut impl Write, syscall: &Syscall) -> io::Result<()> {
+        let io = match syscalls::io_direction(&syscall.name) {
+            Some(io) if syscall.return_value > 0 && syscall.errno.is_none() => io,
+            _ => return Ok(()),
+        };
+        let fd = match syscall.arg(0).map(|a| &a.value) {
+            Some(SyscallArgValue::Number(fd)) => *fd,
+            _ => return Ok(()),
+        };
+        let index = match self.net.index(syscall.pid, fd, &self.fds) {
+            Some(index) => index,
+            None => return Ok(()),
+        };
+        let connection = &self.net.connections[index];
+        let protocol = match connection.protocol.as_str() {
+            "tcp" => TCP,
+            "udp" => UDP,
+            _ => return Ok(()),
+        };
+        if let Some(filter) = &self.filter {
+            if !connection.describe().contains(filter.as_str()) {
+                return Ok(());
+            }
+        }
+
+        let length = syscall.return_value as usize;
+        let mut data: Vec<u8> = net::payloads(syscall)
+            .into_iter()
+            .flat_map(|(text, _)| strace::unescape(text))
+            .collect();
+        data.truncate(length);
+        let outgoing = io == Io::Write;
+        let (local, remote) = addresses(connection, index);
+        let (source, destination) = if outgoing {
+            (local, remote)
+        } else {
+            (remote, local)
+        };
+
+        // a datagram is one packet, however big, whereas a stream is split into segments
+        let segment = if protocol == TCP { MAX_SEGMENT } else { length };
+        for start in (0..length).step_by(segment.max(1)) {
+            let end = (start + segment).min(length);
+            let captured = &data[start.min(data.len())..end.min(data.len())];
+            let transport = if protocol == TCP {
+                // everything that came the other way has been received
+                let acknowledged = *self.sequences.entry((index, !outgoing)).or_insert(1);
+                let sequence = self.sequences.entry((index, outgoing)).or_insert(1);
+                let header = tcp_header(source.1, destination.1, *sequence, acknowledged);
+                *sequence = sequence.wrapping_add((end - start) as u32);
+                header
+            } else {
+                udp_header(source.1, destination.1, end - start)
+            };
+            let ip = ip_header(
+                source.0,
+                destination.0,
+                protocol,
+                transport.len() + end - start,
+            );
+
+            let mut packet = ip;
+            packet.extend(transport);
+            let headers = packet.len();
+            packet.extend_from_slice(captured);
+            write_record(w, syscall.entry_time_micros, &packet, headers + end - start)?;
+        }
+        Ok(())
+    }
+}
+
+// the local and remote ends of a socket, making up whatever is unknown
+fn addresses(connection: &Connection, index: usize) -> (Address, Address) {
+    let inet = |e: &Option<Endpoint>| match e {
+        Some(Endpoint::Inet(ip, port)) => Some((*ip, *port)),
+        _ => None,
+    };
+    let remote = inet(&connection.remote);
+    let localhost = match remote {
+        Some((IpAddr::V6(_), _)) => IpAddr::V6(Ipv6Addr::LOCALHOST),
+        _ => IpAddr::V4(Ipv4Addr::LOCALHOST),
+    };
+    let local = match inet(&connection.local) {
+        // e.g., a server listening on 0.0.0.0:8080
+        Some((ip, port)) if ip.is_unspecified() => (localhost, port),
+        Some(local) => local,
+        // a different port for each socket, so that Wireshark keeps them apart
+        None => (localhost, EPHEMERAL_PORT + (index % 28000) as u16),
+    };
+    let remote = remote.unwrap_or((IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0));
+    (local, remote)
+}
+
+fn ip_header(source: IpAddr, destination: IpAddr, protocol: u8, length: usize) -> Vec<u8> {
+    match (source, destination) {
+        (IpAddr::V4(source), IpAddr::V4(destination)) => {
+            let mut header = Vec::with_capacity(20);
+            header.extend([0x45, 0]);
+            header.extend(((20 + length).min(65535) as u16).to_be_bytes());
+            // no fragmentation: an ID of 0 and the "don't fragment" flag
+            header.extend([0, 0, 0x40, 0]);
+            header.extend([64, protocol, 0, 0]);
+            header.extend(source.octets());
+            header.extend(destination.octets());
+            let checksum = checksum(&header);
+            header[10..12].copy_from_slice(&checksum.to_be_bytes());
+            header
+        }
+        // one end is IPv6, so both are written as IPv6
+        (source, destination) => {
+            let v6 = |ip: IpAddr| match ip {
+                IpAddr::V4(ip) => ip.to_ipv6_mapped(),
+                IpAddr::V6(ip) => ip,
+            };
+            let mut header = Vec::with_capacity(40);
+            header.extend([0x60, 0, 0, 0]);
+            header.extend((length.min(65535) as u16).to_be_bytes());
+            header.extend([protocol, 64]);
+            header.extend(v6(source).octets());
+            header.extend(v6(destination).octets());
+            header
+        }
+    }
+}
+
+// PSH and ACK, with the checksum left as 0, which Wireshark does not check by default
+fn tcp_header(source: u16, destination: u16, sequence: u32, acknowledged: u32) -> Vec<u8> {
+    let mut header = Vec::with_capacity(20);
+    header.extend(source.to_be_bytes());
+    header.extend(destination.to_be_bytes());
+    header.extend(sequence.to_be_bytes());
+    header.extend(acknowledged.to_be_bytes());
+    header.extend([0x50, 0x18]);
+    header.extend(u16::MAX.to_be_bytes());
+    header.extend([0, 0, 0, 0]);
+    header
+}
+
+// with the checksum left as 0, which means none for UDP over IPv4
+fn udp_header(source: u16, destination: u16, length: usize) -> Vec<u8> {
+    let mut header = Vec::with_capacity(8);
+    header.extend(source.to_be_bytes());
+    header.extend(destination.to_be_bytes());
+    header.extend(((8 + length).min(65535) as u16).to_be_bytes());
+    header.extend([0, 0]);
+    header
+}
+
+// the Internet checksum of an IPv4 header
+fn checksum(header: &[u8]) -> u16 {
+    let mut sum: u32 = header
+        .chunks(2)
+        .map(|pair| u32::from(u16::from_be_bytes([pair[0], pair[1]])))
+        .sum();
+    while sum > 0xffff {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+// `original` is the length of the packet before strace cut off the payload
+fn write_record(
+    w: &mut impl Write,
+    time_micros: u64,
+    packet: &[u8],
+    original: usize,
+) -> io::Result<()> {
+    w.write_all(&((time_micros / 1_000_000) as u32).to_le_bytes())?;
+    w.write_all(&((time_micros % 1_000_000) as u32).to_le_bytes())?;
+    w.write_all(&(packet.len() as u32).to_le_bytes())?;
+    w.write_all(&(original as u32).to_le_bytes())?;
+    w.write_all(packet)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::strace::LineParser;
+
+    use super::{checksum, Capture};
+
+    #[test]
+    fn test_capture() {
+        let lines = [
+            "10 1.000000 socket(AF_INET, SOCK_STREAM, IPPROTO_TCP) = 3 <0.000010>",
+            "10 1.000100 connect(3, {sa_family=AF_INET, sin_port=htons(80), sin_addr=inet_addr(\"93.184.216.34\")}, 16) = 0 <0.000100>",
+            "10 1.000200 sendto(3, \"GET / HTTP/1.1\\r\\n\\r\\n\", 18, 0, NULL, 0) = 18 <0.000010>",
+            "10 1.000300 recvfrom(3, \"HTTP/1.1 200\"..., 4096, 0, NULL, NULL) = 100 <0.000010>",
+            "10 1.000400 socket(AF_INET, SOCK_DGRAM, IPPROTO_UDP) = 4 <0.000010>",
+            "10 1.000500 sendto(4, \"x\", 1, 0, {sa_family=AF_INET, sin_port=htons(53), sin_addr=inet_addr(\"10.0.0.1\")}, 16) = 1 <0.000010>",
+        ];
+        let capture = |filter: Option<&str>| {
+            let mut capture = Capture::new(filter.map(str::to_string));
+            let mut parser = LineParser::default();
+            let mut out = Vec::new();
+            for line in lines {
+                capture
+                    .write_message(&mut out, &parser.parse_line(line).unwrap())
+                    .unwrap();
+            }
+            capture.finish(&mut out).unwrap();
+            out
+        };
+
+        let out = capture(None);
+        assert_eq!(&out[..4], &[0xd4, 0xc3, 0xb2, 0xa1]);
+        // each record is its header and then the packet
+        let mut records = Vec::new();
+        let mut rest = &out[24..];
+        while !rest.is_empty() {
+            let included = u32::from_le_bytes(rest[8..12].try_into().unwrap()) as usize;
+            let original = u32::from_le_bytes(rest[12..16].try_into().unwrap()) as usize;
+            records.push((&rest[16..16 + included], original));
+            rest = &rest[16 + included..];
+        }
+        assert_eq!(records.len(), 3);
+
+        let (request, original) = records[0];
+        assert_eq!(original, 20 + 20 + 18);
+        assert_eq!(checksum(&request[..20]), 0);
+        assert_eq!(&request[12..16], &[127, 0, 0, 1]);
+        assert_eq!(&request[16..20], &[93, 184, 216, 34]);
+        assert_eq!(&request[22..24], &80u16.to_be_bytes());
+        assert_eq!(&request[40..], b"GET / HTTP/1.1\r\n\r\n");
+
+        // cut off by strace, and acknowledging the request
+        let (response, original) = records[1];
+        assert_eq!(original, 20 + 20 + 100);
+        assert_eq!(&response[40..], b"HTTP/1.1 200");
+        assert_eq!(&response[24..28], &1u32.to_be_bytes());
+        assert_eq!(&response[28..32], &19u32.to_be_bytes());
+
+        let (datagram, _) = records[2];
+        assert_eq!(datagram[9], 17);
+        assert_eq!(&datagram[20..], &[0x80, 0x01, 0, 53, 0, 9, 0, 0, b'x']);
+
+        assert_eq!(capture(Some("udp")).len(), 24 + 16 + 20 + 8 + 1);
+    }
+}
@@ -0,0 +1,273 @@
+// Works out which traced processes talk to each other, over pipes, socketpairs, and unix sockets,
+// and how much each one sent and received.
+//
+// A channel has two ends, each of which is an open file description that may be shared by many
+// processes (e.g., a pipe created by a shell and inherited by both sides of `a | b`). Only the
+// processes that actually read or wrote through an end count as using it.
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fmt;
+
+use crate::fdtable::{ByteCounts, FdTable};
+use crate::net::{Connections, Endpoint};
+use crate::strace::{Syscall, SyscallArgValue};
+use crate::syscalls::{self, Io};
+
+#[derive(Default)]
+pub struct Channels {
+    pub channels: Vec<Channel>,
+    // the channel and end (0 or 1) of each open file description
+    ends: HashMap<u64, (usize, usize)>,
+    // the descriptions of unix sockets that connected to (or were accepted on) a path, waiting for
+    // the other side, since strace may print the connect and the accept in either order
+    connected: HashMap<String, VecDeque<u64>>,
+    accepted: HashMap<String, VecDeque<u64>>,
+}
+
+pub struct Channel {
+    pub kind: ChannelKind,
+    // the bytes read and written through each end, by process
+    pub ends: [BTreeMap<u32, ByteCounts>; 2],
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ChannelKind {
+    Pipe,
+    SocketPair,
+    // the path that the server listened on
+    Unix(String),
+}
+
+/// Bytes that one process read or wrote through an end of a channel.
+pub struct Transfer {
+    pub pid: u32,
+    // indices into `Channels::channels` and `Channel::ends`
+    pub channel: usize,
+    pub end: usize,
+    pub io: Io,
+    pub bytes: u64,
+}
+
+/// The other side of a channel, from the point of view of one process.
+pub struct Peer<'a> {
+    pub pid: u32,
+    pub kind: &'a ChannelKind,
+    // through this process's end, so if several processes share the other end, each one is shown
+    // with the same counts
+    pub bytes: ByteCounts,
+}
+
+impl Channels {
+    /// Must be called after `fds` and `net` have been updated with `syscall`. Returns the bytes
+    /// that were read or written, if `syscall` used a channel.
+    pub fn update(
+        &mut self,
+        syscall: &Syscall,
+        fds: &FdTable,
+        net: &Connections,
+    ) -> Option<Transfer> {
+        if syscall.errno.is_some() || syscall.error_details.is_some() {
+            return None;
+        }
+        let time = syscall.entry_time_micros;
+        let description = |fd: i64| fds.resolve(syscall.pid, fd, time).description;
+        let sockfd = match syscall.arg(0).map(|a| &a.value) {
+            Some(SyscallArgValue::Number(fd)) => *fd,
+            _ => -1,
+        };
+        let unix_path = |endpoint: Option<&Endpoint>| match endpoint {
+            Some(Endpoint::Unix(path)) if !path.is_empty() => Some(path.clone()),
+            _ => None,
+        };
+
+        match syscall.name.as_str() {
+            "pipe" | "pipe2" | "socketpair" => {
+                let kind = if syscall.name == "socketpair" {
+                    ChannelKind::SocketPair
+                } else {
+                    ChannelKind::Pipe
+                };
+                if let [a, b] = syscalls::created_fds(syscall)[..] {
+                    self.link(kind, description(a), description(b));
+                }
+            }
+            "connect" => {
+                let connection = net.find(syscall.pid, sockfd, fds);
+                if let Some(path) = unix_path(connection.and_then(|c| c.remote.as_ref())) {
+                    let client = description(sockfd);
+                    match self.accepted.get_mut(&path).and_then(|q| q.pop_front()) {
+                        Some(server) => self.link(ChannelKind::Unix(path), client, server),
+                        None => self.connected.entry(path).or_default().push_back(client),
+                    }
+                }
+            }
+            "accept" | "accept4" => {
+                let connection = net.find(syscall.pid, syscall.return_value, fds);
+                if let Some(path) = unix_path(connection.and_then(|c| c.local.as_ref())) {
+                    let server = description(syscall.return_value);
+                    match self.connected.get_mut(&path).and_then(|q| q.pop_front()) {
+                        Some(client) => self.link(ChannelKind::Unix(path), client, server),
+                        None => self.accepted.entry(path).or_default().push_back(server),
+                    }
+                }
+            }
+            name => {
+                let (io, pid) = match (syscalls::io_direction(name), syscall.pid) {
+                    (Some(io), Some(pid)) if syscall.return_value > 0 => (io, pid),
+                    _ => return None,
+                };
+                let (channel, end) = *self.ends.get(&description(sockfd))?;
+                let bytes = self.channels[channel].ends[end].entry(pid).or_default();
+                let count = syscall.return_value as u64;
+                match io {
+                    Io::Read => bytes.read += count,
+                    Io::Write => bytes.written += count,
+                }
+                return Some(Transfer {
+                    pid,
+                    channel,
+                    end,
+                    io,
+                    bytes: count,
+                });
+            }
+        }
+        None
+    }
+
+    /// Returns the processes that `pid` talked to.
+    pub fn peers(&self, pid: u32) -> Vec<Peer<'_>> {
+        let mut peers = Vec::new();
+        for channel in &self.channels {
+            for end in 0..2 {
+                let bytes = match channel.ends[end].get(&pid) {
+                    Some(bytes) => *bytes,
+                    None => continue,
+                };
+                for other in channel.ends[1 - end].keys().filter(|p| **p != pid) {
+                    peers.push(Peer {
+                        pid: *other,
+                        kind: &channel.kind,
+                        bytes,
+                    });
+                }
+            }
+        }
+        peers
+    }
+
+    fn link(&mut self, kind: ChannelKind, a: u64, b: u64) {
+        // the description of an fd that vistrace did not see opened
+        if a == 0 || b == 0 {
+            return;
+        }
+        self.channels.push(Channel {
+            kind,
+            ends: Default::default(),
+        });
+        let channel = self.channels.len() - 1;
+        self.ends.insert(a, (channel, 0));
+        self.ends.insert(b, (channel, 1));
+    }
+}
+
+impl Peer<'_> {
+    /// Which way the data went: "→" if this process only sent, "←" if it only received, and "↔"
+    /// otherwise.
+    pub fn arrow(&self) -> &'static str {
+        match (self.bytes.written > 0, self.bytes.read > 0) {
+            (true, false) => "→",
+            (false, true) => "←",
+            _ => "↔",
+        }
+    }
+}
+
+impl fmt::Display for ChannelKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChannelKind::Pipe => write!(f, "pipe"),
+            ChannelKind::SocketPair => write!(f, "socketpair"),
+            ChannelKind::Unix(path) => write!(f, "unix socket {}", path),
+        }
+    }
+}
+
+impl fmt::Display for Peer<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "over {}: sent {} bytes, received {}",
+            self.kind, self.bytes.written, self.bytes.read
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fdtable::FdTable;
+    use crate::net::Connections;
+    use crate::strace::{LineParser, Message};
+
+    use super::Channels;
+
+    #[test]
+    fn test_peers() {
+        let mut fds = FdTable::default();
+        let mut net = Connections::default();
+        let mut channels = Channels::default();
+        let mut parser = LineParser::default();
+        for line in [
+            // a shell runs `echo hi | cat`
+            "10 pipe2([3, 4], O_CLOEXEC) = 0",
+            "10 clone(child_stack=NULL, flags=SIGCHLD) = 11",
+            "10 clone(child_stack=NULL, flags=SIGCHLD) = 12",
+            "10 close(3) = 0",
+            "10 close(4) = 0",
+            "11 dup2(4, 1) = 1",
+            "11 write(1, \"hi\\n\", 3) = 3",
+            "12 dup2(3, 0) = 0",
+            "12 read(0, \"hi\\n\", 4096) = 3",
+            // 12 then talks to a server, 13, which accepted before 12's connect returned
+            "13 socket(AF_UNIX, SOCK_STREAM, 0) = 5",
+            "13 bind(5, {sa_family=AF_UNIX, sun_path=\"/run/x.sock\"}, 110) = 0",
+            "13 listen(5, 128) = 0",
+            "13 accept4(5, NULL, NULL, SOCK_CLOEXEC) = 6",
+            "12 socket(AF_UNIX, SOCK_STREAM|SOCK_CLOEXEC, 0) = 7",
+            "12 connect(7, {sa_family=AF_UNIX, sun_path=\"/run/x.sock\"}, 110) = 0",
+            "12 sendto(7, \"ping\", 4, 0, NULL, 0) = 4",
+            "13 recvfrom(6, \"ping\", 4096, 0, NULL, NULL) = 4",
+            "13 write(6, \"pong!\", 5) = 5",
+        ] {
+            let syscall = parser
+                .parse_line(line)
+                .and_then(Message::into_syscall)
+                .unwrap();
+            fds.update(&syscall);
+            net.update(&syscall, &fds);
+            channels.update(&syscall, &fds, &net);
+        }
+
+        let peers = |pid: u32| {
+            channels
+                .peers(pid)
+                .iter()
+                .map(|p| format!("{} {} {}", p.arrow(), p.pid, p))
+                .collect::<Vec<_>>()
+        };
+        // the shell created the pipe, but did not use it
+        assert!(peers(10).is_empty());
+        assert_eq!(peers(11), ["→ 12 over pipe: sent 3 bytes, received 0"]);
+        assert_eq!(
+            peers(12),
+            [
+                "← 11 over pipe: sent 0 bytes, received 3",
+                "→ 13 over unix socket /run/x.sock: sent 4 bytes, received 0",
+            ]
+        );
+        assert_eq!(
+            peers(13),
+            ["↔ 12 over unix socket /run/x.sock: sent 5 bytes, received 4"]
+        );
+    }
+}
@@ -0,0 +1,475 @@
+// Suggests a sandbox for the traced program from the files it used and the sockets it opened:
+// either an AppArmor profile or a list of Landlock rules. Both allow only what the traced run did,
+// so they are a starting point to review and widen (e.g., with globs), not a finished policy.
+//
+// Landlock has no file format of its own, since a program applies its rules through syscalls, so
+// the rules are written one per line, for translating into code or a sandboxing tool's options:
+//
+//   path /etc/hosts READ_FILE
+//   path /tmp MAKE_REG REMOVE_FILE
+//   port 443 CONNECT_TCP
+//
+// where the rights are the LANDLOCK_ACCESS_FS_* and LANDLOCK_ACCESS_NET_* flags, minus the prefix.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{self, Write};
+
+use crate::fdtable::{FdInfo, FdKind, FdTable};
+use crate::net::{Connections, Endpoint, Role};
+use crate::strace::{FlagSetValue, Message, Syscall, SyscallArgValue};
+use crate::syscalls;
+
+#[derive(Default)]
+pub struct Policy {
+    // the first program that was exec'd
+    program: Option<String>,
+    paths: BTreeMap<String, Access>,
+    // e.g., ("inet", "stream"), in AppArmor's terms
+    sockets: BTreeSet<(String, String)>,
+    fds: FdTable,
+    net: Connections,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+struct Access {
+    read: bool,
+    write: bool,
+    truncate: bool,
+    execute: bool,
+    // mapped as executable, e.g. a shared library
+    map: bool,
+    // read as a directory
+    list: bool,
+    create: bool,
+    remove: bool,
+    make_dir: bool,
+    remove_dir: bool,
+}
+
+impl Policy {
+    pub fn update(&mut self, msg: &Message) {
+        match msg {
+            Message::Syscall(syscall) => {
+                // paths are resolved against directory fds before `fds` forgets them
+                if syscall.errno.is_none() && syscall.error_details.is_none() {
+                    self.update_syscall(syscall);
+                }
+                self.fds.update(syscall);
+                self.net.update(syscall, &self.fds);
+            }
+            Message::Exit(exit) => self.net.record_exit(exit, &self.fds),
+            Message::Signal(_)
+            | Message::Frame(_)
+            | Message::LibCall(_)
+            | Message::Skipped(_)
+            | Message::Notice(_) => {}
+        }
+    }
+
+    fn update_syscall(&mut self, syscall: &Syscall) {
+        let path = |name: &str, dirfd: &str| self.path_arg(syscall, name, dirfd);
+        let flag = |name: &str, flag: &str| {
+            syscalls::arg_index(&syscall.name, name)
+                .and_then(|i| syscall.arg(i))
+                .is_some_and(|a| syscalls::value_has_flag(&a.value, flag))
+        };
+
+        let mut grants: Vec<(Option<String>, Access)> = Vec::new();
+        match syscall.name.as_str() {
+            "open" | "openat" | "creat" => {
+                let write = flag("flags", "O_WRONLY") || syscall.name == "creat";
+                let read_write = flag("flags", "O_RDWR");
+                let access = Access {
+                    read: !write,
+                    write: write || read_write,
+                    truncate: flag("flags", "O_TRUNC") || syscall.name == "creat",
+                    list: flag("flags", "O_DIRECTORY"),
+                    create: flag("flags", "O_CREAT") || syscall.name == "creat",
+                    ..Access::default()
+                };
+                grants.push((path("pathname", "dirfd"), access));
+            }
+            "execve" | "execveat" => {
+                let program = path("pathname", "dirfd");
+                if self.program.is_none() {
+                    self.program = program.clone();
+                }
+                grants.push((program, Access::execute()));
+            }
+            "mmap" if flag("prot", "PROT_EXEC") => {
+                let file = match syscall.arg(4).map(|a| &a.value) {
+                    Some(SyscallArgValue::Number(fd)) => {
+                        match self
+                            .fds
+                            .resolve(syscall.pid, *fd, syscall.entry_time_micros)
+                        {
+                            FdInfo {
+                                kind: FdKind::File(path),
+                                ..
+                            } => Some(path),
+                            _ => None,
+                        }
+                    }
+                    _ => None,
+                };
+                let access = Access {
+                    map: true,
+                    ..Access::default()
+                };
+                grants.push((file, access));
+            }
+            "unlink" | "unlinkat" if flag("flags", "AT_REMOVEDIR") => {
+                grants.push((path("pathname", "dirfd"), Access::remove_dir()));
+            }
+            "unlink" | "unlinkat" => {
+                let access = Access {
+                    remove: true,
+                    ..Access::default()
+                };
+                grants.push((path("pathname", "dirfd"), access));
+            }
+            "rmdir" => grants.push((path("pathname", "dirfd"), Access::remove_dir())),
+            "mkdir" | "mkdirat" => {
+                let access = Access {
+                    make_dir: true,
+                    ..Access::default()
+                };
+                grants.push((path("pathname", "dirfd"), access));
+            }
+            "rename" | "renameat" | "renameat2" => {
+                let removed = Access {
+                    remove: true,
+                    ..Access::default()
+                };
+                let created = Access {
+                    create: true,
+                    ..Access::default()
+                };
+                grants.push((path("oldpath", "olddirfd"), removed));
+                grants.push((path("newpath", "newdirfd"), created));
+            }
+            "socket" | "socketpair" => {
+                let symbol = |i: usize| match syscall.arg(i).map(|a| &a.value) {
+                    Some(SyscallArgValue::Symbol(s)) => Some(s.clone()),
+                    Some(SyscallArgValue::FlagSet(flags)) => flags.iter().find_map(|f| match f {
+                        FlagSetValue::Symbol(s) if s.starts_with("SOCK_") => Some(s.clone()),
+                        _ => None,
+                    }),
+                    _ => None,
+                };
+                if let (Some(domain), Some(socket_type)) = (symbol(0), symbol(1)) {
+                    self.sockets.insert((
+                        domain.trim_start_matches("AF_").to_lowercase(),
+                        socket_type.trim_start_matches("SOCK_").to_lowercase(),
+                    ));
+                }
+            }
+            _ => {}
+        }
+
+        for (path, access) in grants {
+            if let Some(path) = path {
+                self.paths.entry(path).or_default().add(access);
+            }
+        }
+    }
+
+    // the path in argument `name`, joined to the directory in argument `dirfd` if it is relative
+    fn path_arg(&self, syscall: &Syscall, name: &str, dirfd: &str) -> Option<String> {
+        let arg = |name: &str| {
+            syscalls::arg_index(&syscall.name, name)
+                .and_then(|i| syscall.arg(i))
+                .map(|a| &a.value)
+        };
+        let path = match arg(name)? {
+            SyscallArgValue::Quoted { text, .. } => text.clone(),
+            _ => return None,
+        };
+        if path.starts_with('/') {
+            return Some(path);
+        }
+        match arg(dirfd) {
+            Some(SyscallArgValue::Number(fd)) => {
+                match self
+                    .fds
+                    .resolve(syscall.pid, *fd, syscall.entry_time_micros)
+                {
+                    FdInfo {
+                        kind: FdKind::File(dir),
+                        ..
+                    } => Some(format!("{}/{}", dir, path)),
+                    _ => Some(path),
+                }
+            }
+            _ => Some(path),
+        }
+    }
+
+    pub fn write_apparmor(&self, w: &mut impl Write) -> io::Result<()> {
+        let program = self.program.as_deref().unwrap_or("/path/to/program");
+        let name = program.rsplit('/').next().unwrap_or(program);
+        writeln!(
+            w,
+            "# AppArmor profile generated by vistrace from a trace of {}.",
+            program
+        )?;
+        writeln!(
+            w,
+            "# It allows only what the traced run did, so review it before use."
+        )?;
+        writeln!(w, "abi <abi/3.0>,")?;
+        writeln!(w)?;
+        writeln!(w, "include <tunables/global>")?;
+        writeln!(w)?;
+        writeln!(w, "profile {} {} {{", name, program)?;
+        writeln!(w, "  include <abstractions/base>")?;
+
+        if !self.sockets.is_empty() {
+            writeln!(w)?;
+        }
+        for (domain, socket_type) in &self.sockets {
+            writeln!(w, "  network {} {},", domain, socket_type)?;
+        }
+
+        let (absolute, relative): (Vec<_>, Vec<_>) = self
+            .paths
+            .iter()
+            .partition(|(path, _)| path.starts_with('/'));
+        if !absolute.is_empty() {
+            writeln!(w)?;
+        }
+        for (path, access) in absolute {
+            writeln!(
+                w,
+                "  {} {},",
+                apparmor_path(path, access),
+                access.apparmor()
+            )?;
+        }
+        if !relative.is_empty() {
+            writeln!(w)?;
+            writeln!(
+                w,
+                "  # relative to the working directory, which vistrace does not know"
+            )?;
+        }
+        for (path, access) in relative {
+            writeln!(
+                w,
+                "  # {} {},",
+                apparmor_path(path, access),
+                access.apparmor()
+            )?;
+        }
+        writeln!(w, "}}")
+    }
+
+    pub fn write_landlock(&self, w: &mut impl Write) -> io::Result<()> {
+        let mut rules: BTreeMap<&str, BTreeSet<&str>> = BTreeMap::new();
+        for (path, access) in &self.paths {
+            // creating and removing are rights on the directory that holds the entry
+            let parent = match path.rsplit_once('/') {
+                Some(("", _)) => "/",
+                Some((parent, _)) => parent,
+                None => ".",
+            };
+            for (right, on_parent) in access.landlock() {
+                let target = if on_parent { parent } else { path.as_str() };
+                rules.entry(target).or_default().insert(right);
+            }
+        }
+
+        writeln!(
+            w,
+            "# Landlock rules generated by vistrace (see src/policy.rs for the format)."
+        )?;
+        writeln!(
+            w,
+            "# They allow only what the traced run did, so review them before use."
+        )?;
+        for (path, rights) in &rules {
+            let rights: Vec<&str> = rights.iter().copied().collect();
+            writeln!(w, "path {} {}", path, rights.join(" "))?;
+        }
+
+        // Landlock can only restrict TCP ports
+        let mut ports: BTreeMap<u16, BTreeSet<&str>> = BTreeMap::new();
+        for connection in self.net.connections.iter().filter(|c| c.protocol == "tcp") {
+            let (endpoint, right) = match connection.role {
+                Role::Client => (&connection.remote, "CONNECT_TCP"),
+                Role::Listener => (&connection.local, "BIND_TCP"),
+                Role::Unknown | Role::Server => continue,
+            };
+            if let Some(Endpoint::Inet(_, port)) = endpoint {
+                ports.entry(*port).or_default().insert(right);
+            }
+        }
+        for (port, rights) in &ports {
+            let rights: Vec<&str> = rights.iter().copied().collect();
+            writeln!(w, "port {} {}", port, rights.join(" "))?;
+        }
+        Ok(())
+    }
+}
+
+impl Access {
+    fn execute() -> Access {
+        Access {
+            execute: true,
+            ..Access::default()
+        }
+    }
+
+    fn remove_dir() -> Access {
+        Access {
+            remove_dir: true,
+            ..Access::default()
+        }
+    }
+
+    fn add(&mut self, other: Access) {
+        self.read |= other.read;
+        self.write |= other.write;
+        self.truncate |= other.truncate;
+        self.execute |= other.execute;
+        self.map |= other.map;
+        self.list |= other.list;
+        self.create |= other.create;
+        self.remove |= other.remove;
+        self.make_dir |= other.make_dir;
+        self.remove_dir |= other.remove_dir;
+    }
+
+    fn is_dir(&self) -> bool {
+        self.list || self.make_dir || self.remove_dir
+    }
+
+    // e.g., "rw" or "mr"
+    fn apparmor(&self) -> String {
+        let mut mode = String::new();
+        if self.map {
+            mode.push('m');
+        }
+        if self.read || self.list || self.map {
+            mode.push('r');
+        }
+        if self.write || self.create || self.remove || self.make_dir || self.remove_dir {
+            mode.push('w');
+        }
+        if self.execute {
+            // run the program under this same profile
+            mode.push_str("ix");
+        }
+        mode
+    }
+
+    // each right, and whether it applies to the parent directory rather than the path itself
+    fn landlock(&self) -> Vec<(&'static str, bool)> {
+        let mut rights = Vec::new();
+        if self.read || self.map {
+            rights.push(("READ_FILE", false));
+        }
+        if self.write {
+            rights.push(("WRITE_FILE", false));
+        }
+        if self.truncate {
+            rights.push(("TRUNCATE", false));
+        }
+        if self.execute {
+            rights.push(("EXECUTE", false));
+        }
+        if self.list {
+            rights.push(("READ_DIR", false));
+        }
+        if self.create {
+            rights.push(("MAKE_REG", true));
+        }
+        if self.remove {
+            rights.push(("REMOVE_FILE", true));
+        }
+        if self.make_dir {
+            rights.push(("MAKE_DIR", true));
+        }
+        if self.remove_dir {
+            rights.push(("REMOVE_DIR", true));
+        }
+        rights
+    }
+}
+
+// AppArmor matches directories with a trailing slash
+fn apparmor_path(path: &str, access: &Access) -> String {
+    if access.is_dir() && !path.ends_with('/') {
+        format!("{}/", path)
+    } else {
+        path.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::strace::LineParser;
+
+    use super::Policy;
+
+    #[test]
+    fn test_policy() {
+        let mut policy = Policy::default();
+        let mut parser = LineParser::default();
+        for line in [
+            "execve(\"/usr/bin/app\", [\"app\"], 0x7ffc /* 1 vars */) = 0",
+            "openat(AT_FDCWD, \"/usr/lib/libc.so.6\", O_RDONLY|O_CLOEXEC) = 3",
+            "mmap(NULL, 1000, PROT_READ|PROT_EXEC, MAP_PRIVATE|MAP_DENYWRITE, 3, 0) = 0x7f00",
+            "close(3) = 0",
+            "openat(AT_FDCWD, \"/var/lib/app\", O_RDONLY|O_DIRECTORY) = 3",
+            "openat(3, \"state\", O_WRONLY|O_CREAT|O_TRUNC, 0644) = 4",
+            "unlinkat(3, \"old\", 0) = 0",
+            "openat(AT_FDCWD, \"/etc/shadow\", O_RDONLY) = -1 EACCES (Permission denied)",
+            "openat(AT_FDCWD, \"notes.txt\", O_RDONLY) = 5",
+            "socket(AF_INET, SOCK_STREAM|SOCK_CLOEXEC, IPPROTO_TCP) = 6",
+            "connect(6, {sa_family=AF_INET, sin_port=htons(443), sin_addr=inet_addr(\"1.2.3.4\")}, 16) = 0",
+        ] {
+            policy.update(&parser.parse_line(line).unwrap());
+        }
+
+        let mut apparmor = Vec::new();
+        policy.write_apparmor(&mut apparmor).unwrap();
+        let apparmor = String::from_utf8(apparmor).unwrap();
+        let rules = &apparmor[apparmor.find("\nprofile").unwrap() + 1..];
+        assert_eq!(
+            rules,
+            "\
+profile app /usr/bin/app {
+  include <abstractions/base>
+
+  network inet stream,
+
+  /usr/bin/app ix,
+  /usr/lib/libc.so.6 mr,
+  /var/lib/app/ r,
+  /var/lib/app/old w,
+  /var/lib/app/state w,
+
+  # relative to the working directory, which vistrace does not know
+  # notes.txt r,
+}
+"
+        );
+
+        let mut landlock = Vec::new();
+        policy.write_landlock(&mut landlock).unwrap();
+        let landlock = String::from_utf8(landlock).unwrap();
+        let rules: Vec<&str> = landlock.lines().filter(|l| !l.starts_with('#')).collect();
+        assert_eq!(
+            rules,
+            [
+                "path /usr/bin/app EXECUTE",
+                "path /usr/lib/libc.so.6 READ_FILE",
+                "path /var/lib/app MAKE_REG READ_DIR READ_FILE REMOVE_FILE",
+                "path /var/lib/app/state TRUNCATE WRITE_FILE",
+                "path notes.txt READ_FILE",
+                "port 443 CONNECT_TCP",
+            ]
+        );
+    }
+}
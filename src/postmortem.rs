@@ -0,0 +1,181 @@
+// Explains why the traced program failed, if it did: when the first process exits with a non-zero
+// status or is killed, collects what most likely led up to it, namely the last syscalls that
+// failed (in any process), what was written to stderr, and the last signal that the process got.
+
+use std::collections::{HashMap, VecDeque};
+
+use crate::fdtable::{FdKind, FdTable};
+use crate::net;
+use crate::strace::{self, Exit, ExitKind, Signal, Syscall, SyscallArgValue};
+use crate::syscalls::{self, Io};
+
+const FAILURES: usize = 10;
+const STDERR_WRITES: usize = 5;
+
+#[derive(Default)]
+pub struct PostMortem {
+    // the first process in the trace
+    tracee: Option<u32>,
+    // the most recent failed syscalls, with the files that their fds referred to
+    failures: VecDeque<String>,
+    // the most recent writes to stderr, by any process
+    stderr: VecDeque<String>,
+    signals: HashMap<Option<u32>, String>,
+    pub report: Option<Report>,
+}
+
+pub struct Report {
+    pub pid: u32,
+    // e.g., "exited with 1" or "killed by SIGSEGV (core dumped)"
+    pub status: String,
+    pub last_signal: Option<String>,
+    pub failures: Vec<String>,
+    pub stderr: Vec<String>,
+}
+
+impl PostMortem {
+    /// Must be called after `fds` has been updated with `syscall`.
+    pub fn update(&mut self, syscall: &Syscall, fds: &FdTable) {
+        if self.tracee.is_none() {
+            self.tracee = syscall.pid;
+        }
+
+        if syscall.errno.is_some() {
+            let mut text = syscall.to_string();
+            for fd in syscalls::fd_args(syscall) {
+                let info = fds.resolve(syscall.pid, fd, syscall.entry_time_micros);
+                if info.kind != FdKind::Unknown {
+                    text.push_str(&format!("  (fd {}: {})", fd, info.kind));
+                }
+            }
+            push_bounded(&mut self.failures, text, FAILURES);
+            return;
+        }
+
+        let to_stderr = matches!(
+            syscall.arg(0).map(|a| &a.value),
+            Some(SyscallArgValue::Number(2))
+        );
+        if to_stderr && syscalls::io_direction(&syscall.name) == Some(Io::Write) {
+            let mut text = Vec::new();
+            for (payload, _) in net::payloads(syscall) {
+                text.extend(strace::unescape(payload));
+            }
+            let text = String::from_utf8_lossy(&text).trim_end().to_string();
+            if !text.is_empty() {
+                let text = match syscall.pid {
+                    Some(pid) => format!("{}: {}", pid, text),
+                    None => text,
+                };
+                push_bounded(&mut self.stderr, text, STDERR_WRITES);
+            }
+        }
+    }
+
+    pub fn record_signal(&mut self, signal: &Signal) {
+        let mut text = signal.name.clone();
+        if !signal.info.is_empty() {
+            text.push_str(&format!(
+                " {}",
+                SyscallArgValue::Struct(signal.info.clone())
+            ));
+        }
+        self.signals.insert(signal.pid, text);
+    }
+
+    /// Returns whether `exit` was the traced program failing, in which case `report` is now set.
+    pub fn record_exit(&mut self, exit: &Exit) -> bool {
+        let pid = match exit.pid {
+            Some(pid) if exit.pid == self.tracee => pid,
+            _ => return false,
+        };
+        if let ExitKind::Exited(0) = exit.status {
+            return false;
+        }
+        self.report = Some(Report {
+            pid,
+            status: exit.status.to_string(),
+            last_signal: self.signals.get(&exit.pid).cloned(),
+            failures: self.failures.iter().cloned().collect(),
+            stderr: self.stderr.iter().cloned().collect(),
+        });
+        true
+    }
+}
+
+impl Report {
+    /// Describes the failure, with `label` naming the process.
+    pub fn lines(&self, label: &str) -> Vec<String> {
+        let mut lines = vec![format!("{} {}", label, self.status)];
+        if let Some(signal) = &self.last_signal {
+            lines.push(format!("last signal: {}", signal));
+        }
+        if !self.failures.is_empty() {
+            lines.push("last failed syscalls:".to_string());
+            lines.extend(self.failures.iter().map(|f| format!("  {}", f)));
+        }
+        if !self.stderr.is_empty() {
+            lines.push("last written to stderr:".to_string());
+            for text in &self.stderr {
+                lines.extend(text.lines().map(|l| format!("  {}", l)));
+            }
+        }
+        lines
+    }
+}
+
+fn push_bounded(queue: &mut VecDeque<String>, text: String, limit: usize) {
+    if queue.len() == limit {
+        queue.pop_front();
+    }
+    queue.push_back(text);
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fdtable::FdTable;
+    use crate::strace::{LineParser, Message};
+
+    use super::PostMortem;
+
+    #[test]
+    fn test_postmortem() {
+        let mut postmortem = PostMortem::default();
+        let mut fds = FdTable::default();
+        let mut parser = LineParser::default();
+        let mut failed = false;
+        for line in [
+            "10 openat(AT_FDCWD, \"/etc/app.conf\", O_RDONLY) = 3",
+            "10 read(3, 0x7ffc1000, 4096) = -1 EISDIR (Is a directory)",
+            "10 clone(child_stack=NULL, flags=SIGCHLD) = 11",
+            "11 write(2, \"warning: ignoring x\\n\", 20) = 20",
+            "11 +++ exited with 1 +++",
+            "10 write(2, \"error: bad config\\n\", 18) = 18",
+            "10 +++ exited with 2 +++",
+        ] {
+            match parser.parse_line(line).unwrap() {
+                Message::Syscall(syscall) => {
+                    fds.update(&syscall);
+                    postmortem.update(&syscall, &fds);
+                }
+                Message::Exit(exit) => failed = postmortem.record_exit(&exit),
+                _ => {}
+            }
+        }
+
+        // only the first process's failure counts
+        assert!(failed);
+        let report = postmortem.report.unwrap();
+        assert_eq!(
+            report.lines("10 (app)"),
+            [
+                "10 (app) exited with 2",
+                "last failed syscalls:",
+                "  10 read(3, 0x7ffc1000, 4096) = -1 EISDIR (Is a directory)  (fd 3: /etc/app.conf)",
+                "last written to stderr:",
+                "  11: warning: ignoring x",
+                "  10: error: bad config",
+            ]
+        );
+    }
+}
@@ -0,0 +1,274 @@
+// Previews the data that a read or write moved: strace prints it as an escaped string, which is
+// fine for a line of text but unreadable for JSON on one line or for binary data. The data is
+// classified as text, JSON, or binary, and shown as a snippet of the text, the JSON pretty-printed,
+// or a hexdump of the first bytes (with the file format, if its magic number is a known one).
+//
+// strace only prints the first 32 bytes of each buffer unless it is run with a larger -s (e.g.,
+// `--strace-arg=-s4096`), so previews are often of a cut-off prefix.
+
+use std::fmt;
+
+use crate::net;
+use crate::strace::{self, Syscall};
+use crate::syscalls;
+
+// the most lines of text or JSON that a preview shows
+const MAX_LINES: usize = 20;
+// the most bytes that a hexdump shows
+const MAX_HEX_BYTES: usize = 64;
+// known formats by their magic numbers
+const MAGIC: &[(&[u8], &str)] = &[
+    (b"\x7fELF", "ELF"),
+    (b"\x89PNG", "PNG"),
+    (b"\xff\xd8\xff", "JPEG"),
+    (b"GIF8", "GIF"),
+    (b"%PDF", "PDF"),
+    (b"PK\x03\x04", "zip"),
+    (b"\x1f\x8b", "gzip"),
+    (b"\x28\xb5\x2f\xfd", "zstd"),
+    (b"\xfd7zXZ\x00", "xz"),
+    (b"BZh", "bzip2"),
+    (b"SQLite format 3\x00", "SQLite"),
+    (b"\x00asm", "WebAssembly"),
+];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ContentType {
+    Text,
+    Json,
+    Binary,
+}
+
+pub struct Preview {
+    pub content_type: ContentType,
+    // e.g., "gzip", for binary data in a known format
+    pub format: Option<&'static str>,
+    // whether strace cut the data off
+    pub truncated: bool,
+    // the snippet, pretty-printed JSON, or hexdump
+    pub text: String,
+}
+
+/// Previews the data that `syscall` read or wrote, if it is a read or write that moved any.
+pub fn of_syscall(syscall: &Syscall) -> Option<Preview> {
+    if syscall.errno.is_some() || syscall.return_value <= 0 {
+        return None;
+    }
+    syscalls::io_direction(&syscall.name)?;
+    let payloads = net::payloads(syscall);
+    let truncated = payloads.iter().any(|(_, truncated)| *truncated);
+    let mut data: Vec<u8> = payloads
+        .into_iter()
+        .flat_map(|(text, _)| strace::unescape(text))
+        .collect();
+    data.truncate(syscall.return_value as usize);
+    if data.is_empty() {
+        return None;
+    }
+    Some(preview(&data, truncated))
+}
+
+pub fn preview(data: &[u8], truncated: bool) -> Preview {
+    let format = MAGIC
+        .iter()
+        .find(|(magic, _)| data.starts_with(magic))
+        .map(|(_, name)| *name);
+    let (content_type, text) = match text_of(data, truncated) {
+        Some(text) if format.is_none() => match pretty_json(text, truncated) {
+            Some(json) => (ContentType::Json, first_lines(&json)),
+            None => (ContentType::Text, first_lines(text)),
+        },
+        _ => (ContentType::Binary, hexdump(data)),
+    };
+    Preview {
+        content_type,
+        format,
+        truncated,
+        text,
+    }
+}
+
+// `data` as text, if it is UTF-8 without control characters other than whitespace and escape
+// sequences
+fn text_of(data: &[u8], truncated: bool) -> Option<&str> {
+    let text = match std::str::from_utf8(data) {
+        Ok(text) => text,
+        // a character that strace cut in half
+        Err(e) if truncated && e.error_len().is_none() => {
+            std::str::from_utf8(&data[..e.valid_up_to()]).ok()?
+        }
+        Err(_) => return None,
+    };
+    let control = text
+        .chars()
+        .any(|c| c.is_control() && !matches!(c, '\t' | '\n' | '\r' | '\x0c' | '\x1b'));
+    (!control).then_some(text)
+}
+
+// `text` re-indented, if it looks like JSON (or JSON Lines): it starts with an object or array, its
+// brackets balance (unless it was cut off), and nothing is outside them
+fn pretty_json(text: &str, truncated: bool) -> Option<String> {
+    if !text.trim_start().starts_with(['{', '[']) {
+        return None;
+    }
+    let mut out = String::new();
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut escaped = false;
+    let newline = |out: &mut String, depth: usize| {
+        out.push('\n');
+        out.push_str(&"  ".repeat(depth));
+    };
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if in_string {
+            out.push(c);
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match c {
+            c if c.is_whitespace() => {}
+            '{' | '[' => {
+                // another document in JSON Lines
+                if depth == 0 && !out.is_empty() {
+                    out.push('\n');
+                }
+                out.push(c);
+                depth += 1;
+                // keep empty objects and arrays on one line
+                if !matches!(chars.peek(), Some('}' | ']')) {
+                    newline(&mut out, depth);
+                }
+            }
+            '}' | ']' => {
+                depth = depth.checked_sub(1)?;
+                if !out.ends_with(['{', '[']) {
+                    newline(&mut out, depth);
+                }
+                out.push(c);
+            }
+            _ if depth == 0 => return None,
+            ',' => {
+                out.push(c);
+                newline(&mut out, depth);
+            }
+            ':' => out.push_str(": "),
+            '"' => {
+                out.push(c);
+                in_string = true;
+            }
+            c => out.push(c),
+        }
+    }
+    (depth == 0 || truncated).then_some(out)
+}
+
+fn first_lines(text: &str) -> String {
+    let mut lines: Vec<&str> = text.lines().take(MAX_LINES + 1).collect();
+    if lines.len() > MAX_LINES {
+        lines[MAX_LINES] = "...";
+    }
+    lines.join("\n")
+}
+
+// e.g., "00000000  7f 45 4c 46 02 01 01 00  00 00 00 00 00 00 00 00  |.ELF............|"
+fn hexdump(data: &[u8]) -> String {
+    let mut lines = Vec::new();
+    for (i, chunk) in data[..data.len().min(MAX_HEX_BYTES)].chunks(16).enumerate() {
+        let mut line = format!("{:08x} ", i * 16);
+        for j in 0..16 {
+            if j == 8 {
+                line.push(' ');
+            }
+            match chunk.get(j) {
+                Some(byte) => line.push_str(&format!(" {:02x}", byte)),
+                None => line.push_str("   "),
+            }
+        }
+        let ascii: String = chunk
+            .iter()
+            .map(|b| match b {
+                0x20..=0x7e => *b as char,
+                _ => '.',
+            })
+            .collect();
+        line.push_str(&format!("  |{}|", ascii));
+        lines.push(line);
+    }
+    if data.len() > MAX_HEX_BYTES {
+        lines.push(format!("... ({} bytes in all)", data.len()));
+    }
+    lines.join("\n")
+}
+
+impl fmt::Display for ContentType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ContentType::Text => write!(f, "text"),
+            ContentType::Json => write!(f, "json"),
+            ContentType::Binary => write!(f, "binary"),
+        }
+    }
+}
+
+impl Preview {
+    /// e.g., "binary (gzip, cut off by strace)"
+    pub fn describe(&self) -> String {
+        let mut notes = Vec::new();
+        notes.extend(self.format);
+        if self.truncated {
+            notes.push("cut off by strace");
+        }
+        if notes.is_empty() {
+            self.content_type.to_string()
+        } else {
+            format!("{} ({})", self.content_type, notes.join(", "))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{preview, ContentType};
+
+    #[test]
+    fn test_preview() {
+        let json = preview(br#"{"a": [1, 2], "b": {}, "c": "x,y"}"#, false);
+        assert_eq!(json.content_type, ContentType::Json);
+        assert_eq!(
+            json.text,
+            "{\n  \"a\": [\n    1,\n    2\n  ],\n  \"b\": {},\n  \"c\": \"x,y\"\n}"
+        );
+        assert_eq!(json.describe(), "json");
+
+        let cut = preview(br#"{"key": "valu"#, true);
+        assert_eq!(cut.content_type, ContentType::Json);
+        assert_eq!(cut.describe(), "json (cut off by strace)");
+
+        let text = preview(b"GET / HTTP/1.1\r\nHost: x\r\n\r\n", false);
+        assert_eq!(text.content_type, ContentType::Text);
+        assert_eq!(text.text, "GET / HTTP/1.1\nHost: x\n");
+        // braces, but not JSON
+        let text = preview(b"{ echo hi; } > out", false);
+        assert_eq!(text.content_type, ContentType::Text);
+
+        let gzip = preview(b"\x1f\x8b\x08\x00\x00\x00\x00\x00\x00\x03hello", false);
+        assert_eq!(gzip.describe(), "binary (gzip)");
+        assert_eq!(
+            gzip.text,
+            "00000000  1f 8b 08 00 00 00 00 00  00 03 68 65 6c 6c 6f     |..........hello|"
+        );
+    }
+
+    #[test]
+    fn test_preview_json_lines() {
+        let json = preview(b"{\"a\": 1}\n{\"b\": \"}\"}\n", false);
+        assert_eq!(json.content_type, ContentType::Json);
+        assert_eq!(json.text, "{\n  \"a\": 1\n}\n{\n  \"b\": \"}\"\n}");
+    }
+}
@@ -0,0 +1,156 @@
+// Follows each process's changes to its user and group IDs, supplementary groups, and
+// capabilities, to show when a program gave up (or tried to give up) its privileges.
+//
+// A privilege drop that fails and is not checked leaves a program running with more privileges
+// than it expects, so failed drops are called out.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::strace::{format_timestamp, Syscall, SyscallArg, SyscallArgValue};
+use crate::syscalls;
+
+const ID_SYSCALLS: &[&str] = &[
+    "setuid",
+    "setgid",
+    "setreuid",
+    "setregid",
+    "setresuid",
+    "setresgid",
+    "setfsuid",
+    "setfsgid",
+];
+// the prctl options that change privileges, all of which give them up
+const PRCTL_OPTIONS: &[&str] = &[
+    "PR_SET_NO_NEW_PRIVS",
+    "PR_CAPBSET_DROP",
+    "PR_SET_KEEPCAPS",
+    "PR_SET_SECUREBITS",
+    "PR_CAP_AMBIENT",
+];
+
+#[derive(Default)]
+pub struct Privileges {
+    // keyed by pid
+    pub changes: BTreeMap<u32, Vec<Change>>,
+}
+
+pub struct Change {
+    pub time_micros: u64,
+    // e.g., "setuid(1000)"
+    pub call: String,
+    pub return_value: i64,
+    pub errno: Option<String>,
+    // whether the call gives up privileges rather than gaining (or keeping) them
+    pub drop: bool,
+}
+
+impl Privileges {
+    pub fn update(&mut self, syscall: &Syscall) {
+        let pid = match syscall.pid {
+            Some(pid) if syscall.error_details.is_none() => pid,
+            _ => return,
+        };
+        let drop = match syscall.name.as_str() {
+            name if ID_SYSCALLS.contains(&name) => drops_ids(syscall),
+            "setgroups" | "capset" => true,
+            "prctl" if syscall.arg(0).is_some_and(is_privilege_option) => true,
+            _ => return,
+        };
+        let args: Vec<String> = syscall.args.iter().map(|a| a.to_string()).collect();
+        self.changes.entry(pid).or_default().push(Change {
+            time_micros: syscall.entry_time_micros,
+            call: format!("{}({})", syscall.name, args.join(", ")),
+            return_value: syscall.return_value,
+            errno: syscall.errno.clone(),
+            drop,
+        });
+    }
+}
+
+impl Change {
+    pub fn failed_drop(&self) -> bool {
+        self.drop && self.errno.is_some()
+    }
+}
+
+fn is_privilege_option(option: &SyscallArg) -> bool {
+    PRCTL_OPTIONS
+        .iter()
+        .any(|o| syscalls::value_has_flag(&option.value, o))
+}
+
+// whether the IDs that a set*id call sets are all unprivileged (-1 leaves an ID unchanged)
+fn drops_ids(syscall: &Syscall) -> bool {
+    let ids: Vec<i64> = syscall
+        .args
+        .iter()
+        .filter_map(|a| match a.value {
+            SyscallArgValue::Number(n) if n != -1 => Some(n),
+            _ => None,
+        })
+        .collect();
+    !ids.is_empty() && ids.iter().all(|id| *id > 0)
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.time_micros != 0 {
+            write!(f, "{} ", format_timestamp(self.time_micros))?;
+        }
+        write!(f, "{} = {}", self.call, self.return_value)?;
+        if let Some(errno) = &self.errno {
+            write!(f, " {}", errno)?;
+        }
+        if self.failed_drop() {
+            write!(f, "  (failed to drop privileges)")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::strace::{LineParser, Message};
+
+    use super::Privileges;
+
+    #[test]
+    fn test_privileges() {
+        let mut privileges = Privileges::default();
+        let mut parser = LineParser::default();
+        for line in [
+            "10 setgroups(1, [1000]) = 0",
+            "10 setresgid(-1, 1000, -1) = 0",
+            "10 prctl(PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) = 0",
+            "10 prctl(PR_SET_NAME, \"worker\") = 0",
+            "10 setuid(1000) = -1 EAGAIN (Resource temporarily unavailable)",
+            "11 setuid(0) = -1 EPERM (Operation not permitted)",
+            "11 getuid() = 1000",
+        ] {
+            let syscall = parser
+                .parse_line(line)
+                .and_then(Message::into_syscall)
+                .unwrap();
+            privileges.update(&syscall);
+        }
+
+        let timeline = |pid: u32| {
+            privileges.changes[&pid]
+                .iter()
+                .map(|c| c.to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            timeline(10),
+            [
+                "setgroups(1, [1000]) = 0",
+                "setresgid(-1, 1000, -1) = 0",
+                "prctl(PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) = 0",
+                "setuid(1000) = -1 EAGAIN  (failed to drop privileges)",
+            ]
+        );
+        // trying to gain privileges is not a drop
+        assert_eq!(timeline(11), ["setuid(0) = -1 EPERM"]);
+    }
+}
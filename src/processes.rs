@@ -0,0 +1,247 @@
+use std::collections::BTreeMap;
+
+use crate::strace::{Exit, ExitKind, Syscall, SyscallArgValue};
+use crate::syscalls;
+
+// longest command (program and arguments) shown in a process's label
+const COMMAND_WIDTH: usize = 40;
+
+/// A process or thread that appeared in the trace.
+pub struct Process {
+    pub pid: u32,
+    // `None` if the process was already running when tracing began. A child's parent is always a
+    // process, even if one of the process's threads started it.
+    pub parent: Option<u32>,
+    // the process that a thread belongs to (its thread group), or `pid` itself for a process
+    pub tgid: u32,
+    pub syscall_count: usize,
+    // `None` if the process is still running (or strace did not say)
+    pub exit: Option<ExitKind>,
+    // the program the process is running and its arguments, abbreviated, if it was seen to exec
+    // (or was forked from a process that was)
+    pub command: Option<String>,
+}
+
+#[derive(Default)]
+pub struct ProcessTable {
+    pub processes: BTreeMap<u32, Process>,
+}
+
+impl ProcessTable {
+    pub fn update(&mut self, syscall: &Syscall) {
+        let pid = match syscall.pid {
+            Some(pid) => pid,
+            None => return,
+        };
+        let process = self.get_or_insert(pid);
+        process.syscall_count += 1;
+
+        let execs = matches!(syscall.name.as_str(), "execve" | "execveat");
+        if execs && syscall.errno.is_none() {
+            if let Some(command) = exec_command(syscall) {
+                process.command = Some(command);
+            }
+        }
+
+        // the child may show up in the trace before its parent's fork returns, so it may already
+        // be in the table
+        let forks = matches!(syscall.name.as_str(), "clone" | "clone3" | "fork" | "vfork");
+        if forks && syscall.return_value > 0 {
+            let command = process.command.clone();
+            let tgid = process.tgid;
+            let child_pid = syscall.return_value as u32;
+            let child = self.get_or_insert(child_pid);
+            // CLONE_VM without CLONE_THREAD (as vfork and posix_spawn do) still makes a process
+            if syscalls::has_clone_flag(syscall, "CLONE_THREAD") {
+                child.tgid = tgid;
+            } else {
+                child.tgid = child_pid;
+            }
+            child.parent = Some(tgid);
+            // until it execs, the child is running the same program as its parent
+            if child.command.is_none() {
+                child.command = command;
+            }
+        }
+    }
+
+    /// Describes the process by its PID and, if known, its command, e.g. "1234 (ls -l)", or a
+    /// thread by its TID and its process, e.g. "1235 (ls -l, thread of 1234)".
+    pub fn label(&self, pid: u32) -> String {
+        let process = self.processes.get(&pid);
+        let command = process.and_then(|p| p.command.as_ref());
+        match (command, process.filter(|p| p.is_thread())) {
+            (Some(command), Some(thread)) => {
+                format!("{} ({}, thread of {})", pid, command, thread.tgid)
+            }
+            (None, Some(thread)) => format!("{} (thread of {})", pid, thread.tgid),
+            (Some(command), None) => format!("{} ({})", pid, command),
+            (None, None) => pid.to_string(),
+        }
+    }
+
+    pub fn record_exit(&mut self, exit: Exit) {
+        if let Some(pid) = exit.pid {
+            self.get_or_insert(pid).exit = Some(exit.status);
+        }
+    }
+
+    /// Returns the processes (not threads) that `pid` or its threads started.
+    pub fn children(&self, pid: u32) -> Vec<u32> {
+        self.processes
+            .values()
+            .filter(|p| p.parent == Some(pid) && !p.is_thread())
+            .map(|p| p.pid)
+            .collect()
+    }
+
+    /// Returns the threads of process `pid`, other than its main thread.
+    pub fn threads(&self, pid: u32) -> Vec<u32> {
+        self.processes
+            .values()
+            .filter(|p| p.tgid == pid && p.is_thread())
+            .map(|p| p.pid)
+            .collect()
+    }
+
+    /// Returns the processes that were not started by another traced process.
+    pub fn roots(&self) -> Vec<u32> {
+        self.processes
+            .values()
+            .filter(|p| p.parent.is_none())
+            .map(|p| p.pid)
+            .collect()
+    }
+
+    fn get_or_insert(&mut self, pid: u32) -> &mut Process {
+        self.processes.entry(pid).or_insert(Process {
+            pid,
+            parent: None,
+            tgid: pid,
+            syscall_count: 0,
+            exit: None,
+            command: None,
+        })
+    }
+}
+
+impl Process {
+    pub fn is_thread(&self) -> bool {
+        self.tgid != self.pid
+    }
+}
+
+// e.g., "ls -l" for `execve("/bin/ls", ["ls", "-l"], ...)`
+fn exec_command(syscall: &Syscall) -> Option<String> {
+    // execveat has a directory fd in front of the path
+    let offset = usize::from(syscall.name == "execveat");
+    let path = match &syscall.args.get(offset)?.value {
+        SyscallArgValue::Quoted { text, .. } => text,
+        _ => return None,
+    };
+
+    // the program is named by its path rather than argv[0], which could be anything
+    let mut words = vec![path.rsplit('/').next().unwrap_or(path).to_string()];
+    if let Some(SyscallArgValue::Array(argv)) = syscall.args.get(offset + 1).map(|a| &a.value) {
+        for arg in argv.iter().skip(1) {
+            if let SyscallArgValue::Quoted { text, .. } = &arg.value {
+                words.push(text.clone());
+            }
+        }
+    }
+
+    let command = words.join(" ");
+    if command.chars().count() > COMMAND_WIDTH {
+        let truncated: String = command.chars().take(COMMAND_WIDTH - 3).collect();
+        Some(format!("{}...", truncated))
+    } else {
+        Some(command)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::strace::{LineParser, Message};
+
+    use super::ProcessTable;
+
+    #[test]
+    fn test_process_table() {
+        let mut table = ProcessTable::default();
+        let mut parser = LineParser::default();
+        for line in [
+            "100 clone(child_stack=NULL, flags=CLONE_CHILD_CLEARTID|SIGCHLD) = 101",
+            "101 execve(\"/bin/true\", [\"true\"], 0x7ffc /* 1 vars */) = 0",
+            "102 close(3) = 0",
+            "101 vfork() = 102",
+            "100 wait4(-1, NULL, 0, NULL) = 101",
+        ] {
+            table.update(
+                &parser
+                    .parse_line(line)
+                    .and_then(Message::into_syscall)
+                    .unwrap(),
+            );
+        }
+
+        assert_eq!(table.roots(), vec![100]);
+        assert_eq!(table.children(100), vec![101]);
+        assert_eq!(table.children(101), vec![102]);
+        assert_eq!(table.processes[&100].syscall_count, 2);
+        assert_eq!(table.processes[&102].syscall_count, 1);
+
+        assert_eq!(table.label(100), "100");
+        assert_eq!(table.label(101), "101 (true)");
+        // forked by 101 after it exec'd
+        assert_eq!(table.label(102), "102 (true)");
+    }
+
+    #[test]
+    fn test_exec_command() {
+        let mut table = ProcessTable::default();
+        let mut parser = LineParser::default();
+        for line in [
+            "10 execve(\"/usr/bin/grep\", [\"grep\", \"-r\", \"a very long pattern that goes on\", \".\"], 0x7ffc /* 1 vars */) = 0",
+            "11 execve(\"/bin/nope\", [\"nope\"], 0x7ffc /* 1 vars */) = -1 ENOENT (No such file or directory)",
+        ] {
+            table.update(
+                &parser
+                    .parse_line(line)
+                    .and_then(Message::into_syscall)
+                    .unwrap(),
+            );
+        }
+
+        assert_eq!(
+            table.label(10),
+            "10 (grep -r a very long pattern that goes...)"
+        );
+        assert_eq!(table.label(11), "11");
+    }
+
+    #[test]
+    fn test_threads() {
+        let mut table = ProcessTable::default();
+        let mut parser = LineParser::default();
+        for line in [
+            "10 execve(\"/usr/bin/app\", [\"app\"], 0x7ffc /* 1 vars */) = 0",
+            "10 clone3({flags=CLONE_VM|CLONE_FS|CLONE_FILES|CLONE_SIGHAND|CLONE_THREAD|CLONE_SYSVSEM|CLONE_SETTLS|CLONE_PARENT_SETTID|CLONE_CHILD_CLEARTID, child_tid=0x7f00, parent_tid=0x7f00, exit_signal=0, stack=0x7f00, stack_size=0x1000, tls=0x7f00}, 88) = 11",
+            // a thread starts a child process, the way posix_spawn does
+            "11 clone(child_stack=0x7f00, flags=CLONE_VM|CLONE_VFORK|SIGCHLD) = 12",
+        ] {
+            table.update(
+                &parser
+                    .parse_line(line)
+                    .and_then(Message::into_syscall)
+                    .unwrap(),
+            );
+        }
+
+        assert_eq!(table.roots(), vec![10]);
+        assert_eq!(table.threads(10), vec![11]);
+        assert_eq!(table.children(10), vec![12]);
+        assert!(table.threads(12).is_empty());
+        assert_eq!(table.label(11), "11 (app, thread of 10)");
+        assert_eq!(table.label(12), "12 (app)");
+    }
+}
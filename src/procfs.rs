@@ -0,0 +1,238 @@
+// helpers for reading process information out of /proc
+
+use std::fs;
+use std::path::Path;
+
+use anyhow::{anyhow, Result};
+
+/// Returns the PIDs of running processes whose name matches `pattern`, which may contain `*` and
+/// `?` wildcards. A process's name is either its `comm` (as shown by `ps`) or the basename of its
+/// first command-line argument.
+pub fn find_pids(pattern: &str) -> Result<Vec<u32>> {
+    let own_pid = std::process::id();
+    let entries = fs::read_dir("/proc").map_err(|e| anyhow!("unable to read /proc: {}", e))?;
+
+    let mut pids = Vec::new();
+    for entry in entries.flatten() {
+        let pid = match entry
+            .file_name()
+            .to_str()
+            .and_then(|s| s.parse::<u32>().ok())
+        {
+            Some(pid) => pid,
+            None => continue,
+        };
+        if pid == own_pid {
+            continue;
+        }
+
+        // processes can exit at any moment, so errors are ignored
+        let comm = fs::read_to_string(entry.path().join("comm")).unwrap_or_default();
+        let cmdline = fs::read(entry.path().join("cmdline")).unwrap_or_default();
+        let argv0 = cmdline.split(|b| *b == 0).next().unwrap_or_default();
+        let argv0 = String::from_utf8_lossy(argv0);
+        let argv0 = Path::new(argv0.as_ref())
+            .file_name()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default();
+
+        if glob_match(pattern, comm.trim_end()) || glob_match(pattern, &argv0) {
+            pids.push(pid);
+        }
+    }
+
+    pids.sort();
+    Ok(pids)
+}
+
+/// Returns the working directory of `pid`, or `None` if it cannot be read (e.g., because the
+/// process belongs to another user).
+pub fn cwd(pid: u32) -> Option<String> {
+    fs::read_link(format!("/proc/{}/cwd", pid))
+        .ok()
+        .map(|path| path.to_string_lossy().into_owned())
+}
+
+/// Returns the cgroup that `pid` belongs to, e.g., "/system.slice/docker-3f2a….scope". With both
+/// cgroup versions mounted, the unified (v2) hierarchy is preferred.
+pub fn cgroup(pid: u32) -> Option<String> {
+    let text = fs::read_to_string(format!("/proc/{}/cgroup", pid)).ok()?;
+    // each line is "hierarchy-ID:controllers:path"
+    let paths: Vec<(&str, &str)> = text
+        .lines()
+        .filter_map(|line| {
+            let (id, rest) = line.split_once(':')?;
+            Some((id, rest.split_once(':')?.1))
+        })
+        .collect();
+    paths
+        .iter()
+        .find(|(id, _)| *id == "0")
+        .or(paths.first())
+        .map(|(_, path)| path.to_string())
+}
+
+/// Returns the namespace of type `kind` (e.g., "mnt" or "net") that `pid` is in, as the kernel
+/// names it, e.g., "mnt:[4026531841]". Pass `None` for vistrace's own.
+pub fn namespace(pid: Option<u32>, kind: &str) -> Option<String> {
+    let pid = pid.map_or_else(|| "self".to_string(), |pid| pid.to_string());
+    fs::read_link(format!("/proc/{}/ns/{}", pid, kind))
+        .ok()
+        .map(|path| path.to_string_lossy().into_owned())
+}
+
+/// Returns the PIDs of the direct children of `pid`. Errors are ignored, since the process may
+/// exit at any moment.
+pub fn children(pid: u32) -> Vec<u32> {
+    let tasks = match fs::read_dir(format!("/proc/{}/task", pid)) {
+        Ok(tasks) => tasks,
+        Err(_) => return Vec::new(),
+    };
+
+    let mut children = Vec::new();
+    for task in tasks.flatten() {
+        let text = fs::read_to_string(task.path().join("children")).unwrap_or_default();
+        children.extend(
+            text.split_whitespace()
+                .filter_map(|s| s.parse::<u32>().ok()),
+        );
+    }
+    children
+}
+
+/// Returns `pid` and all of its descendants that are still running.
+pub fn descendants(pid: u32) -> Vec<u32> {
+    let mut pids = vec![pid];
+    let mut i = 0;
+    while i < pids.len() {
+        pids.extend(children(pids[i]));
+        i += 1;
+    }
+    pids
+}
+
+/// What a process is using at the moment, from /proc.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Usage {
+    // user and system time so far, in clock ticks (see `clock_ticks`)
+    pub cpu_ticks: u64,
+    pub rss_kib: u64,
+    pub threads: u64,
+    pub fds: u64,
+}
+
+/// Returns what `pid` is using, or `None` if it has exited (or cannot be read).
+pub fn usage(pid: u32) -> Option<Usage> {
+    let stat = fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+    let status = fs::read_to_string(format!("/proc/{}/status", pid)).ok()?;
+    // the fds of another user's process cannot be listed
+    let fds = fs::read_dir(format!("/proc/{}/fd", pid)).map_or(0, |fds| fds.count() as u64);
+    let mut usage = parse_status(&status);
+    usage.cpu_ticks = parse_stat_cpu(&stat)?;
+    usage.fds = fds;
+    Some(usage)
+}
+
+/// The number of clock ticks per second, the unit of CPU time in /proc.
+#[cfg(feature = "native")]
+pub fn clock_ticks() -> u64 {
+    // SAFETY: sysconf has no preconditions
+    let ticks = unsafe { libc::sysconf(libc::_SC_CLK_TCK) };
+    if ticks > 0 {
+        ticks as u64
+    } else {
+        100
+    }
+}
+
+// utime + stime, the 14th and 15th fields of /proc/PID/stat; the second field (the command) is in
+// parentheses and may contain spaces, so fields are counted from after it
+fn parse_stat_cpu(stat: &str) -> Option<u64> {
+    let (_, rest) = stat.rsplit_once(')')?;
+    let mut fields = rest.split_whitespace().skip(11);
+    let utime: u64 = fields.next()?.parse().ok()?;
+    let stime: u64 = fields.next()?.parse().ok()?;
+    Some(utime + stime)
+}
+
+// VmRSS and Threads from /proc/PID/status
+fn parse_status(status: &str) -> Usage {
+    let mut usage = Usage::default();
+    for line in status.lines() {
+        let (key, value) = match line.split_once(':') {
+            Some(pair) => pair,
+            None => continue,
+        };
+        let number = || value.split_whitespace().next()?.parse::<u64>().ok();
+        match key {
+            // e.g., "VmRSS:     1234 kB"
+            "VmRSS" => usage.rss_kib = number().unwrap_or(0),
+            "Threads" => usage.threads = number().unwrap_or(0),
+            _ => {}
+        }
+    }
+    usage
+}
+
+/// Matches `text` against a shell-style pattern supporting `*` and `?`.
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+
+    // backtracking matcher: remember the last '*' seen and how much text it has consumed
+    let (mut p, mut t) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, t));
+            p += 1;
+        } else if let Some((star_p, star_t)) = star {
+            p = star_p + 1;
+            t = star_t + 1;
+            star = Some((star_p, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{glob_match, parse_stat_cpu, parse_status, Usage};
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("nginx", "nginx"));
+        assert!(!glob_match("nginx", "nginx2"));
+        assert!(glob_match("nginx*", "nginx: worker"));
+        assert!(glob_match("*sql*", "postgresql"));
+        assert!(glob_match("py?hon", "python"));
+        assert!(glob_match("*", ""));
+        assert!(!glob_match("?", ""));
+        assert!(glob_match("a*b*c", "aXbYbZc"));
+        assert!(!glob_match("a*b*c", "aXbYbZ"));
+    }
+
+    #[test]
+    fn test_usage() {
+        let stat = "1234 (my prog) S 1 1234 1234 0 -1 4194560 100 0 0 0 250 50 0 0 20 0 3 0 10";
+        assert_eq!(parse_stat_cpu(stat), Some(300));
+        assert_eq!(parse_stat_cpu("1234 (x"), None);
+
+        let status = "Name:\tprog\nVmRSS:\t    2048 kB\nThreads:\t3\n";
+        assert_eq!(
+            parse_status(status),
+            Usage {
+                cpu_ticks: 0,
+                rss_kib: 2048,
+                threads: 3,
+                fds: 0,
+            }
+        );
+    }
+}
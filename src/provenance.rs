@@ -0,0 +1,206 @@
+// Works out which process (and which command) produced each file that the trace wrote, across all
+// of the traced processes, to answer questions like "which step of the build wrote this stale
+// object file?"
+//
+// A file's origin follows it through renames, since many programs write to a temporary file and
+// rename it into place. It is forgotten when the file is deleted, and its writers are forgotten
+// when it is truncated, since the content that they wrote is gone.
+
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+
+use crate::changes::{Changes, Kind};
+use crate::commands::Commands;
+use crate::fdtable::{FdKind, FdTable};
+use crate::processes::ProcessTable;
+use crate::strace::{format_timestamp, Syscall};
+use crate::syscalls::{self, Io};
+
+#[derive(Default)]
+pub struct Provenance {
+    // keyed by absolute path
+    pub files: BTreeMap<String, Origin>,
+    changes: Changes,
+}
+
+#[derive(Default)]
+pub struct Origin {
+    pub creator: Option<Writer>,
+    // in the order they first wrote to the file
+    pub writers: Vec<Writer>,
+    // the path that the file was written under before it was renamed
+    pub renamed_from: Option<String>,
+}
+
+pub struct Writer {
+    pub pid: Option<u32>,
+    // the command that the process was running, as an index into `Commands::commands`
+    pub command: Option<usize>,
+    pub time_micros: u64,
+    pub bytes: u64,
+}
+
+impl Provenance {
+    /// Must be called after `fds`, `processes`, and `commands` have been updated with `syscall`.
+    pub fn update(
+        &mut self,
+        syscall: &Syscall,
+        fds: &FdTable,
+        processes: &ProcessTable,
+        commands: &Commands,
+    ) {
+        let writer = |bytes: u64| Writer {
+            pid: syscall.pid,
+            command: commands.running(syscall.pid, processes),
+            time_micros: syscall.entry_time_micros,
+            bytes,
+        };
+
+        let before = self.changes.changes.len();
+        self.changes.update(syscall, fds);
+        if let Some(change) = self.changes.changes.get(before) {
+            let path = change.path.clone();
+            match &change.kind {
+                Kind::Create { truncate } => {
+                    let origin = self.files.entry(path).or_default();
+                    if *truncate {
+                        origin.writers.clear();
+                    }
+                    origin.creator.get_or_insert_with(|| writer(0));
+                }
+                Kind::Symlink(_) | Kind::Link(_) => {
+                    self.files.insert(
+                        path,
+                        Origin {
+                            creator: Some(writer(0)),
+                            ..Origin::default()
+                        },
+                    );
+                }
+                Kind::Write { truncate: true } | Kind::Truncate { length: 0 } => {
+                    if let Some(origin) = self.files.get_mut(&path) {
+                        origin.writers.clear();
+                    }
+                }
+                Kind::RenameTo(to) => {
+                    if let Some(mut origin) = self.files.remove(&path) {
+                        origin.renamed_from = Some(path);
+                        self.files.insert(to.clone(), origin);
+                    } else {
+                        self.files.remove(to);
+                    }
+                }
+                Kind::Delete => {
+                    self.files.remove(&path);
+                }
+                _ => {}
+            }
+            return;
+        }
+
+        if syscalls::io_direction(&syscall.name) != Some(Io::Write)
+            || syscall.errno.is_some()
+            || syscall.return_value <= 0
+        {
+            return;
+        }
+        let fd = match syscalls::fd_args(syscall).first() {
+            Some(fd) => *fd,
+            None => return,
+        };
+        let path = match fds.resolve(syscall.pid, fd, syscall.entry_time_micros).kind {
+            FdKind::File(path) if !path.starts_with("/dev/") && !path.starts_with("/proc/") => path,
+            _ => return,
+        };
+        let origin = self.files.entry(path).or_default();
+        let bytes = syscall.return_value as u64;
+        match origin.writers.iter_mut().find(|w| w.pid == syscall.pid) {
+            Some(existing) => existing.bytes += bytes,
+            None => origin.writers.push(writer(bytes)),
+        }
+    }
+
+    /// Writes each file that was created or written, with who created it and who wrote to it.
+    pub fn write(&self, w: &mut impl Write, commands: &Commands) -> io::Result<()> {
+        let describe = |writer: &Writer| match (writer.command, writer.pid) {
+            (Some(command), _) => commands.commands[command].to_string(),
+            (None, Some(pid)) => format!("process {}", pid),
+            (None, None) => "the traced process".to_string(),
+        };
+        for (path, origin) in &self.files {
+            writeln!(w, "{}", path)?;
+            if let Some(creator) = &origin.creator {
+                writeln!(
+                    w,
+                    "  created at {} by {}",
+                    format_timestamp(creator.time_micros),
+                    describe(creator)
+                )?;
+            }
+            for writer in &origin.writers {
+                writeln!(
+                    w,
+                    "  {} bytes written from {} by {}",
+                    writer.bytes,
+                    format_timestamp(writer.time_micros),
+                    describe(writer)
+                )?;
+            }
+            if let Some(old) = &origin.renamed_from {
+                writeln!(w, "  renamed from {}", old)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::commands::Commands;
+    use crate::fdtable::FdTable;
+    use crate::processes::ProcessTable;
+    use crate::strace::LineParser;
+
+    use super::Provenance;
+
+    #[test]
+    fn test_provenance() {
+        let mut provenance = Provenance::default();
+        let mut fds = FdTable::default();
+        let mut processes = ProcessTable::default();
+        let mut commands = Commands::default();
+        let mut parser = LineParser::default();
+        for line in [
+            "10 1.000000 execve(\"/usr/bin/make\", [\"make\"], 0x7ffc1000 /* 20 vars */) = 0",
+            "10 1.000001 clone(child_stack=NULL, flags=SIGCHLD) = 11",
+            "11 1.000002 execve(\"/usr/bin/cc\", [\"cc\", \"-c\", \"a.c\"], 0x7ffc1000 /* 20 vars */) = 0",
+            "11 1.000003 openat(AT_FDCWD, \"/b/a.o.tmp\", O_WRONLY|O_CREAT|O_TRUNC, 0644) = 3",
+            "11 1.000004 write(3, \"\\177ELF\", 100) = 100",
+            "11 1.000005 write(3, \"\\0\\0\", 50) = 50",
+            "11 1.000006 rename(\"/b/a.o.tmp\", \"/b/a.o\") = 0",
+            "11 1.000007 write(1, \"done\\n\", 5) = 5",
+            "10 1.000008 openat(AT_FDCWD, \"/b/log\", O_WRONLY|O_APPEND) = 4",
+            "10 1.000009 write(4, \"ok\\n\", 3) = 3",
+            "10 1.000010 openat(AT_FDCWD, \"/b/gone\", O_WRONLY|O_CREAT, 0644) = 5",
+            "10 1.000011 unlink(\"/b/gone\") = 0",
+        ] {
+            let syscall = parser.parse_line(line).unwrap().into_syscall().unwrap();
+            processes.update(&syscall);
+            commands.update(&syscall, &processes);
+            fds.update(&syscall);
+            provenance.update(&syscall, &fds, &processes, &commands);
+        }
+
+        let mut out = Vec::new();
+        provenance.write(&mut out, &commands).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "/b/a.o\n\
+             \x20 created at 1.000003 by 11: /usr/bin/cc -c a.c\n\
+             \x20 150 bytes written from 1.000004 by 11: /usr/bin/cc -c a.c\n\
+             \x20 renamed from /b/a.o.tmp\n\
+             /b/log\n\
+             \x20 3 bytes written from 1.000009 by 10: /usr/bin/make\n"
+        );
+    }
+}
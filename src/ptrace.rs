@@ -0,0 +1,624 @@
+// A tracer that uses ptrace directly rather than running strace (`--backend ptrace`), for machines
+// without strace and for data that strace would cut short: it records the whole buffer of each
+// read and write (up to MAX_DATA bytes), rather than strace's first 32 bytes. It produces the same
+// messages that strace's output is parsed into, and saves them in strace's format, so the rest of
+// vistrace cannot tell which tracer was used.
+//
+// It decodes less than strace does: paths, the data of reads and writes, the arguments of execve,
+// open flags, and AT_FDCWD, while other arguments (other flags, structs, socket addresses) are
+// shown as numbers, and syscalls that src/syscalls.rs has no argument names for are shown without
+// any.
+// It only runs commands on this machine, which must be x86-64 Linux; it cannot attach to processes,
+// record stacks, or inject faults.
+//
+// The command is started the way that strace starts it: the child stops itself before it calls
+// execve, so that it can be seized (PTRACE_SEIZE) and then continued, and none of its syscalls are
+// missed. Each syscall stops the tracee twice, on entry and on exit; the arguments are read on
+// entry, and the data that a read returned on exit.
+
+use std::process::ExitStatus;
+use std::thread::JoinHandle;
+
+use anyhow::Result;
+
+use crate::strace::Sink;
+
+pub struct Options {
+    pub command: Vec<String>,
+    // trace child processes and threads as well
+    pub follow: bool,
+    // syscall names and `%category`s not to send, as returned by `strace::parse_exclude`
+    pub exclude: Vec<String>,
+}
+
+/// Runs `options.command` under ptrace on a thread of its own (only the thread that seized a
+/// process can control it), which sends the trace to `sink` until every traced process has exited,
+/// and returns the command's exit status. Returns the pid of the command and the thread.
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+pub fn spawn(options: Options, sink: Sink) -> Result<(u32, JoinHandle<Result<ExitStatus>>)> {
+    imp::spawn(options, sink)
+}
+
+#[cfg(not(all(target_os = "linux", target_arch = "x86_64")))]
+pub fn spawn(_options: Options, _sink: Sink) -> Result<(u32, JoinHandle<Result<ExitStatus>>)> {
+    Err(anyhow::anyhow!(
+        "--backend ptrace only works on x86-64 Linux"
+    ))
+}
+
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+mod imp {
+    use std::collections::HashMap;
+    use std::env;
+    use std::ffi::CString;
+    use std::fs::File;
+    use std::io::IoSliceMut;
+    use std::os::fd::AsRawFd;
+    use std::os::unix::process::ExitStatusExt;
+    use std::path::Path;
+    use std::process::ExitStatus;
+    use std::sync::mpsc;
+    use std::thread::{self, JoinHandle};
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    use anyhow::{anyhow, Result};
+    use nix::errno::Errno;
+    use nix::sys::ptrace;
+    use nix::sys::signal::{self, Signal};
+    use nix::sys::uio::{process_vm_readv, RemoteIoVec};
+    use nix::sys::wait::{waitpid, WaitPidFlag, WaitStatus};
+    use nix::unistd::{self, ForkResult, Pid};
+
+    use super::Options;
+    use crate::strace::{
+        self, Exit, ExitKind, Message, Signal as SignalMessage, Sink, Syscall, SyscallArg,
+        SyscallArgValue,
+    };
+    use crate::syscalls::{self, Io};
+
+    // the most bytes of a read or write to record
+    const MAX_DATA: usize = 1 << 20;
+    // the longest path to read, which is PATH_MAX
+    const MAX_PATH: usize = 4096;
+    // the most arguments of execve to read
+    const MAX_ARGV: usize = 1024;
+
+    pub fn spawn(options: Options, sink: Sink) -> Result<(u32, JoinHandle<Result<ExitStatus>>)> {
+        let (pid_tx, pid_rx) = mpsc::channel();
+        let thread = thread::spawn(move || {
+            let pid = match start(&options) {
+                Ok(pid) => pid,
+                Err(e) => {
+                    let _ = pid_tx.send(Err(e));
+                    return Err(anyhow!("unable to start the command"));
+                }
+            };
+            let _ = pid_tx.send(Ok(pid));
+            Tracer {
+                root: pid,
+                exclude: options.exclude,
+                sink,
+                entered: HashMap::new(),
+                status: None,
+                started: false,
+            }
+            .run()
+        });
+        let pid = pid_rx
+            .recv()
+            .map_err(|_| anyhow!("the tracer stopped before it started the command"))??;
+        tracing::info!(pid, "command started under ptrace");
+        Ok((pid, thread))
+    }
+
+    // forks and execs the command, and seizes it before it execs
+    fn start(options: &Options) -> Result<u32> {
+        let program = options
+            .command
+            .first()
+            .ok_or(anyhow!("no command to run"))?;
+        // the child cannot report that execvp failed, so check first
+        if !found(program) {
+            return Err(anyhow!("unable to find command: {}", program));
+        }
+        let args = options
+            .command
+            .iter()
+            .map(|arg| CString::new(arg.as_bytes()))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| anyhow!("command contains a NUL byte"))?;
+        // the command's output and errors would draw over the interface (under strace, they go to
+        // strace's pipes)
+        let null = File::options()
+            .write(true)
+            .open("/dev/null")
+            .map_err(|e| anyhow!("unable to open /dev/null: {}", e))?;
+
+        // after fork, the child may only make async-signal-safe calls
+        match unsafe { unistd::fork() }.map_err(|e| anyhow!("unable to fork: {}", e))? {
+            ForkResult::Child => {
+                unsafe {
+                    libc::dup2(null.as_raw_fd(), libc::STDOUT_FILENO);
+                    libc::dup2(null.as_raw_fd(), libc::STDERR_FILENO);
+                    libc::raise(libc::SIGSTOP);
+                }
+                let _ = unistd::execvp(&args[0], &args);
+                unsafe { libc::_exit(127) }
+            }
+            ForkResult::Parent { child } => {
+                match waitpid(child, Some(WaitPidFlag::WUNTRACED)) {
+                    Ok(WaitStatus::Stopped(_, Signal::SIGSTOP)) => {}
+                    status => {
+                        return Err(anyhow!("command did not stop to be traced: {:?}", status))
+                    }
+                }
+                let mut flags = ptrace::Options::PTRACE_O_TRACESYSGOOD
+                    | ptrace::Options::PTRACE_O_TRACEEXEC
+                    | ptrace::Options::PTRACE_O_EXITKILL;
+                if options.follow {
+                    flags |= ptrace::Options::PTRACE_O_TRACEFORK
+                        | ptrace::Options::PTRACE_O_TRACEVFORK
+                        | ptrace::Options::PTRACE_O_TRACECLONE;
+                }
+                ptrace::seize(child, flags).map_err(|e| anyhow!("unable to trace: {}", e))?;
+                signal::kill(child, Signal::SIGCONT)
+                    .map_err(|e| anyhow!("unable to continue command: {}", e))?;
+                Ok(child.as_raw() as u32)
+            }
+        }
+    }
+
+    fn found(program: &str) -> bool {
+        if program.contains('/') {
+            return Path::new(program).is_file();
+        }
+        env::var_os("PATH")
+            .is_some_and(|path| env::split_paths(&path).any(|dir| dir.join(program).is_file()))
+    }
+
+    struct Tracer {
+        root: u32,
+        exclude: Vec<String>,
+        sink: Sink,
+        // the syscall that each process is in the middle of, if any
+        entered: HashMap<u32, Entered>,
+        // the root process's exit status, once it has exited
+        status: Option<ExitStatus>,
+        // whether the command has made a syscall since it was continued, before which the SIGCONT
+        // that continued it is not part of the trace
+        started: bool,
+    }
+
+    struct Entered {
+        // `None` if the syscall is excluded
+        syscall: Option<Syscall>,
+        // the raw arguments
+        args: [u64; 6],
+    }
+
+    impl Tracer {
+        fn run(mut self) -> Result<ExitStatus> {
+            loop {
+                // nix's WaitStatus cannot hold real-time signals, so wait with libc; only for this
+                // thread's tracees, since another thread may be tracing another command
+                let mut status = 0;
+                let flags = libc::__WALL | libc::__WNOTHREAD;
+                let pid = unsafe { libc::waitpid(-1, &mut status, flags) };
+                if pid < 0 {
+                    match Errno::last() {
+                        Errno::EINTR => continue,
+                        // every traced process has exited
+                        Errno::ECHILD => break,
+                        e => return Err(anyhow!("unable to wait for traced processes: {}", e)),
+                    }
+                }
+                let pid = pid as u32;
+
+                if libc::WIFEXITED(status) || libc::WIFSIGNALED(status) {
+                    self.exited(pid, status)?;
+                    continue;
+                }
+                let sig = libc::WSTOPSIG(status);
+                let event = status >> 16;
+                if sig == libc::SIGTRAP | 0x80 {
+                    self.syscall_stop(pid)?;
+                    restart(pid, libc::PTRACE_SYSCALL, 0);
+                } else if event == libc::PTRACE_EVENT_STOP {
+                    // a group-stop, which lasts until SIGCONT, or else a new process starting
+                    if [libc::SIGSTOP, libc::SIGTSTP, libc::SIGTTIN, libc::SIGTTOU].contains(&sig) {
+                        restart(pid, libc::PTRACE_LISTEN, 0);
+                    } else {
+                        restart(pid, libc::PTRACE_SYSCALL, 0);
+                    }
+                } else if event != 0 {
+                    // fork, clone, or exec
+                    restart(pid, libc::PTRACE_SYSCALL, 0);
+                } else {
+                    if self.started || sig != libc::SIGCONT {
+                        self.signal(pid, sig)?;
+                    }
+                    restart(pid, libc::PTRACE_SYSCALL, sig);
+                }
+            }
+
+            self.sink.finish()?;
+            let status = self.status.unwrap_or_default();
+            tracing::info!(%status, "traced command exited");
+            Ok(status)
+        }
+
+        fn syscall_stop(&mut self, pid: u32) -> Result<()> {
+            // the process may have been killed in the meantime
+            let regs = match ptrace::getregs(Pid::from_raw(pid as i32)) {
+                Ok(regs) => regs,
+                Err(_) => return Ok(()),
+            };
+            match self.entered.remove(&pid) {
+                Some(entered) => self.returned(pid, entered, regs.rax as i64),
+                // the kernel sets the return value to -ENOSYS until the syscall has run
+                None if regs.rax as i64 == -(libc::ENOSYS as i64) => {
+                    let entered = self.enter(pid, &regs);
+                    self.entered.insert(pid, entered);
+                    self.started = true;
+                    Ok(())
+                }
+                // the end of the syscall that the command was in when it was seized
+                None => Ok(()),
+            }
+        }
+
+        fn enter(&self, pid: u32, regs: &libc::user_regs_struct) -> Entered {
+            let args = [regs.rdi, regs.rsi, regs.rdx, regs.r10, regs.r8, regs.r9];
+            let name = syscalls::syscall_name(regs.orig_rax);
+            if strace::excludes(&self.exclude, &name) {
+                return Entered {
+                    syscall: None,
+                    args,
+                };
+            }
+
+            // for a syscall that it does not know, it cannot tell how many of the registers are
+            // arguments and how many are leftovers, so it shows none
+            let names = syscalls::arg_names(&name).unwrap_or_default();
+            let decoded = names
+                .iter()
+                .zip(args)
+                .map(|(arg_name, value)| SyscallArg {
+                    name: String::new(),
+                    value: decode(pid, &name, arg_name, value, &args),
+                })
+                .collect();
+            Entered {
+                syscall: Some(Syscall {
+                    pid: Some(pid),
+                    name,
+                    args: decoded,
+                    return_value: 0,
+                    no_return: false,
+                    errno: None,
+                    returned: None,
+                    explanation: None,
+                    entry_time_micros: now_micros(),
+                    syscall_time_micros: None,
+                    injected: false,
+                    error_details: None,
+                }),
+                args,
+            }
+        }
+
+        fn returned(&mut self, pid: u32, entered: Entered, value: i64) -> Result<()> {
+            let mut syscall = match entered.syscall {
+                Some(syscall) => syscall,
+                None => return Ok(()),
+            };
+            syscall.syscall_time_micros =
+                Some(now_micros().saturating_sub(syscall.entry_time_micros));
+            if (-4095..0).contains(&value) {
+                syscall.return_value = -1;
+                syscall.errno = Some(syscalls::errno_name(-value as i32));
+                // as strace explains it
+                syscall.explanation = Some(format!("({})", Errno::from_raw(-value as i32).desc()));
+            } else {
+                syscall.return_value = value;
+                // what was read is only there once the syscall has returned
+                let buf = syscalls::arg_index(&syscall.name, "buf")
+                    .filter(|_| syscalls::io_direction(&syscall.name) == Some(Io::Read));
+                if let Some(i) = buf.filter(|i| *i < syscall.args.len()) {
+                    syscall.args[i].value = quoted_bytes(pid, entered.args[i], value as usize);
+                }
+            }
+            self.send(Message::Syscall(syscall))
+        }
+
+        fn signal(&mut self, pid: u32, sig: i32) -> Result<()> {
+            let name = syscalls::signal_name(sig);
+            let mut info = HashMap::new();
+            info.insert(
+                "si_signo".to_string(),
+                SyscallArg {
+                    name: "si_signo".to_string(),
+                    value: SyscallArgValue::Symbol(name.clone()),
+                },
+            );
+            if let Ok(siginfo) = ptrace::getsiginfo(Pid::from_raw(pid as i32)) {
+                let mut field = |name: &str, value: i64| {
+                    info.insert(
+                        name.to_string(),
+                        SyscallArg {
+                            name: name.to_string(),
+                            value: SyscallArgValue::Number(value),
+                        },
+                    );
+                };
+                field("si_code", siginfo.si_code as i64);
+                // the sender, for signals sent by a process and for SIGCHLD
+                if siginfo.si_code <= 0 || sig == libc::SIGCHLD {
+                    unsafe {
+                        field("si_pid", siginfo.si_pid() as i64);
+                        field("si_uid", siginfo.si_uid() as i64);
+                    }
+                }
+            }
+            self.send(Message::Signal(SignalMessage {
+                pid: Some(pid),
+                time_micros: now_micros(),
+                name,
+                info,
+            }))
+        }
+
+        fn exited(&mut self, pid: u32, status: i32) -> Result<()> {
+            // exit and exit_group never return, which strace shows as `= ?`
+            if let Some(Entered {
+                syscall: Some(mut syscall),
+                ..
+            }) = self.entered.remove(&pid)
+            {
+                syscall.no_return = true;
+                self.send(Message::Syscall(syscall))?;
+            }
+            if pid == self.root {
+                self.status = Some(ExitStatus::from_raw(status));
+            }
+            let kind = if libc::WIFEXITED(status) {
+                ExitKind::Exited(libc::WEXITSTATUS(status) as i64)
+            } else {
+                ExitKind::Killed {
+                    signal: syscalls::signal_name(libc::WTERMSIG(status)),
+                    core_dumped: libc::WCOREDUMP(status),
+                }
+            };
+            self.send(Message::Exit(Exit {
+                pid: Some(pid),
+                time_micros: now_micros(),
+                status: kind,
+            }))
+        }
+
+        fn send(&mut self, msg: Message) -> Result<()> {
+            // saved as strace would have printed it
+            let line = format!("{}\n", msg);
+            self.sink.send(&line, Some(msg))
+        }
+    }
+
+    // resumes a stopped process, unless it has already gone
+    fn restart(pid: u32, request: libc::c_uint, sig: i32) {
+        // nix cannot pass real-time signals on, so restart with libc
+        unsafe {
+            libc::ptrace(request, pid as libc::pid_t, 0, sig as libc::c_long);
+        }
+    }
+
+    fn decode(
+        pid: u32,
+        syscall: &str,
+        arg_name: &str,
+        value: u64,
+        args: &[u64; 6],
+    ) -> SyscallArgValue {
+        match (syscall, arg_name) {
+            ("execve" | "execveat", "argv") => {
+                let mut argv = Vec::new();
+                for i in 0..MAX_ARGV {
+                    let pointer = match read(pid, value + 8 * i as u64, 8) {
+                        Some(bytes) if bytes.len() == 8 => {
+                            u64::from_ne_bytes(bytes.try_into().unwrap())
+                        }
+                        _ => break,
+                    };
+                    if pointer == 0 {
+                        break;
+                    }
+                    argv.push(SyscallArg {
+                        name: String::new(),
+                        value: quoted_string(pid, pointer),
+                    });
+                }
+                SyscallArgValue::Array(argv)
+            }
+            (_, "buf") if syscalls::io_direction(syscall) == Some(Io::Write) => {
+                quoted_bytes(pid, value, args[2] as usize)
+            }
+            (_, name) if syscalls::is_path_arg(name) && value != 0 => quoted_string(pid, value),
+            _ => syscalls::decode_raw(syscall, arg_name, value),
+        }
+    }
+
+    fn quoted_bytes(pid: u32, address: u64, length: usize) -> SyscallArgValue {
+        let bytes = read(pid, address, length.min(MAX_DATA)).unwrap_or_default();
+        SyscallArgValue::Quoted {
+            text: strace::escape(&bytes),
+            truncated: bytes.len() < length,
+        }
+    }
+
+    fn quoted_string(pid: u32, address: u64) -> SyscallArgValue {
+        let mut bytes = Vec::new();
+        let mut address = address;
+        // a page at a time, since the next page may not be mapped
+        while bytes.len() < MAX_PATH {
+            let page_end = (address / 4096 + 1) * 4096;
+            let chunk = match read(pid, address, (page_end - address) as usize) {
+                Some(chunk) if !chunk.is_empty() => chunk,
+                _ => break,
+            };
+            if let Some(end) = chunk.iter().position(|b| *b == 0) {
+                bytes.extend_from_slice(&chunk[..end]);
+                return SyscallArgValue::Quoted {
+                    text: strace::escape(&bytes),
+                    truncated: false,
+                };
+            }
+            bytes.extend_from_slice(&chunk);
+            address = page_end;
+        }
+        SyscallArgValue::Quoted {
+            text: strace::escape(&bytes),
+            truncated: true,
+        }
+    }
+
+    // reads up to `length` bytes of the process's memory, stopping at the first unmapped page
+    fn read(pid: u32, address: u64, length: usize) -> Option<Vec<u8>> {
+        let mut buf = vec![0u8; length];
+        let n = process_vm_readv(
+            Pid::from_raw(pid as i32),
+            &mut [IoSliceMut::new(&mut buf)],
+            &[RemoteIoVec {
+                base: address as usize,
+                len: length,
+            }],
+        )
+        .ok()?;
+        buf.truncate(n);
+        Some(buf)
+    }
+
+    fn now_micros() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_micros() as u64)
+    }
+}
+
+#[cfg(all(test, target_os = "linux", target_arch = "x86_64"))]
+mod tests {
+    use std::process::ExitStatus;
+    use std::sync::mpsc;
+
+    use crate::strace::{ExitKind, Message, Outputs, Sink};
+    use crate::syscalls::{self, open_flags, syscall_name};
+
+    use super::{spawn, Options};
+
+    // runs `command` under the tracer, returning its exit status and the trace
+    fn trace(command: &[&str]) -> (ExitStatus, Vec<Message>) {
+        let (tx, rx) = mpsc::channel();
+        let sink = Sink::new(tx, Outputs::default(), None, None, None);
+        let options = Options {
+            command: command.iter().map(|arg| arg.to_string()).collect(),
+            follow: true,
+            exclude: Vec::new(),
+        };
+        let (_, thread) = spawn(options, sink).unwrap();
+        let status = thread.join().unwrap().unwrap();
+        (status, rx.try_iter().collect())
+    }
+
+    #[test]
+    fn test_trace() {
+        let (status, messages) = trace(&["true"]);
+        assert!(status.success());
+        let syscalls: Vec<_> = messages
+            .iter()
+            .filter_map(|msg| match msg {
+                Message::Syscall(syscall) => Some(syscall),
+                _ => None,
+            })
+            .collect();
+        // the command is seized before it execs, which execvp tries in each directory of PATH
+        let execs: Vec<_> = syscalls.iter().take_while(|s| s.name == "execve").collect();
+        assert_eq!(execs.last().unwrap().result(), Some(0));
+        assert!(execs.last().unwrap().to_string().contains("/true\""));
+
+        let last = syscalls.last().unwrap();
+        assert_eq!(last.name, "exit_group");
+        assert_eq!(last.result(), None);
+        assert!(last.to_string().ends_with("exit_group(0) = ?"));
+        assert!(matches!(
+            messages.last(),
+            Some(Message::Exit(exit)) if matches!(exit.status, ExitKind::Exited(0))
+        ));
+    }
+
+    #[test]
+    fn test_trace_stderr() {
+        // what the command writes to stderr does not reach vistrace's
+        let (status, _) = trace(&[
+            "sh",
+            "-c",
+            "echo oops >&2; [ \"$(readlink /proc/$$/fd/2)\" = /dev/null ]",
+        ]);
+        assert!(status.success());
+    }
+
+    #[test]
+    fn test_trace_exit_status() {
+        let (status, messages) = trace(&["sh", "-c", "exit 3"]);
+        assert_eq!(status.code(), Some(3));
+        assert!(matches!(
+            messages.last(),
+            Some(Message::Exit(exit)) if matches!(exit.status, ExitKind::Exited(3))
+        ));
+
+        assert!(spawn(
+            Options {
+                command: vec!["vistrace-no-such-command".to_string()],
+                follow: false,
+                exclude: Vec::new(),
+            },
+            Sink::new(mpsc::channel().0, Outputs::default(), None, None, None),
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_trace_args() {
+        let (_, messages) = trace(&["cat", "/dev/null"]);
+        let mut opened = false;
+        for msg in &messages {
+            let Message::Syscall(syscall) = msg else {
+                continue;
+            };
+            // no leftover registers are shown as arguments
+            let names = syscalls::arg_names(&syscall.name).unwrap_or_default();
+            assert!(syscall.args.len() <= names.len(), "{}", syscall);
+            opened |= syscall
+                .to_string()
+                .contains("openat(AT_FDCWD, \"/dev/null\", O_RDONLY");
+        }
+        assert!(opened);
+    }
+
+    #[test]
+    fn test_decode() {
+        assert_eq!(syscall_name(0), "read");
+        assert_eq!(syscall_name(257), "openat");
+        assert_eq!(syscall_name(231), "exit_group");
+        assert_eq!(syscall_name(9999), "syscall_0x270f");
+
+        let flags = |flags: i32| open_flags(flags).to_string();
+        assert_eq!(
+            flags(libc::O_RDONLY | libc::O_CLOEXEC),
+            "O_RDONLY|O_CLOEXEC"
+        );
+        assert_eq!(
+            flags(libc::O_WRONLY | libc::O_CREAT | libc::O_TRUNC),
+            "O_WRONLY|O_CREAT|O_TRUNC"
+        );
+        assert_eq!(flags(libc::O_RDWR | libc::O_TMPFILE), "O_RDWR|O_TMPFILE");
+        assert_eq!(flags(libc::O_RDONLY | 0o40000000), "O_RDONLY|040000000");
+    }
+}
@@ -0,0 +1,208 @@
+// Redaction (`--redact` when tracing, or `vistrace export --redact`), so that a trace can be
+// attached to a public bug report without giving away what the program read, wrote, or talked to:
+//
+//   - strings are masked, keeping their length, e.g. `write(1, "*****", 5)`
+//   - paths keep only their last component, e.g. `openat(AT_FDCWD, "id_rsa", O_RDONLY)`
+//   - IP addresses are replaced by made-up ones (in 10.0.0.0/8 or fd00::/16), the same one for
+//     each address throughout the trace
+//
+// Hostnames only appear in strings (DNS messages, command lines, and so on), so they are masked
+// along with them. The made-up addresses are hashed with a random key, so that the real ones cannot
+// be found by hashing every address; loopback and unspecified addresses are kept as they are.
+
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use crate::strace::{self, Message, Syscall, SyscallArgValue};
+use crate::syscalls;
+
+#[derive(Debug, Default)]
+pub struct Redactor {
+    key: RandomState,
+}
+
+impl Redactor {
+    pub fn redact(&self, msg: &mut Message) {
+        match msg {
+            Message::Syscall(syscall) => self.redact_syscall(syscall),
+            Message::Signal(signal) => {
+                for arg in signal.info.values_mut() {
+                    self.redact_value(&mut arg.value, false);
+                }
+            }
+            Message::Frame(frame) => frame.module = basename(&frame.module),
+            Message::LibCall(call) => {
+                call.caller = call.caller.as_deref().map(basename);
+                call.args = mask_quoted(&call.args);
+                call.return_value = mask_quoted(&call.return_value);
+            }
+            Message::Exit(_) | Message::Skipped(_) | Message::Notice(_) => {}
+        }
+    }
+
+    fn redact_syscall(&self, syscall: &mut Syscall) {
+        let names = syscalls::arg_names(&syscall.name);
+        for (i, arg) in syscall.args.iter_mut().enumerate() {
+            let name = names.and_then(|names| names.get(i)).copied();
+            let path = syscalls::is_path_arg(name.unwrap_or(&arg.name));
+            self.redact_value(&mut arg.value, path);
+        }
+        if let Some(returned) = &mut syscall.returned {
+            self.redact_value(returned, false);
+        }
+        // the line that could not be parsed
+        if let Some(details) = &mut syscall.error_details {
+            details.fulltext.clear();
+        }
+    }
+
+    fn redact_value(&self, value: &mut SyscallArgValue, path: bool) {
+        match value {
+            SyscallArgValue::Quoted { text, .. } if path => *text = basename(text),
+            SyscallArgValue::Quoted { text, .. } => {
+                *text = "*".repeat(strace::unescape(text).len())
+            }
+            SyscallArgValue::Array(args) => {
+                for arg in args {
+                    self.redact_value(&mut arg.value, false);
+                }
+            }
+            SyscallArgValue::Struct(fields) => {
+                for (name, arg) in fields {
+                    self.redact_value(&mut arg.value, name == "sun_path");
+                }
+            }
+            // e.g., `inet_addr("10.0.0.1")` or `inet_pton(AF_INET6, "::1", &sin6_addr)`
+            SyscallArgValue::FunctionCall(function, args)
+                if function == "inet_addr" || function == "inet_pton" =>
+            {
+                for arg in args {
+                    if let SyscallArgValue::Quoted { text, .. } = &mut arg.value {
+                        if let Ok(address) = text.parse() {
+                            *text = self.address(address).to_string();
+                        }
+                    }
+                }
+            }
+            SyscallArgValue::FunctionCall(_, args) => {
+                for arg in args {
+                    self.redact_value(&mut arg.value, false);
+                }
+            }
+            SyscallArgValue::Symbol(_)
+            | SyscallArgValue::FlagSet(_)
+            | SyscallArgValue::Number(_)
+            | SyscallArgValue::Product(_, _) => {}
+        }
+    }
+
+    // the made-up address that stands in for `address`
+    fn address(&self, address: IpAddr) -> IpAddr {
+        if address.is_loopback() || address.is_unspecified() {
+            return address;
+        }
+        let hash = self.key.hash_one(address).to_be_bytes();
+        match address {
+            IpAddr::V4(_) => IpAddr::V4(Ipv4Addr::new(10, hash[0], hash[1], hash[2])),
+            IpAddr::V6(_) => {
+                let mut octets = [0; 16];
+                octets[..2].copy_from_slice(&[0xfd, 0x00]);
+                octets[8..].copy_from_slice(&hash);
+                IpAddr::V6(Ipv6Addr::from(octets))
+            }
+        }
+    }
+}
+
+// e.g., "id_rsa" for "/home/me/.ssh/id_rsa"
+fn basename(path: &str) -> String {
+    match path.trim_end_matches('/').rsplit('/').next() {
+        Some("") | None if path.starts_with('/') => "/".to_string(),
+        Some(base) => base.to_string(),
+        None => String::new(),
+    }
+}
+
+// masks the strings in text that ltrace printed, e.g. `"*****", 5` for `"hello", 5`
+fn mask_quoted(text: &str) -> String {
+    let (mut masked, mut quoted, mut escaped) = (String::with_capacity(text.len()), false, false);
+    for c in text.chars() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if quoted => {
+                escaped = true;
+                masked.push('*');
+            }
+            '"' => {
+                quoted = !quoted;
+                masked.push(c);
+            }
+            _ if quoted => masked.push('*'),
+            _ => masked.push(c),
+        }
+    }
+    masked
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::strace::{LineParser, Message};
+
+    use super::Redactor;
+
+    #[test]
+    fn test_redact() {
+        let redactor = Redactor::default();
+        let mut parser = LineParser::default();
+        let mut redact = |line: &str| {
+            let mut msg = parser.parse_line(line).unwrap();
+            redactor.redact(&mut msg);
+            msg.to_string()
+        };
+
+        assert_eq!(
+            redact("10 openat(AT_FDCWD, \"/home/me/.ssh/id_rsa\", O_RDONLY) = 3"),
+            "10 openat(AT_FDCWD, \"id_rsa\", O_RDONLY) = 3"
+        );
+        assert_eq!(
+            redact("10 openat(AT_FDCWD, \"/\", O_RDONLY|O_DIRECTORY) = 3"),
+            "10 openat(AT_FDCWD, \"/\", O_RDONLY|O_DIRECTORY) = 3"
+        );
+        assert_eq!(
+            redact("10 write(1, \"token=s3cr\\n\"..., 64) = 64"),
+            "10 write(1, \"***********\"..., 64) = 64"
+        );
+        assert_eq!(
+            redact(
+                "10 execve(\"/usr/bin/curl\", [\"curl\", \"example.com\"], [\"HOME=/root\"]) = 0"
+            ),
+            "10 execve(\"curl\", [\"****\", \"***********\"], [\"**********\"]) = 0"
+        );
+        assert_eq!(
+            redact("10 ->getenv(\"HOME\") = \"/home/me\""),
+            "10 ->getenv(\"****\") = \"********\""
+        );
+
+        let connect = |ip: &str| {
+            format!(
+                "10 connect(3, {{sa_family=AF_INET, sin_port=htons(443), sin_addr=inet_addr(\"{}\")}}, 16) = 0",
+                ip
+            )
+        };
+        let first = redact(&connect("93.184.216.34"));
+        assert!(!first.contains("93.184.216.34"));
+        assert!(first.contains("inet_addr(\"10."));
+        assert_eq!(redact(&connect("93.184.216.34")), first);
+        assert_ne!(redact(&connect("93.184.216.35")), first);
+        assert!(redact(&connect("127.0.0.1")).contains("127.0.0.1"));
+
+        let mut frame = LineParser::default();
+        frame.parse_line("10 close(3) = 0");
+        let mut msg = frame
+            .parse_line(" > /home/me/build/libfoo.so(foo+0x14) [0x114887]")
+            .unwrap();
+        redactor.redact(&mut msg);
+        assert!(matches!(msg, Message::Frame(frame) if frame.module == "libfoo.so"));
+    }
+}
@@ -0,0 +1,256 @@
+// Streams a trace from a headless vistrace (`vistrace run --listen ADDRESS -- cmd`) to another one
+// that shows it (`vistrace connect ADDRESS`), e.g. to trace a program on a server and explore the
+// trace from a laptop.
+//
+// The address is "unix:PATH" for a unix socket, or "HOST:PORT" for TCP. The listening side waits
+// for one client before it starts tracing, so that nothing is missed, and sends it the magic bytes
+// "VSR", a version byte (currently 3), and then each line of strace output, preceded by its length
+// as a little-endian u32. Unlike a .vst file (see src/vst.rs), there is no parsed form of the
+// events (version 2 sent one, in the encoding of `--encoding`), since the client would need the
+// lines anyway: the parsed form leaves out stack frames, notices, and what strace printed after a
+// return value (e.g., the fds that poll found ready). The client parses the lines again, just as
+// the listening side did (see benches/encoding.rs).
+//
+// Anyone who can connect sees the whole trace, which may include secrets, so over a network,
+// prefer a unix socket forwarded over SSH (`ssh -L`) to a TCP port.
+
+use std::fmt;
+use std::fs;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::PathBuf;
+use std::sync::mpsc;
+
+use anyhow::{anyhow, Result};
+
+use crate::strace::{LineParser, Message};
+use crate::vst;
+
+const MAGIC: &[u8; 3] = b"VSR";
+const VERSION: u8 = 3;
+
+#[derive(Clone, Debug)]
+pub enum Address {
+    Unix(PathBuf),
+    Tcp(String),
+}
+
+pub enum Listener {
+    Unix(UnixListener, PathBuf),
+    Tcp(TcpListener),
+}
+
+/// The listening side's connection to its client.
+pub struct Sender {
+    stream: BufWriter<Box<dyn Write + Send>>,
+}
+
+/// The client's connection to the listening side.
+pub struct Receiver {
+    address: Address,
+    stream: BufReader<Box<dyn Read + Send>>,
+}
+
+/// Parses "unix:PATH" or "HOST:PORT".
+pub fn parse_address(text: &str) -> Result<Address> {
+    if let Some(path) = text.strip_prefix("unix:") {
+        if path.is_empty() {
+            return Err(anyhow!("expected a path after 'unix:'"));
+        }
+        Ok(Address::Unix(PathBuf::from(path)))
+    } else if text
+        .rsplit_once(':')
+        .is_some_and(|(_, port)| port.parse::<u16>().is_ok())
+    {
+        Ok(Address::Tcp(text.to_string()))
+    } else {
+        Err(anyhow!(
+            "expected 'unix:PATH' or 'HOST:PORT', e.g. 'unix:/tmp/vistrace.sock' or 'localhost:7000'"
+        ))
+    }
+}
+
+impl Listener {
+    pub fn bind(address: &Address) -> Result<Listener> {
+        let listener = match address {
+            Address::Unix(path) => {
+                UnixListener::bind(path).map(|l| Listener::Unix(l, path.clone()))
+            }
+            Address::Tcp(address) => TcpListener::bind(address).map(Listener::Tcp),
+        };
+        listener.map_err(|e| anyhow!("unable to listen on {}: {}", address, e))
+    }
+
+    /// Waits for a client to connect.
+    pub fn accept(self) -> Result<Sender> {
+        let stream: Box<dyn Write + Send> = match self {
+            Listener::Unix(listener, path) => {
+                let (stream, _) = listener.accept()?;
+                // only one client is served, so the socket is no longer needed
+                let _ = fs::remove_file(&path);
+                Box::new(stream)
+            }
+            Listener::Tcp(listener) => {
+                let (stream, peer) = listener.accept()?;
+                tracing::info!(%peer, "client connected");
+                // records are sent one at a time as strace prints them
+                stream.set_nodelay(true)?;
+                Box::new(stream)
+            }
+        };
+        let mut stream = BufWriter::new(stream);
+        stream.write_all(MAGIC)?;
+        stream.write_all(&[VERSION])?;
+        Ok(Sender { stream })
+    }
+}
+
+impl Sender {
+    /// Sends a line of strace output.
+    pub fn write(&mut self, line: &str) -> io::Result<()> {
+        vst::write_bytes(&mut self.stream, line.as_bytes())?;
+        self.stream.flush()
+    }
+}
+
+impl Receiver {
+    /// Connects to a vistrace that is listening at `address`.
+    pub fn connect(address: &Address) -> Result<Receiver> {
+        let stream: Box<dyn Read + Send> = match address {
+            Address::Unix(path) => UnixStream::connect(path).map(|s| Box::new(s) as _),
+            Address::Tcp(address) => TcpStream::connect(address).map(|s| Box::new(s) as _),
+        }
+        .map_err(|e| anyhow!("unable to connect to {}: {}", address, e))?;
+        let mut stream = BufReader::new(stream);
+
+        let mut header = [0u8; 4];
+        stream.read_exact(&mut header)?;
+        if &header[..3] != MAGIC {
+            return Err(anyhow!(
+                "{} is not a vistrace listening with --listen",
+                address
+            ));
+        }
+        if header[3] != VERSION {
+            return Err(anyhow!(
+                "{} streams version {} of the protocol, but this vistrace only understands version {}",
+                address,
+                header[3],
+                VERSION
+            ));
+        }
+        Ok(Receiver {
+            address: address.clone(),
+            stream,
+        })
+    }
+
+    /// Sends every message in the trace, as parsed by `parser`, to `tx`, until the trace is over.
+    pub fn receive(mut self, mut parser: LineParser, tx: mpsc::Sender<Message>) -> Result<()> {
+        loop {
+            let line = match vst::read_bytes(&mut self.stream) {
+                Ok(line) => line,
+                // the trace is over
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+                Err(e) => return Err(self.error(e)),
+            };
+            if let Some(msg) = parser.parse_line(&String::from_utf8_lossy(&line)) {
+                tx.send(msg).map_err(|e| anyhow!("transmit error: {}", e))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn error(&self, e: io::Error) -> anyhow::Error {
+        match e.kind() {
+            io::ErrorKind::InvalidData => {
+                anyhow!("{} sent a malformed record: {}", self.address, e)
+            }
+            _ => anyhow!("lost the connection to {}: {}", self.address, e),
+        }
+    }
+}
+
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Address::Unix(path) => write!(f, "unix:{}", path.display()),
+            Address::Tcp(address) => write!(f, "{}", address),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use std::os::unix::net::UnixListener;
+    use std::sync::mpsc;
+    use std::thread;
+
+    use crate::strace::LineParser;
+
+    use super::{parse_address, Address, Listener, Receiver, MAGIC, VERSION};
+
+    #[test]
+    fn test_round_trip() {
+        let path = std::env::temp_dir().join(format!("vistrace-test-{}.sock", std::process::id()));
+        let address = Address::Unix(path.clone());
+        let listener = Listener::bind(&address).unwrap();
+
+        let (tx, rx) = mpsc::channel();
+        let client = thread::spawn(move || {
+            Receiver::connect(&address)
+                .and_then(|receiver| receiver.receive(LineParser::default(), tx))
+        });
+        let mut sender = listener.accept().unwrap();
+        for line in [
+            "10 1720000000.000001 read(3,  <unfinished ...>\n",
+            "11 1720000000.000002 close(4) = 0 <0.000001>\n",
+            "10 1720000000.000003 <... read resumed>\"abc\", 4096) = 3 <0.000002>\n",
+        ] {
+            sender.write(line).unwrap();
+        }
+        drop(sender);
+        client.join().unwrap().unwrap();
+
+        let messages: Vec<String> = rx.iter().map(|msg| msg.to_string()).collect();
+        assert_eq!(
+            messages,
+            [
+                "11 1720000000.000002 close(4) = 0 <0.000001>",
+                "10 1720000000.000001 read(3, \"abc\", 4096) = 3 <0.000002>",
+            ]
+        );
+        assert!(!path.exists());
+
+        assert!(matches!(
+            parse_address("localhost:7000"),
+            Ok(Address::Tcp(_))
+        ));
+        assert!(matches!(parse_address("[::1]:7000"), Ok(Address::Tcp(_))));
+        assert!(parse_address("localhost").is_err());
+        assert!(parse_address("unix:").is_err());
+    }
+
+    #[test]
+    fn test_bogus_length() {
+        let path =
+            std::env::temp_dir().join(format!("vistrace-test-bogus-{}.sock", std::process::id()));
+        let listener = UnixListener::bind(&path).unwrap();
+        let address = Address::Unix(path.clone());
+        let client = thread::spawn(move || {
+            Receiver::connect(&address)
+                .and_then(|receiver| receiver.receive(LineParser::default(), mpsc::channel().0))
+        });
+        let (mut stream, _) = listener.accept().unwrap();
+        std::fs::remove_file(&path).unwrap();
+        stream.write_all(MAGIC).unwrap();
+        stream.write_all(&[VERSION]).unwrap();
+        // a 4 GiB line, which is not allocated
+        stream.write_all(&u32::MAX.to_le_bytes()).unwrap();
+        drop(stream);
+        let err = client.join().unwrap().unwrap_err().to_string();
+        assert!(err.contains("sent a malformed record"), "{}", err);
+    }
+}
@@ -0,0 +1,403 @@
+// Rules from vistrace's config file, which the interface applies to events as they arrive: alerts
+// that highlight matching events, ring the terminal bell, log them, pause the interface at them, or
+// stop the process that made them, named filters that can be picked instead of typed out, and
+// layouts of the interface's panes.
+//
+// The config file is ~/.config/vistrace/config (or $XDG_CONFIG_HOME/vistrace/config), unless
+// --config says otherwise, and is reloaded with 'r'. Each line is one of:
+//
+//   alert ACTIONS FILTER     act on events that pass the filter, where ACTIONS is a comma-separated
+//                            list of highlight, beep, log (to --log-file), pause, and stop
+//   highlight FILTER         the same as `alert highlight FILTER`
+//   break FILTER             the same as `alert stop FILTER`
+//   filter NAME FILTER       a named filter, which --preset NAME starts the interface with
+//   layout NAME PANES        a layout of the panes (see src/layout.rs)
+//
+// FILTER is a filter expression as typed into the interface (see src/filter.rs), e.g.
+//
+//   alert beep,pause path=^/etc/shadow$
+//   highlight errno=EACCES
+//   break connect port=5432
+//   filter sqlite path=\.sqlite(-wal)?$
+//
+// Blank lines and lines that start with '#' are ignored. The filter dialog ('/') saves a filter
+// under a name, in place of the filter of that name if there is one, so that it can be used in
+// later sessions, or by others who are given the file.
+//
+// `stop` is a breakpoint: it pauses the interface and also stops the process, until the interface
+// is resumed with 'p'. If the filter picks out syscalls by name alone (e.g., `break connect`),
+// strace itself sends SIGSTOP as the process makes the syscall (`-e
+// inject=connect:signal=SIGSTOP`), so the process stops as the syscall returns, before it runs any
+// further. strace cannot pick syscalls by their arguments, so for any other filter (e.g., `break
+// connect port=5432`), the interface falls back to sending SIGSTOP once it has seen the syscall, by
+// which time the process has run on; the status line says so. Breakpoints that are added by
+// reloading the rules fall back in the same way until the trace is restarted. Processes that are
+// traced over ssh, or that are in a saved trace, cannot be stopped.
+
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Result};
+
+use crate::filter::Filter;
+use crate::inject::Injection;
+use crate::layout::Layout;
+use crate::strace::Syscall;
+use crate::syscalls;
+
+#[derive(Default)]
+pub struct Rules {
+    // the file given with --config, if any, to reload the rules from
+    pub path: Option<PathBuf>,
+    pub alerts: Vec<Alert>,
+    // names and filter expressions, in the order they were defined
+    pub filters: Vec<(String, String)>,
+    pub layouts: Vec<Layout>,
+}
+
+pub struct Alert {
+    pub filter: Filter,
+    pub actions: Vec<Action>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Action {
+    Highlight,
+    Beep,
+    Log,
+    Pause,
+    Stop,
+}
+
+impl Rules {
+    /// Loads the rules from `path`, or else from the default config file, if it exists.
+    pub fn load(path: Option<&Path>) -> Result<Rules> {
+        let (file, required) = match path {
+            Some(path) => (path.to_path_buf(), true),
+            None => match default_path() {
+                Some(path) => (path, false),
+                None => return Ok(Rules::default()),
+            },
+        };
+        let text = match fs::read_to_string(&file) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound && !required => {
+                return Ok(Rules::default())
+            }
+            Err(e) => return Err(anyhow!("unable to read {}: {}", file.display(), e)),
+        };
+        let mut rules = Rules::parse(&text).map_err(|e| anyhow!("in {}: {}", file.display(), e))?;
+        rules.path = path.map(Path::to_path_buf);
+        Ok(rules)
+    }
+
+    pub fn parse(text: &str) -> Result<Rules> {
+        let mut rules = Rules::default();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            rules
+                .parse_line(line)
+                .map_err(|e| anyhow!("line {}: {}", i + 1, e))?;
+        }
+        Ok(rules)
+    }
+
+    fn parse_line(&mut self, line: &str) -> Result<()> {
+        let (kind, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let rest = rest.trim_start();
+        let (actions, filter) = match kind {
+            "alert" => {
+                let (actions, filter) = rest
+                    .split_once(char::is_whitespace)
+                    .ok_or(anyhow!("expected `alert ACTIONS FILTER`"))?;
+                let actions = actions
+                    .split(',')
+                    .map(Action::parse)
+                    .collect::<Result<Vec<_>>>()?;
+                (actions, filter)
+            }
+            "highlight" => (vec![Action::Highlight], rest),
+            "break" => (vec![Action::Stop], rest),
+            "filter" => {
+                let (name, filter) = rest
+                    .split_once(char::is_whitespace)
+                    .ok_or(anyhow!("expected `filter NAME FILTER`"))?;
+                // check it now, rather than when it is picked
+                Filter::parse(filter).map_err(|e| anyhow!("invalid filter: {}", e))?;
+                self.filters
+                    .push((name.to_string(), filter.trim().to_string()));
+                return Ok(());
+            }
+            "layout" => {
+                self.layouts.push(Layout::parse(rest)?);
+                return Ok(());
+            }
+            _ => return Err(anyhow!("unknown rule: {:?}", kind)),
+        };
+        let filter = Filter::parse(filter).map_err(|e| anyhow!("invalid filter: {}", e))?;
+        // an empty filter would match every event
+        if filter.is_empty() {
+            return Err(anyhow!("missing filter"));
+        }
+        self.alerts.push(Alert { filter, actions });
+        Ok(())
+    }
+
+    /// The filter expression named `name`, the last one if there are several.
+    pub fn named_filter(&self, name: &str) -> Option<&str> {
+        self.filters
+            .iter()
+            .rev()
+            .find(|(n, _)| n == name)
+            .map(|(_, filter)| filter.as_str())
+    }
+
+    /// Saves `filter` in the config file as the named filter `name`, and adds it to the rules.
+    /// Returns the path of the config file.
+    pub fn save_filter(&mut self, name: &str, filter: &str) -> Result<PathBuf> {
+        let filter = filter.trim();
+        if name.is_empty() || name.contains(char::is_whitespace) {
+            return Err(anyhow!("a filter's name must be one word"));
+        }
+        if Filter::parse(filter)?.is_empty() {
+            return Err(anyhow!("missing filter"));
+        }
+        let file = self
+            .path
+            .clone()
+            .or_else(default_path)
+            .ok_or(anyhow!("no config file (HOME is not set)"))?;
+        let text = match fs::read_to_string(&file) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(anyhow!("unable to read {}: {}", file.display(), e)),
+        };
+        if let Some(dir) = file.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(&file, with_filter(&text, name, filter))
+            .map_err(|e| anyhow!("unable to write {}: {}", file.display(), e))?;
+
+        self.filters.retain(|(n, _)| n != name);
+        self.filters.push((name.to_string(), filter.to_string()));
+        Ok(file)
+    }
+
+    /// Returns the alerts (as indices into `alerts`) that `syscall` matched. Must be called on
+    /// every event in order, as for `Filter::matches`.
+    pub fn check(&mut self, index: usize, syscall: &Syscall) -> Vec<usize> {
+        self.alerts
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(i, alert)| alert.filter.matches(index, syscall).then_some(i))
+            .collect()
+    }
+
+    /// The faults that make strace stop processes at breakpoints, for the alerts that
+    /// `Alert::stop_injection` gives one.
+    pub fn stop_injections(&self) -> Vec<Injection> {
+        self.alerts
+            .iter()
+            .filter_map(Alert::stop_injection)
+            .collect()
+    }
+
+    /// Clears any state accumulated by `check`.
+    pub fn reset(&mut self) {
+        for alert in &mut self.alerts {
+            alert.filter.reset();
+        }
+    }
+}
+
+fn default_path() -> Option<PathBuf> {
+    let dir = match env::var_os("XDG_CONFIG_HOME").filter(|dir| !dir.is_empty()) {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(env::var_os("HOME")?).join(".config"),
+    };
+    Some(dir.join("vistrace").join("config"))
+}
+
+// Sets the named filter `name` in the text of a config file, keeping its other lines as they are.
+fn with_filter(text: &str, name: &str, filter: &str) -> String {
+    let line = format!("filter {} {}", name, filter);
+    let mut found = false;
+    let mut lines: Vec<&str> = Vec::new();
+    for current in text.lines() {
+        let mut words = current.split_whitespace();
+        if (words.next(), words.next()) != (Some("filter"), Some(name)) {
+            lines.push(current);
+        } else if !found {
+            // the first definition is replaced, and any others dropped
+            found = true;
+            lines.push(&line);
+        }
+    }
+    if !found {
+        lines.push(&line);
+    }
+    let mut text = lines.join("\n");
+    text.push('\n');
+    text
+}
+
+impl Alert {
+    /// The fault that makes strace stop a process just after the syscalls that this breakpoint
+    /// stops at, if it is a breakpoint whose filter strace can express.
+    pub fn stop_injection(&self) -> Option<Injection> {
+        if !self.actions.contains(&Action::Stop) {
+            return None;
+        }
+        let names = self.filter.names_only()?;
+        // strace refuses to start with a name that it does not know
+        if !names.iter().all(|name| syscalls::arg_names(name).is_some()) {
+            return None;
+        }
+        Some(Injection {
+            syscalls: names.to_vec(),
+            params: vec![("signal".to_string(), "SIGSTOP".to_string())],
+        })
+    }
+}
+
+impl Action {
+    fn parse(word: &str) -> Result<Action> {
+        match word {
+            "highlight" => Ok(Action::Highlight),
+            "beep" => Ok(Action::Beep),
+            "log" => Ok(Action::Log),
+            "pause" => Ok(Action::Pause),
+            "stop" => Ok(Action::Stop),
+            _ => Err(anyhow!("unknown action: {:?}", word)),
+        }
+    }
+}
+
+impl fmt::Display for Alert {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let actions: Vec<&str> = self
+            .actions
+            .iter()
+            .map(|action| match action {
+                Action::Highlight => "highlight",
+                Action::Beep => "beep",
+                Action::Log => "log",
+                Action::Pause => "pause",
+                Action::Stop => "stop",
+            })
+            .collect();
+        write!(f, "alert {} {}", actions.join(","), self.filter.text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::strace::parse_syscall;
+
+    use super::{with_filter, Action, Rules};
+
+    #[test]
+    fn test_rules() {
+        let mut rules = Rules::parse(
+            "# comment\n\
+             \n\
+             alert beep,pause path=^/etc/shadow$\n\
+             highlight errno=EACCES\n\
+             break connect port=5432\n\
+             filter sqlite   path=\\.sqlite(-wal)?$\n\
+             layout wide 40 stats | events\n",
+        )
+        .unwrap();
+        assert_eq!(rules.alerts.len(), 3);
+        assert_eq!(rules.alerts[2].to_string(), "alert stop connect port=5432");
+        assert_eq!(rules.alerts[0].actions, [Action::Beep, Action::Pause]);
+        assert_eq!(rules.alerts[1].to_string(), "alert highlight errno=EACCES");
+        assert_eq!(
+            rules.filters,
+            [("sqlite".to_string(), "path=\\.sqlite(-wal)?$".to_string())]
+        );
+        assert_eq!(rules.layouts[0].name, "wide");
+        assert_eq!(rules.named_filter("sqlite"), Some("path=\\.sqlite(-wal)?$"));
+        assert_eq!(rules.named_filter("network-only"), None);
+
+        assert_eq!(
+            with_filter("# mine\nfilter net %network\n", "net", "connect,sendto"),
+            "# mine\nfilter net connect,sendto\n"
+        );
+        assert_eq!(
+            with_filter("highlight failed", "net", "%network"),
+            "highlight failed\nfilter net %network\n"
+        );
+        assert_eq!(with_filter("", "net", "%network"), "filter net %network\n");
+
+        let path =
+            std::env::temp_dir().join(format!("vistrace-test-{}.config", std::process::id()));
+        rules.path = Some(path.clone());
+        assert!(rules.save_filter("two words", "close").is_err());
+        assert!(rules.save_filter("empty", " ").is_err());
+        rules.save_filter("sqlite", "path=\\.db$").unwrap();
+        let saved = Rules::load(Some(&path)).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(saved.filters, rules.filters);
+        assert_eq!(rules.named_filter("sqlite"), Some("path=\\.db$"));
+
+        let lines = [
+            "openat(AT_FDCWD, \"/etc/passwd\", O_RDONLY) = 3",
+            "openat(AT_FDCWD, \"/etc/shadow\", O_RDONLY) = -1 EACCES (Permission denied)",
+            "openat(AT_FDCWD, \"/etc/shadow-\", O_RDONLY) = -1 EACCES (Permission denied)",
+        ];
+        let matched: Vec<Vec<usize>> = lines
+            .iter()
+            .enumerate()
+            .map(|(i, line)| rules.check(i, &parse_syscall(line, false)))
+            .collect();
+        assert_eq!(matched, [vec![], vec![0, 1], vec![1]]);
+
+        let err = |text: &str| Rules::parse(text).err().unwrap().to_string();
+        assert_eq!(
+            err("highlight failed\nalert ring failed"),
+            "line 2: unknown action: \"ring\""
+        );
+        assert_eq!(err("highlight"), "line 1: missing filter");
+        assert_eq!(err("watch x"), "line 1: unknown rule: \"watch\"");
+        assert!(err("filter bad fd=x").starts_with("line 1: invalid filter"));
+    }
+
+    #[test]
+    fn test_stop_injections() {
+        let rules = Rules::parse(
+            "break connect,sendto
+             break connect port=5432
+             alert pause,beep openat
+             alert stop,log execve
+             break not_a_syscall
+",
+        )
+        .unwrap();
+        let injections: Vec<Option<String>> = rules
+            .alerts
+            .iter()
+            .map(|alert| alert.stop_injection().map(|i| i.to_string()))
+            .collect();
+        assert_eq!(
+            injections,
+            [
+                Some("connect,sendto:signal=SIGSTOP".to_string()),
+                // strace cannot pick out a port
+                None,
+                // not a breakpoint
+                None,
+                Some("execve:signal=SIGSTOP".to_string()),
+                // strace would refuse to start
+                None,
+            ]
+        );
+        assert_eq!(rules.stop_injections().len(), 2);
+    }
+}
@@ -0,0 +1,187 @@
+// Sampling (`--sample`), for programs that make so many syscalls that tracing every one of them
+// takes too long or too much memory, e.g. busy servers. `--sample 1/100` keeps one in every 100
+// calls of each syscall, and `--sample read,write=1/1000` one in every 1000 reads and writes; rates
+// for syscall names and `%category`s can be given along with one for everything, and take
+// precedence over it. The first call of each syscall is always kept, and the rate for everything
+// does not apply to %process syscalls, so that the process tree is complete.
+//
+// The calls that are left out are still counted: before the next call of the same syscall that is
+// kept (or at the end of the trace), a `Message::Skipped` says how many were left out, how many of
+// them failed, and how long they took, so that the statistics of the trace are exact.
+
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+use anyhow::{anyhow, Result};
+
+use crate::strace::{self, Skipped, Syscall};
+use crate::syscalls;
+
+/// One rate of `--sample`.
+#[derive(Clone, Debug)]
+pub struct Rate {
+    // names and `%category`s, or empty for every syscall
+    syscalls: Vec<String>,
+    // keep one call in every this many
+    every: u64,
+}
+
+impl Rate {
+    /// Parses a rate, e.g. "1/100" or "read,%memory=1/100".
+    pub fn parse(text: &str) -> Result<Rate> {
+        let (syscalls, rate) = match text.split_once('=') {
+            Some((syscalls, rate)) => {
+                let syscalls: Vec<String> = syscalls.split(',').map(str::to_string).collect();
+                (strace::parse_exclude(&syscalls)?, rate)
+            }
+            None => (Vec::new(), text),
+        };
+        let every = rate
+            .trim()
+            .strip_prefix("1/")
+            .and_then(|n| n.parse().ok())
+            .filter(|&n| n > 0)
+            .ok_or_else(|| {
+                anyhow!(
+                    "expected a rate like '1/100' or 'read=1/100', not {:?}",
+                    text
+                )
+            })?;
+        Ok(Rate { syscalls, every })
+    }
+}
+
+pub enum Sample {
+    // with the calls of the same syscall that were left out before it, if any
+    Keep(Option<Skipped>),
+    Skip,
+}
+
+pub struct Sampler {
+    rates: Vec<Rate>,
+    // the calls of each syscall so far, and those that were left out since the last that was kept
+    calls: Mutex<BTreeMap<String, (u64, Option<Skipped>)>>,
+}
+
+impl Sampler {
+    pub fn new(rates: Vec<Rate>) -> Sampler {
+        Sampler {
+            rates,
+            calls: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    // keep one call in every this many of `name`
+    fn every(&self, name: &str) -> u64 {
+        let named = self
+            .rates
+            .iter()
+            .find(|r| !r.syscalls.is_empty() && strace::excludes(&r.syscalls, name));
+        match named {
+            Some(rate) => rate.every,
+            None if syscalls::in_category(name, "process") => 1,
+            None => self
+                .rates
+                .iter()
+                .find(|r| r.syscalls.is_empty())
+                .map_or(1, |r| r.every),
+        }
+    }
+
+    pub fn sample(&self, syscall: &Syscall) -> Sample {
+        let every = self.every(&syscall.name);
+        let mut calls = self.calls.lock().unwrap();
+        let (seen, skipped) = calls.entry(syscall.name.clone()).or_default();
+        *seen += 1;
+        if (*seen - 1) % every == 0 {
+            return Sample::Keep(skipped.take());
+        }
+        let skipped = skipped.get_or_insert_with(|| Skipped {
+            name: syscall.name.clone(),
+            calls: 0,
+            errors: 0,
+            total_micros: 0,
+        });
+        skipped.calls += 1;
+        if syscall.errno.is_some() {
+            skipped.errors += 1;
+        }
+        skipped.total_micros += syscall.syscall_time_micros.unwrap_or(0);
+        Sample::Skip
+    }
+
+    /// Returns the calls that were left out since the last ones that were kept, at the end of the
+    /// trace.
+    pub fn finish(&self) -> Vec<Skipped> {
+        let mut calls = self.calls.lock().unwrap();
+        calls
+            .values_mut()
+            .filter_map(|(_, skipped)| skipped.take())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::strace::{parse_syscall, LineParser, Message};
+
+    use super::{Rate, Sample, Sampler};
+
+    #[test]
+    fn test_sample() {
+        assert!(Rate::parse("2/3").is_err());
+        assert!(Rate::parse("1/0").is_err());
+        assert!(Rate::parse("%nope=1/10").is_err());
+
+        let sampler = Sampler::new(vec![
+            Rate::parse("1/3").unwrap(),
+            Rate::parse("write=1/1").unwrap(),
+        ]);
+        let mut kept = Vec::new();
+        for line in [
+            "read(3, \"\", 10) = 0 <0.000010>",
+            "read(3, \"\", 10) = -1 EAGAIN (Resource temporarily unavailable) <0.000020>",
+            "read(3, \"\", 10) = 0 <0.000030>",
+            "write(1, \"x\", 1) = 1",
+            "write(1, \"x\", 1) = 1",
+            "clone(child_stack=NULL, flags=SIGCHLD) = 11",
+            "clone(child_stack=NULL, flags=SIGCHLD) = 12",
+            "read(3, \"\", 10) = 0",
+            "read(3, \"\", 10) = 0 <0.000005>",
+        ] {
+            let syscall = parse_syscall(&format!("1720000000.000000 {}", line), true);
+            match sampler.sample(&syscall) {
+                Sample::Keep(skipped) => {
+                    kept.extend(skipped.map(|s| Message::Skipped(s).to_string()));
+                    kept.push(syscall.name);
+                }
+                Sample::Skip => {}
+            }
+        }
+        kept.extend(
+            sampler
+                .finish()
+                .into_iter()
+                .map(|s| Message::Skipped(s).to_string()),
+        );
+        assert_eq!(
+            kept,
+            [
+                "read",
+                "write",
+                "write",
+                "clone",
+                "clone",
+                "+++ skipped 2 read (1 failed) <0.000050> +++",
+                "read",
+                "+++ skipped 1 read <0.000005> +++",
+            ]
+        );
+
+        // saved traces are read back by strace's parser
+        assert!(matches!(
+            LineParser::default().parse_line(&kept[5]),
+            Some(Message::Skipped(s)) if s.name == "read" && s.calls == 2 && s.errors == 1 && s.total_micros == 50
+        ));
+    }
+}
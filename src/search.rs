@@ -0,0 +1,170 @@
+// Spots a process looking for a file in one directory after another, as the dynamic loader does
+// for libraries and shells do for commands, and sums each search up as e.g. "looked for
+// libfoo.so.3 in 7 directories, all ENOENT", which is usually why a program fails to start.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::strace::Syscall;
+use crate::syscalls;
+
+// the syscalls that look a path up, which a search may mix (e.g., stat and then openat)
+const LOOKUPS: &[&str] = &[
+    "open",
+    "openat",
+    "openat2",
+    "stat",
+    "lstat",
+    "newfstatat",
+    "statx",
+    "access",
+    "faccessat",
+    "faccessat2",
+    "execve",
+    "execveat",
+];
+
+#[derive(Default)]
+pub struct Searches {
+    pub searches: Vec<Search>,
+    // the search that each process is in the middle of, by index into `searches`
+    current: HashMap<Option<u32>, usize>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Search {
+    pub pid: Option<u32>,
+    // e.g., "libfoo.so.3"
+    pub name: String,
+    // the directories where the file was not found, in order
+    pub misses: Vec<String>,
+    // the directory where it was found, if it was
+    pub found: Option<String>,
+}
+
+impl Searches {
+    /// Returns the index into `searches` of the search that `syscall` is part of, if any.
+    pub fn update(&mut self, syscall: &Syscall) -> Option<usize> {
+        if !LOOKUPS.contains(&syscall.name.as_str()) || syscall.error_details.is_some() {
+            return None;
+        }
+        let path = syscalls::path_args(syscall).into_iter().next()?;
+        let (dir, name) = match path.rsplit_once('/') {
+            Some(("", name)) => ("/", name),
+            Some((dir, name)) => (dir, name),
+            None => (".", path.as_str()),
+        };
+
+        let current = self
+            .current
+            .get(&syscall.pid)
+            .copied()
+            .filter(|i| self.searches[*i].name == name);
+        match (syscall.errno.as_deref(), current) {
+            (Some("ENOENT"), Some(index)) => {
+                let search = &mut self.searches[index];
+                // e.g., a stat and then an open of the same path
+                if search.misses.last().map(String::as_str) != Some(dir) {
+                    search.misses.push(dir.to_string());
+                }
+                Some(index)
+            }
+            (Some("ENOENT"), None) => {
+                self.searches.push(Search {
+                    pid: syscall.pid,
+                    name: name.to_string(),
+                    misses: vec![dir.to_string()],
+                    found: None,
+                });
+                let index = self.searches.len() - 1;
+                self.current.insert(syscall.pid, index);
+                Some(index)
+            }
+            (None, Some(index)) => {
+                self.current.remove(&syscall.pid);
+                let search = &mut self.searches[index];
+                // a stat after a miss in the same directory is not a find
+                if search.misses.last().map(String::as_str) == Some(dir) {
+                    return Some(index);
+                }
+                search.found = Some(dir.to_string());
+                Some(index)
+            }
+            // some other error ends the search, but lookups of other files in between (e.g., of
+            // the directories themselves) do not
+            (Some(_), Some(_)) => {
+                self.current.remove(&syscall.pid);
+                None
+            }
+            (_, None) => None,
+        }
+    }
+}
+
+impl Search {
+    /// Whether the file was looked for in enough places to be worth reporting.
+    pub fn is_interesting(&self) -> bool {
+        self.misses.len() >= 2
+    }
+}
+
+impl fmt::Display for Search {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let misses = self.misses.len();
+        let s = if misses == 1 { "y" } else { "ies" };
+        match &self.found {
+            Some(dir) => write!(
+                f,
+                "found {} in {} after {} missing director{}",
+                self.name, dir, misses, s
+            ),
+            None => write!(
+                f,
+                "looked for {} in {} director{}, all ENOENT",
+                self.name, misses, s
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::strace::parse_syscall;
+
+    use super::Searches;
+
+    #[test]
+    fn test_searches() {
+        let mut searches = Searches::default();
+        let indices: Vec<Option<usize>> = [
+            "openat(AT_FDCWD, \"/opt/app/lib/libfoo.so.3\", O_RDONLY|O_CLOEXEC) = -1 ENOENT (No such file or directory)",
+            "newfstatat(AT_FDCWD, \"/opt/app/lib\", 0x7ffc, 0) = 0",
+            "openat(AT_FDCWD, \"/usr/lib/libfoo.so.3\", O_RDONLY|O_CLOEXEC) = -1 ENOENT (No such file or directory)",
+            "openat(AT_FDCWD, \"/lib/libfoo.so.3\", O_RDONLY|O_CLOEXEC) = -1 ENOENT (No such file or directory)",
+            "stat(\"/usr/local/bin/git\", 0x7ffc) = -1 ENOENT (No such file or directory)",
+            "stat(\"/usr/bin/git\", 0x7ffc) = 0",
+            "openat(AT_FDCWD, \"/etc/hosts\", O_RDONLY) = 3",
+        ]
+        .iter()
+        .map(|line| searches.update(&parse_syscall(line, false)))
+        .collect();
+        assert_eq!(
+            indices,
+            [Some(0), None, Some(0), Some(0), Some(1), Some(1), None]
+        );
+
+        let summaries: Vec<String> = searches.searches.iter().map(|s| s.to_string()).collect();
+        assert_eq!(
+            summaries,
+            [
+                "looked for libfoo.so.3 in 3 directories, all ENOENT",
+                "found git in /usr/bin after 1 missing directory",
+            ]
+        );
+        assert_eq!(
+            searches.searches[0].misses,
+            ["/opt/app/lib", "/usr/lib", "/lib"]
+        );
+        assert!(!searches.searches[1].is_interesting());
+    }
+}
@@ -0,0 +1,277 @@
+// Builds a seccomp profile, in the JSON format that Docker and other OCI runtimes accept, that
+// allows only the syscalls seen in a trace and fails every other one with EPERM.
+//
+// A few syscalls are further restricted by their arguments, where the trace shows that the
+// program only needs the safe uses of them:
+//
+//   socket         only the address families that were used
+//   personality    only the personas that were used
+//   clone          no new namespaces, unless the program created one
+//
+// A profile built from one run is only as complete as that run: code paths that the run did not
+// exercise (error handling, say) may need syscalls that are missing.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::json::Json;
+use crate::strace::{Syscall, SyscallArgValue};
+use crate::syscalls;
+
+// the namespace flags for clone, as masked by Docker's default profile
+const CLONE_NAMESPACES: i64 = 0x7e020000;
+const NAMESPACES: &[&str] = &[
+    "CLONE_NEWNS",
+    "CLONE_NEWUTS",
+    "CLONE_NEWIPC",
+    "CLONE_NEWUSER",
+    "CLONE_NEWPID",
+    "CLONE_NEWNET",
+    "CLONE_NEWCGROUP",
+];
+
+#[derive(Default)]
+pub struct Profile {
+    // every syscall seen, with whether any call did not fail with ENOSYS (a program may probe for
+    // a syscall and fall back if the kernel lacks it)
+    syscalls: BTreeMap<String, bool>,
+    socket_domains: Constraint,
+    personas: Constraint,
+    clone_namespaces: bool,
+}
+
+// the values seen for an argument, or `None` once a value that cannot be compared was seen
+struct Constraint(Option<BTreeSet<i64>>);
+
+impl Default for Constraint {
+    fn default() -> Self {
+        Constraint(Some(BTreeSet::new()))
+    }
+}
+
+impl Profile {
+    pub fn update(&mut self, syscall: &Syscall) {
+        if syscall.error_details.is_some() {
+            return;
+        }
+        let needed = syscall.errno.as_deref() != Some("ENOSYS");
+        *self.syscalls.entry(syscall.name.clone()).or_default() |= needed;
+
+        let arg = |i: usize| syscall.arg(i).map(|a| &a.value);
+        match syscall.name.as_str() {
+            "socket" | "socketpair" => self.socket_domains.add(arg(0), socket_domain),
+            "personality" => self.personas.add(arg(0), persona),
+            "clone" | "clone3" => {
+                self.clone_namespaces |= NAMESPACES
+                    .iter()
+                    .any(|ns| syscalls::has_clone_flag(syscall, ns));
+            }
+            "unshare" => {
+                self.clone_namespaces |= NAMESPACES
+                    .iter()
+                    .any(|ns| arg(0).is_some_and(|v| syscalls::value_has_flag(v, ns)));
+            }
+            _ => {}
+        }
+    }
+
+    pub fn to_json(&self) -> Json {
+        let names: Vec<&str> = self
+            .syscalls
+            .iter()
+            .filter(|(name, needed)| **needed && !self.is_constrained(name))
+            .map(|(name, _)| name.as_str())
+            .collect();
+        let mut rules = Vec::new();
+        if !names.is_empty() {
+            rules.push(allow(&names, Vec::new()));
+        }
+
+        let mut constrain = |name: &str, constraint: &Constraint| {
+            if let Some(values) = &constraint.0 {
+                for value in values {
+                    rules.push(allow(&[name], vec![compare(0, *value, None)]));
+                }
+            }
+        };
+        if self.syscalls.get("socket") == Some(&true) {
+            constrain("socket", &self.socket_domains);
+        }
+        if self.syscalls.get("socketpair") == Some(&true) {
+            constrain("socketpair", &self.socket_domains);
+        }
+        if self.syscalls.get("personality") == Some(&true) {
+            constrain("personality", &self.personas);
+        }
+        if self.syscalls.get("clone") == Some(&true) && !self.clone_namespaces {
+            rules.push(allow(
+                &["clone"],
+                vec![compare(0, 0, Some(CLONE_NAMESPACES))],
+            ));
+        }
+
+        Json::object([
+            ("defaultAction", Json::string("SCMP_ACT_ERRNO")),
+            // EPERM
+            ("defaultErrnoRet", Json::Number(1)),
+            (
+                "architectures",
+                Json::Array(architectures().iter().map(|a| Json::string(a)).collect()),
+            ),
+            ("syscalls", Json::Array(rules)),
+        ])
+    }
+
+    // whether `name` is allowed only by a rule with argument constraints
+    fn is_constrained(&self, name: &str) -> bool {
+        match name {
+            "socket" | "socketpair" => self.socket_domains.0.is_some(),
+            "personality" => self.personas.0.is_some(),
+            "clone" => !self.clone_namespaces,
+            _ => false,
+        }
+    }
+}
+
+impl Constraint {
+    fn add(
+        &mut self,
+        value: Option<&SyscallArgValue>,
+        decode: fn(&SyscallArgValue) -> Option<i64>,
+    ) {
+        let value = value.and_then(decode);
+        match (&mut self.0, value) {
+            (Some(values), Some(value)) => {
+                values.insert(value);
+            }
+            _ => self.0 = None,
+        }
+    }
+}
+
+fn allow(names: &[&str], args: Vec<Json>) -> Json {
+    let mut fields = vec![
+        (
+            "names",
+            Json::Array(names.iter().map(|n| Json::string(n)).collect()),
+        ),
+        ("action", Json::string("SCMP_ACT_ALLOW")),
+    ];
+    if !args.is_empty() {
+        fields.push(("args", Json::Array(args)));
+    }
+    Json::object(fields)
+}
+
+// a comparison of argument `index` to `value`, after masking it with `mask` if given
+fn compare(index: i64, value: i64, mask: Option<i64>) -> Json {
+    let (op, value, value_two) = match mask {
+        Some(mask) => ("SCMP_CMP_MASKED_EQ", mask, value),
+        None => ("SCMP_CMP_EQ", value, 0),
+    };
+    Json::object([
+        ("index", Json::Number(index)),
+        ("value", Json::Number(value)),
+        ("valueTwo", Json::Number(value_two)),
+        ("op", Json::string(op)),
+    ])
+}
+
+fn socket_domain(value: &SyscallArgValue) -> Option<i64> {
+    match value {
+        SyscallArgValue::Number(n) => Some(*n),
+        SyscallArgValue::Symbol(s) => Some(match s.as_str() {
+            "AF_UNIX" | "AF_LOCAL" => 1,
+            "AF_INET" => 2,
+            "AF_INET6" => 10,
+            "AF_NETLINK" => 16,
+            "AF_PACKET" => 17,
+            "AF_VSOCK" => 40,
+            _ => return None,
+        }),
+        _ => None,
+    }
+}
+
+fn persona(value: &SyscallArgValue) -> Option<i64> {
+    match value {
+        SyscallArgValue::Number(n) => Some(*n),
+        SyscallArgValue::Symbol(s) => Some(match s.as_str() {
+            "PER_LINUX" => 0,
+            "PER_LINUX32" => 8,
+            "UNAME26" => 0x20000,
+            _ => return None,
+        }),
+        _ => None,
+    }
+}
+
+// the profile applies to the architecture that vistrace runs on, along with the 32-bit
+// architectures that it can also run
+fn architectures() -> &'static [&'static str] {
+    match std::env::consts::ARCH {
+        "x86_64" => &["SCMP_ARCH_X86_64", "SCMP_ARCH_X86", "SCMP_ARCH_X32"],
+        "aarch64" => &["SCMP_ARCH_AARCH64", "SCMP_ARCH_ARM"],
+        "riscv64" => &["SCMP_ARCH_RISCV64"],
+        "s390x" => &["SCMP_ARCH_S390X", "SCMP_ARCH_S390"],
+        "powerpc64" => &["SCMP_ARCH_PPC64LE"],
+        _ => &[],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::strace::parse_syscall;
+
+    use super::Profile;
+
+    #[test]
+    fn test_profile() {
+        let mut profile = Profile::default();
+        for line in [
+            "openat(AT_FDCWD, \"/etc/hosts\", O_RDONLY) = 3",
+            "read(3, \"\", 4096) = 0",
+            "close(3) = 0",
+            "rseq(0x7f00, 0x20, 0, 0x53053053) = -1 ENOSYS (Function not implemented)",
+            "socket(AF_INET, SOCK_STREAM, IPPROTO_TCP) = 3",
+            "socket(AF_UNIX, SOCK_STREAM, 0) = 4",
+            "personality(0xffffffff) = 0 (PER_LINUX)",
+            "clone(child_stack=NULL, flags=CLONE_CHILD_CLEARTID|SIGCHLD, child_tidptr=0x7f00) = 11",
+        ] {
+            profile.update(&parse_syscall(line, false));
+        }
+
+        let json = profile.to_json().to_string();
+        let rules = &json[json.find("\"syscalls\"").unwrap()..];
+        assert_eq!(
+            rules,
+            concat!(
+                r#""syscalls":["#,
+                r#"{"names":["close","openat","read"],"action":"SCMP_ACT_ALLOW"},"#,
+                r#"{"names":["socket"],"action":"SCMP_ACT_ALLOW","args":[{"index":0,"value":1,"valueTwo":0,"op":"SCMP_CMP_EQ"}]},"#,
+                r#"{"names":["socket"],"action":"SCMP_ACT_ALLOW","args":[{"index":0,"value":2,"valueTwo":0,"op":"SCMP_CMP_EQ"}]},"#,
+                r#"{"names":["personality"],"action":"SCMP_ACT_ALLOW","args":[{"index":0,"value":4294967295,"valueTwo":0,"op":"SCMP_CMP_EQ"}]},"#,
+                r#"{"names":["clone"],"action":"SCMP_ACT_ALLOW","args":[{"index":0,"value":2114060288,"valueTwo":0,"op":"SCMP_CMP_MASKED_EQ"}]}"#,
+                "]}"
+            )
+        );
+
+        // a namespace, or an address family that cannot be decoded, lifts the restriction
+        profile.update(&parse_syscall("unshare(CLONE_NEWNS) = 0", false));
+        profile.update(&parse_syscall(
+            "socket(AF_BLUETOOTH, SOCK_RAW, 0) = 5",
+            false,
+        ));
+        let json = profile.to_json().to_string();
+        assert!(json.contains(r#""names":["clone","close","openat","read","socket","unshare"]"#));
+    }
+
+    #[test]
+    fn test_only_constrained() {
+        let mut profile = Profile::default();
+        profile.update(&parse_syscall("socket(AF_UNIX, SOCK_STREAM, 0) = 3", false));
+        let json = profile.to_json().to_string();
+        // no rule that allows no syscalls
+        assert!(!json.contains(r#""names":[]"#), "{}", json);
+        assert!(json.contains(r#""names":["socket"]"#), "{}", json);
+    }
+}
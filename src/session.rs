@@ -0,0 +1,301 @@
+// A session is the state of the interface for a saved trace, kept in a file next to it (e.g.,
+// trace.vst.session for trace.vst), so that a long investigation can be picked up where it was left
+// off. `vistrace run --save PATH --session` writes one when the interface quits, and `vistrace
+// view` restores it, and keeps it up to date, if there is one (or with --session, starts one):
+//
+//   # vistrace session v1
+//   filter: openat !failed
+//   watch: count openat ENOENT
+//   mark: 12 the config file is opened here
+//   tab: 2
+//   selected: 1234
+//
+// Marks and the selected event refer to events by their zero-based position in the trace. Lines
+// that vistrace does not know are skipped, so that newer sessions can still be read.
+//
+// A snapshot (see src/snapshot.rs) carries its session in the comment lines at the top of the
+// trace, each line of the session behind "# ", which `vistrace view` restores if the trace has no
+// session file of its own.
+
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Result};
+
+use crate::filter::Filter;
+use crate::model::Model;
+use crate::watch::Watch;
+
+pub const HEADER: &str = "# vistrace session v1";
+// the header of snapshots from before they held a session, whose lines are the same
+const OLD_SNAPSHOT_HEADER: &str = "# vistrace snapshot v1";
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Session {
+    pub filter: String,
+    pub watches: Vec<String>,
+    pub marks: BTreeMap<usize, String>,
+    // the command whose tab is showing, if not all of them
+    pub tab: Option<usize>,
+    pub selected: Option<usize>,
+}
+
+/// Where the session of the trace saved at `trace` is kept.
+pub fn path_for(trace: &Path) -> PathBuf {
+    let mut path = trace.as_os_str().to_owned();
+    path.push(".session");
+    PathBuf::from(path)
+}
+
+impl Session {
+    /// The state of the interface, with `selected` the event that is selected, if any.
+    pub fn of(model: &Model, selected: Option<usize>) -> Session {
+        Session {
+            filter: model.filter.text.clone(),
+            watches: model.watches.iter().map(|w| w.text.clone()).collect(),
+            marks: model.marks.clone(),
+            tab: model.tab,
+            selected,
+        }
+    }
+
+    /// Reads the session at `path`, or returns `None` if there is none.
+    pub fn load(path: &Path) -> Result<Option<Session>> {
+        match fs::read_to_string(path) {
+            Ok(text) => Session::parse(&text)
+                .map(Some)
+                .map_err(|e| anyhow!("{}: {}", path.display(), e)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(anyhow!("unable to read {}: {}", path.display(), e)),
+        }
+    }
+
+    /// Reads the session at the top of the trace at `path`, if it is a snapshot.
+    pub fn embedded(path: &Path) -> Result<Option<Session>> {
+        let file =
+            File::open(path).map_err(|e| anyhow!("unable to open {}: {}", path.display(), e))?;
+        // a trace that is not text (e.g., a .vst file) is not a snapshot
+        let comments: Vec<String> = BufReader::new(file)
+            .lines()
+            .map_while(|line| line.ok())
+            .take_while(|line| line.starts_with('#'))
+            .collect();
+        Session::parse_embedded(&comments.join("\n"))
+            .map_err(|e| anyhow!("{}: {}", path.display(), e))
+    }
+
+    // the session in the comment lines at the top of a snapshot, if there is one
+    fn parse_embedded(comments: &str) -> Result<Option<Session>> {
+        let mut lines = comments.lines();
+        if !matches!(lines.next(), Some(HEADER | OLD_SNAPSHOT_HEADER)) {
+            return Ok(None);
+        }
+        let mut text = format!("{}\n", HEADER);
+        for line in lines {
+            text.push_str(line.trim_start_matches('#').trim_start());
+            text.push('\n');
+        }
+        Session::parse(&text).map(Some)
+    }
+
+    pub fn parse(text: &str) -> Result<Session> {
+        let mut lines = text.lines();
+        if lines.next() != Some(HEADER) {
+            return Err(anyhow!("not a vistrace session"));
+        }
+        let mut session = Session::default();
+        for (i, line) in lines.enumerate() {
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+            let value = value.trim();
+            let index = |text: &str| {
+                text.parse::<usize>()
+                    .map_err(|_| anyhow!("line {}: expected an event number", i + 2))
+            };
+            match key {
+                "filter" => session.filter = value.to_string(),
+                "watch" => session.watches.push(value.to_string()),
+                "mark" => {
+                    let (event, note) = value.split_once(' ').unwrap_or((value, ""));
+                    session.marks.insert(index(event)?, note.trim().to_string());
+                }
+                "tab" => session.tab = Some(index(value)?),
+                "selected" => session.selected = Some(index(value)?),
+                _ => {}
+            }
+        }
+        Ok(session)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let mut text = Vec::new();
+        self.write_to(&mut text)?;
+        fs::write(path, text).map_err(|e| anyhow!("unable to write {}: {}", path.display(), e))
+    }
+
+    /// Writes the session as comment lines, for the top of a snapshot.
+    pub fn write_embedded(&self, w: &mut impl Write) -> io::Result<()> {
+        let mut text = Vec::new();
+        self.write_to(&mut text)?;
+        for line in String::from_utf8_lossy(&text).lines() {
+            if line.starts_with('#') {
+                writeln!(w, "{}", line)?;
+            } else {
+                writeln!(w, "# {}", line)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn write_to(&self, w: &mut impl Write) -> io::Result<()> {
+        writeln!(w, "{}", HEADER)?;
+        if !self.filter.is_empty() {
+            writeln!(w, "filter: {}", self.filter)?;
+        }
+        for watch in &self.watches {
+            writeln!(w, "watch: {}", watch)?;
+        }
+        for (index, note) in &self.marks {
+            if note.is_empty() {
+                writeln!(w, "mark: {}", index)?;
+            } else {
+                writeln!(w, "mark: {} {}", index, note)?;
+            }
+        }
+        if let Some(tab) = self.tab {
+            writeln!(w, "tab: {}", tab)?;
+        }
+        if let Some(selected) = self.selected {
+            writeln!(w, "selected: {}", selected)?;
+        }
+        Ok(())
+    }
+
+    /// Puts the model in the state of the session, before the trace is loaded into it. The tab is
+    /// left alone if the trace has no such tab.
+    pub fn restore(&self, model: &mut Model) -> Result<()> {
+        model.marks = self.marks.clone();
+        if self.tab.is_some_and(|tab| tab < model.targets.names.len()) {
+            model.tab = self.tab;
+        }
+        for watch in &self.watches {
+            model.add_watch(Watch::parse(watch)?);
+        }
+        model.set_filter(Filter::parse(&self.filter)?);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::filter::Filter;
+    use crate::model::Model;
+    use crate::strace::parse_syscall;
+    use crate::watch::Watch;
+
+    use super::{path_for, Session};
+
+    #[test]
+    fn test_session() {
+        let mut model = Model::default();
+        model.add_watch(Watch::parse("count close").unwrap());
+        model.set_filter(Filter::parse("close").unwrap());
+        model.marks.insert(0, "opened here".to_string());
+        model.toggle_mark(1);
+
+        let mut text = Vec::new();
+        Session::of(&model, Some(1)).write_to(&mut text).unwrap();
+        let text = String::from_utf8(text).unwrap();
+        assert_eq!(
+            text,
+            "# vistrace session v1\n\
+             filter: close\n\
+             watch: count close\n\
+             mark: 0 opened here\n\
+             mark: 1\n\
+             selected: 1\n"
+        );
+
+        // a restored model filters and watches the trace as it is loaded
+        let session = Session::parse(&format!("{}future: ?\n", text)).unwrap();
+        assert_eq!(session.selected, Some(1));
+        let mut restored = Model::default();
+        session.restore(&mut restored).unwrap();
+        assert_eq!(
+            restored.push(parse_syscall(
+                "openat(AT_FDCWD, \"/a\", O_RDONLY) = 3",
+                false
+            )),
+            None
+        );
+        assert_eq!(restored.push(parse_syscall("close(3) = 0", false)), Some(1));
+        assert_eq!(restored.watches[0].text, "count close");
+        assert_eq!(restored.marks, model.marks);
+
+        assert!(Session::parse("filter: close\n").is_err());
+        assert!(Session::parse("# vistrace session v1\nmark: x\n").is_err());
+        assert_eq!(
+            path_for("/tmp/trace.vst".as_ref()).to_str(),
+            Some("/tmp/trace.vst.session")
+        );
+    }
+
+    #[test]
+    fn test_embedded_session() {
+        let session = Session {
+            filter: "openat".to_string(),
+            watches: vec!["count close".to_string()],
+            marks: [(2, "here".to_string())].into(),
+            tab: None,
+            selected: Some(2),
+        };
+        let mut text = Vec::new();
+        session.write_embedded(&mut text).unwrap();
+        let text = String::from_utf8(text).unwrap();
+        assert_eq!(
+            text,
+            "# vistrace session v1\n\
+             # filter: openat\n\
+             # watch: count close\n\
+             # mark: 2 here\n\
+             # selected: 2\n"
+        );
+        assert_eq!(Session::parse_embedded(&text).unwrap(), Some(session));
+    }
+
+    #[test]
+    fn test_embedded_session_old_snapshot() {
+        let session =
+            Session::parse_embedded("# vistrace snapshot v1\n# filter: close\n# mark: 1\n")
+                .unwrap()
+                .unwrap();
+        assert_eq!(session.filter, "close");
+        assert_eq!(session.marks, [(1, String::new())].into());
+    }
+
+    #[test]
+    fn test_embedded_session_none() {
+        // comments that strace itself writes, or none at all
+        assert_eq!(Session::parse_embedded("").unwrap(), None);
+        assert_eq!(Session::parse_embedded("# strace -o\n").unwrap(), None);
+        assert!(Session::parse_embedded("# vistrace session v1\n# mark: x\n").is_err());
+    }
+
+    #[test]
+    fn test_embedded_session_in_trace() {
+        let path = std::env::temp_dir().join(format!("vistrace-embedded-{}", std::process::id()));
+        std::fs::write(
+            &path,
+            "# vistrace session v1\n# mark: 0\nclose(3) = 0\n# filter: ignored\n",
+        )
+        .unwrap();
+        let session = Session::embedded(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let session = session.unwrap();
+        assert_eq!(session.marks, [(0, String::new())].into());
+        assert_eq!(session.filter, "");
+    }
+}
@@ -0,0 +1,88 @@
+// A snapshot is a trace file in the same format that `strace -o` writes, preceded by the session
+// (see src/session.rs) that records the state of the UI, as comment lines that strace's parser
+// skips:
+//
+//   # vistrace session v1
+//   # filter: openat !failed
+//   # watch: count openat ENOENT
+//   # mark: 12 the config file is opened here
+//   # selected: 12
+//   1720000000.000001 openat(AT_FDCWD, "/etc/hosts", O_RDONLY|O_CLOEXEC) = 3 <0.000010>
+//   ...
+//
+// `vistrace view` restores the session when the snapshot is opened.
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use anyhow::{anyhow, Result};
+
+use crate::model::Model;
+use crate::session::Session;
+
+/// Writes the trace in `model` to `path`, with `selected` the event that is selected, if any.
+pub fn write(path: &Path, model: &Model, selected: Option<usize>) -> Result<()> {
+    let file =
+        File::create(path).map_err(|e| anyhow!("unable to create {}: {}", path.display(), e))?;
+    let mut w = BufWriter::new(file);
+    write_to(&mut w, model, selected)
+        .and_then(|_| w.flush())
+        .map_err(|e| anyhow!("unable to write {}: {}", path.display(), e))
+}
+
+fn write_to(w: &mut impl Write, model: &Model, selected: Option<usize>) -> std::io::Result<()> {
+    Session::of(model, selected).write_embedded(w)?;
+    for syscall in &model.syscalls {
+        writeln!(w, "{}", syscall)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::filter::Filter;
+    use crate::model::Model;
+    use crate::strace::parse_syscall;
+    use crate::watch::Watch;
+
+    use super::write_to;
+
+    #[test]
+    fn test_write_snapshot() {
+        let mut model = Model::default();
+        model.push(parse_syscall(
+            "1720000000.000001 openat(AT_FDCWD, \"/a\", O_RDONLY) = 3 <0.000010>",
+            true,
+        ));
+        model.push(parse_syscall(
+            "1720000000.000002 close(3) = 0 <0.000001>",
+            true,
+        ));
+        model.add_watch(Watch::parse("count close").unwrap());
+        model.set_filter(Filter::parse("openat").unwrap());
+        model.toggle_mark(1);
+        model.marks.insert(0, "opened here".to_string());
+
+        // buffered syscalls are not part of the snapshot
+        model.frozen = true;
+        model.push(parse_syscall(
+            "1720000000.000003 close(4) = 0 <0.000001>",
+            true,
+        ));
+
+        let mut out = Vec::new();
+        write_to(&mut out, &model, Some(1)).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "# vistrace session v1\n\
+             # filter: openat\n\
+             # watch: count close\n\
+             # mark: 0 opened here\n\
+             # mark: 1\n\
+             # selected: 1\n\
+             1720000000.000001 openat(AT_FDCWD, \"/a\", O_RDONLY) = 3 <0.000010>\n\
+             1720000000.000002 close(3) = 0 <0.000001>\n"
+        );
+    }
+}
@@ -0,0 +1,362 @@
+// Writes a trace as a SQL script that creates and fills a SQLite database, for ad-hoc queries over
+// traces too big to grep, e.g.:
+//
+//   SELECT name, COUNT(*), SUM(duration_us) FROM events WHERE type = 'syscall' GROUP BY name;
+//
+// `vistrace export --format sqlite --output-file trace.db` feeds the script to the sqlite3 program;
+// without --output-file, the script is written to standard output instead.
+//
+// Tables:
+//
+//   events         one row per syscall, signal, or exit, in order: type ("syscall", "signal", or
+//                  "exit"), pid, time_us, name (the syscall, the signal, or e.g. "exited with 1"),
+//                  return_value (or the exit code), errno, and duration_us
+//   args           the arguments of each syscall: event_id, position, name, and value (as strace
+//                  printed it)
+//   processes      every process and thread: pid, parent, tgid (the process that a thread belongs
+//                  to), command, syscalls (how many it made), and exit
+//   fds            every use of every file descriptor: pid (the owner of the fd table), fd,
+//                  description, opened_us, closed_us, bytes_read, and bytes_written
+//   connections    every socket: pid, fd, protocol, role, local, remote, opened_us, closed_us,
+//                  bytes_read, bytes_written, and close_reason
+//
+// Times are in microseconds since the Unix epoch, and unknown values are NULL.
+
+use std::fmt::Display;
+use std::io::{self, Write};
+
+use crate::fdtable::FdTable;
+use crate::net::{Connections, Endpoint};
+use crate::processes::ProcessTable;
+use crate::strace::{Exit, ExitKind, Message};
+use crate::syscalls;
+
+const SCHEMA: &str = "\
+DROP TABLE IF EXISTS events;
+DROP TABLE IF EXISTS args;
+DROP TABLE IF EXISTS processes;
+DROP TABLE IF EXISTS fds;
+DROP TABLE IF EXISTS connections;
+CREATE TABLE events (id INTEGER PRIMARY KEY, type TEXT NOT NULL, pid INTEGER, time_us INTEGER, \
+name TEXT NOT NULL, return_value INTEGER, errno TEXT, duration_us INTEGER);
+CREATE TABLE args (event_id INTEGER NOT NULL REFERENCES events (id), position INTEGER NOT NULL, \
+name TEXT, value TEXT NOT NULL);
+CREATE TABLE processes (pid INTEGER PRIMARY KEY, parent INTEGER, tgid INTEGER NOT NULL, \
+command TEXT, syscalls INTEGER NOT NULL, exit TEXT);
+CREATE TABLE fds (pid INTEGER, fd INTEGER NOT NULL, description TEXT NOT NULL, opened_us INTEGER, \
+closed_us INTEGER, bytes_read INTEGER NOT NULL, bytes_written INTEGER NOT NULL);
+CREATE TABLE connections (pid INTEGER, fd INTEGER NOT NULL, protocol TEXT NOT NULL, \
+role TEXT NOT NULL, local TEXT, remote TEXT, opened_us INTEGER, closed_us INTEGER, \
+bytes_read INTEGER NOT NULL, bytes_written INTEGER NOT NULL, close_reason TEXT);
+";
+
+// created after the rows are inserted, which is faster than keeping them up to date
+const INDICES: &str = "\
+CREATE INDEX events_by_name ON events (name);
+CREATE INDEX events_by_pid ON events (pid);
+CREATE INDEX args_by_event ON args (event_id);
+";
+
+#[derive(Default)]
+pub struct SqlExport {
+    processes: ProcessTable,
+    fds: FdTable,
+    net: Connections,
+    started: bool,
+    events: usize,
+}
+
+impl SqlExport {
+    pub fn write_message(&mut self, w: &mut impl Write, msg: &Message) -> io::Result<()> {
+        self.start(w)?;
+        let id = self.events + 1;
+        match msg {
+            Message::Syscall(syscall) => {
+                self.processes.update(syscall);
+                self.fds.update(syscall);
+                self.net.update(syscall, &self.fds);
+                insert(
+                    w,
+                    "events",
+                    &[
+                        id.to_string(),
+                        text("syscall"),
+                        null(syscall.pid),
+                        time(syscall.entry_time_micros),
+                        text(&syscall.name),
+                        null(syscall.result()),
+                        syscall.errno.as_deref().map_or_else(null_literal, text),
+                        null(syscall.syscall_time_micros),
+                    ],
+                )?;
+                let names = syscalls::arg_names(&syscall.name).unwrap_or_default();
+                for (i, arg) in syscall.args.iter().enumerate() {
+                    let name = names.get(i).copied().unwrap_or(&arg.name);
+                    insert(
+                        w,
+                        "args",
+                        &[
+                            id.to_string(),
+                            i.to_string(),
+                            if name.is_empty() {
+                                null_literal()
+                            } else {
+                                text(name)
+                            },
+                            text(&arg.value.to_string()),
+                        ],
+                    )?;
+                }
+            }
+            Message::Signal(signal) => insert(
+                w,
+                "events",
+                &[
+                    id.to_string(),
+                    text("signal"),
+                    null(signal.pid),
+                    time(signal.time_micros),
+                    text(&signal.name),
+                    null_literal(),
+                    null_literal(),
+                    null_literal(),
+                ],
+            )?,
+            Message::Exit(exit) => {
+                let code = match exit.status {
+                    ExitKind::Exited(code) => Some(code),
+                    ExitKind::Killed { .. } => None,
+                };
+                insert(
+                    w,
+                    "events",
+                    &[
+                        id.to_string(),
+                        text("exit"),
+                        null(exit.pid),
+                        time(exit.time_micros),
+                        text(&exit.status.to_string()),
+                        null(code),
+                        null_literal(),
+                        null_literal(),
+                    ],
+                )?;
+                self.net.record_exit(exit, &self.fds);
+                self.processes.record_exit(Exit {
+                    pid: exit.pid,
+                    time_micros: exit.time_micros,
+                    status: exit.status.clone(),
+                });
+            }
+            Message::Frame(_) | Message::LibCall(_) | Message::Skipped(_) | Message::Notice(_) => {
+                return Ok(())
+            }
+        }
+        self.events += 1;
+        Ok(())
+    }
+
+    /// Writes the processes, fds, and connections, which are only complete at the end of the
+    /// trace.
+    pub fn finish(&mut self, w: &mut impl Write) -> io::Result<()> {
+        self.start(w)?;
+        for process in self.processes.processes.values() {
+            insert(
+                w,
+                "processes",
+                &[
+                    process.pid.to_string(),
+                    null(process.parent),
+                    process.tgid.to_string(),
+                    process.command.as_deref().map_or_else(null_literal, text),
+                    process.syscall_count.to_string(),
+                    process
+                        .exit
+                        .as_ref()
+                        .map_or_else(null_literal, |e| text(&e.to_string())),
+                ],
+            )?;
+        }
+        for (owner, fd, info) in self.fds.all() {
+            insert(
+                w,
+                "fds",
+                &[
+                    null(owner),
+                    fd.to_string(),
+                    text(&info.kind.to_string()),
+                    time(info.opened_micros),
+                    null(info.closed_micros),
+                    info.bytes.read.to_string(),
+                    info.bytes.written.to_string(),
+                ],
+            )?;
+        }
+        for connection in &self.net.connections {
+            let endpoint = |e: &Option<Endpoint>| {
+                e.as_ref()
+                    .map_or_else(null_literal, |e| text(&e.to_string()))
+            };
+            insert(
+                w,
+                "connections",
+                &[
+                    null(connection.pid),
+                    connection.fd.to_string(),
+                    text(&connection.protocol),
+                    text(&connection.role.to_string()),
+                    endpoint(&connection.local),
+                    endpoint(&connection.remote),
+                    time(connection.opened_micros),
+                    null(connection.closed_micros),
+                    connection.bytes.read.to_string(),
+                    connection.bytes.written.to_string(),
+                    connection
+                        .close_reason
+                        .as_ref()
+                        .map_or_else(null_literal, |r| text(&r.to_string())),
+                ],
+            )?;
+        }
+        writeln!(w, "{}COMMIT;", INDICES)
+    }
+
+    fn start(&mut self, w: &mut impl Write) -> io::Result<()> {
+        if !self.started {
+            // one transaction for the whole trace, which is much faster than one per row
+            writeln!(w, "{}BEGIN;", SCHEMA)?;
+            self.started = true;
+        }
+        Ok(())
+    }
+}
+
+fn insert(w: &mut impl Write, table: &str, values: &[String]) -> io::Result<()> {
+    writeln!(w, "INSERT INTO {} VALUES ({});", table, values.join(", "))
+}
+
+// a string literal
+fn text(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "''"))
+}
+
+fn null<T: Display>(value: Option<T>) -> String {
+    value.map_or_else(null_literal, |v| v.to_string())
+}
+
+fn null_literal() -> String {
+    "NULL".to_string()
+}
+
+// a time in microseconds, which is 0 if unknown
+fn time(micros: u64) -> String {
+    null(Some(micros).filter(|m| *m != 0))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::strace::{parse_syscall, LineParser, Message};
+
+    use super::SqlExport;
+
+    #[test]
+    fn test_sql_export() {
+        let mut export = SqlExport::default();
+        let mut parser = LineParser::default();
+        let mut out = Vec::new();
+        for line in [
+            "10 1.000000 openat(AT_FDCWD, \"/etc/it's\", O_RDONLY) = 3 <0.000010>",
+            "10 1.000100 read(3, \"abc\", 4096) = 3 <0.000020>",
+            "10 1.000200 close(3) = 0 <0.000001>",
+            "10 1.000300 +++ exited with 1 +++",
+        ] {
+            export
+                .write_message(&mut out, &parser.parse_line(line).unwrap())
+                .unwrap();
+        }
+        export.finish(&mut out).unwrap();
+
+        let script = String::from_utf8(out).unwrap();
+        let rows: Vec<&str> = script
+            .lines()
+            .filter(|line| line.starts_with("INSERT"))
+            .collect();
+        assert_eq!(
+            rows,
+            [
+                "INSERT INTO events VALUES (1, 'syscall', 10, 1000000, 'openat', 3, NULL, 10);",
+                "INSERT INTO args VALUES (1, 0, 'dirfd', 'AT_FDCWD');",
+                "INSERT INTO args VALUES (1, 1, 'pathname', '\"/etc/it''s\"');",
+                "INSERT INTO args VALUES (1, 2, 'flags', 'O_RDONLY');",
+                "INSERT INTO events VALUES (2, 'syscall', 10, 1000100, 'read', 3, NULL, 20);",
+                "INSERT INTO args VALUES (2, 0, 'fd', '3');",
+                "INSERT INTO args VALUES (2, 1, 'buf', '\"abc\"');",
+                "INSERT INTO args VALUES (2, 2, 'count', '4096');",
+                "INSERT INTO events VALUES (3, 'syscall', 10, 1000200, 'close', 0, NULL, 1);",
+                "INSERT INTO args VALUES (3, 0, 'fd', '3');",
+                "INSERT INTO events VALUES (4, 'exit', 10, 1000300, 'exited with 1', 1, NULL, NULL);",
+                "INSERT INTO processes VALUES (10, NULL, 10, NULL, 3, 'exited with 1');",
+                "INSERT INTO fds VALUES (10, 3, '/etc/it''s', 1000000, 1000200, 3, 0);",
+            ]
+        );
+        assert!(script.starts_with("DROP TABLE IF EXISTS events;"));
+        assert!(script.ends_with("COMMIT;\n"));
+    }
+
+    #[test]
+    fn test_sql_export_durations() {
+        let mut export = SqlExport::default();
+        let mut out = Vec::new();
+        for (line, timestamps) in [
+            // too quick for strace to measure
+            ("1.000000 getpid() = 10 <0.000000>", true),
+            // with no times at all
+            ("getpid() = 10", false),
+        ] {
+            let message = Message::Syscall(parse_syscall(line, timestamps));
+            export.write_message(&mut out, &message).unwrap();
+        }
+        let script = String::from_utf8(out).unwrap();
+        let rows: Vec<&str> = script
+            .lines()
+            .filter(|line| line.starts_with("INSERT INTO events"))
+            .collect();
+        assert_eq!(
+            rows,
+            [
+                "INSERT INTO events VALUES (1, 'syscall', NULL, 1000000, 'getpid', 10, NULL, 0);",
+                "INSERT INTO events VALUES (2, 'syscall', NULL, NULL, 'getpid', 10, NULL, NULL);",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_sql_export_empty() {
+        let mut export = SqlExport::default();
+        let mut out = Vec::new();
+        export.finish(&mut out).unwrap();
+        let script = String::from_utf8(out).unwrap();
+        assert!(!script.contains("INSERT"));
+        assert!(script.ends_with("COMMIT;\n"));
+    }
+
+    #[test]
+    fn test_sql_export_malformed() {
+        let mut export = SqlExport::default();
+        let mut out = Vec::new();
+        let message = LineParser::default()
+            .parse_line("10 1.000000 read(3, \"abc")
+            .unwrap();
+        export.write_message(&mut out, &message).unwrap();
+        let script = String::from_utf8(out).unwrap();
+        let rows: Vec<&str> = script
+            .lines()
+            .filter(|line| line.starts_with("INSERT INTO events"))
+            .collect();
+        // no return value rather than 0
+        assert_eq!(
+            rows,
+            ["INSERT INTO events VALUES (1, 'syscall', 10, NULL, 'read', NULL, NULL, NULL);"]
+        );
+    }
+}
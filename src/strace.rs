@@ -1,20 +1,151 @@
 use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
 use std::io::{BufRead, BufReader};
-use std::process::{Command, Stdio};
-use std::sync::mpsc;
+use std::path::{Path, PathBuf};
+#[cfg(feature = "native")]
+use std::process::{Child, Command, ExitStatus, Stdio};
+#[cfg(feature = "native")]
+use std::sync::{mpsc, Arc, Mutex};
 
 use anyhow::{anyhow, Result};
 
+use crate::inject::Injection;
+#[cfg(feature = "native")]
+use crate::{
+    commands, control, inject,
+    journal::Journal,
+    limits::Limiter,
+    redact::Redactor,
+    remote,
+    sample::{Sample, Sampler},
+    vst,
+};
+use crate::{ltrace, syscalls};
+
 pub enum Message {
     Syscall(Syscall),
+    Signal(Signal),
+    Exit(Exit),
+    Frame(Frame),
+    LibCall(LibCall),
+    Skipped(Skipped),
+    // informational messages from strace itself, e.g., "Process 1234 attached"
+    Notice(String),
+}
+
+/// A signal delivered to a traced process, e.g.:
+///
+///   --- SIGCHLD {si_signo=SIGCHLD, si_code=CLD_EXITED, si_pid=1235, ...} ---
+pub struct Signal {
+    pub pid: Option<u32>,
+    pub time_micros: u64,
+    pub name: String,
+    // the fields of the siginfo_t struct, if strace printed it
+    pub info: HashMap<String, SyscallArg>,
+}
+
+/// The end of a traced process, e.g.:
+///
+///   +++ exited with 0 +++
+///   +++ killed by SIGSEGV (core dumped) +++
+pub struct Exit {
+    pub pid: Option<u32>,
+    pub time_micros: u64,
+    pub status: ExitKind,
+}
+
+/// One frame of the stack of the syscall just before it, which `strace -k` prints innermost first,
+/// e.g.:
+///
+///    > /usr/lib/x86_64-linux-gnu/libc.so.6(__write+0x14) [0x114887]
+pub struct Frame {
+    pub pid: Option<u32>,
+    // the executable or library, e.g. "/usr/lib/x86_64-linux-gnu/libc.so.6" (or an error, e.g.
+    // "backtracing_error")
+    pub module: String,
+    // `None` if strace could not find the symbol
+    pub function: Option<String>,
+    pub offset: u64,
+    pub address: u64,
+}
+
+/// A call to a library function, which ltrace prints (see src/ltrace.rs), e.g.:
+///
+///   ls->malloc(16) = 0x55d0c8a2a2a0 <0.000012>
+pub struct LibCall {
+    pub pid: Option<u32>,
+    // the program or library that made the call, if ltrace said, e.g. "ls" above
+    pub caller: Option<String>,
+    pub name: String,
+    // the arguments and the return value as ltrace printed them, which depends on its prototypes
+    pub args: String,
+    pub return_value: String,
+    pub time_micros: u64,
+    pub duration_micros: u64,
+}
+
+/// Syscalls that `--sample` left out of the trace (see src/sample.rs), which are only counted,
+/// e.g.:
+///
+///   +++ skipped 99 read (3 failed) <0.001234> +++
+pub struct Skipped {
+    pub name: String,
+    pub calls: u64,
+    pub errors: u64,
+    // how long they took in all, or 0 if unknown
+    pub total_micros: u64,
+}
+
+#[derive(Clone)]
+pub enum ExitKind {
+    Exited(i64),
+    Killed { signal: String, core_dumped: bool },
+}
+
+impl Message {
+    /// Returns when the message was printed, in microseconds since the Unix epoch, or 0 if unknown.
+    pub fn time_micros(&self) -> u64 {
+        match self {
+            Message::Syscall(syscall) => syscall.entry_time_micros,
+            Message::Signal(signal) => signal.time_micros,
+            Message::Exit(exit) => exit.time_micros,
+            Message::LibCall(call) => call.time_micros,
+            Message::Frame(_) | Message::Skipped(_) | Message::Notice(_) => 0,
+        }
+    }
+
+    pub fn into_syscall(self) -> Option<Syscall> {
+        match self {
+            Message::Syscall(syscall) => Some(syscall),
+            _ => None,
+        }
+    }
 }
 
 pub struct Syscall {
+    // `None` if strace did not say, which means it was tracing a single process
+    pub pid: Option<u32>,
     pub name: String,
     pub args: Vec<SyscallArg>,
     pub return_value: i64,
+    // whether the syscall never returned (e.g., exit_group), which strace shows as `= ?`, in which
+    // case `return_value` is 0
+    pub no_return: bool,
+    // e.g., "ENOENT" when the syscall failed
+    pub errno: Option<String>,
+    // the array that strace prints after the return value of poll and ppoll, e.g.,
+    // `[{fd=3, revents=POLLIN}]` for `poll(...) = 1 ([{fd=3, revents=POLLIN}])`
+    pub returned: Option<SyscallArgValue>,
+    // the rest of what strace printed after the return value, to print it back, e.g.
+    // `(No such file or directory)` after ENOENT, `(Timeout)` after poll's 0, or `ERESTARTSYS (To
+    // be restarted if SA_RESTART is set)` after a `?`
+    pub explanation: Option<String>,
     pub entry_time_micros: u64,
-    pub syscall_time_micros: u64,
+    // how long the syscall took, if the tracer said (strace does with -T, truss does not)
+    pub syscall_time_micros: Option<u64>,
+    // whether strace tampered with the syscall (see src/inject.rs)
+    pub injected: bool,
     pub error_details: Option<SyscallErrorDetails>,
 }
 
@@ -49,62 +180,821 @@ pub enum FlagSetValue {
     Bits(i64),
 }
 
-pub fn strace(cmd: &Vec<String>, tx: mpsc::Sender<Message>) -> Result<()> {
-    let mut child: std::process::Child = Command::new("strace")
-        .arg("--absolute-timestamps=format:unix,us")
-        .arg("--syscall-times=us")
-        .args(cmd)
-        .stderr(Stdio::piped())
-        .stdout(Stdio::piped())
+#[derive(Clone)]
+pub struct StraceOptions {
+    pub strace_path: PathBuf,
+    // program to run under strace, if any
+    pub command: Vec<String>,
+    // already-running processes to attach to
+    pub pids: Vec<u32>,
+    // trace child processes and threads as well
+    pub follow: bool,
+    // syscall names and `%category`s for strace not to trace
+    pub exclude: Vec<String>,
+    // other options to pass on to strace, already checked by `parse_strace_args`
+    pub extra_args: Vec<String>,
+    // print the stack of each syscall (-k)
+    pub stacks: bool,
+    // faults to inject (-e inject=)
+    pub injections: Vec<Injection>,
+    // run strace on this host over ssh (e.g., "user@host"), rather than here
+    pub host: Option<String>,
+}
+
+#[cfg(feature = "native")]
+pub fn spawn(options: &StraceOptions) -> Result<Child> {
+    let mut args: Vec<String> = vec![
+        "--absolute-timestamps=format:unix,us".to_string(),
+        "--syscall-times=us".to_string(),
+    ];
+    if options.follow {
+        // With -o, strace prefixes every line with the PID, whereas otherwise it only does so once
+        // there is more than one process, which leaves the first process's lines ambiguous. (-ff
+        // would split the output into one file per process, which is no use when streaming.)
+        args.extend(["-f", "-o", "/dev/stderr"].map(String::from));
+    }
+    if !options.exclude.is_empty() {
+        args.push("-e".to_string());
+        args.push(format!("trace=!{}", options.exclude.join(",")));
+    }
+    if options.stacks {
+        args.push("-k".to_string());
+    }
+    args.extend(inject::strace_args(&options.injections));
+    args.extend(options.extra_args.iter().cloned());
+    for pid in &options.pids {
+        args.push("-p".to_string());
+        args.push(pid.to_string());
+    }
+    args.extend(options.command.iter().cloned());
+
+    let mut cmd = command(&options.strace_path, &args, options.host.as_deref());
+    cmd.stderr(Stdio::piped()).stdout(Stdio::piped());
+    tracing::info!(command = ?cmd, "spawning strace");
+    let child = cmd
         .spawn()
         .map_err(|e| anyhow!("unable to spawn strace: {}", e))?;
+    tracing::info!(pid = child.id(), "strace started");
+    Ok(child)
+}
+
+// runs `program` here, or on `host` over ssh
+#[cfg(feature = "native")]
+fn command(program: &Path, args: &[String], host: Option<&str>) -> Command {
+    match host {
+        None => {
+            let mut cmd = Command::new(program);
+            cmd.args(args);
+            cmd
+        }
+        Some(host) => {
+            // ssh passes the command to the remote user's shell as one string
+            let words: Vec<String> = [program.to_string_lossy().into_owned()]
+                .iter()
+                .chain(args)
+                .map(|word| commands::shell_quote(word))
+                .collect();
+            let mut cmd = Command::new("ssh");
+            // -n so that ssh does not read the keys meant for the interface
+            cmd.arg("-n").arg(host).arg("--").arg(words.join(" "));
+            cmd
+        }
+    }
+}
+
+/// Checks that `path` is a working strace (on `host`, if given) and returns its version as (major,
+/// minor).
+#[cfg(feature = "native")]
+pub fn check_version(path: &Path, host: Option<&str>) -> Result<(u32, u32)> {
+    let output = command(path, &["-V".to_string()], host)
+        .output()
+        .map_err(|e| match host {
+            Some(host) => anyhow!("unable to run ssh {}: {}", host, e),
+            None => anyhow!("unable to run {}: {}", path.display(), e),
+        })?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let first_line = stdout.lines().next().unwrap_or_default();
+    tracing::debug!(path = %path.display(), version = first_line, "checked strace version");
+    if let (Some(host), "") = (host, first_line) {
+        // e.g., ssh could not log in, or strace is not installed there
+        return Err(anyhow!(
+            "unable to run {} on {}: {}",
+            path.display(),
+            host,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    parse_version(first_line).ok_or(anyhow!(
+        "{} does not look like strace (`{} -V` printed {:?})",
+        path.display(),
+        path.display(),
+        first_line
+    ))
+}
+
+/// Returns true if strace `version` understands `-e trace=!%<category>`. (Older versions spell
+/// categories without the `%`.)
+pub fn supports_trace_categories(version: (u32, u32)) -> bool {
+    version >= (5, 0)
+}
+
+/// Splits `--exclude` values into names and `%category`s, accepting categories with or without
+/// the `%`, and checks that the categories are known.
+pub fn parse_exclude(values: &[String]) -> Result<Vec<String>> {
+    values
+        .iter()
+        .map(|value| {
+            let category = value.strip_prefix('%').unwrap_or(value);
+            if syscalls::CATEGORIES.contains(&category) {
+                Ok(format!("%{}", category))
+            } else if value.starts_with('%') {
+                Err(anyhow!(
+                    "unknown syscall category {:?} (expected one of: {})",
+                    value,
+                    syscalls::CATEGORIES.join(", ")
+                ))
+            } else {
+                Ok(value.clone())
+            }
+        })
+        .collect()
+}
+
+/// Splits `--strace-arg` values on whitespace (so that `-s 4096` is two arguments) and checks
+/// that none of them conflicts with the options that vistrace sets itself or with the output
+/// format that it expects. `exclude` is what vistrace itself passes to `-e trace=`.
+pub fn parse_strace_args(values: &[String], exclude: &[String]) -> Result<Vec<String>> {
+    // each argument along with the value it came from, for error messages
+    let args: Vec<(&String, &str)> = values
+        .iter()
+        .flat_map(|value| value.split_whitespace().map(move |arg| (value, arg)))
+        .collect();
+
+    for (i, (value, arg)) in args.iter().enumerate() {
+        let conflict = if let Some(long) = arg.strip_prefix("--") {
+            let name = long.split('=').next().unwrap_or_default();
+            if name == "trace" && !exclude.is_empty() {
+                Some("--exclude")
+            } else {
+                long_option_conflict(name)
+            }
+        } else if let Some(short) = arg.strip_prefix('-') {
+            let qualifier = if short == "e" {
+                args.get(i + 1).map(|(_, arg)| *arg).unwrap_or_default()
+            } else {
+                short.strip_prefix('e').unwrap_or_default()
+            };
+            if qualifier.starts_with("trace=") && !exclude.is_empty() {
+                Some("--exclude")
+            } else {
+                short_option_conflict(short)
+            }
+        } else {
+            None
+        };
+
+        if let Some(conflict) = conflict {
+            return Err(anyhow!(
+                "--strace-arg {:?} conflicts with {}",
+                value,
+                conflict
+            ));
+        }
+    }
+    Ok(args.into_iter().map(|(_, arg)| arg.to_string()).collect())
+}
+
+const FORMAT_CONFLICT: &str = "the output format that vistrace expects";
+
+fn long_option_conflict(name: &str) -> Option<&'static str> {
+    match name {
+        "absolute-timestamps" | "relative-timestamps" | "syscall-times" | "timestamps" => {
+            Some("vistrace's own timestamp options")
+        }
+        "stack-trace" => Some("--stacks"),
+        "output"
+        | "output-separately"
+        | "summary"
+        | "summary-only"
+        | "instruction-pointer"
+        | "syscall-number" => Some(FORMAT_CONFLICT),
+        "follow-forks" => Some("--follow/--no-follow"),
+        "attach" => Some("`vistrace attach`"),
+        _ => None,
+    }
+}
+
+// `-fv` is two options, but `-s4096` is one option and its value
+fn short_option_conflict(options: &str) -> Option<&'static str> {
+    for c in options.chars() {
+        match c {
+            't' | 'r' | 'T' => return Some("vistrace's own timestamp options"),
+            'k' => return Some("--stacks"),
+            'o' | 'c' | 'C' | 'i' | 'n' => return Some(FORMAT_CONFLICT),
+            'f' => return Some("--follow/--no-follow"),
+            'p' => return Some("`vistrace attach`"),
+            // the rest of the argument is the option's value
+            'a' | 'b' | 'e' | 'E' | 'I' | 'O' | 'P' | 's' | 'S' | 'u' | 'U' | 'X' => return None,
+            _ => {}
+        }
+    }
+    None
+}
+
+// e.g., "strace -- version 6.1"
+#[cfg(feature = "native")]
+fn parse_version(line: &str) -> Option<(u32, u32)> {
+    let version = line.strip_prefix("strace -- version ")?;
+    let mut parts = version.trim().split('.');
+    let major = parts.next()?.parse().ok()?;
+    let minor = parts.next().unwrap_or("0").parse().ok()?;
+    Some((major, minor))
+}
+
+/// Turns lines of a tracer's output into messages: strace's output, or another tracer's (see
+/// src/bpf.rs, src/dtruss.rs, and src/truss.rs).
+pub trait Parse {
+    fn parse_line(&mut self, line: &str) -> Option<Message>;
+
+    /// Whether the lines are strace's, and so can be saved as they are. Otherwise, the messages are
+    /// saved as strace would have printed them.
+    fn is_strace(&self) -> bool {
+        false
+    }
+}
+
+/// Sends the tracer's output, as parsed by `parser`, to `sink` until it exits, and returns its exit
+/// status. The tracer writes to standard error.
+#[cfg(feature = "native")]
+pub fn stream(mut child: Child, mut parser: impl Parse, mut sink: Sink) -> Result<ExitStatus> {
     let stderr = child
         .stderr
         .as_mut()
-        .ok_or(anyhow!("unable to access strace's standard error"))?;
+        .ok_or(anyhow!("unable to access the tracer's standard error"))?;
 
     let mut reader = BufReader::new(stderr);
-
     loop {
         let mut line = String::new();
         let n = reader
             .read_line(&mut line)
-            .map_err(|e| anyhow!("unable to read output from strace: {}", e))?;
+            .map_err(|e| anyhow!("unable to read output from the tracer: {}", e))?;
         if n == 0 {
             break;
         }
 
-        // '+++' is used to report the exit code at end of process
-        // '---' is used to report signals
-        // '[ ... ]' is used to report process interactions
-        if line.starts_with("+++") || line.starts_with("---") || line.starts_with("[") {
-            continue;
+        let msg = parser.parse_line(&line);
+        if parser.is_strace() {
+            sink.send(&line, msg)?;
+        } else if let Some(msg) = msg {
+            sink.send(&format!("{}\n", msg), Some(msg))?;
         }
-
-        let syscall = parse_syscall(&line, true);
-        let msg = Message::Syscall(syscall);
-        tx.send(msg).map_err(|e| anyhow!("transmit error: {}", e))?;
     }
+    sink.finish()?;
 
-    let exit_result = child
+    let status = child
         .wait()
-        .map_err(|e| anyhow!("failed to wait for strace to terminate: {}", e))?;
-    if !exit_result.success() {
-        return Err(anyhow!("strace returned a non-zero exit code"));
+        .map_err(|e| anyhow!("failed to wait for the tracer to terminate: {}", e))?;
+    tracing::info!(%status, "tracer exited");
+    Ok(status)
+}
+
+/// Passes each message from the tracer on to the limiter, the saved trace, the client of
+/// `--listen`, the clients of `--control-socket`, and the interface, in that order. It is cloned
+/// for each tracer when `vistrace run` traces several commands at once (see src/targets.rs).
+#[cfg(feature = "native")]
+#[derive(Clone)]
+pub struct Sink {
+    tx: mpsc::Sender<Message>,
+    outputs: Arc<Mutex<Outputs>>,
+    limiter: Option<Arc<Limiter>>,
+    // whether the limiter dropped the last syscall, in which case its stack is dropped too
+    dropped: bool,
+    // with --redact
+    redactor: Option<Arc<Redactor>>,
+    // with --sample
+    sampler: Option<Arc<Sampler>>,
+}
+
+/// Where the lines of the trace go besides the interface.
+#[cfg(feature = "native")]
+#[derive(Default)]
+pub struct Outputs {
+    // with --save
+    pub save: Option<vst::Writer>,
+    // with --listen
+    pub remote: Option<remote::Sender>,
+    // with --control-socket
+    pub control: Option<Arc<control::Server>>,
+    // with --journal
+    pub journal: Option<Journal>,
+}
+
+#[cfg(feature = "native")]
+impl Sink {
+    pub fn new(
+        tx: mpsc::Sender<Message>,
+        outputs: Outputs,
+        limiter: Option<Arc<Limiter>>,
+        redactor: Option<Redactor>,
+        sampler: Option<Sampler>,
+    ) -> Sink {
+        Sink {
+            tx,
+            outputs: Arc::new(Mutex::new(outputs)),
+            limiter,
+            dropped: false,
+            redactor: redactor.map(Arc::new),
+            sampler: sampler.map(Arc::new),
+        }
+    }
+
+    /// Passes on `msg`, which was parsed from `line` (a line of strace output, with its newline).
+    pub fn send(&mut self, line: &str, mut msg: Option<Message>) -> Result<()> {
+        let mut skipped = None;
+        if let (Some(Message::Syscall(syscall)), Some(sampler)) = (&msg, &self.sampler) {
+            match sampler.sample(syscall) {
+                Sample::Keep(before) => skipped = before,
+                Sample::Skip => {
+                    self.dropped = true;
+                    return Ok(());
+                }
+            }
+        }
+        match (&msg, &self.limiter) {
+            (Some(Message::Syscall(syscall)), Some(limiter)) => {
+                self.dropped = !limiter.record(syscall);
+                if self.dropped {
+                    return Ok(());
+                }
+            }
+            (Some(Message::Frame(_)), _) if self.dropped => return Ok(()),
+            _ => self.dropped = false,
+        }
+        // the calls of the syscall that were left out come before the one that was kept
+        if let Some(skipped) = skipped {
+            let skipped = Message::Skipped(skipped);
+            self.forward(&format!("{}\n", skipped), Some(skipped))?;
+        }
+
+        // the saved trace has the redacted message instead of the line, and no lines that were
+        // not understood
+        let redacted;
+        let line = match (&self.redactor, &mut msg) {
+            (Some(redactor), Some(m)) => {
+                redactor.redact(m);
+                redacted = format!("{}\n", m);
+                &redacted
+            }
+            (Some(_), None) => return Ok(()),
+            (None, _) => line,
+        };
+        self.forward(line, msg)
+    }
+
+    fn forward(&mut self, line: &str, msg: Option<Message>) -> Result<()> {
+        self.outputs.lock().unwrap().write(line, msg.as_ref())?;
+        if let Some(msg) = msg {
+            self.tx
+                .send(msg)
+                .map_err(|e| anyhow!("transmit error: {}", e))?;
+        }
+        Ok(())
+    }
+
+    /// Must be called once the tracer has no more messages. The trace is only over once every
+    /// clone has been finished.
+    pub fn finish(self) -> Result<()> {
+        let Some(outputs) = Arc::into_inner(self.outputs) else {
+            return Ok(());
+        };
+        let mut outputs = outputs.into_inner().unwrap();
+        // the calls that were left out since the last ones that were kept
+        for skipped in self.sampler.iter().flat_map(|sampler| sampler.finish()) {
+            let skipped = Message::Skipped(skipped);
+            outputs.write(&format!("{}\n", skipped), Some(&skipped))?;
+            let _ = self.tx.send(skipped);
+        }
+        if let Some(control) = &outputs.control {
+            control.finish();
+        }
+        if let Some(journal) = &mut outputs.journal {
+            journal.finish();
+        }
+        if let Some(mut save) = outputs.save {
+            save.finish()
+                .map_err(|e| anyhow!("unable to save trace: {}", e))?;
+        }
+        if let Some(reason) = self.limiter.and_then(|l| l.finish()) {
+            let _ = self
+                .tx
+                .send(Message::Notice(format!("Stopped tracing: {}", reason)));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "native")]
+impl Outputs {
+    fn write(&mut self, line: &str, msg: Option<&Message>) -> Result<()> {
+        if let Some(save) = &mut self.save {
+            save.write(line, msg)
+                .map_err(|e| anyhow!("unable to save trace: {}", e))?;
+        }
+        if let Some(remote) = &mut self.remote {
+            remote
+                .write(line)
+                .map_err(|e| anyhow!("unable to send trace to client: {}", e))?;
+        }
+        if let Some(control) = &self.control {
+            control.write(line, msg);
+        }
+        if let Some(journal) = &mut self.journal {
+            journal.write(line, msg);
+        }
+        Ok(())
     }
-    Ok(())
 }
 
-fn parse_syscall(text: &str, timestamps: bool) -> Syscall {
+/// Asks strace to detach from its tracees and exit.
+#[cfg(feature = "native")]
+pub fn detach(child_pid: u32) {
+    // strace detaches cleanly on SIGINT; if it has already exited then there is nothing to do
+    unsafe {
+        libc::kill(child_pid as libc::pid_t, libc::SIGINT);
+    }
+}
+
+/// Stops a traced process, as for a breakpoint, until `resume` is called.
+#[cfg(feature = "native")]
+pub fn stop(pid: u32) {
+    unsafe {
+        libc::kill(pid as libc::pid_t, libc::SIGSTOP);
+    }
+}
+
+#[cfg(feature = "native")]
+pub fn resume(pid: u32) {
+    unsafe {
+        libc::kill(pid as libc::pid_t, libc::SIGCONT);
+    }
+}
+
+/// Kills a traced process outright.
+#[cfg(feature = "native")]
+pub fn kill(pid: u32) {
+    unsafe {
+        libc::kill(pid as libc::pid_t, libc::SIGKILL);
+    }
+}
+
+/// Turns lines of strace output into messages, reassembling syscalls that strace splits across two
+/// lines when another process makes a syscall in the middle, e.g.:
+///
+///   [pid 1234] read(3,  <unfinished ...>
+///   [pid 1235] close(4) = 0
+///   [pid 1234] <... read resumed>"abc", 4096) = 3
+#[derive(Default)]
+pub struct LineParser {
+    unfinished: HashMap<Option<u32>, String>,
+    // syscall names and `%category`s to drop, for when strace could not exclude them itself
+    exclude: Vec<String>,
+    // the pid of the syscall that the last line completed, which any stack frames after it belong
+    // to (`None` if the last line was something else)
+    stack_of: Option<Option<u32>>,
+}
+
+impl LineParser {
+    pub fn with_exclude(exclude: Vec<String>) -> Self {
+        Self {
+            exclude,
+            ..Default::default()
+        }
+    }
+
+    /// Returns `None` for lines that do not complete a message.
+    pub fn parse_line(&mut self, line: &str) -> Option<Message> {
+        if let Some(notice) = line.strip_prefix("strace: ") {
+            // e.g., "strace: Process 1234 attached"
+            return Some(Message::Notice(notice.trim().to_string()));
+        }
+
+        let (pid, line) = split_pid_prefix(line);
+        if let Some(frame) = line.trim_start().strip_prefix("> ") {
+            // the frames of a syscall that was dropped (or not parsed) are dropped as well
+            let owner = self.stack_of?;
+            return parse_frame(frame).map(|frame| {
+                Message::Frame(Frame {
+                    pid: pid.or(owner),
+                    ..frame
+                })
+            });
+        }
+        self.stack_of = None;
+        let (time_micros, rest) = split_timestamp(line);
+        if rest.starts_with("---") {
+            return parse_signal(rest).map(|(name, info)| {
+                Message::Signal(Signal {
+                    pid,
+                    time_micros,
+                    name,
+                    info,
+                })
+            });
+        } else if let Some(skipped) = parse_skipped(rest) {
+            return Some(Message::Skipped(skipped));
+        } else if rest.starts_with("+++") {
+            return parse_exit(rest).map(|status| {
+                Message::Exit(Exit {
+                    pid,
+                    time_micros,
+                    status,
+                })
+            });
+        } else if ltrace::is_libcall_line(rest) {
+            return ltrace::parse_call(rest).map(|call| {
+                Message::LibCall(LibCall {
+                    pid,
+                    time_micros,
+                    ..call
+                })
+            });
+        } else if !is_syscall_line(line) {
+            return None;
+        }
+
+        let line = line.trim_end();
+        if let Some(start) = line.strip_suffix("<unfinished ...>") {
+            self.unfinished.insert(pid, start.to_string());
+            return None;
+        }
+
+        let joined;
+        let line = match line.split_once("resumed>") {
+            Some((before, rest)) if before.contains("<... ") => {
+                match self.unfinished.remove(&pid) {
+                    Some(start) => {
+                        joined = format!("{}{}", start, rest);
+                        &joined
+                    }
+                    // e.g., the process was attached in the middle of a syscall
+                    None => line,
+                }
+            }
+            _ => line,
+        };
+
+        let timestamps = line.starts_with(|c: char| c.is_ascii_digit());
+        let mut syscall = parse_syscall(line, timestamps);
+        if let Some(details) = &syscall.error_details {
+            tracing::warn!(line, error = details.message, "unable to parse syscall");
+        }
+        if self.is_excluded(&syscall.name) {
+            return None;
+        }
+        syscall.pid = pid;
+        self.stack_of = Some(pid);
+        Some(Message::Syscall(syscall))
+    }
+
+    fn is_excluded(&self, name: &str) -> bool {
+        excludes(&self.exclude, name)
+    }
+}
+
+impl<P: Parse + ?Sized> Parse for Box<P> {
+    fn parse_line(&mut self, line: &str) -> Option<Message> {
+        (**self).parse_line(line)
+    }
+
+    fn is_strace(&self) -> bool {
+        (**self).is_strace()
+    }
+}
+
+impl Parse for LineParser {
+    fn parse_line(&mut self, line: &str) -> Option<Message> {
+        LineParser::parse_line(self, line)
+    }
+
+    fn is_strace(&self) -> bool {
+        true
+    }
+}
+
+/// Parses strace's output from `reader` (e.g., a file written with `strace -f -ttt -T -o`), without
+/// spawning anything, for use as a library. Lines that do not complete a message, such as the first
+/// half of an unfinished syscall, yield nothing.
+pub fn parse_reader(reader: impl BufRead) -> impl Iterator<Item = Result<Message>> {
+    let mut parser = LineParser::default();
+    reader.lines().filter_map(move |line| match line {
+        Ok(line) => parser.parse_line(&line).map(Ok),
+        Err(e) => Some(Err(anyhow!("unable to read trace: {}", e))),
+    })
+}
+
+/// Splits the arguments of a syscall, as another tracer printed them, on the commas that are not
+/// inside strings, structs, or arrays.
+pub fn split_args(args: &str) -> Vec<&str> {
+    let mut split = Vec::new();
+    let (mut start, mut depth, mut quoted, mut escaped) = (0, 0, false, false);
+    for (i, c) in args.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            _ if quoted => {}
+            '{' | '[' | '(' => depth += 1,
+            '}' | ']' | ')' => depth -= 1,
+            ',' if depth == 0 => {
+                split.push(args[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    if !args.trim().is_empty() {
+        split.push(args[start..].trim());
+    }
+    split
+}
+
+/// Whether the syscall `name` is one of `exclude`, as returned by `parse_exclude`.
+pub fn excludes(exclude: &[String], name: &str) -> bool {
+    exclude.iter().any(|e| match e.strip_prefix('%') {
+        Some(category) => syscalls::in_category(name, category),
+        None => e == name,
+    })
+}
+
+pub fn split_timestamp(line: &str) -> (u64, &str) {
+    if !line.starts_with(|c: char| c.is_ascii_digit()) {
+        return (0, line);
+    }
+
+    let mut parser = SyscallParser::new(line);
+    match parser.consume_timestamp() {
+        Ok(t) => {
+            parser.whitespace();
+            (t, &line[parser.index..])
+        }
+        Err(_) => (0, line),
+    }
+}
+
+// e.g., "--- SIGCHLD {si_signo=SIGCHLD, si_pid=1235} ---" or "--- stopped by SIGSTOP ---"
+fn parse_signal(text: &str) -> Option<(String, HashMap<String, SyscallArg>)> {
+    let text = text.trim().strip_prefix("---")?.strip_suffix("---")?.trim();
+    let text = text.strip_prefix("stopped by ").unwrap_or(text);
+    let mut parser = SyscallParser::new(text);
+    let name = parser.consume_symbol().ok()?;
+    parser.whitespace();
+    let info = if parser.read() == Some('{') {
+        parser.consume_struct().unwrap_or_default()
+    } else {
+        HashMap::new()
+    };
+    Some((name, info))
+}
+
+fn parse_exit(text: &str) -> Option<ExitKind> {
+    let text = text.trim().strip_prefix("+++")?.strip_suffix("+++")?.trim();
+    if let Some(code) = text.strip_prefix("exited with ") {
+        return code.trim().parse().ok().map(ExitKind::Exited);
+    }
+
+    let rest = text.strip_prefix("killed by ")?;
+    let (signal, core_dumped) = match rest.strip_suffix("(core dumped)") {
+        Some(signal) => (signal.trim(), true),
+        None => (rest.trim(), false),
+    };
+    Some(ExitKind::Killed {
+        signal: signal.to_string(),
+        core_dumped,
+    })
+}
+
+// e.g., "+++ skipped 99 read (3 failed) <0.001234> +++"
+fn parse_skipped(text: &str) -> Option<Skipped> {
+    let text = text
+        .trim()
+        .strip_prefix("+++ skipped ")?
+        .strip_suffix("+++")?;
+    let (text, total_micros) = match text.trim().rsplit_once(" <") {
+        Some((before, secs)) => {
+            let secs: f64 = secs.strip_suffix('>')?.parse().ok()?;
+            (before, (secs * 1e6).round() as u64)
+        }
+        None => (text.trim(), 0),
+    };
+    let (text, errors) = match text
+        .strip_suffix(" failed)")
+        .and_then(|t| t.rsplit_once(" ("))
+    {
+        Some((before, errors)) => (before, errors.parse().ok()?),
+        None => (text, 0),
+    };
+    let (calls, name) = text.split_once(' ')?;
+    Some(Skipped {
+        name: name.to_string(),
+        calls: calls.parse().ok()?,
+        errors,
+        total_micros,
+    })
+}
+
+// strace prefixes lines with the PID as either "[pid 1234] " or, when writing to a file with -o,
+// "1234 "
+// e.g., "/usr/lib/libc.so.6(__write+0x14) [0x114887]", "/usr/bin/cat() [0x4f8e]", or
+// "backtracing_error [0x7f00]"
+fn parse_frame(text: &str) -> Option<Frame> {
+    let (location, address) = text.trim_end().rsplit_once(" [0x")?;
+    let address = u64::from_str_radix(address.strip_suffix(']')?, 16).ok()?;
+    let (module, function, offset) =
+        match location.strip_suffix(')').and_then(|l| l.split_once('(')) {
+            Some((module, symbol)) => match symbol.rsplit_once("+0x") {
+                Some((function, offset)) => (
+                    module,
+                    Some(function),
+                    u64::from_str_radix(offset, 16).ok()?,
+                ),
+                None => (module, Some(symbol).filter(|s| !s.is_empty()), 0),
+            },
+            None => (location, None, 0),
+        };
+    Some(Frame {
+        pid: None,
+        module: module.to_string(),
+        function: function.map(str::to_string),
+        offset,
+        address,
+    })
+}
+
+pub fn split_pid_prefix(line: &str) -> (Option<u32>, &str) {
+    if let Some(rest) = line.strip_prefix("[pid ") {
+        if let Some((pid, rest)) = rest.split_once(']') {
+            if let Ok(pid) = pid.trim().parse() {
+                return (Some(pid), rest.trim_start());
+            }
+        }
+        return (None, line);
+    }
+
+    if let Some((first, rest)) = line.split_once(char::is_whitespace) {
+        // unlike a timestamp, a PID has no decimal point
+        if !first.is_empty() && first.bytes().all(|b| b.is_ascii_digit()) {
+            if let Ok(pid) = first.parse() {
+                return (Some(pid), rest.trim_start());
+            }
+        }
+    }
+
+    (None, line)
+}
+
+/// Parses a file written by `strace -o`, with or without timestamps.
+pub fn parse_file(path: &Path) -> Result<Vec<Syscall>> {
+    let file = File::open(path).map_err(|e| anyhow!("unable to open {}: {}", path.display(), e))?;
+    let mut syscalls = Vec::new();
+    let mut parser = LineParser::default();
+    for line in BufReader::new(file).lines() {
+        let line = line.map_err(|e| anyhow!("unable to read {}: {}", path.display(), e))?;
+        syscalls.extend(parser.parse_line(&line).and_then(Message::into_syscall));
+    }
+    Ok(syscalls)
+}
+
+fn is_syscall_line(line: &str) -> bool {
+    let line = line
+        .trim_start_matches(|c: char| c.is_ascii_digit() || c == '.')
+        .trim_start();
+    // '+++' is used to report the exit code at end of process
+    // '---' is used to report signals
+    // '[ ... ]' is used to report process interactions
+    // '#' is used for comments in vistrace snapshots
+    !(line.is_empty()
+        || line.starts_with('#')
+        || line.starts_with("+++")
+        || line.starts_with("---")
+        || line.starts_with('['))
+}
+
+pub fn parse_syscall(text: &str, timestamps: bool) -> Syscall {
     let mut parser = SyscallParser::new(text);
     match parser.parse(timestamps) {
         Ok(r) => r,
         Err(e) => Syscall {
+            pid: None,
             name: parser.current_name.clone(),
             args: Vec::new(),
             return_value: 0,
+            no_return: false,
+            errno: None,
+            returned: None,
+            explanation: None,
             entry_time_micros: 0,
-            syscall_time_micros: 0,
+            syscall_time_micros: None,
+            injected: false,
             error_details: Some(SyscallErrorDetails {
                 message: e.to_string(),
                 fulltext: text.to_string(),
@@ -148,21 +1038,64 @@ impl<'a> SyscallParser<'a> {
         self.whitespace_comments();
         self.require('=')?;
         self.whitespace_comments();
-        let return_value = self.consume_i64()?;
+        let no_return = self.read() == Some('?');
+        let return_value = if no_return {
+            self.advance();
+            0
+        } else {
+            self.consume_i64()?
+        };
+        self.whitespace();
+        let errno = if return_value < 0 && self.read().is_some_and(|c| c.is_ascii_uppercase()) {
+            Some(self.consume_symbol()?)
+        } else {
+            None
+        };
+        // the rest of ppoll's (e.g., `left {tv_sec=0, tv_nsec=999}`) is skipped
+        let returned = if self.starts_with("([") {
+            self.advance();
+            let returned = self.consume_single_arg().ok().flatten().map(|a| a.value);
+            self.skip_to(')');
+            self.advance();
+            returned
+        } else {
+            None
+        };
+        // strace says so after the explanation, e.g. `= -1 EIO (Input/output error) (INJECTED)`
+        let end = self.bytes[self.index..]
+            .iter()
+            .position(|b| *b == b'<')
+            .map_or(self.bytes.len(), |i| self.index + i);
+        let mut rest = String::from_utf8_lossy(&self.bytes[self.index.min(end)..end]).into_owned();
+        let mut injected = false;
+        for mark in ["(INJECTED)", "(DELAYED)"] {
+            if rest.contains(mark) {
+                injected = true;
+                rest = rest.replace(mark, "");
+            }
+        }
+        let explanation = Some(rest.trim().to_string()).filter(|rest| !rest.is_empty());
+        // strace has no duration for a syscall that never returned
         self.skip_to('<');
-        self.advance();
-        let syscall_time_micros = if timestamps {
-            self.consume_timestamp()?
+        let syscall_time_micros = if timestamps && self.read() == Some('<') {
+            self.advance();
+            Some(self.consume_timestamp()?)
         } else {
-            0
+            None
         };
 
         Ok(Syscall {
+            pid: None,
             name: self.current_name.clone(),
             args,
             return_value,
+            no_return,
+            errno,
+            returned,
+            explanation,
             entry_time_micros,
             syscall_time_micros,
+            injected,
             error_details: None,
         })
     }
@@ -172,12 +1105,7 @@ impl<'a> SyscallParser<'a> {
 
     fn consume_symbol(&mut self) -> Result<String> {
         let start = self.index;
-        loop {
-            let c = match self.read() {
-                Some(c) => c,
-                None => break,
-            };
-
+        while let Some(c) = self.read() {
             if start == self.index {
                 if !c.is_alphabetic() {
                     return Err(anyhow!("expected to see name"));
@@ -191,12 +1119,35 @@ impl<'a> SyscallParser<'a> {
     }
 
     fn consume_arg(&mut self) -> Result<Option<SyscallArg>> {
+        let arg = match self.consume_single_arg()? {
+            Some(arg) => arg,
+            None => return Ok(None),
+        };
+
+        // value-result arguments are printed with their values before and after the syscall,
+        // e.g., `[128 => 16]`; only the value after is kept
+        self.whitespace_comments();
+        if self.starts_with("=>") {
+            self.advance_n(2);
+            let after = self
+                .consume_single_arg()?
+                .ok_or(anyhow!("expected argument after '=>'"))?;
+            return Ok(Some(SyscallArg {
+                name: arg.name,
+                value: after.value,
+            }));
+        }
+        Ok(Some(arg))
+    }
+
+    fn consume_single_arg(&mut self) -> Result<Option<SyscallArg>> {
         // arg can be:
         //   - the literal NULL
         //   - a symbol (e.g., O_RDONLY)
         //   - a flag set (e.g., O_RDONLY|O_CLOEXEC)
         //   - a quoted string (e.g., "path/to/file")
         //     - may be followed by ellipsis
+        //     - may be preceded by '@' for abstract socket addresses, which is kept in the text
         //   - a number (e.g., 1024)
         //   - a number multipled by another number (e.g., 8192*1024)
         //   - an array (e.g., ["df", "-h"])
@@ -204,6 +1155,7 @@ impl<'a> SyscallParser<'a> {
         //     - the final field of the struct may be followed by an ellipsis
         //   - a C-style comment (e.g., /* 40 vars */)
         //   - a function call (e.g., makedev(0x1, 0x3))
+        //   - a reference to a field, as an argument to a function call (e.g., &sin6_addr)
         //
 
         // this technically matches malformed strings like "(,a,b)"
@@ -260,6 +1212,19 @@ impl<'a> SyscallParser<'a> {
                 text,
                 truncated,
             })))
+        } else if c == '@' {
+            self.advance();
+            let (text, truncated) = self.consume_quoted()?;
+            Ok(Some(SyscallArg::positional(SyscallArgValue::Quoted {
+                text: format!("@{}", text),
+                truncated,
+            })))
+        } else if c == '&' {
+            self.advance();
+            let symbol = self.consume_symbol()?;
+            Ok(Some(SyscallArg::positional(SyscallArgValue::Symbol(
+                format!("&{}", symbol),
+            ))))
         } else if c == '{' {
             let st = self.consume_struct()?;
             Ok(Some(SyscallArg::positional(SyscallArgValue::Struct(st))))
@@ -319,6 +1284,20 @@ impl<'a> SyscallParser<'a> {
             }
 
             let field = self.consume_symbol()?;
+            if self.read() == Some('(') {
+                // some fields are printed as a call that fills them in, named by its last
+                // argument, e.g., `inet_pton(AF_INET6, "::1", &sin6_addr)`
+                self.advance();
+                let args = self.consume_arg_list()?;
+                self.require(')')?;
+                let name = match args.last().map(|a| &a.value) {
+                    Some(SyscallArgValue::Symbol(s)) if s.starts_with('&') => s[1..].to_string(),
+                    _ => field.clone(),
+                };
+                let call = SyscallArgValue::FunctionCall(field, args);
+                r.insert(name, SyscallArg::positional(call));
+                continue;
+            }
             self.require('=')?;
             let value = match self.consume_arg()? {
                 Some(v) => v,
@@ -341,12 +1320,7 @@ impl<'a> SyscallParser<'a> {
     fn consume_flagset(&mut self, first: String) -> Result<Vec<FlagSetValue>> {
         self.require('|')?;
         let mut r = vec![FlagSetValue::Symbol(first)];
-        loop {
-            let c = match self.read() {
-                Some(c) => c,
-                None => break,
-            };
-
+        while let Some(c) = self.read() {
             if c.is_ascii_digit() {
                 let bits = self.consume_i64()?;
                 r.push(FlagSetValue::Bits(bits));
@@ -374,12 +1348,7 @@ impl<'a> SyscallParser<'a> {
 
         let radix = self.consume_optional_i64_prefix();
         let mut r = 0i64;
-        loop {
-            let c = match self.read() {
-                Some(c) => c,
-                None => break,
-            };
-
+        while let Some(c) = self.read() {
             match c.to_digit(radix) {
                 Some(v) => {
                     r *= radix as i64;
@@ -396,12 +1365,7 @@ impl<'a> SyscallParser<'a> {
     fn consume_timestamp(&mut self) -> Result<u64> {
         let mut r = 0u64;
         let mut decimal_places_seen = -1;
-        loop {
-            let c = match self.read() {
-                Some(c) => c,
-                None => break,
-            };
-
+        while let Some(c) = self.read() {
             if let Some(v) = c.to_digit(10) {
                 self.advance();
                 if decimal_places_seen >= 0 {
@@ -514,16 +1478,11 @@ impl<'a> SyscallParser<'a> {
     }
 
     fn skip_to(&mut self, delim: char) {
-        loop {
-            match self.read() {
-                Some(c) => {
-                    if c == delim {
-                        break;
-                    }
-                    self.advance();
-                }
-                None => break,
+        while let Some(c) = self.read() {
+            if c == delim {
+                break;
             }
+            self.advance();
         }
     }
 
@@ -563,6 +1522,303 @@ impl<'a> SyscallParser<'a> {
     }
 }
 
+impl Syscall {
+    /// returns the argument at `index`, ignoring argument names
+    pub fn arg(&self, index: usize) -> Option<&SyscallArg> {
+        self.args.get(index)
+    }
+
+    /// The return value, or `None` if the syscall never returned or its line could not be parsed.
+    pub fn result(&self) -> Option<i64> {
+        (!self.no_return && self.error_details.is_none()).then_some(self.return_value)
+    }
+}
+
+impl fmt::Display for Syscall {
+    // renders the syscall in the same format that strace uses
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(details) = &self.error_details {
+            return write!(f, "{}", details.fulltext.trim_end());
+        }
+
+        write_prefix(f, self.pid, self.entry_time_micros)?;
+        write!(f, "{}(", self.name)?;
+        write_args(f, &self.args)?;
+        if self.no_return {
+            write!(f, ") = ?")?;
+        } else {
+            write!(f, ") = {}", self.return_value)?;
+        }
+        if let Some(errno) = &self.errno {
+            write!(f, " {}", errno)?;
+        }
+        if let Some(returned) = &self.returned {
+            write!(f, " ({})", returned)?;
+        }
+        if let Some(explanation) = &self.explanation {
+            write!(f, " {}", explanation)?;
+        }
+        if self.injected {
+            write!(f, " (INJECTED)")?;
+        }
+        if let Some(micros) = self.syscall_time_micros {
+            write!(f, " <{}>", format_timestamp(micros))?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for LibCall {
+    // strace has no such line, so the "->" (which ltrace puts after the caller) marks it even if
+    // the caller is unknown
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_prefix(f, self.pid, self.time_micros)?;
+        write!(
+            f,
+            "{}->{}({}) = {}",
+            self.caller.as_deref().unwrap_or_default(),
+            self.name,
+            self.args,
+            self.return_value
+        )?;
+        if self.duration_micros != 0 {
+            write!(f, " <{}>", format_timestamp(self.duration_micros))?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Message {
+    // renders the message in the same format that strace uses
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Message::Syscall(syscall) => write!(f, "{}", syscall),
+            Message::Signal(signal) => {
+                write_prefix(f, signal.pid, signal.time_micros)?;
+                write!(f, "--- {} ", signal.name)?;
+                if !signal.info.is_empty() {
+                    write!(f, "{} ", SyscallArgValue::Struct(signal.info.clone()))?;
+                }
+                write!(f, "---")
+            }
+            Message::Exit(exit) => {
+                write_prefix(f, exit.pid, exit.time_micros)?;
+                write!(f, "+++ {} +++", exit.status)
+            }
+            Message::Frame(frame) => write!(f, " > {}", frame),
+            Message::LibCall(call) => write!(f, "{}", call),
+            Message::Skipped(skipped) => {
+                write!(f, "+++ skipped {} {}", skipped.calls, skipped.name)?;
+                if skipped.errors > 0 {
+                    write!(f, " ({} failed)", skipped.errors)?;
+                }
+                if skipped.total_micros > 0 {
+                    write!(f, " <{}>", format_timestamp(skipped.total_micros))?;
+                }
+                write!(f, " +++")
+            }
+            Message::Notice(notice) => write!(f, "strace: {}", notice),
+        }
+    }
+}
+
+// same format as `strace -f -o`
+fn write_prefix(f: &mut fmt::Formatter<'_>, pid: Option<u32>, time_micros: u64) -> fmt::Result {
+    if let Some(pid) = pid {
+        write!(f, "{} ", pid)?;
+    }
+    if time_micros != 0 {
+        write!(f, "{} ", format_timestamp(time_micros))?;
+    }
+    Ok(())
+}
+
+impl fmt::Display for Frame {
+    // same format as strace, without the leading " > "
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.function {
+            Some(function) if self.offset != 0 => write!(
+                f,
+                "{}({}+{:#x}) [{:#x}]",
+                self.module, function, self.offset, self.address
+            ),
+            Some(function) => write!(f, "{}({}) [{:#x}]", self.module, function, self.address),
+            None => write!(f, "{}() [{:#x}]", self.module, self.address),
+        }
+    }
+}
+
+impl fmt::Display for ExitKind {
+    // same wording as strace
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExitKind::Exited(code) => write!(f, "exited with {}", code),
+            ExitKind::Killed {
+                signal,
+                core_dumped,
+            } => {
+                write!(f, "killed by {}", signal)?;
+                if *core_dumped {
+                    write!(f, " (core dumped)")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl fmt::Display for SyscallArg {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.name.is_empty() {
+            write!(f, "{}=", self.name)?;
+        }
+        write!(f, "{}", self.value)
+    }
+}
+
+impl fmt::Display for SyscallArgValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SyscallArgValue::Quoted { text, truncated } => {
+                write!(f, "\"{}\"", text)?;
+                if *truncated {
+                    write!(f, "...")?;
+                }
+                Ok(())
+            }
+            SyscallArgValue::Symbol(s) => write!(f, "{}", s),
+            SyscallArgValue::FlagSet(flags) => {
+                for (i, flag) in flags.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, "|")?;
+                    }
+                    match flag {
+                        FlagSetValue::Symbol(s) => write!(f, "{}", s)?,
+                        FlagSetValue::Bits(x) => write!(f, "0{:o}", x)?,
+                    }
+                }
+                Ok(())
+            }
+            SyscallArgValue::Number(x) => write_number(f, *x),
+            SyscallArgValue::Product(x, y) => write!(f, "{}*{}", x, y),
+            SyscallArgValue::Array(xs) => {
+                write!(f, "[")?;
+                write_args(f, xs)?;
+                write!(f, "]")
+            }
+            SyscallArgValue::Struct(fields) => {
+                // HashMap has no inherent order, so sort to get stable output
+                let mut keys: Vec<&String> = fields.keys().collect();
+                keys.sort();
+                write!(f, "{{")?;
+                for (i, key) in keys.into_iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}={}", key, fields[key].value)?;
+                }
+                write!(f, "}}")
+            }
+            SyscallArgValue::FunctionCall(name, args) => {
+                write!(f, "{}(", name)?;
+                write_args(f, args)?;
+                write!(f, ")")
+            }
+        }
+    }
+}
+
+fn write_args(f: &mut fmt::Formatter<'_>, args: &[SyscallArg]) -> fmt::Result {
+    for (i, arg) in args.iter().enumerate() {
+        if i > 0 {
+            write!(f, ", ")?;
+        }
+        write!(f, "{}", arg)?;
+    }
+    Ok(())
+}
+
+fn write_number(f: &mut fmt::Formatter<'_>, x: i64) -> fmt::Result {
+    // large values are almost always addresses, which strace prints in hex
+    if x > 0xffffff {
+        write!(f, "{:#x}", x)
+    } else {
+        write!(f, "{}", x)
+    }
+}
+
+pub fn format_timestamp(micros: u64) -> String {
+    format!("{}.{:06}", micros / 1_000_000, micros % 1_000_000)
+}
+
+/// Escapes raw bytes as strace prints them in a string, the inverse of `unescape`.
+pub fn escape(bytes: &[u8]) -> String {
+    let mut r = String::with_capacity(bytes.len());
+    for (i, b) in bytes.iter().enumerate() {
+        match b {
+            b'"' => r.push_str("\\\""),
+            b'\\' => r.push_str("\\\\"),
+            b'\t' => r.push_str("\\t"),
+            b'\n' => r.push_str("\\n"),
+            0x0b => r.push_str("\\v"),
+            0x0c => r.push_str("\\f"),
+            b'\r' => r.push_str("\\r"),
+            0x20..=0x7e => r.push(*b as char),
+            // as few octal digits as will do, unless a digit follows
+            _ if bytes.get(i + 1).is_some_and(u8::is_ascii_digit) => {
+                r.push_str(&format!("\\{:03o}", b))
+            }
+            _ => r.push_str(&format!("\\{:o}", b)),
+        }
+    }
+    r
+}
+
+/// Resolves the backslash escapes in a string as strace prints it, e.g. `\177ELF\2` (or
+/// `\x7fELF\x02` with `-x`), into the raw bytes.
+pub fn unescape(text: &str) -> Vec<u8> {
+    let bytes = text.as_bytes();
+    let mut r = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] != b'\\' || i + 1 == bytes.len() {
+            r.push(bytes[i]);
+            i += 1;
+            continue;
+        }
+
+        i += 1;
+        let (digits, radix) = match bytes[i] {
+            b'x' => {
+                i += 1;
+                (2, 16)
+            }
+            b'0'..=b'7' => (3, 8),
+            c => {
+                r.push(match c {
+                    b't' => b'\t',
+                    b'n' => b'\n',
+                    b'v' => 0x0b,
+                    b'f' => 0x0c,
+                    b'r' => b'\r',
+                    c => c,
+                });
+                i += 1;
+                continue;
+            }
+        };
+        // strace prints as few octal digits as it can
+        let mut n: u32 = 0;
+        let start = i;
+        while i < bytes.len() && i - start < digits && (bytes[i] as char).is_digit(radix) {
+            n = n * radix + (bytes[i] as char).to_digit(radix).unwrap();
+            i += 1;
+        }
+        r.push(n as u8);
+    }
+    r
+}
+
 impl SyscallArg {
     fn positional(value: SyscallArgValue) -> Self {
         Self {
@@ -579,10 +1835,19 @@ impl SyscallArg {
 #[cfg(test)]
 mod tests {
     use std::collections::HashMap;
+    #[cfg(feature = "native")]
+    use std::path::Path;
+    #[cfg(feature = "native")]
+    use std::process::Command;
 
-    use crate::strace::{parse_syscall, FlagSetValue};
+    #[cfg(feature = "native")]
+    use crate::strace::{command, parse_version};
+    use crate::strace::{
+        parse_exclude, parse_strace_args, parse_syscall, split_pid_prefix, ExitKind, FlagSetValue,
+        LineParser, Message, Syscall,
+    };
 
-    use super::{SyscallArg, SyscallArgValue, SyscallParser};
+    use super::{escape, unescape, SyscallArg, SyscallArgValue, SyscallParser};
 
     #[test]
     fn test_syscall_parse() {
@@ -675,7 +1940,10 @@ mod tests {
         assert_arg_number(&sc.args[2], 0xef6aae8510f0);
         assert_eq!(sc.args[2].name, "child_tidptr");
 
-        sc = parse_syscall("fstat(2, {st_mode=S_IFCHR|0666, st_rdev=makedev(0x1, 0x3), ...}) = 0", false);
+        sc = parse_syscall(
+            "fstat(2, {st_mode=S_IFCHR|0666, st_rdev=makedev(0x1, 0x3), ...}) = 0",
+            false,
+        );
         assert_eq!(sc.name, "fstat");
         assert_eq!(sc.args.len(), 2);
         let fields = assert_arg_struct(&sc.args[1]);
@@ -690,6 +1958,70 @@ mod tests {
         // TODO: "wait4(-1, [{WIFEXITED(s) && WEXITSTATUS(s) == 0}], WNOHANG, NULL) = 2082600"
     }
 
+    #[test]
+    fn test_syscall_parse_errno() {
+        let sc = parse_syscall(
+            "openat(AT_FDCWD, \"/etc/nope\", O_RDONLY) = -1 ENOENT (No such file or directory)",
+            false,
+        );
+        assert_eq!(sc.return_value, -1);
+        assert_eq!(sc.errno.as_deref(), Some("ENOENT"));
+
+        let sc = parse_syscall("1720000000.000001 close(3) = 0 <0.000012>", true);
+        assert_eq!(sc.errno, None);
+        assert_eq!(sc.entry_time_micros, 1720000000000001);
+        assert_eq!(sc.syscall_time_micros, Some(12));
+        assert!(!sc.injected);
+
+        let sc = parse_syscall(
+            "1720000000.000001 openat(AT_FDCWD, \"/a\", O_RDONLY) = -1 EIO (Input/output error) (INJECTED) <0.000003>",
+            true,
+        );
+        assert_eq!(sc.errno.as_deref(), Some("EIO"));
+        assert!(sc.injected);
+        assert_eq!(sc.syscall_time_micros, Some(3));
+        assert_eq!(
+            sc.to_string(),
+            "1720000000.000001 openat(AT_FDCWD, \"/a\", O_RDONLY) = -1 EIO (Input/output error) \
+             (INJECTED) <0.000003>"
+        );
+    }
+
+    #[test]
+    fn test_syscall_display() {
+        let line = "1720000000.000001 openat(AT_FDCWD, \"/etc/nope\", O_RDONLY|O_CLOEXEC) = -1 ENOENT <0.000012>";
+        let sc = parse_syscall(line, true);
+        assert_eq!(sc.to_string(), line);
+
+        let sc = parse_syscall(
+            "fstat(1, {st_mode=S_IFIFO|0600, st_size=0, ...}) = 0",
+            false,
+        );
+        assert_eq!(
+            sc.to_string(),
+            "fstat(1, {st_mode=S_IFIFO|0600, st_size=0}) = 0"
+        );
+
+        let line = "poll([{events=POLLIN, fd=3}, {events=POLLIN, fd=4}], 2, -1) = 1 ([{fd=4, revents=POLLIN}])";
+        let sc = parse_syscall(line, false);
+        assert_eq!(sc.to_string(), line);
+    }
+
+    #[test]
+    fn test_syscall_no_return() {
+        let line = "1720000000.000001 exit_group(0) = ?";
+        let sc = parse_syscall(line, true);
+        assert!(sc.error_details.is_none());
+        assert!(sc.no_return);
+        assert_eq!(sc.result(), None);
+        assert_eq!(sc.to_string(), line);
+
+        // a real return of 0 is not mistaken for one
+        let sc = parse_syscall("close(3) = 0", false);
+        assert!(!sc.no_return);
+        assert_eq!(sc.result(), Some(0));
+    }
+
     #[test]
     fn test_syscall_parse_partial() {
         let sc = parse_syscall("write(", false);
@@ -697,6 +2029,207 @@ mod tests {
         assert!(sc.error_details.is_some());
     }
 
+    #[test]
+    #[cfg(feature = "native")]
+    fn test_parse_version() {
+        assert_eq!(parse_version("strace -- version 6.1"), Some((6, 1)));
+        assert_eq!(parse_version("strace -- version 5.10.0.42"), Some((5, 10)));
+        assert_eq!(parse_version("strace -- version 6"), Some((6, 0)));
+        assert_eq!(parse_version("ltrace version 0.7.3."), None);
+    }
+
+    #[test]
+    fn test_split_pid_prefix() {
+        assert_eq!(
+            split_pid_prefix("[pid 123] close(3) = 0"),
+            (Some(123), "close(3) = 0")
+        );
+        assert_eq!(
+            split_pid_prefix("[pid  77] close(3) = 0"),
+            (Some(77), "close(3) = 0")
+        );
+        assert_eq!(
+            split_pid_prefix("123   1720000000.000001 close(3) = 0"),
+            (Some(123), "1720000000.000001 close(3) = 0")
+        );
+        assert_eq!(
+            split_pid_prefix("1720000000.000001 close(3) = 0"),
+            (None, "1720000000.000001 close(3) = 0")
+        );
+        assert_eq!(split_pid_prefix("close(3) = 0"), (None, "close(3) = 0"));
+        assert_eq!(split_pid_prefix("[pid 123"), (None, "[pid 123"));
+    }
+
+    fn parse_line_syscall(p: &mut LineParser, line: &str) -> Syscall {
+        p.parse_line(line)
+            .and_then(Message::into_syscall)
+            .expect("expected a syscall")
+    }
+
+    #[test]
+    fn test_line_parser() {
+        let mut p = LineParser::default();
+        assert!(p
+            .parse_line("[pid 10] 1720000000.000001 read(3,  <unfinished ...>\n")
+            .is_none());
+        let sc = parse_line_syscall(
+            &mut p,
+            "[pid 11] 1720000000.000002 close(4) = 0 <0.000001>\n",
+        );
+        assert_eq!(sc.pid, Some(11));
+        assert_eq!(sc.name, "close");
+        let sc = parse_line_syscall(
+            &mut p,
+            "[pid 10] 1720000000.000003 <... read resumed>\"abc\", 4096) = 3 <0.000002>\n",
+        );
+        assert_eq!(sc.pid, Some(10));
+        assert_eq!(sc.name, "read");
+        assert_eq!(sc.args.len(), 3);
+        assert_eq!(sc.return_value, 3);
+        assert_eq!(sc.entry_time_micros, 1720000000000001);
+        assert_eq!(sc.syscall_time_micros, Some(2));
+        assert!(sc.error_details.is_none());
+
+        assert!(p
+            .parse_line("[ Process PID=10 runs in x32 mode. ]\n")
+            .is_none());
+        assert!(matches!(
+            p.parse_line("strace: Process 10 attached\n"),
+            Some(Message::Notice(notice)) if notice == "Process 10 attached"
+        ));
+    }
+
+    #[test]
+    fn test_exclude() {
+        let exclude = parse_exclude(&["futex".to_string(), "memory".to_string()]).unwrap();
+        assert_eq!(exclude, vec!["futex", "%memory"]);
+        assert!(parse_exclude(&["%bogus".to_string()]).is_err());
+
+        let mut p = LineParser::with_exclude(exclude);
+        assert!(p.parse_line("futex(0x7f, FUTEX_WAKE, 1) = 0").is_none());
+        assert!(p.parse_line("brk(NULL) = 0x55d4").is_none());
+        assert!(p.parse_line("close(3) = 0").is_some());
+        assert!(p.parse_line("+++ exited with 0 +++").is_some());
+    }
+
+    #[test]
+    fn test_parse_strace_args() {
+        let parse = |values: &[&str], exclude: &[&str]| {
+            let values: Vec<String> = values.iter().map(|v| v.to_string()).collect();
+            let exclude: Vec<String> = exclude.iter().map(|v| v.to_string()).collect();
+            parse_strace_args(&values, &exclude)
+        };
+        assert_eq!(
+            parse(&["-e trace=%file", "-s 4096", "-v"], &[]).unwrap(),
+            ["-e", "trace=%file", "-s", "4096", "-v"]
+        );
+        assert!(parse(&["-s4096", "-yy", "--string-limit=100"], &[]).is_ok());
+        assert!(parse(&["-e trace=%file"], &["futex"]).is_err());
+        assert!(parse(&["-etrace=%file"], &["futex"]).is_err());
+        assert!(parse(&["--trace=%file"], &["futex"]).is_err());
+        assert!(parse(&["-e signal=none"], &["futex"]).is_ok());
+        assert!(parse(&["-tt"], &[]).is_err());
+        assert!(parse(&["-vf"], &[]).is_err());
+        assert!(parse(&["-o /tmp/x"], &[]).is_err());
+        assert!(parse(&["--output=/tmp/x"], &[]).is_err());
+        assert!(parse(&["-k"], &[]).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "native")]
+    fn test_command() {
+        let args = |cmd: &Command| -> Vec<String> {
+            cmd.get_args()
+                .map(|a| a.to_string_lossy().into_owned())
+                .collect()
+        };
+        let words = [
+            "-f".to_string(),
+            "echo".to_string(),
+            "it's here".to_string(),
+        ];
+        let local = command(Path::new("strace"), &words, None);
+        assert_eq!(local.get_program(), "strace");
+        assert_eq!(args(&local), words);
+
+        let remote = command(Path::new("strace"), &words, Some("me@host"));
+        assert_eq!(remote.get_program(), "ssh");
+        assert_eq!(
+            args(&remote),
+            ["-n", "me@host", "--", r"strace -f echo 'it'\''s here'"]
+        );
+    }
+
+    #[test]
+    fn test_parse_signal_and_exit() {
+        let mut p = LineParser::default();
+        match p.parse_line(
+            "[pid 10] 1720000000.000004 --- SIGCHLD {si_signo=SIGCHLD, si_code=CLD_EXITED, si_pid=11} ---\n",
+        ) {
+            Some(Message::Signal(signal)) => {
+                assert_eq!(signal.pid, Some(10));
+                assert_eq!(signal.time_micros, 1720000000000004);
+                assert_eq!(signal.name, "SIGCHLD");
+                assert_arg_number(signal.info.get("si_pid").unwrap(), 11);
+            }
+            _ => panic!("expected signal"),
+        }
+
+        let line = "10 1720000000.000004 --- SIGCHLD {si_pid=11, si_signo=SIGCHLD} ---";
+        assert_eq!(p.parse_line(line).unwrap().to_string(), line);
+        let line = "1720000000.000005 +++ killed by SIGSEGV (core dumped) +++";
+        assert_eq!(p.parse_line(line).unwrap().to_string(), line);
+
+        match p.parse_line("--- stopped by SIGTTOU ---") {
+            Some(Message::Signal(signal)) => {
+                assert_eq!(signal.name, "SIGTTOU");
+                assert!(signal.info.is_empty());
+            }
+            _ => panic!("expected signal"),
+        }
+
+        match p.parse_line("10 1720000000.000005 +++ exited with 3 +++\n") {
+            Some(Message::Exit(exit)) => {
+                assert_eq!(exit.pid, Some(10));
+                assert!(matches!(exit.status, ExitKind::Exited(3)));
+            }
+            _ => panic!("expected exit"),
+        }
+
+        match p.parse_line("+++ killed by SIGSEGV (core dumped) +++") {
+            Some(Message::Exit(exit)) => {
+                assert_eq!(exit.pid, None);
+                assert!(matches!(
+                    exit.status,
+                    ExitKind::Killed { signal, core_dumped: true } if signal == "SIGSEGV"
+                ));
+            }
+            _ => panic!("expected exit"),
+        }
+    }
+
+    #[test]
+    fn test_parse_frames() {
+        let mut p = LineParser::with_exclude(vec!["close".to_string()]);
+        // frames before any syscall, or after one that was excluded, are dropped
+        assert!(p.parse_line(" > /usr/bin/cat() [0x4f8e]").is_none());
+        assert!(p.parse_line("10 write(1, \"hi\\n\", 3) = 3").is_some());
+        match p.parse_line(" > /usr/lib/libc.so.6(__write+0x14) [0x114887]\n") {
+            Some(Message::Frame(frame)) => {
+                assert_eq!(frame.pid, Some(10));
+                assert_eq!(frame.module, "/usr/lib/libc.so.6");
+                assert_eq!(frame.function.as_deref(), Some("__write"));
+                assert_eq!(frame.offset, 0x14);
+                assert_eq!(frame.address, 0x114887);
+            }
+            _ => panic!("expected frame"),
+        }
+        let line = " > /usr/bin/cat() [0x4f8e]";
+        assert_eq!(p.parse_line(line).unwrap().to_string(), line);
+        assert!(p.parse_line("10 close(3) = 0").is_none());
+        assert!(p.parse_line(" > /usr/bin/cat() [0x4f8e]").is_none());
+    }
+
     #[test]
     fn test_consume_symbol() {
         let mut p = SyscallParser::new("read");
@@ -729,6 +2262,34 @@ mod tests {
         assert!(p.consume_arg().unwrap().is_none());
     }
 
+    #[test]
+    fn test_consume_sockaddr() {
+        let mut p = SyscallParser::new(
+            "{sa_family=AF_INET6, sin6_port=htons(443), inet_pton(AF_INET6, \"::1\", &sin6_addr)}, [128 => 28], {sa_family=AF_UNIX, sun_path=@\"x\"}",
+        );
+        let fields = match p.consume_arg().unwrap().unwrap().value {
+            SyscallArgValue::Struct(fields) => fields,
+            value => panic!("expected struct, got {:?}", value),
+        };
+        match &fields["sin6_addr"].value {
+            SyscallArgValue::FunctionCall(name, args) => {
+                assert_eq!(name, "inet_pton");
+                assert_arg_string(&args[1], "::1", false);
+            }
+            value => panic!("expected function call, got {:?}", value),
+        }
+
+        match p.consume_arg().unwrap().unwrap().value {
+            SyscallArgValue::Array(values) => assert_arg_number(&values[0], 28),
+            value => panic!("expected array, got {:?}", value),
+        }
+
+        match p.consume_arg().unwrap().unwrap().value {
+            SyscallArgValue::Struct(fields) => assert_arg_string(&fields["sun_path"], "@x", false),
+            value => panic!("expected struct, got {:?}", value),
+        }
+    }
+
     #[test]
     fn test_consume_i64() {
         let mut p = SyscallParser::new("123");
@@ -762,6 +2323,20 @@ mod tests {
         assert!(truncated);
     }
 
+    #[test]
+    fn test_unescape() {
+        assert_eq!(unescape(r"\177ELF\2\1\1\0"), b"\x7fELF\x02\x01\x01\x00");
+        assert_eq!(unescape(r"\x7fELF\x02"), b"\x7fELF\x02");
+        assert_eq!(unescape(r#"a\tb\n\"\\"#), b"a\tb\n\"\\");
+        // a digit after an octal escape is part of the text if the escape has three digits
+        assert_eq!(unescape(r"\0011"), b"\x011");
+
+        assert_eq!(escape(b"\x7fELF\x02\x01\x001"), r"\177ELF\2\1\0001");
+        assert_eq!(escape(b"a\tb\n\"\\"), r#"a\tb\n\"\\"#);
+        let bytes: Vec<u8> = (0..=255).collect();
+        assert_eq!(unescape(&escape(&bytes)), bytes);
+    }
+
     #[test]
     fn test_advance_whitespace() {
         let mut p = SyscallParser::new("  /* one comment */   ab    ");
@@ -854,4 +2429,57 @@ mod tests {
             panic!("expected SyscallArg::FunctionCall, got {:?}", arg);
         }
     }
+
+    #[test]
+    fn test_parse_reader() {
+        let text = "[pid 10] 1720000000.000001 read(3,  <unfinished ...>\n\
+                    [pid 11] 1720000000.000002 close(4) = 0\n\
+                    [pid 10] 1720000000.000003 <... read resumed>\"abc\", 4096) = 3\n\
+                    [pid 10] 1720000000.000004 +++ exited with 0 +++\n";
+        let messages: Vec<String> = super::parse_reader(text.as_bytes())
+            .map(|msg| msg.unwrap().to_string())
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                "11 1720000000.000002 close(4) = 0",
+                "10 1720000000.000001 read(3, \"abc\", 4096) = 3",
+                "10 1720000000.000004 +++ exited with 0 +++",
+            ]
+        );
+
+        let invalid: &[u8] = b"close(4) = 0\n\xff\xfe\n";
+        let results: Vec<bool> = super::parse_reader(invalid).map(|r| r.is_ok()).collect();
+        assert_eq!(results, [true, false]);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let text = "[pid 10] 1720000000.000001 openat(AT_FDCWD, \"/nope\", O_RDONLY) = -1 ENOENT (No such file or directory) <0.000012>\n\
+                    [pid 10] 1720000000.000002 read(3,  <unfinished ...>\n\
+                    [pid 11] 1720000000.000003 wait4(-1, NULL, 0, NULL) = ? ERESTARTSYS (To be restarted if SA_RESTART is set)\n\
+                    [pid 10] 1720000000.000004 <... read resumed>\"abc\", 4096) = 3\n\
+                    [pid 10] 1720000000.000005 poll([{fd=3, events=POLLIN}], 1, 0) = 0 (Timeout)\n\
+                    [pid 10] 1720000000.000006 write(1, \"x\", 1) = -1 EIO (Input/output error) (INJECTED)\n\
+                    [pid 10] 1720000000.000007 exit_group(0) = ?\n";
+        let print = |text: &str| -> Vec<String> {
+            super::parse_reader(text.as_bytes())
+                .map(|msg| msg.unwrap().to_string())
+                .collect()
+        };
+        let printed = print(text);
+        assert_eq!(
+            printed,
+            [
+                "10 1720000000.000001 openat(AT_FDCWD, \"/nope\", O_RDONLY) = -1 ENOENT (No such file or directory) <0.000012>",
+                "11 1720000000.000003 wait4(-1, NULL, 0, NULL) = ? ERESTARTSYS (To be restarted if SA_RESTART is set)",
+                "10 1720000000.000002 read(3, \"abc\", 4096) = 3",
+                "10 1720000000.000005 poll([{events=POLLIN, fd=3}], 1, 0) = 0 (Timeout)",
+                "10 1720000000.000006 write(1, \"x\", 1) = -1 EIO (Input/output error) (INJECTED)",
+                "10 1720000000.000007 exit_group(0) = ?",
+            ]
+        );
+        // and vistrace reads it back the same
+        assert_eq!(print(&(printed.join("\n") + "\n")), printed);
+    }
 }
@@ -0,0 +1,947 @@
+// A non-interactive report of a whole trace, similar to `strace -c` but with a report of the files
+// that were accessed and the tree of processes as well.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{self, Write};
+
+use crate::antidebug::AntiDebug;
+use crate::audit::Audit;
+use crate::blocking::Blocking;
+use crate::changes::Changes;
+use crate::commands::Commands;
+use crate::config::{ConfigFiles, Status};
+use crate::csv;
+use crate::dns::{self, Resolution};
+use crate::epoll::EventLoops;
+use crate::eventfds::EventFds;
+use crate::fdtable::FdTable;
+use crate::futex::Futexes;
+use crate::heatmap::{self, Heatmap};
+use crate::http::Exchanges;
+use crate::iosizes::IoSizes;
+use crate::ipc::IpcObjects;
+use crate::libraries::Libraries;
+use crate::loops::Loops;
+use crate::memory::Memory;
+use crate::namespaces::{Container, Namespaces};
+use crate::net::Connections;
+use crate::outliers::Outliers;
+use crate::palette;
+use crate::peers::Channels;
+use crate::postmortem::PostMortem;
+use crate::privileges::Privileges;
+use crate::processes::ProcessTable;
+use crate::search::Searches;
+use crate::strace::{format_timestamp, Message, Syscall};
+use crate::symlinks::Symlinks;
+use crate::syscalls;
+use crate::units;
+
+// columns in the heatmap of syscalls over time
+const HEATMAP_WIDTH: usize = 60;
+
+#[derive(Default)]
+pub struct Summary {
+    syscalls: BTreeMap<String, SyscallStats>,
+    files: BTreeMap<String, FileStats>,
+    processes: ProcessTable,
+    commands: Commands,
+    fds: FdTable,
+    net: Connections,
+    channels: Channels,
+    resolutions: Vec<Resolution>,
+    http: Exchanges,
+    privileges: Privileges,
+    searches: Searches,
+    libraries: Libraries,
+    config: ConfigFiles,
+    postmortem: PostMortem,
+    loops: Loops,
+    outliers: Outliers,
+    antidebug: AntiDebug,
+    futexes: Futexes,
+    event_loops: EventLoops,
+    event_fds: EventFds,
+    changes: Changes,
+    heatmap: Heatmap,
+    memory: Memory,
+    io_sizes: IoSizes,
+    ipc: IpcObjects,
+    namespaces: Namespaces,
+    symlinks: Symlinks,
+    blocking: Blocking,
+    // only with --audit
+    audit: Option<Audit>,
+}
+
+#[derive(Default)]
+pub struct SyscallStats {
+    pub calls: usize,
+    pub errors: usize,
+    pub total_micros: u64,
+}
+
+#[derive(Default)]
+struct FileStats {
+    calls: usize,
+    errors: usize,
+    errnos: BTreeSet<String>,
+}
+
+impl SyscallStats {
+    pub fn add(&mut self, syscall: &Syscall) {
+        self.calls += 1;
+        self.total_micros += syscall.syscall_time_micros.unwrap_or(0);
+        if syscall.errno.is_some() {
+            self.errors += 1;
+        }
+    }
+}
+
+/// Returns the stats of each syscall in `stats`, those that took the most time first.
+pub fn rank(stats: &BTreeMap<String, SyscallStats>) -> Vec<(&String, &SyscallStats)> {
+    let mut rows: Vec<(&String, &SyscallStats)> = stats.iter().collect();
+    rows.sort_by(|a, b| {
+        (b.1.total_micros, b.1.calls)
+            .cmp(&(a.1.total_micros, a.1.calls))
+            .then(a.0.cmp(b.0))
+    });
+    rows
+}
+
+impl Summary {
+    /// A summary with a section for what `audit` flags.
+    pub fn with_audit() -> Self {
+        Summary {
+            audit: Some(Audit::default()),
+            ..Default::default()
+        }
+    }
+
+    pub fn update(&mut self, msg: Message) {
+        match msg {
+            Message::Syscall(syscall) => self.update_syscall(&syscall),
+            Message::Exit(exit) => {
+                self.net.record_exit(&exit, &self.fds);
+                self.postmortem.record_exit(&exit);
+                self.commands.record_exit(&exit);
+                self.processes.record_exit(exit);
+            }
+            Message::Signal(signal) => self.postmortem.record_signal(&signal),
+            Message::Skipped(skipped) => {
+                let stats = self.syscalls.entry(skipped.name).or_default();
+                stats.calls += skipped.calls as usize;
+                stats.errors += skipped.errors as usize;
+                stats.total_micros += skipped.total_micros;
+            }
+            Message::Frame(_) | Message::LibCall(_) | Message::Notice(_) => {}
+        }
+    }
+
+    /// Records the working directory that process `pid` (or, if `None`, any process) started in.
+    pub fn set_cwd(&mut self, pid: Option<u32>, dir: String) {
+        self.fds.set_cwd(pid, dir);
+    }
+
+    /// Looks up paths on this machine to see where symlinks lead (see `Symlinks::host`).
+    pub fn resolve_links(&mut self, on: bool) {
+        self.symlinks.host = on;
+    }
+
+    /// Records that `container` was attached to.
+    pub fn add_container(&mut self, container: Container) {
+        self.namespaces.add_container(container);
+    }
+
+    fn update_syscall(&mut self, syscall: &Syscall) {
+        // before the fds are updated, in case the syscall changes the working directory
+        let paths: Vec<String> = syscalls::path_args(syscall)
+            .iter()
+            .map(|path| self.fds.absolute(syscall.pid, path))
+            .collect();
+        self.processes.update(syscall);
+        self.commands.update(syscall, &self.processes);
+        self.fds.update(syscall);
+        self.net.update(syscall, &self.fds);
+        self.channels.update(syscall, &self.fds, &self.net);
+        self.resolutions
+            .extend(dns::decode(syscall, &self.fds, &self.net));
+        self.http.update(syscall, &self.fds, &self.net);
+        self.privileges.update(syscall);
+        self.searches.update(syscall);
+        self.libraries.update(syscall);
+        self.config.update(syscall);
+        self.postmortem.update(syscall, &self.fds);
+        self.loops.update(syscall, &self.fds);
+        self.outliers.update(syscall, &self.fds);
+        self.antidebug.update(syscall, &self.fds);
+        self.futexes.update(syscall);
+        self.event_loops.update(syscall, &self.fds);
+        self.event_fds.update(syscall, &self.fds);
+        self.changes.update(syscall, &self.fds);
+        self.heatmap.update(syscall);
+        self.memory.update(syscall, &self.fds);
+        self.io_sizes.update(syscall, &self.fds);
+        self.ipc.update(syscall, &self.fds);
+        self.namespaces.update(syscall, &self.fds);
+        self.blocking.update(syscall);
+        self.symlinks.update(syscall, &self.fds, &self.namespaces);
+        if let Some(audit) = &mut self.audit {
+            audit.update(syscall);
+        }
+
+        self.syscalls
+            .entry(syscall.name.clone())
+            .or_default()
+            .add(syscall);
+
+        for path in paths {
+            let stats = self.files.entry(path).or_default();
+            stats.calls += 1;
+            if let Some(errno) = &syscall.errno {
+                stats.errors += 1;
+                stats.errnos.insert(errno.clone());
+            }
+        }
+    }
+
+    /// Writes the report, coloring syscall names by category and errors in red if `color`.
+    pub fn write(&self, w: &mut impl Write, color: bool) -> io::Result<()> {
+        // first, since it casts doubt on the rest
+        if !self.antidebug.detections.is_empty() {
+            self.write_antidebug(w, color)?;
+            writeln!(w)?;
+        }
+        self.write_syscalls(w, color)?;
+        if self.heatmap.calls() >= heatmap::MIN_CALLS {
+            writeln!(w)?;
+            writeln!(w, "syscalls over time (darker is busier)")?;
+            self.heatmap.write(w, HEATMAP_WIDTH)?;
+        }
+        if !self.files.is_empty() {
+            writeln!(w)?;
+            self.write_files(w, color)?;
+        }
+        if !self.config.files.is_empty() {
+            writeln!(w)?;
+            self.write_config(w, color)?;
+        }
+        if !self.net.connections.is_empty() {
+            writeln!(w)?;
+            self.write_connections(w)?;
+        }
+        if !self.resolutions.is_empty() {
+            writeln!(w)?;
+            self.write_resolutions(w)?;
+        }
+        if !self.http.exchanges.is_empty() {
+            writeln!(w)?;
+            self.write_http(w)?;
+        }
+        if self
+            .libraries
+            .programs
+            .values()
+            .any(|l| !l.loaded.is_empty())
+        {
+            writeln!(w)?;
+            self.write_libraries(w)?;
+        }
+        if !self.futexes.contended().is_empty() {
+            writeln!(w)?;
+            self.write_futexes(w)?;
+        }
+        if !self.ipc.objects.is_empty() {
+            writeln!(w)?;
+            self.write_ipc(w)?;
+        }
+        if self.memory.spaces.values().any(|s| s.peak > 0) {
+            writeln!(w)?;
+            self.write_memory(w)?;
+        }
+        if self
+            .io_sizes
+            .busiest()
+            .iter()
+            .any(|(_, io, sizes)| sizes.hint(*io).is_some())
+        {
+            writeln!(w)?;
+            self.write_io_sizes(w)?;
+        }
+        if self
+            .blocking
+            .processes
+            .values()
+            .any(|t| t.blocked_micros > 0)
+        {
+            writeln!(w)?;
+            self.write_blocking(w)?;
+        }
+        if !self.event_loops.loops.is_empty() {
+            writeln!(w)?;
+            self.write_event_loops(w)?;
+        }
+        if !self.event_fds.fds.is_empty() {
+            writeln!(w)?;
+            self.write_event_fds(w)?;
+        }
+        if !self.loops.found().is_empty() {
+            writeln!(w)?;
+            self.write_loops(w)?;
+        }
+        if !self.outliers.found.is_empty() {
+            writeln!(w)?;
+            self.write_outliers(w)?;
+        }
+        if self.searches.searches.iter().any(|s| s.is_interesting()) {
+            writeln!(w)?;
+            self.write_searches(w, color)?;
+        }
+        if !self.privileges.changes.is_empty() {
+            writeln!(w)?;
+            self.write_privileges(w, color)?;
+        }
+        if !self.namespaces.changes.is_empty() || !self.namespaces.containers.is_empty() {
+            writeln!(w)?;
+            self.write_namespaces(w)?;
+        }
+        if let Some(audit) = &self.audit {
+            writeln!(w)?;
+            self.write_audit(w, audit, color)?;
+        }
+        if !self.processes.processes.is_empty() {
+            writeln!(w)?;
+            self.write_processes(w)?;
+        }
+        if !self.commands.commands.is_empty() {
+            writeln!(w)?;
+            self.write_commands(w)?;
+        }
+        if !self.changes.changes.is_empty() {
+            writeln!(w)?;
+            writeln!(w, "changes on disk")?;
+            for change in &self.changes.changes {
+                writeln!(w, "  {}", change)?;
+            }
+        }
+        if let Some(report) = &self.postmortem.report {
+            writeln!(w)?;
+            writeln!(w, "why it failed")?;
+            for line in report.lines(&self.processes.label(report.pid)) {
+                writeln!(w, "  {}", line)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the per-syscall statistics (the first section of the summary) as CSV.
+    pub fn write_syscalls_csv(&self, w: &mut impl Write) -> io::Result<()> {
+        let rows = self.syscall_rows();
+        let total_micros: u64 = rows.iter().map(|(_, s)| s.total_micros).sum();
+        csv::write_row(
+            w,
+            [
+                "syscall",
+                "calls",
+                "errors",
+                "total_us",
+                "us_per_call",
+                "percent_time",
+            ]
+            .map(String::from),
+        )?;
+        for (name, stats) in rows {
+            csv::write_row(
+                w,
+                [
+                    name.clone(),
+                    stats.calls.to_string(),
+                    stats.errors.to_string(),
+                    stats.total_micros.to_string(),
+                    (stats.total_micros / stats.calls as u64).to_string(),
+                    format!("{:.2}", percent(stats.total_micros, total_micros)),
+                ],
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Returns the stats of each syscall, those that took the most time first.
+    pub fn syscall_rows(&self) -> Vec<(&String, &SyscallStats)> {
+        rank(&self.syscalls)
+    }
+
+    fn write_syscalls(&self, w: &mut impl Write, color: bool) -> io::Result<()> {
+        // same layout as `strace -c`
+        let rows = self.syscall_rows();
+        let total_micros: u64 = rows.iter().map(|(_, s)| s.total_micros).sum();
+        let total_calls: usize = rows.iter().map(|(_, s)| s.calls).sum();
+        let total_errors: usize = rows.iter().map(|(_, s)| s.errors).sum();
+
+        let rule = "------ ----------- ----------- --------- --------- ----------------";
+        writeln!(
+            w,
+            "{:>6} {:>11} {:>11} {:>9} {:>9} syscall",
+            "% time", "seconds", "usecs/call", "calls", "errors"
+        )?;
+        writeln!(w, "{}", rule)?;
+        for (name, stats) in rows {
+            writeln!(
+                w,
+                "{:>6.2} {:>11} {:>11} {:>9} {} {}",
+                percent(stats.total_micros, total_micros),
+                format_timestamp(stats.total_micros),
+                stats.total_micros / stats.calls as u64,
+                stats.calls,
+                errors_column(stats.errors, color),
+                palette::paint(name, palette::category_color(name), color)
+            )?;
+        }
+        writeln!(w, "{}", rule)?;
+        writeln!(
+            w,
+            "{:>6.2} {:>11} {:>11} {:>9} {} total",
+            100.0,
+            format_timestamp(total_micros),
+            "",
+            total_calls,
+            errors_column(total_errors, color)
+        )
+    }
+
+    fn write_files(&self, w: &mut impl Write, color: bool) -> io::Result<()> {
+        // most-accessed first
+        let mut rows: Vec<(&String, &FileStats)> = self.files.iter().collect();
+        rows.sort_by(|a, b| b.1.calls.cmp(&a.1.calls).then(a.0.cmp(b.0)));
+        let bytes = self.fds.file_bytes();
+
+        writeln!(
+            w,
+            "{:>9} {:>9} {:>11} {:>11} file",
+            "calls", "errors", "read", "written"
+        )?;
+        writeln!(
+            w,
+            "--------- --------- ----------- ----------- ----------------"
+        )?;
+        for (path, stats) in rows {
+            let bytes = bytes.get(path.as_str()).copied().unwrap_or_default();
+            write!(
+                w,
+                "{:>9} {} {:>11} {:>11} {}",
+                stats.calls,
+                errors_column(stats.errors, color),
+                units::bytes(bytes.read),
+                units::bytes(bytes.written),
+                path
+            )?;
+            if let Some(target) = self.symlinks.resolve(path) {
+                write!(w, " → {}", target)?;
+            }
+            if !stats.errnos.is_empty() {
+                let errnos: Vec<&str> = stats.errnos.iter().map(|e| e.as_str()).collect();
+                write!(w, " ({})", errnos.join(", "))?;
+            }
+            writeln!(w)?;
+        }
+        Ok(())
+    }
+
+    fn write_connections(&self, w: &mut impl Write) -> io::Result<()> {
+        writeln!(
+            w,
+            "{:>11} {:>11} {:>11} connection",
+            "read", "written", "time"
+        )?;
+        writeln!(w, "----------- ----------- ----------- ----------------")?;
+        for connection in &self.net.connections {
+            let duration = connection
+                .duration_micros()
+                .map(units::duration)
+                .unwrap_or_default();
+            write!(
+                w,
+                "{:>11} {:>11} {:>11} ",
+                units::bytes(connection.bytes.read),
+                units::bytes(connection.bytes.written),
+                duration
+            )?;
+            if let Some(pid) = connection.pid {
+                write!(w, "{}: ", self.processes.label(pid))?;
+            }
+            write!(w, "{}", connection.describe())?;
+            match &connection.close_reason {
+                Some(reason) => writeln!(w, " ({})", reason)?,
+                None => writeln!(w, " (open)")?,
+            }
+        }
+        Ok(())
+    }
+
+    fn write_resolutions(&self, w: &mut impl Write) -> io::Result<()> {
+        writeln!(w, "name resolution")?;
+        for resolution in &self.resolutions {
+            write!(w, "  ")?;
+            if let Some(pid) = resolution.pid {
+                write!(w, "{}: ", self.processes.label(pid))?;
+            }
+            writeln!(w, "{}", resolution)?;
+        }
+        Ok(())
+    }
+
+    fn write_http(&self, w: &mut impl Write) -> io::Result<()> {
+        writeln!(w, "http")?;
+        for exchange in &self.http.exchanges {
+            write!(w, "  ")?;
+            if let Some(pid) = exchange.pid {
+                write!(w, "{}: ", self.processes.label(pid))?;
+            }
+            write!(w, "{}", exchange)?;
+            match &self.net.connections[exchange.connection].remote {
+                Some(remote) => writeln!(w, " ({})", remote)?,
+                None => writeln!(w)?,
+            }
+        }
+        Ok(())
+    }
+
+    fn write_config(&self, w: &mut impl Write, color: bool) -> io::Result<()> {
+        writeln!(w, "{:>9} config file", "status")?;
+        writeln!(w, "--------- ----------------")?;
+        for (path, status) in &self.config.files {
+            // pad before coloring, since the escape codes would count towards the width
+            let text = format!("{:>9}", status);
+            let failed = matches!(status, Status::Failed(_)).then_some(palette::Color::Red);
+            writeln!(w, "{} {}", palette::paint(&text, failed, color), path)?;
+        }
+        Ok(())
+    }
+
+    fn write_libraries(&self, w: &mut impl Write) -> io::Result<()> {
+        writeln!(w, "shared libraries")?;
+        for (pid, loading) in &self.libraries.programs {
+            if loading.loaded.is_empty() {
+                continue;
+            }
+            write!(w, "  ")?;
+            if let Some(pid) = pid {
+                write!(w, "{}: ", self.processes.label(*pid))?;
+            }
+            writeln!(
+                w,
+                "{} loaded in {}, {} misses",
+                loading.loaded.len(),
+                units::duration(loading.duration_micros()),
+                loading.misses
+            )?;
+            for path in &loading.loaded {
+                writeln!(w, "    {}", path)?;
+            }
+            let dirs: Vec<String> = loading
+                .directories()
+                .iter()
+                .map(|(dir, count)| format!("{} ({})", dir, count))
+                .collect();
+            writeln!(w, "    from {}", dirs.join(", "))?;
+        }
+        Ok(())
+    }
+
+    fn write_futexes(&self, w: &mut impl Write) -> io::Result<()> {
+        writeln!(w, "{:>9} {:>11} {:>9} futex", "waits", "time", "wakes")?;
+        writeln!(w, "--------- ----------- --------- ----------------")?;
+        let labels = |pids: &BTreeSet<u32>| {
+            let labels: Vec<String> = pids.iter().map(|p| self.processes.label(*p)).collect();
+            labels.join(", ")
+        };
+        for (address, stats) in self.futexes.contended() {
+            writeln!(
+                w,
+                "{:>9} {:>11} {:>9} {:#x} (waiters: {}; wakers: {})",
+                stats.waits,
+                units::duration(stats.wait_micros),
+                stats.wakes,
+                address,
+                labels(&stats.waiters),
+                labels(&stats.wakers)
+            )?;
+        }
+        Ok(())
+    }
+
+    fn write_ipc(&self, w: &mut impl Write) -> io::Result<()> {
+        writeln!(w, "ipc objects")?;
+        for object in &self.ipc.objects {
+            writeln!(w, "  {}", object)?;
+            if let Some(creator) = object.creator {
+                writeln!(w, "    created by {}", self.processes.label(creator))?;
+            }
+            let users: Vec<String> = object
+                .users
+                .iter()
+                .map(|p| self.processes.label(*p))
+                .collect();
+            writeln!(w, "    used by {}", users.join(", "))?;
+        }
+        Ok(())
+    }
+
+    fn write_memory(&self, w: &mut impl Write) -> io::Result<()> {
+        writeln!(w, "memory")?;
+        for (owner, space) in &self.memory.spaces {
+            if space.peak == 0 {
+                continue;
+            }
+            write!(w, "  ")?;
+            if let Some(pid) = owner {
+                write!(w, "{}: ", self.processes.label(*pid))?;
+            }
+            writeln!(
+                w,
+                "{} mapped in {} mappings (peak {})",
+                units::bytes(space.mapped),
+                space.mappings.len(),
+                units::bytes(space.peak)
+            )?;
+            for (backing, count, bytes) in space.by_backing() {
+                writeln!(
+                    w,
+                    "    {:>10} in {:>3} {}",
+                    units::bytes(bytes),
+                    count,
+                    backing
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    fn write_blocking(&self, w: &mut impl Write) -> io::Result<()> {
+        writeln!(w, "blocked vs running")?;
+        for (pid, time) in &self.blocking.processes {
+            if time.total_micros() == 0 {
+                continue;
+            }
+            writeln!(w, "  {}", self.processes.label(*pid))?;
+            writeln!(w, "    {}", time)?;
+            if let Some(syscalls) = time.mostly_in() {
+                writeln!(w, "    mostly in {}", syscalls)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn write_event_loops(&self, w: &mut impl Write) -> io::Result<()> {
+        writeln!(w, "event loops")?;
+        for (owner, event_loop) in &self.event_loops.loops {
+            match owner {
+                Some(pid) => writeln!(w, "  {}", self.processes.label(*pid))?,
+                None => writeln!(w, "  (unknown process)")?,
+            }
+            for interest in event_loop.interests.values() {
+                writeln!(w, "    {}", interest)?;
+            }
+            for (pid, waits) in &event_loop.waits {
+                write!(w, "    ")?;
+                if let Some(pid) = pid {
+                    write!(w, "{}: ", self.processes.label(*pid))?;
+                }
+                writeln!(w, "{}", waits)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn write_event_fds(&self, w: &mut impl Write) -> io::Result<()> {
+        writeln!(w, "timers, eventfds, and signalfds")?;
+        for event_fd in &self.event_fds.fds {
+            write!(w, "  ")?;
+            if let Some(pid) = event_fd.pid {
+                write!(w, "{}: ", self.processes.label(pid))?;
+            }
+            writeln!(w, "{}", event_fd)?;
+        }
+        Ok(())
+    }
+
+    fn write_loops(&self, w: &mut impl Write) -> io::Result<()> {
+        writeln!(w, "busy loops")?;
+        for spin in self.loops.found() {
+            write!(w, "  ")?;
+            if let Some(pid) = spin.pid {
+                write!(w, "{}: ", self.processes.label(pid))?;
+            }
+            writeln!(w, "{}", spin)?;
+        }
+        Ok(())
+    }
+
+    fn write_io_sizes(&self, w: &mut impl Write) -> io::Result<()> {
+        writeln!(w, "unbuffered I/O (bytes per call)")?;
+        for (target, io, sizes) in self.io_sizes.busiest() {
+            let hint = match sizes.hint(io) {
+                Some(hint) => hint,
+                None => continue,
+            };
+            writeln!(w, "  {} ({}): {}", target, io, sizes)?;
+            writeln!(w, "    hint: {}", hint)?;
+        }
+        Ok(())
+    }
+
+    fn write_antidebug(&self, w: &mut impl Write, color: bool) -> io::Result<()> {
+        let title = "warning: the program checked whether it is being traced, so it may behave \
+                     differently than it does untraced";
+        writeln!(
+            w,
+            "{}",
+            palette::paint(title, Some(palette::Color::Red), color)
+        )?;
+        for detection in &self.antidebug.detections {
+            write!(w, "  ")?;
+            if let Some(pid) = detection.pid {
+                write!(w, "{}: ", self.processes.label(pid))?;
+            }
+            writeln!(w, "{}", detection)?;
+        }
+        Ok(())
+    }
+
+    fn write_outliers(&self, w: &mut impl Write) -> io::Result<()> {
+        writeln!(w, "unusually slow syscalls")?;
+        for outlier in &self.outliers.found {
+            write!(w, "  ")?;
+            if let Some(pid) = outlier.pid {
+                write!(w, "{}: ", self.processes.label(pid))?;
+            }
+            writeln!(w, "{}", outlier)?;
+        }
+        Ok(())
+    }
+
+    fn write_searches(&self, w: &mut impl Write, color: bool) -> io::Result<()> {
+        writeln!(w, "search paths")?;
+        for search in self.searches.searches.iter().filter(|s| s.is_interesting()) {
+            write!(w, "  ")?;
+            if let Some(pid) = search.pid {
+                write!(w, "{}: ", self.processes.label(pid))?;
+            }
+            let text = search.to_string();
+            let missing = search.found.is_none().then_some(palette::Color::Red);
+            writeln!(w, "{}", palette::paint(&text, missing, color))?;
+            for dir in &search.misses {
+                writeln!(w, "    {}", dir)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn write_privileges(&self, w: &mut impl Write, color: bool) -> io::Result<()> {
+        writeln!(w, "privileges")?;
+        for (pid, changes) in &self.privileges.changes {
+            writeln!(w, "  {}", self.processes.label(*pid))?;
+            for change in changes {
+                let text = change.to_string();
+                let failed = change.failed_drop().then_some(palette::Color::Red);
+                writeln!(w, "    {}", palette::paint(&text, failed, color))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn write_namespaces(&self, w: &mut impl Write) -> io::Result<()> {
+        writeln!(w, "namespaces")?;
+        for container in self.namespaces.containers.values() {
+            writeln!(w, "  {}: {}", container.pid, container)?;
+        }
+        for (pid, changes) in &self.namespaces.changes {
+            writeln!(w, "  {}", self.processes.label(*pid))?;
+            for change in changes {
+                writeln!(w, "    {}", change)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn write_audit(&self, w: &mut impl Write, audit: &Audit, color: bool) -> io::Result<()> {
+        writeln!(w, "audit")?;
+        if audit.findings.is_empty() {
+            return writeln!(w, "  nothing found");
+        }
+        for (pid, finding, count) in &audit.findings {
+            write!(w, "  ")?;
+            if let Some(pid) = pid {
+                write!(w, "{}: ", self.processes.label(*pid))?;
+            }
+            let finding = finding.to_string();
+            write!(
+                w,
+                "{}",
+                palette::paint(&finding, Some(palette::Color::Red), color)
+            )?;
+            if *count > 1 {
+                write!(w, " ({} times)", count)?;
+            }
+            writeln!(w)?;
+        }
+        Ok(())
+    }
+
+    fn write_commands(&self, w: &mut impl Write) -> io::Result<()> {
+        writeln!(w, "commands")?;
+        for (depth, index) in self.commands.tree() {
+            let command = &self.commands.commands[index];
+            writeln!(w, "  {}{}", "  ".repeat(depth), command)?;
+        }
+        Ok(())
+    }
+
+    fn write_processes(&self, w: &mut impl Write) -> io::Result<()> {
+        writeln!(w, "processes")?;
+        for root in self.processes.roots() {
+            self.write_process(w, root, 1)?;
+        }
+        Ok(())
+    }
+
+    fn write_process(&self, w: &mut impl Write, pid: u32, depth: usize) -> io::Result<()> {
+        let process = &self.processes.processes[&pid];
+        write!(
+            w,
+            "{}{}: {} syscalls",
+            "  ".repeat(depth),
+            self.processes.label(pid),
+            process.syscall_count
+        )?;
+        if let Some(exit) = &process.exit {
+            write!(w, ", {}", exit)?;
+        }
+        writeln!(w)?;
+        for peer in self.channels.peers(pid) {
+            writeln!(
+                w,
+                "{}  {} {} {}",
+                "  ".repeat(depth),
+                peer.arrow(),
+                self.processes.label(peer.pid),
+                peer
+            )?;
+        }
+        // threads are grouped under their process rather than shown as children
+        for thread in self.processes.threads(pid) {
+            let thread = &self.processes.processes[&thread];
+            write!(
+                w,
+                "{}  thread {}: {} syscalls",
+                "  ".repeat(depth),
+                thread.pid,
+                thread.syscall_count
+            )?;
+            if let Some(exit) = &thread.exit {
+                write!(w, ", {}", exit)?;
+            }
+            writeln!(w)?;
+        }
+        for child in self.processes.children(pid) {
+            self.write_process(w, child, depth + 1)?;
+        }
+        Ok(())
+    }
+}
+
+fn percent(part: u64, total: u64) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        part as f64 * 100.0 / total as f64
+    }
+}
+
+fn errors_column(errors: usize, color: bool) -> String {
+    if errors == 0 {
+        " ".repeat(9)
+    } else {
+        // pad before coloring, since the escape codes would count towards the width
+        palette::paint(&format!("{:>9}", errors), Some(palette::Color::Red), color)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::strace::LineParser;
+
+    use super::Summary;
+
+    #[test]
+    fn test_summary() {
+        let mut summary = Summary::default();
+        let mut parser = LineParser::default();
+        for line in [
+            "10 1.000001 openat(AT_FDCWD, \"/etc/hosts\", O_RDONLY) = 3 <0.000030>",
+            "10 1.000002 openat(AT_FDCWD, \"/etc/nope\", O_RDONLY) = -1 ENOENT (No such file or directory) <0.000010>",
+            "10 1.000003 read(3, \"abc\", 4096) = 3 <0.000040>",
+            "10 1.000004 clone(child_stack=NULL, flags=SIGCHLD) = 11 <0.000020>",
+            "10 1.000004 socket(AF_INET, SOCK_STREAM, IPPROTO_TCP) = 4 <0.000000>",
+            "10 1.000004 sendto(4, \"hello\", 5, 0, {sa_family=AF_INET, sin_port=htons(80), sin_addr=inet_addr(\"1.2.3.4\")}, 16) = 5 <0.000000>",
+            "11 1.000005 access(\"/etc/hosts\", R_OK) = 0 <0.000000>",
+            "11 1.000006 +++ exited with 1 +++",
+        ] {
+            summary.update(parser.parse_line(line).unwrap());
+        }
+
+        let mut out = Vec::new();
+        summary.write(&mut out, false).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "\
+% time     seconds  usecs/call     calls    errors syscall
+------ ----------- ----------- --------- --------- ----------------
+ 40.00    0.000040          20         2         1 openat
+ 40.00    0.000040          40         1           read
+ 20.00    0.000020          20         1           clone
+  0.00    0.000000           0         1           access
+  0.00    0.000000           0         1           sendto
+  0.00    0.000000           0         1           socket
+------ ----------- ----------- --------- --------- ----------------
+100.00    0.000100                     7         1 total
+
+    calls    errors        read     written file
+--------- --------- ----------- ----------- ----------------
+        2                   3 B         0 B /etc/hosts
+        1         1         0 B         0 B /etc/nope (ENOENT)
+
+   status config file
+--------- ----------------
+     read /etc/hosts
+   ENOENT /etc/nope
+
+       read     written        time connection
+----------- ----------- ----------- ----------------
+        0 B         5 B             10: tcp socket ? -> 1.2.3.4:80 (open)
+
+blocked vs running
+  10
+    ████████████████████ 100.0% blocked (100 µs in syscalls, 0 µs between them)
+    mostly in openat 40 µs, read 40 µs, clone 20 µs
+
+processes
+  10: 6 syscalls
+    11: 1 syscalls, exited with 1
+"
+        );
+
+        let mut out = Vec::new();
+        summary.write_syscalls_csv(&mut out).unwrap();
+        let csv = String::from_utf8(out).unwrap();
+        let rows: Vec<&str> = csv.split_terminator("\r\n").collect();
+        assert_eq!(
+            rows[..3],
+            [
+                "syscall,calls,errors,total_us,us_per_call,percent_time",
+                "openat,2,1,40,20,40.00",
+                "read,1,0,40,40,40.00",
+            ]
+        );
+    }
+}
@@ -0,0 +1,134 @@
+// Works out where paths really lead when they go through a symlink, e.g. that /etc/resolv.conf is
+// /run/systemd/resolve/stub-resolv.conf. Symlinks are learned from readlink calls in the trace and,
+// when tracing live with --resolve-links, by looking the paths up on this machine.
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+
+use crate::fdtable::{self, FdTable};
+use crate::namespaces::Namespaces;
+use crate::strace::{Syscall, SyscallArgValue};
+use crate::syscalls;
+
+// the most symlinks followed in a row, as for the kernel
+const MAX_HOPS: usize = 40;
+
+#[derive(Default)]
+pub struct Symlinks {
+    // the absolute path of each known symlink, and what it points to (also absolute)
+    links: HashMap<String, String>,
+    // look paths up on this machine as well, which only makes sense while tracing live
+    pub host: bool,
+    // the paths that were looked up on this machine
+    looked_up: HashSet<String>,
+}
+
+impl Symlinks {
+    /// Must be called after `fds` has been updated with `syscall`.
+    pub fn update(&mut self, syscall: &Syscall, fds: &FdTable, namespaces: &Namespaces) {
+        if syscall.errno.is_some() || syscall.error_details.is_some() {
+            return;
+        }
+
+        let offset = match syscall.name.as_str() {
+            "readlink" => Some(0),
+            "readlinkat" => Some(1),
+            _ => None,
+        };
+        // e.g., readlink("/etc/resolv.conf", "../run/systemd/resolve/stub-resolv.conf", 4095)
+        if let (Some(offset), true) = (offset, syscall.return_value > 0) {
+            let args = (
+                syscall.arg(offset).map(|a| &a.value),
+                syscall.arg(offset + 1).map(|a| &a.value),
+            );
+            if let (
+                Some(SyscallArgValue::Quoted { text: path, .. }),
+                Some(SyscallArgValue::Quoted {
+                    text: target,
+                    truncated: false,
+                }),
+            ) = args
+            {
+                let path = fds.absolute(syscall.pid, path);
+                if path.starts_with('/') {
+                    // a relative target is relative to the symlink's directory
+                    let dir = match path.rsplit_once('/') {
+                        Some((dir, _)) if !target.starts_with('/') => dir,
+                        _ => "/",
+                    };
+                    let target = fdtable::join(dir, target);
+                    self.links.insert(path, target);
+                }
+            }
+        }
+
+        // a process in a container sees a different filesystem, which can't be reliably resolved
+        // from here because /proc/<pid>/root is itself a symlink
+        if self.host && !namespaces.in_container(syscall.pid) {
+            for path in syscalls::path_args(syscall) {
+                let path = fds.absolute(syscall.pid, &path);
+                if !path.starts_with('/') || !self.looked_up.insert(path.clone()) {
+                    continue;
+                }
+                // canonicalize resolves symlinks anywhere in the path, not just at the end
+                if let Ok(real) = fs::canonicalize(&path) {
+                    let real = real.to_string_lossy().into_owned();
+                    if real != path {
+                        self.links.insert(path, real);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Returns where `path` (an absolute path) really leads, if it is a known symlink.
+    pub fn resolve(&self, path: &str) -> Option<String> {
+        let mut current = self.links.get(path)?;
+        for _ in 0..MAX_HOPS {
+            match self.links.get(current) {
+                Some(next) if next != current => current = next,
+                _ => break,
+            }
+        }
+        Some(current.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fdtable::FdTable;
+    use crate::namespaces::Namespaces;
+    use crate::strace::{LineParser, Message};
+
+    use super::Symlinks;
+
+    #[test]
+    fn test_symlinks() {
+        let mut symlinks = Symlinks::default();
+        let mut fds = FdTable::default();
+        let mut parser = LineParser::default();
+        for line in [
+            "readlink(\"/etc/resolv.conf\", \"../run/systemd/resolve/stub-resolv.conf\", 4095) = 39",
+            "readlink(\"/run/systemd/resolve/stub-resolv.conf\", \"/run/resolved/stub.conf\", 4095) = 23",
+            "readlink(\"/etc/hosts\", 0x7ffc1000, 4095) = -1 EINVAL (Invalid argument)",
+            "readlinkat(AT_FDCWD, \"/usr/bin/cc\", \"gcc\", 4095) = 3",
+        ] {
+            let syscall = parser
+                .parse_line(line)
+                .and_then(Message::into_syscall)
+                .unwrap();
+            fds.update(&syscall);
+            symlinks.update(&syscall, &fds, &Namespaces::default());
+        }
+
+        assert_eq!(
+            symlinks.resolve("/etc/resolv.conf").as_deref(),
+            Some("/run/resolved/stub.conf")
+        );
+        assert_eq!(
+            symlinks.resolve("/usr/bin/cc").as_deref(),
+            Some("/usr/bin/gcc")
+        );
+        assert_eq!(symlinks.resolve("/etc/hosts"), None);
+    }
+}
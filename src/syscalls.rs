@@ -0,0 +1,975 @@
+// static knowledge about individual syscalls
+
+use std::fmt;
+
+#[cfg(feature = "native")]
+use nix::errno::Errno;
+#[cfg(feature = "native")]
+use nix::sys::signal::Signal;
+
+use crate::strace::{FlagSetValue, Syscall, SyscallArgValue};
+
+/// Returns the names of the positional arguments of `syscall`, following the man pages, or `None`
+/// if the syscall is not known.
+pub fn arg_names(syscall: &str) -> Option<&'static [&'static str]> {
+    let names: &'static [&'static str] = match syscall {
+        "read" | "write" => &["fd", "buf", "count"],
+        "pread64" | "pwrite64" => &["fd", "buf", "count", "offset"],
+        "readv" | "writev" => &["fd", "iov", "iovcnt"],
+        "preadv" | "pwritev" => &["fd", "iov", "iovcnt", "offset"],
+        "preadv2" | "pwritev2" => &["fd", "iov", "iovcnt", "offset", "flags"],
+        "sendfile" => &["out_fd", "in_fd", "offset", "count"],
+        "open" => &["pathname", "flags", "mode"],
+        "openat" => &["dirfd", "pathname", "flags", "mode"],
+        "creat" => &["pathname", "mode"],
+        "close" => &["fd"],
+        "stat" | "lstat" => &["pathname", "statbuf"],
+        "fstat" => &["fd", "statbuf"],
+        "newfstatat" => &["dirfd", "pathname", "statbuf", "flags"],
+        "statx" => &["dirfd", "pathname", "flags", "mask", "statxbuf"],
+        "access" => &["pathname", "mode"],
+        "faccessat" | "faccessat2" => &["dirfd", "pathname", "mode", "flags"],
+        "lseek" => &["fd", "offset", "whence"],
+        "mmap" => &["addr", "length", "prot", "flags", "fd", "offset"],
+        "munmap" => &["addr", "length"],
+        "mprotect" => &["addr", "len", "prot"],
+        "mremap" => &[
+            "old_address",
+            "old_size",
+            "new_size",
+            "flags",
+            "new_address",
+        ],
+        "madvise" => &["addr", "length", "advice"],
+        "brk" => &["addr"],
+        "ioctl" => &["fd", "request", "argp"],
+        "fcntl" => &["fd", "cmd", "arg"],
+        "dup" => &["oldfd"],
+        "dup2" => &["oldfd", "newfd"],
+        "dup3" => &["oldfd", "newfd", "flags"],
+        "pipe" => &["pipefd"],
+        "pipe2" => &["pipefd", "flags"],
+        "socket" => &["domain", "type", "protocol"],
+        "socketpair" => &["domain", "type", "protocol", "sv"],
+        "connect" | "bind" => &["sockfd", "addr", "addrlen"],
+        "accept" => &["sockfd", "addr", "addrlen"],
+        "accept4" => &["sockfd", "addr", "addrlen", "flags"],
+        "listen" => &["sockfd", "backlog"],
+        "getsockname" | "getpeername" => &["sockfd", "addr", "addrlen"],
+        "shutdown" => &["sockfd", "how"],
+        "sendto" => &["sockfd", "buf", "len", "flags", "dest_addr", "addrlen"],
+        "recvfrom" => &["sockfd", "buf", "len", "flags", "src_addr", "addrlen"],
+        "sendmsg" | "recvmsg" => &["sockfd", "msg", "flags"],
+        "getdents64" => &["fd", "dirp", "count"],
+        "execve" => &["pathname", "argv", "envp"],
+        "execveat" => &["dirfd", "pathname", "argv", "envp", "flags"],
+        "exit" | "exit_group" => &["status"],
+        "arch_prctl" => &["code", "addr"],
+        "set_tid_address" => &["tidptr"],
+        "set_robust_list" => &["head", "len"],
+        "prlimit64" => &["pid", "resource", "new_limit", "old_limit"],
+        "rseq" => &["rseq", "rseq_len", "flags", "sig"],
+        "chdir" => &["path"],
+        "fchdir" => &["fd"],
+        "unlink" | "rmdir" => &["pathname"],
+        "mkdir" => &["pathname", "mode"],
+        "mkdirat" => &["dirfd", "pathname", "mode"],
+        "unlinkat" => &["dirfd", "pathname", "flags"],
+        "rename" => &["oldpath", "newpath"],
+        "renameat" | "renameat2" => &["olddirfd", "oldpath", "newdirfd", "newpath", "flags"],
+        "symlink" => &["target", "linkpath"],
+        "symlinkat" => &["target", "newdirfd", "linkpath"],
+        "link" => &["oldpath", "newpath"],
+        "linkat" => &["olddirfd", "oldpath", "newdirfd", "newpath", "flags"],
+        "chmod" => &["pathname", "mode"],
+        "fchmod" => &["fd", "mode"],
+        "fchmodat" => &["dirfd", "pathname", "mode", "flags"],
+        "chown" | "lchown" => &["pathname", "owner", "group"],
+        "fchown" => &["fd", "owner", "group"],
+        "fchownat" => &["dirfd", "pathname", "owner", "group", "flags"],
+        "truncate" => &["path", "length"],
+        "ftruncate" => &["fd", "length"],
+        "readlink" => &["pathname", "buf", "bufsiz"],
+        "readlinkat" => &["dirfd", "pathname", "buf", "bufsiz"],
+        "poll" => &["fds", "nfds", "timeout"],
+        "futex" => &["uaddr", "futex_op", "val", "timeout", "uaddr2", "val3"],
+        "wait4" => &["pid", "wstatus", "options", "rusage"],
+        "kill" => &["pid", "sig"],
+        "getrandom" => &["buf", "buflen", "flags"],
+        _ => return None,
+    };
+    Some(names)
+}
+
+/// Returns the position of the argument called `name` for `syscall`, if known.
+pub fn arg_index(syscall: &str, name: &str) -> Option<usize> {
+    arg_names(syscall)?.iter().position(|n| *n == name)
+}
+
+/// Returns the positions of the arguments of `syscall` that are file descriptors.
+pub fn fd_arg_indices(syscall: &str) -> Vec<usize> {
+    let names = match arg_names(syscall) {
+        Some(names) => names,
+        None => return Vec::new(),
+    };
+
+    names
+        .iter()
+        .enumerate()
+        .filter(|(_, name)| {
+            matches!(
+                **name,
+                "fd" | "oldfd"
+                    | "newfd"
+                    | "sockfd"
+                    | "dirfd"
+                    | "olddirfd"
+                    | "newdirfd"
+                    | "out_fd"
+                    | "in_fd"
+            )
+        })
+        .map(|(i, _)| i)
+        .collect()
+}
+
+/// The categories of syscalls that strace accepts in `-e trace=%<category>`.
+pub const CATEGORIES: &[&str] = &[
+    "file", "desc", "network", "process", "signal", "ipc", "memory",
+];
+
+/// Returns true if `syscall` is in `category` (one of `CATEGORIES`), following strace's own
+/// classification as closely as this module's knowledge allows.
+pub fn in_category(syscall: &str, category: &str) -> bool {
+    match category {
+        "file" => matches!(
+            syscall,
+            "open"
+                | "openat"
+                | "openat2"
+                | "creat"
+                | "stat"
+                | "lstat"
+                | "newfstatat"
+                | "statx"
+                | "statfs"
+                | "access"
+                | "faccessat"
+                | "faccessat2"
+                | "execve"
+                | "execveat"
+                | "chdir"
+                | "chroot"
+                | "mkdir"
+                | "mkdirat"
+                | "rmdir"
+                | "unlink"
+                | "unlinkat"
+                | "rename"
+                | "renameat"
+                | "renameat2"
+                | "link"
+                | "linkat"
+                | "symlink"
+                | "symlinkat"
+                | "readlink"
+                | "readlinkat"
+                | "chmod"
+                | "fchmodat"
+                | "chown"
+                | "lchown"
+                | "fchownat"
+                | "truncate"
+                | "utime"
+                | "utimes"
+                | "utimensat"
+                | "mknod"
+                | "mknodat"
+                | "getxattr"
+                | "lgetxattr"
+                | "setxattr"
+                | "lsetxattr"
+                | "listxattr"
+                | "llistxattr"
+                | "removexattr"
+                | "lremovexattr"
+        ),
+        // syscalls that take or return a file descriptor
+        "desc" => !fd_arg_indices(syscall).is_empty() || returns_fd(syscall),
+        "network" => matches!(
+            syscall,
+            "socket"
+                | "socketpair"
+                | "connect"
+                | "bind"
+                | "listen"
+                | "accept"
+                | "accept4"
+                | "sendto"
+                | "recvfrom"
+                | "sendmsg"
+                | "recvmsg"
+                | "sendmmsg"
+                | "recvmmsg"
+                | "shutdown"
+                | "getsockname"
+                | "getpeername"
+                | "setsockopt"
+                | "getsockopt"
+        ),
+        "process" => matches!(
+            syscall,
+            "fork"
+                | "vfork"
+                | "clone"
+                | "clone3"
+                | "execve"
+                | "execveat"
+                | "exit"
+                | "exit_group"
+                | "wait4"
+                | "waitid"
+                | "kill"
+                | "tkill"
+                | "tgkill"
+                | "pidfd_send_signal"
+        ),
+        "signal" => matches!(
+            syscall,
+            "rt_sigaction"
+                | "rt_sigprocmask"
+                | "rt_sigreturn"
+                | "rt_sigsuspend"
+                | "rt_sigpending"
+                | "rt_sigtimedwait"
+                | "rt_sigqueueinfo"
+                | "rt_tgsigqueueinfo"
+                | "sigaltstack"
+                | "signalfd"
+                | "signalfd4"
+                | "kill"
+                | "tkill"
+                | "tgkill"
+                | "pause"
+        ),
+        "ipc" => matches!(
+            syscall,
+            "msgget"
+                | "msgsnd"
+                | "msgrcv"
+                | "msgctl"
+                | "semget"
+                | "semop"
+                | "semctl"
+                | "semtimedop"
+                | "shmget"
+                | "shmat"
+                | "shmdt"
+                | "shmctl"
+        ),
+        "memory" => matches!(
+            syscall,
+            "brk"
+                | "mmap"
+                | "munmap"
+                | "mremap"
+                | "mprotect"
+                | "madvise"
+                | "mlock"
+                | "mlock2"
+                | "munlock"
+                | "mlockall"
+                | "munlockall"
+                | "msync"
+                | "mincore"
+        ),
+        _ => false,
+    }
+}
+
+/// Returns true if a successful call to `syscall` returns a new file descriptor.
+///
+/// `fcntl` only does so for `F_DUPFD` and `F_DUPFD_CLOEXEC`, so callers need to check the command
+/// themselves.
+pub fn returns_fd(syscall: &str) -> bool {
+    matches!(
+        syscall,
+        "open"
+            | "openat"
+            | "openat2"
+            | "creat"
+            | "socket"
+            | "accept"
+            | "accept4"
+            | "dup"
+            | "dup2"
+            | "dup3"
+            | "epoll_create"
+            | "epoll_create1"
+            | "eventfd"
+            | "eventfd2"
+            | "timerfd_create"
+            | "signalfd"
+            | "signalfd4"
+            | "inotify_init"
+            | "inotify_init1"
+            | "memfd_create"
+            | "pidfd_open"
+    )
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Io {
+    Read,
+    Write,
+}
+
+impl fmt::Display for Io {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Io::Read => write!(f, "read"),
+            Io::Write => write!(f, "write"),
+        }
+    }
+}
+
+/// Returns whether a successful call to `syscall` reads or writes as many bytes as it returns,
+/// through the file descriptor that is its first argument. (`sendfile` does both, so it is not
+/// included.)
+pub fn io_direction(syscall: &str) -> Option<Io> {
+    match syscall {
+        "read" | "pread64" | "readv" | "preadv" | "preadv2" | "recvfrom" | "recvmsg" => {
+            Some(Io::Read)
+        }
+        "write" | "pwrite64" | "writev" | "pwritev" | "pwritev2" | "sendto" | "sendmsg" => {
+            Some(Io::Write)
+        }
+        _ => None,
+    }
+}
+
+/// Returns the file descriptors passed as arguments to `syscall`.
+pub fn fd_args(syscall: &Syscall) -> Vec<i64> {
+    fd_arg_indices(&syscall.name)
+        .into_iter()
+        .filter_map(|i| match syscall.arg(i).map(|a| &a.value) {
+            Some(SyscallArgValue::Number(fd)) => Some(*fd),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Returns the file paths passed as arguments to `syscall`, as strace printed them.
+/// Returns whether the argument named `name` (as in `arg_names`) is a path.
+pub fn is_path_arg(name: &str) -> bool {
+    matches!(name, "pathname" | "path" | "oldpath" | "newpath")
+}
+
+/// Returns whether the argument named `name` (as in `arg_names`) is a number of bytes.
+pub fn is_size_arg(name: &str) -> bool {
+    matches!(name, "count" | "len" | "length" | "bufsiz")
+}
+
+/// Returns whether the argument named `name` (as in `arg_names`, or as strace names it, e.g.
+/// `child_tidptr`) is an address.
+pub fn is_pointer_arg(name: &str) -> bool {
+    matches!(
+        name,
+        "buf"
+            | "statbuf"
+            | "statxbuf"
+            | "argp"
+            | "iov"
+            | "dirp"
+            | "argv"
+            | "envp"
+            | "msg"
+            | "head"
+            | "rseq"
+            | "new_limit"
+            | "old_limit"
+            | "wstatus"
+            | "rusage"
+    ) || ["addr", "addr2", "address", "ptr", "stack"]
+        .iter()
+        .any(|suffix| name.ends_with(suffix))
+}
+
+/// Returns whether a successful call to `syscall` returns an address.
+pub fn returns_pointer(syscall: &str) -> bool {
+    matches!(syscall, "mmap" | "mmap2" | "mremap" | "brk" | "shmat")
+}
+
+/// Returns whether `syscall` returns the number of bytes that it moved, if it succeeds.
+pub fn returns_bytes(syscall: &str) -> bool {
+    io_direction(syscall).is_some()
+        || matches!(
+            syscall,
+            "sendfile" | "getdents64" | "readlink" | "readlinkat"
+        )
+}
+
+pub fn path_args(syscall: &Syscall) -> Vec<String> {
+    let names = match arg_names(&syscall.name) {
+        Some(names) => names,
+        None => return Vec::new(),
+    };
+
+    names
+        .iter()
+        .enumerate()
+        .filter(|(_, name)| is_path_arg(name))
+        .filter_map(|(i, _)| match syscall.arg(i).map(|a| &a.value) {
+            Some(SyscallArgValue::Quoted { text, .. }) => Some(text.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Returns the file descriptors created by `syscall`, e.g., the return value of `open` or the
+/// pair of descriptors filled in by `pipe`.
+pub fn created_fds(syscall: &Syscall) -> Vec<i64> {
+    if syscall.errno.is_some() || syscall.error_details.is_some() {
+        return Vec::new();
+    }
+
+    match syscall.name.as_str() {
+        "pipe" | "pipe2" | "socketpair" => {
+            let index = if syscall.name == "socketpair" { 3 } else { 0 };
+            match syscall.arg(index).map(|a| &a.value) {
+                Some(SyscallArgValue::Array(fds)) => fds
+                    .iter()
+                    .filter_map(|fd| match fd.value {
+                        SyscallArgValue::Number(fd) => Some(fd),
+                        _ => None,
+                    })
+                    .collect(),
+                _ => Vec::new(),
+            }
+        }
+        "fcntl" if is_fcntl_dupfd(syscall) => vec![syscall.return_value],
+        name if returns_fd(name) => vec![syscall.return_value],
+        _ => Vec::new(),
+    }
+}
+
+fn is_fcntl_dupfd(syscall: &Syscall) -> bool {
+    matches!(
+        syscall.arg(1).map(|a| &a.value),
+        Some(SyscallArgValue::Symbol(cmd)) if cmd == "F_DUPFD" || cmd == "F_DUPFD_CLOEXEC"
+    )
+}
+
+/// Returns true if `syscall` is a `clone` or `clone3` with `flag` set, e.g. `CLONE_FILES`.
+pub fn has_clone_flag(syscall: &Syscall, flag: &str) -> bool {
+    // clone's flags are a named argument, and clone3's are a field of its first argument
+    let named = syscall
+        .args
+        .iter()
+        .find(|a| a.name == "flags")
+        .map(|a| &a.value);
+    let field = match syscall.arg(0).map(|a| &a.value) {
+        Some(SyscallArgValue::Struct(fields)) => fields.get("flags").map(|a| &a.value),
+        _ => None,
+    };
+    named
+        .or(field)
+        .is_some_and(|flags| value_has_flag(flags, flag))
+}
+
+/// Returns true if `value` is `flag` or a set of flags that includes it.
+pub fn value_has_flag(value: &SyscallArgValue, flag: &str) -> bool {
+    match value {
+        SyscallArgValue::Symbol(s) => s == flag,
+        SyscallArgValue::FlagSet(flags) => flags
+            .iter()
+            .any(|f| matches!(f, FlagSetValue::Symbol(s) if s == flag)),
+        _ => false,
+    }
+}
+
+// Decoding the raw values that ptrace and bpftrace give, with the native feature only, since it
+// relies on libc and nix.
+
+// the names of the syscalls on x86-64, by number, from libc
+#[cfg(all(feature = "native", target_os = "linux", target_arch = "x86_64"))]
+macro_rules! syscalls {
+    ($($number:ident),* $(,)?) => {
+        &[$((libc::$number, stringify!($number))),*]
+    };
+}
+#[cfg(all(feature = "native", target_os = "linux", target_arch = "x86_64"))]
+const SYSCALLS: &[(libc::c_long, &str)] = syscalls![
+    SYS_read,
+    SYS_write,
+    SYS_open,
+    SYS_close,
+    SYS_stat,
+    SYS_fstat,
+    SYS_lstat,
+    SYS_poll,
+    SYS_lseek,
+    SYS_mmap,
+    SYS_mprotect,
+    SYS_munmap,
+    SYS_brk,
+    SYS_rt_sigaction,
+    SYS_rt_sigprocmask,
+    SYS_rt_sigreturn,
+    SYS_ioctl,
+    SYS_pread64,
+    SYS_pwrite64,
+    SYS_readv,
+    SYS_writev,
+    SYS_access,
+    SYS_pipe,
+    SYS_select,
+    SYS_sched_yield,
+    SYS_mremap,
+    SYS_msync,
+    SYS_mincore,
+    SYS_madvise,
+    SYS_shmget,
+    SYS_shmat,
+    SYS_shmctl,
+    SYS_dup,
+    SYS_dup2,
+    SYS_pause,
+    SYS_nanosleep,
+    SYS_getitimer,
+    SYS_alarm,
+    SYS_setitimer,
+    SYS_getpid,
+    SYS_sendfile,
+    SYS_socket,
+    SYS_connect,
+    SYS_accept,
+    SYS_sendto,
+    SYS_recvfrom,
+    SYS_sendmsg,
+    SYS_recvmsg,
+    SYS_shutdown,
+    SYS_bind,
+    SYS_listen,
+    SYS_getsockname,
+    SYS_getpeername,
+    SYS_socketpair,
+    SYS_setsockopt,
+    SYS_getsockopt,
+    SYS_clone,
+    SYS_fork,
+    SYS_vfork,
+    SYS_execve,
+    SYS_exit,
+    SYS_wait4,
+    SYS_kill,
+    SYS_uname,
+    SYS_semget,
+    SYS_semop,
+    SYS_semctl,
+    SYS_shmdt,
+    SYS_msgget,
+    SYS_msgsnd,
+    SYS_msgrcv,
+    SYS_msgctl,
+    SYS_fcntl,
+    SYS_flock,
+    SYS_fsync,
+    SYS_fdatasync,
+    SYS_truncate,
+    SYS_ftruncate,
+    SYS_getdents,
+    SYS_getcwd,
+    SYS_chdir,
+    SYS_fchdir,
+    SYS_rename,
+    SYS_mkdir,
+    SYS_rmdir,
+    SYS_creat,
+    SYS_link,
+    SYS_unlink,
+    SYS_symlink,
+    SYS_readlink,
+    SYS_chmod,
+    SYS_fchmod,
+    SYS_chown,
+    SYS_fchown,
+    SYS_lchown,
+    SYS_umask,
+    SYS_gettimeofday,
+    SYS_getrlimit,
+    SYS_getrusage,
+    SYS_sysinfo,
+    SYS_times,
+    SYS_ptrace,
+    SYS_getuid,
+    SYS_syslog,
+    SYS_getgid,
+    SYS_setuid,
+    SYS_setgid,
+    SYS_geteuid,
+    SYS_getegid,
+    SYS_setpgid,
+    SYS_getppid,
+    SYS_getpgrp,
+    SYS_setsid,
+    SYS_setreuid,
+    SYS_setregid,
+    SYS_getgroups,
+    SYS_setgroups,
+    SYS_setresuid,
+    SYS_getresuid,
+    SYS_setresgid,
+    SYS_getresgid,
+    SYS_getpgid,
+    SYS_setfsuid,
+    SYS_setfsgid,
+    SYS_getsid,
+    SYS_capget,
+    SYS_capset,
+    SYS_rt_sigpending,
+    SYS_rt_sigtimedwait,
+    SYS_rt_sigqueueinfo,
+    SYS_rt_sigsuspend,
+    SYS_sigaltstack,
+    SYS_utime,
+    SYS_mknod,
+    SYS_personality,
+    SYS_statfs,
+    SYS_fstatfs,
+    SYS_getpriority,
+    SYS_setpriority,
+    SYS_sched_setparam,
+    SYS_sched_getparam,
+    SYS_sched_setscheduler,
+    SYS_sched_getscheduler,
+    SYS_mlock,
+    SYS_munlock,
+    SYS_mlockall,
+    SYS_munlockall,
+    SYS_prctl,
+    SYS_arch_prctl,
+    SYS_setrlimit,
+    SYS_chroot,
+    SYS_sync,
+    SYS_mount,
+    SYS_umount2,
+    SYS_sethostname,
+    SYS_gettid,
+    SYS_readahead,
+    SYS_setxattr,
+    SYS_lsetxattr,
+    SYS_fsetxattr,
+    SYS_getxattr,
+    SYS_lgetxattr,
+    SYS_fgetxattr,
+    SYS_listxattr,
+    SYS_llistxattr,
+    SYS_flistxattr,
+    SYS_removexattr,
+    SYS_tkill,
+    SYS_time,
+    SYS_futex,
+    SYS_sched_setaffinity,
+    SYS_sched_getaffinity,
+    SYS_io_setup,
+    SYS_io_destroy,
+    SYS_io_getevents,
+    SYS_io_submit,
+    SYS_epoll_create,
+    SYS_getdents64,
+    SYS_set_tid_address,
+    SYS_restart_syscall,
+    SYS_fadvise64,
+    SYS_timer_create,
+    SYS_timer_settime,
+    SYS_timer_gettime,
+    SYS_timer_delete,
+    SYS_clock_settime,
+    SYS_clock_gettime,
+    SYS_clock_getres,
+    SYS_clock_nanosleep,
+    SYS_exit_group,
+    SYS_epoll_wait,
+    SYS_epoll_ctl,
+    SYS_tgkill,
+    SYS_utimes,
+    SYS_mbind,
+    SYS_waitid,
+    SYS_inotify_init,
+    SYS_inotify_add_watch,
+    SYS_inotify_rm_watch,
+    SYS_openat,
+    SYS_mkdirat,
+    SYS_mknodat,
+    SYS_fchownat,
+    SYS_futimesat,
+    SYS_newfstatat,
+    SYS_unlinkat,
+    SYS_renameat,
+    SYS_linkat,
+    SYS_symlinkat,
+    SYS_readlinkat,
+    SYS_fchmodat,
+    SYS_faccessat,
+    SYS_pselect6,
+    SYS_ppoll,
+    SYS_unshare,
+    SYS_set_robust_list,
+    SYS_get_robust_list,
+    SYS_splice,
+    SYS_tee,
+    SYS_sync_file_range,
+    SYS_vmsplice,
+    SYS_utimensat,
+    SYS_epoll_pwait,
+    SYS_signalfd,
+    SYS_timerfd_create,
+    SYS_eventfd,
+    SYS_fallocate,
+    SYS_timerfd_settime,
+    SYS_timerfd_gettime,
+    SYS_accept4,
+    SYS_signalfd4,
+    SYS_eventfd2,
+    SYS_epoll_create1,
+    SYS_dup3,
+    SYS_pipe2,
+    SYS_inotify_init1,
+    SYS_preadv,
+    SYS_pwritev,
+    SYS_perf_event_open,
+    SYS_recvmmsg,
+    SYS_prlimit64,
+    SYS_name_to_handle_at,
+    SYS_open_by_handle_at,
+    SYS_syncfs,
+    SYS_sendmmsg,
+    SYS_setns,
+    SYS_getcpu,
+    SYS_process_vm_readv,
+    SYS_process_vm_writev,
+    SYS_sched_setattr,
+    SYS_sched_getattr,
+    SYS_renameat2,
+    SYS_seccomp,
+    SYS_getrandom,
+    SYS_memfd_create,
+    SYS_bpf,
+    SYS_execveat,
+    SYS_membarrier,
+    SYS_mlock2,
+    SYS_copy_file_range,
+    SYS_preadv2,
+    SYS_pwritev2,
+    SYS_statx,
+    SYS_rseq,
+    SYS_pidfd_send_signal,
+    SYS_io_uring_setup,
+    SYS_io_uring_enter,
+    SYS_pidfd_open,
+    SYS_clone3,
+    SYS_close_range,
+    SYS_openat2,
+    SYS_pidfd_getfd,
+    SYS_faccessat2,
+    SYS_epoll_pwait2,
+];
+
+/// The name of syscall `number`, or else the name that strace gives unknown syscalls.
+#[cfg(all(feature = "native", target_os = "linux", target_arch = "x86_64"))]
+pub fn syscall_name(number: u64) -> String {
+    match SYSCALLS.iter().find(|(n, _)| *n as u64 == number) {
+        Some((_, name)) => name.trim_start_matches("SYS_").to_string(),
+        None => format!("syscall_{:#x}", number),
+    }
+}
+
+#[cfg(all(
+    feature = "native",
+    not(all(target_os = "linux", target_arch = "x86_64"))
+))]
+pub fn syscall_name(number: u64) -> String {
+    format!("syscall_{:#x}", number)
+}
+
+// the flags of open and openat, where those that include others come first
+#[cfg(feature = "native")]
+const OPEN_FLAGS: &[(i32, &str)] = &[
+    (libc::O_TMPFILE, "O_TMPFILE"),
+    (libc::O_SYNC, "O_SYNC"),
+    (libc::O_CREAT, "O_CREAT"),
+    (libc::O_EXCL, "O_EXCL"),
+    (libc::O_NOCTTY, "O_NOCTTY"),
+    (libc::O_TRUNC, "O_TRUNC"),
+    (libc::O_APPEND, "O_APPEND"),
+    (libc::O_NONBLOCK, "O_NONBLOCK"),
+    (libc::O_DSYNC, "O_DSYNC"),
+    (libc::O_ASYNC, "O_ASYNC"),
+    (libc::O_DIRECT, "O_DIRECT"),
+    // the kernel's value, since it is 0 for 64-bit programs
+    (0o100000, "O_LARGEFILE"),
+    (libc::O_DIRECTORY, "O_DIRECTORY"),
+    (libc::O_NOFOLLOW, "O_NOFOLLOW"),
+    (libc::O_NOATIME, "O_NOATIME"),
+    (libc::O_CLOEXEC, "O_CLOEXEC"),
+    (libc::O_PATH, "O_PATH"),
+];
+
+/// Decodes the flags of open and openat as strace prints them, e.g. `O_RDONLY|O_CLOEXEC`.
+#[cfg(feature = "native")]
+pub fn open_flags(flags: i32) -> SyscallArgValue {
+    let mode = match flags & libc::O_ACCMODE {
+        libc::O_RDONLY => "O_RDONLY",
+        libc::O_WRONLY => "O_WRONLY",
+        _ => "O_RDWR",
+    };
+    let mut values = vec![FlagSetValue::Symbol(mode.to_string())];
+    let mut rest = flags & !libc::O_ACCMODE;
+    for (flag, name) in OPEN_FLAGS {
+        if rest & flag == *flag {
+            values.push(FlagSetValue::Symbol(name.to_string()));
+            rest &= !flag;
+        }
+    }
+    if rest != 0 {
+        values.push(FlagSetValue::Bits(rest as i64));
+    }
+    SyscallArgValue::FlagSet(values)
+}
+
+/// Decodes the argument `arg_name` of `syscall` from the raw value that it was passed, as far as
+/// that can be done without reading the process's memory: AT_FDCWD and the flags of open are
+/// decoded, and everything else is a number.
+#[cfg(feature = "native")]
+pub fn decode_raw(syscall: &str, arg_name: &str, value: u64) -> SyscallArgValue {
+    match (syscall, arg_name) {
+        (_, "dirfd" | "olddirfd" | "newdirfd") if value as i32 == libc::AT_FDCWD => {
+            SyscallArgValue::Symbol("AT_FDCWD".to_string())
+        }
+        ("open" | "openat", "flags") => open_flags(value as i32),
+        // ints, which the register may have junk above
+        (_, name) if name.ends_with("fd") || ["pid", "status"].contains(&name) => {
+            SyscallArgValue::Number(value as i32 as i64)
+        }
+        _ => SyscallArgValue::Number(value as i64),
+    }
+}
+
+/// The name of errno `errno`, e.g. "ENOENT".
+#[cfg(feature = "native")]
+pub fn errno_name(errno: i32) -> String {
+    match errno {
+        // errnos that only the kernel sees, but a tracer does too
+        512 => "ERESTARTSYS".to_string(),
+        513 => "ERESTARTNOINTR".to_string(),
+        514 => "ERESTARTNOHAND".to_string(),
+        516 => "ERESTART_RESTARTBLOCK".to_string(),
+        _ => match Errno::from_raw(errno) {
+            Errno::UnknownErrno => format!("errno {}", errno),
+            e => format!("{:?}", e),
+        },
+    }
+}
+
+// Linux's errnos up to ERANGE, by number, so that errnos can be named without libc
+const LINUX_ERRNOS: &[&str] = &[
+    "", "EPERM", "ENOENT", "ESRCH", "EINTR", "EIO", "ENXIO", "E2BIG", "ENOEXEC", "EBADF", "ECHILD",
+    "EAGAIN", "ENOMEM", "EACCES", "EFAULT", "ENOTBLK", "EBUSY", "EEXIST", "EXDEV", "ENODEV",
+    "ENOTDIR", "EISDIR", "EINVAL", "ENFILE", "EMFILE", "ENOTTY", "ETXTBSY", "EFBIG", "ENOSPC",
+    "ESPIPE", "EROFS", "EMLINK", "EPIPE", "EDOM", "ERANGE",
+];
+
+/// The name of errno `errno` on macOS or FreeBSD, whose errnos are Linux's up to ERANGE and then
+/// go their own way.
+pub fn bsd_errno_name(errno: i32) -> String {
+    const ERRNOS: &[(i32, &str)] = &[
+        (35, "EAGAIN"),
+        (36, "EINPROGRESS"),
+        (37, "EALREADY"),
+        (38, "ENOTSOCK"),
+        (39, "EDESTADDRREQ"),
+        (40, "EMSGSIZE"),
+        (41, "EPROTOTYPE"),
+        (42, "ENOPROTOOPT"),
+        (43, "EPROTONOSUPPORT"),
+        (45, "ENOTSUP"),
+        (47, "EAFNOSUPPORT"),
+        (48, "EADDRINUSE"),
+        (49, "EADDRNOTAVAIL"),
+        (50, "ENETDOWN"),
+        (51, "ENETUNREACH"),
+        (53, "ECONNABORTED"),
+        (54, "ECONNRESET"),
+        (55, "ENOBUFS"),
+        (56, "EISCONN"),
+        (57, "ENOTCONN"),
+        (60, "ETIMEDOUT"),
+        (61, "ECONNREFUSED"),
+        (62, "ELOOP"),
+        (63, "ENAMETOOLONG"),
+        (65, "EHOSTUNREACH"),
+        (66, "ENOTEMPTY"),
+        (69, "EDQUOT"),
+        (70, "ESTALE"),
+        (77, "ENOLCK"),
+        (78, "ENOSYS"),
+    ];
+    match ERRNOS.iter().find(|(n, _)| *n == errno) {
+        Some((_, name)) => name.to_string(),
+        None => match LINUX_ERRNOS.get(errno as usize) {
+            Some(name) if errno > 0 => name.to_string(),
+            _ => format!("errno {}", errno),
+        },
+    }
+}
+
+/// The name of signal `sig` on macOS or FreeBSD, e.g. "SIGCHLD" for 20.
+pub fn bsd_signal_name(sig: i32) -> String {
+    const SIGNALS: &[&str] = &[
+        "SIGHUP",
+        "SIGINT",
+        "SIGQUIT",
+        "SIGILL",
+        "SIGTRAP",
+        "SIGABRT",
+        "SIGEMT",
+        "SIGFPE",
+        "SIGKILL",
+        "SIGBUS",
+        "SIGSEGV",
+        "SIGSYS",
+        "SIGPIPE",
+        "SIGALRM",
+        "SIGTERM",
+        "SIGURG",
+        "SIGSTOP",
+        "SIGTSTP",
+        "SIGCONT",
+        "SIGCHLD",
+        "SIGTTIN",
+        "SIGTTOU",
+        "SIGIO",
+        "SIGXCPU",
+        "SIGXFSZ",
+        "SIGVTALRM",
+        "SIGPROF",
+        "SIGWINCH",
+        "SIGINFO",
+        "SIGUSR1",
+        "SIGUSR2",
+    ];
+    match usize::try_from(sig - 1).ok().and_then(|i| SIGNALS.get(i)) {
+        Some(name) => name.to_string(),
+        None => format!("signal {}", sig),
+    }
+}
+
+/// The name of signal `sig`, e.g. "SIGCHLD", with real-time signals named as strace names them.
+#[cfg(feature = "native")]
+pub fn signal_name(sig: i32) -> String {
+    match Signal::try_from(sig) {
+        Ok(signal) => signal.as_str().to_string(),
+        Err(_) => format!("SIGRT_{}", sig - libc::SIGRTMIN()),
+    }
+}
@@ -0,0 +1,299 @@
+// The aggregate tables of the interface: the events grouped by syscall, path, errno, process, or fd
+// (see src/groups.rs), the files they used, and the network connections they used. Each table adds
+// up the events that pass a filter, in the same language as the events' filter (see src/filter.rs),
+// and can be sorted by any of its columns: numbers biggest first and text in order, or the other
+// way around. The interface builds a table again as the trace goes on.
+//
+// Each row keeps the events that it adds up, so that a row can be drilled down into.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use anyhow::Result;
+
+use crate::filter::Filter;
+use crate::groups::{self, Group, Key};
+use crate::model::Model;
+use crate::syscalls::{self, Io};
+use crate::units;
+
+/// What a table adds up.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Source {
+    // the events grouped by `key`, within the group of each key and value of `trail` in turn
+    Groups { key: Key, trail: Vec<(Key, String)> },
+    // the events grouped by the file they used
+    Files,
+    // the events grouped by the network connection they used
+    Connections,
+}
+
+pub struct Table {
+    // the last column is text, what each row adds up the events of (e.g., a path), and the others
+    // are numbers
+    pub columns: Vec<String>,
+    pub rows: Vec<Row>,
+}
+
+pub struct Row {
+    pub cells: Vec<Cell>,
+    // indices into the syscalls, in order
+    pub indices: Vec<usize>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Cell {
+    Count(u64),
+    Bytes(u64),
+    Micros(u64),
+    Text(String),
+}
+
+impl Source {
+    pub fn title(&self) -> String {
+        match self {
+            Source::Groups { key, trail } if trail.is_empty() => format!("events by {}", key),
+            Source::Groups { key, trail } => {
+                let values: Vec<&str> = trail.iter().map(|(_, value)| value.as_str()).collect();
+                format!("events by {} in {}", key, values.join(" > "))
+            }
+            Source::Files => "files".to_string(),
+            Source::Connections => "network connections".to_string(),
+        }
+    }
+
+    /// Adds up the events of `m` that pass `filter` (in the language of src/filter.rs).
+    pub fn build(&self, m: &Model, filter: &str) -> Result<Table> {
+        let mut filter = Filter::parse(filter)?;
+        let mut indices: Vec<usize> = (0..m.syscalls.len())
+            .filter(|i| filter.matches(*i, &m.syscalls[*i]))
+            .collect();
+        Ok(match self {
+            Source::Groups { key, trail } => {
+                for (key, value) in trail {
+                    indices = groups::group(&m.syscalls, &indices, *key, &m.fds)
+                        .into_iter()
+                        .find(|group| group.value == *value)
+                        .map(|group| group.indices)
+                        .unwrap_or_default();
+                }
+                groups_table(groups::group(&m.syscalls, &indices, *key, &m.fds), key)
+            }
+            Source::Files => {
+                let mut files = groups::group(&m.syscalls, &indices, Key::Path, &m.fds);
+                files.retain(|group| group.value != groups::NONE);
+                groups_table(files, "file")
+            }
+            Source::Connections => connections_table(m, &indices),
+        })
+    }
+}
+
+fn groups_table(groups: Vec<Group>, name: impl fmt::Display) -> Table {
+    let columns = ["calls", "errors", "read", "written", "time"]
+        .iter()
+        .map(|c| c.to_string())
+        .chain([name.to_string()])
+        .collect();
+    let rows = groups
+        .into_iter()
+        .map(|group| Row {
+            cells: vec![
+                Cell::Count(group.indices.len() as u64),
+                Cell::Count(group.errors as u64),
+                Cell::Bytes(group.read),
+                Cell::Bytes(group.written),
+                Cell::Micros(group.micros),
+                Cell::Text(group.value),
+            ],
+            indices: group.indices,
+        })
+        .collect();
+    Table { columns, rows }
+}
+
+fn connections_table(m: &Model, indices: &[usize]) -> Table {
+    let mut by_connection: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+    for index in indices {
+        if let Some(connection) = m.connection_syscalls.get(index) {
+            by_connection.entry(*connection).or_default().push(*index);
+        }
+    }
+    let columns = ["calls", "read", "written", "time", "connection"]
+        .iter()
+        .map(|c| c.to_string())
+        .collect();
+    let rows = by_connection
+        .into_iter()
+        .map(|(connection, indices)| {
+            let (mut read, mut written, mut micros) = (0, 0, 0);
+            for syscall in indices.iter().map(|i| &m.syscalls[*i]) {
+                micros += syscall.syscall_time_micros.unwrap_or(0);
+                let bytes = syscall.return_value.max(0) as u64;
+                match syscalls::io_direction(&syscall.name) {
+                    Some(Io::Read) if syscall.errno.is_none() => read += bytes,
+                    Some(Io::Write) if syscall.errno.is_none() => written += bytes,
+                    _ => {}
+                }
+            }
+            let connection = &m.net.connections[connection];
+            // e.g., "1234 (curl): tcp client 10.0.0.2:40000 -> 93.184.216.34:80 (closed)"
+            let mut text = String::new();
+            if let Some(pid) = connection.pid {
+                text.push_str(&format!("{}: ", m.processes.label(pid)));
+            }
+            text.push_str(&connection.describe());
+            match &connection.close_reason {
+                Some(reason) => text.push_str(&format!(" ({})", reason)),
+                None => text.push_str(" (open)"),
+            }
+            Row {
+                cells: vec![
+                    Cell::Count(indices.len() as u64),
+                    Cell::Bytes(read),
+                    Cell::Bytes(written),
+                    Cell::Micros(micros),
+                    Cell::Text(text),
+                ],
+                indices,
+            }
+        })
+        .collect();
+    Table { columns, rows }
+}
+
+impl Table {
+    /// Sorts the rows by `column`, with the biggest numbers or the first text first, or the other
+    /// way around if `reversed`. Rows that tie keep their order.
+    pub fn sort(&mut self, column: usize, reversed: bool) {
+        self.rows.sort_by(|a, b| {
+            let order = match (&a.cells[column], &b.cells[column]) {
+                (Cell::Text(a), Cell::Text(b)) => a.cmp(b),
+                (a, b) => b.number().cmp(&a.number()),
+            };
+            if reversed {
+                order.reverse()
+            } else {
+                order
+            }
+        });
+    }
+
+    /// The names of the columns, lined up with `line`, with an arrow by the one that the rows are
+    /// sorted by.
+    pub fn header(&self, sorted: usize, reversed: bool) -> String {
+        let names: Vec<String> = self
+            .columns
+            .iter()
+            .enumerate()
+            .map(|(i, name)| match (i == sorted, reversed) {
+                (false, _) => name.clone(),
+                (true, false) => format!("↓{}", name),
+                (true, true) => format!("↑{}", name),
+            })
+            .collect();
+        align(&names)
+    }
+
+    pub fn line(&self, row: &Row) -> String {
+        let cells: Vec<String> = row.cells.iter().map(|cell| cell.to_string()).collect();
+        align(&cells)
+    }
+}
+
+// the numbers are right-aligned, and the text in the last column is left as it is
+fn align(cells: &[String]) -> String {
+    let last = cells.len().saturating_sub(1);
+    let cells: Vec<String> = cells
+        .iter()
+        .enumerate()
+        .map(|(i, cell)| {
+            if i == last {
+                cell.clone()
+            } else {
+                format!("{:>11}", cell)
+            }
+        })
+        .collect();
+    cells.join(" ")
+}
+
+impl Cell {
+    fn number(&self) -> u64 {
+        match self {
+            Cell::Count(n) | Cell::Bytes(n) | Cell::Micros(n) => *n,
+            Cell::Text(_) => 0,
+        }
+    }
+}
+
+impl fmt::Display for Cell {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Cell::Count(n) => write!(f, "{}", units::count(*n)),
+            Cell::Bytes(n) => write!(f, "{}", units::bytes(*n)),
+            Cell::Micros(n) => write!(f, "{}", units::duration(*n)),
+            Cell::Text(text) => write!(f, "{}", text),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::groups::Key;
+    use crate::model::Model;
+    use crate::strace::parse_syscall;
+
+    use super::{Cell, Source};
+
+    #[test]
+    fn test_table() {
+        let mut m = Model::default();
+        for line in [
+            "1.000000 openat(AT_FDCWD, \"/etc/hosts\", O_RDONLY) = 3 <0.000010>",
+            "1.000001 read(3, \"127.0.0.1 localhost\\n\", 4096) = 20 <0.000005>",
+            "1.000002 close(3) = 0 <0.000001>",
+            "1.000003 openat(AT_FDCWD, \"/etc/missing\", O_RDONLY) = -1 ENOENT (No such file or directory) <0.000004>",
+            "1.000004 socket(AF_INET, SOCK_STREAM, IPPROTO_TCP) = 4 <0.000002>",
+            "1.000005 connect(4, {sa_family=AF_INET, sin_port=htons(80), sin_addr=inet_addr(\"93.184.216.34\")}, 16) = 0 <0.000100>",
+            "1.000006 write(4, \"GET / HTTP/1.1\\r\\n\\r\\n\", 18) = 18 <0.000003>",
+        ] {
+            m.push(parse_syscall(line, true));
+        }
+
+        let by_name = Source::Groups {
+            key: Key::Name,
+            trail: Vec::new(),
+        };
+        let mut table = by_name.build(&m, "").unwrap();
+        assert_eq!(table.columns.len(), 6);
+        // by the time they took
+        table.sort(4, false);
+        let names: Vec<String> = table.rows.iter().map(|r| r.cells[5].to_string()).collect();
+        assert_eq!(names[..2], ["connect", "openat"]);
+        assert_eq!(table.rows[1].cells[1], Cell::Count(1));
+        // by name, the other way around
+        table.sort(5, true);
+        assert_eq!(table.rows[0].cells[5], Cell::Text("write".to_string()));
+        assert!(table.header(5, true).ends_with("↑name"));
+
+        let table = by_name.build(&m, "openat,read").unwrap();
+        assert_eq!(table.rows.len(), 2);
+        assert!(by_name.build(&m, "fd=abc").is_err());
+
+        let drilled = Source::Groups {
+            key: Key::Path,
+            trail: vec![(Key::Name, "openat".to_string())],
+        };
+        assert_eq!(drilled.build(&m, "").unwrap().rows.len(), 2);
+
+        let files = Source::Files.build(&m, "").unwrap();
+        let paths: Vec<String> = files.rows.iter().map(|r| r.cells[5].to_string()).collect();
+        assert_eq!(paths, ["/etc/hosts", "/etc/missing"]);
+
+        let connections = Source::Connections.build(&m, "").unwrap();
+        assert_eq!(connections.rows.len(), 1);
+        assert_eq!(connections.rows[0].indices, [4, 5, 6]);
+        assert_eq!(connections.rows[0].cells[2], Cell::Bytes(18));
+    }
+}
@@ -0,0 +1,192 @@
+// `vistrace run` can trace several commands at once, so that services that talk to each other can
+// be watched together, e.g.:
+//
+//   vistrace run --name api ./api -- --name worker ./worker
+//
+// Each command (a "target") gets its own tracer, and their events are merged into one model. The
+// first process of each target is whichever one its tracer reports first, and every process that
+// it starts belongs to the same target, which is what the interface's tabs show.
+
+use std::sync::{Arc, Mutex};
+
+use anyhow::{anyhow, Result};
+
+use crate::processes::ProcessTable;
+use crate::strace::{Message, Parse};
+
+pub struct Target {
+    pub name: String,
+    pub command: Vec<String>,
+}
+
+/// Splits the arguments of `vistrace run` into the commands to trace. A `--` followed by `--name`
+/// starts another command, so that a lone `--` can still be passed to a command.
+pub fn split(name: Option<String>, args: Vec<String>) -> Result<Vec<Target>> {
+    let mut targets = Vec::new();
+    let mut name = name;
+    let mut command = Vec::new();
+    let mut args = args.into_iter().peekable();
+    while let Some(arg) = args.next() {
+        if arg == "--" && args.peek().is_some_and(|next| next == "--name") {
+            args.next();
+            let next_name = args.next().ok_or_else(|| anyhow!("--name needs a value"))?;
+            targets.push(target(
+                name.take(),
+                std::mem::take(&mut command),
+                targets.len(),
+            )?);
+            name = Some(next_name);
+        } else {
+            command.push(arg);
+        }
+    }
+    targets.push(target(name, command, targets.len())?);
+    Ok(targets)
+}
+
+fn target(name: Option<String>, command: Vec<String>, index: usize) -> Result<Target> {
+    let program = command.first().ok_or_else(|| match &name {
+        Some(name) => anyhow!("no command to trace for --name {}", name),
+        None => anyhow!("no command to trace"),
+    })?;
+    // e.g., "api" for "./bin/api --port 80", if not named
+    let name = name.unwrap_or_else(|| match program.rsplit_once('/') {
+        Some((_, base)) => base.to_string(),
+        None => program.clone(),
+    });
+    let name = if name.is_empty() {
+        format!("#{}", index + 1)
+    } else {
+        name
+    };
+    Ok(Target { name, command })
+}
+
+/// The first process of each target, by index, as its tracer finds it. Shared between the tracers
+/// and the interface.
+#[derive(Clone, Default)]
+pub struct Roots(Arc<Mutex<Vec<Option<u32>>>>);
+
+impl Roots {
+    pub fn new(count: usize) -> Roots {
+        Roots(Arc::new(Mutex::new(vec![None; count])))
+    }
+
+    pub fn set(&self, target: usize, pid: u32) {
+        self.0.lock().unwrap()[target].get_or_insert(pid);
+    }
+
+    fn find(&self, pid: u32) -> Option<usize> {
+        self.0.lock().unwrap().iter().position(|r| *r == Some(pid))
+    }
+}
+
+/// Parses the output of one target's tracer, noting the first process that it reports.
+pub struct Tagged<P> {
+    pub parser: P,
+    pub roots: Roots,
+    pub target: usize,
+}
+
+impl<P: Parse> Parse for Tagged<P> {
+    fn parse_line(&mut self, line: &str) -> Option<Message> {
+        let msg = self.parser.parse_line(line);
+        if let Some(Message::Syscall(syscall)) = &msg {
+            if let Some(pid) = syscall.pid {
+                self.roots.set(self.target, pid);
+            }
+        }
+        msg
+    }
+
+    fn is_strace(&self) -> bool {
+        self.parser.is_strace()
+    }
+}
+
+/// The commands that were traced together, by name, and which one each process belongs to.
+#[derive(Default)]
+pub struct Targets {
+    pub names: Vec<String>,
+    roots: Roots,
+}
+
+impl Targets {
+    pub fn new(names: Vec<String>, roots: Roots) -> Targets {
+        Targets { names, roots }
+    }
+
+    /// Returns the target of process `pid`, if it is known yet: that of the first process of a
+    /// target, or of the process that started it.
+    pub fn target_of(&self, pid: u32, processes: &ProcessTable) -> Option<usize> {
+        let mut ancestor = pid;
+        loop {
+            if let Some(target) = self.roots.find(ancestor) {
+                return Some(target);
+            }
+            let process = processes.processes.get(&ancestor)?;
+            ancestor = match process.parent {
+                _ if process.is_thread() => process.tgid,
+                Some(parent) => parent,
+                None => return None,
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::processes::ProcessTable;
+    use crate::strace::{LineParser, Message};
+
+    use super::{split, Roots, Targets};
+
+    #[test]
+    fn test_split() {
+        let args = |s: &str| s.split(' ').map(String::from).collect::<Vec<_>>();
+
+        let targets = split(
+            Some("api".to_string()),
+            args("./api --port 80 -- --name worker ./bin/worker -- -v"),
+        )
+        .unwrap();
+        assert_eq!(targets.len(), 2);
+        assert_eq!(targets[0].name, "api");
+        assert_eq!(targets[0].command, args("./api --port 80"));
+        assert_eq!(targets[1].name, "worker");
+        assert_eq!(targets[1].command, args("./bin/worker -- -v"));
+
+        let targets = split(None, args("./bin/cargo test -- --nocapture")).unwrap();
+        assert_eq!(targets.len(), 1);
+        assert_eq!(targets[0].name, "cargo");
+
+        assert!(split(None, args("ls -- --name")).is_err());
+        assert!(split(None, args("ls -- --name x")).is_err());
+    }
+
+    #[test]
+    fn test_target_of() {
+        let mut processes = ProcessTable::default();
+        let mut parser = LineParser::default();
+        for line in [
+            "10 clone(child_stack=NULL, flags=SIGCHLD) = 11",
+            "20 clone(child_stack=NULL, flags=SIGCHLD) = 21",
+        ] {
+            processes.update(
+                &parser
+                    .parse_line(line)
+                    .and_then(Message::into_syscall)
+                    .unwrap(),
+            );
+        }
+        let roots = Roots::new(2);
+        roots.set(0, 10);
+        roots.set(1, 20);
+        roots.set(1, 22);
+        let targets = Targets::new(vec!["a".to_string(), "b".to_string()], roots);
+        assert_eq!(targets.target_of(11, &processes), Some(0));
+        assert_eq!(targets.target_of(21, &processes), Some(1));
+        assert_eq!(targets.target_of(20, &processes), Some(1));
+        assert_eq!(targets.target_of(30, &processes), None);
+    }
+}
@@ -0,0 +1,232 @@
+// Recognizes the unencrypted start of a TLS handshake, so that vistrace can at least say where
+// encrypted traffic is going: the ClientHello names the server (SNI), and the ServerHello says
+// which version of TLS was agreed on.
+//
+// strace only prints the first 32 bytes of a buffer by default, which is too few to reach the
+// server name; pass `--strace-arg=-s1024` to see it.
+
+use std::fmt;
+
+const HANDSHAKE: u8 = 0x16;
+const CLIENT_HELLO: u8 = 1;
+const SERVER_HELLO: u8 = 2;
+const SERVER_NAME: u16 = 0;
+const SUPPORTED_VERSIONS: u16 = 43;
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Tls {
+    // e.g., "example.com", from the ClientHello
+    pub server_name: Option<String>,
+    // e.g., "1.3", from the ServerHello
+    pub version: Option<String>,
+}
+
+pub enum Hello {
+    Client { server_name: Option<String> },
+    Server { version: String },
+}
+
+/// Returns whether `text`, as strace prints it, might be the start of a TLS handshake (to avoid
+/// decoding every buffer).
+pub fn maybe_handshake(text: &str) -> bool {
+    // 0x16, with or without -x
+    text.starts_with("\\26") || text.starts_with("\\x16")
+}
+
+/// Decodes a TLS record holding a ClientHello or a ServerHello. A ClientHello that was cut off
+/// is still recognized, though without its server name.
+pub fn decode_hello(record: &[u8]) -> Option<Hello> {
+    // content type, legacy version, length, then the handshake type, length, and legacy version
+    if record.len() < 6 || record[0] != HANDSHAKE || record[1] != 3 {
+        return None;
+    }
+    let mut reader = Reader {
+        data: record,
+        index: 9,
+    };
+    match record[5] {
+        CLIENT_HELLO => Some(Hello::Client {
+            server_name: client_hello_server_name(&mut reader),
+        }),
+        SERVER_HELLO => {
+            let legacy_version = reader.u16()?;
+            let version = server_hello_version(&mut reader).unwrap_or(legacy_version);
+            Some(Hello::Server {
+                version: version_name(version),
+            })
+        }
+        _ => None,
+    }
+}
+
+fn client_hello_server_name(reader: &mut Reader) -> Option<String> {
+    // legacy version, random, session id, cipher suites, compression methods
+    reader.skip(2 + 32)?;
+    reader.skip_u8_prefixed()?;
+    reader.skip_u16_prefixed()?;
+    reader.skip_u8_prefixed()?;
+    let mut extensions = reader.extensions()?;
+    let mut names = extensions
+        .find(|(t, _)| *t == SERVER_NAME)
+        .map(|(_, r)| r)?;
+    // the list's length, then entries of a type (0 for a host name) and a name
+    names.skip(2)?;
+    if names.u8()? != 0 {
+        return None;
+    }
+    let len = names.u16()? as usize;
+    Some(String::from_utf8_lossy(names.bytes(len)?).into_owned())
+}
+
+fn server_hello_version(reader: &mut Reader) -> Option<u16> {
+    // random, session id, cipher suite, compression method
+    reader.skip(32)?;
+    reader.skip_u8_prefixed()?;
+    reader.skip(3)?;
+    let mut extensions = reader.extensions()?;
+    // TLS 1.3 hides behind the legacy version 1.2 and puts the real one in an extension
+    let mut versions = extensions
+        .find(|(t, _)| *t == SUPPORTED_VERSIONS)
+        .map(|(_, r)| r)?;
+    versions.u16()
+}
+
+fn version_name(version: u16) -> String {
+    match version {
+        0x0300 => "SSL 3.0".to_string(),
+        0x0301 => "1.0".to_string(),
+        0x0302 => "1.1".to_string(),
+        0x0303 => "1.2".to_string(),
+        0x0304 => "1.3".to_string(),
+        v => format!("{:#06x}", v),
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    index: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, n: usize) -> Option<&'a [u8]> {
+        let bytes = self.data.get(self.index..self.index + n)?;
+        self.index += n;
+        Some(bytes)
+    }
+
+    fn skip(&mut self, n: usize) -> Option<()> {
+        self.bytes(n).map(|_| ())
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        Some(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        let bytes = self.bytes(2)?;
+        Some(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn skip_u8_prefixed(&mut self) -> Option<()> {
+        let len = self.u8()? as usize;
+        self.skip(len)
+    }
+
+    fn skip_u16_prefixed(&mut self) -> Option<()> {
+        let len = self.u16()? as usize;
+        self.skip(len)
+    }
+
+    // the type and data of each extension, up to the end of the data if it was cut off
+    fn extensions(&mut self) -> Option<impl Iterator<Item = (u16, Reader<'a>)>> {
+        let len = self.u16()? as usize;
+        let end = (self.index + len).min(self.data.len());
+        let mut extensions = Reader {
+            data: &self.data[..end],
+            index: self.index,
+        };
+        Some(std::iter::from_fn(move || {
+            let extension_type = extensions.u16()?;
+            let len = extensions.u16()? as usize;
+            let data = extensions.bytes(len)?;
+            Some((extension_type, Reader { data, index: 0 }))
+        }))
+    }
+}
+
+impl fmt::Display for Tls {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "TLS")?;
+        if let Some(version) = &self.version {
+            write!(f, " {}", version)?;
+        }
+        if let Some(server_name) = &self.server_name {
+            write!(f, ", {}", server_name)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode_hello, Hello};
+
+    fn client_hello(server_name: &str) -> Vec<u8> {
+        let mut sni = vec![0, 0];
+        let list_len = (server_name.len() + 3) as u16;
+        sni.extend((list_len + 2).to_be_bytes());
+        sni.extend(list_len.to_be_bytes());
+        sni.push(0);
+        sni.extend((server_name.len() as u16).to_be_bytes());
+        sni.extend(server_name.as_bytes());
+
+        // legacy version, random, session id, one cipher suite, one compression method
+        let mut body = vec![3, 3];
+        body.extend([0; 32]);
+        body.extend([0, 0, 2, 0x13, 0x01, 1, 0]);
+        // an empty extension before the server name
+        let extensions_len = (sni.len() + 4) as u16;
+        body.extend(extensions_len.to_be_bytes());
+        body.extend([0, 23, 0, 0]);
+        body.extend(sni);
+
+        let mut record = vec![0x16, 3, 1];
+        record.extend(((body.len() + 4) as u16).to_be_bytes());
+        record.extend([1, 0]);
+        record.extend((body.len() as u16).to_be_bytes());
+        record.extend(body);
+        record
+    }
+
+    #[test]
+    fn test_decode_hello() {
+        let record = client_hello("example.com");
+        match decode_hello(&record) {
+            Some(Hello::Client { server_name }) => {
+                assert_eq!(server_name.as_deref(), Some("example.com"))
+            }
+            _ => panic!("expected a ClientHello"),
+        }
+        // cut off by strace
+        match decode_hello(&record[..32]) {
+            Some(Hello::Client { server_name }) => assert_eq!(server_name, None),
+            _ => panic!("expected a ClientHello"),
+        }
+
+        // a TLS 1.3 ServerHello, with supported_versions
+        let mut server_hello = vec![0x16, 3, 3, 0, 46, 2, 0, 0, 42, 3, 3];
+        server_hello.extend([0; 32]);
+        server_hello.extend([0, 0x13, 0x01, 0, 0, 6, 0, 43, 0, 2, 3, 4]);
+        match decode_hello(&server_hello) {
+            Some(Hello::Server { version }) => assert_eq!(version, "1.3"),
+            _ => panic!("expected a ServerHello"),
+        }
+        // without extensions, the legacy version is the real one
+        match decode_hello(&server_hello[..11 + 32 + 4]) {
+            Some(Hello::Server { version }) => assert_eq!(version, "1.2"),
+            _ => panic!("expected a ServerHello"),
+        }
+
+        assert!(decode_hello(b"GET / HTTP/1.1\r\n").is_none());
+    }
+}
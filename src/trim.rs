@@ -0,0 +1,385 @@
+// Cuts a saved trace down to a window of time (`vistrace export --from 1:30 --to 2:00 in.vst
+// out.vst`), to share or look closer at the interesting part of a long trace.
+//
+// The events before the window that the window still depends on are kept as well, so that the
+// slice makes sense on its own: for each process that is still running when the window starts, the
+// syscall that created it, its last execve, and its last chdir, and for each file descriptor that
+// is still open, the syscall that opened it (and those that opened the descriptors that it came
+// from, e.g. the listener that a connection was accepted from), along with any bind, connect, or
+// listen on it. They keep their original times, so they show up just before the window.
+//
+// The trimmed trace is written as .vst, with each event as strace would have printed it, whatever
+// tracer the original came from.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+
+use crate::fdtable::FdTable;
+use crate::strace::{Message, Parse, Syscall};
+use crate::{syscalls, vst};
+
+/// Writes the events of the trace at `input` (a .vst file or a tracer's text output, as parsed by
+/// `parser`) from `from` until `to` into the trace, with their context, to a .vst file at `output`.
+/// Each event that is written is first passed to `transform`. Returns the number of events written.
+pub fn trim(
+    input: &Path,
+    mut parser: impl Parse,
+    output: &Path,
+    from: Option<Duration>,
+    to: Option<Duration>,
+    mut transform: impl FnMut(&mut Message),
+) -> Result<usize> {
+    let mut writer = vst::Writer::create(output, vst::Encoding::default())?;
+    let mut trimmer = Trimmer::new(from, to);
+    let mut written = 0;
+    for_each_line(input, |line| {
+        let Some(msg) = parser.parse_line(line) else {
+            return Ok(true);
+        };
+        let Some(messages) = trimmer.push(msg) else {
+            return Ok(false);
+        };
+        for mut msg in messages {
+            transform(&mut msg);
+            writer
+                .write(&format!("{}\n", msg), Some(&msg))
+                .map_err(|e| anyhow!("unable to write {}: {}", output.display(), e))?;
+            written += 1;
+        }
+        Ok(true)
+    })?;
+    writer
+        .finish()
+        .map_err(|e| anyhow!("unable to write {}: {}", output.display(), e))?;
+    Ok(written)
+}
+
+// calls `f` with each line of the trace at `path` until it returns false
+fn for_each_line(path: &Path, mut f: impl FnMut(&str) -> Result<bool>) -> Result<()> {
+    if !vst::is_vst(path) {
+        let file =
+            File::open(path).map_err(|e| anyhow!("unable to open {}: {}", path.display(), e))?;
+        for line in BufReader::new(file).lines() {
+            let line = line.map_err(|e| anyhow!("unable to read {}: {}", path.display(), e))?;
+            if !f(&line)? {
+                break;
+            }
+        }
+        return Ok(());
+    }
+    let mut reader = vst::Reader::open(path)?;
+    for i in 0..reader.block_count() {
+        for line in reader.read_block(i)? {
+            if !f(&line)? {
+                return Ok(());
+            }
+        }
+    }
+    Ok(())
+}
+
+struct Trimmer {
+    from: Option<Duration>,
+    to: Option<Duration>,
+    // when the trace started, once an event with a time has been seen
+    start_micros: Option<u64>,
+    in_window: bool,
+    context: Context,
+}
+
+impl Trimmer {
+    fn new(from: Option<Duration>, to: Option<Duration>) -> Trimmer {
+        Trimmer {
+            from,
+            to,
+            start_micros: None,
+            in_window: from.is_none(),
+            context: Context::default(),
+        }
+    }
+
+    // returns the messages to write, or `None` once the window is over
+    fn push(&mut self, msg: Message) -> Option<Vec<Message>> {
+        let time_micros = msg.time_micros();
+        let start_micros = match (self.start_micros, time_micros) {
+            (Some(start), _) => Some(start),
+            (None, 0) => None,
+            (None, time) => Some(*self.start_micros.insert(time)),
+        };
+        let into = |offset: Option<Duration>| {
+            offset
+                .zip(start_micros)
+                .map(|(d, s)| s + d.as_micros() as u64)
+        };
+        if let Some(to_micros) = into(self.to) {
+            if time_micros > to_micros {
+                return None;
+            }
+        }
+        if self.in_window {
+            return Some(vec![msg]);
+        }
+        match into(self.from) {
+            Some(from_micros) if time_micros >= from_micros => {
+                self.in_window = true;
+                let mut messages = self.context.take();
+                messages.push(msg);
+                Some(messages)
+            }
+            _ => {
+                self.context.record(msg);
+                Some(Vec::new())
+            }
+        }
+    }
+}
+
+// The events so far that later events may depend on.
+#[derive(Default)]
+struct Context {
+    // to find the process whose fds a thread uses
+    fds: FdTable,
+    // the events that are kept, by position in the trace, with how many things depend on them
+    kept: BTreeMap<usize, (Message, usize)>,
+    // for each process, the events that created it, ran its program, and set its working directory
+    processes: HashMap<Option<u32>, Vec<usize>>,
+    // for each open fd, by the process whose fd table it is in, the events that set it up
+    open: HashMap<(Option<u32>, i64), Vec<usize>>,
+    next: usize,
+}
+
+impl Context {
+    fn record(&mut self, msg: Message) {
+        let i = self.next;
+        self.next += 1;
+        let depended = match &msg {
+            Message::Syscall(syscall) => self.record_syscall(i, syscall),
+            Message::Exit(exit) => {
+                let released: Vec<usize> = self
+                    .processes
+                    .remove(&exit.pid)
+                    .into_iter()
+                    .flatten()
+                    .chain(
+                        self.open
+                            .extract_if(|(owner, _), _| *owner == exit.pid)
+                            .flat_map(|(_, events)| events),
+                    )
+                    .collect();
+                self.release(released);
+                0
+            }
+            _ => 0,
+        };
+        if depended > 0 {
+            self.kept.insert(i, (msg, depended));
+        }
+    }
+
+    // returns how many things depend on the syscall, which is the `i`th event
+    fn record_syscall(&mut self, i: usize, syscall: &Syscall) -> usize {
+        if syscall.errno.is_some() || syscall.error_details.is_some() {
+            return 0;
+        }
+        let owner = self.fds.owner(syscall.pid);
+        let fd_args = syscalls::fd_args(syscall);
+        let created = syscalls::created_fds(syscall);
+        self.fds.update(syscall);
+        let mut depended = 0;
+
+        match syscall.name.as_str() {
+            "clone" | "clone3" | "fork" | "vfork" if syscall.return_value > 0 => {
+                let child = Some(syscall.return_value as u32);
+                // the child starts with its parent's program, directory, and (unless it shares
+                // them) copies of its fds
+                let mut inherited = self
+                    .processes
+                    .get(&syscall.pid)
+                    .cloned()
+                    .unwrap_or_default();
+                inherited.retain(|&e| e != i);
+                if !syscalls::has_clone_flag(syscall, "CLONE_FILES") {
+                    let fds: Vec<(i64, Vec<usize>)> = self
+                        .open
+                        .iter()
+                        .filter(|((o, _), _)| *o == owner)
+                        .map(|((_, fd), events)| (*fd, events.clone()))
+                        .collect();
+                    for (fd, events) in fds {
+                        self.refer(&events);
+                        self.replace_fd((self.fds.owner(child), fd), events);
+                    }
+                }
+                self.refer(&inherited);
+                inherited.push(i);
+                depended += 1;
+                let old = self.processes.insert(child, inherited);
+                self.release(old.into_iter().flatten().collect());
+            }
+            "execve" | "execveat" => {
+                depended += 1;
+                self.replace_process_event(syscall.pid, i, |name| {
+                    name == "execve" || name == "execveat"
+                });
+            }
+            "chdir" | "fchdir" => {
+                depended += 1;
+                self.replace_process_event(syscall.pid, i, |name| {
+                    name == "chdir" || name == "fchdir"
+                });
+            }
+            "close" => {
+                for fd in &fd_args {
+                    let old = self.open.remove(&(owner, *fd));
+                    self.release(old.into_iter().flatten().collect());
+                }
+            }
+            "bind" | "connect" | "listen" => {
+                if let Some(events) = fd_args
+                    .first()
+                    .and_then(|fd| self.open.get_mut(&(owner, *fd)))
+                {
+                    events.push(i);
+                    depended += 1;
+                }
+            }
+            _ => {}
+        }
+
+        // a new fd depends on the fds that it came from, e.g. the one that it duplicates
+        for fd in &created {
+            let mut events: Vec<usize> = fd_args
+                .iter()
+                .filter(|fd| !created.contains(fd))
+                .filter_map(|fd| self.open.get(&(owner, *fd)))
+                .flatten()
+                .copied()
+                .collect();
+            self.refer(&events);
+            events.push(i);
+            depended += 1;
+            self.replace_fd((owner, *fd), events);
+        }
+        depended
+    }
+
+    // replaces the event of a process that `is_same` says is of the same kind as the `i`th event
+    fn replace_process_event(
+        &mut self,
+        pid: Option<u32>,
+        i: usize,
+        is_same: impl Fn(&str) -> bool,
+    ) {
+        let events = self.processes.entry(pid).or_default();
+        let kept = &self.kept;
+        let old: Vec<usize> = events
+            .iter()
+            .copied()
+            .filter(|e| {
+                kept.get(e)
+                    .is_some_and(|(msg, _)| matches!(msg, Message::Syscall(s) if is_same(&s.name)))
+            })
+            .collect();
+        events.retain(|e| !old.contains(e));
+        events.push(i);
+        self.release(old);
+    }
+
+    fn replace_fd(&mut self, key: (Option<u32>, i64), events: Vec<usize>) {
+        let old = self.open.insert(key, events);
+        self.release(old.into_iter().flatten().collect());
+    }
+
+    fn refer(&mut self, events: &[usize]) {
+        for e in events {
+            if let Some((_, count)) = self.kept.get_mut(e) {
+                *count += 1;
+            }
+        }
+    }
+
+    fn release(&mut self, events: Vec<usize>) {
+        for e in events {
+            if let Some((_, count)) = self.kept.get_mut(&e) {
+                *count -= 1;
+                if *count == 0 {
+                    self.kept.remove(&e);
+                }
+            }
+        }
+    }
+
+    // the events that are kept, in order
+    fn take(&mut self) -> Vec<Message> {
+        std::mem::take(&mut self.kept)
+            .into_values()
+            .map(|(msg, _)| msg)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::strace::LineParser;
+
+    use super::Trimmer;
+
+    #[test]
+    fn test_trim() {
+        let mut trimmer =
+            Trimmer::new(Some(Duration::from_secs(10)), Some(Duration::from_secs(20)));
+        let mut parser = LineParser::default();
+        let mut written = Vec::new();
+        for line in [
+            "1 1720000000.000000 execve(\"/usr/bin/server\", [\"server\"], 0x7ffd) = 0",
+            "1 1720000001.000000 openat(AT_FDCWD, \"/etc/passwd\", O_RDONLY) = 3",
+            "1 1720000001.000001 read(3, \"root\", 4) = 4",
+            "1 1720000001.000002 close(3) = 0",
+            "1 1720000002.000000 socket(AF_INET, SOCK_STREAM, IPPROTO_TCP) = 3",
+            "1 1720000002.000001 bind(3, {sa_family=AF_INET, sin_port=htons(80), sin_addr=inet_addr(\"0.0.0.0\")}, 16) = 0",
+            "1 1720000002.000002 listen(3, 128) = 0",
+            "1 1720000003.000000 openat(AT_FDCWD, \"/var/log/server.log\", O_WRONLY) = 4",
+            "1 1720000004.000000 clone(child_stack=NULL, flags=SIGCHLD) = 2",
+            "2 1720000004.000001 execve(\"/bin/sh\", [\"sh\"], 0x7ffd) = 0",
+            "2 1720000004.000002 close(4) = 0",
+            "1 1720000005.000000 accept(3, NULL, NULL) = 5",
+            "1 1720000006.000000 clone(child_stack=NULL, flags=SIGCHLD) = 3",
+            "3 1720000006.000001 +++ exited with 0 +++",
+            "1 1720000011.000000 read(5, \"GET /\", 5) = 5",
+            "1 1720000012.000000 write(4, \"GET /\", 5) = 5",
+            "1 1720000021.000000 write(5, \"200\", 3) = 3",
+        ] {
+            let msg = parser.parse_line(line).unwrap();
+            match trimmer.push(msg) {
+                Some(messages) => written.extend(messages.iter().map(|m| m.to_string())),
+                None => break,
+            }
+        }
+        let names: Vec<String> = written
+            .iter()
+            .map(|line| line.split('(').next().unwrap().to_string())
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "1 1720000000.000000 execve",
+                "1 1720000002.000000 socket",
+                "1 1720000002.000001 bind",
+                "1 1720000002.000002 listen",
+                "1 1720000003.000000 openat",
+                "1 1720000004.000000 clone",
+                "2 1720000004.000001 execve",
+                "1 1720000005.000000 accept",
+                "1 1720000011.000000 read",
+                "1 1720000012.000000 write",
+            ]
+        );
+    }
+}
@@ -0,0 +1,338 @@
+// A tracer for FreeBSD (`--backend truss`), which has truss rather than strace. With -f -d, truss
+// prints a line like this when a syscall returns, with the pid, and the time since it started in
+// seconds:
+//
+//   4120: 0.001085123 open("/etc/hosts",O_RDONLY|O_CLOEXEC,00) = 3 (0x3)
+//   4120: 0.001101456 stat("/nope",0x7fffffffe0a0) ERR#2 'No such file or directory'
+//   4120: 0.002000000 SIGNAL 20 (SIGCHLD) code=CLD_EXITED pid=4121 uid=1001 status=0
+//   4120: 0.002100000 process exit, rval = 0
+//
+// It does not say how long syscalls took. Its output can also be viewed later, with `vistrace
+// view`.
+
+#[cfg(feature = "native")]
+use std::path::PathBuf;
+#[cfg(feature = "native")]
+use std::process::{Child, Command, Stdio};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[cfg(feature = "native")]
+use anyhow::{anyhow, Result};
+
+use crate::strace::{
+    self, Exit, ExitKind, FlagSetValue, Message, Parse, Signal, Syscall, SyscallArg,
+    SyscallArgValue,
+};
+use crate::syscalls;
+
+#[cfg(feature = "native")]
+pub struct TrussOptions {
+    pub truss_path: PathBuf,
+    // program to run under truss, if any
+    pub command: Vec<String>,
+    // already-running processes to trace (truss can only attach to one)
+    pub pids: Vec<u32>,
+    // trace the children of the command or process as well
+    pub follow: bool,
+}
+
+#[cfg(feature = "native")]
+pub fn spawn(options: &TrussOptions) -> Result<Child> {
+    let mut cmd = Command::new(&options.truss_path);
+    cmd.arg("-d");
+    if options.follow {
+        cmd.arg("-f");
+    }
+    match options.pids.as_slice() {
+        [] => {}
+        [pid] => {
+            cmd.arg("-p").arg(pid.to_string());
+        }
+        _ => return Err(anyhow!("--backend truss can only attach to one process")),
+    }
+    cmd.args(&options.command)
+        .stderr(Stdio::piped())
+        .stdout(Stdio::piped());
+    tracing::info!(command = ?cmd, "spawning truss");
+    let child = cmd
+        .spawn()
+        .map_err(|e| anyhow!("unable to spawn truss: {}", e))?;
+    tracing::info!(pid = child.id(), "truss started");
+    Ok(child)
+}
+
+/// Returns true if `line`, the first of a trace, looks like the output of truss.
+pub fn is_truss(line: &str) -> bool {
+    let (_, _, body) = split_prefix(line.trim());
+    body.starts_with("SIGNAL ")
+        || body.starts_with("process exit")
+        || split_result(body).is_some_and(|(_, result)| {
+            result.starts_with("ERR#") || result.ends_with(')') && result.contains(" (0x")
+        })
+}
+
+// splits "4120: 0.001085123 open(...) = 3 (0x3)" into its pid, time in seconds, and the rest; the
+// pid is there with -f, and the time with -d (since truss started) or -A (since the epoch)
+fn split_prefix(line: &str) -> (Option<u32>, Option<f64>, &str) {
+    let mut pid = None;
+    let mut rest = line;
+    if let Some((prefix, after)) = line.split_once(": ") {
+        // with -H, the thread id comes after the pid
+        let mut ids = prefix.split(' ');
+        if let Some(first) = ids.next().and_then(|id| id.parse().ok()) {
+            if ids.all(|id| id.parse::<u64>().is_ok()) {
+                pid = Some(first);
+                rest = after.trim_start();
+            }
+        }
+    }
+    let mut time = None;
+    if let Some((first, after)) = rest.split_once(' ') {
+        if first.contains('.') {
+            if let Ok(secs) = first.parse::<f64>() {
+                time = Some(secs);
+                rest = after.trim_start();
+            }
+        }
+    }
+    (pid, time, rest)
+}
+
+// splits "open(...) = 3 (0x3)" into the call and the result, e.g. "3 (0x3)" or "ERR#2 '...'"
+fn split_result(body: &str) -> Option<(&str, &str)> {
+    let (call, result) = match body.rfind(" ERR#") {
+        Some(i) => (&body[..i], &body[i + 1..]),
+        None => body.rsplit_once(" = ")?,
+    };
+    let call = call.trim_end();
+    (call.ends_with(')') && call.contains('(')).then_some((call, result))
+}
+
+/// Turns lines of truss's output into messages.
+#[derive(Default)]
+pub struct Parser {
+    // syscall names and `%category`s to drop
+    exclude: Vec<String>,
+    // the time since the epoch that truss started at, in microseconds
+    start_micros: Option<u64>,
+}
+
+impl Parser {
+    pub fn with_exclude(exclude: Vec<String>) -> Parser {
+        Parser {
+            exclude,
+            ..Parser::default()
+        }
+    }
+
+    // returns `None` if the line is malformed, and `Some(None)` if the syscall is excluded
+    fn parse_syscall(
+        &self,
+        pid: Option<u32>,
+        time_micros: u64,
+        body: &str,
+    ) -> Option<Option<Message>> {
+        let (call, result) = split_result(body)?;
+        let (name, args) = call.split_once('(')?;
+        let args = args.strip_suffix(')')?;
+        if strace::excludes(&self.exclude, name) {
+            return Some(None);
+        }
+        let (return_value, errno) = match result.strip_prefix("ERR#") {
+            Some(errno) => {
+                let errno = errno.split(' ').next()?.parse().ok()?;
+                (-1, Some(syscalls::bsd_errno_name(errno)))
+            }
+            None => (parse_number(result.split(' ').next()?)?, None),
+        };
+        let args = strace::split_args(args)
+            .into_iter()
+            .map(|arg| SyscallArg {
+                name: String::new(),
+                value: parse_arg(arg),
+            })
+            .collect();
+        Some(Some(Message::Syscall(Syscall {
+            pid,
+            name: name.to_string(),
+            args,
+            return_value,
+            no_return: false,
+            errno,
+            returned: None,
+            explanation: None,
+            entry_time_micros: time_micros,
+            syscall_time_micros: None,
+            injected: false,
+            error_details: None,
+        })))
+    }
+
+    // converts the time that truss printed to microseconds since the epoch
+    fn time_micros(&mut self, secs: Option<f64>) -> u64 {
+        let Some(secs) = secs else {
+            return 0;
+        };
+        let micros = (secs * 1e6) as u64;
+        // -A prints the time since the epoch, which is long after truss could have started
+        if secs > 1e9 {
+            return micros;
+        }
+        let start_micros = *self.start_micros.get_or_insert_with(|| {
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_micros() as u64);
+            now.saturating_sub(micros)
+        });
+        start_micros + micros
+    }
+}
+
+impl Parse for Parser {
+    fn parse_line(&mut self, line: &str) -> Option<Message> {
+        let line = line.trim();
+        let (pid, secs, body) = split_prefix(line);
+        if line.is_empty() || body == "<new process>" {
+            return None;
+        }
+        let time_micros = self.time_micros(secs);
+        let parsed = if let Some(signal) = body.strip_prefix("SIGNAL ") {
+            // e.g., "SIGNAL 20 (SIGCHLD) code=CLD_EXITED pid=4121 uid=1001 status=0"
+            signal
+                .split_once(" (")
+                .and_then(|(_, rest)| rest.split_once(')'))
+                .map(|(name, _)| {
+                    Some(Message::Signal(Signal {
+                        pid,
+                        time_micros,
+                        name: name.to_string(),
+                        info: Default::default(),
+                    }))
+                })
+        } else if let Some(rval) = body.strip_prefix("process exit, rval = ") {
+            rval.parse().ok().map(|code| {
+                Some(Message::Exit(Exit {
+                    pid,
+                    time_micros,
+                    status: ExitKind::Exited(code),
+                }))
+            })
+        } else if let Some(killed) = body.strip_prefix("process killed, signal = ") {
+            let (signal, core_dumped) = match killed.split_once(' ') {
+                Some((signal, rest)) => (signal, rest.contains("core dumped")),
+                None => (killed, false),
+            };
+            signal.parse().ok().map(|signal| {
+                Some(Message::Exit(Exit {
+                    pid,
+                    time_micros,
+                    status: ExitKind::Killed {
+                        signal: syscalls::bsd_signal_name(signal),
+                        core_dumped,
+                    },
+                }))
+            })
+        } else {
+            self.parse_syscall(pid, time_micros, body)
+        };
+        match parsed {
+            Some(msg) => msg,
+            // e.g., "truss: can not attach to target process: Operation not permitted"
+            None => Some(Message::Notice(line.to_string())),
+        }
+    }
+}
+
+// e.g., "3", "-1", or "0x800a00000"
+fn parse_number(text: &str) -> Option<i64> {
+    match text.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16).ok().map(|n| n as i64),
+        None => text.parse().ok(),
+    }
+}
+
+// e.g., "\"/etc/hosts\"", "O_RDONLY|O_CLOEXEC", "0x7fffffffe0a0", or "{ mode=-rw-r--r-- ,... }"
+fn parse_arg(arg: &str) -> SyscallArgValue {
+    // strings longer than -s are cut off with "..."
+    let (unquoted, truncated) = match arg.strip_suffix("...") {
+        Some(arg) => (arg, true),
+        None => (arg, false),
+    };
+    if let Some(text) = unquoted
+        .strip_prefix('"')
+        .and_then(|text| text.strip_suffix('"'))
+    {
+        return SyscallArgValue::Quoted {
+            text: text.to_string(),
+            truncated,
+        };
+    }
+    // modes are printed in octal, which would be misread as decimal
+    if !arg.starts_with('0') || arg == "0" || arg.starts_with("0x") {
+        if let Some(number) = parse_number(arg) {
+            return SyscallArgValue::Number(number);
+        }
+    }
+    if arg.contains('|') {
+        let flags = arg
+            .split('|')
+            .map(|flag| match parse_number(flag) {
+                Some(bits) => FlagSetValue::Bits(bits),
+                None => FlagSetValue::Symbol(flag.to_string()),
+            })
+            .collect();
+        return SyscallArgValue::FlagSet(flags);
+    }
+    SyscallArgValue::Symbol(arg.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::strace::{Message, Parse};
+
+    use super::{is_truss, Parser};
+
+    #[test]
+    fn test_parse() {
+        let mut p = Parser::with_exclude(vec!["close".to_string()]);
+        let lines = [
+            "4120: 0.001085123 open(\"/etc/hosts\",O_RDONLY|O_CLOEXEC,00) = 3 (0x3)",
+            "4120: 0.001101456 stat(\"/no, pe\",0x7fffffffe0a0) ERR#2 'No such file or directory'",
+            "4120: 0.001200000 close(3) = 0 (0x0)",
+            "4120: 0.001300000 write(1,\"hello wo\"...,12) = 12 (0xc)",
+            "4121: 0.001400000 <new process>",
+            "4120: 0.002000000 SIGNAL 20 (SIGCHLD) code=CLD_EXITED pid=4121 uid=1001 status=0",
+            "4120: 0.002100000 process exit, rval = 3",
+            "4121: 0.002200000 process killed, signal = 11 (core dumped)",
+        ];
+        assert!(is_truss(lines[0]));
+        assert!(is_truss(lines[1]));
+        assert!(!is_truss("4120 1720000000.000001 close(3) = 0 <0.000001>"));
+
+        let printed: Vec<String> = lines
+            .iter()
+            .filter_map(|line| p.parse_line(line))
+            .map(|mut msg| {
+                // without the times, which depend on when truss started
+                match &mut msg {
+                    Message::Syscall(syscall) => syscall.entry_time_micros = 0,
+                    Message::Signal(signal) => signal.time_micros = 0,
+                    Message::Exit(exit) => exit.time_micros = 0,
+                    _ => {}
+                }
+                msg.to_string()
+            })
+            .collect();
+        assert_eq!(
+            printed,
+            [
+                "4120 open(\"/etc/hosts\", O_RDONLY|O_CLOEXEC, 00) = 3",
+                "4120 stat(\"/no, pe\", 0x7fffffffe0a0) = -1 ENOENT",
+                "4120 write(1, \"hello wo\"..., 12) = 12",
+                "4120 --- SIGCHLD ---",
+                "4120 +++ exited with 3 +++",
+                "4121 +++ killed by SIGSEGV (core dumped) +++",
+            ]
+        );
+    }
+}
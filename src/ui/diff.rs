@@ -0,0 +1,116 @@
+use cursive::theme::{BaseColor, Color, ColorStyle};
+use cursive::utils::markup::StyledString;
+use cursive::view::{Nameable, Resizable, Scrollable};
+use cursive::views::{LinearLayout, Panel, ScrollView, TextView};
+use cursive::Cursive;
+
+use crate::diff::{render, DiffEntry};
+use crate::strace::Syscall;
+
+type DiffScroll = ScrollView<LinearLayout>;
+
+/// line numbers of the rows that differ, used to jump between differences
+struct DiffState {
+    changes: Vec<usize>,
+}
+
+/// Shows `left` and `right` side by side, aligned according to `entries`.
+pub fn diff(
+    left_title: &str,
+    right_title: &str,
+    left: &[Syscall],
+    right: &[Syscall],
+    entries: &[DiffEntry],
+) {
+    let mut siv = super::new_cursive();
+
+    let mut left_text = StyledString::new();
+    let mut right_text = StyledString::new();
+    let mut changes = Vec::new();
+    let (mut changed, mut removed, mut added) = (0, 0, 0);
+    for (line, entry) in entries.iter().enumerate() {
+        let (l, r, color) = match entry {
+            DiffEntry::Same(i, j) => (render(&left[*i]), render(&right[*j]), None),
+            DiffEntry::Changed(i, j) => {
+                changed += 1;
+                (
+                    render(&left[*i]),
+                    render(&right[*j]),
+                    Some(BaseColor::Yellow),
+                )
+            }
+            DiffEntry::Removed(i) => {
+                removed += 1;
+                (render(&left[*i]), String::new(), Some(BaseColor::Red))
+            }
+            DiffEntry::Added(j) => {
+                added += 1;
+                (String::new(), render(&right[*j]), Some(BaseColor::Green))
+            }
+        };
+
+        match color {
+            Some(color) => {
+                changes.push(line);
+                let style = ColorStyle::front(Color::Light(color));
+                left_text.append_styled(format!("{}\n", l), style);
+                right_text.append_styled(format!("{}\n", r), style);
+            }
+            None => {
+                left_text.append_plain(format!("{}\n", l));
+                right_text.append_plain(format!("{}\n", r));
+            }
+        }
+    }
+
+    let title = format!(
+        "{} changed, {} removed, {} added (n/p: next/previous difference, q: quit)",
+        changed, removed, added
+    );
+    siv.add_fullscreen_layer(
+        Panel::new(
+            LinearLayout::horizontal()
+                .child(
+                    Panel::new(TextView::new(left_text).no_wrap())
+                        .title(left_title)
+                        .full_width(),
+                )
+                .child(
+                    Panel::new(TextView::new(right_text).no_wrap())
+                        .title(right_title)
+                        .full_width(),
+                )
+                .scrollable()
+                .with_name("diff"),
+        )
+        .title(title)
+        .full_screen(),
+    );
+
+    siv.set_user_data(DiffState { changes });
+    siv.add_global_callback('q', |s| s.quit());
+    siv.add_global_callback('n', |s| jump(s, true));
+    siv.add_global_callback('p', |s| jump(s, false));
+
+    siv.run();
+}
+
+fn jump(s: &mut Cursive, forward: bool) {
+    let changes = match s.with_user_data(|state: &mut DiffState| state.changes.clone()) {
+        Some(changes) => changes,
+        None => return,
+    };
+
+    s.call_on_name("diff", |v: &mut DiffScroll| {
+        // +1 to skip the top border of the panels
+        let current = v.content_viewport().top();
+        let target = if forward {
+            changes.iter().find(|line| **line + 1 > current)
+        } else {
+            changes.iter().rev().find(|line| **line + 1 < current)
+        };
+        if let Some(line) = target {
+            v.set_offset((0, line + 1));
+        }
+    });
+}
@@ -0,0 +1,2309 @@
+use std::collections::BTreeSet;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::thread;
+
+use cursive::reexports::crossbeam_channel::Sender;
+use cursive::theme::{BaseColor, BorderStyle, Color, Effect, Palette, Style};
+use cursive::traits::With;
+use cursive::utils::markup::StyledString;
+use cursive::view::{Nameable, Resizable, ScrollStrategy, Scrollable};
+use cursive::views::{
+    BoxedView, Dialog, EditView, LinearLayout, NamedView, Panel, ScrollView, SelectView, TextView,
+};
+use cursive::{Cursive, CursiveRunnable};
+
+use crate::config;
+use crate::environment;
+use crate::errnos;
+use crate::explain;
+use crate::fdtable::FdKind;
+use crate::filter::Filter;
+use crate::groups::Key;
+use crate::heatmap::Heatmap;
+use crate::inject::{self, Injection};
+use crate::jump;
+use crate::layout::{self, Layout, Pane};
+use crate::memory;
+use crate::model::{Event, Model};
+use crate::namespaces::Container;
+use crate::palette;
+use crate::preview;
+use crate::procfs;
+use crate::rules::{Action, Rules};
+use crate::session::Session;
+use crate::snapshot;
+use crate::strace;
+use crate::summary;
+use crate::syscalls;
+use crate::table::Source;
+use crate::targets::Targets;
+use crate::units;
+use crate::usage;
+use crate::vst;
+use crate::watch::Watch;
+
+mod diff;
+mod tables;
+
+pub use diff::diff;
+
+type Callback = Box<dyn FnOnce(&mut Cursive) + Send>;
+type Sink = Sender<Callback>;
+type EventsPanel = Panel<ScrollView<NamedView<SelectView<Event>>>>;
+
+// rows of the stats pane
+const STATS_PANE_ROWS: usize = 50;
+const MEMORY_GRAPH_WIDTH: usize = 60;
+// events waiting for the interface to draw them, beyond which the interface is stalled
+const STALL_BACKLOG: usize = 10_000;
+
+pub struct Options {
+    // `None` when viewing a saved trace
+    pub strace_pid: Option<u32>,
+    // if non-empty, strace was attached to these running processes and must detach before quitting
+    pub attached_pids: Vec<u32>,
+    // when replaying a saved trace at its original pace, how fast to go
+    pub replay_speed: Option<vst::Speed>,
+    // pause at the first failed syscall that passes the filter
+    pub pause_on_error: bool,
+    // highlight security-relevant events
+    pub audit: bool,
+    // look up paths on this machine to see where symlinks lead
+    pub resolve_links: bool,
+    // the directories that processes started in, if known, with `None` for any process not listed
+    pub cwds: Vec<(Option<u32>, String)>,
+    // the containers of the processes that were attached to
+    pub containers: Vec<Container>,
+    // what the traced processes were using over time, if they are being sampled
+    pub samples: Option<usage::Samples>,
+    // from the config file
+    pub rules: Rules,
+    // whether the traced processes are on this machine, so that breakpoints can stop them
+    pub stoppable: bool,
+    // the faults that strace was started with to stop processes at breakpoints
+    pub stop_injections: Vec<Injection>,
+    // the faults being injected, if they can be changed (which restarts the trace)
+    pub injections: Option<Vec<Injection>>,
+    // the commands that were traced together, each of which gets a tab
+    pub targets: Targets,
+    // the state of the interface to start in, from the last time the trace was viewed
+    pub session: Option<Session>,
+    // where to save the state of the interface when it quits
+    pub save_session: Option<PathBuf>,
+    // the name of the layout of the panes, from `layout::all`
+    pub layout: String,
+    // explain each event next to it
+    pub explain: bool,
+    // the named filter to start with, which is in `rules`
+    pub preset: Option<String>,
+}
+
+/// Returns the new faults to inject if they were changed, in which case the trace must be
+/// restarted.
+pub fn main(rx: mpsc::Receiver<strace::Message>, options: Options) -> Option<Vec<Injection>> {
+    let mut siv = new_cursive();
+    let mut model = Model {
+        pause_on_error: options.pause_on_error,
+        audit: options.audit,
+        rules: options.rules,
+        stoppable: options.stoppable,
+        stop_injections: options.stop_injections,
+        strace_pid: options.strace_pid,
+        attached_pids: options.attached_pids.clone(),
+        injections: options.injections,
+        targets: options.targets,
+        layout: options.layout,
+        explain: options.explain,
+        ..Default::default()
+    };
+    let layout = current_layout(&model);
+    // tab 0 is every command, and tab n the nth
+    let tabs = model.targets.names.len().min(9);
+    model.symlinks.host = options.resolve_links;
+    for container in options.containers {
+        model.namespaces.add_container(container);
+    }
+    for (pid, dir) in options.cwds {
+        model.fds.set_cwd(pid, dir);
+    }
+    model.samples = options.samples;
+    let restored = options.session.map(|session| {
+        let selected = session.selected;
+        let notice = match session.restore(&mut model) {
+            Ok(()) => "Restored the last session".to_string(),
+            Err(e) => format!("Unable to fully restore the last session: {}", e),
+        };
+        (selected, notice)
+    });
+    // over the session's filter, since it was asked for
+    let preset = options.preset.as_deref();
+    if let Some(Ok(filter)) = preset
+        .and_then(|p| model.rules.named_filter(p))
+        .map(Filter::parse)
+    {
+        model.set_filter(filter);
+    }
+    siv.set_user_data(model);
+
+    siv.add_fullscreen_layer(build_layout(&layout, tabs > 1));
+    if let (Some(strace_pid), false) = (options.strace_pid, options.attached_pids.is_empty()) {
+        let attached_pids = options.attached_pids.clone();
+        siv.add_global_callback('q', move |s| {
+            show_detach_dialog(s, strace_pid, &attached_pids)
+        });
+    } else {
+        siv.add_global_callback('q', |s| s.quit());
+    }
+    siv.add_global_callback('w', show_add_watch_dialog);
+    siv.add_global_callback('W', |s| {
+        s.with_user_data(|m: &mut Model| m.watches.clear());
+        refresh_watches(s);
+    });
+    siv.add_global_callback('/', show_filter_dialog);
+    siv.add_global_callback('f', follow_selected_fd);
+    siv.add_global_callback('p', toggle_freeze);
+    siv.add_global_callback('e', toggle_pause_on_error);
+    siv.add_global_callback('r', reload_rules);
+    siv.add_global_callback('m', toggle_mark);
+    siv.add_global_callback('a', show_annotate_dialog);
+    siv.add_global_callback('s', show_snapshot_dialog);
+    siv.add_global_callback('t', show_process_tree);
+    siv.add_global_callback('F', show_files);
+    siv.add_global_callback('N', show_network);
+    siv.add_global_callback('P', show_privileges);
+    siv.add_global_callback('c', show_namespaces);
+    siv.add_global_callback('T', show_blocking);
+    siv.add_global_callback('L', show_libraries);
+    siv.add_global_callback('C', show_config_files);
+    siv.add_global_callback('X', show_postmortem);
+    siv.add_global_callback('B', show_loops);
+    siv.add_global_callback('O', show_outliers);
+    siv.add_global_callback('S', show_io_sizes);
+    siv.add_global_callback('U', show_usage);
+    siv.add_global_callback('D', show_antidebug);
+    siv.add_global_callback('K', show_futexes);
+    siv.add_global_callback('E', show_event_loops);
+    siv.add_global_callback('M', show_memory);
+    siv.add_global_callback('I', show_ipc);
+    siv.add_global_callback('R', show_commands);
+    siv.add_global_callback('H', show_heatmap);
+    siv.add_global_callback('V', next_layout);
+    siv.add_global_callback('x', toggle_exact);
+    siv.add_global_callback('i', toggle_explain);
+    siv.add_global_callback('g', show_jump_dialog);
+    let (strace_pid, attached_pids) = (options.strace_pid, options.attached_pids.clone());
+    siv.add_global_callback('J', move |s| {
+        show_inject_dialog(s, strace_pid, attached_pids.clone())
+    });
+    siv.add_global_callback('G', show_groups);
+    siv.add_global_callback(cursive::event::Event::Refresh, tables::refresh_tables);
+    if tabs > 1 {
+        for tab in 0..=tabs {
+            let key = char::from_digit(tab as u32, 10).unwrap();
+            siv.add_global_callback(key, move |s| select_tab(s, tab.checked_sub(1)));
+        }
+        refresh_tabs(&mut siv);
+    }
+    if let Some(speed) = options.replay_speed {
+        let faster = speed.clone();
+        siv.add_global_callback('>', move |s| change_replay_speed(s, &faster, 2.0));
+        siv.add_global_callback('<', move |s| change_replay_speed(s, &speed, 0.5));
+    }
+
+    siv.set_fps(10);
+    refresh_title(&mut siv);
+
+    // the selection is restored once the whole trace has been loaded
+    let mut loaded = None;
+    if let Some((selected, notice)) = restored {
+        refresh_panes(&mut siv);
+        siv.call_on_name("status", |t: &mut TextView| t.set_content(notice));
+        if let Some(index) = selected {
+            loaded = Some(Box::new(move |s: &mut Cursive| select_event(s, index)) as Callback);
+        }
+    }
+    let sink = siv.cb_sink().clone();
+    let handle = thread::spawn(move || {
+        read_messages(rx, sink, loaded);
+    });
+
+    siv.run();
+
+    if let Some(path) = options.save_session {
+        let selected = selected_event(&mut siv);
+        let saved = siv
+            .with_user_data(|m: &mut Model| Session::of(m, selected).save(&path))
+            .unwrap_or(Ok(()));
+        if let Err(e) = saved {
+            eprintln!("vistrace: unable to save the session: {}", e);
+        }
+    }
+    // so that they are not left stopped after vistrace detaches
+    resume_stopped(&mut siv);
+    handle.join().unwrap();
+    siv.with_user_data(|m: &mut Model| m.restart.take())
+        .flatten()
+}
+
+fn new_cursive() -> CursiveRunnable {
+    let mut siv = cursive::default();
+
+    // from https://github.com/gyscos/cursive/blob/cursive-v0.20.0/cursive/examples/theme_manual.rs
+    siv.set_theme(cursive::theme::Theme {
+        shadow: true,
+        borders: BorderStyle::Simple,
+        palette: Palette::default().with(|palette| {
+            use cursive::theme::BaseColor::*;
+            use cursive::theme::Color::*;
+            use cursive::theme::PaletteColor::*;
+
+            palette[Background] = TerminalDefault;
+            palette[View] = TerminalDefault;
+            palette[Primary] = White.dark();
+            palette[TitlePrimary] = Blue.light();
+            palette[Secondary] = Blue.light();
+            palette[Highlight] = Blue.dark();
+        }),
+    });
+    siv
+}
+
+// `loaded` is called once the trace is over.
+fn read_messages(rx: mpsc::Receiver<strace::Message>, sink: Sink, loaded: Option<Callback>) {
+    // logged each time the backlog doubles, so that a long stall is not logged for every event
+    let mut stall_threshold = STALL_BACKLOG;
+    let mut dropping = false;
+    for msg in rx.iter() {
+        let backlog = sink.len();
+        if backlog >= stall_threshold {
+            tracing::warn!(backlog, "interface is falling behind the trace");
+            stall_threshold *= 2;
+        } else if backlog < STALL_BACKLOG / 2 {
+            stall_threshold = STALL_BACKLOG;
+        }
+
+        let callback: Callback = match msg {
+            strace::Message::Syscall(syscall) => Box::new(|s: &mut Cursive| {
+                let (item, resolved) = s
+                    .with_user_data(|m: &mut Model| {
+                        let n = m.resolutions.len();
+                        let item = m.push(syscall).map(|i| (event_label(m, i), i));
+                        let resolved = m.resolutions[n..].last().map(|(_, r)| r.to_string());
+                        (item, resolved)
+                    })
+                    .unwrap_or_default();
+                if let Some((label, index)) = item {
+                    s.call_on_name("events", |v: &mut SelectView<Event>| {
+                        v.add_item(label, Event::Syscall(index));
+                    });
+                }
+                // e.g., "resolved example.com → 93.184.216.34"
+                if let Some(resolved) = resolved {
+                    s.call_on_name("status", |t: &mut TextView| t.set_content(resolved));
+                }
+                show_paused_on(s);
+                show_new_detection(s);
+                show_alerts(s);
+                if is_frozen(s) {
+                    refresh_title(s);
+                } else {
+                    refresh_panes(s);
+                }
+            }),
+            strace::Message::Signal(signal) => Box::new(move |s: &mut Cursive| {
+                let notice = match signal.pid {
+                    Some(pid) => {
+                        format!("Process {} received {}", process_label(s, pid), signal.name)
+                    }
+                    None => format!("Received {}", signal.name),
+                };
+                s.with_user_data(|m: &mut Model| m.record_signal(&signal));
+                s.call_on_name("status", |t: &mut TextView| t.set_content(notice));
+            }),
+            strace::Message::Exit(exit) => Box::new(move |s: &mut Cursive| {
+                let notice = match exit.pid {
+                    Some(pid) => format!("Process {} {}", process_label(s, pid), exit.status),
+                    None => format!("Process {}", exit.status),
+                };
+                let failed = s
+                    .with_user_data(|m: &mut Model| m.record_exit(exit))
+                    .unwrap_or_default();
+                s.call_on_name("status", |t: &mut TextView| t.set_content(notice));
+                if failed {
+                    show_postmortem(s);
+                }
+            }),
+            strace::Message::LibCall(call) => Box::new(|s: &mut Cursive| {
+                let item = s
+                    .with_user_data(|m: &mut Model| {
+                        m.push_libcall(call).map(|i| (libcall_label(m, i), i))
+                    })
+                    .flatten();
+                if let Some((label, index)) = item {
+                    s.call_on_name("events", |v: &mut SelectView<Event>| {
+                        v.add_item(label, Event::LibCall(index));
+                    });
+                }
+                if is_frozen(s) {
+                    refresh_title(s);
+                }
+            }),
+            strace::Message::Skipped(skipped) => Box::new(move |s: &mut Cursive| {
+                s.with_user_data(|m: &mut Model| m.skipped += skipped.calls);
+                refresh_title(s);
+            }),
+            strace::Message::Notice(notice) => status_callback(notice),
+            strace::Message::Frame(_) => continue,
+        };
+
+        // fails once the interface has quit, while strace is still being stopped
+        if sink.send(callback).is_err() && !dropping {
+            tracing::info!("interface has quit; dropping the rest of the trace");
+            dropping = true;
+        }
+    }
+    if let Some(callback) = loaded {
+        let _ = sink.send(callback);
+    }
+}
+
+fn process_label(s: &mut Cursive, pid: u32) -> String {
+    s.with_user_data(|m: &mut Model| m.processes.label(pid))
+        .unwrap_or_else(|| pid.to_string())
+}
+
+fn status_callback(text: String) -> Callback {
+    Box::new(|s: &mut Cursive| {
+        s.call_on_name("status", |t: &mut TextView| t.set_content(text));
+    })
+}
+
+fn change_replay_speed(s: &mut Cursive, speed: &vst::Speed, factor: f64) {
+    let new_speed = {
+        let mut speed = speed.lock().unwrap();
+        *speed = (*speed * factor).clamp(1.0 / 64.0, 64.0);
+        *speed
+    };
+    s.call_on_name("status", |t: &mut TextView| {
+        t.set_content(format!("Replay speed: {}x", new_speed))
+    });
+}
+
+fn show_detach_dialog(s: &mut Cursive, strace_pid: u32, attached_pids: &[u32]) {
+    let pids = attached_pids
+        .iter()
+        .map(|p| p.to_string())
+        .collect::<Vec<_>>()
+        .join(", ");
+    s.add_layer(
+        Dialog::text(format!("Detach from {} and quit?", pids))
+            .title("quit")
+            .button("Detach", move |s| {
+                strace::detach(strace_pid);
+                s.quit();
+            })
+            .dismiss_button("Cancel"),
+    );
+}
+
+fn show_add_watch_dialog(s: &mut Cursive) {
+    s.add_layer(
+        Dialog::new()
+            .title("add watch (e.g., 'count openat ENOENT', 'last read(fd=7)')")
+            .content(
+                EditView::new()
+                    .on_submit(|s, text| {
+                        s.pop_layer();
+                        add_watch(s, text);
+                    })
+                    .with_name("watch_input")
+                    .min_width(40),
+            )
+            .button("Cancel", |s| {
+                s.pop_layer();
+            }),
+    );
+}
+
+fn add_watch(s: &mut Cursive, text: &str) {
+    match Watch::parse(text) {
+        Ok(watch) => {
+            s.with_user_data(|m: &mut Model| m.add_watch(watch));
+            refresh_watches(s);
+        }
+        Err(e) => {
+            s.add_layer(Dialog::info(format!("invalid watch: {}", e)));
+        }
+    }
+}
+
+fn refresh_watches(s: &mut Cursive) {
+    let text = s
+        .with_user_data(|m: &mut Model| {
+            m.watches
+                .iter()
+                .map(|w| format!("{}\n  = {}\n", w.text, w.value()))
+                .collect::<String>()
+        })
+        .unwrap_or_default();
+    s.call_on_name("watches", |t: &mut TextView| t.set_content(text));
+}
+
+// the watches, output, and stats panes, whichever the layout has
+fn refresh_panes(s: &mut Cursive) {
+    refresh_watches(s);
+    if s.debug_name("output").is_some() {
+        let text = s
+            .with_user_data(|m: &mut Model| {
+                let mut text = StyledString::new();
+                for (stderr, line) in m.console.lines() {
+                    if stderr {
+                        text.append_styled(line, Color::Dark(BaseColor::Red));
+                    } else {
+                        text.append_plain(line);
+                    }
+                    text.append_plain("\n");
+                }
+                text
+            })
+            .unwrap_or_default();
+        s.call_on_name("output", |t: &mut TextView| t.set_content(text));
+    }
+    if s.debug_name("stats").is_some() {
+        let text = s
+            .with_user_data(|m: &mut Model| {
+                let mut text = format!(
+                    "{:<16} {:>7} {:>6} {:>9}\n",
+                    "syscall", "calls", "errors", "time"
+                );
+                for (name, stats) in summary::rank(&m.stats).into_iter().take(STATS_PANE_ROWS) {
+                    text.push_str(&format!(
+                        "{:<16} {:>7} {:>6} {:>9}\n",
+                        name,
+                        units::count(stats.calls as u64),
+                        units::count(stats.errors as u64),
+                        units::duration(stats.total_micros)
+                    ));
+                }
+                text
+            })
+            .unwrap_or_default();
+        s.call_on_name("stats", |t: &mut TextView| t.set_content(text));
+    }
+}
+
+// The panes of `layout`, with a row of tabs above the events if `tabs`.
+fn build_layout(layout: &Layout, tabs: bool) -> LinearLayout {
+    let mut columns = LinearLayout::horizontal();
+    for column in &layout.columns {
+        let mut panes = LinearLayout::vertical();
+        for (pane, height) in &column.panes {
+            if *pane == Pane::Events && tabs {
+                panes.add_child(TextView::new("").with_name("tabs"));
+            }
+            let view = pane_view(*pane);
+            match height {
+                Some(height) => panes.add_child(view.fixed_height(*height)),
+                None => panes.add_child(view.full_screen()),
+            }
+            if *pane == Pane::Events {
+                panes.add_child(TextView::new("").with_name("status"));
+            }
+        }
+        match column.width {
+            Some(width) => columns.add_child(panes.fixed_width(width).full_height()),
+            None => columns.add_child(panes.full_screen()),
+        }
+    }
+    columns
+}
+
+fn pane_view(pane: Pane) -> BoxedView {
+    match pane {
+        Pane::Events => BoxedView::boxed(
+            Panel::new(
+                SelectView::<Event>::new()
+                    .on_select(show_detail)
+                    .with_name("events")
+                    .scrollable()
+                    // long events scroll sideways, rather than crowding out the other columns
+                    .scroll_x(true)
+                    .scroll_strategy(ScrollStrategy::StickToBottom),
+            )
+            .title("events")
+            .with_name("events_panel"),
+        ),
+        Pane::Detail => BoxedView::boxed(
+            Panel::new(TextView::new("").with_name("detail").scrollable()).title("detail"),
+        ),
+        Pane::Watches => {
+            BoxedView::boxed(Panel::new(TextView::new("").with_name("watches")).title("watches"))
+        }
+        Pane::Output => BoxedView::boxed(
+            Panel::new(
+                TextView::new("")
+                    .with_name("output")
+                    .scrollable()
+                    .scroll_strategy(ScrollStrategy::StickToBottom),
+            )
+            .title("output"),
+        ),
+        Pane::Stats => BoxedView::boxed(
+            Panel::new(TextView::new("").with_name("stats").scrollable()).title("stats"),
+        ),
+    }
+}
+
+// the layout that the model names, or the default one if there is no such layout (e.g., since
+// the config file was reloaded)
+fn current_layout(m: &Model) -> Layout {
+    let mut layouts = layout::all(&m.rules.layouts);
+    let i = layouts.iter().position(|l| l.name == m.layout).unwrap_or(0);
+    layouts.swap_remove(i)
+}
+
+// Switches to the next layout, keeping the selection.
+fn next_layout(s: &mut Cursive) {
+    // the layout is the bottom layer, under any dialogs
+    if s.screen().len() > 1 {
+        return;
+    }
+    let selected = selected_event(s);
+    let next = s.with_user_data(|m: &mut Model| {
+        let layouts = layout::all(&m.rules.layouts);
+        let i = layouts.iter().position(|l| l.name == m.layout);
+        let next = layouts[i.map_or(0, |i| (i + 1) % layouts.len())].clone();
+        m.layout = next.name.clone();
+        (next, m.targets.names.len().min(9) > 1, m.tab)
+    });
+    let Some((layout, tabs, tab)) = next else {
+        return;
+    };
+    s.pop_layer();
+    s.add_fullscreen_layer(build_layout(&layout, tabs));
+    redraw(s, tab, selected);
+    s.call_on_name("status", |t: &mut TextView| {
+        t.set_content(format!("Layout: {}", layout.name))
+    });
+}
+
+// Switches between human units and exact values.
+fn toggle_exact(s: &mut Cursive) {
+    units::set_exact(!units::exact());
+    let selected = selected_event(s);
+    let tab = s.with_user_data(|m: &mut Model| m.tab).flatten();
+    redraw(s, tab, selected);
+    let status = if units::exact() {
+        "Showing exact values"
+    } else {
+        "Showing human units"
+    };
+    s.call_on_name("status", |t: &mut TextView| t.set_content(status));
+}
+
+// Shows or hides the explanation next to each event.
+fn toggle_explain(s: &mut Cursive) {
+    let selected = selected_event(s);
+    let (tab, explain) = s
+        .with_user_data(|m: &mut Model| {
+            m.explain = !m.explain;
+            (m.tab, m.explain)
+        })
+        .unwrap_or_default();
+    redraw(s, tab, selected);
+    let status = if explain {
+        "Explaining each event"
+    } else {
+        "Explaining only the selected event"
+    };
+    s.call_on_name("status", |t: &mut TextView| t.set_content(status));
+}
+
+// Fills in the events of `tab` and the panes again, and selects the event that was selected.
+fn redraw(s: &mut Cursive, tab: Option<usize>, selected: Option<usize>) {
+    select_tab(s, tab);
+    refresh_title(s);
+    refresh_panes(s);
+    if let Some(index) = selected {
+        select_event(s, index);
+    }
+}
+
+// The syscall as strace printed it, but with how long it took in human units, unless they are
+// exact.
+fn syscall_text(syscall: &strace::Syscall) -> String {
+    let text = syscall.to_string();
+    let micros = match syscall.syscall_time_micros {
+        Some(micros) if !units::exact() => micros,
+        _ => return text,
+    };
+    let exact = format!(" <{}>", strace::format_timestamp(micros));
+    match text.strip_suffix(&exact) {
+        Some(text) => format!("{} <{}>", text, units::duration(micros)),
+        None => text,
+    }
+}
+
+fn row_label(m: &Model, event: Event) -> StyledString {
+    match event {
+        Event::Syscall(index) => event_label(m, index),
+        Event::LibCall(index) => libcall_label(m, index),
+    }
+}
+
+fn event_label(m: &Model, index: usize) -> StyledString {
+    let syscall = &m.syscalls[index];
+    let text = with_target(m, syscall.pid, syscall_text(syscall));
+    let mut label = match (m.marks.get(&index), m.findings.contains_key(&index)) {
+        (Some(_), _) => format!("* {}", text),
+        (None, true) => format!("! {}", text),
+        (None, false) => text,
+    };
+    if let Some(details) = &syscall.error_details {
+        label.push_str(&format!("  (parse error: {})", details.message));
+    }
+    if let Some(note) = m.marks.get(&index).filter(|n| !n.is_empty()) {
+        label.push_str(&format!("  # {}", note));
+    }
+    if let Some(description) = m.event_fd_syscalls.get(&index) {
+        label.push_str(&format!("  ({})", description));
+    }
+    if let Some(explanation) = m.explain.then(|| explain_syscall(m, syscall)).flatten() {
+        label.push_str(&format!("  — {}", explanation));
+    }
+    if let Some(outlier) = m.outlier_syscalls.get(&index) {
+        let outlier = &m.outliers.found[*outlier];
+        label.push_str(&format!(
+            "  (slow: typically {})",
+            units::duration(outlier.typical_micros)
+        ));
+    }
+    if m.findings.contains_key(&index) {
+        let style = Style::from(Color::Dark(BaseColor::Red)).combine(Effect::Reverse);
+        return StyledString::styled(label, style);
+    }
+    if syscall.injected {
+        let style = Style::from(Color::Dark(BaseColor::Magenta)).combine(Effect::Reverse);
+        return StyledString::styled(label, style);
+    }
+    if m.highlighted.contains(&index) {
+        let style = Style::from(Color::Dark(BaseColor::Yellow)).combine(Effect::Reverse);
+        return StyledString::styled(label, style);
+    }
+    match palette::syscall_color(syscall) {
+        Some(color) => StyledString::styled(label, cursive_color(color)),
+        None => StyledString::plain(label),
+    }
+}
+
+// explains `syscall` in plain English, describing its fds as they were when it was made
+fn explain_syscall(m: &Model, syscall: &strace::Syscall) -> Option<String> {
+    explain::explain(syscall, |fd| {
+        let info = m.fds.resolve(syscall.pid, fd, syscall.entry_time_micros);
+        (info.kind != FdKind::Unknown).then(|| info.kind.to_string())
+    })
+}
+
+// library calls are dimmed, so that the syscalls among them stand out
+fn libcall_label(m: &Model, index: usize) -> StyledString {
+    let call = &m.libcalls[index].1;
+    let text = with_target(m, call.pid, call.to_string());
+    StyledString::styled(text, Color::Light(BaseColor::Black))
+}
+
+// prefixes `text` with the command that process `pid` belongs to, when several were traced together
+fn with_target(m: &Model, pid: Option<u32>, text: String) -> String {
+    if m.targets.names.len() > 1 {
+        if let Some(target) = pid.and_then(|pid| m.targets.target_of(pid, &m.processes)) {
+            return format!("[{}] {}", m.targets.names[target], text);
+        }
+    }
+    text
+}
+
+fn cursive_color(color: palette::Color) -> Color {
+    match color {
+        palette::Color::Red => Color::Dark(BaseColor::Red),
+        palette::Color::Green => Color::Dark(BaseColor::Green),
+        palette::Color::Yellow => Color::Dark(BaseColor::Yellow),
+        palette::Color::Magenta => Color::Dark(BaseColor::Magenta),
+        palette::Color::Cyan => Color::Dark(BaseColor::Cyan),
+        palette::Color::Gray => Color::Light(BaseColor::Black),
+    }
+}
+
+fn show_detail(s: &mut Cursive, event: &Event) {
+    let index = match event {
+        Event::Syscall(index) => index,
+        Event::LibCall(index) => return show_libcall_detail(s, *index),
+    };
+    let text = s
+        .with_user_data(|m: &mut Model| {
+            let syscall = &m.syscalls[*index];
+            let mut text = format!("#{} {}", index, syscall.name);
+            if let Some(pid) = syscall.pid {
+                text.push_str(&format!(" in process {}", m.processes.label(pid)));
+            }
+            text.push('\n');
+            // e.g., "opened /etc/hosts read-only as fd 3"
+            if let Some(explanation) = explain_syscall(m, syscall) {
+                text.push_str(&format!("{}\n", explanation));
+            }
+            let fd_indices = syscalls::fd_arg_indices(&syscall.name);
+            for (i, arg) in syscall.args.iter().enumerate() {
+                let name = syscalls::arg_names(&syscall.name)
+                    .and_then(|names| names.get(i))
+                    .copied()
+                    .unwrap_or(&arg.name);
+                match &arg.value {
+                    // e.g., "count: 4.0 KiB"
+                    strace::SyscallArgValue::Number(n)
+                        if *n >= 0 && syscalls::is_size_arg(name) =>
+                    {
+                        text.push_str(&format!("  {}: {}", name, units::bytes(*n as u64)));
+                    }
+                    value => text.push_str(&format!("  {}: {}", name, value)),
+                }
+                // e.g., "fd: 3 (/etc/hosts)"
+                if let (true, strace::SyscallArgValue::Number(fd)) =
+                    (fd_indices.contains(&i), &arg.value)
+                {
+                    let info = m.fds.resolve(syscall.pid, *fd, syscall.entry_time_micros);
+                    if info.kind != FdKind::Unknown {
+                        text.push_str(&format!(" ({})", info.kind));
+                    }
+                }
+                // e.g., "pathname: "/etc/resolv.conf" (→ /run/systemd/resolve/stub-resolv.conf)"
+                if let (true, strace::SyscallArgValue::Quoted { text: path, .. }) =
+                    (syscalls::is_path_arg(name), &arg.value)
+                {
+                    if let Some(target) = m.symlinks.resolve(path) {
+                        text.push_str(&format!(" (→ {})", target));
+                    }
+                    if let Some(host) = m.namespaces.host_path(syscall.pid, path) {
+                        text.push_str(&format!(" (on this machine: {})", host));
+                    }
+                }
+                text.push('\n');
+            }
+            let moved = (syscall.errno.is_none() && syscall.return_value >= 0)
+                .then_some(syscall.return_value as u64)
+                .filter(|_| syscalls::returns_bytes(&syscall.name));
+            match moved {
+                Some(bytes) => text.push_str(&format!("  return: {}", units::bytes(bytes))),
+                None => text.push_str(&format!("  return: {}", syscall.return_value)),
+            }
+            if let Some(errno) = &syscall.errno {
+                text.push_str(&format!(" {}", errno));
+            }
+            if syscall.injected {
+                text.push_str(" (injected by strace)");
+            }
+            text.push('\n');
+            // e.g., "error: ENOENT (2), No such file or directory"
+            if let Some(errno) = &syscall.errno {
+                match errnos::lookup(errno) {
+                    Some((number, description)) => text.push_str(&format!(
+                        "  error: {} ({}), {}\n",
+                        errno, number, description
+                    )),
+                    None => text.push_str(&format!("  error: {}\n", errno)),
+                }
+                if let Some(hint) = errnos::hint(&syscall.name, errno) {
+                    text.push_str(&format!("  hint: {}\n", hint));
+                }
+            }
+            if let Some(micros) = syscall.syscall_time_micros {
+                text.push_str(&format!("  took: {}", units::duration(micros)));
+                // e.g., "took: 3.4 ms (1.2 MB/s)"
+                if let Some(bytes) = moved.filter(|bytes| *bytes > 0) {
+                    text.push_str(&format!(" ({})", units::rate(bytes, micros)));
+                }
+                text.push('\n');
+            }
+            if let Some(details) = &syscall.error_details {
+                text.push_str(&format!("parse error: {}\n", details.message));
+            }
+            if let Some(preview) = preview::of_syscall(syscall) {
+                text.push_str(&format!("content: {}\n", preview.describe()));
+                for line in preview.text.lines() {
+                    text.push_str(&format!("  {}\n", line));
+                }
+            }
+            for (_, resolution) in m.resolutions.iter().filter(|(i, _)| i == index) {
+                text.push_str(&format!("{}\n", resolution));
+            }
+            if let Some(description) = m.event_fd_syscalls.get(index) {
+                text.push_str(&format!("event fd: {}\n", description));
+            }
+            if let Some(exchange) = m.http_syscalls.get(index) {
+                text.push_str(&format!("http: {}\n", m.http.exchanges[*exchange]));
+            }
+            if let Some(search) = m.search_syscalls.get(index) {
+                let search = &m.searches.searches[*search];
+                if search.is_interesting() {
+                    text.push_str(&format!("search: {}", search));
+                    text.push_str(&format!(" ({})\n", search.misses.join(", ")));
+                }
+            }
+            for finding in m.findings.get(index).into_iter().flatten() {
+                text.push_str(&format!("audit: {}\n", finding));
+            }
+            if let Some(outlier) = m.outlier_syscalls.get(index) {
+                let outlier = &m.outliers.found[*outlier];
+                text.push_str(&format!(
+                    "outlier: {}x as long as usual for {} ({})\n",
+                    outlier.micros / outlier.typical_micros.max(1),
+                    syscall.name,
+                    units::duration(outlier.typical_micros)
+                ));
+            }
+            text
+        })
+        .unwrap_or_default();
+    s.call_on_name("detail", |t: &mut TextView| t.set_content(text));
+}
+
+fn show_libcall_detail(s: &mut Cursive, index: usize) {
+    let text = s
+        .with_user_data(|m: &mut Model| {
+            let call = &m.libcalls[index].1;
+            let mut text = format!("library call {}", call.name);
+            if let Some(caller) = &call.caller {
+                text.push_str(&format!(" from {}", caller));
+            }
+            if let Some(pid) = call.pid {
+                text.push_str(&format!(" in process {}", m.processes.label(pid)));
+            }
+            text.push_str(&format!("\n  args: {}\n", call.args));
+            text.push_str(&format!("  return: {}\n", call.return_value));
+            if call.duration_micros != 0 {
+                text.push_str(&format!(
+                    "  took: {}\n",
+                    units::duration(call.duration_micros)
+                ));
+            }
+            text
+        })
+        .unwrap_or_default();
+    s.call_on_name("detail", |t: &mut TextView| t.set_content(text));
+}
+
+fn show_process_tree(s: &mut Cursive) {
+    let rows = s
+        .with_user_data(|m: &mut Model| {
+            let mut rows = Vec::new();
+            for root in m.processes.roots() {
+                add_process_rows(m, root, 0, &mut rows);
+            }
+            rows
+        })
+        .unwrap_or_default();
+    if rows.is_empty() {
+        s.add_layer(Dialog::info("No processes yet. (Is --no-follow set?)"));
+        return;
+    }
+
+    let mut tree = SelectView::new();
+    tree.add_all(rows);
+    tree.set_on_submit(|s, pid: &u32| {
+        s.pop_layer();
+        match Filter::parse(&format!("pid={}", pid)) {
+            Ok(filter) => set_filter(s, filter),
+            Err(e) => s.add_layer(Dialog::info(format!("invalid filter: {}", e))),
+        }
+    });
+    s.add_layer(
+        Dialog::around(tree.scrollable())
+            .title("processes (enter: show only this process)")
+            .dismiss_button("Close"),
+    );
+}
+
+fn show_files(s: &mut Cursive) {
+    let empty = s
+        .with_user_data(|m: &mut Model| m.fds.file_bytes().is_empty())
+        .unwrap_or(true);
+    if empty {
+        s.add_layer(Dialog::info("No files opened yet."));
+    } else {
+        tables::show_table(s, Source::Files, String::new(), None);
+    }
+}
+
+fn show_network(s: &mut Cursive) {
+    let found = s
+        .with_user_data(|m: &mut Model| {
+            if m.net.connections.is_empty()
+                && m.resolutions.is_empty()
+                && m.http.exchanges.is_empty()
+            {
+                return None;
+            }
+
+            let mut text = String::new();
+            if !m.resolutions.is_empty() {
+                text.push_str("\nname resolution\n");
+            }
+            for (_, resolution) in &m.resolutions {
+                text.push_str("  ");
+                if let Some(pid) = resolution.pid {
+                    text.push_str(&format!("{}: ", m.processes.label(pid)));
+                }
+                text.push_str(&format!("{}\n", resolution));
+            }
+            if !m.http.exchanges.is_empty() {
+                text.push_str("\nhttp\n");
+            }
+            for exchange in &m.http.exchanges {
+                text.push_str("  ");
+                if let Some(pid) = exchange.pid {
+                    text.push_str(&format!("{}: ", m.processes.label(pid)));
+                }
+                text.push_str(&exchange.to_string());
+                match &m.net.connections[exchange.connection].remote {
+                    Some(remote) => text.push_str(&format!(" ({})\n", remote)),
+                    None => text.push('\n'),
+                }
+            }
+            Some((m.net.connections.is_empty(), text.trim_start().to_string()))
+        })
+        .flatten();
+    match found {
+        Some((false, text)) => {
+            let extra = Some(text).filter(|text| !text.is_empty());
+            tables::show_table(s, Source::Connections, String::new(), extra);
+        }
+        Some((true, text)) => s.add_layer(
+            Dialog::around(TextView::new(text).scrollable())
+                .title("network connections")
+                .dismiss_button("Close"),
+        ),
+        None => s.add_layer(Dialog::info("No sockets opened yet.")),
+    }
+}
+
+fn show_privileges(s: &mut Cursive) {
+    let text = s
+        .with_user_data(|m: &mut Model| {
+            let mut text = StyledString::new();
+            for (pid, changes) in &m.privileges.changes {
+                text.append_plain(format!("{}\n", m.processes.label(*pid)));
+                for change in changes {
+                    let line = format!("  {}\n", change);
+                    if change.failed_drop() {
+                        text.append_styled(line, Color::Dark(BaseColor::Red));
+                    } else {
+                        text.append_plain(line);
+                    }
+                }
+            }
+            text
+        })
+        .unwrap_or_default();
+    if text.is_empty() {
+        s.add_layer(Dialog::info("No privilege changes yet."));
+        return;
+    }
+    s.add_layer(
+        Dialog::around(TextView::new(text).scrollable())
+            .title("privilege timeline")
+            .dismiss_button("Close"),
+    );
+}
+
+fn show_namespaces(s: &mut Cursive) {
+    let text = s
+        .with_user_data(|m: &mut Model| {
+            let mut text = String::new();
+            for container in m.namespaces.containers.values() {
+                text.push_str(&format!("{}: {}\n", container.pid, container));
+            }
+            for (pid, changes) in &m.namespaces.changes {
+                text.push_str(&format!("{}\n", m.processes.label(*pid)));
+                for change in changes {
+                    text.push_str(&format!("  {}\n", change));
+                }
+            }
+            text
+        })
+        .unwrap_or_default();
+    if text.is_empty() {
+        s.add_layer(Dialog::info("No containers or namespace changes yet."));
+        return;
+    }
+    s.add_layer(
+        Dialog::around(TextView::new(text).scrollable())
+            .title("namespaces")
+            .dismiss_button("Close"),
+    );
+}
+
+fn show_blocking(s: &mut Cursive) {
+    let text = s
+        .with_user_data(|m: &mut Model| {
+            let mut text = String::new();
+            for (pid, time) in &m.blocking.processes {
+                if time.total_micros() == 0 {
+                    continue;
+                }
+                text.push_str(&format!("{}\n  {}\n", m.processes.label(*pid), time));
+                if let Some(syscalls) = time.mostly_in() {
+                    text.push_str(&format!("  mostly in {}\n", syscalls));
+                }
+            }
+            text
+        })
+        .unwrap_or_default();
+    if text.is_empty() {
+        s.add_layer(Dialog::info(
+            "No timed syscalls yet (this needs strace's timestamps).",
+        ));
+        return;
+    }
+    s.add_layer(
+        Dialog::around(TextView::new(text).scrollable())
+            .title("blocked vs running")
+            .dismiss_button("Close"),
+    );
+}
+
+fn show_futexes(s: &mut Cursive) {
+    let text = s
+        .with_user_data(|m: &mut Model| {
+            let contended = m.futexes.contended();
+            if contended.is_empty() {
+                return None;
+            }
+
+            let labels = |pids: &BTreeSet<u32>| {
+                let labels: Vec<String> = pids.iter().map(|p| m.processes.label(*p)).collect();
+                labels.join(", ")
+            };
+            let mut text = format!("{:>9} {:>11} {:>9} futex\n", "waits", "time", "wakes");
+            for (address, stats) in contended {
+                text.push_str(&format!(
+                    "{:>9} {:>11} {:>9} {:#x}\n{:>32}waiters: {}\n{:>32}wakers: {}\n",
+                    stats.waits,
+                    units::duration(stats.wait_micros),
+                    stats.wakes,
+                    address,
+                    "",
+                    labels(&stats.waiters),
+                    "",
+                    labels(&stats.wakers)
+                ));
+            }
+            Some(text)
+        })
+        .flatten();
+    match text {
+        Some(text) => s.add_layer(
+            Dialog::around(TextView::new(text).scrollable())
+                .title("futex contention")
+                .dismiss_button("Close"),
+        ),
+        None => s.add_layer(Dialog::info("No futexes shared between threads yet.")),
+    }
+}
+
+fn show_commands(s: &mut Cursive) {
+    let rows = s
+        .with_user_data(|m: &mut Model| {
+            m.commands
+                .tree()
+                .into_iter()
+                .map(|(depth, index)| {
+                    let command = &m.commands.commands[index];
+                    (format!("{}{}", "  ".repeat(depth), command), index)
+                })
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    if rows.is_empty() {
+        s.add_layer(Dialog::info("No programs have been run yet."));
+        return;
+    }
+
+    let mut tree = SelectView::new();
+    tree.add_all(rows);
+    tree.set_on_submit(show_environment);
+    s.add_layer(
+        Dialog::around(tree.scrollable())
+            .title("commands run (enter: compare environment with your shell)")
+            .dismiss_button("Close"),
+    );
+}
+
+fn show_environment(s: &mut Cursive, index: &usize) {
+    let text = s
+        .with_user_data(|m: &mut Model| {
+            let command = &m.commands.commands[*index];
+            let envp = command.env.as_ref()?;
+            let changes =
+                environment::diff(&environment::current(), &environment::from_trace(envp));
+            let mut text = String::new();
+            for change in changes {
+                text.push_str(&format!("{}\n", change));
+            }
+            Some(text)
+        })
+        .flatten();
+    match text {
+        Some(text) if text.is_empty() => s.add_layer(Dialog::info("Same as your shell's.")),
+        Some(text) => s.add_layer(
+            Dialog::around(TextView::new(text).scrollable())
+                .title("environment (- your shell, + the command)")
+                .dismiss_button("Close"),
+        ),
+        None => s.add_layer(Dialog::info(
+            "strace did not print this command's environment. (Use --strace-arg=-v to record it.)",
+        )),
+    }
+}
+
+fn show_ipc(s: &mut Cursive) {
+    let text = s
+        .with_user_data(|m: &mut Model| {
+            if m.ipc.objects.is_empty() {
+                return None;
+            }
+
+            let mut text = String::new();
+            for object in &m.ipc.objects {
+                text.push_str(&format!("{}\n", object));
+                if let Some(creator) = object.creator {
+                    text.push_str(&format!("  created by {}\n", m.processes.label(creator)));
+                }
+                let users: Vec<String> =
+                    object.users.iter().map(|p| m.processes.label(*p)).collect();
+                text.push_str(&format!("  used by {}\n", users.join(", ")));
+            }
+            Some(text)
+        })
+        .flatten();
+    match text {
+        Some(text) => s.add_layer(
+            Dialog::around(TextView::new(text).scrollable())
+                .title("ipc objects")
+                .dismiss_button("Close"),
+        ),
+        None => s.add_layer(Dialog::info(
+            "No shared memory, semaphores, or message queues yet.",
+        )),
+    }
+}
+
+fn show_memory(s: &mut Cursive) {
+    let text = s
+        .with_user_data(|m: &mut Model| {
+            if m.memory.history.is_empty() {
+                return None;
+            }
+
+            let peak = m.memory.history.iter().map(|(_, bytes)| *bytes).max();
+            let mut text = format!(
+                "mapped over time (peak {})\n{}\n",
+                units::bytes(peak.unwrap_or(0)),
+                memory::graph(&m.memory.history, MEMORY_GRAPH_WIDTH)
+            );
+            for (owner, space) in &m.memory.spaces {
+                if space.mappings.is_empty() {
+                    continue;
+                }
+                text.push('\n');
+                if let Some(pid) = owner {
+                    text.push_str(&format!("{}: ", m.processes.label(*pid)));
+                }
+                text.push_str(&format!("{} mapped\n", units::bytes(space.mapped)));
+                for mapping in space.mappings.values() {
+                    text.push_str(&format!("  {}\n", mapping));
+                }
+            }
+            Some(text)
+        })
+        .flatten();
+    match text {
+        Some(text) => s.add_layer(
+            Dialog::around(TextView::new(text).scrollable())
+                .title("memory")
+                .dismiss_button("Close"),
+        ),
+        None => s.add_layer(Dialog::info("No memory mapped yet.")),
+    }
+}
+
+fn show_usage(s: &mut Cursive) {
+    let text = s
+        .with_user_data(|m: &mut Model| {
+            let samples = m.samples.as_ref()?.lock().unwrap().clone();
+            if samples.len() < 2 {
+                return Some(String::new());
+            }
+
+            let rate =
+                usage::syscall_rate(&samples, m.syscalls.iter().map(|s| s.entry_time_micros));
+            let history = |value: fn(&usage::Sample) -> u64| -> Vec<(u64, u64)> {
+                samples.iter().map(|s| (s.time_micros, value(s))).collect()
+            };
+            let series = [
+                ("CPU", "%", history(|s| s.cpu_percent)),
+                ("memory", " KiB", history(|s| s.rss_kib)),
+                ("threads", "", history(|s| s.threads)),
+                ("open fds", "", history(|s| s.fds)),
+                ("syscalls", "/s", rate),
+            ];
+            let mut text = format!(
+                "over {}, sampled from /proc\n",
+                units::duration(samples[samples.len() - 1].time_micros - samples[0].time_micros)
+            );
+            for (name, unit, history) in series {
+                let peak = history.iter().map(|(_, x)| *x).max().unwrap_or(0);
+                let now = history.last().map_or(0, |(_, x)| *x);
+                text.push_str(&format!(
+                    "\n{} (now {}{}, peak {}{})\n{}\n",
+                    name,
+                    now,
+                    unit,
+                    peak,
+                    unit,
+                    memory::graph(&history, MEMORY_GRAPH_WIDTH)
+                ));
+            }
+            Some(text)
+        })
+        .flatten();
+    match text {
+        Some(text) if !text.is_empty() => s.add_layer(
+            Dialog::around(TextView::new(text).scrollable())
+                .title("resource usage")
+                .dismiss_button("Close"),
+        ),
+        Some(_) => s.add_layer(Dialog::info("No samples yet.")),
+        None => s.add_layer(Dialog::info(
+            "Resource usage is only sampled while tracing processes on this machine.",
+        )),
+    }
+}
+
+fn show_event_loops(s: &mut Cursive) {
+    let text = s
+        .with_user_data(|m: &mut Model| {
+            if m.event_loops.loops.is_empty() {
+                return None;
+            }
+
+            let mut text = String::new();
+            for (owner, event_loop) in &m.event_loops.loops {
+                match owner {
+                    Some(pid) => text.push_str(&format!("{}\n", m.processes.label(*pid))),
+                    None => text.push_str("(unknown process)\n"),
+                }
+                for interest in event_loop.interests.values() {
+                    text.push_str(&format!("  {}\n", interest));
+                }
+                for (pid, waits) in &event_loop.waits {
+                    text.push_str("  ");
+                    if let Some(pid) = pid {
+                        text.push_str(&format!("{}: ", m.processes.label(*pid)));
+                    }
+                    text.push_str(&format!("{}\n", waits));
+                }
+            }
+            Some(text)
+        })
+        .flatten();
+    match text {
+        Some(text) => s.add_layer(
+            Dialog::around(TextView::new(text).scrollable())
+                .title("event loops")
+                .dismiss_button("Close"),
+        ),
+        None => s.add_layer(Dialog::info("No epoll or poll calls yet.")),
+    }
+}
+
+fn show_loops(s: &mut Cursive) {
+    let text = s
+        .with_user_data(|m: &mut Model| {
+            let mut text = String::new();
+            for spin in m.loops.found() {
+                if let Some(pid) = spin.pid {
+                    text.push_str(&format!("{}: ", m.processes.label(pid)));
+                }
+                text.push_str(&format!("{}\n", spin));
+            }
+            text
+        })
+        .unwrap_or_default();
+    if text.is_empty() {
+        s.add_layer(Dialog::info("No busy loops found yet."));
+        return;
+    }
+    s.add_layer(
+        Dialog::around(TextView::new(text).scrollable())
+            .title("busy loops")
+            .dismiss_button("Close"),
+    );
+}
+
+fn show_io_sizes(s: &mut Cursive) {
+    let text = s
+        .with_user_data(|m: &mut Model| {
+            let mut text = StyledString::new();
+            for (target, io, sizes) in m.io_sizes.busiest() {
+                text.append_plain(format!(
+                    "{} ({}): {} calls, {} bytes on average\n  {}\n",
+                    target,
+                    io,
+                    sizes.calls,
+                    sizes.average(),
+                    sizes
+                ));
+                if let Some(hint) = sizes.hint(io) {
+                    text.append_styled(
+                        format!("  hint: {}\n", hint),
+                        Color::Dark(BaseColor::Yellow),
+                    );
+                }
+            }
+            text
+        })
+        .unwrap_or_default();
+    if text.is_empty() {
+        s.add_layer(Dialog::info("No reads or writes yet."));
+        return;
+    }
+    s.add_layer(
+        Dialog::around(TextView::new(text).scrollable())
+            .title("I/O sizes (bytes per call)")
+            .dismiss_button("Close"),
+    );
+}
+
+fn show_outliers(s: &mut Cursive) {
+    let rows = s
+        .with_user_data(|m: &mut Model| {
+            m.outlier_syscalls
+                .iter()
+                .map(|(index, outlier)| {
+                    let outlier = &m.outliers.found[*outlier];
+                    let label = match outlier.pid {
+                        Some(pid) => format!("{}: {}", m.processes.label(pid), outlier),
+                        None => outlier.to_string(),
+                    };
+                    (label, *index)
+                })
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    if rows.is_empty() {
+        s.add_layer(Dialog::info("No unusually slow syscalls found yet."));
+        return;
+    }
+
+    let mut list = SelectView::new();
+    list.add_all(rows);
+    list.set_on_submit(|s, index: &usize| {
+        s.pop_layer();
+        select_event(s, *index);
+    });
+    s.add_layer(
+        Dialog::around(list.scrollable())
+            .title("unusually slow syscalls (enter: go to event)")
+            .dismiss_button("Close"),
+    );
+}
+
+fn show_heatmap(s: &mut Cursive) {
+    // the syscall names and borders take up the rest
+    let width = s.screen_size().x.saturating_sub(40).max(20);
+    let text = s
+        .with_user_data(|m: &mut Model| {
+            let mut heatmap = Heatmap::default();
+            for syscall in &m.syscalls {
+                heatmap.update(syscall);
+            }
+            if heatmap.calls() == 0 {
+                return None;
+            }
+            let mut out = Vec::new();
+            heatmap.write(&mut out, width).ok()?;
+            String::from_utf8(out).ok()
+        })
+        .flatten();
+    match text {
+        Some(text) => s.add_layer(
+            Dialog::around(TextView::new(text).scrollable())
+                .title("syscalls over time (darker is busier)")
+                .dismiss_button("Close"),
+        ),
+        None => s.add_layer(Dialog::info("No timestamped syscalls yet.")),
+    }
+}
+
+fn show_groups(s: &mut Cursive) {
+    let empty = s
+        .with_user_data(|m: &mut Model| m.syscalls.is_empty())
+        .unwrap_or(true);
+    let source = Source::Groups {
+        key: Key::Name,
+        trail: Vec::new(),
+    };
+    if empty {
+        s.add_layer(Dialog::info("No events yet."));
+    } else {
+        tables::show_table(s, source, String::new(), None);
+    }
+}
+
+// lists the events in a group, `depth` levels down
+fn show_group_events(s: &mut Cursive, indices: Vec<usize>, depth: usize) {
+    let rows = s
+        .with_user_data(|m: &mut Model| {
+            indices
+                .iter()
+                .map(|i| (event_label(m, *i), *i))
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    let mut list = SelectView::new();
+    list.add_all(rows);
+    list.set_on_submit(move |s, index: &usize| {
+        // this list, and the group views under it
+        for _ in 0..depth + 2 {
+            s.pop_layer();
+        }
+        select_event(s, *index);
+    });
+    s.add_layer(
+        Dialog::around(list.scrollable())
+            .title("events in the group (enter: go to event)")
+            .dismiss_button("Close"),
+    );
+}
+
+fn show_postmortem(s: &mut Cursive) {
+    let text = s
+        .with_user_data(|m: &mut Model| {
+            let report = m.postmortem.report.as_ref()?;
+            Some(report.lines(&m.processes.label(report.pid)).join("\n"))
+        })
+        .flatten();
+    match text {
+        Some(text) => s.add_layer(
+            Dialog::around(TextView::new(text).scrollable())
+                .title("why it failed")
+                .dismiss_button("Close"),
+        ),
+        None => s.add_layer(Dialog::info("The program has not failed.")),
+    }
+}
+
+fn show_config_files(s: &mut Cursive) {
+    let text = s
+        .with_user_data(|m: &mut Model| {
+            let mut text = StyledString::new();
+            for (path, status) in &m.config.files {
+                let line = format!("{:>9} {}\n", status, path);
+                match status {
+                    config::Status::Failed(_) => {
+                        text.append_styled(line, Color::Dark(BaseColor::Red))
+                    }
+                    _ => text.append_plain(line),
+                }
+            }
+            text
+        })
+        .unwrap_or_default();
+    if text.is_empty() {
+        s.add_layer(Dialog::info("No config files looked for yet."));
+        return;
+    }
+    s.add_layer(
+        Dialog::around(TextView::new(text).scrollable())
+            .title("config files")
+            .dismiss_button("Close"),
+    );
+}
+
+fn show_libraries(s: &mut Cursive) {
+    let text = s
+        .with_user_data(|m: &mut Model| {
+            let mut text = String::new();
+            for (pid, loading) in &m.libraries.programs {
+                if loading.loaded.is_empty() {
+                    continue;
+                }
+                if let Some(pid) = pid {
+                    text.push_str(&format!("{}: ", m.processes.label(*pid)));
+                }
+                text.push_str(&format!(
+                    "{} loaded in {}, {} misses\n",
+                    loading.loaded.len(),
+                    units::duration(loading.duration_micros()),
+                    loading.misses
+                ));
+                for path in &loading.loaded {
+                    text.push_str(&format!("  {}\n", path));
+                }
+            }
+            text
+        })
+        .unwrap_or_default();
+    if text.is_empty() {
+        s.add_layer(Dialog::info("No shared libraries loaded yet."));
+        return;
+    }
+    s.add_layer(
+        Dialog::around(TextView::new(text).scrollable())
+            .title("shared libraries")
+            .dismiss_button("Close"),
+    );
+}
+
+fn add_process_rows(m: &Model, pid: u32, depth: usize, rows: &mut Vec<(String, u32)>) {
+    let process = &m.processes.processes[&pid];
+    rows.push((
+        format!(
+            "{}{}: {} syscalls",
+            "  ".repeat(depth),
+            m.processes.label(process.pid),
+            process.syscall_count
+        ),
+        pid,
+    ));
+    // e.g., "→ 12 (cat) over pipe: sent 3 bytes, received 0"
+    for peer in m.channels.peers(pid) {
+        rows.push((
+            format!(
+                "{}  {} {} {}",
+                "  ".repeat(depth),
+                peer.arrow(),
+                m.processes.label(peer.pid),
+                peer
+            ),
+            peer.pid,
+        ));
+    }
+    // threads are grouped under their process rather than shown as children
+    for thread in m.processes.threads(pid) {
+        rows.push((
+            format!(
+                "{}  thread {}: {} syscalls",
+                "  ".repeat(depth),
+                thread,
+                m.processes.processes[&thread].syscall_count
+            ),
+            thread,
+        ));
+    }
+    for child in m.processes.children(pid) {
+        add_process_rows(m, child, depth + 1, rows);
+    }
+}
+
+fn show_filter_dialog(s: &mut Cursive) {
+    let current = s
+        .with_user_data(|m: &mut Model| m.filter.text.clone())
+        .unwrap_or_default();
+    s.add_layer(
+        Dialog::new()
+            .title("filter (e.g., 'openat,close', 'errno=ENOENT', '!futex', 'path=\\.db$')")
+            .content(
+                EditView::new()
+                    .content(current)
+                    .on_submit(|s, text| {
+                        s.pop_layer();
+                        apply_filter(s, text);
+                    })
+                    .with_name("filter text")
+                    .min_width(40),
+            )
+            // the named filters from the config file
+            .button("Named", show_named_filters)
+            .button("Save", show_save_filter_dialog)
+            .button("Cancel", |s| {
+                s.pop_layer();
+            }),
+    );
+}
+
+fn show_named_filters(s: &mut Cursive) {
+    let named = s
+        .with_user_data(|m: &mut Model| m.rules.filters.clone())
+        .unwrap_or_default();
+    if named.is_empty() {
+        s.add_layer(Dialog::info(
+            "No named filters yet. Save one with Save, or see src/rules.rs.",
+        ));
+        return;
+    }
+    let mut choices = SelectView::new();
+    for (name, text) in named {
+        choices.add_item(format!("{}: {}", name, text), text);
+    }
+    choices.set_on_submit(|s, text: &String| {
+        // this and the filter dialog
+        s.pop_layer();
+        s.pop_layer();
+        apply_filter(s, text);
+    });
+    s.add_layer(
+        Dialog::around(choices.scrollable())
+            .title("named filters")
+            .dismiss_button("Cancel"),
+    );
+}
+
+// Asks for a name to save the filter being typed as, in the config file.
+fn show_save_filter_dialog(s: &mut Cursive) {
+    let text = s
+        .call_on_name("filter text", |v: &mut EditView| v.get_content())
+        .unwrap_or_default();
+    s.add_layer(
+        Dialog::new()
+            .title(format!("save {:?} as (e.g., network-only)", text.trim()))
+            .content(
+                EditView::new()
+                    .on_submit(move |s, name| save_filter(s, name.trim(), &text))
+                    .min_width(30),
+            )
+            .button("Cancel", |s| {
+                s.pop_layer();
+            }),
+    );
+}
+
+fn save_filter(s: &mut Cursive, name: &str, text: &str) {
+    match s.with_user_data(|m: &mut Model| m.rules.save_filter(name, text)) {
+        Some(Ok(file)) => {
+            // this and the filter dialog
+            s.pop_layer();
+            s.pop_layer();
+            apply_filter(s, text);
+            let status = format!("Saved the filter as {} in {}", name, file.display());
+            s.call_on_name("status", |t: &mut TextView| t.set_content(status));
+        }
+        Some(Err(e)) => s.add_layer(Dialog::info(format!("Unable to save the filter: {}", e))),
+        None => {}
+    }
+}
+
+fn apply_filter(s: &mut Cursive, text: &str) {
+    match Filter::parse(text) {
+        Ok(filter) => set_filter(s, filter),
+        Err(e) => s.add_layer(Dialog::info(format!("invalid filter: {}", e))),
+    }
+}
+
+fn follow_selected_fd(s: &mut Cursive) {
+    let index = match selected_event(s) {
+        Some(index) => index,
+        None => return,
+    };
+
+    let mut fds = s
+        .with_user_data(|m: &mut Model| {
+            let syscall = &m.syscalls[index];
+            let mut fds = syscalls::fd_args(syscall);
+            fds.extend(syscalls::created_fds(syscall));
+            fds
+        })
+        .unwrap_or_default();
+    fds.dedup();
+
+    match fds.len() {
+        0 => s.add_layer(Dialog::info(
+            "This event does not involve a file descriptor.",
+        )),
+        1 => follow_fd(s, index, fds[0]),
+        _ => {
+            let mut choices = SelectView::new();
+            for fd in fds {
+                choices.add_item(format!("fd {}", fd), fd);
+            }
+            choices.set_on_submit(move |s, fd: &i64| {
+                s.pop_layer();
+                follow_fd(s, index, *fd);
+            });
+            s.add_layer(
+                Dialog::around(choices)
+                    .title("follow which fd?")
+                    .dismiss_button("Cancel"),
+            );
+        }
+    }
+}
+
+fn follow_fd(s: &mut Cursive, index: usize, fd: i64) {
+    let filter = s.with_user_data(|m: &mut Model| Filter::follow_fd(&m.syscalls, index, fd));
+    if let Some(filter) = filter {
+        set_filter(s, filter);
+    }
+}
+
+fn set_filter(s: &mut Cursive, filter: Filter) {
+    let items = s
+        .with_user_data(|m: &mut Model| {
+            m.set_filter(filter)
+                .into_iter()
+                .map(|event| (row_label(m, event), event))
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    s.call_on_name("events", |v: &mut SelectView<Event>| {
+        v.clear();
+        v.add_all(items);
+    });
+    refresh_title(s);
+}
+
+// Shows only the events of the nth command traced, or of all of them if `None`.
+fn select_tab(s: &mut Cursive, tab: Option<usize>) {
+    let items = s
+        .with_user_data(|m: &mut Model| {
+            m.set_tab(tab)
+                .into_iter()
+                .map(|event| (row_label(m, event), event))
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    s.call_on_name("events", |v: &mut SelectView<Event>| {
+        v.clear();
+        v.add_all(items);
+    });
+    refresh_tabs(s);
+}
+
+fn refresh_tabs(s: &mut Cursive) {
+    let tabs = s
+        .with_user_data(|m: &mut Model| {
+            let mut tabs = StyledString::new();
+            let names = std::iter::once("all").chain(m.targets.names.iter().map(String::as_str));
+            for (i, name) in names.take(10).enumerate() {
+                let label = format!(" {} {} ", i, name);
+                if m.tab == i.checked_sub(1) {
+                    tabs.append_styled(label, Effect::Reverse);
+                } else {
+                    tabs.append_plain(label);
+                }
+            }
+            tabs
+        })
+        .unwrap_or_default();
+    s.call_on_name("tabs", |t: &mut TextView| t.set_content(tabs));
+}
+
+fn refresh_title(s: &mut Cursive) {
+    let title = s
+        .with_user_data(|m: &mut Model| {
+            let mut title = "events".to_string();
+            if !m.filter.is_empty() {
+                title.push_str(&format!(" [{}]", m.filter.text));
+            }
+            if let Some(injections) = m.injections.as_ref().filter(|i| !i.is_empty()) {
+                let faults: Vec<String> = injections.iter().map(|i| i.to_string()).collect();
+                title.push_str(&format!(" injecting {}", faults.join(" ")));
+            }
+            if m.frozen {
+                title.push_str(&format!(" FROZEN (+{} buffered)", m.buffered.len()));
+            }
+            if m.skipped > 0 {
+                title.push_str(&format!(" sampled ({} skipped)", m.skipped));
+            }
+            title
+        })
+        .unwrap_or_default();
+    s.call_on_name("events_panel", |p: &mut EventsPanel| p.set_title(title));
+}
+
+fn is_frozen(s: &mut Cursive) -> bool {
+    s.with_user_data(|m: &mut Model| m.frozen).unwrap_or(false)
+}
+
+fn toggle_freeze(s: &mut Cursive) {
+    resume_stopped(s);
+    let items = s
+        .with_user_data(|m: &mut Model| {
+            if m.frozen {
+                m.thaw()
+                    .into_iter()
+                    .map(|event| (row_label(m, event), event))
+                    .collect::<Vec<_>>()
+            } else {
+                m.frozen = true;
+                Vec::new()
+            }
+        })
+        .unwrap_or_default();
+    s.call_on_name("events", |v: &mut SelectView<Event>| v.add_all(items));
+    show_paused_on(s);
+    show_alerts(s);
+    refresh_title(s);
+    refresh_panes(s);
+}
+
+fn toggle_pause_on_error(s: &mut Cursive) {
+    let armed = s
+        .with_user_data(|m: &mut Model| {
+            m.pause_on_error = !m.pause_on_error;
+            m.pause_on_error
+        })
+        .unwrap_or(false);
+    let status = if armed {
+        "Will pause at the next failed syscall"
+    } else {
+        "Will not pause on failed syscalls"
+    };
+    s.call_on_name("status", |t: &mut TextView| t.set_content(status));
+}
+
+/// If the model just paused itself at a failed syscall, selects it.
+fn show_paused_on(s: &mut Cursive) {
+    let index = match s
+        .with_user_data(|m: &mut Model| m.paused_on.take())
+        .flatten()
+    {
+        Some(index) => index,
+        None => return,
+    };
+
+    select_event(s, index);
+    s.call_on_name("status", |t: &mut TextView| {
+        t.set_content(format!(
+            "Paused at failed syscall #{} (p: resume, e: pause at the next one)",
+            index
+        ))
+    });
+}
+
+fn show_inject_dialog(s: &mut Cursive, strace_pid: Option<u32>, attached_pids: Vec<u32>) {
+    let (current, strace_pid) = match (
+        s.with_user_data(|m: &mut Model| m.injections.clone())
+            .flatten(),
+        strace_pid,
+    ) {
+        (Some(current), Some(strace_pid)) => (current, strace_pid),
+        _ => {
+            s.add_layer(Dialog::info(
+                "Faults can only be injected into a program that vistrace is tracing on this \
+                 machine.",
+            ));
+            return;
+        }
+    };
+    let text: Vec<String> = current.iter().map(|i| i.to_string()).collect();
+    let restart = if attached_pids.is_empty() {
+        "restarts the command"
+    } else {
+        "reattaches to the processes"
+    };
+    s.add_layer(
+        Dialog::new()
+            .title(format!(
+                "inject faults (e.g., 'openat:error=EIO:every=3'); {}",
+                restart
+            ))
+            .content(
+                EditView::new()
+                    .content(text.join(" "))
+                    .on_submit(move |s, text| match inject::parse_all(text) {
+                        Ok(injections) => {
+                            s.pop_layer();
+                            restart_trace(s, strace_pid, &attached_pids, injections);
+                        }
+                        Err(e) => s.add_layer(Dialog::info(format!("invalid fault: {}", e))),
+                    })
+                    .min_width(40),
+            )
+            .button("Cancel", |s| {
+                s.pop_layer();
+            }),
+    );
+}
+
+/// Stops the trace, so that `main` can start it again with `injections`.
+fn restart_trace(
+    s: &mut Cursive,
+    strace_pid: u32,
+    attached_pids: &[u32],
+    injections: Vec<Injection>,
+) {
+    let pids: Vec<u32> = s
+        .with_user_data(|m: &mut Model| {
+            m.restart = Some(injections);
+            m.processes.processes.keys().copied().collect()
+        })
+        .unwrap_or_default();
+    if attached_pids.is_empty() {
+        // strace's own children cover the command when strace did not print PIDs
+        for pid in pids.into_iter().chain(procfs::children(strace_pid)) {
+            strace::kill(pid);
+        }
+    } else {
+        strace::detach(strace_pid);
+    }
+    s.quit();
+}
+
+/// Acts on the alerts that new events set off: rings the bell, logs them, and, if one paused the
+/// interface, selects its event.
+fn show_alerts(s: &mut Cursive) {
+    let alerts = s
+        .with_user_data(|m: &mut Model| {
+            std::mem::take(&mut m.new_alerts)
+                .into_iter()
+                .map(|(index, alert)| {
+                    let alert = &m.rules.alerts[alert];
+                    // strace stopped the process itself, if it was started with the fault
+                    let injected = alert
+                        .stop_injection()
+                        .is_some_and(|injection| m.stop_injections.contains(&injection));
+                    (
+                        index,
+                        alert.actions.clone(),
+                        alert.to_string(),
+                        m.syscalls[index].to_string(),
+                        injected,
+                    )
+                })
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+
+    let mut beep = false;
+    let mut status = None;
+    for (index, actions, alert, syscall, injected) in alerts {
+        if actions.contains(&Action::Log) {
+            tracing::warn!(event = index, rule = %alert, "{}", syscall);
+        }
+        beep |= actions.contains(&Action::Beep);
+        if actions.contains(&Action::Stop) {
+            select_event(s, index);
+            let stopped = s
+                .with_user_data(|m: &mut Model| {
+                    if !m.stoppable {
+                        return None;
+                    }
+                    let pid = m.syscalls[index].pid.or_else(|| lone_process(m))?;
+                    if !injected {
+                        strace::stop(pid);
+                    }
+                    m.stopped.insert(pid);
+                    Some(m.processes.label(pid))
+                })
+                .flatten();
+            status = Some(match stopped {
+                Some(process) if injected => format!(
+                    "Stopped process {} at #{} by `{}` (p: resume)",
+                    process, index, alert
+                ),
+                Some(process) => format!(
+                    "Stopped process {} some time after #{} by `{}`, since strace cannot stop at \
+                     that filter itself (p: resume)",
+                    process, index, alert
+                ),
+                None => format!(
+                    "Paused at #{} by `{}`, but unable to stop the process (p: resume)",
+                    index, alert
+                ),
+            });
+        } else if actions.contains(&Action::Pause) {
+            select_event(s, index);
+            status = Some(format!("Paused at #{} by `{}` (p: resume)", index, alert));
+        } else if status.is_none() {
+            status = Some(format!("#{} set off `{}`", index, alert));
+        }
+    }
+    if beep {
+        // cursive has no way to ring the terminal bell
+        let mut stdout = io::stdout();
+        let _ = stdout.write_all(b"\x07").and_then(|_| stdout.flush());
+    }
+    if let Some(status) = status {
+        s.call_on_name("status", |t: &mut TextView| t.set_content(status));
+    }
+}
+
+// The process that made a syscall without a pid, which strace leaves out when it traces a single
+// process without -f: the one that it attached to, or else its only child, the command.
+fn lone_process(m: &Model) -> Option<u32> {
+    match m.attached_pids[..] {
+        [pid] => Some(pid),
+        [] => match procfs::children(m.strace_pid?)[..] {
+            [pid] => Some(pid),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Resumes the processes that breakpoints stopped.
+fn resume_stopped(s: &mut Cursive) {
+    let stopped = s
+        .with_user_data(|m: &mut Model| std::mem::take(&mut m.stopped))
+        .unwrap_or_default();
+    for pid in stopped {
+        strace::resume(pid);
+    }
+}
+
+fn reload_rules(s: &mut Cursive) {
+    let path = s
+        .with_user_data(|m: &mut Model| m.rules.path.clone())
+        .flatten();
+    let rules = match Rules::load(path.as_deref()) {
+        Ok(rules) => rules,
+        Err(e) => {
+            s.add_layer(Dialog::info(format!(
+                "Unable to reload the config file: {}",
+                e
+            )));
+            return;
+        }
+    };
+    let status = format!(
+        "Reloaded {} alerts and {} named filters",
+        rules.alerts.len(),
+        rules.filters.len()
+    );
+    // re-applying the filter redraws the events with the new highlights
+    let filter = s.with_user_data(|m: &mut Model| {
+        m.set_rules(rules);
+        std::mem::take(&mut m.filter)
+    });
+    if let Some(filter) = filter {
+        set_filter(s, filter);
+    }
+    s.call_on_name("status", |t: &mut TextView| t.set_content(status));
+}
+
+/// If the program just checked whether it is being traced, says so in the status line.
+fn show_new_detection(s: &mut Cursive) {
+    let text = s
+        .with_user_data(|m: &mut Model| {
+            let detection = &m.antidebug.detections[m.new_detection.take()?];
+            let who = match detection.pid {
+                Some(pid) => format!("Process {}", m.processes.label(pid)),
+                None => "The program".to_string(),
+            };
+            Some(format!(
+                "Warning: {} {}; it may act differently when traced (D: details)",
+                who, detection
+            ))
+        })
+        .flatten();
+    if let Some(text) = text {
+        s.call_on_name("status", |t: &mut TextView| {
+            t.set_content(StyledString::styled(text, Color::Dark(BaseColor::Red)))
+        });
+    }
+}
+
+fn show_antidebug(s: &mut Cursive) {
+    let text = s
+        .with_user_data(|m: &mut Model| {
+            let mut text = String::new();
+            for detection in &m.antidebug.detections {
+                if let Some(pid) = detection.pid {
+                    text.push_str(&format!("{}: ", m.processes.label(pid)));
+                }
+                text.push_str(&format!("{}\n", detection));
+            }
+            text
+        })
+        .unwrap_or_default();
+    if text.is_empty() {
+        s.add_layer(Dialog::info(
+            "The program has not checked whether it is being traced.",
+        ));
+        return;
+    }
+    s.add_layer(
+        Dialog::around(TextView::new(text).scrollable())
+            .title("checks for a tracer (the trace may not show how the program acts untraced)")
+            .dismiss_button("Close"),
+    );
+}
+
+/// Selects the event for the syscall at `index`, if it passes the filter.
+fn select_event(s: &mut Cursive, index: usize) {
+    let callback = s
+        .call_on_name("events", |v: &mut SelectView<Event>| {
+            let row = v.iter().position(|(_, e)| *e == Event::Syscall(index))?;
+            Some(v.set_selection(row))
+        })
+        .flatten();
+    if let Some(callback) = callback {
+        callback(s);
+    }
+}
+
+// the syscall that is selected, if any (and not a library call)
+fn selected_event(s: &mut Cursive) -> Option<usize> {
+    s.call_on_name("events", |v: &mut SelectView<Event>| v.selection())
+        .flatten()
+        .and_then(|event| match *event {
+            Event::Syscall(index) => Some(index),
+            Event::LibCall(_) => None,
+        })
+}
+
+fn relabel_event(s: &mut Cursive, index: usize) {
+    let label = match s.with_user_data(|m: &mut Model| event_label(m, index)) {
+        Some(label) => label,
+        None => return,
+    };
+    s.call_on_name("events", |v: &mut SelectView<Event>| {
+        let row = v.iter().position(|(_, e)| *e == Event::Syscall(index));
+        if let Some((text, _)) = row.and_then(|row| v.get_item_mut(row)) {
+            *text = label;
+        }
+    });
+}
+
+fn toggle_mark(s: &mut Cursive) {
+    if let Some(index) = selected_event(s) {
+        s.with_user_data(|m: &mut Model| m.toggle_mark(index));
+        relabel_event(s, index);
+    }
+}
+
+fn show_annotate_dialog(s: &mut Cursive) {
+    let index = match selected_event(s) {
+        Some(index) => index,
+        None => return,
+    };
+    let current = s
+        .with_user_data(|m: &mut Model| m.marks.get(&index).cloned())
+        .flatten()
+        .unwrap_or_default();
+    s.add_layer(
+        Dialog::new()
+            .title(format!("note for event #{}", index))
+            .content(
+                EditView::new()
+                    .content(current)
+                    .on_submit(move |s, text| {
+                        s.pop_layer();
+                        s.with_user_data(|m: &mut Model| {
+                            m.marks.insert(index, text.trim().to_string())
+                        });
+                        relabel_event(s, index);
+                    })
+                    .min_width(40),
+            )
+            .button("Cancel", |s| {
+                s.pop_layer();
+            }),
+    );
+}
+
+fn show_jump_dialog(s: &mut Cursive) {
+    s.add_layer(
+        Dialog::new()
+            .title("jump to (e.g., '+5s', '-250ms', '14:02:10', '1720000000.5')")
+            .content(
+                EditView::new()
+                    .on_submit(|s, text| {
+                        s.pop_layer();
+                        match jump::Time::parse(text) {
+                            Ok(time) => jump_to(s, &time),
+                            Err(e) => s.add_layer(Dialog::info(e.to_string())),
+                        }
+                    })
+                    .min_width(40),
+            )
+            .button("Cancel", |s| {
+                s.pop_layer();
+            }),
+    );
+}
+
+// Selects the first event shown at or after `time`, or the last event if there is none.
+fn jump_to(s: &mut Cursive, time: &jump::Time) {
+    let times: Vec<u64> = s
+        .call_on_name("events", |v: &mut SelectView<Event>| {
+            v.iter().map(|(_, e)| *e).collect::<Vec<_>>()
+        })
+        .and_then(|events| {
+            s.with_user_data(|m: &mut Model| {
+                events
+                    .iter()
+                    .map(|event| match *event {
+                        Event::Syscall(index) => m.syscalls[index].entry_time_micros,
+                        Event::LibCall(index) => m.libcalls[index].1.time_micros,
+                    })
+                    .collect()
+            })
+        })
+        .unwrap_or_default();
+    if times.iter().all(|t| *t == 0) {
+        s.add_layer(Dialog::info("The events have no times to jump between"));
+        return;
+    }
+    let from = s
+        .call_on_name("events", |v: &mut SelectView<Event>| v.selected_id())
+        .flatten()
+        .and_then(|row| times.get(row).copied())
+        // e.g., a line that could not be parsed has no time of its own
+        .filter(|t| *t != 0)
+        .or_else(|| times.iter().copied().find(|t| *t != 0))
+        .unwrap_or_default();
+    let target = time.resolve(from, jump::local_utc_offset(from));
+    let (row, status) = match jump::first_at_or_after(&times, target) {
+        Some(row) => (
+            row,
+            format!("Jumped to {}", strace::format_timestamp(target)),
+        ),
+        None => (
+            times.len() - 1,
+            format!(
+                "No events at or after {}, so jumped to the last one",
+                strace::format_timestamp(target)
+            ),
+        ),
+    };
+    let callback = s.call_on_name("events", |v: &mut SelectView<Event>| v.set_selection(row));
+    if let Some(callback) = callback {
+        callback(s);
+    }
+    // which also stops the events from following the end of the trace
+    s.call_on_name("events_panel", |p: &mut EventsPanel| {
+        let events = p.get_inner_mut();
+        events.set_scroll_strategy(ScrollStrategy::KeepRow);
+        events.scroll_to_important_area()
+    });
+    s.call_on_name("status", |t: &mut TextView| t.set_content(status));
+}
+
+fn show_snapshot_dialog(s: &mut Cursive) {
+    s.add_layer(
+        Dialog::new()
+            .title("write snapshot to file")
+            .content(
+                EditView::new()
+                    .content("vistrace-snapshot.trace")
+                    .on_submit(|s, path| {
+                        s.pop_layer();
+                        let selected = selected_event(s);
+                        let result = s.with_user_data(|m: &mut Model| {
+                            snapshot::write(Path::new(path), m, selected)
+                        });
+                        let message = match result {
+                            Some(Ok(())) => format!("wrote snapshot to {}", path),
+                            Some(Err(e)) => format!("error: {}", e),
+                            None => return,
+                        };
+                        s.add_layer(Dialog::info(message));
+                    })
+                    .min_width(40),
+            )
+            .button("Cancel", |s| {
+                s.pop_layer();
+            }),
+    );
+}
@@ -0,0 +1,299 @@
+use std::time::{Duration, Instant};
+
+use cursive::event::{Event, EventResult};
+use cursive::view::{Nameable, Resizable, Scrollable, ViewWrapper};
+use cursive::views::{Dialog, EditView, LinearLayout, ScrollView, SelectView, TextView};
+use cursive::{wrap_impl, Cursive, View};
+
+use crate::groups::{self, Key};
+use crate::model::Model;
+use crate::table::{Source, Table};
+
+// how often an open table is built again while the trace goes on
+const REBUILD_INTERVAL: Duration = Duration::from_secs(1);
+
+type Rows = ScrollView<SelectView<usize>>;
+
+/// A table (see src/table.rs) with its filter and the column it is sorted by, which 's' moves to
+/// the next column, 'r' reverses, and '/' asks for a new filter.
+pub struct TableView {
+    source: Source,
+    filter: String,
+    sorted: usize,
+    reversed: bool,
+    table: Table,
+    // the number of syscalls that the table was built from, and when
+    seen: usize,
+    built_at: Instant,
+    // a header above the rows
+    layout: LinearLayout,
+}
+
+/// Shows the table of `source`, filtered by `filter`, with `extra` text below it, if any.
+pub fn show_table(s: &mut Cursive, source: Source, filter: String, extra: Option<String>) {
+    let built = s
+        .with_user_data(|m: &mut Model| (source.build(m, &filter), m.syscalls.len()))
+        .map(|(table, seen)| table.map(|table| (table, seen)));
+    let (table, seen) = match built {
+        Some(Ok(built)) => built,
+        Some(Err(e)) => return s.add_layer(Dialog::info(format!("invalid filter: {}", e))),
+        None => return,
+    };
+    let mut rows = SelectView::<usize>::new();
+    let drill = source.clone();
+    rows.set_on_submit(move |s, row: &usize| drill_down(s, &drill, *row));
+    let mut view = TableView {
+        source: source.clone(),
+        filter,
+        sorted: 0,
+        reversed: false,
+        table,
+        seen,
+        built_at: Instant::now(),
+        layout: LinearLayout::vertical()
+            .child(TextView::new(""))
+            .child(rows.scrollable()),
+    };
+    view.render(None);
+
+    let mut title = source.title();
+    title.push_str(match source {
+        Source::Connections => " (enter: events",
+        _ => " (enter: drill down",
+    });
+    title.push_str(", s: sort, r: reverse, /: filter)");
+    let mut content = LinearLayout::vertical().child(view.with_name("table"));
+    if let Some(extra) = extra {
+        content.add_child(TextView::new(extra));
+    }
+    let mut dialog = Dialog::around(content).title(title);
+    if let Source::Groups { key, trail } = &source {
+        for other in groups::KEYS.into_iter().filter(|k| k != key) {
+            let trail = trail.clone();
+            dialog.add_button(format!("By {}", other), move |s| {
+                let filter = current_filter(s);
+                s.pop_layer();
+                let source = Source::Groups {
+                    key: other,
+                    trail: trail.clone(),
+                };
+                show_table(s, source, filter, None);
+            });
+        }
+    }
+    dialog.add_button("Events", |s| {
+        let indices = on_top(s, |t| {
+            let mut indices: Vec<usize> = t
+                .table
+                .rows
+                .iter()
+                .flat_map(|row| row.indices.iter().copied())
+                .collect();
+            indices.sort_unstable();
+            indices
+        })
+        .unwrap_or_default();
+        let depth = table_depth(s);
+        super::show_group_events(s, indices, depth);
+    });
+    s.add_layer(dialog.dismiss_button("Close"));
+}
+
+// Opens the groups within a row of the table on top, or for connections, the row's events.
+fn drill_down(s: &mut Cursive, source: &Source, row: usize) {
+    let found = on_top(s, |t| {
+        let row = t.table.rows.get(row)?;
+        let value = row.cells.last()?.to_string();
+        Some((value, row.indices.clone(), t.filter.clone()))
+    })
+    .flatten();
+    let (value, indices, filter) = match found {
+        Some(found) => found,
+        None => return,
+    };
+    let (key, mut trail) = match source {
+        Source::Groups { key, trail } => (*key, trail.clone()),
+        Source::Files => (Key::Path, Vec::new()),
+        Source::Connections => {
+            let depth = table_depth(s);
+            return super::show_group_events(s, indices, depth);
+        }
+    };
+    trail.push((key, value));
+    let next = match source {
+        Source::Files => Key::Name,
+        _ => key.next(),
+    };
+    show_table(s, Source::Groups { key: next, trail }, filter, None);
+}
+
+// the number of tables open, which the events of a row are shown on top of
+fn table_depth(s: &mut Cursive) -> usize {
+    let mut depth: usize = 0;
+    s.call_on_all_named("table", |_: &mut TableView| depth += 1);
+    depth.saturating_sub(1)
+}
+
+// Calls `f` on the table on top, as drilling down into a row opens another table on top of it.
+fn on_top<R>(s: &mut Cursive, f: impl FnOnce(&mut TableView) -> R) -> Option<R> {
+    let mut count = table_depth(s) + 1;
+    let mut f = Some(f);
+    let mut result = None;
+    s.call_on_all_named("table", |t: &mut TableView| {
+        count -= 1;
+        if count == 0 {
+            result = f.take().map(|f| f(t));
+        }
+    });
+    result
+}
+
+fn current_filter(s: &mut Cursive) -> String {
+    on_top(s, |t| t.filter.clone()).unwrap_or_default()
+}
+
+fn show_filter_dialog(s: &mut Cursive) {
+    let current = current_filter(s);
+    s.add_layer(
+        Dialog::new()
+            .title("filter the table's events (e.g., 'openat,close', 'errno=ENOENT', '!futex')")
+            .content(
+                EditView::new()
+                    .content(current)
+                    .on_submit(|s, text| {
+                        s.pop_layer();
+                        set_filter(s, text);
+                    })
+                    .min_width(40),
+            )
+            .button("Cancel", |s| {
+                s.pop_layer();
+            }),
+    );
+}
+
+// Filters the table on top by `filter`.
+fn set_filter(s: &mut Cursive, filter: &str) {
+    let source = match on_top(s, |t| t.source.clone()) {
+        Some(source) => source,
+        None => return,
+    };
+    let built = s
+        .with_user_data(|m: &mut Model| (source.build(m, filter), m.syscalls.len()))
+        .map(|(table, seen)| table.map(|table| (table, seen)));
+    match built {
+        Some(Ok((table, seen))) => {
+            on_top(s, |t| {
+                t.filter = filter.trim().to_string();
+                t.replace(table, seen);
+            });
+        }
+        Some(Err(e)) => s.add_layer(Dialog::info(format!("invalid filter: {}", e))),
+        None => {}
+    }
+}
+
+/// Builds the open tables again if there are new events and it has been long enough since the last
+/// time.
+pub fn refresh_tables(s: &mut Cursive) {
+    let mut stale = Vec::new();
+    s.call_on_all_named("table", |t: &mut TableView| {
+        let due = t.built_at.elapsed() >= REBUILD_INTERVAL;
+        stale.push(due.then(|| (t.source.clone(), t.filter.clone(), t.seen)));
+    });
+    if stale.iter().all(Option::is_none) {
+        return;
+    }
+    let mut tables = s
+        .with_user_data(|m: &mut Model| {
+            let seen = m.syscalls.len();
+            stale
+                .into_iter()
+                .map(|table| {
+                    let (source, filter, _) = table.filter(|(_, _, before)| *before != seen)?;
+                    Some((source.build(m, &filter).ok()?, seen))
+                })
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default()
+        .into_iter();
+    s.call_on_all_named("table", |t: &mut TableView| {
+        if let Some(Some((table, seen))) = tables.next() {
+            t.replace(table, seen);
+        }
+    });
+}
+
+impl TableView {
+    // Puts `table` in place of the current one, keeping the sort and the selected row.
+    fn replace(&mut self, table: Table, seen: usize) {
+        let selected = self.rows().selection().and_then(|row| {
+            let row = self.table.rows.get(*row)?;
+            Some(row.cells.last()?.to_string())
+        });
+        self.table = table;
+        self.seen = seen;
+        self.built_at = Instant::now();
+        self.render(selected);
+    }
+
+    // Sorts the table and shows it, selecting the row whose last cell is `selected`, if any.
+    fn render(&mut self, selected: Option<String>) {
+        self.table.sort(self.sorted, self.reversed);
+        let mut header = String::new();
+        if !self.filter.is_empty() {
+            header.push_str(&format!("filter: {}\n", self.filter));
+        }
+        header.push_str(&self.table.header(self.sorted, self.reversed));
+        let lines: Vec<String> = self.table.rows.iter().map(|r| self.table.line(r)).collect();
+        let row = selected.and_then(|selected| {
+            self.table
+                .rows
+                .iter()
+                .position(|r| r.cells.last().is_some_and(|c| c.to_string() == selected))
+        });
+        if let Some(text) = self
+            .layout
+            .get_child_mut(0)
+            .and_then(|v| v.downcast_mut::<TextView>())
+        {
+            text.set_content(header);
+        }
+        let rows = self.rows();
+        rows.clear();
+        rows.add_all(lines.into_iter().zip(0..));
+        if let Some(row) = row {
+            rows.set_selection(row);
+        }
+    }
+
+    fn rows(&mut self) -> &mut SelectView<usize> {
+        self.layout
+            .get_child_mut(1)
+            .and_then(|v| v.downcast_mut::<Rows>())
+            .expect("table has no rows")
+            .get_inner_mut()
+    }
+}
+
+impl ViewWrapper for TableView {
+    wrap_impl!(self.layout: LinearLayout);
+
+    fn wrap_on_event(&mut self, event: Event) -> EventResult {
+        match event {
+            Event::Char('s') => {
+                self.sorted = (self.sorted + 1) % self.table.columns.len();
+                self.reversed = false;
+            }
+            Event::Char('r') => self.reversed = !self.reversed,
+            Event::Char('/') => return EventResult::with_cb(show_filter_dialog),
+            event => return self.layout.on_event(event),
+        }
+        let selected = self.rows().selection().and_then(|row| {
+            let row = self.table.rows.get(*row)?;
+            Some(row.cells.last()?.to_string())
+        });
+        self.render(selected);
+        EventResult::Consumed(None)
+    }
+}
@@ -0,0 +1,135 @@
+// Sizes, durations, rates, and counts in human units (e.g., "4.0 KiB", "3.4 ms", "1.2 MB/s",
+// "12k"), as the interface and the summary show them, or exactly (e.g., "4096", "0.003412s") with
+// --exact or after 'x' in the interface. Sizes are in powers of 1024, as memory and buffers are
+// usually measured, and rates in powers of 1000, as transfer speeds usually are.
+//
+// Whether to be exact is a setting of the whole process, since many of the values are shown by
+// `Display` implementations, which have nowhere else to get it from. Machine-readable output (JSON,
+// CSV, SQL, ...) is always exact and does not go through here, and nor does the `strace -c` table
+// of the summary, which keeps strace's layout.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::strace::format_timestamp;
+
+static EXACT: AtomicBool = AtomicBool::new(false);
+
+pub fn set_exact(exact: bool) {
+    EXACT.store(exact, Ordering::Relaxed);
+}
+
+pub fn exact() -> bool {
+    EXACT.load(Ordering::Relaxed)
+}
+
+/// e.g., "512 B" or "4.0 KiB"
+pub fn bytes(n: u64) -> String {
+    format_bytes(n, exact())
+}
+
+/// e.g., "40 µs", "3.4 ms", or "2m05s"
+pub fn duration(micros: u64) -> String {
+    format_duration(micros, exact())
+}
+
+/// The rate of `bytes` moved in `micros`, e.g., "1.2 MB/s".
+pub fn rate(bytes: u64, micros: u64) -> String {
+    format_rate(bytes, micros, exact())
+}
+
+/// e.g., "950", "1.2k", or "3.0M"
+pub fn count(n: u64) -> String {
+    format_count(n, exact())
+}
+
+fn format_bytes(n: u64, exact: bool) -> String {
+    if exact {
+        n.to_string()
+    } else if n < 1024 {
+        format!("{} B", n)
+    } else {
+        scaled(n as f64, 1024.0, &["KiB", "MiB", "GiB", "TiB", "PiB"], " ")
+    }
+}
+
+fn format_duration(micros: u64, exact: bool) -> String {
+    let seconds = micros / 1_000_000;
+    if exact {
+        format!("{}s", format_timestamp(micros))
+    } else if micros < 1000 {
+        format!("{} µs", micros)
+    } else if seconds < 60 {
+        scaled(micros as f64, 1000.0, &["ms", "s"], " ")
+    } else if seconds < 3600 {
+        format!("{}m{:02}s", seconds / 60, seconds % 60)
+    } else {
+        format!("{}h{:02}m", seconds / 3600, seconds % 3600 / 60)
+    }
+}
+
+fn format_rate(bytes: u64, micros: u64, exact: bool) -> String {
+    let per_second = bytes as f64 * 1e6 / micros.max(1) as f64;
+    if exact || per_second < 1000.0 {
+        format!("{:.0} B/s", per_second)
+    } else {
+        scaled(per_second, 1000.0, &["kB/s", "MB/s", "GB/s", "TB/s"], " ")
+    }
+}
+
+fn format_count(n: u64, exact: bool) -> String {
+    if exact || n < 1000 {
+        n.to_string()
+    } else {
+        scaled(n as f64, 1000.0, &["k", "M", "G", "T"], "")
+    }
+}
+
+// Divides `n` by `base` until it rounds to less than `base` (or the units run out), and shows it
+// with one decimal place if that is one of its first two significant digits, e.g. "4.0 KiB" but
+// "132 KiB".
+fn scaled(n: f64, base: f64, units: &[&str], space: &str) -> String {
+    let mut n = n / base;
+    let mut unit = 0;
+    while n.round() >= base && unit + 1 < units.len() {
+        n /= base;
+        unit += 1;
+    }
+    if n < 10.0 {
+        format!("{:.1}{}{}", n, space, units[unit])
+    } else {
+        format!("{:.0}{}{}", n, space, units[unit])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{format_bytes, format_count, format_duration, format_rate};
+
+    #[test]
+    fn test_units() {
+        assert_eq!(format_bytes(20, false), "20 B");
+        assert_eq!(format_bytes(4096, false), "4.0 KiB");
+        assert_eq!(format_bytes(135_168, false), "132 KiB");
+        assert_eq!(format_bytes(3 << 30, false), "3.0 GiB");
+        assert_eq!(format_bytes(4096, true), "4096");
+
+        assert_eq!(format_duration(40, false), "40 µs");
+        assert_eq!(format_duration(3412, false), "3.4 ms");
+        assert_eq!(format_duration(250_000, false), "250 ms");
+        assert_eq!(format_duration(1_500_000, false), "1.5 s");
+        assert_eq!(format_duration(999_999, false), "1.0 s");
+        assert_eq!(format_duration(125_000_000, false), "2m05s");
+        assert_eq!(format_duration(7_500_000_000, false), "2h05m");
+        assert_eq!(format_duration(3412, true), "0.003412s");
+
+        assert_eq!(format_rate(1_200_000, 1_000_000, false), "1.2 MB/s");
+        assert_eq!(format_rate(500, 1_000_000, false), "500 B/s");
+        assert_eq!(format_rate(1_200_000, 1_000_000, true), "1200000 B/s");
+
+        assert_eq!(format_count(950, false), "950");
+        assert_eq!(format_count(1_234, false), "1.2k");
+        assert_eq!(format_count(12_345, false), "12k");
+        assert_eq!(format_count(12_345, true), "12345");
+        assert_eq!(format_count(3_000_000, false), "3.0M");
+    }
+}
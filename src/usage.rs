@@ -0,0 +1,134 @@
+// Samples what the traced processes are using (CPU, resident memory, threads, and open fds) from
+// /proc at an interval while tracing, so that the interface can plot it next to the rate of
+// syscalls, e.g. to see that memory grew while a program was reading a file, or that its CPU use
+// spiked while it made no syscalls at all.
+//
+// The numbers are summed over every traced process. Only processes on this machine can be
+// sampled, so there are no samples for `vistrace ssh` or for saved traces.
+
+use std::sync::{Arc, Mutex};
+#[cfg(feature = "native")]
+use std::thread;
+#[cfg(feature = "native")]
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+#[cfg(feature = "native")]
+use crate::procfs;
+
+/// Shared between the sampling thread and the interface.
+pub type Samples = Arc<Mutex<Vec<Sample>>>;
+
+#[derive(Clone, Debug)]
+pub struct Sample {
+    // microseconds since the Unix epoch, as in the trace
+    pub time_micros: u64,
+    // since the previous sample, as a percentage of one CPU
+    pub cpu_percent: u64,
+    pub rss_kib: u64,
+    pub threads: u64,
+    pub fds: u64,
+}
+
+/// Samples the processes that strace (with pid `strace_pid`) is tracing every `interval`, until
+/// strace exits. If strace attached to `attached` processes, they and their children are sampled;
+/// otherwise strace's own children are.
+#[cfg(feature = "native")]
+pub fn spawn(strace_pid: u32, attached: Vec<u32>, interval: Duration) -> Samples {
+    let samples = Samples::default();
+    let thread_samples = samples.clone();
+    thread::spawn(move || {
+        let ticks = procfs::clock_ticks();
+        let mut last: Option<(u64, u64)> = None;
+        while procfs::usage(strace_pid).is_some() {
+            let roots = if attached.is_empty() {
+                procfs::children(strace_pid)
+            } else {
+                attached.clone()
+            };
+            let usage = roots
+                .into_iter()
+                .flat_map(procfs::descendants)
+                .filter_map(procfs::usage)
+                .fold(procfs::Usage::default(), |total, usage| procfs::Usage {
+                    cpu_ticks: total.cpu_ticks + usage.cpu_ticks,
+                    rss_kib: total.rss_kib + usage.rss_kib,
+                    threads: total.threads + usage.threads,
+                    fds: total.fds + usage.fds,
+                });
+            let time_micros = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_micros() as u64);
+
+            // the processes are gone (or not started yet)
+            if usage.threads > 0 {
+                if let Some((last_micros, last_ticks)) = last {
+                    // ticks go down if a process exits
+                    let ticks_used = usage.cpu_ticks.saturating_sub(last_ticks);
+                    let micros = time_micros.saturating_sub(last_micros).max(1);
+                    thread_samples.lock().unwrap().push(Sample {
+                        time_micros,
+                        cpu_percent: ticks_used * 100_000_000 / ticks / micros,
+                        rss_kib: usage.rss_kib,
+                        threads: usage.threads,
+                        fds: usage.fds,
+                    });
+                }
+                last = Some((time_micros, usage.cpu_ticks));
+            }
+            thread::sleep(interval);
+        }
+    });
+    samples
+}
+
+/// Counts the syscalls (given by their start times) between each sample and the one before it,
+/// per second, for plotting alongside the samples.
+pub fn syscall_rate(samples: &[Sample], times: impl Iterator<Item = u64>) -> Vec<(u64, u64)> {
+    let mut counts = vec![0u64; samples.len()];
+    for time in times {
+        let i = samples.partition_point(|s| s.time_micros < time);
+        // before the first sample, or after the last one
+        if i == 0 || i == samples.len() {
+            continue;
+        }
+        counts[i] += 1;
+    }
+    samples
+        .iter()
+        .enumerate()
+        .map(|(i, sample)| {
+            let micros = match i {
+                0 => 0,
+                i => sample.time_micros - samples[i - 1].time_micros,
+            };
+            (sample.time_micros, counts[i] * 1_000_000 / micros.max(1))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{syscall_rate, Sample};
+
+    #[test]
+    fn test_syscall_rate() {
+        let sample = |time_micros| Sample {
+            time_micros,
+            cpu_percent: 0,
+            rss_kib: 0,
+            threads: 1,
+            fds: 0,
+        };
+        let samples = [sample(1_000_000), sample(2_000_000), sample(2_500_000)];
+        // 0.5 s and 3.0 s are outside the samples, so they are not counted
+        let times = [
+            500_000, 1_100_000, 1_900_000, 2_000_000, 2_100_000, 3_000_000,
+        ];
+        assert_eq!(
+            syscall_rate(&samples, times.into_iter()),
+            // three syscalls in (1.0 s, 2.0 s] is 3 per second, and one (2.1 s) in the half second
+            // of (2.0 s, 2.5 s] is 2 per second
+            vec![(1_000_000, 0), (2_000_000, 3), (2_500_000, 2)]
+        );
+    }
+}
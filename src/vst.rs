@@ -0,0 +1,682 @@
+// The .vst format saves a trace as it streams, so that it can be viewed again later without
+// re-running the program.
+//
+// A .vst file is the magic bytes "VST", a version byte (currently 3), an encoding byte (0 for JSON,
+// 1 for MessagePack; absent before version 3, which only had JSON), a sequence of
+// independently-compressed blocks, and an index of the blocks. Each block is:
+//
+//   u32    length of the compressed data
+//   u32    number of records in the block
+//   u64    timestamp of the block's earliest event, in microseconds since the Unix epoch (0 if
+//          unknown), which is not always its first: with -f, a syscall that was interrupted by
+//          another process's is recorded when it resumes, with the time that it started at
+//   ...    records, compressed with raw DEFLATE
+//
+// All integers are little-endian. Because each block has its own header, a reader can seek to any
+// block (e.g., by timestamp) without decompressing the blocks before it.
+//
+// Each record is one line of strace output:
+//
+//   u32    length of the raw line
+//   ...    the raw line, exactly as strace printed it
+//   u32    length of the parsed event (0 if the line did not complete an event, e.g. because it
+//          was the first half of an unfinished syscall)
+//   ...    the parsed event, as an object in the schema described in jsonl.rs, in the file's
+//          encoding
+//
+// vistrace itself reloads traces by re-parsing the raw lines; the parsed form is there for other
+// tools. MessagePack (`--encoding msgpack`) is for firehose traces, which it keeps up with where
+// JSON does not (see benches/encoding.rs). There is no separate string table: the paths and names
+// that repeat from line to line are shared by DEFLATE within a block, and a table for the whole
+// file would tie the blocks together, so that one lost block (e.g., the last, if vistrace was
+// killed) could take the rest with it.
+//
+// Once the trace is over, the index is written after the last block, so that a reader can find
+// every block (and the time that it starts at) without walking through a multi-gigabyte file:
+//
+//   u64    zero, which reads as an empty block header, so that a reader walking the blocks stops
+//          there
+//   u32    number of blocks
+//   ...    for each block, its offset (u64, of its header), and its header as above
+//   u64    offset of the index
+//   ...    the magic bytes "VSTI"
+//
+// A file without an index (from version 1, or because vistrace was killed) is read by walking
+// from block header to block header.
+
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use clap::ValueEnum;
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use flate2::Compression;
+
+use crate::strace::{Message, Parse};
+use crate::{jsonl, msgpack};
+
+const MAGIC: &[u8; 3] = b"VST";
+const VERSION: u8 = 3;
+const INDEX_MAGIC: &[u8; 4] = b"VSTI";
+// the offset of the index and its magic bytes
+const TRAILER_LEN: u64 = 12;
+// the size of a block header, and of an entry in the index
+const HEADER_LEN: u64 = 16;
+const ENTRY_LEN: u64 = 8 + HEADER_LEN;
+// records per block: small enough that little is lost if vistrace is killed, large enough to
+// compress well
+const BLOCK_RECORDS: u32 = 512;
+// the longest raw line or parsed event that is read back: far more than strace prints with a large
+// `-s`, or the ptrace backend with a whole buffer escaped, and small enough that a corrupt length
+// (e.g., from a broken --listen peer) does not allocate gigabytes
+const MAX_RECORD_LEN: u32 = 64 << 20;
+
+/// How the parsed form of each event is written in .vst files.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq)]
+pub enum Encoding {
+    /// JSON, as in `vistrace export --format jsonl`
+    #[default]
+    Json,
+    /// MessagePack, with the same fields as JSON: smaller, and several times faster to write
+    Msgpack,
+}
+
+impl Encoding {
+    /// Appends the parsed form of `msg` to `buf`, or nothing if it has none (e.g., a stack frame).
+    pub fn encode(self, msg: &Message, buf: &mut Vec<u8>) {
+        match self {
+            Encoding::Json => {
+                if let Some(json) = jsonl::to_json(msg) {
+                    let _ = write!(buf, "{}", json);
+                }
+            }
+            Encoding::Msgpack => msgpack::write_message(buf, msg),
+        }
+    }
+
+    pub(crate) fn to_byte(self) -> u8 {
+        match self {
+            Encoding::Json => 0,
+            Encoding::Msgpack => 1,
+        }
+    }
+
+    pub(crate) fn from_byte(byte: u8) -> Option<Encoding> {
+        match byte {
+            0 => Some(Encoding::Json),
+            1 => Some(Encoding::Msgpack),
+            _ => None,
+        }
+    }
+}
+
+pub struct Writer {
+    file: BufWriter<File>,
+    encoding: Encoding,
+    // the parsed form of the event being written, kept to reuse its allocation
+    parsed: Vec<u8>,
+    // where the next block will be written
+    offset: u64,
+    blocks: Vec<Block>,
+    block: Vec<u8>,
+    block_records: u32,
+    block_time_micros: u64,
+}
+
+impl Writer {
+    pub fn create(path: &Path, encoding: Encoding) -> Result<Writer> {
+        let file = File::create(path)
+            .map_err(|e| anyhow!("unable to create {}: {}", path.display(), e))?;
+        let mut file = BufWriter::new(file);
+        file.write_all(MAGIC)
+            .and_then(|_| file.write_all(&[VERSION, encoding.to_byte()]))
+            .map_err(|e| anyhow!("unable to write {}: {}", path.display(), e))?;
+        Ok(Writer {
+            file,
+            encoding,
+            parsed: Vec::new(),
+            offset: (MAGIC.len() + 2) as u64,
+            blocks: Vec::new(),
+            block: Vec::new(),
+            block_records: 0,
+            block_time_micros: 0,
+        })
+    }
+
+    /// Records a line of strace output, along with the message it completed, if any.
+    pub fn write(&mut self, line: &str, msg: Option<&Message>) -> io::Result<()> {
+        let time_micros = msg.map(Message::time_micros).unwrap_or(0);
+        if time_micros != 0 && (self.block_time_micros == 0 || time_micros < self.block_time_micros)
+        {
+            self.block_time_micros = time_micros;
+        }
+
+        self.parsed.clear();
+        if let Some(msg) = msg {
+            self.encoding.encode(msg, &mut self.parsed);
+        }
+        write_bytes(&mut self.block, line.as_bytes())?;
+        write_bytes(&mut self.block, &self.parsed)?;
+        self.block_records += 1;
+
+        if self.block_records >= BLOCK_RECORDS {
+            self.flush_block()?;
+        }
+        Ok(())
+    }
+
+    /// Writes out any buffered records and the index. Must be called once the trace is over, or
+    /// the last block will be lost.
+    pub fn finish(&mut self) -> io::Result<()> {
+        self.flush_block()?;
+
+        self.file.write_all(&0u64.to_le_bytes())?;
+        self.file
+            .write_all(&(self.blocks.len() as u32).to_le_bytes())?;
+        for block in &self.blocks {
+            self.file.write_all(&block.offset.to_le_bytes())?;
+            block.write_header(&mut self.file)?;
+        }
+        self.file.write_all(&self.offset.to_le_bytes())?;
+        self.file.write_all(INDEX_MAGIC)?;
+        self.file.flush()
+    }
+
+    fn flush_block(&mut self) -> io::Result<()> {
+        if self.block_records == 0 {
+            return Ok(());
+        }
+
+        // traces repeat themselves so much that the fastest level compresses them nearly as well,
+        // and the default level could not keep up with a busy trace
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::fast());
+        encoder.write_all(&self.block)?;
+        let compressed = encoder.finish()?;
+
+        let block = Block {
+            offset: self.offset,
+            compressed_len: compressed.len() as u32,
+            records: self.block_records,
+            time_micros: self.block_time_micros,
+        };
+        block.write_header(&mut self.file)?;
+        self.file.write_all(&compressed)?;
+        // so that the block survives if vistrace is killed
+        self.file.flush()?;
+        self.offset += HEADER_LEN + compressed.len() as u64;
+        self.blocks.push(block);
+
+        self.block.clear();
+        self.block_records = 0;
+        self.block_time_micros = 0;
+        Ok(())
+    }
+}
+
+pub struct Reader {
+    file: BufReader<File>,
+    blocks: Vec<Block>,
+}
+
+struct Block {
+    // of the block's header
+    offset: u64,
+    compressed_len: u32,
+    records: u32,
+    time_micros: u64,
+}
+
+impl Block {
+    fn write_header(&self, w: &mut impl Write) -> io::Result<()> {
+        w.write_all(&self.compressed_len.to_le_bytes())?;
+        w.write_all(&self.records.to_le_bytes())?;
+        w.write_all(&self.time_micros.to_le_bytes())
+    }
+
+    fn read_header(r: &mut impl Read, offset: u64) -> io::Result<Block> {
+        Ok(Block {
+            offset,
+            compressed_len: read_u32(r)?,
+            records: read_u32(r)?,
+            time_micros: read_u64(r)?,
+        })
+    }
+
+    fn end(&self) -> u64 {
+        self.offset + HEADER_LEN + self.compressed_len as u64
+    }
+}
+
+impl Reader {
+    /// Opens a .vst file and finds every block, from the index if the file has one. A truncated
+    /// final block (e.g., because vistrace was killed while writing it) is ignored.
+    pub fn open(path: &Path) -> Result<Reader> {
+        let file =
+            File::open(path).map_err(|e| anyhow!("unable to open {}: {}", path.display(), e))?;
+        let len = file
+            .metadata()
+            .map_err(|e| anyhow!("unable to read {}: {}", path.display(), e))?
+            .len();
+        let mut file = BufReader::new(file);
+        let err = |e: io::Error| anyhow!("unable to read {}: {}", path.display(), e);
+
+        let mut header = [0u8; 4];
+        if file.read_exact(&mut header).is_err() || &header[..3] != MAGIC {
+            return Err(anyhow!("{} is not a vistrace file", path.display()));
+        }
+        if header[3] == 0 || header[3] > VERSION {
+            return Err(anyhow!(
+                "{} has unsupported version {} (expected {} or earlier)",
+                path.display(),
+                header[3],
+                VERSION
+            ));
+        }
+        // the encoding is not needed since the raw lines are parsed again
+        let mut start = header.len() as u64;
+        if header[3] >= 3 {
+            let mut encoding = [0u8];
+            file.read_exact(&mut encoding).map_err(err)?;
+            if Encoding::from_byte(encoding[0]).is_none() {
+                return Err(anyhow!(
+                    "{} has unsupported encoding {}",
+                    path.display(),
+                    encoding[0]
+                ));
+            }
+            start += 1;
+        }
+
+        let blocks = match read_index(&mut file, len).map_err(err)? {
+            Some(blocks) => blocks,
+            None => {
+                tracing::info!(path = %path.display(), "trace has no index, scanning blocks");
+                scan_blocks(&mut file, start, len).map_err(err)?
+            }
+        };
+        Ok(Reader { file, blocks })
+    }
+
+    /// Returns the index of the block to start reading at to see the events from `time_micros`
+    /// on, i.e. the last block with an event at or before it, so that every block after it is
+    /// entirely from after `time_micros`. The blocks' times are not in order (see above), so they
+    /// are searched one by one.
+    pub fn find_block(&self, time_micros: u64) -> usize {
+        self.blocks
+            .iter()
+            .rposition(|block| block.time_micros <= time_micros)
+            .unwrap_or(0)
+    }
+
+    pub fn block_count(&self) -> usize {
+        self.blocks.len()
+    }
+
+    /// The time of the first event in the trace, in microseconds since the Unix epoch, if known.
+    pub fn start_micros(&self) -> Option<u64> {
+        self.blocks
+            .iter()
+            .map(|block| block.time_micros)
+            .filter(|&time| time != 0)
+            .min()
+    }
+
+    /// Returns the raw strace lines in the given block.
+    pub fn read_block(&mut self, index: usize) -> Result<Vec<String>> {
+        let block = &self.blocks[index];
+        let err = |e: io::Error| anyhow!("corrupt block {} in trace file: {}", index, e);
+        self.file
+            .seek(SeekFrom::Start(block.offset + HEADER_LEN))
+            .map_err(err)?;
+
+        let mut data = Vec::new();
+        DeflateDecoder::new((&mut self.file).take(block.compressed_len as u64))
+            .read_to_end(&mut data)
+            .map_err(err)?;
+
+        let mut data = data.as_slice();
+        let mut lines = Vec::with_capacity(block.records as usize);
+        for _ in 0..block.records {
+            let line = read_bytes(&mut data).map_err(err)?;
+            // the parsed form is not needed since the raw line is parsed again
+            read_bytes(&mut data).map_err(err)?;
+            lines.push(String::from_utf8_lossy(&line).into_owned());
+        }
+        Ok(lines)
+    }
+}
+
+// Reads the index at the end of the file, or returns `None` if there isn't one.
+fn read_index(file: &mut BufReader<File>, len: u64) -> io::Result<Option<Vec<Block>>> {
+    if len < TRAILER_LEN {
+        return Ok(None);
+    }
+    file.seek(SeekFrom::Start(len - TRAILER_LEN))?;
+    let index_offset = read_u64(file)?;
+    let mut magic = [0u8; 4];
+    file.read_exact(&mut magic)?;
+    if &magic != INDEX_MAGIC || index_offset + 12 > len - TRAILER_LEN {
+        return Ok(None);
+    }
+
+    file.seek(SeekFrom::Start(index_offset + 8))?;
+    let count = read_u32(file)? as u64;
+    if index_offset + 12 + count * ENTRY_LEN != len - TRAILER_LEN {
+        return Ok(None);
+    }
+    let mut blocks = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        let offset = read_u64(file)?;
+        let block = Block::read_header(file, offset)?;
+        if block.end() > index_offset {
+            return Ok(None);
+        }
+        blocks.push(block);
+    }
+    Ok(Some(blocks))
+}
+
+// Finds the blocks by walking from each block's header to the next.
+fn scan_blocks(file: &mut BufReader<File>, mut offset: u64, len: u64) -> io::Result<Vec<Block>> {
+    let mut blocks = Vec::new();
+    file.seek(SeekFrom::Start(offset))?;
+    while offset + HEADER_LEN <= len {
+        let block = Block::read_header(file, offset)?;
+        // an empty block is the start of the index
+        if block.compressed_len == 0 || block.end() > len {
+            break;
+        }
+        offset = block.end();
+        blocks.push(block);
+        file.seek(SeekFrom::Start(offset))?;
+    }
+    Ok(blocks)
+}
+
+/// Returns true if the file at `path` looks like a .vst file, rather than, e.g., the output of
+/// `strace -o`.
+pub fn is_vst(path: &Path) -> bool {
+    let mut magic = [0u8; 3];
+    File::open(path).is_ok_and(|mut file| file.read_exact(&mut magic).is_ok() && &magic == MAGIC)
+}
+
+/// Returns the first line of the text trace at `path` that is not blank, or `None` if it has none,
+/// or is a .vst file, so that the tracer that wrote it can be recognized.
+pub fn first_line(path: &Path) -> Option<String> {
+    if is_vst(path) {
+        return None;
+    }
+    let file = File::open(path).ok()?;
+    BufReader::new(file)
+        .lines()
+        .map_while(|line| line.ok())
+        .find(|line| !line.trim().is_empty())
+}
+
+/// How fast to replay a trace relative to the original pace, e.g. 2.0 for twice as fast. Shared
+/// with the UI so that it can be changed during the replay.
+pub type Speed = Arc<Mutex<f64>>;
+
+/// Sends every message in the saved trace at `path`, as parsed by `parser`, to `tx`. If `speed`
+/// is given, messages are sent at the pace they were originally recorded at (scaled by `speed`)
+/// rather than all at once. If `from` is given, the messages in the first `from` of the trace are
+/// skipped, without reading the blocks that they are in. A file that is not a .vst file is read as
+/// the text that a tracer wrote, e.g. with `strace -o`.
+pub fn replay(
+    path: &Path,
+    parser: impl Parse,
+    tx: mpsc::Sender<Message>,
+    speed: Option<Speed>,
+    from: Option<Duration>,
+) -> Result<()> {
+    let mut player = Player {
+        parser,
+        tx,
+        speed,
+        from,
+        from_micros: 0,
+        last_time_micros: 0,
+    };
+    if !is_vst(path) {
+        let file =
+            File::open(path).map_err(|e| anyhow!("unable to open {}: {}", path.display(), e))?;
+        for line in BufReader::new(file).lines() {
+            let line = line.map_err(|e| anyhow!("unable to read {}: {}", path.display(), e))?;
+            player.play(&line)?;
+        }
+        return Ok(());
+    }
+
+    let mut reader = Reader::open(path)?;
+    tracing::info!(path = %path.display(), blocks = reader.blocks.len(), "replaying trace");
+    let mut first_block = 0;
+    if let Some(from) = player.from.take() {
+        let start_micros = reader
+            .start_micros()
+            .ok_or_else(|| anyhow!("{} has no timestamps to start from", path.display()))?;
+        player.from_micros = start_micros + from.as_micros() as u64;
+        first_block = reader.find_block(player.from_micros);
+    }
+    for i in first_block..reader.blocks.len() {
+        for line in reader.read_block(i)? {
+            player.play(&line)?;
+        }
+    }
+    Ok(())
+}
+
+// Sends the messages of a trace, line by line, at their recorded pace if `speed` is given.
+struct Player<P> {
+    parser: P,
+    tx: mpsc::Sender<Message>,
+    speed: Option<Speed>,
+    // how far into the trace to start, if not yet worked out from its first timestamp
+    from: Option<Duration>,
+    from_micros: u64,
+    last_time_micros: u64,
+}
+
+impl<P: Parse> Player<P> {
+    fn play(&mut self, line: &str) -> Result<()> {
+        let msg = match self.parser.parse_line(line) {
+            Some(msg) => msg,
+            None => return Ok(()),
+        };
+
+        let time_micros = msg.time_micros();
+        if time_micros != 0 {
+            if let Some(from) = self.from.take() {
+                self.from_micros = time_micros + from.as_micros() as u64;
+            }
+        }
+        if time_micros != 0 && time_micros < self.from_micros {
+            return Ok(());
+        }
+        if let Some(speed) = &self.speed {
+            if self.last_time_micros != 0 && time_micros > self.last_time_micros {
+                wait(time_micros - self.last_time_micros, speed);
+            }
+        }
+        if time_micros != 0 {
+            self.last_time_micros = time_micros;
+        }
+
+        self.tx
+            .send(msg)
+            .map_err(|e| anyhow!("transmit error: {}", e))
+    }
+}
+
+// Sleeps for `micros` of trace time, in short steps so that speed changes take effect promptly.
+fn wait(mut micros: u64, speed: &Speed) {
+    const STEP: Duration = Duration::from_millis(100);
+    while micros > 0 {
+        let speed = *speed.lock().unwrap();
+        let step_micros = micros.min((STEP.as_micros() as f64 * speed) as u64).max(1);
+        thread::sleep(Duration::from_micros((step_micros as f64 / speed) as u64));
+        micros -= step_micros;
+    }
+}
+
+pub fn write_bytes(w: &mut impl Write, bytes: &[u8]) -> io::Result<()> {
+    w.write_all(&(bytes.len() as u32).to_le_bytes())?;
+    w.write_all(bytes)
+}
+
+fn read_u32(r: &mut impl Read) -> io::Result<u32> {
+    let mut buf = [0u8; 4];
+    r.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+fn read_u64(r: &mut impl Read) -> io::Result<u64> {
+    let mut buf = [0u8; 8];
+    r.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+pub fn read_bytes(r: &mut impl Read) -> io::Result<Vec<u8>> {
+    let len = read_u32(r)?;
+    if len > MAX_RECORD_LEN {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "record of {} bytes, more than the limit of {}",
+                len, MAX_RECORD_LEN
+            ),
+        ));
+    }
+    let mut buf = vec![0u8; len as usize];
+    r.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::OpenOptions;
+    use std::io::Write;
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    use crate::strace::{LineParser, Message};
+
+    use super::{replay, Encoding, Reader, Writer, BLOCK_RECORDS};
+
+    #[test]
+    fn test_round_trip() {
+        let path = std::env::temp_dir().join(format!("vistrace-test-{}.vst", std::process::id()));
+        let lines: Vec<String> = (0..BLOCK_RECORDS + 10)
+            .map(|i| format!("10 1720000000.{:06} close({}) = 0 <0.000001>\n", i + 1, i))
+            .chain(["10 1720000001.000000 read(3,  <unfinished ...>\n".to_string()])
+            .collect();
+
+        let mut writer = Writer::create(&path, Encoding::Msgpack).unwrap();
+        let mut parser = LineParser::default();
+        for line in &lines {
+            let msg = parser.parse_line(line);
+            writer.write(line, msg.as_ref()).unwrap();
+        }
+        writer.finish().unwrap();
+
+        let reader = Reader::open(&path).unwrap();
+        assert_eq!(reader.blocks.len(), 2);
+        assert_eq!(reader.start_micros(), Some(1720000000000001));
+        assert_eq!(reader.find_block(1720000000000513), 1);
+        assert_eq!(reader.find_block(1720000000000512), 0);
+        assert_eq!(reader.find_block(0), 0);
+
+        // without the index (here, because a partially-written block follows it), the blocks are
+        // found by walking through them
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(&[100, 0, 0, 0, 1, 0]).unwrap();
+
+        let mut reader = Reader::open(&path).unwrap();
+        assert_eq!(reader.blocks.len(), 2);
+        assert_eq!(reader.blocks[0].records, BLOCK_RECORDS);
+        assert_eq!(
+            reader.read_block(1).unwrap(),
+            lines[BLOCK_RECORDS as usize..]
+        );
+
+        let (tx, rx) = mpsc::channel();
+        replay(&path, LineParser::default(), tx, None, None).unwrap();
+        let syscalls: Vec<_> = rx.iter().filter_map(Message::into_syscall).collect();
+        assert_eq!(syscalls.len(), BLOCK_RECORDS as usize + 10);
+        assert_eq!(syscalls[3].to_string(), lines[3].trim_end());
+
+        let (tx, rx) = mpsc::channel();
+        let from = Duration::from_micros(BLOCK_RECORDS as u64 + 3);
+        replay(&path, LineParser::default(), tx, None, Some(from)).unwrap();
+        let syscalls: Vec<_> = rx.iter().filter_map(Message::into_syscall).collect();
+        assert_eq!(syscalls.len(), 7);
+        assert_eq!(syscalls[0].to_string(), lines[515].trim_end());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_find_block_interleaved() {
+        let path = std::env::temp_dir().join(format!("vistrace-test-{}-f.vst", std::process::id()));
+        // with -f, a read that started before the rest of its block is only recorded when it
+        // resumes, at the end of the block
+        let mut lines: Vec<String> =
+            vec!["10 1720000000.000010 read(3,  <unfinished ...>\n".into()];
+        lines.extend(
+            (1..BLOCK_RECORDS - 1)
+                .map(|i| format!("11 1720000000.{:06} close({}) = 0 <0.000001>\n", 100 + i, i)),
+        );
+        lines.push("10 1720000000.000900 <... read resumed>\"a\", 1) = 1 <0.000890>\n".into());
+        lines.push("11 1720000000.001000 close(3) = 0 <0.000001>\n".into());
+
+        let mut writer = Writer::create(&path, Encoding::Json).unwrap();
+        let mut parser = LineParser::default();
+        for line in &lines {
+            let msg = parser.parse_line(line);
+            writer.write(line, msg.as_ref()).unwrap();
+        }
+        writer.finish().unwrap();
+
+        let reader = Reader::open(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(reader.blocks.len(), 2);
+        // the first block is as early as the read, whose line came last
+        assert_eq!(reader.start_micros(), Some(1720000000000010));
+        assert_eq!(reader.find_block(1720000000000050), 0);
+        assert_eq!(reader.find_block(1720000000001000), 1);
+        assert_eq!(reader.find_block(1720000000000000), 0);
+    }
+
+    #[test]
+    fn test_open_not_vst() {
+        let path = std::env::temp_dir().join(format!("vistrace-test-{}.txt", std::process::id()));
+        std::fs::write(&path, "close(3) = 0\n").unwrap();
+        let result = Reader::open(&path);
+        std::fs::write(&path, "").unwrap();
+        let empty = Reader::open(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert!(result.is_err_and(|e| e.to_string().ends_with("is not a vistrace file")));
+        assert!(empty.is_err());
+    }
+
+    #[test]
+    fn test_empty_trace() {
+        let path = std::env::temp_dir().join(format!("vistrace-test-{}-e.vst", std::process::id()));
+        Writer::create(&path, Encoding::Json)
+            .unwrap()
+            .finish()
+            .unwrap();
+        let reader = Reader::open(&path).unwrap();
+        let (tx, rx) = mpsc::channel();
+        replay(&path, LineParser::default(), tx, None, None).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(reader.block_count(), 0);
+        assert_eq!(reader.start_micros(), None);
+        assert_eq!(reader.find_block(1720000000000000), 0);
+        assert_eq!(rx.iter().count(), 0);
+    }
+}
@@ -0,0 +1,219 @@
+// Watch expressions are pinned in a side pane and updated live as syscalls arrive.
+//
+// Syntax:
+//
+//   count <syscall> [<errno>]            e.g., count openat ENOENT
+//   last <syscall>[(<arg>=<value>, ...)] e.g., last read(fd=7)
+//
+// `<syscall>` may be `*` to match any syscall. Arguments may be given by name (if the syscall is
+// known to `syscalls::arg_names`) or by position (e.g., `read(0=7)`).
+
+use anyhow::{anyhow, Result};
+
+use crate::strace::Syscall;
+use crate::syscalls;
+
+pub struct Watch {
+    pub text: String,
+    kind: WatchKind,
+    matcher: Matcher,
+}
+
+enum WatchKind {
+    Count(u64),
+    Last(Option<i64>),
+}
+
+struct Matcher {
+    syscall: Option<String>,
+    errno: Option<String>,
+    args: Vec<(ArgRef, String)>,
+}
+
+enum ArgRef {
+    Index(usize),
+    Name(String),
+}
+
+impl Watch {
+    pub fn parse(text: &str) -> Result<Watch> {
+        let text = text.trim();
+        let (op, rest) = text.split_once(char::is_whitespace).ok_or(anyhow!(
+            "expected a watch like 'count openat' or 'last read(fd=3)'"
+        ))?;
+        let rest = rest.trim();
+
+        let (kind, matcher) = match op {
+            "count" => {
+                let mut words = rest.split_whitespace();
+                let syscall = words.next().ok_or(anyhow!("expected syscall name"))?;
+                let errno = words.next().map(|s| s.to_string());
+                if words.next().is_some() {
+                    return Err(anyhow!("too many words in count watch"));
+                }
+                (
+                    WatchKind::Count(0),
+                    Matcher {
+                        syscall: parse_syscall_name(syscall),
+                        errno,
+                        args: Vec::new(),
+                    },
+                )
+            }
+            "last" => {
+                let (syscall, args) = match rest.split_once('(') {
+                    Some((syscall, args)) => {
+                        let args = args
+                            .strip_suffix(')')
+                            .ok_or(anyhow!("expected ')' at end of watch"))?;
+                        (syscall.trim(), parse_arg_constraints(args)?)
+                    }
+                    None => (rest, Vec::new()),
+                };
+                (
+                    WatchKind::Last(None),
+                    Matcher {
+                        syscall: parse_syscall_name(syscall),
+                        errno: None,
+                        args,
+                    },
+                )
+            }
+            _ => return Err(anyhow!("unknown watch type: {:?}", op)),
+        };
+
+        Ok(Watch {
+            text: text.to_string(),
+            kind,
+            matcher,
+        })
+    }
+
+    pub fn update(&mut self, syscall: &Syscall) {
+        if !self.matcher.matches(syscall) {
+            return;
+        }
+
+        match &mut self.kind {
+            WatchKind::Count(n) => *n += 1,
+            WatchKind::Last(v) => *v = Some(syscall.return_value),
+        }
+    }
+
+    pub fn value(&self) -> String {
+        match &self.kind {
+            WatchKind::Count(n) => n.to_string(),
+            WatchKind::Last(Some(v)) => v.to_string(),
+            WatchKind::Last(None) => "-".to_string(),
+        }
+    }
+}
+
+impl Matcher {
+    fn matches(&self, syscall: &Syscall) -> bool {
+        if let Some(name) = &self.syscall {
+            if *name != syscall.name {
+                return false;
+            }
+        }
+
+        if let Some(errno) = &self.errno {
+            if syscall.errno.as_ref() != Some(errno) {
+                return false;
+            }
+        }
+
+        for (arg, expected) in &self.args {
+            let index = match arg {
+                ArgRef::Index(i) => Some(*i),
+                ArgRef::Name(name) => syscalls::arg_index(&syscall.name, name),
+            };
+            let actual = index
+                .and_then(|i| syscall.arg(i))
+                .map(|a| a.value.to_string());
+            if actual.as_ref() != Some(expected) {
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+fn parse_syscall_name(name: &str) -> Option<String> {
+    if name == "*" {
+        None
+    } else {
+        Some(name.to_string())
+    }
+}
+
+fn parse_arg_constraints(text: &str) -> Result<Vec<(ArgRef, String)>> {
+    let mut r = Vec::new();
+    for constraint in text.split(',') {
+        let constraint = constraint.trim();
+        if constraint.is_empty() {
+            continue;
+        }
+
+        let (arg, value) = constraint
+            .split_once('=')
+            .ok_or(anyhow!("expected argument constraint like 'fd=3'"))?;
+        let arg = arg.trim();
+        let arg = match arg.parse::<usize>() {
+            Ok(i) => ArgRef::Index(i),
+            Err(_) => ArgRef::Name(arg.to_string()),
+        };
+        r.push((arg, value.trim().to_string()));
+    }
+    Ok(r)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::strace::parse_syscall;
+
+    use super::Watch;
+
+    #[test]
+    fn test_count_watch() {
+        let mut w = Watch::parse("count openat ENOENT").unwrap();
+        assert_eq!(w.value(), "0");
+        w.update(&parse_syscall(
+            "openat(AT_FDCWD, \"/a\", O_RDONLY) = 3",
+            false,
+        ));
+        w.update(&parse_syscall(
+            "openat(AT_FDCWD, \"/b\", O_RDONLY) = -1 ENOENT (No such file or directory)",
+            false,
+        ));
+        w.update(&parse_syscall("close(3) = 0", false));
+        assert_eq!(w.value(), "1");
+
+        let mut w = Watch::parse("count *").unwrap();
+        w.update(&parse_syscall("close(3) = 0", false));
+        w.update(&parse_syscall("close(4) = 0", false));
+        assert_eq!(w.value(), "2");
+    }
+
+    #[test]
+    fn test_last_watch() {
+        let mut w = Watch::parse("last read(fd=7)").unwrap();
+        assert_eq!(w.value(), "-");
+        w.update(&parse_syscall("read(7, \"abc\", 4096) = 3", false));
+        w.update(&parse_syscall("read(3, \"abcdef\", 4096) = 6", false));
+        assert_eq!(w.value(), "3");
+
+        let mut w = Watch::parse("last read(0=3)").unwrap();
+        w.update(&parse_syscall("read(3, \"abcdef\", 4096) = 6", false));
+        assert_eq!(w.value(), "6");
+    }
+
+    #[test]
+    fn test_watch_parse_errors() {
+        assert!(Watch::parse("count").is_err());
+        assert!(Watch::parse("sum read").is_err());
+        assert!(Watch::parse("last read(fd=7").is_err());
+        assert!(Watch::parse("last read(fd)").is_err());
+    }
+}
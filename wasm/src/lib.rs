@@ -0,0 +1,150 @@
+// Bindings of vistrace's parser and analyzers for the browser, so that a viewer can parse traces
+// that are uploaded to it exactly as vistrace does:
+//
+//   import init, { parse, Analyzer } from "./pkg/vistrace_wasm.js";
+//
+//   await init();
+//   const events = parse(text).split("\n").filter(Boolean).map(JSON.parse);
+//
+//   const analyzer = new Analyzer();
+//   for (const line of text.split("\n")) {
+//     const event = analyzer.feed(line + "\n");  // a JSON string, or undefined
+//   }
+//   JSON.parse(analyzer.fds());            // every file descriptor that was used
+//   JSON.parse(analyzer.syscall_stats());  // calls, errors, and time of each syscall
+//
+// Events are in the schema of `vistrace export --format jsonl` (see src/jsonl.rs in the vistrace
+// crate), and are passed as JSON, which the browser parses faster than it could build the objects
+// one field at a time through the bindings.
+
+use wasm_bindgen::prelude::*;
+
+use vistrace::fdtable::{FdKind, FdTable};
+use vistrace::json::Json;
+use vistrace::strace::{self, LineParser, Message};
+use vistrace::{jsonl, summary};
+
+/// The events in strace's output, as JSON Lines.
+#[wasm_bindgen]
+pub fn parse(text: &str) -> Result<String, JsError> {
+    let mut events = Vec::new();
+    for msg in strace::parse_reader(text.as_bytes()) {
+        let msg = msg.map_err(|e| JsError::new(&e.to_string()))?;
+        jsonl::write_message(&mut events, &msg).map_err(|e| JsError::new(&e.to_string()))?;
+    }
+    Ok(String::from_utf8_lossy(&events).into_owned())
+}
+
+/// Parses strace's output a line at a time, keeping track of file descriptors and of statistics
+/// along the way.
+#[wasm_bindgen]
+#[derive(Default)]
+pub struct Analyzer {
+    parser: LineParser,
+    fds: FdTable,
+    summary: summary::Summary,
+}
+
+#[wasm_bindgen]
+impl Analyzer {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Analyzer {
+        Analyzer::default()
+    }
+
+    /// The event that the line completes, as JSON, if any.
+    pub fn feed(&mut self, line: &str) -> Option<String> {
+        let msg = self.parser.parse_line(line)?;
+        let event = jsonl::to_json(&msg).map(|json| json.to_string());
+        if let Message::Syscall(syscall) = &msg {
+            self.fds.update(syscall);
+        }
+        self.summary.update(msg);
+        event
+    }
+
+    /// Every use of every file descriptor, oldest first for each, as a JSON array of objects with
+    /// the same fields as the Python bindings give.
+    pub fn fds(&self) -> String {
+        let micros = |t: u64| {
+            if t == 0 {
+                Json::Null
+            } else {
+                Json::Number(t as i64)
+            }
+        };
+        let fds = self.fds.all().into_iter().map(|(pid, fd, info)| {
+            let (kind, path) = match &info.kind {
+                FdKind::File(path) => ("file", Json::string(path)),
+                FdKind::Socket { .. } => ("socket", Json::Null),
+                FdKind::Pipe { .. } => ("pipe", Json::Null),
+                FdKind::Other(_) => ("other", Json::Null),
+                FdKind::Unknown => ("unknown", Json::Null),
+            };
+            Json::object([
+                ("pid", pid.into()),
+                ("fd", Json::Number(fd)),
+                ("kind", Json::string(kind)),
+                ("path", path),
+                ("description", Json::String(info.kind.to_string())),
+                ("opened_us", micros(info.opened_micros)),
+                ("closed_us", info.closed_micros.map_or(Json::Null, micros)),
+                ("cloexec", Json::Bool(info.cloexec)),
+                ("bytes_read", Json::Number(info.bytes.read as i64)),
+                ("bytes_written", Json::Number(info.bytes.written as i64)),
+            ])
+        });
+        Json::Array(fds.collect()).to_string()
+    }
+
+    /// The calls, errors, and total time (in microseconds) of each syscall, those that took the
+    /// most time first, as a JSON array of objects.
+    pub fn syscall_stats(&self) -> String {
+        let stats = self.summary.syscall_rows().into_iter().map(|(name, s)| {
+            Json::object([
+                ("name", Json::string(name)),
+                ("calls", Json::Number(s.calls as i64)),
+                ("errors", Json::Number(s.errors as i64)),
+                ("total_us", Json::Number(s.total_micros as i64)),
+            ])
+        });
+        Json::Array(stats.collect()).to_string()
+    }
+
+    /// The report of `vistrace --summary`, as text.
+    pub fn summary(&self) -> String {
+        let mut report = Vec::new();
+        let _ = self.summary.write(&mut report, false);
+        String::from_utf8_lossy(&report).into_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse, Analyzer};
+
+    const TRACE: &str = "10 openat(AT_FDCWD, \"/etc/hosts\", O_RDONLY) = 3\n\
+                         10 read(3, \"127.0.0.1 localhost\\n\", 4096) = 20\n\
+                         10 close(3) = 0\n\
+                         10 +++ exited with 0 +++\n";
+
+    #[test]
+    fn test_bindings() {
+        let events = parse(TRACE).unwrap();
+        assert_eq!(events.lines().count(), 4);
+        assert!(events.starts_with(r#"{"schema_version":1,"type":"syscall","pid":10,"#));
+
+        let mut analyzer = Analyzer::new();
+        let fed: Vec<_> = TRACE
+            .split_inclusive('\n')
+            .filter_map(|line| analyzer.feed(line))
+            .collect();
+        assert_eq!(fed, events.lines().collect::<Vec<_>>());
+        assert!(analyzer.fds().contains(r#""path":"/etc/hosts""#));
+        assert!(analyzer.fds().contains(r#""bytes_read":20"#));
+        assert!(analyzer
+            .syscall_stats()
+            .contains(r#"{"name":"openat","calls":1,"errors":0,"total_us":0}"#));
+        assert!(analyzer.summary().contains("close"));
+    }
+}